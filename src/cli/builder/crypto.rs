@@ -24,7 +24,11 @@ pub enum Bicrypter {
     Aes256Gcm(crypto::Aes256GcmBicrypter),
     Aes128GcmSiv(crypto::Aes128GcmSivBicrypter),
     Aes256GcmSiv(crypto::Aes256GcmSivBicrypter),
+    // NOTE: The SIV bicrypters are not Clone, so clients and servers cannot
+    //       be built from them yet and are rejected as unsupported
+    #[allow(dead_code)]
     Aes128Siv(crypto::Aes128SivBicrypter),
+    #[allow(dead_code)]
     Aes256Siv(crypto::Aes256SivBicrypter),
 }
 
@@ -1,13 +1,22 @@
 mod auth;
 mod crypto;
 
-use crate::cli::opts::{client::ClientCommand, server::ServerCommand, types};
-use log::debug;
+use crate::cli::opts::{
+    client::ClientCommand, server::ServerCommand, types, CommonOpts,
+};
+use log::{debug, warn};
 use crate::core::{
-    ClientBuilder, ConnectedClient, ListeningServer, ServerBuilder, Transport,
+    reply::DiagnosticConfigArgs, AskOptions, ClientBuilder, ConfigStore,
+    ConnectedClient, ListeningServer, Permissions, PowerControl, Preset,
+    Relay, RetryPolicy, ServerBuilder, SignatureMode, SignaturePolicy,
+    Transport, TrustedClients, Webhook, DEFAULT_COMPRESSION_THRESHOLD,
+};
+use crate::core::transport::{
+    auth::identity::{self, IdentityKey},
+    Authenticator, Bicrypter, SocketOptions,
 };
-use crate::core::transport::{Authenticator, Bicrypter};
 use std::io;
+use std::net::SocketAddr;
 use tokio::net;
 
 pub async fn start_client(cmd: &ClientCommand) -> io::Result<ConnectedClient> {
@@ -86,20 +95,20 @@ where
     A: Authenticator + Send + Sync + Clone + Default + 'static,
     B: Bicrypter + Send + Sync + Clone + Default + 'static,
 {
-    // Attempt to resolve provided address, filtering out IPv4 if looking for
-    // IPv6 and vice versa, selecting very first match in resolution
-    let maybe_resolved_addr = net::lookup_host(cmd.addr.clone())
-        .await?
-        .find(|x| x.is_ipv6() == cmd.ipv6);
+    let maybe_resolved_addr = resolve_addr(&cmd.addr, cmd.ipv6).await?;
 
-    debug!(
-        "Resolved {} to {}",
-        cmd.addr,
-        maybe_resolved_addr
-            .as_ref()
-            .map(|x| x.to_string())
-            .unwrap_or_default()
-    );
+    // Fallback servers that cannot be resolved are skipped rather than
+    // preventing the connection to the server
+    let mut fallback_servers = Vec::new();
+    for addr in cmd.fallback_servers.iter() {
+        match resolve_addr(addr, cmd.ipv6).await {
+            Ok(Some(addr)) => fallback_servers.push(addr),
+            Ok(None) => warn!("Fallback server {} did not resolve", addr),
+            Err(x) => {
+                warn!("Failed to resolve fallback server {}: {}", addr, x)
+            }
+        }
+    }
 
     let addrs = maybe_resolved_addr.map(|x| vec![x]).unwrap_or_default();
     let transport = match cmd.opts.transport {
@@ -107,12 +116,45 @@ where
         types::Transport::Udp => Transport::Udp(addrs),
     };
 
-    ClientBuilder::default()
+    let signing_key = match cmd.signing_key.as_ref() {
+        Some(key) => Some(IdentityKey::from_secret(&decode_hex_key(key)?)?),
+        None => None,
+    };
+
+    let mut config = ClientBuilder::default();
+    config
         .authenticator(authenticator)
         .bicrypter(bicrypter)
         .transport(transport)
         .buffer(cmd.opts.internal_buffer_size)
+        .socket_options(socket_options(&cmd.opts))
+        .timeout(cmd.opts.timeout)
         .packet_ttl(cmd.opts.packet_ttl)
+        .adaptive_packet_ttl(!cmd.opts.fixed_packet_ttl)
+        .max_packet_groups(cmd.opts.max_packet_groups)
+        .max_packets_per_msg(cmd.opts.max_packets_per_msg)
+        .packet_gc_interval(cmd.opts.packet_gc_interval)
+        .max_outstanding_asks(cmd.max_outstanding_asks)
+        .pinned_server_keys(
+            cmd.pinned_server_keys
+                .iter()
+                .map(|key| decode_hex_key(key))
+                .collect::<io::Result<Vec<Vec<u8>>>>()?,
+        )
+        .bind_origin(cmd.bind_origin)
+        .handshake(cmd.handshake)
+        .fallback_servers(fallback_servers);
+
+    // A preset overrides the individual settings that it bundles
+    if let Some(preset) = cmd.preset {
+        config.preset(match preset {
+            types::Preset::Lan => Preset::Lan,
+            types::Preset::Wan => Preset::Wan,
+            types::Preset::Lossy => Preset::Lossy,
+        });
+    }
+
+    config
         .build()
         .map_err(|x| {
             io::Error::new(
@@ -122,9 +164,58 @@ where
         })?
         .connect()
         .await
+        .map(|mut client| {
+            client.trace_id = cmd.trace_id.clone();
+            client.parent_span_id = cmd.parent_span_id.clone();
+            client.signing_key = signing_key;
+            client.compression_threshold = if cmd.compress {
+                Some(DEFAULT_COMPRESSION_THRESHOLD)
+            } else {
+                None
+            };
+            client.ask_options = AskOptions {
+                no_compression: cmd.no_compression,
+                require_signature: cmd.require_signature,
+            };
+            client.slow_ask_threshold = cmd.slow_ask_threshold;
+            client
+        })
+}
+
+/// Socket-level options shared by the client and server
+fn socket_options(opts: &CommonOpts) -> SocketOptions {
+    SocketOptions {
+        tcp_keepalive: opts.tcp_keepalive,
+        tcp_nodelay: opts.tcp_nodelay,
+        recv_buffer_size: opts.recv_buffer_size,
+        send_buffer_size: opts.send_buffer_size,
+    }
+}
+
+/// Resolves `addr`, filtering out IPv4 if looking for IPv6 and vice versa,
+/// selecting the very first match in the resolution
+async fn resolve_addr(
+    addr: &str,
+    ipv6: bool,
+) -> io::Result<Option<SocketAddr>> {
+    let maybe_resolved_addr =
+        net::lookup_host(addr).await?.find(|x| x.is_ipv6() == ipv6);
+
+    debug!(
+        "Resolved {} to {}",
+        addr,
+        maybe_resolved_addr
+            .as_ref()
+            .map(|x| x.to_string())
+            .unwrap_or_default()
+    );
+
+    Ok(maybe_resolved_addr)
 }
 
 pub async fn start_server(cmd: &ServerCommand) -> io::Result<ListeningServer> {
+    check_limits(cmd)?;
+
     match (
         auth::Authenticator::new(
             cmd.opts.authentication,
@@ -211,6 +302,26 @@ where
 
     let mut config = ServerBuilder::default();
 
+    // Forwarded requests are sent with the same configuration that clients
+    // of this server use, only pointed at the server addressed
+    if cmd.forwarding {
+        config.relay(Relay::from_client(
+            ClientBuilder::default()
+                .authenticator(authenticator.clone())
+                .bicrypter(bicrypter.clone())
+                .transport(transport.clone())
+                .buffer(cmd.opts.internal_buffer_size)
+                .socket_options(socket_options(&cmd.opts))
+                .build()
+                .map_err(|x| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid relay config: {}", x),
+                    )
+                })?,
+        ));
+    }
+
     config
         .authenticator(authenticator)
         .bicrypter(bicrypter)
@@ -219,8 +330,88 @@ where
         .file_ttl(cmd.untouched_file_ttl)
         .proc_ttl(cmd.untouched_proc_ttl)
         .dead_proc_ttl(cmd.dead_proc_ttl)
+        .max_request_depth(cmd.max_request_depth)
+        .max_nested_operations(cmd.max_nested_operations)
+        .max_log_file_size(cmd.max_log_file_size)
+        .max_log_files(cmd.max_log_files)
         .buffer(cmd.opts.internal_buffer_size)
-        .packet_ttl(cmd.opts.packet_ttl);
+        .socket_options(socket_options(&cmd.opts))
+        .packet_ttl(cmd.opts.packet_ttl)
+        .adaptive_packet_ttl(!cmd.opts.fixed_packet_ttl)
+        .max_packet_groups(cmd.opts.max_packet_groups)
+        .max_packets_per_msg(cmd.opts.max_packets_per_msg)
+        .packet_gc_interval(cmd.opts.packet_gc_interval);
+
+    if let Some(mode) = cmd.default_file_mode {
+        config.default_file_mode(mode);
+    }
+
+    if let Some(mode) = cmd.default_dir_mode {
+        config.default_dir_mode(mode);
+    }
+
+    if cmd.fs_retry_attempts > 1 {
+        config.fs_retry_policy(RetryPolicy::new(
+            cmd.fs_retry_attempts,
+            cmd.fs_retry_backoff,
+        ));
+    }
+
+    if let Some(path) = cmd.jobs_dir.as_ref() {
+        config.jobs_dir(path.clone());
+    }
+
+    if let Some(path) = cmd.logs_dir.as_ref() {
+        config.logs_dir(path.clone());
+    }
+
+    config.webhooks(webhooks(cmd));
+
+    if let Some(key) = identity_key(cmd)? {
+        debug!(
+            "Server identity public key: {}",
+            hex::encode(key.public_key())
+        );
+        config.identity_key(key);
+    }
+
+    config.signature_policy(signature_policy(cmd)?);
+    config.strict_origin_binding(cmd.strict_origin_binding);
+    config.socket_activation(cmd.socket_activation);
+    if let Some(name) = cmd.activation_socket_name.as_deref() {
+        config.activation_socket_name(name);
+    }
+    if let Some(user) = cmd.run_as_user.as_deref() {
+        config.run_as(user, cmd.run_as_group.as_deref());
+    }
+    if let Some(trusted_clients) = trusted_clients(cmd)? {
+        config.trusted_clients(trusted_clients);
+    }
+    if let Some(power_control) = power_control(cmd)? {
+        config.power_control(power_control);
+    }
+    config.permissions(permissions(cmd));
+    if let Some(ratio) = cmd.max_amplification {
+        config.max_amplification(ratio);
+    }
+    config.min_unbound_request_size(cmd.min_unbound_request_size);
+
+    if let Some(quota) = cmd.transfer_quota {
+        config.transfer_quota(quota);
+    }
+    config.fs_event_history(cmd.fs_event_history);
+
+    if let Some(config_store) = config_store(cmd)? {
+        config.config_store(config_store);
+    }
+
+    #[cfg(feature = "script")]
+    if let Some(path) = cmd.script.as_ref() {
+        config.script(crate::core::ScriptHooks::open(path)?);
+    }
+
+    #[cfg(feature = "seccomp")]
+    config.syscall_filter(cmd.syscall_filter);
 
     // Change our process's current working directory if specified
     if let Some(path) = cmd.working_dir.as_ref() {
@@ -239,3 +430,180 @@ where
         .cloneable_listen()
         .await
 }
+
+/// Validates the configuration of the server without binding to its
+/// address, yielding the configuration the server would run with once
+/// defaults are applied
+pub fn check_server_config(
+    cmd: &ServerCommand,
+) -> io::Result<DiagnosticConfigArgs> {
+    check_limits(cmd)?;
+
+    auth::Authenticator::new(
+        cmd.opts.authentication,
+        cmd.opts.authentication_key.clone(),
+    )?;
+    crypto::Bicrypter::new(
+        cmd.opts.encryption,
+        cmd.opts.encryption_key.clone(),
+    )?;
+
+    let webhooks = webhooks(cmd);
+    for hook in webhooks.iter() {
+        hook.validate()?;
+    }
+
+    identity_key(cmd)?;
+    let signature_policy = signature_policy(cmd)?;
+    config_store(cmd)?;
+    trusted_clients(cmd)?;
+    power_control(cmd)?;
+
+    #[cfg(feature = "script")]
+    if let Some(path) = cmd.script.as_ref() {
+        crate::core::ScriptHooks::open(path)?;
+    }
+
+    if let Some(path) = cmd.working_dir.as_ref() {
+        if !std::fs::metadata(path)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Working dir {:?} is not a directory", path),
+            ));
+        }
+    }
+
+    Ok(DiagnosticConfigArgs {
+        file_ttl_millis: cmd.untouched_file_ttl.as_millis() as u64,
+        proc_ttl_millis: cmd.untouched_proc_ttl.as_millis() as u64,
+        dead_proc_ttl_millis: cmd.dead_proc_ttl.as_millis() as u64,
+        webhooks: webhooks.len(),
+        max_request_depth: cmd.max_request_depth,
+        max_nested_operations: cmd.max_nested_operations,
+        transfer_quota: cmd.transfer_quota,
+        fs_event_history: cmd.fs_event_history,
+        signature_mode: signature_policy.mode.name().to_string(),
+        trusted_signers: signature_policy
+            .trusted_keys
+            .iter()
+            .map(|key| identity::fingerprint(key))
+            .collect(),
+    })
+}
+
+/// Rejects limits that would leave the server unable to run or to serve
+/// any request
+fn check_limits(cmd: &ServerCommand) -> io::Result<()> {
+    let invalid = |msg: &str| {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, msg.to_string()))
+    };
+
+    if cmd.cleanup_interval.as_nanos() == 0 {
+        return invalid("Cleanup interval must be greater than zero");
+    }
+
+    if cmd.max_request_depth == 0 {
+        return invalid("Max request depth must be at least 1");
+    }
+
+    if cmd.max_nested_operations == 0 {
+        return invalid("Max nested operations must be at least 1");
+    }
+
+    Ok(())
+}
+
+fn permissions(cmd: &ServerCommand) -> Permissions {
+    Permissions {
+        allow_exec: !cmd.deny_exec,
+        allow_admin: !cmd.deny_admin,
+        allow_fs_read: !cmd.deny_fs_read,
+        allow_fs_write: !cmd.deny_fs_write,
+        allowed_paths: cmd.allowed_paths.clone(),
+    }
+}
+
+fn webhooks(cmd: &ServerCommand) -> Vec<Webhook> {
+    cmd.webhooks
+        .iter()
+        .map(|url| {
+            let mut hook = Webhook::new(url.as_str());
+            hook.secret =
+                cmd.webhook_secret.as_ref().map(|s| s.as_bytes().to_vec());
+            hook
+        })
+        .collect()
+}
+
+fn identity_key(cmd: &ServerCommand) -> io::Result<Option<IdentityKey>> {
+    match cmd.identity_key.as_ref() {
+        Some(key) => Ok(Some(IdentityKey::from_secret(&decode_hex_key(key)?)?)),
+        None => Ok(None),
+    }
+}
+
+fn signature_policy(cmd: &ServerCommand) -> io::Result<SignaturePolicy> {
+    let signature_mode = match cmd.msg_signatures {
+        types::SignatureMode::Ignore => SignatureMode::Ignore,
+        types::SignatureMode::Verify => SignatureMode::Verify,
+        types::SignatureMode::Require => SignatureMode::Require,
+    };
+
+    Ok(SignaturePolicy::new(
+        signature_mode,
+        cmd.trusted_signers
+            .iter()
+            .map(|key| decode_hex_key(key))
+            .collect::<io::Result<Vec<Vec<u8>>>>()?,
+    ))
+}
+
+/// Opens the store of pushed configs if one is persisted, loading any
+/// config persisted by an earlier run
+fn config_store(cmd: &ServerCommand) -> io::Result<Option<ConfigStore>> {
+    match cmd.config_path.as_ref() {
+        Some(path) => {
+            let key = cmd.config_key.as_ref().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Config key is required to persist configs",
+                )
+            })?;
+            Ok(Some(ConfigStore::open(path, key.as_bytes())?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Loads the list of trusted clients if one is persisted or any client is
+/// trusted on the command line
+fn trusted_clients(cmd: &ServerCommand) -> io::Result<Option<TrustedClients>> {
+    let fingerprints = cmd.trusted_clients.clone();
+    match cmd.trusted_clients_path.as_ref() {
+        Some(path) => Ok(Some(TrustedClients::open(path, fingerprints)?)),
+        None if !fingerprints.is_empty() => {
+            Ok(Some(TrustedClients::in_memory(fingerprints)?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Creates the gate on power actions if any admin is given on the command
+/// line
+fn power_control(cmd: &ServerCommand) -> io::Result<Option<PowerControl>> {
+    if cmd.power_admins.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(PowerControl::new(cmd.power_admins.clone())?))
+    }
+}
+
+/// Decodes a hex-encoded identity key provided on the command line
+fn decode_hex_key(key: &str) -> io::Result<Vec<u8>> {
+    hex::decode(key).map_err(|x| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Identity key is not valid hex: {}", x),
+        )
+    })
+}
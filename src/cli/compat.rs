@@ -0,0 +1,391 @@
+use crate::cli::diagnostic::TaggedSchema;
+use crate::core::{Reply, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Maximum depth of references followed when comparing two schemas, which
+/// stops schemas of recursive types from being expanded forever
+const MAX_REF_DEPTH: usize = 32;
+
+/// Schemas of requests and replies exported by a version of over-there, which
+/// the schemas of later versions can be compared against
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExportedSchemas {
+    pub version: String,
+    pub request: Value,
+    pub reply: Value,
+}
+
+impl ExportedSchemas {
+    /// Schemas of the requests and replies of this version
+    pub fn current() -> Self {
+        Self {
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            request: schema_value::<Request>(),
+            reply: schema_value::<Reply>(),
+        }
+    }
+
+    /// Compares `old` against these schemas, yielding what changed since
+    pub fn diff(&self, old: &Self) -> SchemaDiff {
+        SchemaDiff {
+            old_version: old.version.clone(),
+            new_version: self.version.clone(),
+            request: diff_variants(&old.request, &self.request),
+            reply: diff_variants(&old.reply, &self.reply),
+        }
+    }
+}
+
+fn schema_value<T: schemars::JsonSchema>() -> Value {
+    serde_json::to_value(schemars::schema_for!(T))
+        .expect("Failed to serialize schema")
+}
+
+/// Changes to the schemas of requests and replies between two versions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaDiff {
+    pub old_version: String,
+    pub new_version: String,
+    pub request: Vec<VariantChange>,
+    pub reply: Vec<VariantChange>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.request.is_empty() && self.reply.is_empty()
+    }
+
+    /// Number of changes that can stop the two versions from understanding
+    /// one another
+    pub fn breaking(&self) -> usize {
+        self.request
+            .iter()
+            .chain(self.reply.iter())
+            .map(VariantChange::breaking)
+            .sum()
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Comparing {} against {}",
+            self.old_version, self.new_version
+        )?;
+
+        for (name, changes) in
+            &[("Request", &self.request), ("Reply", &self.reply)]
+        {
+            if changes.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "{}:", name)?;
+            for change in changes.iter() {
+                write!(f, "{}", change)?;
+            }
+        }
+
+        if self.is_empty() {
+            write!(f, "No changes")
+        } else {
+            write!(f, "{} breaking change(s)", self.breaking())
+        }
+    }
+}
+
+/// Change to a type of request or reply
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariantChange {
+    Added(String),
+    Removed(String),
+    Changed {
+        name: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+impl VariantChange {
+    fn breaking(&self) -> usize {
+        match self {
+            Self::Added(_) => 0,
+            Self::Removed(_) => 1,
+            Self::Changed { fields, .. } => {
+                fields.iter().filter(|x| x.is_breaking()).count()
+            }
+        }
+    }
+}
+
+impl fmt::Display for VariantChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Added(name) => writeln!(f, "  + {}", name),
+            Self::Removed(name) => writeln!(f, "  - {}", name),
+            Self::Changed { name, fields } => {
+                writeln!(f, "  ~ {}", name)?;
+                for field in fields {
+                    writeln!(f, "      {}", field)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Change to a field of the payload of a type of request or reply
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldChange {
+    Added { name: String, required: bool },
+    Removed(String),
+    Changed(String),
+}
+
+impl FieldChange {
+    /// Whether the change stops content of one version from being decoded
+    /// by the other, which is the case for all but optional new fields
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            Self::Added { required, .. } => *required,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Added {
+                name,
+                required: true,
+            } => write!(f, "+ {} (required)", name),
+            Self::Added { name, .. } => write!(f, "+ {}", name),
+            Self::Removed(name) => write!(f, "- {}", name),
+            Self::Changed(name) => write!(f, "~ {}", name),
+        }
+    }
+}
+
+/// Compares the variants of the tagged schemas `old` and `new`, yielding
+/// the changes in the order of the types of `old` followed by those added
+fn diff_variants(old: &Value, new: &Value) -> Vec<VariantChange> {
+    let old = TaggedSchema::from_value(old.clone());
+    let new = TaggedSchema::from_value(new.clone());
+    let new_types = new.types();
+    let old_types = old.types();
+
+    let mut changes = Vec::new();
+    for name in old_types.iter() {
+        if !new_types.contains(name) {
+            changes.push(VariantChange::Removed(name.clone()));
+            continue;
+        }
+
+        let fields = diff_fields(&old, &new, name);
+        if !fields.is_empty() {
+            changes.push(VariantChange::Changed {
+                name: name.clone(),
+                fields,
+            });
+        }
+    }
+
+    for name in new_types {
+        if !old_types.contains(&name) {
+            changes.push(VariantChange::Added(name));
+        }
+    }
+
+    changes
+}
+
+/// Compares the fields of the payload of type `name` within `old` and `new`
+fn diff_fields(
+    old: &TaggedSchema,
+    new: &TaggedSchema,
+    name: &str,
+) -> Vec<FieldChange> {
+    let (old_fields, old_required) = payload_fields(old, name);
+    let (new_fields, new_required) = payload_fields(new, name);
+
+    let mut changes = Vec::new();
+    for (field, schema) in old_fields.iter() {
+        match new_fields.get(field) {
+            None => changes.push(FieldChange::Removed(field.clone())),
+            Some(x)
+                if x != schema
+                    || old_required.contains(field)
+                        != new_required.contains(field) =>
+            {
+                changes.push(FieldChange::Changed(field.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    for field in new_fields.keys() {
+        if !old_fields.contains_key(field) {
+            changes.push(FieldChange::Added {
+                name: field.clone(),
+                required: new_required.contains(field),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Schemas of the fields of the payload of type `name` within `schema`,
+/// along with the names of those that are required
+fn payload_fields(
+    schema: &TaggedSchema,
+    name: &str,
+) -> (BTreeMap<String, Value>, BTreeSet<String>) {
+    let payload = schema.payload(name).unwrap_or(&Value::Null);
+    let fields = match payload.get("properties").and_then(Value::as_object) {
+        Some(x) => x
+            .iter()
+            .map(|(k, v)| (k.clone(), expand(schema, v, 0)))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+    let required = payload
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|x| {
+            x.iter()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    (fields, required)
+}
+
+/// Replaces every reference within `value` by what it refers to and drops
+/// descriptions, so that schemas are only found to differ in what they
+/// accept, regardless of what their definitions are named or documented as
+fn expand(schema: &TaggedSchema, value: &Value, depth: usize) -> Value {
+    if depth > MAX_REF_DEPTH {
+        return value.clone();
+    }
+
+    match schema.resolve(value) {
+        Value::Object(x) => Value::Object(
+            x.iter()
+                .filter(|(k, _)| k.as_str() != "description")
+                .map(|(k, v)| (k.clone(), expand(schema, v, depth + 1)))
+                .collect(),
+        ),
+        Value::Array(x) => Value::Array(
+            x.iter().map(|v| expand(schema, v, depth + 1)).collect(),
+        ),
+        x => x.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variant(name: &str, definition: Option<&str>) -> Value {
+        let mut variant = json!({
+            "type": "object",
+            "properties": { "type": { "type": "string", "enum": [name] } },
+        });
+        if let Some(definition) = definition {
+            variant["properties"]["payload"] =
+                json!({ "$ref": format!("#/definitions/{}", definition) });
+        }
+        variant
+    }
+
+    fn exported(version: &str, request: Value) -> ExportedSchemas {
+        ExportedSchemas {
+            version: String::from(version),
+            request,
+            reply: json!({}),
+        }
+    }
+
+    #[test]
+    fn diff_should_report_nothing_for_same_schemas() {
+        let current = ExportedSchemas::current();
+        let diff = current.diff(&current.clone());
+        assert!(diff.is_empty(), "{}", diff);
+        assert_eq!(diff.breaking(), 0);
+    }
+
+    #[test]
+    fn diff_should_report_changed_variants_and_fields() {
+        let old = exported(
+            "0.1.0",
+            json!({
+                "oneOf": [variant("a", Some("A")), variant("b", None)],
+                "definitions": {
+                    "A": {
+                        "properties": {
+                            "x": { "$ref": "#/definitions/X" },
+                            "y": { "type": "integer" },
+                            "gone": { "type": "string" },
+                        },
+                        "required": ["x"],
+                    },
+                    "X": { "type": "string", "description": "Old" },
+                },
+            }),
+        );
+        let new = exported(
+            "0.2.0",
+            json!({
+                "oneOf": [variant("a", Some("Renamed")), variant("c", None)],
+                "definitions": {
+                    "Renamed": {
+                        "properties": {
+                            "x": { "$ref": "#/definitions/X" },
+                            "y": { "type": "string" },
+                            "opt": { "type": "string" },
+                            "req": { "type": "string" },
+                        },
+                        "required": ["x", "req"],
+                    },
+                    "X": { "type": "string", "description": "New" },
+                },
+            }),
+        );
+
+        let diff = new.diff(&old);
+        assert_eq!(
+            diff.request,
+            vec![
+                VariantChange::Changed {
+                    name: String::from("a"),
+                    fields: vec![
+                        FieldChange::Removed(String::from("gone")),
+                        FieldChange::Changed(String::from("y")),
+                        FieldChange::Added {
+                            name: String::from("opt"),
+                            required: false,
+                        },
+                        FieldChange::Added {
+                            name: String::from("req"),
+                            required: true,
+                        },
+                    ],
+                },
+                VariantChange::Removed(String::from("b")),
+                VariantChange::Added(String::from("c")),
+            ]
+        );
+        assert!(diff.reply.is_empty());
+
+        // Removing `b`, `gone`, and `req` and changing `y` are all breaking
+        assert_eq!(diff.breaking(), 4);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::cli::format::{self, FormatOption};
+use crate::core::Request;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Explanation of why raw input could not be decoded as a request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeDiagnostic {
+    pub msg: String,
+
+    /// Path to the value within the input that could not be decoded, such
+    /// as `/payload/path`, where an empty path is the input as a whole
+    pub path: String,
+
+    /// Line and column within the input where decoding failed, which is
+    /// only known when the input is not well-formed
+    pub position: Option<(usize, usize)>,
+
+    /// Names that were valid at the path, such as the types of requests
+    pub expected: Vec<String>,
+
+    /// Name from `expected` that was most likely intended
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for DecodeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid request")?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        if let Some((line, column)) = self.position {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        write!(f, ": {}", self.msg)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; did you mean `{}`?", suggestion)?;
+        }
+        if !self.expected.is_empty() {
+            write!(f, "; expected one of: {}", self.expected.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DecodeDiagnostic {}
+
+/// Decodes `input` as `T`, which holds a request at `request_path` within
+/// it, yielding a diagnostic explaining what was wrong with the request if
+/// decoding fails
+pub fn decode<T: for<'de> Deserialize<'de>>(
+    format: FormatOption,
+    input: &str,
+    request_path: &[&str],
+) -> Result<T, DecodeDiagnostic> {
+    format::convert_text(format, input)
+        .map_err(|x| diagnose(format, input, request_path, x.to_string()))
+}
+
+/// Determines why `input` failed to decode with the error `msg`
+fn diagnose(
+    format: FormatOption,
+    input: &str,
+    request_path: &[&str],
+    msg: String,
+) -> DecodeDiagnostic {
+    // Input that is not well-formed never reaches the request, so the best
+    // that can be done is to point at where it went wrong
+    let value: Value = match format {
+        FormatOption::Json => match serde_json::from_str(input) {
+            Ok(x) => x,
+            Err(x) => {
+                return DecodeDiagnostic {
+                    msg: strip_position(&x.to_string()),
+                    path: String::new(),
+                    position: Some((x.line(), x.column())),
+                    expected: Vec::new(),
+                    suggestion: None,
+                }
+            }
+        },
+        _ => match format::convert_text(format, input) {
+            Ok(x) => x,
+            Err(x) => return diagnostic(String::new(), x.to_string()),
+        },
+    };
+
+    let mut path = String::new();
+    let mut value = &value;
+    for name in request_path {
+        path.push('/');
+        path.push_str(name);
+        value = match value.get(name) {
+            Some(x) => x,
+            None => {
+                return diagnostic(
+                    path.clone(),
+                    format!("missing field `{}`", name),
+                )
+            }
+        };
+    }
+
+    diagnose_request(&path, value).unwrap_or_else(|| diagnostic(path, msg))
+}
+
+/// Checks the type and payload of `value` against the schema of requests,
+/// yielding none if nothing is found to be wrong with it
+fn diagnose_request(path: &str, value: &Value) -> Option<DecodeDiagnostic> {
+    let schema = TaggedSchema::of::<Request>();
+    let types = schema.types();
+
+    let r#type = match value.get("type") {
+        Some(Value::String(x)) => x,
+        Some(_) => {
+            return Some(DecodeDiagnostic {
+                expected: types,
+                ..diagnostic(
+                    format!("{}/type", path),
+                    String::from("request type must be a string"),
+                )
+            })
+        }
+        None => {
+            return Some(DecodeDiagnostic {
+                expected: types,
+                ..diagnostic(
+                    path.to_string(),
+                    String::from("missing field `type`"),
+                )
+            })
+        }
+    };
+
+    let fields = match schema.fields(r#type) {
+        Some(x) => x,
+        None => {
+            return Some(DecodeDiagnostic {
+                suggestion: nearest(r#type, &types),
+                expected: types,
+                ..diagnostic(
+                    format!("{}/type", path),
+                    format!("unknown request type `{}`", r#type),
+                )
+            })
+        }
+    };
+
+    let msg = match serde_json::from_value::<Request>(value.clone()) {
+        Ok(_) => return None,
+        Err(x) => x.to_string(),
+    };
+
+    // A misspelled field is the most likely reason for a field to be missing
+    // or unknown, so suggest the closest of those that were expected
+    let payload = value.get("payload").and_then(Value::as_object);
+    let suggestion = payload.and_then(|x| {
+        x.keys()
+            .filter(|k| !fields.contains(k))
+            .find_map(|k| nearest(k, &fields))
+    });
+
+    Some(DecodeDiagnostic {
+        suggestion,
+        expected: fields,
+        ..diagnostic(format!("{}/payload", path), msg)
+    })
+}
+
+fn diagnostic(path: String, msg: String) -> DecodeDiagnostic {
+    DecodeDiagnostic {
+        msg,
+        path,
+        position: None,
+        expected: Vec::new(),
+        suggestion: None,
+    }
+}
+
+/// Removes the position that serde_json appends to its errors, as it is
+/// reported separately
+fn strip_position(msg: &str) -> String {
+    match msg.rfind(" at line ") {
+        Some(i) => String::from(&msg[..i]),
+        None => String::from(msg),
+    }
+}
+
+/// Schema as JSON of content tagged by type, such as requests, which
+/// describes each type and the fields of its payload
+pub struct TaggedSchema(Value);
+
+impl TaggedSchema {
+    pub fn of<T: schemars::JsonSchema>() -> Self {
+        Self(
+            serde_json::to_value(schemars::schema_for!(T))
+                .expect("Failed to serialize schema"),
+        )
+    }
+
+    pub fn from_value(schema: Value) -> Self {
+        Self(schema)
+    }
+
+    /// Schemas of each type
+    fn variants(&self) -> impl Iterator<Item = &Value> {
+        ["oneOf", "anyOf"]
+            .iter()
+            .filter_map(move |k| self.0.get(*k).and_then(Value::as_array))
+            .flatten()
+    }
+
+    /// Name of each type
+    pub fn types(&self) -> Vec<String> {
+        self.variants().filter_map(variant_type).collect()
+    }
+
+    /// Schema of the payload for the type `name`, which is null if the type
+    /// has no payload, or none if there is no such type
+    pub fn payload(&self, name: &str) -> Option<&Value> {
+        let variant = self
+            .variants()
+            .find(|v| variant_type(v).as_deref() == Some(name))?;
+
+        Some(match variant.pointer("/properties/payload") {
+            Some(x) => self.resolve(x),
+            None => &Value::Null,
+        })
+    }
+
+    /// Names of the fields of the payload for the type `name`, or none if
+    /// there is no such type
+    pub fn fields(&self, name: &str) -> Option<Vec<String>> {
+        Some(
+            self.payload(name)?
+                .get("properties")
+                .and_then(Value::as_object)
+                .map(|x| x.keys().cloned().collect())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Follows `schema` to the definition it refers to, if it is a reference
+    pub fn resolve<'a>(&'a self, schema: &'a Value) -> &'a Value {
+        schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|x| x.strip_prefix('#'))
+            .and_then(|x| self.0.pointer(x))
+            .unwrap_or(schema)
+    }
+}
+
+/// Name of the request type described by the schema of a variant
+fn variant_type(variant: &Value) -> Option<String> {
+    variant
+        .pointer("/properties/type/enum/0")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+/// Finds the name in `names` closest to `name`, provided that it is close
+/// enough to have plausibly been a typo
+fn nearest(name: &str, names: &[String]) -> Option<String> {
+    names
+        .iter()
+        .map(|x| (edit_distance(name, x), x))
+        .filter(|(d, x)| *d <= (x.chars().count() / 3).max(2))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, x)| x.clone())
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Content;
+
+    fn decode_content(input: &str) -> Result<Content, DecodeDiagnostic> {
+        decode(FormatOption::Json, input, &[])
+    }
+
+    #[test]
+    fn decode_should_report_position_of_malformed_input() {
+        let d = decode_content("{\n  \"type\": }").unwrap_err();
+        assert_eq!(d.path, "");
+        assert_eq!(d.position, Some((2, 11)));
+        assert!(!d.msg.contains("at line"), "{}", d.msg);
+    }
+
+    #[test]
+    fn decode_should_suggest_nearest_request_type() {
+        let d =
+            decode_content(r#"{"type": "read_fil_request", "payload": {}}"#)
+                .unwrap_err();
+        assert_eq!(d.path, "/type");
+        assert_eq!(d.suggestion.as_deref(), Some("read_file_request"));
+        assert!(d.expected.contains(&String::from("heartbeat_request")));
+    }
+
+    #[test]
+    fn decode_should_suggest_nearest_payload_field() {
+        let d = decode(
+            FormatOption::Json,
+            r#"{"content": {"type": "create_dir_request",
+                "payload": {"pth": "a", "include_components": true}},
+                "metadata": {}}"#,
+            &["content"],
+        )
+        .map(|x: crate::cli::ContentAndMetadata| x.content)
+        .unwrap_err();
+        assert_eq!(d.path, "/content/payload");
+        assert_eq!(d.suggestion.as_deref(), Some("path"));
+        assert!(d.expected.contains(&String::from("path")));
+    }
+
+    #[test]
+    fn decode_should_report_missing_request() {
+        let d = decode::<crate::cli::ContentAndMetadata>(
+            FormatOption::Json,
+            r#"{"metadata": {}}"#,
+            &["content"],
+        )
+        .unwrap_err();
+        assert_eq!(d.path, "/content");
+        assert_eq!(d.msg, "missing field `content`");
+    }
+
+    #[test]
+    fn edit_distance_should_count_single_character_edits() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -5,9 +5,19 @@ use strum_macros::{AsRefStr, EnumString, EnumVariantNames};
 pub type FormatResult = Result<String, Box<dyn std::error::Error>>;
 
 #[derive(
-    Copy, Clone, Debug, PartialEq, Eq, EnumString, EnumVariantNames, AsRefStr,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumString,
+    EnumVariantNames,
+    AsRefStr,
+    Serialize,
+    Deserialize,
 )]
 #[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum FormatOption {
     /// Human-readable format for input and output
     Human,
@@ -29,7 +39,7 @@ pub fn convert_text<T: for<'de> Deserialize<'de>>(
         FormatOption::Json => Ok(serde_json::from_str(text)?),
 
         #[cfg(feature = "format-sexpression")]
-        FormatOption::Sexpression => Ok(serde_lexpr::from_str(&text)?),
+        FormatOption::Sexpression => Ok(serde_lexpr::from_str(text)?),
 
         FormatOption::Human => Err("Cannot convert to human format".into()),
     }
@@ -0,0 +1,91 @@
+use log::{debug, error};
+use std::io;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Instant;
+
+/// Tracks whether Ctrl-C has been pressed or the deadline of a command has
+/// passed, allowing commands to release remote resources such as open files
+/// and running procs before exiting rather than leaving them for the server
+/// to evict
+///
+/// Once listening, Ctrl-C no longer terminates the process by itself, so
+/// commands are expected to check the interrupt regularly
+#[derive(Clone, Debug, Default)]
+pub struct Interrupt {
+    flag: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl Interrupt {
+    /// Begins listening for Ctrl-C in the background, also interrupting once
+    /// `deadline` (if any) has passed
+    pub fn listen(deadline: Option<Instant>) -> Self {
+        let interrupt = Self {
+            deadline,
+            ..Default::default()
+        };
+        let flag = Arc::clone(&interrupt.flag);
+        tokio::spawn(async move {
+            match tokio::signal::ctrl_c().await {
+                Ok(()) => {
+                    debug!("Received Ctrl-C");
+                    flag.store(true, Ordering::SeqCst);
+                }
+                Err(x) => error!("Failed to listen for Ctrl-C: {}", x),
+            }
+        });
+        interrupt
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// Whether the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        matches!(self.deadline, Some(x) if Instant::now() >= x)
+    }
+
+    /// Fails with an interrupted error if Ctrl-C has been pressed, or with a
+    /// timed out error if the deadline has passed
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_set() {
+            Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "Interrupted by Ctrl-C",
+            ))
+        } else if self.is_expired() {
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Overall deadline exceeded",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn check_should_fail_once_deadline_passes() {
+        let interrupt = Interrupt {
+            deadline: Some(Instant::now() + Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert!(interrupt.check().is_ok());
+
+        let interrupt = Interrupt {
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        };
+        let err = interrupt.check().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::cli::opts::client::{history::HistoryCommand, Subcommand};
+use chrono::prelude::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// Name of the directory (relative to the home directory) containing the
+/// default journal
+pub const DEFAULT_JOURNAL_DIR: &str = ".over-there";
+
+/// Name of the default journal file within the journal directory
+pub const DEFAULT_JOURNAL_FILE: &str = "journal.jsonl";
+
+/// Represents the result of an operation recorded in the journal
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JournalOutcome {
+    Success,
+    Failure { msg: String },
+}
+
+impl fmt::Display for JournalOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Success => write!(f, "ok"),
+            Self::Failure { msg } => write!(f, "failed: {}", msg),
+        }
+    }
+}
+
+/// Represents a single client operation recorded in the journal
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Unique id of the entry within the journal, used for replaying
+    pub id: u32,
+
+    /// When the operation was performed
+    pub timestamp: DateTime<Utc>,
+
+    /// Address (<host>:<port>) of the server the operation targeted
+    pub target: String,
+
+    /// Name of the subcommand that was performed
+    pub command: String,
+
+    /// The subcommand and its arguments, with any contents redacted
+    pub subcommand: Subcommand,
+
+    /// Whether or not part of the subcommand was redacted, meaning that
+    /// it cannot be replayed
+    pub redacted: bool,
+
+    /// Result of performing the operation
+    pub outcome: JournalOutcome,
+}
+
+impl JournalEntry {
+    /// Whether or not the entry satisfies the filters of a history command
+    pub fn matches(&self, cmd: &HistoryCommand) -> bool {
+        cmd.target
+            .as_ref()
+            .map(|t| t == &self.target)
+            .unwrap_or(true)
+            && cmd
+                .command
+                .as_ref()
+                .map(|c| c == &self.command)
+                .unwrap_or(true)
+            && (!cmd.failed || self.outcome != JournalOutcome::Success)
+    }
+}
+
+/// Local, append-only record of operations performed by the client, stored
+/// as one JSON entry per line
+#[derive(Clone, Debug)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Creates a journal using `path` if provided, otherwise falling back to
+    /// the default location within the home directory (if one exists)
+    pub fn from_path_or_default(path: Option<&PathBuf>) -> Option<Self> {
+        path.cloned().or_else(default_path).map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Loads all entries from the journal, yielding an empty list if the
+    /// journal has not yet been created
+    pub async fn entries(&self) -> io::Result<Vec<JournalEntry>> {
+        let text = match tokio::fs::read_to_string(&self.path).await {
+            Ok(text) => text,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(x) => return Err(x),
+        };
+
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+            })
+            .collect()
+    }
+
+    /// Looks up an entry in the journal by its `id`
+    pub async fn get(&self, id: u32) -> io::Result<Option<JournalEntry>> {
+        Ok(self.entries().await?.into_iter().find(|e| e.id == id))
+    }
+
+    /// Appends a new entry to the journal for `subcommand` performed against
+    /// `target`, redacting any contents prior to writing
+    pub async fn record(
+        &self,
+        target: &str,
+        subcommand: &Subcommand,
+        outcome: JournalOutcome,
+    ) -> io::Result<JournalEntry> {
+        let id = self.entries().await?.last().map(|e| e.id + 1).unwrap_or(1);
+        let (subcommand, redacted) = redact(subcommand.clone());
+        let entry = JournalEntry {
+            id,
+            timestamp: Utc::now(),
+            target: target.to_string(),
+            command: subcommand.name().to_string(),
+            subcommand,
+            redacted,
+            outcome,
+        };
+
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+        line.push('\n');
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?
+            .write_all(line.as_bytes())
+            .await?;
+
+        Ok(entry)
+    }
+}
+
+/// Returns the default journal path within the home directory, if available
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| {
+            PathBuf::from(home)
+                .join(DEFAULT_JOURNAL_DIR)
+                .join(DEFAULT_JOURNAL_FILE)
+        })
+}
+
+/// Strips file contents and raw input from `subcommand`, returning the
+/// redacted subcommand and whether or not anything was removed
+fn redact(subcommand: Subcommand) -> (Subcommand, bool) {
+    match subcommand {
+        Subcommand::WriteFile(mut c) => {
+            c.contents = String::from("<redacted>");
+            (Subcommand::WriteFile(c), true)
+        }
+        Subcommand::Raw(mut c) => {
+            let redacted = c.input.is_some() || c.interactive;
+            c.input = c.input.map(|_| String::from("<redacted>"));
+            (Subcommand::Raw(c), redacted)
+        }
+        x => (x, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::opts::client::{dir, file};
+
+    fn make_history_command() -> HistoryCommand {
+        HistoryCommand {
+            replay: None,
+            target: None,
+            command: None,
+            failed: false,
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn entries_should_return_empty_list_if_journal_missing() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(tempdir.as_ref().join("missing.jsonl"));
+
+        assert!(journal.entries().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn record_should_assign_increasing_ids_to_entries() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(tempdir.as_ref().join("a/journal.jsonl"));
+        let subcommand = Subcommand::ListDir(dir::ListDirCommand {
+            path: String::from("some/dir"),
+        });
+
+        let first = journal
+            .record("127.0.0.1:60123", &subcommand, JournalOutcome::Success)
+            .await
+            .unwrap();
+        let second = journal
+            .record(
+                "127.0.0.1:60123",
+                &subcommand,
+                JournalOutcome::Failure {
+                    msg: String::from("some error"),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(first.id, 1);
+        assert_eq!(second.id, 2);
+
+        let entries = journal.entries().await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].command, "ls-dir");
+        assert!(!entries[1].redacted);
+        assert_eq!(
+            entries[1].outcome,
+            JournalOutcome::Failure {
+                msg: String::from("some error")
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn record_should_redact_file_contents() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(tempdir.as_ref().join("journal.jsonl"));
+        let subcommand = Subcommand::WriteFile(file::WriteFileCommand {
+            path: String::from("some/file"),
+            contents: String::from("secret"),
+            append: false,
+            create_new: false,
+        });
+
+        let entry = journal
+            .record("127.0.0.1:60123", &subcommand, JournalOutcome::Success)
+            .await
+            .unwrap();
+        assert!(entry.redacted);
+
+        let text = tokio::fs::read_to_string(journal.path()).await.unwrap();
+        assert!(!text.contains("secret"), "Contents were not redacted");
+    }
+
+    #[test]
+    fn matches_should_apply_all_provided_filters() {
+        let entry = JournalEntry {
+            id: 1,
+            timestamp: Utc::now(),
+            target: String::from("127.0.0.1:60123"),
+            command: String::from("ls-dir"),
+            subcommand: Subcommand::ListDir(dir::ListDirCommand {
+                path: String::from("some/dir"),
+            }),
+            redacted: false,
+            outcome: JournalOutcome::Success,
+        };
+
+        assert!(entry.matches(&make_history_command()));
+
+        let mut cmd = make_history_command();
+        cmd.target = Some(String::from("127.0.0.1:60123"));
+        cmd.command = Some(String::from("ls-dir"));
+        assert!(entry.matches(&cmd));
+
+        cmd.command = Some(String::from("rm-dir"));
+        assert!(!entry.matches(&cmd));
+
+        let mut cmd = make_history_command();
+        cmd.failed = true;
+        assert!(!entry.matches(&cmd));
+    }
+}
@@ -0,0 +1,225 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory (relative to the home directory) containing the
+/// default list of known servers
+pub const DEFAULT_KNOWN_SERVERS_DIR: &str = ".config/over-there";
+
+/// Name of the default list of known servers within its directory
+pub const DEFAULT_KNOWN_SERVERS_FILE: &str = "known_servers";
+
+/// Represents a server whose identity was trusted on first use
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownServer {
+    /// Address (<host>:<port>) used to connect to the server
+    pub addr: String,
+
+    /// Public identity key presented by the server
+    pub public_key: Vec<u8>,
+}
+
+/// Represents how the identity presented by a server compares to the one
+/// recorded for it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trust {
+    /// Server has no identity and none was recorded for it
+    Unverified,
+
+    /// Server presented the identity recorded for it
+    Known,
+
+    /// Server presented an identity and none was recorded for it
+    New,
+
+    /// Server presented a different identity (or none at all) than the one
+    /// recorded for it
+    Changed { previous: Vec<u8> },
+}
+
+/// Local record of the identities presented by servers the first time the
+/// client connected to them, stored as one `<addr> <hex public key>` entry
+/// per line
+#[derive(Clone, Debug)]
+pub struct KnownServers {
+    path: PathBuf,
+}
+
+impl KnownServers {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Creates a list of known servers using `path` if provided, otherwise
+    /// falling back to the default location within the home directory (if
+    /// one exists)
+    pub fn from_path_or_default(path: Option<&PathBuf>) -> Option<Self> {
+        path.cloned().or_else(default_path).map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Loads all known servers, yielding an empty list if the file has not
+    /// yet been created
+    pub async fn entries(&self) -> io::Result<Vec<KnownServer>> {
+        let text = match tokio::fs::read_to_string(&self.path).await {
+            Ok(text) => text,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(x) => return Err(x),
+        };
+
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next().map(hex::decode)) {
+                    (Some(addr), Some(Ok(public_key))) => Ok(KnownServer {
+                        addr: addr.to_string(),
+                        public_key,
+                    }),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Malformed known server: {}", line),
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Compares the `public_key` presented by the server at `addr` to the
+    /// one recorded for it
+    pub async fn check(
+        &self,
+        addr: &str,
+        public_key: Option<&[u8]>,
+    ) -> io::Result<Trust> {
+        let previous = self
+            .entries()
+            .await?
+            .into_iter()
+            .find(|server| server.addr == addr)
+            .map(|server| server.public_key);
+
+        Ok(match (previous, public_key) {
+            (None, None) => Trust::Unverified,
+            (None, Some(_)) => Trust::New,
+            (Some(previous), Some(key)) if previous == key => Trust::Known,
+            (Some(previous), _) => Trust::Changed { previous },
+        })
+    }
+
+    /// Records `public_key` as the identity of the server at `addr`,
+    /// replacing any identity recorded before, or forgets the server if
+    /// no key is provided
+    pub async fn set(
+        &self,
+        addr: &str,
+        public_key: Option<&[u8]>,
+    ) -> io::Result<()> {
+        let mut entries: Vec<KnownServer> = self
+            .entries()
+            .await?
+            .into_iter()
+            .filter(|server| server.addr != addr)
+            .collect();
+
+        if let Some(public_key) = public_key {
+            entries.push(KnownServer {
+                addr: addr.to_string(),
+                public_key: public_key.to_vec(),
+            });
+        }
+
+        let text: String = entries
+            .iter()
+            .map(|server| {
+                format!("{} {}\n", server.addr, hex::encode(&server.public_key))
+            })
+            .collect();
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(&self.path, text).await
+    }
+}
+
+/// Returns the default path of the list of known servers within the home
+/// directory, if available
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| {
+            PathBuf::from(home)
+                .join(DEFAULT_KNOWN_SERVERS_DIR)
+                .join(DEFAULT_KNOWN_SERVERS_FILE)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_should_compare_key_to_one_recorded_on_first_use() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let known_servers =
+            KnownServers::new(tempdir.as_ref().join("known_servers"));
+        let addr = "127.0.0.1:60123";
+
+        let trust = known_servers.check(addr, None).await.unwrap();
+        assert_eq!(trust, Trust::Unverified);
+
+        let trust = known_servers.check(addr, Some(&[1, 2])).await.unwrap();
+        assert_eq!(trust, Trust::New);
+
+        known_servers.set(addr, Some(&[1, 2])).await.unwrap();
+        let trust = known_servers.check(addr, Some(&[1, 2])).await.unwrap();
+        assert_eq!(trust, Trust::Known);
+
+        let trust = known_servers.check(addr, Some(&[3, 4])).await.unwrap();
+        assert_eq!(
+            trust,
+            Trust::Changed {
+                previous: vec![1, 2]
+            }
+        );
+
+        let trust = known_servers.check(addr, None).await.unwrap();
+        assert_eq!(
+            trust,
+            Trust::Changed {
+                previous: vec![1, 2]
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn set_should_replace_or_remove_only_entry_of_addr() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.as_ref().join("known_servers");
+        let known_servers = KnownServers::new(&path);
+
+        known_servers.set("a:1", Some(&[1])).await.unwrap();
+        known_servers.set("b:2", Some(&[2])).await.unwrap();
+        known_servers.set("a:1", Some(&[3])).await.unwrap();
+        assert_eq!(
+            tokio::fs::read_to_string(&path).await.unwrap(),
+            "b:2 02\na:1 03\n"
+        );
+
+        known_servers.set("b:2", None).await.unwrap();
+        assert_eq!(
+            known_servers.entries().await.unwrap(),
+            vec![KnownServer {
+                addr: String::from("a:1"),
+                public_key: vec![3],
+            }]
+        );
+    }
+}
@@ -1,21 +1,49 @@
 mod builder;
+mod compat;
+mod diagnostic;
 pub mod format;
+mod interrupt;
+mod journal;
+mod known_servers;
 mod opts;
+mod terminal;
 
-use crate::core::{ConnectedClient, Content, RemoteProc, Reply, SchemaInfo};
+use crate::core::transport::auth::identity;
+use crate::core::{
+    reply::{
+        DiagnosticsArgs, ErrorCode, PathCreateStatus, UploadSessionStatus,
+    },
+    request::{
+        ChecksumAlgorithm, DiagnosticSection, ExecProcArgs, ExecProcPtyArgs,
+        FileOpenModes, ManifestChunk, ManifestFile, Newline, ProcIoMode,
+    },
+    set_strict_decoding, AskError, Chunker, ClientEvent, ConnectedClient,
+    Content, DirCopy, DownloadOptions, ExecAskError, FileAskError, Mirror,
+    MirrorOptions, MirrorPass, RemoteFile, RemoteProc, Reply, ReplyError,
+    SchemaInfo, SendError,
+};
+use diagnostic::DecodeDiagnostic;
 use format::FormatOption;
-use log::info;
+use futures::{
+    future::{self, Either},
+    stream::{Stream, StreamExt},
+};
+use interrupt::Interrupt;
+use journal::{Journal, JournalEntry, JournalOutcome};
+use known_servers::{KnownServers, Trust};
+use log::{info, trace, warn};
 use opts::{
-    client::{self, ClientCommand},
+    client::{self, history::HistoryCommand, ClientCommand},
     schema::{SchemaSubcommand, SchemaType},
     server::ServerCommand,
-    Command,
+    types, Command,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::error::Error;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 pub use opts::Opts;
@@ -30,15 +58,29 @@ pub struct ContentAndMetadata {
 
 /// Primary entrypoint to run the executable based on input options
 pub async fn run(opts: Opts) -> Result<(), Box<dyn Error>> {
+    if let Some(common_opts) = opts.command.common_opts() {
+        set_strict_decoding(common_opts.strict_decoding);
+    }
+
     match opts.command {
         Command::Server(s) => run_server(s).await?,
         Command::Client(c) => match (c.output_format, run_client(c).await) {
             (FormatOption::Human, Err(x)) => return Err(x),
-            (f, Err(x)) => format::format_content_println(
-                f,
-                Content::from(Reply::from(x)),
-                |_| Err("Cannot write human-readable stderr to stdout".into()),
-            )?,
+            (f, Err(x)) => {
+                let error =
+                    ReplyError::with_code(x.to_string(), error_code(&*x));
+                format::format_content_println(
+                    f,
+                    Content::from(Reply::Error(error)),
+                    |_| {
+                        Err("Cannot write human-readable stderr to stdout"
+                            .into())
+                    },
+                )?;
+
+                // Still fail so that the exit code reflects the error
+                return Err(x);
+            }
             _ => (),
         },
         Command::Schema(s) => run_schema(s.command).await?,
@@ -47,6 +89,26 @@ pub async fn run(opts: Opts) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Stable code identifying the kind of an error that ended a command, which
+/// is also used as the exit code of the process
+pub fn error_code(error: &(dyn Error + 'static)) -> ErrorCode {
+    if let Some(x) = error.downcast_ref::<AskError>() {
+        x.code()
+    } else if let Some(x) = error.downcast_ref::<FileAskError>() {
+        x.code()
+    } else if let Some(x) = error.downcast_ref::<ExecAskError>() {
+        x.code()
+    } else if let Some(x) = error.downcast_ref::<SendError>() {
+        x.code()
+    } else if let Some(x) = error.downcast_ref::<io::Error>() {
+        ErrorCode::from(x.kind())
+    } else if error.is::<DecodeDiagnostic>() {
+        ErrorCode::INVALID_INPUT
+    } else {
+        ErrorCode::GENERIC
+    }
+}
+
 fn validate_opts(opts: &opts::CommonOpts) -> io::Result<()> {
     if opts.encryption != opts::types::Encryption::None
         && opts.encryption_key.is_none()
@@ -113,11 +175,38 @@ async fn write_stderr(text: String, path: Option<&PathBuf>) -> io::Result<()> {
     }
 }
 
+/// Returns the permission bits of a local file so they can be reproduced
+/// on the server, or None if not supported on this platform
+#[cfg(unix)]
+async fn local_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    tokio::fs::metadata(path)
+        .await
+        .ok()
+        .map(|m| m.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+async fn local_mode(_path: &Path) -> Option<u32> {
+    None
+}
+
 async fn run_server(cmd: ServerCommand) -> Result<(), Box<dyn Error>> {
     info!("Launching server: {:?}", cmd);
 
     validate_opts(&cmd.opts)?;
 
+    if cmd.check_config {
+        let config = builder::check_server_config(&cmd)?;
+        let diagnostics = DiagnosticsArgs {
+            config: Some(Box::new(config)),
+            ..Default::default()
+        };
+        print!("{}", diagnostics);
+        println!("Configuration is valid");
+        return Ok(());
+    }
+
     let server = builder::start_server(&cmd).await?;
 
     // Let server run to completion
@@ -150,11 +239,307 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
 
     validate_opts(&cmd.opts)?;
 
-    let mut client = builder::start_client(&cmd)
-        .await
-        .expect("Failed to connect with client");
+    let journal = Journal::from_path_or_default(cmd.journal.as_ref());
+
+    if let client::Subcommand::History(c) = &cmd.command {
+        return run_history(&cmd, c, journal).await;
+    }
+
+    let result = run_within_deadline(&cmd, &cmd.command).await;
+    record_in_journal(&cmd, &cmd.command, journal.as_ref(), &result).await;
+    result
+}
+
+/// Runs `subcommand`, stopping it once the overall deadline (if any) of
+/// `cmd` has passed
+async fn run_within_deadline(
+    cmd: &ClientCommand,
+    subcommand: &client::Subcommand,
+) -> Result<(), Box<dyn Error>> {
+    let duration = match cmd.overall_deadline {
+        Some(x) => x,
+        None => return run_client_subcommand(cmd, subcommand, None).await,
+    };
+
+    // Commands stop themselves at the deadline so that they can release
+    // what they opened on the server, so only cut them off if that cleanup
+    // itself stalls
+    let deadline = Some(Instant::now() + duration);
+    let limit = duration + cmd.opts.timeout;
+    let run = run_client_subcommand(cmd, subcommand, deadline);
+    match tokio::time::timeout(limit, run).await {
+        Ok(result) => result,
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Overall deadline exceeded",
+        )
+        .into()),
+    }
+}
+
+/// Records the outcome of `subcommand` in the journal (if enabled), logging
+/// rather than failing if the journal cannot be written
+async fn record_in_journal(
+    cmd: &ClientCommand,
+    subcommand: &client::Subcommand,
+    journal: Option<&Journal>,
+    result: &Result<(), Box<dyn Error>>,
+) {
+    if cmd.no_journal {
+        return;
+    }
+
+    if let Some(journal) = journal {
+        let outcome = match result {
+            Ok(_) => JournalOutcome::Success,
+            Err(x) => JournalOutcome::Failure { msg: x.to_string() },
+        };
+
+        if let Err(x) = journal.record(&cmd.addr, subcommand, outcome).await {
+            warn!(
+                "Failed to record to journal {}: {}",
+                journal.path().to_string_lossy(),
+                x
+            );
+        }
+    }
+}
+
+async fn run_history(
+    cmd: &ClientCommand,
+    history_cmd: &HistoryCommand,
+    journal: Option<Journal>,
+) -> Result<(), Box<dyn Error>> {
+    let journal = journal.ok_or("Unable to determine journal location")?;
+
+    if let Some(id) = history_cmd.replay {
+        let entry = journal
+            .get(id)
+            .await?
+            .ok_or_else(|| format!("No journal entry with id {}", id))?;
+
+        if entry.redacted {
+            return Err(format!(
+                "Journal entry {} had contents redacted and cannot be replayed",
+                id
+            )
+            .into());
+        }
+
+        info!("Replaying journal entry {}: {:?}", id, entry);
+        let result = run_within_deadline(cmd, &entry.subcommand).await;
+        record_in_journal(cmd, &entry.subcommand, Some(&journal), &result)
+            .await;
+        return result;
+    }
+
+    let mut entries: Vec<JournalEntry> = journal
+        .entries()
+        .await?
+        .into_iter()
+        .filter(|e| e.matches(history_cmd))
+        .collect();
+
+    if let Some(limit) = history_cmd.limit {
+        entries = entries.split_off(entries.len().saturating_sub(limit));
+    }
+
+    format::format_println(cmd.output_format, entries, |entries| {
+        Ok(entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "{:>5} {} {} {} {}",
+                    e.id,
+                    e.timestamp.to_rfc3339(),
+                    e.target,
+                    e.command,
+                    e.outcome,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"))
+    })
+}
 
-    match &cmd.command {
+/// Checks the identity presented by the server against the one recorded for
+/// it in the list of known servers, recording the identity on first use
+async fn check_known_server(
+    cmd: &ClientCommand,
+    client: &mut ConnectedClient,
+) -> io::Result<()> {
+    // Pinned keys are already verified when connecting and take the place
+    // of the list of known servers
+    if cmd.no_known_servers || !cmd.pinned_server_keys.is_empty() {
+        return Ok(());
+    }
+
+    let known_servers =
+        match KnownServers::from_path_or_default(cmd.known_servers.as_ref()) {
+            Some(known_servers) => known_servers,
+            None => return Ok(()),
+        };
+
+    let public_key = client.ask_server_identity().await?;
+    let describe = |key: Option<&[u8]>| {
+        key.map(|key| format!("identity {}", identity::fingerprint(key)))
+            .unwrap_or_else(|| String::from("no identity"))
+    };
+
+    let trust = known_servers
+        .check(&cmd.addr, public_key.as_deref())
+        .await?;
+    match trust {
+        Trust::Unverified | Trust::Known => Ok(()),
+        Trust::New => {
+            known_servers.set(&cmd.addr, public_key.as_deref()).await?;
+            eprintln!(
+                "Permanently added {} with {} to {}",
+                cmd.addr,
+                describe(public_key.as_deref()),
+                known_servers.path().to_string_lossy()
+            );
+            Ok(())
+        }
+        Trust::Changed { previous } => {
+            eprintln!(
+                "@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+                 @    WARNING: SERVER IDENTITY HAS CHANGED!               @\n\
+                 @@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@@\n\
+                 Server {} previously presented {} but now presents {}.\n\
+                 Someone could be intercepting the connection, or the \
+                 identity key of the server was replaced.",
+                cmd.addr,
+                describe(Some(&previous)),
+                describe(public_key.as_deref()),
+            );
+
+            if cmd.accept_new_key {
+                known_servers.set(&cmd.addr, public_key.as_deref()).await?;
+                eprintln!(
+                    "Accepted new identity of {} in {}",
+                    cmd.addr,
+                    known_servers.path().to_string_lossy()
+                );
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "Identity of {} has changed; use --accept-new-key \
+                         to trust the new identity",
+                        cmd.addr
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Runs `subcommand` against the server, stopping once `deadline` (if any)
+/// has passed
+async fn run_client_subcommand(
+    cmd: &ClientCommand,
+    subcommand: &client::Subcommand,
+    deadline: Option<Instant>,
+) -> Result<(), Box<dyn Error>> {
+    // Validating raw input never involves the server, so avoid connecting
+    if let client::Subcommand::Raw(c) = subcommand {
+        if c.validate_only {
+            return validate_raw(c);
+        }
+    }
+
+    // A request that is fired and forgotten has no reply to wait on, which
+    // only makes sense for raw requests sent over udp
+    if cmd.fire_and_forget {
+        let is_raw = matches!(subcommand, client::Subcommand::Raw(_));
+        if !is_raw || cmd.opts.transport != types::Transport::Udp {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Fire and forget only supports raw requests over udp",
+            )
+            .into());
+        }
+    }
+
+    let connect = builder::start_client(cmd);
+    let mut client = match tokio::time::timeout(cmd.opts.timeout, connect).await
+    {
+        Ok(client) => client?,
+        Err(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out connecting to server",
+            )
+            .into())
+        }
+    };
+    let events = client.events().await;
+    let result = watch_for_shutdown(
+        run_against_server(cmd, subcommand, &mut client, deadline),
+        events,
+    )
+    .await;
+
+    // Tear down the client rather than leaving its tasks for the runtime to
+    // drop, which also makes sure that fire-and-forget requests were sent
+    if let Err(x) = client.close().await {
+        warn!("Failed to close client: {}", x);
+    }
+
+    result
+}
+
+/// Waits on `run` while watching `events` for the server announcing that it
+/// is shutting down, which is reported as soon as it arrives and explains
+/// why `run` failed should it fail afterwards
+async fn watch_for_shutdown(
+    run: impl std::future::Future<Output = Result<(), Box<dyn Error>>>,
+    events: impl Stream<Item = ClientEvent> + Send + 'static,
+) -> Result<(), Box<dyn Error>> {
+    let mut notices = events
+        .filter_map(|event| async move {
+            match event {
+                ClientEvent::ServerShuttingDown { grace } => Some(grace),
+                _ => None,
+            }
+        })
+        .boxed();
+    futures::pin_mut!(run);
+
+    let mut grace = None;
+    let result = loop {
+        match future::select(run.as_mut(), notices.next()).await {
+            Either::Left((result, _)) => break result,
+            Either::Right((Some(x), _)) => {
+                eprintln!("Server is shutting down within {}ms", x.as_millis());
+                grace = Some(x);
+            }
+            Either::Right((None, _)) => break run.as_mut().await,
+        }
+    };
+
+    match (result, grace) {
+        (Err(x), Some(_)) => Err(io::Error::new(
+            io::ErrorKind::ConnectionAborted,
+            format!("Server shut down: {}", x),
+        )
+        .into()),
+        (result, _) => result,
+    }
+}
+
+/// Runs `subcommand` against the server that `client` is connected to
+async fn run_against_server(
+    cmd: &ClientCommand,
+    subcommand: &client::Subcommand,
+    client: &mut ConnectedClient,
+    deadline: Option<Instant>,
+) -> Result<(), Box<dyn Error>> {
+    check_known_server(cmd, client).await?;
+
+    match subcommand {
         client::Subcommand::Version(_) => {
             let x = client.ask_version().await?;
             format_content_write!(
@@ -215,8 +600,36 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
                     .join("\n")),
             )?;
         }
+        client::Subcommand::ListMounts(c) => {
+            let x = client.ask_list_mounts(c.all).await?;
+            let bytes = |x: Option<u64>| {
+                x.map(|x| x.to_string())
+                    .unwrap_or_else(|| String::from("?"))
+            };
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::MountsList(x)),
+                Ok(x.mounts
+                    .iter()
+                    .map(|m| {
+                        format!(
+                            "{} [{}{}] {} of {} bytes available",
+                            m.path,
+                            m.fs_type.as_deref().unwrap_or("?"),
+                            if m.read_only { ", ro" } else { "" },
+                            bytes(m.available_bytes),
+                            bytes(m.total_bytes),
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")),
+            )?;
+        }
         client::Subcommand::CreateDir(c) => {
-            let x = client.ask_create_dir(c.path.clone(), c.parents).await?;
+            let x = client
+                .ask_create_dir_with_mode(c.path.clone(), c.parents, c.mode)
+                .await?;
             format_content_write!(
                 cmd.output_format,
                 cmd.redirect_stdout.as_ref(),
@@ -224,6 +637,42 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
                 Ok(format!("Created {}", c.path)),
             )?;
         }
+        client::Subcommand::MakePaths(c) => {
+            let x = client
+                .ask_create_paths(
+                    c.dirs.iter().map(From::from).collect(),
+                    c.files.iter().map(From::from).collect(),
+                )
+                .await?;
+            let lines: Vec<String> = x
+                .dirs
+                .iter()
+                .chain(x.files.iter())
+                .map(|r| match &r.status {
+                    PathCreateStatus::Created => format!("Created {}", r.path),
+                    PathCreateStatus::AlreadyExists => {
+                        format!("Exists {}", r.path)
+                    }
+                    PathCreateStatus::Failed { error } => {
+                        format!("Failed {}: {}", r.path, error.description)
+                    }
+                })
+                .collect();
+            let failed =
+                x.dirs.iter().chain(x.files.iter()).any(|r| {
+                    matches!(r.status, PathCreateStatus::Failed { .. })
+                });
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::PathsCreated(x)),
+                if failed {
+                    Err(lines.join("\n"))
+                } else {
+                    Ok(lines.join("\n"))
+                },
+            )?;
+        }
         client::Subcommand::MoveDir(c) => {
             let x = client.ask_rename_dir(c.from.clone(), c.to.clone()).await?;
             format_content_write!(
@@ -242,21 +691,51 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
                 Ok(format!("Removed {}", c.path)),
             )?;
         }
+        client::Subcommand::WriteFile(c) if c.append || c.create_new => {
+            let modes = FileOpenModes {
+                append: c.append,
+                create_new: c.create_new,
+                ..Default::default()
+            };
+            let mut file: RemoteFile = client
+                .ask_open_file_with_modes(c.path.clone(), modes)
+                .await?
+                .into();
+            let result =
+                client.ask_write_file(&mut file, c.contents.as_ref()).await;
+            client.ask_close_file(&file).await?;
+
+            let x = result?;
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::FileWritten(x)),
+                Ok(format!("{:?}", x)),
+            )?;
+        }
         client::Subcommand::WriteFile(c) => {
-            let mut file = client.ask_open_file(c.path.clone()).await?.into();
             let x = client
-                .ask_write_file(&mut file, c.contents.as_ref())
+                .ask_write_file_atomic_by_path(
+                    c.path.clone(),
+                    c.contents.as_ref(),
+                    None,
+                )
                 .await?;
             format_content_write!(
                 cmd.output_format,
                 cmd.redirect_stdout.as_ref(),
-                Content::from(Reply::FileWritten(x)),
+                Content::from(Reply::AtomicFileWritten(x)),
                 Ok(format!("{:?}", x)),
             )?;
         }
         client::Subcommand::ReadFile(c) => {
+            let interrupt = Interrupt::listen(deadline);
             let file = client.ask_open_file(c.path.clone()).await?.into();
-            let x = client.ask_read_file(&file).await?;
+            let result = client.ask_read_file(&file).await;
+            client.ask_close_file(&file).await?;
+            interrupt.check()?;
+
+            let x = result?;
             format_content_write!(
                 cmd.output_format,
                 cmd.redirect_stdout.as_ref(),
@@ -264,6 +743,51 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
                 Ok(String::from_utf8(x.contents)?),
             )?;
         }
+        client::Subcommand::DiffFile(c) => {
+            let contents_b = if c.local {
+                Some(tokio::fs::read(&c.path_b).await?)
+            } else {
+                None
+            };
+            let x = client
+                .ask_diff_files(
+                    c.path_a.clone(),
+                    c.path_b.clone(),
+                    contents_b,
+                    Some(c.context_lines),
+                )
+                .await?;
+
+            let text = if x.identical {
+                format!("Files {} and {} are identical", x.path_a, x.path_b)
+            } else if x.is_binary {
+                format!("Binary files {} and {} differ", x.path_a, x.path_b)
+            } else {
+                x.diff.trim_end().to_string()
+            };
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::FilesDiffed(x)),
+                Ok(text),
+            )?;
+        }
+        client::Subcommand::ChecksumFile(c) => {
+            let algorithm = match c.algorithm {
+                types::ChecksumAlgorithm::Sha256 => ChecksumAlgorithm::Sha256,
+                types::ChecksumAlgorithm::Sha512 => ChecksumAlgorithm::Sha512,
+                types::ChecksumAlgorithm::Crc32 => ChecksumAlgorithm::Crc32,
+            };
+            let x = client.ask_file_checksum(c.path.clone(), algorithm).await?;
+
+            let text = format!("{}  {}", x.digest, x.path);
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::FileChecksum(x)),
+                Ok(text),
+            )?;
+        }
         client::Subcommand::MoveFile(c) => {
             let x = client
                 .ask_rename_unopened_file(c.from.clone(), c.to.clone())
@@ -284,23 +808,320 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
                 Ok(format!("Removed {}", c.path)),
             )?;
         }
+        client::Subcommand::Upload(c) => {
+            let mut manifest = Vec::new();
+            let mut contents = Vec::new();
+            for path in c.files.iter() {
+                let data = tokio::fs::read(path).await?;
+                let file_name = path.file_name().ok_or_else(|| {
+                    format!("{:?} does not have a file name", path)
+                })?;
+
+                // Cut chunks by content so that an edit to a file that was
+                // uploaded before only changes the chunks around the edit
+                let chunks = Chunker::default().chunks(&data);
+                manifest.push(ManifestFile {
+                    path: Path::new(&c.destination).join(file_name).into(),
+                    size: data.len() as u64,
+                    hash: Some(format!("{:x}", Sha256::digest(&data))),
+                    mode: local_mode(path).await,
+                    chunks: chunks
+                        .iter()
+                        .cloned()
+                        .map(ManifestChunk::from)
+                        .collect(),
+                });
+                contents.push((data, chunks));
+            }
+
+            let interrupt = Interrupt::listen(deadline);
+            let x = client.ask_upload_manifest(manifest).await?;
+            let mut files: Vec<_> = x
+                .sessions
+                .iter()
+                .zip(contents.iter())
+                .filter_map(|(session, contents)| match &session.status {
+                    UploadSessionStatus::Ready { have_chunks, .. } => {
+                        RemoteFile::from_upload_session(session).map(|file| {
+                            (file, contents, have_chunks.as_slice())
+                        })
+                    }
+                    _ => None,
+                })
+                .collect();
+            for i in 0..files.len() {
+                // Close every file that has yet to be uploaded so that the
+                // server does not hold them open until evicted
+                if let Err(x) = interrupt.check() {
+                    for (file, _, _) in files[i..].iter() {
+                        if let Err(x) = client.ask_close_file(file).await {
+                            warn!("Failed to close {}: {}", file.id, x);
+                        }
+                    }
+                    return Err(x.into());
+                }
+
+                // Only send the chunks that the server does not already
+                // hold, where an empty file has no chunks to send at all
+                let (file, (data, chunks), have_chunks) = &mut files[i];
+                if data.is_empty() {
+                    client.ask_write_file(file, data).await?;
+                }
+                for (index, chunk) in chunks.iter().enumerate() {
+                    let index = index as u32;
+                    if !have_chunks.contains(&index) {
+                        let start = chunk.offset as usize;
+                        let data = &data[start..start + chunk.len];
+                        client
+                            .ask_write_upload_chunk(file, index, data)
+                            .await?;
+                    }
+                }
+                client.ask_close_file(file).await?;
+            }
+
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::UploadManifestPrepared(x)),
+                Ok(x.sessions
+                    .iter()
+                    .map(|s| match &s.status {
+                        UploadSessionStatus::Ready { have_chunks, .. }
+                            if !have_chunks.is_empty() =>
+                        {
+                            format!(
+                                "Uploaded {} ({} chunks already on server)",
+                                s.path,
+                                have_chunks.len()
+                            )
+                        }
+                        UploadSessionStatus::Ready { .. } => {
+                            format!("Uploaded {}", s.path)
+                        }
+                        UploadSessionStatus::UpToDate => {
+                            format!("Skipped {} (up-to-date)", s.path)
+                        }
+                        UploadSessionStatus::Failed { error } => {
+                            format!("Failed {}: {}", s.path, error)
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")),
+            )?;
+        }
+        client::Subcommand::Download(c) => {
+            let interrupt = Interrupt::listen(deadline);
+            let mut replies = Vec::new();
+            let mut lines = Vec::new();
+            let mut stopped = None;
+            for path in c.files.iter() {
+                if let Err(x) = interrupt.check() {
+                    stopped = Some(x);
+                    break;
+                }
+
+                let file_name =
+                    Path::new(path).file_name().ok_or_else(|| {
+                        format!("{} does not have a file name", path)
+                    })?;
+                let local_path = c.destination.join(file_name);
+
+                // Provide the etag of any local copy so that the server only
+                // sends the contents if they have changed
+                let etag = tokio::fs::read(&local_path)
+                    .await
+                    .ok()
+                    .map(|data| format!("{:x}", Sha256::digest(&data)));
+
+                // Download into a separate file so that a failed download
+                // never clobbers the local copy
+                let mut part_path = local_path.clone().into_os_string();
+                part_path.push(".part");
+                let mut part = tokio::fs::File::create(&part_path).await?;
+                let options = DownloadOptions {
+                    if_none_match: etag,
+                    window: c.window,
+                    retries: c.retries,
+                    ..Default::default()
+                };
+                let result = client
+                    .download_to(path.clone(), &mut part, options, |p| {
+                        trace!(
+                            "Downloaded {}/{} of {}",
+                            p.written,
+                            p.total,
+                            path
+                        )
+                    })
+                    .await;
+                drop(part);
+
+                let x = match result {
+                    Ok(x) if !x.not_modified => {
+                        tokio::fs::rename(&part_path, &local_path).await?;
+                        x
+                    }
+                    result => {
+                        tokio::fs::remove_file(&part_path).await?;
+                        result?
+                    }
+                };
+                if x.not_modified {
+                    lines.push(format!("Skipped {} (up-to-date)", path));
+                } else {
+                    lines.push(format!(
+                        "Downloaded {} to {}",
+                        path,
+                        local_path.to_string_lossy()
+                    ));
+                }
+
+                replies.push(Reply::FileContents(x));
+            }
+
+            // Report the files that were downloaded before being stopped
+            if stopped.is_none() || !replies.is_empty() {
+                format_content_write!(
+                    cmd.output_format,
+                    cmd.redirect_stdout.as_ref(),
+                    Content::from(Reply::Batch(From::from(replies))),
+                    Ok(lines.join("\n")),
+                )?;
+            }
+
+            if let Some(x) = stopped {
+                return Err(x.into());
+            }
+        }
+        client::Subcommand::UploadDir(c) => {
+            let copy = client
+                .upload_dir(&c.local_dir, c.remote_dir.clone())
+                .await?;
+            write_stdout(
+                dir_copy_summary("Uploaded", &copy),
+                cmd.redirect_stdout.as_ref(),
+            )
+            .await?;
+        }
+        client::Subcommand::DownloadDir(c) => {
+            let options = DownloadOptions {
+                window: c.window,
+                retries: c.retries,
+                ..Default::default()
+            };
+            let copy = client
+                .download_dir_with_options(
+                    c.remote_dir.clone(),
+                    &c.local_dir,
+                    options,
+                )
+                .await?;
+            write_stdout(
+                dir_copy_summary("Downloaded", &copy),
+                cmd.redirect_stdout.as_ref(),
+            )
+            .await?;
+        }
+        client::Subcommand::Mirror(c) => {
+            let interrupt = Interrupt::listen(deadline);
+            let mut mirror = Mirror::new(
+                c.remote_dir.clone(),
+                c.local_dir.clone(),
+                MirrorOptions {
+                    two_way: c.two_way,
+                    interval: c.interval,
+                    download: DownloadOptions {
+                        window: c.window,
+                        retries: c.retries,
+                        ..Default::default()
+                    },
+                },
+            );
+
+            loop {
+                interrupt.check()?;
+                let pass = mirror.sync(client).await?;
+                for line in mirror_pass_lines(&pass) {
+                    write_stdout(
+                        format!("{}\n", line),
+                        cmd.redirect_stdout.as_ref(),
+                    )
+                    .await?;
+                }
+
+                if c.once {
+                    break;
+                }
+                tokio::time::delay_for(mirror.interval()).await;
+            }
+        }
         client::Subcommand::Exec(c) => {
-            let proc = client
-                .ask_exec_proc_with_options(
-                    c.command.clone(),
-                    c.args.clone(),
-                    true,
-                    true,
-                    true,
-                    c.current_dir.clone(),
+            if c.pty && (c.stdin_file.is_some() || c.umask.is_some()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Pty does not support a stdin file or umask",
                 )
-                .await?
-                .into();
+                .into());
+            }
+            let io_mode = match c.io_mode {
+                types::ProcIoMode::Raw => ProcIoMode::Raw,
+                types::ProcIoMode::Line => ProcIoMode::Line,
+            };
+            let newline = match c.newline {
+                types::Newline::Keep => None,
+                types::Newline::Lf => Some(Newline::Lf),
+                types::Newline::Crlf => Some(Newline::Crlf),
+            };
+            let initial_stdin = match &c.stdin_file {
+                Some(path) => Some(tokio::fs::read(path).await?),
+                None => None,
+            };
+            let proc = if c.pty {
+                let (rows, cols) =
+                    terminal::size().unwrap_or(terminal::DEFAULT_SIZE);
+                client
+                    .ask_exec_proc_pty(ExecProcPtyArgs {
+                        command: c.command.clone(),
+                        args: c.args.clone(),
+                        current_dir: c.current_dir.clone(),
+                        rows,
+                        cols,
+                        term: std::env::var("TERM").ok(),
+                        detached: c.detached,
+                        labels: c.labels.clone(),
+                    })
+                    .await?
+            } else {
+                client
+                    .ask_exec_proc_with_args(ExecProcArgs {
+                        command: c.command.clone(),
+                        args: c.args.clone(),
+                        stdin: true,
+                        stdout: true,
+                        stderr: true,
+                        current_dir: c.current_dir.clone(),
+                        umask: c.umask,
+                        detached: c.detached,
+                        stdout_file: None,
+                        stderr_file: None,
+                        io_mode,
+                        newline,
+                        labels: c.labels.clone(),
+                        close_stdin: initial_stdin.is_some(),
+                        initial_stdin,
+                    })
+                    .await?
+            }
+            .into();
             process_proc(
                 client,
-                !c.no_stdin,
-                cmd.redirect_stdout,
-                cmd.redirect_stderr,
+                Interrupt::listen(deadline),
+                !c.detached,
+                !c.no_stdin && c.stdin_file.is_none(),
+                c.pty,
+                cmd.redirect_stdout.clone(),
+                cmd.redirect_stderr.clone(),
                 c.post_exit_duration,
                 proc,
                 cmd.output_format,
@@ -312,9 +1133,12 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
             let proc = RemoteProc::shallow(c.id);
             process_proc(
                 client,
+                Interrupt::listen(deadline),
+                false,
                 !c.no_stdin,
-                cmd.redirect_stdout,
-                cmd.redirect_stderr,
+                c.pty,
+                cmd.redirect_stdout.clone(),
+                cmd.redirect_stderr.clone(),
                 c.post_exit_duration,
                 proc,
                 cmd.output_format,
@@ -325,14 +1149,7 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
         client::Subcommand::Raw(c) => {
             // If provided some input, attempt to execute it
             if let Some(line) = &c.input {
-                execute_raw_and_report(
-                    &mut client,
-                    &line,
-                    c.format,
-                    c.format,
-                    c.meta_mode,
-                )
-                .await?;
+                send_raw(client, line, c, cmd.fire_and_forget).await?;
             }
 
             // If marked interactive, continue to read stdin for more lines
@@ -344,29 +1161,36 @@ async fn run_client(cmd: ClientCommand) -> Result<(), Box<dyn Error>> {
                         break;
                     }
 
-                    execute_raw_and_report(
-                        &mut client,
-                        &line,
-                        c.format,
-                        c.format,
-                        c.meta_mode,
-                    )
-                    .await?;
+                    send_raw(client, &line, c, cmd.fire_and_forget).await?;
 
                     // NOTE: Must clear line contents before next reading
                     line.clear();
                 }
             }
         }
-        client::Subcommand::InternalDebug(_) => {
-            let x = client.ask_internal_debug().await?;
+        client::Subcommand::Diagnostics(c) => {
+            let x = client.ask_diagnostics(c.sections.clone()).await?;
+            format_content_write!(
+                cmd.output_format,
+                cmd.redirect_stdout.as_ref(),
+                Content::from(Reply::Diagnostics(x)),
+                Ok(x.to_string()),
+            )?;
+        }
+        client::Subcommand::PrintConfig(_) => {
+            let x = client
+                .ask_diagnostics(vec![DiagnosticSection::Config])
+                .await?;
             format_content_write!(
                 cmd.output_format,
                 cmd.redirect_stdout.as_ref(),
-                Content::from(Reply::InternalDebug(x)),
-                Ok(format!("{}", String::from_utf8_lossy(&x.output))),
+                Content::from(Reply::Diagnostics(x)),
+                Ok(x.to_string()),
             )?;
         }
+        client::Subcommand::History(_) => {
+            return Err("History cannot be run against the server".into());
+        }
     };
 
     Ok(())
@@ -377,7 +1201,7 @@ async fn execute_raw_content(
     input: &str,
     format: FormatOption,
 ) -> Result<Reply, Box<dyn std::error::Error>> {
-    let content: Content = format::convert_text(format, input)?;
+    let content: Content = diagnostic::decode(format, input, &[])?;
     match content {
         Content::Request(x) => Ok(client.ask(x).await?),
         x => Err(format!("Unexpected input: {:?}", x).into()),
@@ -396,7 +1220,7 @@ async fn execute_raw_content_and_metadata(
     Box<dyn std::error::Error>,
 > {
     let content_and_metadata: ContentAndMetadata =
-        format::convert_text(format, input)?;
+        diagnostic::decode(format, input, &["content"])?;
     match content_and_metadata {
         ContentAndMetadata {
             content: Content::Request(x),
@@ -406,6 +1230,41 @@ async fn execute_raw_content_and_metadata(
     }
 }
 
+/// Sends raw input as a request, reporting the reply unless
+/// `fire_and_forget` is true, in which case no reply is waited on
+async fn send_raw(
+    client: &mut ConnectedClient,
+    input: &str,
+    cmd: &client::raw::RawCommand,
+    fire_and_forget: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !fire_and_forget {
+        return execute_raw_and_report(
+            client,
+            input,
+            cmd.format,
+            cmd.format,
+            cmd.meta_mode,
+        )
+        .await;
+    }
+
+    let content = if cmd.meta_mode {
+        diagnostic::decode::<ContentAndMetadata>(
+            cmd.format,
+            input,
+            &["content"],
+        )?
+        .content
+    } else {
+        diagnostic::decode::<Content>(cmd.format, input, &[])?
+    };
+    match content {
+        Content::Request(x) => Ok(client.tell(x).await?),
+        x => Err(format!("Unexpected input: {:?}", x).into()),
+    }
+}
+
 async fn execute_raw_and_report(
     client: &mut ConnectedClient,
     input: &str,
@@ -414,7 +1273,7 @@ async fn execute_raw_and_report(
     meta_mode: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if meta_mode {
-        match execute_raw_content_and_metadata(client, &input, input_format)
+        match execute_raw_content_and_metadata(client, input, input_format)
             .await
         {
             Ok((result, metadata)) => format::format_println(
@@ -431,14 +1290,14 @@ async fn execute_raw_and_report(
             Err(x) => format::format_println(
                 output_format,
                 ContentAndMetadata {
-                    content: Content::from(Reply::from(x)),
+                    content: Content::from(raw_error_reply(x)),
                     metadata: HashMap::new(),
                 },
                 |_| Err("Unreachable".into()),
             ),
         }
     } else {
-        match execute_raw_content(client, &input, input_format).await {
+        match execute_raw_content(client, input, input_format).await {
             Ok(reply) => format::format_content_println(
                 output_format,
                 Content::from(reply),
@@ -446,25 +1305,167 @@ async fn execute_raw_and_report(
             ),
             Err(x) => format::format_content_println(
                 output_format,
-                Content::from(Reply::from(x)),
+                Content::from(raw_error_reply(x)),
                 |_| Err("Unreachable".into()),
             ),
         }
     }
 }
 
+/// Converts an error that prevented raw input from being sent into a reply,
+/// keeping the code of the error so that bad input can be told apart
+fn raw_error_reply(x: Box<dyn std::error::Error>) -> Reply {
+    Reply::Error(ReplyError::with_code(x.to_string(), error_code(&*x)))
+}
+
+/// Decodes each raw input of `cmd` without sending it, reporting the
+/// request that was decoded or a diagnostic explaining why it is invalid,
+/// including any fields outside of their bounds
+fn validate_raw(cmd: &client::raw::RawCommand) -> Result<(), Box<dyn Error>> {
+    let validate = |input: &str| {
+        let content = if cmd.meta_mode {
+            diagnostic::decode::<ContentAndMetadata>(
+                cmd.format,
+                input,
+                &["content"],
+            )
+            .map(|x| x.content)
+        } else {
+            diagnostic::decode::<Content>(cmd.format, input, &[])
+        };
+
+        // Bounds emitted into the schema are checked here too, so input the
+        // server would reject is caught without connecting
+        let content = match content {
+            Ok(Content::Request(x)) => match x.validate() {
+                Ok(()) => Content::Request(x),
+                Err(x) => Content::from(raw_error_reply(Box::new(x))),
+            },
+            Ok(x) => Content::from(raw_error_reply(
+                format!("Unexpected input: {:?}", x).into(),
+            )),
+            Err(x) => Content::from(raw_error_reply(Box::new(x))),
+        };
+
+        format::format_content_println(cmd.format, content, |_| {
+            Err("Unreachable".into())
+        })
+    };
+
+    if let Some(input) = &cmd.input {
+        validate(input)?;
+    }
+
+    if cmd.interactive {
+        let mut line = String::new();
+        while std::io::stdin().read_line(&mut line)? > 0 {
+            validate(&line)?;
+            line.clear();
+        }
+    }
+
+    Ok(())
+}
+
+/// Interval between polls of a proc's output
+const PROC_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Interval between asking for a proc's status in case the server does not
+/// push its exit
+const PROC_STATUS_FALLBACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Describes each change applied by a pass of a mirror
+fn mirror_pass_lines(pass: &MirrorPass) -> Vec<String> {
+    let changes = [
+        ("Pulled", &pass.pulled),
+        ("Pushed", &pass.pushed),
+        ("Removed locally", &pass.removed_local),
+        ("Removed remotely", &pass.removed_remote),
+        ("Conflict (changed on both sides)", &pass.conflicts),
+    ];
+
+    changes
+        .iter()
+        .flat_map(|(action, paths)| {
+            paths.iter().map(move |path| format!("{} {}", action, path))
+        })
+        .collect()
+}
+
+/// Describes what was copied by uploading or downloading a directory
+fn dir_copy_summary(action: &str, copy: &DirCopy) -> String {
+    format!(
+        "{} {} files ({} bytes) and {} dirs\n",
+        action,
+        copy.files.len(),
+        copy.bytes,
+        copy.dirs.len()
+    )
+}
+
+/// Relays stdin, stdout, and stderr of `proc` until it exits, killing it
+/// upon Ctrl-C or the deadline passing if `kill_on_interrupt` is true rather
+/// than leaving it running
+///
+/// Fails rather than continuing to poll if the server stops replying
+#[allow(clippy::too_many_arguments)]
 async fn process_proc(
-    mut client: ConnectedClient,
+    client: &mut ConnectedClient,
+    interrupt: Interrupt,
+    kill_on_interrupt: bool,
     send_stdin: bool,
+    pty: bool,
     stdout_path: Option<PathBuf>,
     stderr_path: Option<PathBuf>,
     post_exit_duration: Duration,
     proc: RemoteProc,
     format: FormatOption,
     exit_print: bool,
-) -> io::Result<()> {
-    let stdin = io::stdin();
+) -> Result<(), Box<dyn Error>> {
     let mut exit_instant: Option<Instant> = None;
+    let mut status_instant = Instant::now();
+
+    // With a pty, keypresses (including Ctrl-C) are passed along to the
+    // remote terminal as they happen rather than acted on locally
+    let mut raw_mode = if pty && send_stdin {
+        terminal::RawMode::enter()?
+    } else {
+        None
+    };
+    let mut pty_size = terminal::size();
+
+    // Read stdin on a separate thread so that waiting for input does not
+    // prevent relaying output or noticing Ctrl-C
+    let (stdin_tx, mut stdin_rx) = tokio::sync::mpsc::unbounded_channel();
+    if send_stdin && pty {
+        std::thread::spawn(move || {
+            use io::Read;
+            let mut buf = [0; 1024];
+            loop {
+                match io::stdin().read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) if stdin_tx.send(buf[..n].to_vec()).is_err() => break,
+                    Ok(_) => (),
+                    Err(x) => panic!("Failed to read input: {:?}", x),
+                }
+            }
+        });
+    } else if send_stdin {
+        std::thread::spawn(move || {
+            use io::BufRead;
+            let stdin = io::stdin();
+            let mut handle = stdin.lock();
+            loop {
+                let mut line = String::new();
+                match handle.read_line(&mut line) {
+                    Ok(0) => break,
+                    Ok(_) if stdin_tx.send(line.into_bytes()).is_err() => break,
+                    Ok(_) => (),
+                    Err(x) => panic!("Failed to read line of input: {:?}", x),
+                }
+            }
+        });
+    }
 
     // Continue running as long as we haven't exceeded our post-exit duration
     // after the remote process exited
@@ -472,28 +1473,30 @@ async fn process_proc(
         .map(|inst| inst.elapsed() < post_exit_duration)
         .unwrap_or(true)
     {
-        if send_stdin {
-            use io::BufRead;
-            let mut handle = stdin.lock();
-            let mut line = String::new();
-            match handle.read_line(&mut line) {
-                Ok(_) => (),
-                Err(x) if x.kind() == io::ErrorKind::WouldBlock => (),
-                Err(x) => panic!("Failed to read line of input: {:?}", x),
+        if let Err(x) = interrupt.check() {
+            if kill_on_interrupt && exit_instant.is_none() {
+                if let Err(x) = client.ask_proc_kill(&proc).await {
+                    warn!("Failed to kill proc {}: {}", proc.id, x);
+                }
             }
+            return Err(x.into());
+        }
 
-            if !line.is_empty() {
-                client
-                    .ask_write_proc_stdin(&proc, &line.into_bytes())
-                    .await
-                    .expect("Failed to write stdin");
+        while let Ok(input) = stdin_rx.try_recv() {
+            client.ask_write_proc_stdin(&proc, &input).await?;
+        }
+
+        if pty && exit_instant.is_none() {
+            let size = terminal::size();
+            if size != pty_size {
+                if let Some((rows, cols)) = size {
+                    client.ask_resize_pty(&proc, rows, cols).await?;
+                }
+                pty_size = size;
             }
         }
 
-        let stdout_args = client
-            .ask_read_proc_stdout(&proc)
-            .await
-            .expect("Failed to get stdout");
+        let stdout_args = client.ask_read_proc_stdout(&proc).await?;
         if !stdout_args.output.is_empty() {
             format_content_write!(
                 format,
@@ -504,10 +1507,7 @@ async fn process_proc(
             .expect("Failed to format stdout");
         }
 
-        let stderr_args = client
-            .ask_read_proc_stderr(&proc)
-            .await
-            .expect("Failed to get stderr");
+        let stderr_args = client.ask_read_proc_stderr(&proc).await?;
         if !stderr_args.output.is_empty() {
             format_content_write!(
                 format,
@@ -521,13 +1521,23 @@ async fn process_proc(
             .expect("Failed to format stderr");
         }
 
-        // Mark ready for exit if proc has exited
+        // Mark ready for exit if proc has exited, which the server pushes
+        // to us, only asking for the status occasionally in case it did not
         if exit_instant.is_none() {
-            let status = client
-                .ask_read_proc_status(&proc)
-                .await
-                .expect("Failed to get proc status");
-            if !status.is_alive {
+            let status = match client.proc_exit(&proc).await {
+                Some(status) => Some(status),
+                None if status_instant.elapsed()
+                    >= PROC_STATUS_FALLBACK_INTERVAL =>
+                {
+                    status_instant = Instant::now();
+                    Some(client.ask_read_proc_status(&proc).await?)
+                }
+                None => None,
+            };
+            if let Some(status) = status.filter(|s| !s.is_alive) {
+                // Restored before reporting the exit so it is printed as
+                // usual rather than in raw mode
+                drop(raw_mode.take());
                 match format {
                     FormatOption::Human if exit_print => format_content_write!(
                         format,
@@ -551,6 +1561,8 @@ async fn process_proc(
                 exit_instant = Some(Instant::now());
             }
         }
+
+        tokio::time::delay_for(PROC_POLL_INTERVAL).await;
     }
 
     Ok(())
@@ -579,6 +1591,39 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::CapabilitiesRequest => {
                     crate::core::request::CapabilitiesArgs::schema()
                 }
+                SchemaType::TimeInfoRequest => {
+                    crate::core::request::TimeInfoArgs::schema()
+                }
+                SchemaType::IdentifyRequest => {
+                    crate::core::request::IdentifyArgs::schema()
+                }
+                SchemaType::UpdateTrustedClientsRequest => {
+                    crate::core::request::UpdateTrustedClientsArgs::schema()
+                }
+                SchemaType::HandshakeRequest => {
+                    crate::core::request::HandshakeArgs::schema()
+                }
+                SchemaType::SealedRequest => {
+                    crate::core::request::SealedArgs::schema()
+                }
+                SchemaType::PushConfigRequest => {
+                    crate::core::request::PushConfigArgs::schema()
+                }
+                SchemaType::GetConfigRequest => {
+                    String::from("{}")
+                }
+                SchemaType::UpdateWebhooksRequest => {
+                    crate::core::request::UpdateWebhooksArgs::schema()
+                }
+                SchemaType::PowerControlRequest => {
+                    crate::core::request::PowerControlArgs::schema()
+                }
+                SchemaType::ExportStateRequest => {
+                    crate::core::request::ExportStateArgs::schema()
+                }
+                SchemaType::ImportStateRequest => {
+                    crate::core::request::ImportStateArgs::schema()
+                }
                 SchemaType::CreateDirRequest => {
                     crate::core::request::CreateDirArgs::schema()
                 }
@@ -588,9 +1633,30 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::RemoveDirRequest => {
                     crate::core::request::RemoveDirArgs::schema()
                 }
+                SchemaType::CreatePathsRequest => {
+                    crate::core::request::CreatePathsArgs::schema()
+                }
                 SchemaType::ListDirContentsRequest => {
                     crate::core::request::ListDirContentsArgs::schema()
                 }
+                SchemaType::ResolvePathRequest => {
+                    crate::core::request::ResolvePathArgs::schema()
+                }
+                SchemaType::SniffFileRequest => {
+                    crate::core::request::SniffFileArgs::schema()
+                }
+                SchemaType::GetFileChecksumRequest => {
+                    crate::core::request::GetFileChecksumArgs::schema()
+                }
+                SchemaType::DiffFilesRequest => {
+                    crate::core::request::DiffFilesArgs::schema()
+                }
+                SchemaType::RecentFsEventsRequest => {
+                    crate::core::request::RecentFsEventsArgs::schema()
+                }
+                SchemaType::ListMountsRequest => {
+                    crate::core::request::ListMountsArgs::schema()
+                }
                 SchemaType::OpenFileRequest => {
                     crate::core::request::OpenFileArgs::schema()
                 }
@@ -612,12 +1678,36 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::ReadFileRequest => {
                     crate::core::request::ReadFileArgs::schema()
                 }
+                SchemaType::ReadFilesRequest => {
+                    crate::core::request::ReadFilesArgs::schema()
+                }
                 SchemaType::WriteFileRequest => {
                     crate::core::request::WriteFileArgs::schema()
                 }
+                SchemaType::WriteFileAtomicByPathRequest => {
+                    crate::core::request::WriteFileAtomicByPathArgs::schema()
+                }
+                SchemaType::PatchFileLinesRequest => {
+                    crate::core::request::PatchFileLinesArgs::schema()
+                }
+                SchemaType::UploadManifestRequest => {
+                    crate::core::request::UploadManifestArgs::schema()
+                }
+                SchemaType::WriteUploadChunkRequest => {
+                    crate::core::request::WriteUploadChunkArgs::schema()
+                }
                 SchemaType::ExecProcRequest => {
                     crate::core::request::ExecProcArgs::schema()
                 }
+                SchemaType::ExecScriptRequest => {
+                    crate::core::request::ExecScriptArgs::schema()
+                }
+                SchemaType::ExecProcPtyRequest => {
+                    crate::core::request::ExecProcPtyArgs::schema()
+                }
+                SchemaType::ResizePtyRequest => {
+                    crate::core::request::ResizePtyArgs::schema()
+                }
                 SchemaType::WriteProcStdinRequest => {
                     crate::core::request::WriteProcStdinArgs::schema()
                 }
@@ -633,20 +1723,65 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::ReadProcStatusRequest => {
                     crate::core::request::ReadProcStatusArgs::schema()
                 }
+                SchemaType::ReadProcTreeRequest => {
+                    crate::core::request::ReadProcTreeArgs::schema()
+                }
+                SchemaType::ReadProcResourcesRequest => {
+                    crate::core::request::ReadProcResourcesArgs::schema()
+                }
+                SchemaType::ReadProcInfoRequest => {
+                    crate::core::request::ReadProcInfoArgs::schema()
+                }
+                SchemaType::ListProcsRequest => {
+                    crate::core::request::ListProcsArgs::schema()
+                }
+                SchemaType::SubmitJobRequest => {
+                    crate::core::request::SubmitJobArgs::schema()
+                }
+                SchemaType::QueryJobRequest => {
+                    crate::core::request::QueryJobArgs::schema()
+                }
+                SchemaType::CollectJobOutputRequest => {
+                    crate::core::request::CollectJobOutputArgs::schema()
+                }
+                SchemaType::CreateScheduleRequest => {
+                    crate::core::request::CreateScheduleArgs::schema()
+                }
+                SchemaType::DeleteScheduleRequest => {
+                    crate::core::request::DeleteScheduleArgs::schema()
+                }
+                SchemaType::AppendLogRequest => {
+                    crate::core::request::AppendLogArgs::schema()
+                }
+                SchemaType::ReadLogRangeRequest => {
+                    crate::core::request::ReadLogRangeArgs::schema()
+                }
                 SchemaType::SequenceRequest => {
                     crate::core::request::SequenceArgs::schema()
                 }
                 SchemaType::BatchRequest => {
                     crate::core::request::BatchArgs::schema()
                 }
+                SchemaType::TransactionRequest => {
+                    crate::core::request::TransactionArgs::schema()
+                }
                 SchemaType::ForwardRequest => {
                     crate::core::request::ForwardArgs::schema()
                 }
                 SchemaType::CustomRequest => {
                     crate::core::request::CustomArgs::schema()
                 }
-                SchemaType::InternalDebugRequest => {
-                    crate::core::request::InternalDebugArgs::schema()
+                SchemaType::DiagnosticsRequest => {
+                    crate::core::request::DiagnosticsArgs::schema()
+                }
+                SchemaType::ListConnectionsRequest => {
+                    crate::core::request::ListConnectionsArgs::schema()
+                }
+                SchemaType::ListPendingEvictionsRequest => {
+                    crate::core::request::ListPendingEvictionsArgs::schema()
+                }
+                SchemaType::BindOriginRequest => {
+                    crate::core::request::BindOriginArgs::schema()
                 }
                 SchemaType::HeartbeatReply => {
                     String::from("{}")
@@ -657,6 +1792,39 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::CapabilitiesReply => {
                     crate::core::reply::CapabilitiesArgs::schema()
                 }
+                SchemaType::TimeInfoReply => {
+                    crate::core::reply::TimeInfoArgs::schema()
+                }
+                SchemaType::IdentifyReply => {
+                    crate::core::reply::IdentityArgs::schema()
+                }
+                SchemaType::UpdateTrustedClientsReply => {
+                    crate::core::reply::TrustedClientsArgs::schema()
+                }
+                SchemaType::HandshakeReply => {
+                    crate::core::reply::HandshakeArgs::schema()
+                }
+                SchemaType::SealedReply => {
+                    crate::core::reply::SealedArgs::schema()
+                }
+                SchemaType::PushConfigReply => {
+                    crate::core::reply::ConfigPushedArgs::schema()
+                }
+                SchemaType::GetConfigReply => {
+                    crate::core::reply::ConfigArgs::schema()
+                }
+                SchemaType::UpdateWebhooksReply => {
+                    crate::core::reply::WebhooksArgs::schema()
+                }
+                SchemaType::PowerControlReply => {
+                    crate::core::reply::PowerControlArgs::schema()
+                }
+                SchemaType::ExportStateReply => {
+                    crate::core::reply::StateExportedArgs::schema()
+                }
+                SchemaType::ImportStateReply => {
+                    crate::core::reply::StateImportedArgs::schema()
+                }
                 SchemaType::CreateDirReply => {
                     crate::core::reply::DirCreatedArgs::schema()
                 }
@@ -666,9 +1834,33 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::RemoveDirReply => {
                     crate::core::reply::DirRemovedArgs::schema()
                 }
+                SchemaType::CreatePathsReply => {
+                    crate::core::reply::PathsCreatedArgs::schema()
+                }
                 SchemaType::ListDirContentsReply => {
                     crate::core::reply::DirContentsListArgs::schema()
                 }
+                SchemaType::ListDirContentsChunkReply => {
+                    crate::core::reply::DirContentsListChunkArgs::schema()
+                }
+                SchemaType::ResolvePathReply => {
+                    crate::core::reply::PathResolvedArgs::schema()
+                }
+                SchemaType::SniffFileReply => {
+                    crate::core::reply::FileSniffedArgs::schema()
+                }
+                SchemaType::GetFileChecksumReply => {
+                    crate::core::reply::FileChecksumArgs::schema()
+                }
+                SchemaType::DiffFilesReply => {
+                    crate::core::reply::FilesDiffedArgs::schema()
+                }
+                SchemaType::RecentFsEventsReply => {
+                    crate::core::reply::FsEventsArgs::schema()
+                }
+                SchemaType::ListMountsReply => {
+                    crate::core::reply::MountsListArgs::schema()
+                }
                 SchemaType::OpenFileReply => {
                     crate::core::reply::FileOpenedArgs::schema()
                 }
@@ -690,15 +1882,33 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::ReadFileReply => {
                     crate::core::reply::FileContentsArgs::schema()
                 }
+                SchemaType::ReadFilesReply => {
+                    crate::core::reply::FilesContentsArgs::schema()
+                }
                 SchemaType::WriteFileReply => {
                     crate::core::reply::FileWrittenArgs::schema()
                 }
+                SchemaType::WriteFileAtomicByPathReply => {
+                    crate::core::reply::AtomicFileWrittenArgs::schema()
+                }
+                SchemaType::PatchFileLinesReply => {
+                    crate::core::reply::FileLinesPatchedArgs::schema()
+                }
+                SchemaType::UploadManifestReply => {
+                    crate::core::reply::UploadManifestPreparedArgs::schema()
+                }
+                SchemaType::WriteUploadChunkReply => {
+                    crate::core::reply::UploadChunkWrittenArgs::schema()
+                }
                 SchemaType::ExecProcReply => {
                     crate::core::reply::ProcStartedArgs::schema()
                 }
                 SchemaType::WriteProcStdinReply => {
                     crate::core::reply::ProcStdinWrittenArgs::schema()
                 }
+                SchemaType::ResizePtyReply => {
+                    crate::core::reply::PtyResizedArgs::schema()
+                }
                 SchemaType::ReadProcStdoutReply => {
                     crate::core::reply::ProcStdoutContentsArgs::schema()
                 }
@@ -711,6 +1921,42 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::ReadProcStatusReply => {
                     crate::core::reply::ProcStatusArgs::schema()
                 }
+                SchemaType::ReadProcTreeReply => {
+                    crate::core::reply::ProcTreeArgs::schema()
+                }
+                SchemaType::ReadProcResourcesReply => {
+                    crate::core::reply::ProcResourcesArgs::schema()
+                }
+                SchemaType::ReadProcInfoReply => {
+                    crate::core::reply::ProcInfoArgs::schema()
+                }
+                SchemaType::ListProcsReply => {
+                    crate::core::reply::ProcsListArgs::schema()
+                }
+                SchemaType::SubmitJobReply => {
+                    crate::core::reply::JobSubmittedArgs::schema()
+                }
+                SchemaType::QueryJobReply => {
+                    crate::core::reply::JobStatusArgs::schema()
+                }
+                SchemaType::CollectJobOutputReply => {
+                    crate::core::reply::JobOutputArgs::schema()
+                }
+                SchemaType::CreateScheduleReply => {
+                    crate::core::reply::ScheduleCreatedArgs::schema()
+                }
+                SchemaType::ListSchedulesReply => {
+                    crate::core::reply::SchedulesListArgs::schema()
+                }
+                SchemaType::DeleteScheduleReply => {
+                    crate::core::reply::ScheduleDeletedArgs::schema()
+                }
+                SchemaType::AppendLogReply => {
+                    crate::core::reply::LogAppendedArgs::schema()
+                }
+                SchemaType::ReadLogRangeReply => {
+                    crate::core::reply::LogRangeArgs::schema()
+                }
                 SchemaType::ErrorReply => {
                     crate::core::reply::ReplyError::schema()
                 }
@@ -723,23 +1969,68 @@ async fn run_schema(cmd: SchemaSubcommand) -> Result<(), Box<dyn Error>> {
                 SchemaType::FileSigChanged => {
                     crate::core::reply::FileSigChangedArgs::schema()
                 }
+                SchemaType::PreconditionFailed => {
+                    crate::core::reply::PreconditionFailedArgs::schema()
+                }
+                SchemaType::QuotaExceeded => {
+                    crate::core::reply::QuotaExceededArgs::schema()
+                }
+                SchemaType::ErrorCode => {
+                    crate::core::reply::ErrorCode::schema()
+                }
                 SchemaType::SequenceReply => {
                     crate::core::reply::SequenceArgs::schema()
                 }
                 SchemaType::BatchReply => {
                     crate::core::reply::BatchArgs::schema()
                 }
+                SchemaType::BatchResultReply => {
+                    crate::core::reply::BatchResultArgs::schema()
+                }
+                SchemaType::TransactionReply => {
+                    crate::core::reply::TransactionArgs::schema()
+                }
                 SchemaType::ForwardReply => {
                     crate::core::reply::ForwardArgs::schema()
                 }
                 SchemaType::CustomReply => {
                     crate::core::reply::CustomArgs::schema()
                 }
-                SchemaType::InternalDebugReply => {
-                    crate::core::reply::InternalDebugArgs::schema()
+                SchemaType::DiagnosticsReply => {
+                    crate::core::reply::DiagnosticsArgs::schema()
+                }
+                SchemaType::ListConnectionsReply => {
+                    crate::core::reply::ConnectionsListArgs::schema()
+                }
+                SchemaType::ListPendingEvictionsReply => {
+                    crate::core::reply::PendingEvictionsArgs::schema()
+                }
+                SchemaType::BindOriginReply => {
+                    crate::core::reply::OriginBindingArgs::schema()
+                }
+                SchemaType::ServerShuttingDownReply => {
+                    crate::core::reply::ServerShuttingDownArgs::schema()
+                }
+                SchemaType::UnsupportedReply => {
+                    crate::core::reply::UnsupportedArgs::schema()
                 }
             }
         ),
+        SchemaSubcommand::Wire => println!(
+            "{}",
+            serde_json::to_string_pretty(
+                &crate::core::transport::WireSpec::generate()
+            )?
+        ),
+        SchemaSubcommand::Export => println!(
+            "{}",
+            serde_json::to_string_pretty(&compat::ExportedSchemas::current())?
+        ),
+        SchemaSubcommand::Diff(diff) => {
+            let old: compat::ExportedSchemas =
+                serde_json::from_slice(&tokio::fs::read(&diff.path).await?)?;
+            println!("{}", compat::ExportedSchemas::current().diff(&old));
+        }
     };
 
     Ok(())
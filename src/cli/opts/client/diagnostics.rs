@@ -0,0 +1,18 @@
+use crate::core::request::DiagnosticSection;
+use clap::Clap;
+use serde::{Deserialize, Serialize};
+
+/// Retrieve diagnostics about the state of the server
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct DiagnosticsCommand {
+    /// Section of diagnostics to retrieve (state_counters, config, tasks,
+    /// buffers, or transfers), where all sections are retrieved if none are
+    /// provided; can be provided multiple times
+    #[clap(long = "section", number_of_values = 1, parse(try_from_str))]
+    pub sections: Vec<DiagnosticSection>,
+}
+
+/// Print the configuration the server is running with after defaults and
+/// overrides are applied
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct PrintConfigCommand {}
@@ -1,19 +1,28 @@
 use clap::Clap;
+use serde::{Deserialize, Serialize};
 
 /// List files and directories at the root of the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct ListRootDirCommand {}
 
 /// List files and directories at the specified path
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct ListDirCommand {
     /// Path to the directory whose contents to list
     #[clap(parse(try_from_str))]
     pub path: String,
 }
 
+/// Lists the filesystems mounted on the server (or its drives on Windows)
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct ListMountsCommand {
+    /// If provided, also lists pseudo filesystems such as proc and sysfs
+    #[clap(short, long)]
+    pub all: bool,
+}
+
 /// Creates a directory at the specified path on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct CreateDirCommand {
     /// Path to the directory to create
     #[clap(parse(try_from_str))]
@@ -22,10 +31,29 @@ pub struct CreateDirCommand {
     /// If provided, will make parent directories as needed
     #[clap(short, long)]
     pub parents: bool,
+
+    /// If provided, permission bits (in octal) to apply to each created
+    /// directory instead of the server's default
+    #[clap(long, parse(try_from_str = crate::cli::opts::parsers::parse_mode))]
+    pub mode: Option<u32>,
+}
+
+/// Creates many directories and empty files on the server at once, along
+/// with any of their missing parent directories
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct MakePathsCommand {
+    /// Paths to the directories to create
+    #[clap(parse(try_from_str))]
+    pub dirs: Vec<String>,
+
+    /// Path to an empty file to create after the directories, which is left
+    /// untouched if it already exists
+    #[clap(long = "file", parse(try_from_str))]
+    pub files: Vec<String>,
 }
 
 /// Moves a directory at the specified path on the server to the new path
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct MoveDirCommand {
     /// Origin path of the directory to move
     #[clap(parse(try_from_str))]
@@ -37,7 +65,7 @@ pub struct MoveDirCommand {
 }
 
 /// Removes a directory at the specified path on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct RemoveDirCommand {
     /// Path of the directory to remove
     #[clap(parse(try_from_str))]
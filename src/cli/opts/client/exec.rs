@@ -1,9 +1,12 @@
-use crate::cli::opts::parsers;
+use crate::cli::opts::{parsers, types};
 use clap::Clap;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::time::Duration;
+use strum::VariantNames;
 
 /// Executes a process on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct ExecCommand {
     /// The command to execute
     #[clap(parse(try_from_str))]
@@ -17,6 +20,12 @@ pub struct ExecCommand {
     #[clap(long)]
     pub no_stdin: bool,
 
+    /// If provided, sends the contents of this local file as the entire
+    /// stdin of the remote process along with the request to execute it,
+    /// rather than sending stdin from this process
+    #[clap(long, parse(from_os_str))]
+    pub stdin_file: Option<PathBuf>,
+
     /// Whether or not to detach the client from the remote process, thereby
     /// not terminating the process if the client disconnects
     #[clap(short, long)]
@@ -26,6 +35,43 @@ pub struct ExecCommand {
     #[clap(long)]
     pub current_dir: Option<String>,
 
+    /// Label attached to the new process, used to find it when listing
+    /// processes; can be provided multiple times
+    #[clap(long = "label", number_of_values = 1)]
+    pub labels: Vec<String>,
+
+    /// If provided, file mode creation mask (in octal) for the new process
+    #[clap(long, parse(try_from_str = parsers::parse_mode))]
+    pub umask: Option<u32>,
+
+    /// Whether or not to run the process attached to a pseudo-terminal on
+    /// the server, sized like the local terminal, so interactive programs
+    /// behave as if run locally; stdin is relayed keypress by keypress and
+    /// stderr is merged into stdout, while io mode and newline are ignored
+    /// and umask and a stdin file are not supported
+    #[clap(long)]
+    pub pty: bool,
+
+    /// How the server splits output of the process into chunks: raw passes
+    /// output along as it arrives while line only passes complete lines
+    #[clap(
+        long,
+        parse(try_from_str),
+        possible_values = &types::ProcIoMode::VARIANTS,
+        default_value = types::ProcIoMode::Raw.as_ref(),
+    )]
+    pub io_mode: types::ProcIoMode,
+
+    /// In line mode, converts line endings of input and output of the
+    /// process to lf or crlf rather than keeping them as they are
+    #[clap(
+        long,
+        parse(try_from_str),
+        possible_values = &types::Newline::VARIANTS,
+        default_value = types::Newline::Keep.as_ref(),
+    )]
+    pub newline: types::Newline,
+
     /// The time (in milliseconds) to wait after a process exits (or is killed)
     /// to receive lingering stdout/stderr before closing the remote connection
     #[clap(
@@ -37,7 +83,7 @@ pub struct ExecCommand {
 }
 
 /// Reattaches to a running program on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct ReattachExecCommand {
     /// The id of the remote process to connect to
     #[clap(parse(try_from_str))]
@@ -47,6 +93,12 @@ pub struct ReattachExecCommand {
     #[clap(long)]
     pub no_stdin: bool,
 
+    /// Whether or not the remote process was started with a pseudo-terminal,
+    /// relaying stdin keypress by keypress and changes to the size of the
+    /// local terminal
+    #[clap(long)]
+    pub pty: bool,
+
     /// The time (in milliseconds) to wait after a process exits (or is killed)
     /// to receive lingering stdout/stderr before closing the remote connection
     #[clap(
@@ -1,7 +1,12 @@
+use crate::cli::opts::{parsers, types};
 use clap::Clap;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use strum::VariantNames;
 
 /// Writes a file on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct WriteFileCommand {
     /// Path to the file
     #[clap(parse(try_from_str))]
@@ -10,18 +15,68 @@ pub struct WriteFileCommand {
     /// Content to write to the file
     #[clap(parse(try_from_str))]
     pub contents: String,
+
+    /// If provided, will add the content to the end of the file instead of
+    /// replacing the contents of the file
+    #[clap(long)]
+    #[serde(default)]
+    pub append: bool,
+
+    /// If provided, will fail if the file already exists
+    #[clap(long)]
+    #[serde(default)]
+    pub create_new: bool,
 }
 
 /// Reads a file on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct ReadFileCommand {
     /// Path to the file
     #[clap(parse(try_from_str))]
     pub path: String,
 }
 
+/// Compares two files on the server, or a file on the server against a local
+/// file, printing the unified diff between them
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct DiffFileCommand {
+    /// Path to the original file on the server
+    #[clap(parse(try_from_str))]
+    pub path_a: String,
+
+    /// Path to the changed file on the server, or on this machine if local
+    #[clap(parse(try_from_str))]
+    pub path_b: String,
+
+    /// Whether or not the changed file is on this machine, in which case its
+    /// contents are sent to the server to be compared
+    #[clap(long)]
+    pub local: bool,
+
+    /// The number of unchanged lines to include around each change
+    #[clap(long, default_value = "3")]
+    pub context_lines: u32,
+}
+
+/// Computes the checksum of a file on the server, printing its digest
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct ChecksumFileCommand {
+    /// Path to the file on the server
+    #[clap(parse(try_from_str))]
+    pub path: String,
+
+    /// Algorithm used to compute the checksum
+    #[clap(
+        long,
+        parse(try_from_str),
+        possible_values = &types::ChecksumAlgorithm::VARIANTS,
+        default_value = types::ChecksumAlgorithm::Sha256.as_ref(),
+    )]
+    pub algorithm: types::ChecksumAlgorithm,
+}
+
 /// Moves a file at the specified path on the server to the new path
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct MoveFileCommand {
     /// Origin path of the file to move
     #[clap(parse(try_from_str))]
@@ -33,9 +88,120 @@ pub struct MoveFileCommand {
 }
 
 /// Removes a file at the specified path on the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct RemoveFileCommand {
     /// Path of the file to remove
     #[clap(parse(try_from_str))]
     pub path: String,
 }
+
+/// Uploads local files into a directory on the server using a single manifest
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct UploadFilesCommand {
+    /// Path to the directory on the server to upload the files into
+    #[clap(parse(try_from_str))]
+    pub destination: String,
+
+    /// Paths to the local files to upload
+    #[clap(parse(from_os_str), required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+/// Downloads files on the server into a local directory, skipping any file
+/// whose local copy is already up-to-date
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct DownloadFilesCommand {
+    /// Path to the local directory to download the files into
+    #[clap(parse(from_os_str))]
+    pub destination: PathBuf,
+
+    /// Paths to the files on the server to download
+    #[clap(parse(try_from_str), required = true)]
+    pub files: Vec<String>,
+
+    /// The maximum number of chunks of a file requested at once, where more
+    /// chunks in flight improve throughput over high-latency links
+    #[clap(long, default_value = "8")]
+    pub window: usize,
+
+    /// The number of times a chunk is requested again after its request
+    /// times out or fails to be sent
+    #[clap(long, default_value = "3")]
+    #[serde(default)]
+    pub retries: usize,
+}
+
+/// Uploads a local directory to the server, creating every dir within it
+/// and replacing every file within it
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct UploadDirCommand {
+    /// Path to the local directory to upload
+    #[clap(parse(from_os_str))]
+    pub local_dir: PathBuf,
+
+    /// Path to the directory on the server to upload into
+    #[clap(parse(try_from_str))]
+    pub remote_dir: String,
+}
+
+/// Downloads a directory on the server, creating every dir within it locally
+/// and replacing every local file within it
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct DownloadDirCommand {
+    /// Path to the directory on the server to download
+    #[clap(parse(try_from_str))]
+    pub remote_dir: String,
+
+    /// Path to the local directory to download into
+    #[clap(parse(from_os_str))]
+    pub local_dir: PathBuf,
+
+    /// The maximum number of chunks of a file requested at once
+    #[clap(long, default_value = "8")]
+    pub window: usize,
+
+    /// The number of times a chunk is requested again after its request
+    /// times out or fails to be sent
+    #[clap(long, default_value = "3")]
+    pub retries: usize,
+}
+
+/// Mirrors a directory on the server into a local directory, pulling the
+/// changes made on the server until interrupted
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct MirrorCommand {
+    /// Path to the directory on the server to mirror
+    #[clap(parse(try_from_str))]
+    pub remote_dir: String,
+
+    /// Path to the local directory to mirror into
+    #[clap(parse(from_os_str))]
+    pub local_dir: PathBuf,
+
+    /// If provided, also pushes the changes made to the local directory to
+    /// the server
+    #[clap(long)]
+    pub two_way: bool,
+
+    /// If provided, brings the directories in sync once and exits
+    #[clap(long)]
+    pub once: bool,
+
+    /// The time (in milliseconds) to wait between bringing the directories
+    /// in sync, which is at least 100
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_duration_millis),
+        default_value = "1000"
+    )]
+    pub interval: Duration,
+
+    /// The maximum number of chunks of a file requested at once
+    #[clap(long, default_value = "8")]
+    pub window: usize,
+
+    /// The number of times a chunk is requested again after its request
+    /// times out or fails to be sent
+    #[clap(long, default_value = "3")]
+    pub retries: usize,
+}
@@ -0,0 +1,29 @@
+use clap::Clap;
+use serde::{Deserialize, Serialize};
+
+/// Lists or replays operations recorded in the local client journal
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryCommand {
+    /// If provided, will re-run the journaled operation with the given id
+    /// against the address provided to the client
+    #[clap(long)]
+    pub replay: Option<u32>,
+
+    /// If provided, will only list operations made against the given
+    /// address (<host>:<port>)
+    #[clap(long)]
+    pub target: Option<String>,
+
+    /// If provided, will only list operations of the given subcommand
+    /// (e.g. write-file)
+    #[clap(long)]
+    pub command: Option<String>,
+
+    /// If provided, will only list operations that failed
+    #[clap(long)]
+    pub failed: bool,
+
+    /// If provided, will only list the most recent N operations
+    #[clap(long)]
+    pub limit: Option<usize>,
+}
@@ -1,5 +0,0 @@
-use clap::Clap;
-
-/// Retrieve internal debug information from the server (only if enabled)
-#[derive(Clap, Debug)]
-pub struct InternalDebugCommand {}
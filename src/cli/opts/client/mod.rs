@@ -1,18 +1,22 @@
 pub mod capabilities;
+pub mod diagnostics;
 pub mod dir;
 pub mod exec;
 pub mod file;
-pub mod internal_debug;
+pub mod history;
 pub mod raw;
 pub mod version;
 
-use super::CommonOpts;
+use super::{parsers, types, CommonOpts};
 use crate::cli::format::FormatOption;
+use crate::core::Preset;
 use clap::Clap;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 use strum::VariantNames;
 
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub enum Subcommand {
     /// Prints the version of the server
     #[clap(name = "version")]
@@ -30,10 +34,18 @@ pub enum Subcommand {
     #[clap(name = "ls-dir")]
     ListDir(dir::ListDirCommand),
 
+    /// Lists the filesystems mounted on the server
+    #[clap(name = "ls-mounts")]
+    ListMounts(dir::ListMountsCommand),
+
     /// Creates a remote directory
     #[clap(name = "mk-dir")]
     CreateDir(dir::CreateDirCommand),
 
+    /// Creates many remote directories and empty files at once
+    #[clap(name = "mk-paths")]
+    MakePaths(dir::MakePathsCommand),
+
     /// Moves a remote directory
     #[clap(name = "mv-dir")]
     MoveDir(dir::MoveDirCommand),
@@ -50,6 +62,14 @@ pub enum Subcommand {
     #[clap(name = "read-file")]
     ReadFile(file::ReadFileCommand),
 
+    /// Compares two remote files, or a remote file against a local file
+    #[clap(name = "diff-file")]
+    DiffFile(file::DiffFileCommand),
+
+    /// Computes the checksum of a remote file
+    #[clap(name = "checksum-file")]
+    ChecksumFile(file::ChecksumFileCommand),
+
     /// Moves a remote file
     #[clap(name = "mv-file")]
     MoveFile(file::MoveFileCommand),
@@ -58,6 +78,26 @@ pub enum Subcommand {
     #[clap(name = "rm-file")]
     RemoveFile(file::RemoveFileCommand),
 
+    /// Uploads local files into a remote directory
+    #[clap(name = "upload")]
+    Upload(file::UploadFilesCommand),
+
+    /// Downloads remote files into a local directory
+    #[clap(name = "download")]
+    Download(file::DownloadFilesCommand),
+
+    /// Uploads a local directory and everything within it to the server
+    #[clap(name = "upload-dir")]
+    UploadDir(file::UploadDirCommand),
+
+    /// Downloads a remote directory and everything within it
+    #[clap(name = "download-dir")]
+    DownloadDir(file::DownloadDirCommand),
+
+    /// Mirrors a remote directory into a local directory
+    #[clap(name = "mirror")]
+    Mirror(file::MirrorCommand),
+
     /// Executes a process remotely
     #[clap(name = "exec")]
     Exec(exec::ExecCommand),
@@ -71,9 +111,66 @@ pub enum Subcommand {
     #[clap(name = "raw")]
     Raw(raw::RawCommand),
 
-    /// Internal debugging support against the server
-    #[clap(name = "internal-debug")]
-    InternalDebug(internal_debug::InternalDebugCommand),
+    /// Retrieves diagnostics about the state of the server
+    #[clap(name = "diagnostics")]
+    Diagnostics(diagnostics::DiagnosticsCommand),
+
+    /// Prints the configuration the server is running with
+    #[clap(name = "print-config")]
+    PrintConfig(diagnostics::PrintConfigCommand),
+
+    /// Lists or replays operations recorded in the local client journal
+    #[clap(name = "history")]
+    History(history::HistoryCommand),
+}
+
+impl Subcommand {
+    /// Returns the name of the subcommand as provided on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Version(_) => "version",
+            Self::Capabilities(_) => "capabilities",
+            Self::ListRootDir(_) => "ls-root-dir",
+            Self::ListDir(_) => "ls-dir",
+            Self::ListMounts(_) => "ls-mounts",
+            Self::CreateDir(_) => "mk-dir",
+            Self::MakePaths(_) => "mk-paths",
+            Self::MoveDir(_) => "mv-dir",
+            Self::RemoveDir(_) => "rm-dir",
+            Self::WriteFile(_) => "write-file",
+            Self::ReadFile(_) => "read-file",
+            Self::DiffFile(_) => "diff-file",
+            Self::ChecksumFile(_) => "checksum-file",
+            Self::MoveFile(_) => "mv-file",
+            Self::RemoveFile(_) => "rm-file",
+            Self::Upload(_) => "upload",
+            Self::Download(_) => "download",
+            Self::UploadDir(_) => "upload-dir",
+            Self::DownloadDir(_) => "download-dir",
+            Self::Mirror(_) => "mirror",
+            Self::Exec(_) => "exec",
+            Self::ReattachExec(_) => "reattach",
+            Self::Raw(_) => "raw",
+            Self::Diagnostics(_) => "diagnostics",
+            Self::PrintConfig(_) => "print-config",
+            Self::History(_) => "history",
+        }
+    }
+}
+
+/// Help text of the preset option, listing the settings of each preset
+fn preset_help() -> &'static str {
+    let mut help = String::from(
+        "If provided, applies client settings tuned for a kind of network, \
+         overriding the timeout, packet ttl, buffer, and max outstanding asks",
+    );
+    for preset in Preset::ALL.iter() {
+        help.push_str("\n\n");
+        help.push_str(&preset.describe());
+    }
+
+    // NOTE: Help text must outlive the app, which is built once per run
+    Box::leak(help.into_boxed_str())
 }
 
 /// Perform some operation as the client to some remote server instance
@@ -90,6 +187,16 @@ pub struct ClientCommand {
     #[clap(short = "6", long)]
     pub ipv6: bool,
 
+    /// If provided, applies client settings tuned for a kind of network,
+    /// overriding the timeout, packet ttl, buffer, and max outstanding asks
+    #[clap(
+        long,
+        parse(try_from_str),
+        possible_values = &types::Preset::VARIANTS,
+        long_about = preset_help(),
+    )]
+    pub preset: Option<types::Preset>,
+
     /// Specifies the format of output from the client
     #[clap(
         short, 
@@ -116,6 +223,109 @@ pub struct ClientCommand {
     #[clap(long)]
     pub redirect_stderr: Option<PathBuf>,
 
+    /// If provided, will record operations to and read history from the
+    /// journal at the specified path instead of the default location
+    #[clap(long)]
+    pub journal: Option<PathBuf>,
+
+    /// If provided, will not record the operation to the journal
+    #[clap(long)]
+    pub no_journal: bool,
+
+    /// Maximum number of requests that can await a reply from the server at
+    /// once before further requests wait
+    #[clap(long, default_value = "1000")]
+    pub max_outstanding_asks: usize,
+
+    /// Hex-encoded public identity key of a trusted server, where connecting
+    /// fails unless the server proves that it holds a pinned key; can be
+    /// provided multiple times
+    #[clap(long = "pinned-server-key", number_of_values = 1)]
+    pub pinned_server_keys: Vec<String>,
+
+    /// Address (<host>:<port>) of a server to fail over to if the server
+    /// becomes unreachable, resolved like the address of the server; can be
+    /// provided multiple times, where fallback servers are tried in order
+    #[clap(long = "fallback-server", number_of_values = 1)]
+    pub fallback_servers: Vec<String>,
+
+    /// If provided, will record and check server identities using the list
+    /// of known servers at the specified path instead of the default
+    /// location
+    #[clap(long)]
+    pub known_servers: Option<PathBuf>,
+
+    /// If provided, will neither record nor check the identity of the
+    /// server using the list of known servers
+    #[clap(long)]
+    pub no_known_servers: bool,
+
+    /// If provided, will connect even if the server presents a different
+    /// identity than the one recorded for it, recording the new identity
+    #[clap(long)]
+    pub accept_new_key: bool,
+
+    /// If provided, will prove to the server that this client receives
+    /// replies sent to its address before sending anything else, which a
+    /// udp server with strict origin binding requires
+    #[clap(long)]
+    pub bind_origin: bool,
+
+    /// If provided, will negotiate a key for the session with the server
+    /// and seal every msg sent to and received from it with that key, where
+    /// the server must sign the handshake with a pinned key if any are given
+    #[clap(long)]
+    pub handshake: bool,
+
+    /// If provided, will tag requests with the trace id so they can be
+    /// correlated with other requests of the same workflow
+    #[clap(long)]
+    pub trace_id: Option<String>,
+
+    /// If provided, will tag requests with the id of the operation that
+    /// caused them to be sent
+    #[clap(long)]
+    pub parent_span_id: Option<String>,
+
+    /// If provided, hex-encoded 32-byte ed25519 secret key used to sign each
+    /// msg so that servers can authenticate the client across relays
+    #[clap(long)]
+    pub signing_key: Option<String>,
+
+    /// If provided, will compress large msgs sent to the server and accept
+    /// compressed replies from it
+    #[clap(long)]
+    pub compress: bool,
+
+    /// If provided, will neither compress the requests of this command nor
+    /// accept compressed replies to them, such as when their content is
+    /// already compressed
+    #[clap(long)]
+    pub no_compression: bool,
+
+    /// If provided, will have the server reject the requests of this
+    /// command unless they are signed, regardless of the server's signature
+    /// mode
+    #[clap(long)]
+    pub require_signature: bool,
+
+    /// If provided, will log a warning with the breakdown of where the time
+    /// went for any request whose reply takes longer than this many seconds
+    #[clap(long, parse(try_from_str = parsers::parse_duration_secs))]
+    pub slow_ask_threshold: Option<Duration>,
+
+    /// If provided, time (in seconds) that the entire command can take,
+    /// after which it stops, reporting what it completed and releasing what
+    /// it opened on the server, such as killing a proc it started
+    #[clap(long, parse(try_from_str = parsers::parse_duration_secs))]
+    pub overall_deadline: Option<Duration>,
+
+    /// If provided, sends raw requests without waiting for a reply, which
+    /// requires the udp transport; nothing is reported, so there is no way
+    /// to know whether the server received a request
+    #[clap(long)]
+    pub fire_and_forget: bool,
+
     #[clap(flatten)]
     pub opts: CommonOpts,
 }
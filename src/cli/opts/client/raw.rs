@@ -1,10 +1,11 @@
 use crate::cli::format::FormatOption;
 use clap::Clap;
+use serde::{Deserialize, Serialize};
 use strum::VariantNames;
 
 /// Performs an operation using raw input as the instruction, only
 /// valid for non-Human input such as JSON
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct RawCommand {
     /// Raw input to be sent directly to the server
     pub input: Option<String>,
@@ -33,4 +34,11 @@ pub struct RawCommand {
     /// having that information available on replies (such as callback IDs)
     #[clap(short, long)]
     pub meta_mode: bool,
+
+    /// If provided, will only check that inputs are valid requests without
+    /// connecting to the server, printing each decoded request or the reason
+    /// that it is invalid
+    #[clap(long)]
+    #[serde(default)]
+    pub validate_only: bool,
 }
@@ -1,5 +1,6 @@
 use clap::Clap;
+use serde::{Deserialize, Serialize};
 
 /// Retrieve the version of the server
-#[derive(Clap, Debug)]
+#[derive(Clap, Clone, Debug, Serialize, Deserialize)]
 pub struct VersionCommand {}
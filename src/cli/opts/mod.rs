@@ -40,9 +40,18 @@ pub struct Opts {
     pub command: Command,
 }
 
+impl Opts {
+    /// Whether the options are for a client, which runs a single command
+    /// against a server and then exits
+    pub fn is_client(&self) -> bool {
+        matches!(self.command, Command::Client(_))
+    }
+}
+
 #[derive(Clap, Debug)]
 pub struct CommonOpts {
-    /// Timeout (in seconds) used when communicating across the network
+    /// Timeout (in seconds) used when communicating across the network,
+    /// which applies to connecting to a server and to each request made of it
     #[clap(long, parse(try_from_str = parsers::parse_duration_secs), default_value = "5")]
     pub timeout: Duration,
 
@@ -50,11 +59,50 @@ pub struct CommonOpts {
     #[clap(long, parse(try_from_str = parsers::parse_duration_secs), default_value = "300")]
     pub packet_ttl: Duration,
 
+    /// If provided, the time-to-live for collecting the packets of a msg
+    /// starts at its first packet rather than being extended by each packet
+    #[clap(long)]
+    pub fixed_packet_ttl: bool,
+
+    /// Maximum number of msgs whose packets can be collected at once
+    #[clap(long, default_value = "1024")]
+    pub max_packet_groups: usize,
+
+    /// Maximum number of packets that can make up a single msg
+    #[clap(long, default_value = "65536")]
+    pub max_packets_per_msg: usize,
+
+    /// Minimum time (in seconds) between removals of msgs whose packets
+    /// were not all collected in time
+    #[clap(long, parse(try_from_str = parsers::parse_duration_secs), default_value = "1")]
+    pub packet_gc_interval: Duration,
+
     /// Maximum size of internal message passing between reader, writer, and
     /// executor loops
     #[clap(long, default_value = "1000")]
     pub internal_buffer_size: usize,
 
+    /// If provided, time (in seconds) a tcp connection can sit idle before
+    /// keepalive probes are sent, keeping long-idle sessions alive behind
+    /// NATs
+    #[clap(long, parse(try_from_str = parsers::parse_duration_secs))]
+    pub tcp_keepalive: Option<Duration>,
+
+    /// If provided, small tcp writes are sent immediately rather than being
+    /// coalesced (TCP_NODELAY)
+    #[clap(long)]
+    pub tcp_nodelay: bool,
+
+    /// If provided, size (in bytes) of the kernel buffer for data received
+    /// by the tcp or udp socket (SO_RCVBUF)
+    #[clap(long)]
+    pub recv_buffer_size: Option<usize>,
+
+    /// If provided, size (in bytes) of the kernel buffer for data sent by
+    /// the tcp or udp socket (SO_SNDBUF)
+    #[clap(long)]
+    pub send_buffer_size: Option<usize>,
+
     /// Transportation medium used in communication between client and server
     #[clap(
         short = "t", 
@@ -92,4 +140,9 @@ pub struct CommonOpts {
     /// Key to use with encryption
     #[clap(long = "akey")]
     pub authentication_key: Option<String>,
+
+    /// If provided, msgs with content of an unknown type or fields unknown
+    /// to this version will be discarded rather than tolerated
+    #[clap(long)]
+    pub strict_decoding: bool,
 }
@@ -12,6 +12,12 @@ pub fn parse_duration_millis(s: &str) -> Result<Duration, Box<dyn Error>> {
     Ok(Duration::from_millis(millis))
 }
 
+/// Parses unix permission bits written in octal, such as 644 or 0o644
+pub fn parse_mode(s: &str) -> Result<u32, Box<dyn Error>> {
+    let mode = u32::from_str_radix(s.trim_start_matches("0o"), 8)?;
+    Ok(mode)
+}
+
 pub fn parse_socket_addr(s: &str) -> Result<SocketAddr, Box<dyn Error>> {
     let addr = s.parse()?;
     Ok(addr)
@@ -1,4 +1,5 @@
 use clap::Clap;
+use std::path::PathBuf;
 use strum::VariantNames;
 use strum_macros::{EnumString, EnumVariantNames};
 
@@ -18,6 +19,29 @@ pub enum SchemaSubcommand {
     /// Prints information about schema for specific item
     #[clap(name = "info")]
     Info(SchemaInfo),
+
+    /// Prints a description of the packet layout, encryption, and framing
+    /// of msgs sent over the wire
+    #[clap(name = "wire")]
+    Wire,
+
+    /// Prints the schemas of all requests and replies of this version, which
+    /// later versions can be compared against using `diff`
+    #[clap(name = "export")]
+    Export,
+
+    /// Compares the schemas of requests and replies of this version against
+    /// those exported by another version, reporting the types and fields
+    /// that were added, removed, or changed
+    #[clap(name = "diff")]
+    Diff(SchemaDiffCommand),
+}
+
+#[derive(Clap, Debug)]
+pub struct SchemaDiffCommand {
+    /// Path to the schemas exported by the other version
+    #[clap(parse(from_os_str))]
+    pub path: PathBuf,
 }
 
 #[derive(Clap, Debug)]
@@ -41,10 +65,28 @@ pub enum SchemaType {
     HeartbeatRequest,
     VersionRequest,
     CapabilitiesRequest,
+    TimeInfoRequest,
+    IdentifyRequest,
+    UpdateTrustedClientsRequest,
+    HandshakeRequest,
+    SealedRequest,
+    PushConfigRequest,
+    GetConfigRequest,
+    UpdateWebhooksRequest,
+    PowerControlRequest,
+    ExportStateRequest,
+    ImportStateRequest,
     CreateDirRequest,
     RenameDirRequest,
     RemoveDirRequest,
+    CreatePathsRequest,
     ListDirContentsRequest,
+    ResolvePathRequest,
+    SniffFileRequest,
+    GetFileChecksumRequest,
+    DiffFilesRequest,
+    RecentFsEventsRequest,
+    ListMountsRequest,
     OpenFileRequest,
     CloseFileRequest,
     RenameUnopenedFileRequest,
@@ -52,26 +94,68 @@ pub enum SchemaType {
     RemoveUnopenedFileRequest,
     RemoveFileRequest,
     ReadFileRequest,
+    ReadFilesRequest,
     WriteFileRequest,
+    WriteFileAtomicByPathRequest,
+    PatchFileLinesRequest,
+    UploadManifestRequest,
+    WriteUploadChunkRequest,
     ExecProcRequest,
+    ExecScriptRequest,
+    ExecProcPtyRequest,
+    ResizePtyRequest,
     WriteProcStdinRequest,
     ReadProcStdoutRequest,
     ReadProcStderrRequest,
     KillProcRequest,
     ReadProcStatusRequest,
+    ReadProcTreeRequest,
+    ReadProcResourcesRequest,
+    ReadProcInfoRequest,
+    ListProcsRequest,
+    SubmitJobRequest,
+    QueryJobRequest,
+    CollectJobOutputRequest,
+    CreateScheduleRequest,
+    DeleteScheduleRequest,
+    AppendLogRequest,
+    ReadLogRangeRequest,
     SequenceRequest,
     BatchRequest,
+    TransactionRequest,
     ForwardRequest,
     CustomRequest,
-    InternalDebugRequest,
+    DiagnosticsRequest,
+    ListConnectionsRequest,
+    ListPendingEvictionsRequest,
+    BindOriginRequest,
 
     HeartbeatReply,
     VersionReply,
     CapabilitiesReply,
+    TimeInfoReply,
+    IdentifyReply,
+    UpdateTrustedClientsReply,
+    HandshakeReply,
+    SealedReply,
+    PushConfigReply,
+    GetConfigReply,
+    UpdateWebhooksReply,
+    PowerControlReply,
+    ExportStateReply,
+    ImportStateReply,
     CreateDirReply,
     RenameDirReply,
     RemoveDirReply,
+    CreatePathsReply,
     ListDirContentsReply,
+    ListDirContentsChunkReply,
+    ResolvePathReply,
+    SniffFileReply,
+    GetFileChecksumReply,
+    DiffFilesReply,
+    RecentFsEventsReply,
+    ListMountsReply,
     OpenFileReply,
     CloseFileReply,
     RenameUnopenedFileReply,
@@ -79,21 +163,49 @@ pub enum SchemaType {
     RemoveUnopenedFileReply,
     RemoveFileReply,
     ReadFileReply,
+    ReadFilesReply,
     WriteFileReply,
+    WriteFileAtomicByPathReply,
+    PatchFileLinesReply,
+    UploadManifestReply,
+    WriteUploadChunkReply,
     ExecProcReply,
     WriteProcStdinReply,
+    ResizePtyReply,
     ReadProcStdoutReply,
     ReadProcStderrReply,
     KillProcReply,
     ReadProcStatusReply,
+    ReadProcTreeReply,
+    ReadProcResourcesReply,
+    ReadProcInfoReply,
+    ListProcsReply,
+    SubmitJobReply,
+    QueryJobReply,
+    CollectJobOutputReply,
+    CreateScheduleReply,
+    ListSchedulesReply,
+    DeleteScheduleReply,
+    AppendLogReply,
+    ReadLogRangeReply,
     SequenceReply,
     BatchReply,
+    BatchResultReply,
+    TransactionReply,
     ForwardReply,
     CustomReply,
-    InternalDebugReply,
+    DiagnosticsReply,
+    ListConnectionsReply,
+    ListPendingEvictionsReply,
+    BindOriginReply,
+    ServerShuttingDownReply,
+    UnsupportedReply,
 
     ErrorReply,
     GenericError,
     IoError,
     FileSigChanged,
+    PreconditionFailed,
+    QuotaExceeded,
+    ErrorCode,
 }
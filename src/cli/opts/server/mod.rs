@@ -1,8 +1,9 @@
-use super::{parsers, CommonOpts};
+use super::{parsers, types, CommonOpts};
 use clap::Clap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Duration;
+use strum::VariantNames;
 
 /// Binding to a given address and listen for requests
 #[derive(Clap, Debug)]
@@ -18,6 +19,12 @@ pub struct ServerCommand {
     #[clap(long)]
     pub working_dir: Option<PathBuf>,
 
+    /// If provided, validates the configuration (keys, limits, webhooks, and
+    /// persisted config) and prints the configuration the server would run
+    /// with, exiting without binding to the address
+    #[clap(long)]
+    pub check_config: bool,
+
     /// Time (in seconds) between runs of the cleanup process
     #[clap(
         long, 
@@ -52,4 +59,210 @@ pub struct ServerCommand {
         default_value = "30",
     )]
     pub dead_proc_ttl: Duration,
+
+    /// Maximum depth of sequence and batch operations nested within a
+    /// request
+    #[clap(long, default_value = "5")]
+    pub max_request_depth: u8,
+
+    /// Maximum total of operations nested within a request across all
+    /// depths, rejecting requests that exceed it before executing them
+    #[clap(long, default_value = "256")]
+    pub max_nested_operations: usize,
+
+    /// If provided, permission bits (in octal) applied to files created by
+    /// the server instead of relying on the server's umask
+    #[clap(long, parse(try_from_str = parsers::parse_mode))]
+    pub default_file_mode: Option<u32>,
+
+    /// If provided, permission bits (in octal) applied to directories created
+    /// by the server instead of relying on the server's umask
+    #[clap(long, parse(try_from_str = parsers::parse_mode))]
+    pub default_dir_mode: Option<u32>,
+
+    /// Total attempts made of a filesystem operation that fails with a
+    /// transient error, such as a file briefly locked by another process
+    #[clap(long, default_value = "1")]
+    pub fs_retry_attempts: u32,
+
+    /// Time (in milliseconds) to wait before retrying a filesystem operation,
+    /// which doubles with each retry
+    #[clap(
+        long,
+        parse(try_from_str = parsers::parse_duration_millis),
+        default_value = "10",
+    )]
+    pub fs_retry_backoff: Duration,
+
+    /// If provided, directory where the output and outcome of jobs are
+    /// stored instead of within the system's temp directory
+    #[clap(long)]
+    pub jobs_dir: Option<PathBuf>,
+
+    /// If provided, directory where logs appended to by clients are stored
+    /// instead of within the system's temp directory
+    #[clap(long)]
+    pub logs_dir: Option<PathBuf>,
+
+    /// Size in bytes that a log file can reach before it is rotated
+    #[clap(long, default_value = "10485760")]
+    pub max_log_file_size: u64,
+
+    /// Number of files kept for each log, including the file currently
+    /// being appended to
+    #[clap(long, default_value = "5")]
+    pub max_log_files: usize,
+
+    /// Url (http only) to POST a JSON payload to whenever a server event
+    /// occurs, such as a process exiting; can be provided multiple times
+    #[clap(long = "webhook", number_of_values = 1)]
+    pub webhooks: Vec<String>,
+
+    /// If provided, secret used to sign webhook payloads with HMAC-SHA256
+    #[clap(long)]
+    pub webhook_secret: Option<String>,
+
+    /// If provided, hex-encoded 32-byte ed25519 secret key used to prove the
+    /// identity of the server to clients that pin its public key
+    #[clap(long)]
+    pub identity_key: Option<String>,
+
+    /// How signatures of msgs are treated, where verify rejects msgs with a
+    /// bad signature and require also rejects msgs without one
+    #[clap(
+        long,
+        parse(try_from_str),
+        possible_values = &types::SignatureMode::VARIANTS,
+        default_value = types::SignatureMode::Ignore.as_ref(),
+    )]
+    pub msg_signatures: types::SignatureMode,
+
+    /// Hex-encoded public identity key whose msg signatures are trusted,
+    /// where any key is trusted if none are provided; can be provided
+    /// multiple times
+    #[clap(long = "trusted-signer", number_of_values = 1)]
+    pub trusted_signers: Vec<String>,
+
+    /// If provided, maximum bytes that any single identity (the key signing
+    /// its msgs, otherwise its address) can transfer to and from the
+    /// server, beyond which its msgs are rejected
+    #[clap(long)]
+    pub transfer_quota: Option<u64>,
+
+    /// If provided, file listing the fingerprints of client keys trusted by
+    /// the server, where msgs not signed by one of them are rejected; the
+    /// list can be changed at runtime and each change is saved to the file
+    #[clap(long)]
+    pub trusted_clients_path: Option<PathBuf>,
+
+    /// Fingerprint of a client identity key to trust in addition to those
+    /// in the trusted clients file, where only trusted clients are accepted
+    /// once any are provided; can be provided multiple times
+    #[clap(long = "trusted-client", number_of_values = 1)]
+    pub trusted_clients: Vec<String>,
+
+    /// Fingerprint of a client identity key allowed to reboot or shut down
+    /// the host of the server, where no client can if none are provided;
+    /// can be provided multiple times
+    #[clap(long = "power-admin", number_of_values = 1)]
+    pub power_admins: Vec<String>,
+
+    /// If provided, clients cannot run procs, scripts, jobs, or schedules
+    #[clap(long)]
+    pub deny_exec: bool,
+
+    /// If provided, clients cannot manage the server, such as reading or
+    /// changing its config, trusted clients, or handlers, or inspecting its
+    /// connections
+    #[clap(long)]
+    pub deny_admin: bool,
+
+    /// If provided, clients cannot read, list, or inspect files and dirs
+    #[clap(long)]
+    pub deny_fs_read: bool,
+
+    /// If provided, clients cannot create, change, rename, or remove files
+    /// and dirs
+    #[clap(long)]
+    pub deny_fs_write: bool,
+
+    /// Dir that paths of requests from clients must fall within without
+    /// passing through symlinks, where any path is allowed if none are
+    /// provided; can be provided multiple times
+    #[clap(long = "allowed-path", number_of_values = 1)]
+    pub allowed_paths: Vec<PathBuf>,
+
+    /// If provided, requests forwarded through the server are relayed to the
+    /// servers they are addressed to, letting clients reach servers that
+    /// only the server can reach
+    #[clap(long)]
+    pub forwarding: bool,
+
+    /// If provided, the server listens on the socket handed to it by the
+    /// service manager that started it, such as through systemd socket
+    /// activation, rather than binding its address
+    #[clap(long)]
+    pub socket_activation: bool,
+
+    /// Name of the socket handed to the server to listen on when using
+    /// socket activation, which launchd requires
+    #[clap(long)]
+    pub activation_socket_name: Option<String>,
+
+    /// If provided, the server permanently drops its privileges to those of
+    /// this user, by name or id, once it has bound its address, failing to
+    /// start if it cannot
+    #[clap(long)]
+    pub run_as_user: Option<String>,
+
+    /// Group, by name or id, that the server drops its privileges to along
+    /// with the user, where the primary group of the user is used if not
+    /// provided
+    #[clap(long)]
+    pub run_as_group: Option<String>,
+
+    /// If provided, a udp client must echo back a nonce sent by the server
+    /// before the server accepts requests from it that change anything,
+    /// guarding against clients with spoofed addresses
+    #[clap(long)]
+    pub strict_origin_binding: bool,
+
+    /// If provided, maximum ratio of bytes sent to a udp client that has not
+    /// bound its origin to bytes received from it, beyond which replies are
+    /// replaced with errors or dropped
+    #[clap(long)]
+    pub max_amplification: Option<u64>,
+
+    /// Minimum size in bytes of requests from udp clients that have not
+    /// bound their origin, below which requests are ignored
+    #[clap(long, default_value = "0")]
+    pub min_unbound_request_size: u64,
+
+    /// Number of recent changes to the filesystem kept for clients to
+    /// catch up on
+    #[clap(long, default_value = "1000")]
+    pub fs_event_history: usize,
+
+    /// If provided, file where configs pushed by operators are persisted,
+    /// encrypted with the config key, rather than only kept in memory
+    #[clap(long)]
+    pub config_path: Option<PathBuf>,
+
+    /// Key used to encrypt the persisted config, required when providing a
+    /// config path
+    #[clap(long)]
+    pub config_key: Option<String>,
+
+    /// If provided, Rhai script whose `on_request` and `on_reply` hooks can
+    /// rewrite or block requests and rewrite replies, reloaded whenever the
+    /// script changes
+    #[cfg(feature = "script")]
+    #[clap(long)]
+    pub script: Option<PathBuf>,
+
+    /// If provided, the server restricts its system calls to those it needs
+    /// once it is listening, failing to start if it cannot
+    #[cfg(feature = "seccomp")]
+    #[clap(long)]
+    pub syscall_filter: bool,
 }
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use strum_macros::{AsRefStr, EnumString, EnumVariantNames};
 
 #[derive(
@@ -32,3 +33,82 @@ pub enum Transport {
     Tcp,
     Udp,
 }
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, EnumString, EnumVariantNames, AsRefStr,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum SignatureMode {
+    Ignore,
+    Verify,
+    Require,
+}
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, EnumString, EnumVariantNames, AsRefStr,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum Preset {
+    Lan,
+    Wan,
+    Lossy,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumString,
+    EnumVariantNames,
+    AsRefStr,
+    Serialize,
+    Deserialize,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ProcIoMode {
+    Raw,
+    Line,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumString,
+    EnumVariantNames,
+    AsRefStr,
+    Serialize,
+    Deserialize,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Newline {
+    Keep,
+    Lf,
+    Crlf,
+}
+
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumString,
+    EnumVariantNames,
+    AsRefStr,
+    Serialize,
+    Deserialize,
+)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha512,
+    Crc32,
+}
@@ -0,0 +1,77 @@
+use std::io;
+
+/// Rows and columns assumed for the local terminal when its size cannot be
+/// determined, such as when stdout is not a terminal
+pub const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+/// Rows and columns of the terminal that stdout is attached to, if any
+#[cfg(unix)]
+pub fn size() -> Option<(u16, u16)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let result = unsafe {
+        libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size)
+    };
+
+    if result < 0 || size.ws_row == 0 || size.ws_col == 0 {
+        None
+    } else {
+        Some((size.ws_row, size.ws_col))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn size() -> Option<(u16, u16)> {
+    None
+}
+
+/// Places the terminal that stdin is attached to in raw mode, passing every
+/// keypress (including Ctrl-C) along as input rather than acting on it, and
+/// restores the terminal once dropped
+pub struct RawMode {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl RawMode {
+    /// Enters raw mode, yielding none if stdin is not a terminal
+    #[cfg(unix)]
+    pub fn enter() -> io::Result<Option<Self>> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+            return Ok(None);
+        }
+
+        let mut original = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) }
+            < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Some(Self { original }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn enter() -> io::Result<Option<Self>> {
+        Ok(None)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use sha2::{Digest, Sha256};
+use std::io;
+
+/// Default minimum size of a chunk in bytes
+pub const DEFAULT_MIN_CHUNK_SIZE: usize = 2 * 1024;
+
+/// Default size in bytes that chunks are normalized around
+pub const DEFAULT_AVG_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Default maximum size of a chunk in bytes
+pub const DEFAULT_MAX_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Random values for each byte that are rolled into the fingerprint of the
+/// bytes preceding a potential cut point
+static GEAR: [u64; 256] = gear_table();
+
+/// Generates the gear table from a fixed seed so that every build (and
+/// therefore both sides of a sync) cut the same content at the same points
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x6f76_6572_7468_6572;
+    let mut i = 0;
+    while i < table.len() {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Portion of some data cut by a `Chunker`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    /// Offset of the chunk within the data
+    pub offset: u64,
+
+    /// Size of the chunk in bytes
+    pub len: usize,
+
+    /// Hex-encoded SHA-256 hash of the chunk's contents
+    pub hash: String,
+}
+
+/// Splits data into chunks using content-defined chunking (FastCDC), where
+/// cut points are chosen by the content around them rather than at fixed
+/// offsets; inserting or removing bytes therefore only changes the chunks
+/// near the edit instead of every chunk that follows it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Chunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+
+    /// Stricter mask used before reaching the average size, making cuts of
+    /// small chunks less likely
+    mask_small: u64,
+
+    /// Looser mask used after reaching the average size, making cuts of
+    /// large chunks more likely
+    mask_large: u64,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MIN_CHUNK_SIZE,
+            DEFAULT_AVG_CHUNK_SIZE,
+            DEFAULT_MAX_CHUNK_SIZE,
+        )
+        .expect("Default chunk sizes are valid")
+    }
+}
+
+impl Chunker {
+    /// Creates a chunker producing chunks of `min_size` to `max_size` bytes
+    /// that are normalized around `avg_size`, failing unless
+    /// `0 < min_size <= avg_size <= max_size`
+    pub fn new(
+        min_size: usize,
+        avg_size: usize,
+        max_size: usize,
+    ) -> io::Result<Self> {
+        if min_size == 0 || min_size > avg_size || avg_size > max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid chunk sizes: min {}, avg {}, max {}",
+                    min_size, avg_size, max_size
+                ),
+            ));
+        }
+
+        let bits = (usize::BITS - 1 - avg_size.leading_zeros()).max(3);
+        Ok(Self {
+            min_size,
+            avg_size,
+            max_size,
+            mask_small: high_bits(bits + 2),
+            mask_large: high_bits(bits - 2),
+        })
+    }
+
+    pub fn min_size(&self) -> usize {
+        self.min_size
+    }
+
+    pub fn avg_size(&self) -> usize {
+        self.avg_size
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Finds the size of the first chunk of `data`
+    pub fn cut(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+
+        let max = data.len().min(self.max_size);
+        let normal = data.len().min(self.avg_size);
+
+        let mut fingerprint: u64 = 0;
+        let mut i = self.min_size;
+        while i < max {
+            fingerprint =
+                (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            let mask = if i < normal {
+                self.mask_small
+            } else {
+                self.mask_large
+            };
+
+            i += 1;
+            if fingerprint & mask == 0 {
+                return i;
+            }
+        }
+
+        max
+    }
+
+    /// Splits all of `data` into chunks
+    pub fn chunks(&self, data: &[u8]) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let len = self.cut(&data[offset..]);
+            chunks.push(Chunk {
+                offset: offset as u64,
+                len,
+                hash: format!(
+                    "{:x}",
+                    Sha256::digest(&data[offset..offset + len])
+                ),
+            });
+            offset += len;
+        }
+        chunks
+    }
+}
+
+/// Mask of the `n` highest bits, which are the ones that the gear
+/// fingerprint has mixed the most preceding bytes into
+fn high_bits(n: u32) -> u64 {
+    !0u64 << (64 - n.min(63))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+    fn random_data(len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        StdRng::seed_from_u64(1234).fill_bytes(&mut data);
+        data
+    }
+
+    #[test]
+    fn new_should_fail_if_sizes_are_out_of_order() {
+        assert!(Chunker::new(0, 8, 16).is_err());
+        assert!(Chunker::new(16, 8, 32).is_err());
+        assert!(Chunker::new(4, 32, 16).is_err());
+        assert!(Chunker::new(8, 8, 8).is_ok());
+    }
+
+    #[test]
+    fn chunks_should_cover_data_within_size_bounds() {
+        let chunker = Chunker::default();
+        let data = random_data(1024 * 1024);
+        let chunks = chunker.chunks(&data);
+
+        let mut offset = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.offset, offset);
+            assert!(chunk.len <= chunker.max_size());
+            if i + 1 < chunks.len() {
+                assert!(chunk.len >= chunker.min_size());
+            }
+            offset += chunk.len as u64;
+        }
+        assert_eq!(offset, data.len() as u64);
+
+        // Chunks are normalized around the average size
+        let avg = data.len() / chunks.len();
+        assert!(
+            avg > chunker.avg_size() / 2 && avg < chunker.avg_size() * 2,
+            "Unexpected average chunk size: {}",
+            avg
+        );
+    }
+
+    #[test]
+    fn chunks_should_only_change_near_an_insertion() {
+        let chunker = Chunker::default();
+        let data = random_data(512 * 1024);
+        let mut edited = data.clone();
+        edited.splice(100..100, b"inserted bytes".iter().cloned());
+
+        let original: Vec<String> =
+            chunker.chunks(&data).into_iter().map(|c| c.hash).collect();
+        let changed = chunker
+            .chunks(&edited)
+            .into_iter()
+            .filter(|c| !original.contains(&c.hash))
+            .count();
+
+        assert!(changed <= 2, "{} chunks changed", changed);
+    }
+
+    #[test]
+    fn chunks_should_yield_single_chunk_for_small_data() {
+        let chunker = Chunker::default();
+        assert!(chunker.chunks(&[]).is_empty());
+
+        let chunks = chunker.chunks(b"small");
+        assert_eq!(
+            chunks,
+            vec![Chunk {
+                offset: 0,
+                len: 5,
+                hash: format!("{:x}", Sha256::digest(b"small")),
+            }]
+        );
+    }
+}
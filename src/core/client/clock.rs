@@ -0,0 +1,74 @@
+use crate::core::reply::TimeInfoArgs;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Estimate of how far the clock of the server is from that of the client,
+/// made from the timestamps of an exchange in the same way as NTP
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClockOffset {
+    /// Microseconds to add to the clock of the client to get that of the
+    /// server, which is negative if the server is behind
+    pub offset_micros: i64,
+
+    /// Time the exchange spent in transit, not counting time on the server;
+    /// the true offset is within half of this of the estimate
+    pub round_trip: Duration,
+}
+
+impl ClockOffset {
+    /// Estimates the offset from `args` replied by the server, where
+    /// `received_at_micros` is the time on the clock of the client when the
+    /// reply arrived
+    ///
+    /// Assumes the request and reply spent equal time in transit, as any
+    /// asymmetry cannot be told apart from an offset.
+    pub fn from_exchange(args: &TimeInfoArgs, received_at_micros: u64) -> Self {
+        let t0 = i128::from(args.client_sent_at_micros);
+        let t1 = i128::from(args.server_received_at_micros);
+        let t2 = i128::from(args.server_sent_at_micros);
+        let t3 = i128::from(received_at_micros);
+
+        let offset = ((t1 - t0) + (t2 - t3)) / 2;
+        let round_trip = ((t3 - t0) - (t2 - t1)).max(0);
+
+        Self {
+            offset_micros: i64::try_from(offset).unwrap_or(if offset < 0 {
+                i64::MIN
+            } else {
+                i64::MAX
+            }),
+            round_trip: Duration::from_micros(
+                u64::try_from(round_trip).unwrap_or(u64::MAX),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_exchange_should_compensate_for_round_trip() {
+        // Server is 1s ahead, with 10ms in transit each way and 5ms spent
+        // on the server
+        let args = TimeInfoArgs {
+            client_sent_at_micros: 1_000_000,
+            server_received_at_micros: 2_010_000,
+            server_sent_at_micros: 2_015_000,
+        };
+        let offset = ClockOffset::from_exchange(&args, 1_025_000);
+        assert_eq!(offset.offset_micros, 1_000_000);
+        assert_eq!(offset.round_trip, Duration::from_millis(20));
+
+        // Server is 1s behind
+        let args = TimeInfoArgs {
+            client_sent_at_micros: 2_000_000,
+            server_received_at_micros: 1_010_000,
+            server_sent_at_micros: 1_010_000,
+        };
+        let offset = ClockOffset::from_exchange(&args, 2_020_000);
+        assert_eq!(offset.offset_micros, -1_000_000);
+        assert_eq!(offset.round_trip, Duration::from_millis(20));
+    }
+}
@@ -1,8 +1,13 @@
 use super::{
+    clock::ClockOffset,
+    copy::{self, DirCopy},
     error::{AskError, ExecAskError, FileAskError, SendError},
-    file::RemoteFile,
+    event::ClientEvent,
+    failover::Failover,
+    file::{DownloadOptions, DownloadProgress, RemoteFile},
+    mirror::{Mirror, MirrorOptions, MirrorPass},
     proc::RemoteProc,
-    state::ClientState,
+    state::{AskMetrics, AskTiming, ClientState, ClientStats},
 };
 use crate::core::{
     event::{AddrEventManager, EventManager},
@@ -10,27 +15,78 @@ use crate::core::{
         content::{
             reply::{self, *},
             request::{self, *},
-            Reply, ReplyError, Request,
+            RemotePath, Reply, ReplyError, Request,
         },
-        Msg,
+        Msg, MsgFlags,
     },
+    transport::{
+        auth::identity,
+        crypto::handshake::{self, Handshake, Role, Session},
+    },
+};
+use crate::utils::now_micros;
+use futures::{
+    channel::mpsc,
+    future::Future,
+    stream::{self, FuturesUnordered, Stream, StreamExt},
 };
-use crate::utils::Either;
-use log::{error, trace};
+use log::{error, trace, warn};
+use sha2::{Digest, Sha256};
+use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::{
-    sync::{oneshot, Mutex},
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::{oneshot, Mutex, Semaphore},
     task::{JoinError, JoinHandle},
 };
 
+/// Bytes set aside for everything in the reply to a chunked read other than
+/// the contents of the chunk, such as its header and etag
+const READ_CHUNK_OVERHEAD: usize = 1024;
+
+/// Options of a single ask that override how its request is sent
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AskOptions {
+    /// Sends the request as is rather than compressed, such as when its
+    /// content is already compressed
+    pub no_compression: bool,
+
+    /// Signs the request and has the server reject it unless its signature
+    /// is valid, regardless of the signature policy of the server
+    pub require_signature: bool,
+}
+
+impl AskOptions {
+    fn flags(self) -> MsgFlags {
+        MsgFlags {
+            no_compression: self.no_compression,
+            require_signature: self.require_signature,
+            ..Default::default()
+        }
+    }
+}
+
 /// Represents a client after connecting to an endpoint
+/// Means by which a client sends msgs to the server it is connected to
+pub(super) enum ClientEventManager {
+    /// Stream dedicated to the server
+    Stream(EventManager),
+
+    /// Socket dedicated to the server
+    Socket(AddrEventManager),
+
+    /// Socket shared with clients of other servers, whose event manager is
+    /// owned by a `SharedUdpSocket`
+    SharedSocket(tokio::sync::mpsc::Sender<(Vec<u8>, SocketAddr)>),
+}
+
 pub struct ConnectedClient {
     pub(super) state: Arc<Mutex<ClientState>>,
 
     /// Represents the event manager used to send and receive data
-    pub(super) event_manager: Either<EventManager, AddrEventManager>,
+    pub(super) event_manager: ClientEventManager,
 
     /// Represents the handle for processing events
     pub(super) event_handle: JoinHandle<()>,
@@ -40,43 +96,347 @@ pub struct ConnectedClient {
 
     /// Represents maximum to wait on responses before timing out
     pub timeout: Duration,
+
+    /// If provided, trace ID attached to every msg sent to the server so
+    /// that its requests can be correlated with those of other hops
+    pub trace_id: Option<String>,
+
+    /// If provided, ID of the client-side operation attached to every msg
+    /// sent to the server as the parent of its requests
+    pub parent_span_id: Option<String>,
+
+    /// If provided, identity key used to sign every msg sent to the server,
+    /// proving that the client sent it even when relayed by other hops
+    pub signing_key: Option<identity::IdentityKey>,
+
+    /// If provided, msgs of at least this many bytes are compressed before
+    /// being sent to the server, which is also told that it may compress
+    /// its replies
+    pub compression_threshold: Option<usize>,
+
+    /// Options applied to every ask that is not given its own
+    pub ask_options: AskOptions,
+
+    /// If provided, asks whose reply takes longer than this to arrive are
+    /// logged as warnings along with where the time went
+    pub slow_ask_threshold: Option<Duration>,
+
+    /// Permits for asks awaiting a reply, where new asks wait for a permit
+    /// once the maximum number are in flight
+    pub(super) ask_permits: Arc<Semaphore>,
+
+    /// Represents maximum number of asks that can await a reply at once
+    pub(super) max_outstanding_asks: usize,
+
+    /// Represents maximum size of a msg the transport can carry
+    pub(super) max_msg_size: usize,
+
+    /// If provided, fallback servers connected to once the server becomes
+    /// unreachable
+    pub(super) failover: Option<Failover>,
 }
 
 impl ConnectedClient {
     /// Default timeout applied to a new client for any ask made
     pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
 
+    /// Default maximum number of asks that can await a reply at once
+    pub const DEFAULT_MAX_OUTSTANDING_ASKS: usize = 1000;
+
     pub fn remote_addr(&self) -> SocketAddr {
         self.remote_addr
     }
 
+    /// Maximum size of an encoded msg that can be sent to the server, which
+    /// larger requests must be split to fit within
+    pub fn max_msg_size(&self) -> usize {
+        self.max_msg_size
+    }
+
+    /// Fallback servers not yet failed over to, in the order they will be
+    /// tried should the server become unreachable
+    pub fn fallback_servers(&self) -> Vec<SocketAddr> {
+        self.failover
+            .as_ref()
+            .map(Failover::remaining)
+            .unwrap_or_default()
+    }
+
+    /// Subscribes to events of the client, such as failing over to a
+    /// fallback server, where only events occurring afterwards are received
+    pub async fn events(&self) -> impl Stream<Item = ClientEvent> {
+        let (tx, rx) = mpsc::unbounded();
+        self.state.lock().await.event_txs.push(tx);
+        rx
+    }
+
+    /// Reports the asks currently awaiting a reply from the server and the
+    /// callbacks that have been discarded for never receiving one
+    pub async fn ask_metrics(&self) -> AskMetrics {
+        let state = self.state.lock().await;
+        AskMetrics {
+            in_flight: self.max_outstanding_asks
+                - self.ask_permits.available_permits(),
+            max_in_flight: self.max_outstanding_asks,
+            outstanding_callbacks: state.callback_manager.len(),
+            expired_callbacks: state.callback_manager.expired_count(),
+        }
+    }
+
+    /// Reports the time spent on the asks made to the server, which helps
+    /// tell network latency apart from the server being slow
+    pub async fn stats(&self) -> ClientStats {
+        self.state.lock().await.stats
+    }
+
     pub async fn wait(self) -> Result<(), JoinError> {
         match self.event_manager {
-            Either::Left(m) => {
+            ClientEventManager::Stream(m) => {
                 tokio::try_join!(m.wait(), self.event_handle).map(|_| ())
             }
-            Either::Right(m) => {
+            ClientEventManager::Socket(m) => {
                 tokio::try_join!(m.wait(), self.event_handle).map(|_| ())
             }
+            ClientEventManager::SharedSocket(_) => self.event_handle.await,
+        }
+    }
+
+    /// Disconnects from the server once the msgs already queued have been
+    /// sent, such as tells that would otherwise be lost if the runtime shut
+    /// down first, stopping every task of the client rather than leaving
+    /// them for the runtime to drop
+    pub async fn close(self) -> Result<(), JoinError> {
+        match self.event_manager {
+            ClientEventManager::Stream(m) => m.close().await?,
+            ClientEventManager::Socket(m) => m.close().await?,
+
+            // NOTE: Shared socket outlives its clients, so it is left open and
+            //       the client is instead disconnected through the socket
+            ClientEventManager::SharedSocket(_) => return Ok(()),
         }
+        self.event_handle.await
     }
 
-    /// Generic ask of the server that is expecting a response
+    /// Generic ask of the server that is expecting a response, waiting
+    /// first if the maximum number of asks are already in flight
     pub async fn ask(&mut self, request: Request) -> Result<Reply, AskError> {
-        let timeout = self.timeout;
+        self.ask_with_options(request, self.ask_options).await
+    }
+
+    /// Same as `ask`, but sends the request using `options` instead of the
+    /// options of the client
+    pub async fn ask_with_options(
+        &mut self,
+        request: Request,
+        options: AskOptions,
+    ) -> Result<Reply, AskError> {
+        // Held until the ask completes, successfully or otherwise
+        let queued = Instant::now();
+        let permits = Arc::clone(&self.ask_permits);
+        let _permit = permits.acquire().await;
+        let queued = queued.elapsed();
+
+        // Keep a copy of a request that is safe to send again so that it can
+        // be replayed against a fallback server
+        let replay = match &self.failover {
+            Some(_) if request.is_idempotent() => Some(request.clone()),
+            _ => None,
+        };
+
+        let result = match self.send_ask(request, options, queued).await {
+            Ok(reply) => reply.await,
+            Err(x) => Err(x),
+        };
+
+        match result {
+            Err(x)
+                if self.is_unreachable(&x).await && self.fail_over().await =>
+            {
+                match replay {
+                    Some(request) => {
+                        self.send_ask(request, options, queued).await?.await
+                    }
+                    None => Err(x),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Whether `error` from an ask means that the server is unreachable and
+    /// a fallback server remains, confirming with a heartbeat if the ask
+    /// merely went unanswered
+    async fn is_unreachable(&mut self, error: &AskError) -> bool {
+        match &self.failover {
+            Some(failover) if failover.has_remaining() => {}
+            _ => return false,
+        }
+
+        match error {
+            AskError::SendFailed => true,
+            AskError::Timeout | AskError::CallbackLost => {
+                match self
+                    .send_ask(
+                        Request::Heartbeat,
+                        self.ask_options,
+                        Duration::default(),
+                    )
+                    .await
+                {
+                    Ok(reply) => reply.await.is_err(),
+                    Err(_) => true,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the connection to the server with one to the next reachable
+    /// fallback server, yielding whether one was reachable
+    async fn fail_over(&mut self) -> bool {
+        let mut failover = match self.failover.take() {
+            Some(failover) => failover,
+            None => return false,
+        };
+        let next = failover.connect_next(Arc::clone(&self.state)).await;
+        self.failover = Some(failover);
+
+        let next = match next {
+            Some(next) => next,
+            None => return false,
+        };
+
+        // NOTE: The connection to the unreachable server is dropped along
+        //       with its event manager
+        let from = self.remote_addr;
+        self.event_manager = next.event_manager;
+        self.event_handle = next.event_handle;
+        self.remote_addr = next.remote_addr;
+        self.max_msg_size = next.max_msg_size;
+
+        warn!("Failed over from {} to {}", from, self.remote_addr);
+        self.state.lock().await.emit(ClientEvent::Failover {
+            from,
+            to: self.remote_addr,
+        });
+
+        true
+    }
+
+    /// Sends each of `requests` without waiting on the replies to those
+    /// before it, keeping up to `window` asks awaiting a reply at once, and
+    /// yields the replies in the order of the requests
+    ///
+    /// Over high-latency links this avoids paying a full round trip for
+    /// every request; fails with the first error encountered
+    pub async fn ask_pipelined(
+        &mut self,
+        requests: Vec<Request>,
+        window: usize,
+    ) -> Result<Vec<Reply>, AskError> {
+        let total = requests.len();
+        let window = window.max(1).min(self.max_outstanding_asks).min(total);
+
+        // Held until every ask completes, limiting those in flight to the
+        // window
+        let permits = Arc::clone(&self.ask_permits);
+        let mut held_permits = Vec::with_capacity(window);
+        for _ in 0..window {
+            held_permits.push(permits.acquire().await);
+        }
+
+        let mut replies: Vec<Option<Reply>> =
+            (0..total).map(|_| None).collect();
+        let mut requests = requests.into_iter().enumerate();
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while in_flight.len() < window {
+                match requests.next() {
+                    Some((i, request)) => {
+                        let reply = self
+                            .send_ask(
+                                request,
+                                self.ask_options,
+                                Duration::default(),
+                            )
+                            .await?;
+                        in_flight.push(async move { (i, reply.await) });
+                    }
+                    None => break,
+                }
+            }
+
+            match in_flight.next().await {
+                Some((i, reply)) => replies[i] = Some(reply?),
+                None => break,
+            }
+        }
+
+        Ok(replies.into_iter().flatten().collect())
+    }
+
+    /// Sends the request encoded in `data` exactly as it was encoded, such as
+    /// when relaying a msg signed by another client, yielding a future of
+    /// its reply
+    ///
+    /// The client is only borrowed while sending, so that it can go on to
+    /// send other requests while the reply is awaited
+    pub async fn send_encoded(
+        &mut self,
+        data: Vec<u8>,
+    ) -> Result<impl Future<Output = Result<Reply, AskError>>, AskError> {
+        let msg =
+            Msg::from_slice(&data).map_err(|_| AskError::EncodingFailed)?;
+        self.send_ask_msg(msg, Some(data), Duration::default())
+            .await
+    }
+
+    /// Sends `request` to the server, yielding a future that waits on its
+    /// reply so that more asks can be sent in the meantime
+    ///
+    /// Unlike `ask`, no permit is acquired, so the caller is responsible for
+    /// limiting the asks in flight; `queued` is the time already spent
+    /// waiting for one, which is recorded with the timing of the ask
+    async fn send_ask(
+        &mut self,
+        request: Request,
+        options: AskOptions,
+        queued: Duration,
+    ) -> Result<impl Future<Output = Result<Reply, AskError>>, AskError> {
+        let mut msg = Msg::from(request);
+        msg.header.flags = options.flags();
+        self.send_ask_msg(msg, None, queued).await
+    }
+
+    /// Sends `msg`, or `data` in its place if it is already encoded, and
+    /// waits on a reply to it
+    async fn send_ask_msg(
+        &mut self,
+        msg: Msg,
+        data: Option<Vec<u8>>,
+        queued: Duration,
+    ) -> Result<impl Future<Output = Result<Reply, AskError>>, AskError> {
         let (tx, rx) = oneshot::channel::<Result<Reply, AskError>>();
-        let msg = Msg::from(request);
+        let id = msg.header.id;
+        let name = msg.content.type_name().unwrap_or_default();
+        let sending = Instant::now();
 
         // Assign a synchronous callback that uses the oneshot channel to
         // get back the result
-        self.state.lock().await.callback_manager.add_callback(
-            msg.header.id,
-            |reply| {
+        self.state
+            .lock()
+            .await
+            .callback_manager
+            .add_callback(id, |reply| {
                 // NOTE: We handle errors like IO further downstream, so
                 //       only extract the generic error here
                 let result =
                     if let Reply::Error(ReplyError::Generic(x)) = &reply {
-                        tx.send(Err(AskError::Failure { msg: x.to_string() }))
+                        tx.send(Err(AskError::Failure {
+                            msg: x.to_string(),
+                            code: x.code,
+                        }))
                     } else {
                         tx.send(Ok(reply.clone()))
                     };
@@ -84,35 +444,158 @@ impl ConnectedClient {
                 if result.is_err() {
                     error!("Failed to trigger callback: {:?}", reply);
                 }
-            },
-        );
+            });
 
         // Send the msg and report back an error if it occurs
-        self.send_msg(msg).await.map_err(AskError::from)?;
-
-        tokio::time::timeout(timeout, rx)
-            .await
-            .map_err(|_| AskError::Timeout)?
-            .map_err(|_| AskError::CallbackLost)?
+        let sent = match data {
+            Some(data) => self.send_data(&msg, data).await,
+            None => self.send_msg(msg).await,
+        };
+        if let Err(x) = sent {
+            self.state.lock().await.callback_manager.remove_callback(id);
+            return Err(AskError::from(x));
+        }
+        let sent = sending.elapsed();
+
+        // NOTE: The timeout starts now rather than when the future is first
+        //       polled, as the reply may already be on its way
+        let state = Arc::clone(&self.state);
+        let remote_addr = self.remote_addr;
+        let slow_ask_threshold = self.slow_ask_threshold;
+        let reply = tokio::time::timeout(self.timeout, rx);
+        Ok(async move {
+            let result = reply.await;
+            let mut state = state.lock().await;
+            let server = state.server_times.remove(&id);
+
+            match result {
+                Ok(result) => {
+                    let timing = AskTiming {
+                        queue: queued,
+                        send: sent,
+                        server,
+                        rtt: sending.elapsed(),
+                    };
+                    let slow = slow_ask_threshold
+                        .filter(|threshold| timing.total() > *threshold)
+                        .is_some();
+                    if slow {
+                        warn!(
+                            "Slow {} to {}: {:?} total ({:?} queued, {:?} \
+                             sending, {:?} on server, {:?} round trip)",
+                            name,
+                            remote_addr,
+                            timing.total(),
+                            timing.queue,
+                            timing.send,
+                            timing.server,
+                            timing.rtt,
+                        );
+                    }
+                    state.stats.record(timing, slow);
+
+                    result.map_err(|_| AskError::CallbackLost)?
+                }
+                Err(_) => {
+                    // No reply is coming, so stop waiting on one
+                    state.callback_manager.remove_callback(id);
+                    state.stats.timeouts += 1;
+                    Err(AskError::Timeout)
+                }
+            }
+        })
     }
 
     /// Sends a msg to the server, not expecting a response
     pub async fn tell(&mut self, request: Request) -> Result<(), SendError> {
-        self.send_msg(Msg::from(request)).await
+        let mut msg = Msg::from(request);
+        msg.header.flags = self.ask_options.flags();
+        self.send_msg(msg).await
+    }
+
+    async fn send_msg(&mut self, mut msg: Msg) -> Result<(), SendError> {
+        let data = self.encode_msg(&mut msg)?;
+        self.send_data(&msg, data).await
     }
 
-    async fn send_msg(&mut self, msg: Msg) -> Result<(), SendError> {
+    /// Places `msg` in the trace of the client and signs it if the client
+    /// has a key, yielding it encoded as it is sent
+    fn encode_msg(&self, msg: &mut Msg) -> Result<Vec<u8>, SendError> {
+        if msg.header.trace_id.is_none() && msg.header.parent_span_id.is_none()
+        {
+            msg.header
+                .with_trace(self.trace_id.clone(), self.parent_span_id.clone());
+        }
+
+        msg.header.flags.accepts_compression =
+            self.compression_threshold.is_some();
+
+        match self.signing_key.as_ref() {
+            Some(key) => {
+                msg.sign(key).map_err(|_| SendError::EncodingFailed)?;
+            }
+            None if msg.header.flags.require_signature => {
+                return Err(SendError::SigningKeyRequired);
+            }
+            None => {}
+        }
+
         trace!("Sending to {}: {:?}", self.remote_addr, msg);
 
-        let data = msg.to_vec().map_err(|_| SendError::EncodingFailed)?;
+        match self.compression_threshold {
+            Some(threshold) => msg.to_vec_compressed(threshold),
+            None => msg.to_vec(),
+        }
+        .map_err(|_| SendError::EncodingFailed)
+    }
+
+    /// Sends `data`, the encoded form of `msg`, to the server
+    async fn send_data(
+        &mut self,
+        msg: &Msg,
+        data: Vec<u8>,
+    ) -> Result<(), SendError> {
+        // Once a session is negotiated, the msg is sealed with its key and
+        // wrapped in a msg of its own with the same id
+        let session = self.state.lock().await.session.clone();
+        let data = match session {
+            Some(session) => {
+                let (nonce, data) = session
+                    .seal(&data)
+                    .map_err(|_| SendError::EncodingFailed)?;
+                let mut sealed =
+                    Msg::from(Request::Sealed(request::SealedArgs {
+                        nonce,
+                        data,
+                    }));
+                sealed.header.id = msg.header.id;
+                sealed.to_vec().map_err(|_| SendError::EncodingFailed)?
+            }
+            None => data,
+        };
+
+        // Check the size here as the wire processes msgs in the background,
+        // where it can only log that a msg was too large
+        if data.len() > self.max_msg_size {
+            return Err(SendError::MsgTooLarge {
+                request: msg.content.type_name().unwrap_or_default(),
+                size: data.len(),
+                max: self.max_msg_size,
+            });
+        }
+
         match &mut self.event_manager {
-            Either::Left(m) => {
+            ClientEventManager::Stream(m) => {
                 m.send(data).await.map_err(|_| SendError::SendFailed)
             }
-            Either::Right(m) => m
+            ClientEventManager::Socket(m) => m
                 .send_to(data, self.remote_addr)
                 .await
                 .map_err(|_| SendError::SendFailed),
+            ClientEventManager::SharedSocket(tx) => tx
+                .send((data, self.remote_addr))
+                .await
+                .map_err(|_| SendError::SendFailed),
         }
     }
 
@@ -134,6 +617,35 @@ impl ConnectedClient {
         }
     }
 
+    /// Estimates the offset of the clock of the server from that of the
+    /// client by exchanging timestamps `samples` times, at least once,
+    /// keeping the estimate of the exchange with the shortest round trip as
+    /// the one least skewed by delays in transit
+    pub async fn ask_clock_offset(
+        &mut self,
+        samples: usize,
+    ) -> Result<ClockOffset, AskError> {
+        let mut best: Option<ClockOffset> = None;
+        for _ in 0..samples.max(1) {
+            let args = request::TimeInfoArgs {
+                sent_at_micros: now_micros(),
+            };
+            let offset = match self.ask(Request::TimeInfo(args)).await? {
+                Reply::TimeInfo(args) => {
+                    ClockOffset::from_exchange(&args, now_micros())
+                }
+                x => return Err(make_ask_error(x)),
+            };
+
+            best = match best {
+                Some(x) if x.round_trip <= offset.round_trip => Some(x),
+                _ => Some(offset),
+            };
+        }
+
+        Ok(best.unwrap())
+    }
+
     /// Requests the capabilities from the server
     pub async fn ask_capabilities(
         &mut self,
@@ -144,16 +656,311 @@ impl ConnectedClient {
         }
     }
 
+    /// Requests the server to prove that it holds its identity key by
+    /// signing `challenge`
+    pub async fn ask_identify(
+        &mut self,
+        challenge: Vec<u8>,
+    ) -> Result<reply::IdentityArgs, AskError> {
+        match self
+            .ask(Request::Identify(IdentifyArgs { challenge }))
+            .await?
+        {
+            Reply::Identity(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Negotiates a key for the session of the client with the server, with
+    /// which every msg sent to and received from the server is then sealed
+    ///
+    /// The server must sign the public keys of the handshake with one of
+    /// `pinned_keys` if any are provided, proving that nothing in between
+    /// swapped out the keys in order to read the msgs of the session
+    pub async fn ask_handshake(
+        &mut self,
+        pinned_keys: &[Vec<u8>],
+    ) -> Result<(), AskError> {
+        // NOTE: A handshake made during a session is sealed with it, as is
+        //       its reply, so the new session only takes over once negotiated
+        let handshake = Handshake::new(Role::Initiator);
+        let public_key = handshake.public_key();
+        let args = match self
+            .ask(Request::Handshake(request::HandshakeArgs {
+                public_key: public_key.to_vec(),
+            }))
+            .await?
+        {
+            Reply::Handshake(args) => args,
+            x => return Err(make_ask_error(x)),
+        };
+
+        let signed = !args.signature.is_empty();
+        let verified = signed
+            && identity::verify(
+                &args.identity_key,
+                &handshake::signed_keys(&public_key, &args.public_key),
+                &args.signature,
+            );
+        let pinned = verified && pinned_keys.contains(&args.identity_key);
+        if (signed && !verified) || (!pinned_keys.is_empty() && !pinned) {
+            return Err(AskError::Failure {
+                msg: format!(
+                    "Server {} did not sign handshake with a pinned identity",
+                    self.remote_addr
+                ),
+                code: ErrorCode::SIGNATURE_REJECTED,
+            });
+        }
+
+        let key = handshake.finish(&args.public_key).map_err(|x| {
+            AskError::Failure {
+                msg: x.to_string(),
+                code: ErrorCode::INVALID_RESPONSE,
+            }
+        })?;
+        self.state.lock().await.session = Some(Session::new(&key));
+        Ok(())
+    }
+
+    /// Requests the server to store `blob` as its config, which must have a
+    /// `version` newer than that of the config already stored
+    pub async fn ask_push_config(
+        &mut self,
+        blob: Vec<u8>,
+        version: u64,
+    ) -> Result<reply::ConfigPushedArgs, AskError> {
+        match self
+            .ask(Request::PushConfig(PushConfigArgs { blob, version }))
+            .await?
+        {
+            Reply::ConfigPushed(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server start trusting the client keys with the
+    /// fingerprints of `add` and stop trusting those of `remove`, yielding
+    /// the fingerprints it trusts afterward
+    pub async fn ask_update_trusted_clients(
+        &mut self,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> Result<Vec<String>, AskError> {
+        match self
+            .ask(Request::UpdateTrustedClients(UpdateTrustedClientsArgs {
+                add,
+                remove,
+            }))
+            .await?
+        {
+            Reply::TrustedClients(args) => Ok(args.fingerprints),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests the config last pushed to the server
+    pub async fn ask_get_config(
+        &mut self,
+    ) -> Result<reply::ConfigArgs, AskError> {
+        match self.ask(Request::GetConfig).await? {
+            Reply::Config(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server send events to the webhooks of `add` and
+    /// stop sending them to those with the urls of `remove`, yielding the
+    /// webhooks it sends events to afterward
+    pub async fn ask_update_webhooks(
+        &mut self,
+        add: Vec<request::WebhookArgs>,
+        remove: Vec<String>,
+    ) -> Result<Vec<reply::WebhookArgs>, AskError> {
+        match self
+            .ask(Request::UpdateWebhooks(UpdateWebhooksArgs { add, remove }))
+            .await?
+        {
+            Reply::Webhooks(args) => Ok(args.webhooks),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Asks the server to forward `request` to the server at `address`,
+    /// yielding the reply of that server
+    ///
+    /// The request is encoded and signed here, so that it reaches the server
+    /// at `address` unchanged and still signed by this client
+    pub async fn ask_forward(
+        &mut self,
+        address: SocketAddr,
+        request: Request,
+    ) -> Result<Reply, AskError> {
+        let mut msg = Msg::from(request);
+        msg.header.flags = self.ask_options.flags();
+        let msg = self.encode_msg(&mut msg)?;
+
+        match self
+            .ask(Request::Forward(request::ForwardArgs { address, msg }))
+            .await?
+        {
+            Reply::Forward(args) => Ok(*args.reply),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server perform `action` on its host after waiting
+    /// `delay_secs`, yielding the token that the action must be confirmed
+    /// with through `ask_confirm_power_control` before it is performed
+    pub async fn ask_power_control(
+        &mut self,
+        action: PowerAction,
+        delay_secs: u64,
+    ) -> Result<reply::PowerControlArgs, AskError> {
+        match self
+            .ask(Request::PowerControl(request::PowerControlArgs {
+                action,
+                delay_secs,
+                token: None,
+            }))
+            .await?
+        {
+            Reply::PowerControl(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Confirms the power action yielded by `ask_power_control`, after which
+    /// the server performs it
+    pub async fn ask_confirm_power_control(
+        &mut self,
+        pending: &reply::PowerControlArgs,
+    ) -> Result<reply::PowerControlArgs, AskError> {
+        match self
+            .ask(Request::PowerControl(request::PowerControlArgs {
+                action: pending.action.clone(),
+                delay_secs: pending.delay_secs,
+                token: Some(pending.token.clone()),
+            }))
+            .await?
+        {
+            Reply::PowerControl(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests an archive of the durable metadata of the server, such as its
+    /// jobs and schedules, which can be imported into another server through
+    /// `ask_import_state`; requires the client to be signed by an admin key
+    pub async fn ask_export_state(
+        &mut self,
+        skip_job_output: bool,
+    ) -> Result<Vec<u8>, AskError> {
+        match self
+            .ask(Request::ExportState(request::ExportStateArgs {
+                skip_job_output,
+            }))
+            .await?
+        {
+            Reply::StateExported(args) => Ok(args.archive),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Imports an archive yielded by `ask_export_state` into the server,
+    /// keeping whatever the server already has where the two conflict
+    pub async fn ask_import_state(
+        &mut self,
+        archive: Vec<u8>,
+    ) -> Result<reply::StateImportedArgs, AskError> {
+        match self
+            .ask(Request::ImportState(request::ImportStateArgs { archive }))
+            .await?
+        {
+            Reply::StateImported(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Challenges the server to prove that it holds the secret half of the
+    /// identity key it presents, yielding the public half of that key or
+    /// none if the server has no identity key
+    pub async fn ask_server_identity(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let challenge = identity::new_challenge();
+        let args = match self.ask_identify(challenge.clone()).await {
+            Ok(args) => args,
+            Err(x) if x.code() == ErrorCode::NOT_FOUND => return Ok(None),
+            Err(AskError::Unsupported { .. }) => return Ok(None),
+            Err(x) => {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, x))
+            }
+        };
+
+        if identity::verify(&args.public_key, &challenge, &args.proof) {
+            Ok(Some(args.public_key))
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Server {} failed to prove identity {}",
+                    self.remote_addr,
+                    identity::fingerprint(&args.public_key)
+                ),
+            ))
+        }
+    }
+
+    /// Challenges the server to prove that it holds the secret half of one
+    /// of the public `pinned_keys`, failing if it cannot
+    pub async fn verify_server_identity(
+        &mut self,
+        pinned_keys: &[Vec<u8>],
+    ) -> io::Result<()> {
+        let public_key =
+            self.ask_server_identity().await?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("Server {} has no identity", self.remote_addr),
+                )
+            })?;
+
+        if pinned_keys.iter().any(|key| key == &public_key) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Server {} presented unpinned identity {}",
+                    self.remote_addr,
+                    identity::fingerprint(&public_key)
+                ),
+            ))
+        }
+    }
+
     /// Requests to create a new directory
     pub async fn ask_create_dir(
         &mut self,
-        path: String,
+        path: impl Into<RemotePath>,
+        include_components: bool,
+    ) -> Result<DirCreatedArgs, FileAskError> {
+        self.ask_create_dir_with_mode(path, include_components, None)
+            .await
+    }
+
+    /// Requests to create a new directory on the server, applying `mode` to
+    /// each created directory instead of the server's default
+    pub async fn ask_create_dir_with_mode(
+        &mut self,
+        path: impl Into<RemotePath>,
         include_components: bool,
+        mode: Option<u32>,
     ) -> Result<DirCreatedArgs, FileAskError> {
         let result = self
             .ask(Request::CreateDir(CreateDirArgs {
-                path,
+                path: path.into(),
                 include_components,
+                mode,
             }))
             .await;
 
@@ -170,11 +977,14 @@ impl ConnectedClient {
     /// Requests to rename an existing directory
     pub async fn ask_rename_dir(
         &mut self,
-        from: String,
-        to: String,
+        from: impl Into<RemotePath>,
+        to: impl Into<RemotePath>,
     ) -> Result<DirRenamedArgs, FileAskError> {
         let result = self
-            .ask(Request::RenameDir(RenameDirArgs { from, to }))
+            .ask(Request::RenameDir(RenameDirArgs {
+                from: from.into(),
+                to: to.into(),
+            }))
             .await;
 
         if let Err(x) = result {
@@ -190,11 +1000,14 @@ impl ConnectedClient {
     /// Requests to remove an existing directory
     pub async fn ask_remove_dir(
         &mut self,
-        path: String,
+        path: impl Into<RemotePath>,
         non_empty: bool,
     ) -> Result<DirRemovedArgs, FileAskError> {
         let result = self
-            .ask(Request::RemoveDir(RemoveDirArgs { path, non_empty }))
+            .ask(Request::RemoveDir(RemoveDirArgs {
+                path: path.into(),
+                non_empty,
+            }))
             .await;
 
         if let Err(x) = result {
@@ -207,20 +1020,15 @@ impl ConnectedClient {
         }
     }
 
-    /// Requests to get a list of the root directory's contents on the server
-    pub async fn ask_list_root_dir_contents(
-        &mut self,
-    ) -> Result<DirContentsListArgs, FileAskError> {
-        self.ask_list_dir_contents(String::from(".")).await
-    }
-
-    /// Requests to get a list of a directory's contents on the server
-    pub async fn ask_list_dir_contents(
+    /// Requests to create all of `dirs` followed by empty files at all of
+    /// `files` in a single round trip, along with their missing parent dirs
+    pub async fn ask_create_paths(
         &mut self,
-        path: String,
-    ) -> Result<DirContentsListArgs, FileAskError> {
+        dirs: Vec<RemotePath>,
+        files: Vec<RemotePath>,
+    ) -> Result<PathsCreatedArgs, FileAskError> {
         let result = self
-            .ask(Request::ListDirContents(ListDirContentsArgs { path }))
+            .ask(Request::CreatePaths(CreatePathsArgs { dirs, files }))
             .await;
 
         if let Err(x) = result {
@@ -228,36 +1036,26 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::DirContentsList(args) => Ok(args),
+            Reply::PathsCreated(args) => Ok(args),
             x => Err(make_file_ask_error(x)),
         }
     }
 
-    /// Requests to open a file for reading/writing on the server,
-    /// creating the file if it does not exist
-    pub async fn ask_open_file(
+    /// Requests to get a list of the root directory's contents on the server
+    pub async fn ask_list_root_dir_contents(
         &mut self,
-        path: String,
-    ) -> Result<FileOpenedArgs, FileAskError> {
-        self.ask_open_file_with_options(path, true, true, true)
-            .await
+    ) -> Result<DirContentsListArgs, FileAskError> {
+        self.ask_list_dir_contents(String::from(".")).await
     }
 
-    /// Requests to open a file on the server, opening using the provided options
-    pub async fn ask_open_file_with_options(
+    /// Requests to resolve a path on the server into its canonical form,
+    /// including whether or not anything exists at the path
+    pub async fn ask_resolve_path(
         &mut self,
-        path: String,
-        create: bool,
-        write: bool,
-        read: bool,
-    ) -> Result<FileOpenedArgs, FileAskError> {
+        path: impl Into<RemotePath>,
+    ) -> Result<PathResolvedArgs, FileAskError> {
         let result = self
-            .ask(Request::OpenFile(OpenFileArgs {
-                path: path.clone(),
-                create_if_missing: create,
-                write_access: write,
-                read_access: read,
-            }))
+            .ask(Request::ResolvePath(ResolvePathArgs { path: path.into() }))
             .await;
 
         if let Err(x) = result {
@@ -265,20 +1063,22 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::FileOpened(args) => Ok(args),
+            Reply::PathResolved(args) => Ok(args),
             x => Err(make_file_ask_error(x)),
         }
     }
 
-    /// Requests to close an open file
-    pub async fn ask_close_file(
+    /// Requests to detect the type of a file on the server by examining up
+    /// to `max_bytes` from its start, or the server's default if not provided
+    pub async fn ask_sniff_file(
         &mut self,
-        file: &RemoteFile,
-    ) -> Result<FileClosedArgs, FileAskError> {
+        path: impl Into<RemotePath>,
+        max_bytes: Option<u64>,
+    ) -> Result<FileSniffedArgs, FileAskError> {
         let result = self
-            .ask(Request::CloseFile(CloseFileArgs {
-                id: file.id,
-                sig: file.sig,
+            .ask(Request::SniffFile(SniffFileArgs {
+                path: path.into(),
+                max_bytes,
             }))
             .await;
 
@@ -287,22 +1087,23 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::FileClosed(args) => Ok(args),
+            Reply::FileSniffed(args) => Ok(args),
             x => Err(make_file_ask_error(x)),
         }
     }
 
-    /// Requests to rename an open file
-    pub async fn ask_rename_file(
+    /// Requests the checksum of a file on the server computed with
+    /// `algorithm`, letting a transferred file be verified without
+    /// downloading it again
+    pub async fn ask_file_checksum(
         &mut self,
-        file: &mut RemoteFile,
-        to: String,
-    ) -> Result<FileRenamedArgs, FileAskError> {
+        path: impl Into<RemotePath>,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<FileChecksumArgs, FileAskError> {
         let result = self
-            .ask(Request::RenameFile(RenameFileArgs {
-                id: file.id,
-                sig: file.sig,
-                to,
+            .ask(Request::GetFileChecksum(GetFileChecksumArgs {
+                path: path.into(),
+                algorithm,
             }))
             .await;
 
@@ -311,24 +1112,27 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::FileRenamed(args) => {
-                file.sig = args.sig;
-                Ok(args)
-            }
+            Reply::FileChecksum(args) => Ok(args),
             x => Err(make_file_ask_error(x)),
         }
     }
 
-    /// Requests to rename a non-open file
-    pub async fn ask_rename_unopened_file(
+    /// Requests the unified diff between two files on the server, or
+    /// between a file on the server and `contents_b` if provided
+    pub async fn ask_diff_files(
         &mut self,
-        from: String,
-        to: String,
-    ) -> Result<UnopenedFileRenamedArgs, FileAskError> {
+        path_a: impl Into<RemotePath>,
+        path_b: impl Into<RemotePath>,
+        contents_b: Option<Vec<u8>>,
+        context_lines: Option<u32>,
+    ) -> Result<FilesDiffedArgs, FileAskError> {
         let result = self
-            .ask(Request::RenameUnopenedFile(RenameUnopenedFileArgs {
-                from,
-                to,
+            .ask(Request::DiffFiles(DiffFilesArgs {
+                path_a: path_a.into(),
+                path_b: path_b.into(),
+                contents_b,
+                context_lines,
+                max_bytes: None,
             }))
             .await;
 
@@ -337,20 +1141,1140 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::UnopenedFileRenamed(args) => Ok(args),
+            Reply::FilesDiffed(args) => Ok(args),
             x => Err(make_file_ask_error(x)),
         }
     }
 
-    /// Requests to remove an open file
-    pub async fn ask_remove_file(
+    /// Requests the changes the server made to the filesystem after the
+    /// event with id `since`, where 0 requests every change still kept
+    pub async fn ask_recent_fs_events(
         &mut self,
-        file: &mut RemoteFile,
-    ) -> Result<FileRemovedArgs, FileAskError> {
+        since: u64,
+    ) -> Result<FsEventsArgs, AskError> {
+        match self
+            .ask(Request::RecentFsEvents(RecentFsEventsArgs { since }))
+            .await?
+        {
+            Reply::FsEvents(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests the filesystems mounted on the server (or its drives on
+    /// Windows), optionally including pseudo filesystems such as proc
+    pub async fn ask_list_mounts(
+        &mut self,
+        include_pseudo: bool,
+    ) -> Result<MountsListArgs, FileAskError> {
+        let result = self
+            .ask(Request::ListMounts(ListMountsArgs { include_pseudo }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::MountsList(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to get a list of a directory's contents on the server
+    pub async fn ask_list_dir_contents(
+        &mut self,
+        path: impl Into<RemotePath>,
+    ) -> Result<DirContentsListArgs, FileAskError> {
+        let result = self
+            .ask(Request::ListDirContents(ListDirContentsArgs {
+                path: path.into(),
+                stream: false,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::DirContentsList(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to list a directory's contents on the server, receiving
+    /// entries in chunks as the server reads the directory
+    ///
+    /// The timeout applies to each chunk rather than the entire listing
+    pub async fn ask_list_dir_stream(
+        &mut self,
+        path: impl Into<RemotePath>,
+    ) -> Result<impl Stream<Item = Result<DirEntry, FileAskError>>, FileAskError>
+    {
+        let timeout = self.timeout;
+        let (tx, rx) = mpsc::unbounded::<Reply>();
+        let msg = Msg::from(Request::ListDirContents(ListDirContentsArgs {
+            path: path.into(),
+            stream: true,
+        }));
+
+        // Keep forwarding replies until the last chunk is received or the
+        // stream has been dropped
+        self.state
+            .lock()
+            .await
+            .callback_manager
+            .add_repeating_callback(msg.header.id, move |reply| {
+                let has_more = match reply {
+                    Reply::DirContentsListChunk(args) => !args.last,
+                    _ => false,
+                };
+
+                tx.unbounded_send(reply.clone()).is_ok() && has_more
+            });
+
+        self.send_msg(msg).await.map_err(AskError::from)?;
+
+        let chunks =
+            stream::unfold(Some(rx), move |rx| next_dir_chunk(rx, timeout));
+
+        Ok(chunks.map(stream::iter).flatten())
+    }
+
+    /// Requests that the server apply the fs mutations `operations` in
+    /// order, undoing all of them if any fails
+    pub async fn ask_transaction(
+        &mut self,
+        operations: Vec<Request>,
+    ) -> Result<reply::TransactionArgs, AskError> {
+        match self
+            .ask(Request::Transaction(request::TransactionArgs::from(
+                operations,
+            )))
+            .await?
+        {
+            Reply::Transaction(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server execute `operations` in parallel, receiving
+    /// the result of each operation alongside its index as it completes
+    ///
+    /// The timeout applies to each result rather than the entire batch
+    pub async fn ask_batch_stream(
+        &mut self,
+        operations: Vec<Request>,
+    ) -> Result<impl Stream<Item = Result<(usize, Reply), AskError>>, AskError>
+    {
+        let timeout = self.timeout;
+        let (tx, rx) = mpsc::unbounded::<Reply>();
+        let msg = Msg::from(Request::Batch(request::BatchArgs {
+            operations,
+            stream_results: true,
+        }));
+
+        // Keep forwarding replies until the last result is received or the
+        // stream has been dropped
+        self.state
+            .lock()
+            .await
+            .callback_manager
+            .add_repeating_callback(msg.header.id, move |reply| {
+                let has_more = match reply {
+                    Reply::BatchResult(args) => !args.last,
+                    _ => false,
+                };
+
+                tx.unbounded_send(reply.clone()).is_ok() && has_more
+            });
+
+        self.send_msg(msg).await?;
+
+        Ok(stream::unfold(Some(rx), move |rx| {
+            next_batch_result(rx, timeout)
+        }))
+    }
+
+    /// Requests to open a file for reading/writing on the server,
+    /// creating the file if it does not exist
+    pub async fn ask_open_file(
+        &mut self,
+        path: impl Into<RemotePath>,
+    ) -> Result<FileOpenedArgs, FileAskError> {
+        self.ask_open_file_with_options(path, true, true, true, None)
+            .await
+    }
+
+    /// Requests to open a file on the server, opening using the provided options
+    pub async fn ask_open_file_with_options(
+        &mut self,
+        path: impl Into<RemotePath>,
+        create: bool,
+        write: bool,
+        read: bool,
+        mode: Option<u32>,
+    ) -> Result<FileOpenedArgs, FileAskError> {
+        let result = self
+            .ask(Request::OpenFile(OpenFileArgs {
+                path: path.into(),
+                create_if_missing: create,
+                write_access: write,
+                read_access: read,
+                mode,
+                modes: Default::default(),
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileOpened(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to open a file on the server for reading and writing,
+    /// creating it if missing, using `modes` such as appending to the file
+    /// or failing if it already exists
+    pub async fn ask_open_file_with_modes(
+        &mut self,
+        path: impl Into<RemotePath>,
+        modes: FileOpenModes,
+    ) -> Result<FileOpenedArgs, FileAskError> {
+        match self
+            .ask(Request::OpenFile(OpenFileArgs {
+                path: path.into(),
+                create_if_missing: true,
+                write_access: true,
+                read_access: true,
+                mode: None,
+                modes,
+            }))
+            .await?
+        {
+            Reply::FileOpened(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to close an open file
+    pub async fn ask_close_file(
+        &mut self,
+        file: &RemoteFile,
+    ) -> Result<FileClosedArgs, FileAskError> {
+        let result = self
+            .ask(Request::CloseFile(CloseFileArgs {
+                id: file.id,
+                sig: file.sig,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileClosed(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to rename an open file
+    pub async fn ask_rename_file(
+        &mut self,
+        file: &mut RemoteFile,
+        to: impl Into<RemotePath>,
+    ) -> Result<FileRenamedArgs, FileAskError> {
+        self.ask_rename_file_with_preconditions(file, to, None)
+            .await
+    }
+
+    /// Same as `ask_rename_file`, but fails without acting if any
+    /// of the `preconditions` do not hold on the server
+    pub async fn ask_rename_file_with_preconditions(
+        &mut self,
+        file: &mut RemoteFile,
+        to: impl Into<RemotePath>,
+        preconditions: Option<FilePreconditions>,
+    ) -> Result<FileRenamedArgs, FileAskError> {
+        let result = self
+            .ask(Request::RenameFile(RenameFileArgs {
+                id: file.id,
+                sig: file.sig,
+                to: to.into(),
+                preconditions,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileRenamed(args) => {
+                file.sig = args.sig;
+                Ok(args)
+            }
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to rename a non-open file
+    pub async fn ask_rename_unopened_file(
+        &mut self,
+        from: impl Into<RemotePath>,
+        to: impl Into<RemotePath>,
+    ) -> Result<UnopenedFileRenamedArgs, FileAskError> {
+        self.ask_rename_unopened_file_with_preconditions(from, to, None)
+            .await
+    }
+
+    /// Same as `ask_rename_unopened_file`, but fails without acting if any
+    /// of the `preconditions` do not hold on the server
+    pub async fn ask_rename_unopened_file_with_preconditions(
+        &mut self,
+        from: impl Into<RemotePath>,
+        to: impl Into<RemotePath>,
+        preconditions: Option<FilePreconditions>,
+    ) -> Result<UnopenedFileRenamedArgs, FileAskError> {
+        let result = self
+            .ask(Request::RenameUnopenedFile(RenameUnopenedFileArgs {
+                from: from.into(),
+                to: to.into(),
+                preconditions,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::UnopenedFileRenamed(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to remove an open file
+    pub async fn ask_remove_file(
+        &mut self,
+        file: &mut RemoteFile,
+    ) -> Result<FileRemovedArgs, FileAskError> {
+        self.ask_remove_file_with_preconditions(file, None).await
+    }
+
+    /// Same as `ask_remove_file`, but fails without acting if any
+    /// of the `preconditions` do not hold on the server
+    pub async fn ask_remove_file_with_preconditions(
+        &mut self,
+        file: &mut RemoteFile,
+        preconditions: Option<FilePreconditions>,
+    ) -> Result<FileRemovedArgs, FileAskError> {
         let result = self
             .ask(Request::RemoveFile(RemoveFileArgs {
                 id: file.id,
                 sig: file.sig,
+                preconditions,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileRemoved(args) => {
+                file.sig = args.sig;
+                Ok(args)
+            }
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to remove a non-open file
+    pub async fn ask_remove_unopened_file(
+        &mut self,
+        path: impl Into<RemotePath>,
+    ) -> Result<UnopenedFileRemovedArgs, FileAskError> {
+        self.ask_remove_unopened_file_with_preconditions(path, None)
+            .await
+    }
+
+    /// Same as `ask_remove_unopened_file`, but fails without acting if any
+    /// of the `preconditions` do not hold on the server
+    pub async fn ask_remove_unopened_file_with_preconditions(
+        &mut self,
+        path: impl Into<RemotePath>,
+        preconditions: Option<FilePreconditions>,
+    ) -> Result<UnopenedFileRemovedArgs, FileAskError> {
+        let result = self
+            .ask(Request::RemoveUnopenedFile(RemoveUnopenedFileArgs {
+                path: path.into(),
+                preconditions,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::UnopenedFileRemoved(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests the full contents of a file on the server
+    pub async fn ask_read_file(
+        &mut self,
+        file: &RemoteFile,
+    ) -> Result<FileContentsArgs, FileAskError> {
+        self.ask_read_file_if_none_match(file, None).await
+    }
+
+    /// Same as `ask_read_file`, but omits the contents from the reply if
+    /// the file still matches the etag `if_none_match`
+    pub async fn ask_read_file_if_none_match(
+        &mut self,
+        file: &RemoteFile,
+        if_none_match: Option<String>,
+    ) -> Result<FileContentsArgs, FileAskError> {
+        let result = self
+            .ask(Request::ReadFile(ReadFileArgs {
+                id: file.id,
+                sig: file.sig,
+                if_none_match,
+                offset: 0,
+                len: None,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileContents(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests the full contents of a file on the server in chunks of up to
+    /// `chunk_size` bytes, keeping up to `window` chunks awaiting a reply at
+    /// once and reassembling them in order
+    ///
+    /// The chunk size is limited to `max_read_chunk_size` so that each chunk
+    /// fits within a single msg. Like `ask_read_file_if_none_match`, the
+    /// contents are omitted if the file still matches the etag
+    /// `if_none_match`
+    pub async fn ask_read_file_chunked(
+        &mut self,
+        file: &RemoteFile,
+        if_none_match: Option<String>,
+        chunk_size: u64,
+        window: usize,
+    ) -> Result<FileContentsArgs, FileAskError> {
+        let chunk_size = chunk_size.max(1).min(self.max_read_chunk_size());
+        let read_chunk = |offset, if_none_match| {
+            Request::ReadFile(ReadFileArgs {
+                id: file.id,
+                sig: file.sig,
+                if_none_match,
+                offset,
+                len: Some(chunk_size),
+            })
+        };
+
+        // The first chunk reveals the size of the file, and whether it needs
+        // to be read at all
+        let mut args = match self.ask(read_chunk(0, if_none_match)).await? {
+            Reply::FileContents(args) => args,
+            x => return Err(make_file_ask_error(x)),
+        };
+
+        // Servers that do not support ranged reads reply with the entire
+        // file instead
+        let size = match args.size {
+            Some(size) if !args.not_modified => size,
+            _ => return Ok(args),
+        };
+
+        let requests = (args.contents.len() as u64..size)
+            .step_by(chunk_size as usize)
+            .map(|offset| read_chunk(offset, None))
+            .collect();
+        for reply in self.ask_pipelined(requests, window).await? {
+            match reply {
+                Reply::FileContents(chunk) => {
+                    args.contents.extend(chunk.contents);
+                    args.sig = chunk.sig;
+                }
+                x => return Err(make_file_ask_error(x)),
+            }
+        }
+
+        args.etag = format!("{:x}", Sha256::digest(&args.contents));
+        Ok(args)
+    }
+
+    /// Downloads the file at `path` on the server into `writer`, asking for
+    /// it in chunks that are written in order as they arrive
+    ///
+    /// The contents are hashed as they are written and checked against the
+    /// digest of the whole file reported by the server, failing with invalid
+    /// data if they differ. Chunks whose asks time out or fail to be sent are
+    /// asked for again, and `on_progress` is called after each chunk is
+    /// written. Yields the reply to the first chunk without its contents,
+    /// where nothing is written if the file still matches the etag of the
+    /// options.
+    pub async fn download_to<W, F>(
+        &mut self,
+        path: impl Into<RemotePath>,
+        writer: &mut W,
+        options: DownloadOptions,
+        mut on_progress: F,
+    ) -> Result<FileContentsArgs, FileAskError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(DownloadProgress),
+    {
+        let file = self
+            .ask_open_file_with_options(path, false, false, true, None)
+            .await?
+            .into();
+        let result = self
+            .download_file_to(&file, writer, options, &mut on_progress)
+            .await;
+        let closed = self.ask_close_file(&file).await;
+
+        let args = result?;
+        closed?;
+        Ok(args)
+    }
+
+    /// Continuously mirrors the directory `remote_dir` on the server into
+    /// `local_dir`, bringing them in sync every interval of the options and
+    /// calling `on_pass` with what each pass changed
+    ///
+    /// Only returns once a pass fails; see `Mirror` to run passes on demand.
+    pub async fn mirror<F>(
+        &mut self,
+        remote_dir: impl Into<RemotePath>,
+        local_dir: impl Into<std::path::PathBuf>,
+        options: MirrorOptions,
+        mut on_pass: F,
+    ) -> Result<(), FileAskError>
+    where
+        F: FnMut(&MirrorPass),
+    {
+        let mut mirror = Mirror::new(remote_dir, local_dir, options);
+        loop {
+            on_pass(&mirror.sync(self).await?);
+            tokio::time::delay_for(mirror.interval()).await;
+        }
+    }
+
+    /// Uploads every dir and file within the local directory `local_dir`
+    /// into `remote_dir` on the server, creating it if missing and yielding
+    /// what was copied
+    ///
+    /// Fails at the first dir or file that cannot be copied, leaving
+    /// whatever was copied before it in place.
+    pub async fn upload_dir(
+        &mut self,
+        local_dir: impl AsRef<std::path::Path>,
+        remote_dir: impl Into<RemotePath>,
+    ) -> Result<DirCopy, FileAskError> {
+        copy::upload_dir(self, local_dir.as_ref(), &remote_dir.into()).await
+    }
+
+    /// Downloads every dir and file within `remote_dir` on the server into
+    /// the local directory `local_dir`, creating it if missing and yielding
+    /// what was copied
+    ///
+    /// Fails at the first dir or file that cannot be copied, leaving
+    /// whatever was copied before it in place.
+    pub async fn download_dir(
+        &mut self,
+        remote_dir: impl Into<RemotePath>,
+        local_dir: impl AsRef<std::path::Path>,
+    ) -> Result<DirCopy, FileAskError> {
+        self.download_dir_with_options(
+            remote_dir,
+            local_dir,
+            DownloadOptions::default(),
+        )
+        .await
+    }
+
+    /// Same as `download_dir`, but downloads each file with `options`
+    pub async fn download_dir_with_options(
+        &mut self,
+        remote_dir: impl Into<RemotePath>,
+        local_dir: impl AsRef<std::path::Path>,
+        options: DownloadOptions,
+    ) -> Result<DirCopy, FileAskError> {
+        copy::download_dir(
+            self,
+            &remote_dir.into(),
+            local_dir.as_ref(),
+            options,
+        )
+        .await
+    }
+
+    async fn download_file_to<W, F>(
+        &mut self,
+        file: &RemoteFile,
+        writer: &mut W,
+        options: DownloadOptions,
+        on_progress: &mut F,
+    ) -> Result<FileContentsArgs, FileAskError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(DownloadProgress),
+    {
+        let chunk_size =
+            options.chunk_size.max(1).min(self.max_read_chunk_size());
+        let read_chunk = |offset, if_none_match| {
+            Request::ReadFile(ReadFileArgs {
+                id: file.id,
+                sig: file.sig,
+                if_none_match,
+                offset,
+                len: Some(chunk_size),
+            })
+        };
+
+        // Asking with an etag has the server report the digest of the whole
+        // file along with the first chunk, where an empty etag never matches
+        let if_none_match = options.if_none_match.unwrap_or_default();
+        let mut args = match self
+            .ask_with_retries(
+                read_chunk(0, Some(if_none_match)),
+                options.retries,
+            )
+            .await?
+        {
+            Reply::FileContents(args) => args,
+            x => return Err(make_file_ask_error(x)),
+        };
+        if args.not_modified {
+            return Ok(args);
+        }
+
+        // Servers that do not support ranged reads reply with the entire
+        // file instead
+        let contents = std::mem::take(&mut args.contents);
+        let first_len = contents.len() as u64;
+        let total = args.size.unwrap_or(first_len);
+        let mut hasher = Sha256::new();
+        let mut written = 0;
+        let mut write_chunk = |contents: Vec<u8>| {
+            hasher.input(&contents);
+            written += contents.len() as u64;
+            on_progress(DownloadProgress { written, total });
+            contents
+        };
+
+        writer.write_all(&write_chunk(contents)).await?;
+        let offsets: Vec<u64> =
+            (first_len..total).step_by(chunk_size as usize).collect();
+        for offsets in offsets.chunks(options.window.max(1)) {
+            let requests: Vec<Request> =
+                offsets.iter().map(|x| read_chunk(*x, None)).collect();
+            let replies = match self
+                .ask_pipelined(requests.clone(), options.window)
+                .await
+            {
+                Ok(replies) => replies,
+                Err(x) if options.retries > 0 && is_transient(&x) => {
+                    warn!(
+                        "Retrying chunks of {} one at a time: {}",
+                        file.path, x
+                    );
+                    let mut replies = Vec::with_capacity(requests.len());
+                    for request in requests {
+                        replies.push(
+                            self.ask_with_retries(request, options.retries)
+                                .await?,
+                        );
+                    }
+                    replies
+                }
+                Err(x) => return Err(From::from(x)),
+            };
+
+            for reply in replies {
+                match reply {
+                    Reply::FileContents(chunk) => {
+                        writer.write_all(&write_chunk(chunk.contents)).await?;
+                        args.sig = chunk.sig;
+                    }
+                    x => return Err(make_file_ask_error(x)),
+                }
+            }
+        }
+        writer.flush().await?;
+
+        let etag = format!("{:x}", hasher.result());
+        if etag != args.etag {
+            return Err(FileAskError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Downloaded {} has digest {}, expected {}",
+                    file.path, etag, args.etag
+                ),
+            )));
+        }
+
+        Ok(args)
+    }
+
+    /// Asks the server to fulfill `request`, asking again up to `retries`
+    /// times if the ask times out or fails to be sent
+    async fn ask_with_retries(
+        &mut self,
+        request: Request,
+        retries: usize,
+    ) -> Result<Reply, AskError> {
+        let mut attempts = 0;
+        loop {
+            match self.ask(request.clone()).await {
+                Err(x) if attempts < retries && is_transient(&x) => {
+                    attempts += 1;
+                    warn!(
+                        "Retrying {} (attempt {}): {}",
+                        request.class(),
+                        attempts,
+                        x
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Maximum bytes of a file that can be requested at once such that the
+    /// reply fits within a single msg
+    pub fn max_read_chunk_size(&self) -> u64 {
+        // NOTE: Contents are encoded as an array of integers, where each byte
+        //       takes up to two bytes once encoded
+        let max = self.max_msg_size.saturating_sub(READ_CHUNK_OVERHEAD) / 2;
+        max.max(1) as u64
+    }
+
+    /// Requests the full contents of many unopened files on the server,
+    /// optionally limiting the total bytes returned across all files
+    pub async fn ask_read_files(
+        &mut self,
+        paths: Vec<RemotePath>,
+        max_total_bytes: Option<u64>,
+    ) -> Result<FilesContentsArgs, FileAskError> {
+        let result = self
+            .ask(Request::ReadFiles(ReadFilesArgs {
+                paths,
+                max_total_bytes,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FilesContents(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to write the contents of a file on the server
+    pub async fn ask_write_file(
+        &mut self,
+        file: &mut RemoteFile,
+        contents: &[u8],
+    ) -> Result<FileWrittenArgs, FileAskError> {
+        self.ask_write_file_with_preconditions(file, contents, None)
+            .await
+    }
+
+    /// Same as `ask_write_file`, but fails without acting if any
+    /// of the `preconditions` do not hold on the server
+    pub async fn ask_write_file_with_preconditions(
+        &mut self,
+        file: &mut RemoteFile,
+        contents: &[u8],
+        preconditions: Option<FilePreconditions>,
+    ) -> Result<FileWrittenArgs, FileAskError> {
+        let result = self
+            .ask(Request::WriteFile(WriteFileArgs {
+                id: file.id,
+                sig: file.sig,
+                contents: contents.to_vec(),
+                preconditions,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileWritten(args) => {
+                file.sig = args.sig;
+                Ok(args)
+            }
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to open (creating if missing), write, and close a file on
+    /// the server in a single operation
+    pub async fn ask_write_file_atomic_by_path(
+        &mut self,
+        path: impl Into<RemotePath>,
+        data: &[u8],
+        mode: Option<u32>,
+    ) -> Result<AtomicFileWrittenArgs, FileAskError> {
+        let result = self
+            .ask(Request::WriteFileAtomicByPath(WriteFileAtomicByPathArgs {
+                path: path.into(),
+                data: data.to_vec(),
+                mode,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::AtomicFileWritten(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to apply line-based `edits` to a file on the server, where
+    /// either all edits are applied or none of them
+    ///
+    /// If `expected_hash` is provided, the edits are only applied if the
+    /// file's current contents have that hex-encoded SHA-256 hash
+    pub async fn ask_patch_file_lines(
+        &mut self,
+        path: impl Into<RemotePath>,
+        edits: Vec<LineEdit>,
+        expected_hash: Option<String>,
+    ) -> Result<FileLinesPatchedArgs, FileAskError> {
+        let result = self
+            .ask(Request::PatchFileLines(PatchFileLinesArgs {
+                path: path.into(),
+                edits,
+                expected_hash,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::FileLinesPatched(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to prepare many files for upload at once, yielding a session
+    /// per file that is either ready to be written or already up-to-date
+    ///
+    /// Files that list their chunks are uploaded one chunk at a time, where
+    /// each ready session reports the chunks the server already holds.
+    pub async fn ask_upload_manifest(
+        &mut self,
+        files: Vec<ManifestFile>,
+    ) -> Result<UploadManifestPreparedArgs, FileAskError> {
+        let result = self
+            .ask(Request::UploadManifest(UploadManifestArgs { files }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::UploadManifestPrepared(args) => Ok(args),
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to write the chunk at `index` of a file being uploaded in
+    /// chunks, which must match the hash listed for it in the manifest
+    pub async fn ask_write_upload_chunk(
+        &mut self,
+        file: &mut RemoteFile,
+        index: u32,
+        data: &[u8],
+    ) -> Result<UploadChunkWrittenArgs, FileAskError> {
+        let result = self
+            .ask(Request::WriteUploadChunk(WriteUploadChunkArgs {
+                id: file.id,
+                sig: file.sig,
+                index,
+                data: data.to_vec(),
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::UploadChunkWritten(args) => {
+                file.sig = args.sig;
+                Ok(args)
+            }
+            x => Err(make_file_ask_error(x)),
+        }
+    }
+
+    /// Requests to execute a process on the server, providing support to
+    /// send lines of text via stdin and reading back lines of text via
+    /// stdout and stderr
+    pub async fn ask_exec_proc(
+        &mut self,
+        command: String,
+        args: Vec<String>,
+    ) -> Result<ProcStartedArgs, ExecAskError> {
+        self.ask_exec_proc_with_options(command, args, true, true, true, None)
+            .await
+    }
+
+    /// Requests to execute a process on the server, providing support to
+    /// send lines of text via stdin and reading back lines of text via
+    /// stdout and stderr
+    pub async fn ask_exec_proc_with_current_dir(
+        &mut self,
+        command: String,
+        args: Vec<String>,
+        current_dir: String,
+    ) -> Result<ProcStartedArgs, ExecAskError> {
+        self.ask_exec_proc_with_options(
+            command,
+            args,
+            true,
+            true,
+            true,
+            Some(current_dir),
+        )
+        .await
+    }
+
+    /// Requests to execute a process on the server, indicating whether to
+    /// ignore or use stdin, stdout, and stderr
+    pub async fn ask_exec_proc_with_options(
+        &mut self,
+        command: String,
+        args: Vec<String>,
+        stdin: bool,
+        stdout: bool,
+        stderr: bool,
+        current_dir: Option<String>,
+    ) -> Result<ProcStartedArgs, ExecAskError> {
+        self.ask_exec_proc_with_args(ExecProcArgs {
+            command,
+            args,
+            stdin,
+            stdout,
+            stderr,
+            current_dir,
+            umask: None,
+            detached: false,
+            stdout_file: None,
+            stderr_file: None,
+            io_mode: ProcIoMode::Raw,
+            newline: None,
+            labels: Vec::new(),
+            initial_stdin: None,
+            close_stdin: false,
+        })
+        .await
+    }
+
+    /// Requests to execute a process on the server using the full set of
+    /// `args`, such as applying a umask or detaching the process
+    pub async fn ask_exec_proc_with_args(
+        &mut self,
+        args: ExecProcArgs,
+    ) -> Result<ProcStartedArgs, ExecAskError> {
+        let result = self.ask(Request::ExecProc(args)).await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::ProcStarted(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to run `body` as a script using `interpreter` on the server,
+    /// capturing stdin, stdout, and stderr like any other process
+    pub async fn ask_exec_script(
+        &mut self,
+        interpreter: String,
+        body: String,
+        args: Vec<String>,
+    ) -> Result<ProcStartedArgs, ExecAskError> {
+        let result = self
+            .ask(Request::ExecScript(ExecScriptArgs {
+                interpreter,
+                body,
+                args,
+                stdin: true,
+                stdout: true,
+                stderr: true,
+                current_dir: None,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::ProcStarted(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to execute a process on the server attached to a
+    /// pseudo-terminal, whose output is all read as stdout
+    pub async fn ask_exec_proc_pty(
+        &mut self,
+        args: ExecProcPtyArgs,
+    ) -> Result<ProcStartedArgs, ExecAskError> {
+        let result = self.ask(Request::ExecProcPty(args)).await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::ProcStarted(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to change the size of the pseudo-terminal of a remote
+    /// process on the server
+    pub async fn ask_resize_pty(
+        &mut self,
+        proc: &RemoteProc,
+        rows: u16,
+        cols: u16,
+    ) -> Result<PtyResizedArgs, ExecAskError> {
+        let result = self
+            .ask(Request::ResizePty(ResizePtyArgs {
+                id: proc.id,
+                rows,
+                cols,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::PtyResized(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to send lines of text to stdin of a remote process on the server
+    pub async fn ask_write_proc_stdin(
+        &mut self,
+        proc: &RemoteProc,
+        input: &[u8],
+    ) -> Result<ProcStdinWrittenArgs, ExecAskError> {
+        let result = self
+            .ask(Request::WriteProcStdin(WriteProcStdinArgs {
+                id: proc.id,
+                input: input.to_vec(),
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::ProcStdinWritten(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to get all stdout from a remote process on the server since
+    /// the last ask was made
+    pub async fn ask_read_proc_stdout(
+        &mut self,
+        proc: &RemoteProc,
+    ) -> Result<ProcStdoutContentsArgs, ExecAskError> {
+        self.ask_read_proc_stdout_filtered(proc, ProcOutputFilter::default())
+            .await
+    }
+
+    /// Requests to get the stdout from a remote process on the server since
+    /// the last ask was made, having the server skip ahead to an offset,
+    /// limit how much is returned, or only return lines matching a pattern
+    pub async fn ask_read_proc_stdout_filtered(
+        &mut self,
+        proc: &RemoteProc,
+        filter: ProcOutputFilter,
+    ) -> Result<ProcStdoutContentsArgs, ExecAskError> {
+        let result = self
+            .ask(Request::ReadProcStdout(ReadProcStdoutArgs {
+                id: proc.id,
+                filter,
+            }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::ProcStdoutContents(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to get all stderr from a remote process on the server since
+    /// the last ask was made
+    pub async fn ask_read_proc_stderr(
+        &mut self,
+        proc: &RemoteProc,
+    ) -> Result<ProcStderrContentsArgs, ExecAskError> {
+        self.ask_read_proc_stderr_filtered(proc, ProcOutputFilter::default())
+            .await
+    }
+
+    /// Requests to get the stderr from a remote process on the server since
+    /// the last ask was made, having the server skip ahead to an offset,
+    /// limit how much is returned, or only return lines matching a pattern
+    pub async fn ask_read_proc_stderr_filtered(
+        &mut self,
+        proc: &RemoteProc,
+        filter: ProcOutputFilter,
+    ) -> Result<ProcStderrContentsArgs, ExecAskError> {
+        let result = self
+            .ask(Request::ReadProcStderr(ReadProcStderrArgs {
+                id: proc.id,
+                filter,
             }))
             .await;
 
@@ -359,21 +2283,18 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::FileRemoved(args) => {
-                file.sig = args.sig;
-                Ok(args)
-            }
-            x => Err(make_file_ask_error(x)),
+            Reply::ProcStderrContents(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to remove a non-open file
-    pub async fn ask_remove_unopened_file(
+    /// Requests to read the status of a remote process on the server
+    pub async fn ask_read_proc_status(
         &mut self,
-        path: String,
-    ) -> Result<UnopenedFileRemovedArgs, FileAskError> {
+        proc: &RemoteProc,
+    ) -> Result<ProcStatusArgs, ExecAskError> {
         let result = self
-            .ask(Request::RemoveUnopenedFile(RemoveUnopenedFileArgs { path }))
+            .ask(Request::ReadProcStatus(ReadProcStatusArgs { id: proc.id }))
             .await;
 
         if let Err(x) = result {
@@ -381,21 +2302,19 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::UnopenedFileRemoved(args) => Ok(args),
-            x => Err(make_file_ask_error(x)),
+            Reply::ProcStatus(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests the full contents of a file on the server
-    pub async fn ask_read_file(
+    /// Requests to read a remote process on the server along with all of
+    /// its descendants
+    pub async fn ask_read_proc_tree(
         &mut self,
-        file: &RemoteFile,
-    ) -> Result<FileContentsArgs, FileAskError> {
+        proc: &RemoteProc,
+    ) -> Result<ProcTreeArgs, ExecAskError> {
         let result = self
-            .ask(Request::ReadFile(ReadFileArgs {
-                id: file.id,
-                sig: file.sig,
-            }))
+            .ask(Request::ReadProcTree(ReadProcTreeArgs { id: proc.id }))
             .await;
 
         if let Err(x) = result {
@@ -403,22 +2322,26 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::FileContents(args) => Ok(args),
-            x => Err(make_file_ask_error(x)),
+            Reply::ProcTree(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to write the contents of a file on the server
-    pub async fn ask_write_file(
+    /// Yields the exit of a remote process on the server if the server
+    /// pushed it to this client, without asking the server
+    pub async fn proc_exit(&self, proc: &RemoteProc) -> Option<ProcStatusArgs> {
+        self.state.lock().await.proc_exits.get(&proc.id).cloned()
+    }
+
+    /// Requests to read the CPU and memory used by a remote process on the
+    /// server
+    pub async fn ask_read_proc_resources(
         &mut self,
-        file: &mut RemoteFile,
-        contents: &[u8],
-    ) -> Result<FileWrittenArgs, FileAskError> {
+        proc: &RemoteProc,
+    ) -> Result<ProcResourcesArgs, ExecAskError> {
         let result = self
-            .ask(Request::WriteFile(WriteFileArgs {
-                id: file.id,
-                sig: file.sig,
-                contents: contents.to_vec(),
+            .ask(Request::ReadProcResources(ReadProcResourcesArgs {
+                id: proc.id,
             }))
             .await;
 
@@ -427,66 +2350,60 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::FileWritten(args) => {
-                file.sig = args.sig;
-                Ok(args)
-            }
-            x => Err(make_file_ask_error(x)),
+            Reply::ProcResources(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to execute a process on the server, providing support to
-    /// send lines of text via stdin and reading back lines of text via
-    /// stdout and stderr
-    pub async fn ask_exec_proc(
+    /// Requests how a remote process was spawned on the server, including
+    /// the environment, directory, and binary it was started with
+    pub async fn ask_read_proc_info(
         &mut self,
-        command: String,
-        args: Vec<String>,
-    ) -> Result<ProcStartedArgs, ExecAskError> {
-        self.ask_exec_proc_with_options(command, args, true, true, true, None)
-            .await
+        proc: &RemoteProc,
+    ) -> Result<ProcInfoArgs, ExecAskError> {
+        let result = self
+            .ask(Request::ReadProcInfo(ReadProcInfoArgs { id: proc.id }))
+            .await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::ProcInfo(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
     }
 
-    /// Requests to execute a process on the server, providing support to
-    /// send lines of text via stdin and reading back lines of text via
-    /// stdout and stderr
-    pub async fn ask_exec_proc_with_current_dir(
+    /// Requests a page of the procs tracked by the server, filtered by
+    /// status, label, and age
+    pub async fn ask_list_procs(
         &mut self,
-        command: String,
-        args: Vec<String>,
-        current_dir: String,
-    ) -> Result<ProcStartedArgs, ExecAskError> {
-        self.ask_exec_proc_with_options(
-            command,
-            args,
-            true,
-            true,
-            true,
-            Some(current_dir),
-        )
-        .await
+        args: ListProcsArgs,
+    ) -> Result<ProcsListArgs, AskError> {
+        match self.ask(Request::ListProcs(args)).await? {
+            Reply::ProcsList(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
     }
 
-    /// Requests to execute a process on the server, indicating whether to
-    /// ignore or use stdin, stdout, and stderr
-    pub async fn ask_exec_proc_with_options(
+    /// Requests to kill a remote process on the server
+    pub async fn ask_proc_kill(
         &mut self,
-        command: String,
-        args: Vec<String>,
-        stdin: bool,
-        stdout: bool,
-        stderr: bool,
-        current_dir: Option<String>,
-    ) -> Result<ProcStartedArgs, ExecAskError> {
+        proc: &RemoteProc,
+    ) -> Result<ProcKilledArgs, ExecAskError> {
+        self.ask_proc_kill_with_options(proc, false).await
+    }
+
+    /// Requests to kill a remote process on the server, also killing all of
+    /// its descendants if `tree` is true
+    pub async fn ask_proc_kill_with_options(
+        &mut self,
+        proc: &RemoteProc,
+        tree: bool,
+    ) -> Result<ProcKilledArgs, ExecAskError> {
         let result = self
-            .ask(Request::ExecProc(ExecProcArgs {
-                command,
-                args,
-                stdin,
-                stdout,
-                stderr,
-                current_dir,
-            }))
+            .ask(Request::KillProc(KillProcArgs { id: proc.id, tree }))
             .await;
 
         if let Err(x) = result {
@@ -494,62 +2411,53 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::ProcStarted(args) => Ok(args),
+            Reply::ProcKilled(args) => Ok(args),
             x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to send lines of text to stdin of a remote process on the server
-    pub async fn ask_write_proc_stdin(
+    /// Requests to run a process on the server as a job whose output and
+    /// outcome can be retrieved later, even from another connection
+    pub async fn ask_submit_job(
         &mut self,
-        proc: &RemoteProc,
-        input: &[u8],
-    ) -> Result<ProcStdinWrittenArgs, ExecAskError> {
-        let result = self
-            .ask(Request::WriteProcStdin(WriteProcStdinArgs {
-                id: proc.id,
-                input: input.to_vec(),
-            }))
-            .await;
+        spec: JobSpec,
+    ) -> Result<JobSubmittedArgs, ExecAskError> {
+        let result = self.ask(Request::SubmitJob(SubmitJobArgs { spec })).await;
 
         if let Err(x) = result {
             return Err(From::from(x));
         }
 
         match result.unwrap() {
-            Reply::ProcStdinWritten(args) => Ok(args),
+            Reply::JobSubmitted(args) => Ok(args),
             x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to get all stdout from a remote process on the server since
-    /// the last ask was made
-    pub async fn ask_read_proc_stdout(
+    /// Requests the status of a previously-submitted job
+    pub async fn ask_query_job(
         &mut self,
-        proc: &RemoteProc,
-    ) -> Result<ProcStdoutContentsArgs, ExecAskError> {
-        let result = self
-            .ask(Request::ReadProcStdout(ReadProcStdoutArgs { id: proc.id }))
-            .await;
+        id: u32,
+    ) -> Result<JobStatusArgs, ExecAskError> {
+        let result = self.ask(Request::QueryJob(QueryJobArgs { id })).await;
 
         if let Err(x) = result {
             return Err(From::from(x));
         }
 
         match result.unwrap() {
-            Reply::ProcStdoutContents(args) => Ok(args),
+            Reply::JobStatus(args) => Ok(args),
             x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to get all stderr from a remote process on the server since
-    /// the last ask was made
-    pub async fn ask_read_proc_stderr(
+    /// Requests all output captured so far for a previously-submitted job
+    pub async fn ask_collect_job_output(
         &mut self,
-        proc: &RemoteProc,
-    ) -> Result<ProcStderrContentsArgs, ExecAskError> {
+        id: u32,
+    ) -> Result<JobOutputArgs, ExecAskError> {
         let result = self
-            .ask(Request::ReadProcStderr(ReadProcStderrArgs { id: proc.id }))
+            .ask(Request::CollectJobOutput(CollectJobOutputArgs { id }))
             .await;
 
         if let Err(x) = result {
@@ -557,18 +2465,22 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::ProcStderrContents(args) => Ok(args),
+            Reply::JobOutput(args) => Ok(args),
             x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to read the status of a remote process on the server
-    pub async fn ask_read_proc_status(
+    /// Requests to submit a job matching `spec` each time `trigger` fires
+    pub async fn ask_create_schedule(
         &mut self,
-        proc: &RemoteProc,
-    ) -> Result<ProcStatusArgs, ExecAskError> {
+        spec: JobSpec,
+        trigger: ScheduleTrigger,
+    ) -> Result<ScheduleCreatedArgs, ExecAskError> {
         let result = self
-            .ask(Request::ReadProcStatus(ReadProcStatusArgs { id: proc.id }))
+            .ask(Request::CreateSchedule(CreateScheduleArgs {
+                spec,
+                trigger,
+            }))
             .await;
 
         if let Err(x) = result {
@@ -576,18 +2488,34 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::ProcStatus(args) => Ok(args),
+            Reply::ScheduleCreated(args) => Ok(args),
             x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests to kill a remote process on the server
-    pub async fn ask_proc_kill(
+    /// Requests all schedules on the server along with their recent runs
+    pub async fn ask_list_schedules(
         &mut self,
-        proc: &RemoteProc,
-    ) -> Result<ProcKilledArgs, ExecAskError> {
+    ) -> Result<SchedulesListArgs, ExecAskError> {
+        let result = self.ask(Request::ListSchedules).await;
+
+        if let Err(x) = result {
+            return Err(From::from(x));
+        }
+
+        match result.unwrap() {
+            Reply::SchedulesList(args) => Ok(args),
+            x => Err(make_exec_ask_error(x)),
+        }
+    }
+
+    /// Requests to delete a schedule, preventing it from submitting more jobs
+    pub async fn ask_delete_schedule(
+        &mut self,
+        id: u32,
+    ) -> Result<ScheduleDeletedArgs, ExecAskError> {
         let result = self
-            .ask(Request::KillProc(KillProcArgs { id: proc.id }))
+            .ask(Request::DeleteSchedule(DeleteScheduleArgs { id }))
             .await;
 
         if let Err(x) = result {
@@ -595,33 +2523,277 @@ impl ConnectedClient {
         }
 
         match result.unwrap() {
-            Reply::ProcKilled(args) => Ok(args),
+            Reply::ScheduleDeleted(args) => Ok(args),
             x => Err(make_exec_ask_error(x)),
         }
     }
 
-    /// Requests internal state of server
-    pub async fn ask_internal_debug(
+    /// Requests to append `lines` to the log `name` on the server
+    pub async fn ask_append_log(
+        &mut self,
+        name: impl Into<String>,
+        lines: Vec<String>,
+    ) -> Result<LogAppendedArgs, AskError> {
+        let name = name.into();
+        match self
+            .ask(Request::AppendLog(AppendLogArgs { name, lines }))
+            .await?
+        {
+            Reply::LogAppended(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests up to `max_lines` lines of the log `name` on the server,
+    /// starting at line `start`
+    pub async fn ask_read_log_range(
+        &mut self,
+        name: impl Into<String>,
+        start: u64,
+        max_lines: Option<u64>,
+    ) -> Result<LogRangeArgs, AskError> {
+        let name = name.into();
+        match self
+            .ask(Request::ReadLogRange(ReadLogRangeArgs {
+                name,
+                start,
+                max_lines,
+            }))
+            .await?
+        {
+            Reply::LogRange(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests diagnostics about the state of the server, limited to
+    /// `sections` unless empty
+    pub async fn ask_diagnostics(
+        &mut self,
+        sections: Vec<DiagnosticSection>,
+    ) -> Result<reply::DiagnosticsArgs, AskError> {
+        let result = self
+            .ask(Request::Diagnostics(request::DiagnosticsArgs { sections }))
+            .await?;
+
+        match result {
+            Reply::Diagnostics(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests a page of the clients that have communicated with the
+    /// server, filtered by origin and idle time
+    pub async fn ask_list_connections(
+        &mut self,
+        args: ListConnectionsArgs,
+    ) -> Result<ConnectionsListArgs, AskError> {
+        match self.ask(Request::ListConnections(args)).await? {
+            Reply::ConnectionsList(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests the resources that the server will evict unless they are
+    /// touched, first touching those in `args` to keep them alive
+    pub async fn ask_list_pending_evictions(
+        &mut self,
+        args: ListPendingEvictionsArgs,
+    ) -> Result<PendingEvictionsArgs, AskError> {
+        match self.ask(Request::ListPendingEvictions(args)).await? {
+            Reply::PendingEvictions(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests a challenge from the server and echoes back its nonce signed
+    /// with the signing key, proving that this client receives replies sent
+    /// to its origin so that a server with strict origin binding accepts
+    /// requests that change it
+    ///
+    /// Without a signing key, the nonce is signed with a key generated for
+    /// the echo, which servers that only trust certain keys reject.
+    pub async fn ask_bind_origin(&mut self) -> Result<(), AskError> {
+        let key = self
+            .signing_key
+            .clone()
+            .unwrap_or_else(identity::IdentityKey::generate);
+        let mut nonce = Vec::new();
+
+        // The first ask yields the challenge unless the origin is already
+        // bound, and the second echoes back its nonce
+        for _ in 0..2 {
+            let signature = if nonce.is_empty() {
+                Vec::new()
+            } else {
+                key.sign(&signed_nonce(&nonce))
+            };
+            match self
+                .ask(Request::BindOrigin(BindOriginArgs {
+                    nonce,
+                    identity_key: key.public_key().to_vec(),
+                    signature,
+                    padding: vec![0; BIND_ORIGIN_PADDING],
+                }))
+                .await?
+            {
+                Reply::OriginBinding(args) if args.bound => return Ok(()),
+                Reply::OriginBinding(args) => nonce = args.nonce,
+                x => return Err(make_ask_error(x)),
+            }
+        }
+
+        Err(AskError::Failure {
+            msg: String::from("Server did not accept the echoed nonce"),
+            code: ErrorCode::ORIGIN_UNBOUND,
+        })
+    }
+
+    /// Requests that the server force requests of `request_type` to fail,
+    /// be delayed, or panic, affecting at most `times` requests if provided
+    #[cfg(feature = "fault-injection")]
+    pub async fn ask_inject_fault(
+        &mut self,
+        request_type: impl Into<String>,
+        fault: Fault,
+        times: Option<u32>,
+    ) -> Result<FaultInjectedArgs, AskError> {
+        let result = self
+            .ask(Request::InjectFault(InjectFaultArgs {
+                request_type: request_type.into(),
+                fault,
+                times,
+            }))
+            .await?;
+
+        match result {
+            Reply::FaultInjected(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server remove all injected faults
+    #[cfg(feature = "fault-injection")]
+    pub async fn ask_clear_faults(
         &mut self,
-    ) -> Result<reply::InternalDebugArgs, AskError> {
+    ) -> Result<FaultsClearedArgs, AskError> {
+        let result = self.ask(Request::ClearFaults).await?;
+
+        match result {
+            Reply::FaultsCleared(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server serve custom requests of `namespace` with
+    /// the wasm `module`, given in binary or text format
+    #[cfg(feature = "wasm")]
+    pub async fn ask_load_wasm_handler(
+        &mut self,
+        namespace: impl Into<String>,
+        module: Vec<u8>,
+    ) -> Result<WasmHandlerLoadedArgs, AskError> {
+        let result = self
+            .ask(Request::LoadWasmHandler(LoadWasmHandlerArgs {
+                namespace: namespace.into(),
+                module,
+            }))
+            .await?;
+
+        match result {
+            Reply::WasmHandlerLoaded(args) => Ok(args),
+            x => Err(make_ask_error(x)),
+        }
+    }
+
+    /// Requests that the server stop serving custom requests of `namespace`
+    /// with a wasm module
+    #[cfg(feature = "wasm")]
+    pub async fn ask_unload_wasm_handler(
+        &mut self,
+        namespace: impl Into<String>,
+    ) -> Result<WasmHandlerUnloadedArgs, AskError> {
         let result = self
-            .ask(Request::InternalDebug(request::InternalDebugArgs {
-                input: vec![],
+            .ask(Request::UnloadWasmHandler(UnloadWasmHandlerArgs {
+                namespace: namespace.into(),
             }))
             .await?;
 
         match result {
-            Reply::InternalDebug(args) => Ok(args),
+            Reply::WasmHandlerUnloaded(args) => Ok(args),
             x => Err(make_ask_error(x)),
         }
     }
 }
 
+/// Waits for the next chunk of a streamed directory listing, yielding its
+/// entries alongside the receiver if more chunks are expected
+async fn next_dir_chunk(
+    rx: Option<mpsc::UnboundedReceiver<Reply>>,
+    timeout: Duration,
+) -> Option<(
+    Vec<Result<DirEntry, FileAskError>>,
+    Option<mpsc::UnboundedReceiver<Reply>>,
+)> {
+    let mut rx = rx?;
+    let result = match tokio::time::timeout(timeout, rx.next()).await {
+        Ok(Some(Reply::DirContentsListChunk(args))) => Ok(args),
+        Ok(Some(x)) => Err(make_file_ask_error(x)),
+        Ok(None) => Err(FileAskError::from(AskError::CallbackLost)),
+        Err(_) => Err(FileAskError::from(AskError::Timeout)),
+    };
+
+    match result {
+        Ok(args) => {
+            let rx = if args.last { None } else { Some(rx) };
+            Some((args.entries.into_iter().map(Ok).collect(), rx))
+        }
+        Err(x) => Some((vec![Err(x)], None)),
+    }
+}
+
+/// Waits for the next result of a streamed batch, yielding it alongside the
+/// receiver if more results are expected
+async fn next_batch_result(
+    rx: Option<mpsc::UnboundedReceiver<Reply>>,
+    timeout: Duration,
+) -> Option<(
+    Result<(usize, Reply), AskError>,
+    Option<mpsc::UnboundedReceiver<Reply>>,
+)> {
+    let mut rx = rx?;
+    match tokio::time::timeout(timeout, rx.next()).await {
+        Ok(Some(Reply::BatchResult(args))) => {
+            let rx = if args.last { None } else { Some(rx) };
+            Some((Ok((args.index, *args.result)), rx))
+        }
+
+        // An empty batch has no results to stream, so it is replied to with
+        // an empty batch instead
+        Ok(Some(Reply::Batch(args))) if args.results.is_empty() => None,
+        Ok(Some(x)) => Some((Err(make_ask_error(x)), None)),
+        Ok(None) => Some((Err(AskError::CallbackLost), None)),
+        Err(_) => Some((Err(AskError::Timeout), None)),
+    }
+}
+
+/// Whether an ask failed in a way that asking again may not, such as by
+/// timing out
+fn is_transient(x: &AskError) -> bool {
+    matches!(
+        x,
+        AskError::Timeout | AskError::SendFailed | AskError::CallbackLost
+    )
+}
+
 fn make_file_ask_error(x: Reply) -> FileAskError {
     match x {
         Reply::Error(ReplyError::Io(args)) => {
             FileAskError::IoError(args.into())
         }
+        Reply::Error(ReplyError::PreconditionFailed(args)) => {
+            FileAskError::PreconditionFailed(args)
+        }
         x => From::from(make_ask_error(x)),
     }
 }
@@ -636,5 +2808,15 @@ fn make_exec_ask_error(x: Reply) -> ExecAskError {
 }
 
 fn make_ask_error(reply: Reply) -> AskError {
-    AskError::InvalidResponse { reply }
+    match reply {
+        Reply::Unsupported(args) => AskError::Unsupported {
+            type_name: args.type_name,
+            description: args.description,
+        },
+        Reply::Error(ReplyError::QuotaExceeded(args)) => AskError::Failure {
+            msg: args.to_string(),
+            code: ErrorCode::QUOTA_EXCEEDED,
+        },
+        reply => AskError::InvalidResponse { reply },
+    }
 }
@@ -0,0 +1,126 @@
+use super::{
+    error::FileAskError,
+    file::DownloadOptions,
+    tree::{local_path, local_tree, remote_path, remote_tree},
+    ConnectedClient,
+};
+use crate::core::RemotePath;
+use std::path::Path;
+
+/// What was copied by uploading or downloading a directory, as paths
+/// relative to the copied directories
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirCopy {
+    /// Directories created within the destination
+    pub dirs: Vec<RemotePath>,
+
+    /// Files written within the destination
+    pub files: Vec<RemotePath>,
+
+    /// Total bytes of the files written
+    pub bytes: u64,
+}
+
+/// Uploads every dir and file within the local directory `local_dir` into
+/// `remote_dir` on the server, creating it if missing
+///
+/// Files on the server are replaced atomically with their local contents
+/// and permission bits. Symlinks are not followed or copied.
+pub(super) async fn upload_dir(
+    client: &mut ConnectedClient,
+    local_dir: &Path,
+    remote_dir: &RemotePath,
+) -> Result<DirCopy, FileAskError> {
+    let tree = local_tree(local_dir).await?;
+    let mut copy = DirCopy::default();
+
+    client.ask_create_dir(remote_dir, true).await?;
+    for dir in tree.dirs {
+        client
+            .ask_create_dir(remote_path(remote_dir, &dir), true)
+            .await?;
+        copy.dirs.push(dir);
+    }
+
+    for file in tree.files {
+        let path = local_path(local_dir, &file);
+        let contents = tokio::fs::read(&path).await?;
+        client
+            .ask_write_file_atomic_by_path(
+                remote_path(remote_dir, &file),
+                &contents,
+                local_mode(&path).await,
+            )
+            .await?;
+        copy.bytes += contents.len() as u64;
+        copy.files.push(file);
+    }
+
+    Ok(copy)
+}
+
+/// Downloads every dir and file within `remote_dir` on the server into the
+/// local directory `local_dir`, creating it if missing
+///
+/// Each file is streamed into a separate file that replaces the local copy
+/// once complete, so a failed download never clobbers it. Symlinks are not
+/// followed or copied.
+pub(super) async fn download_dir(
+    client: &mut ConnectedClient,
+    remote_dir: &RemotePath,
+    local_dir: &Path,
+    options: DownloadOptions,
+) -> Result<DirCopy, FileAskError> {
+    let tree = remote_tree(client, remote_dir).await?;
+    let mut copy = DirCopy::default();
+
+    tokio::fs::create_dir_all(local_dir).await?;
+    for dir in tree.dirs {
+        tokio::fs::create_dir_all(local_path(local_dir, &dir)).await?;
+        copy.dirs.push(dir);
+    }
+
+    for file in tree.files {
+        let path = local_path(local_dir, &file);
+        let mut part_path = path.clone().into_os_string();
+        part_path.push(".part");
+
+        let mut part = tokio::fs::File::create(&part_path).await?;
+        let mut written = 0;
+        let result = client
+            .download_to(
+                remote_path(remote_dir, &file),
+                &mut part,
+                options.clone(),
+                |p| written = p.written,
+            )
+            .await;
+        drop(part);
+
+        if let Err(x) = result {
+            let _ = tokio::fs::remove_file(&part_path).await;
+            return Err(x);
+        }
+        tokio::fs::rename(&part_path, &path).await?;
+        copy.bytes += written;
+        copy.files.push(file);
+    }
+
+    Ok(copy)
+}
+
+/// Permission bits of a local file so they can be reproduced on the server,
+/// or None if not supported on this platform
+#[cfg(unix)]
+async fn local_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    tokio::fs::metadata(path)
+        .await
+        .ok()
+        .map(|m| m.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+async fn local_mode(_path: &Path) -> Option<u32> {
+    None
+}
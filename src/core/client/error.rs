@@ -1,4 +1,7 @@
-use crate::core::Reply;
+use crate::core::{
+    reply::{ErrorCode, PreconditionFailedArgs},
+    Reply,
+};
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
@@ -8,15 +11,49 @@ use std::io;
 pub enum SendError {
     EncodingFailed,
     SendFailed,
+
+    /// The encoded msg is larger than the transport can carry, so the
+    /// request must be split into smaller requests
+    #[display(
+        fmt = "{} of {} bytes exceeds maximum msg size of {} bytes",
+        request,
+        size,
+        max
+    )]
+    MsgTooLarge {
+        request: String,
+        size: usize,
+        max: usize,
+    },
+
+    /// The msg must be signed, but the client has no key to sign it with
+    #[display(fmt = "Msg must be signed, but no signing key is available")]
+    SigningKeyRequired,
 }
 
 impl Error for SendError {}
 
+impl SendError {
+    /// Stable code identifying the kind of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::EncodingFailed => ErrorCode::ENCODING_FAILED,
+            Self::SendFailed => ErrorCode::SEND_FAILED,
+            Self::MsgTooLarge { .. } => ErrorCode::MSG_TOO_LARGE,
+            Self::SigningKeyRequired => ErrorCode::SIGNING_KEY_REQUIRED,
+        }
+    }
+}
+
 impl From<AskError> for Option<SendError> {
     fn from(error: AskError) -> Self {
         match error {
             AskError::EncodingFailed => Some(SendError::EncodingFailed),
             AskError::SendFailed => Some(SendError::SendFailed),
+            AskError::MsgTooLarge { request, size, max } => {
+                Some(SendError::MsgTooLarge { request, size, max })
+            }
+            AskError::SigningKeyRequired => Some(SendError::SigningKeyRequired),
             _ => None,
         }
     }
@@ -27,24 +64,68 @@ pub enum AskError {
     #[display(fmt = "Failed: {}", msg)]
     Failure {
         msg: String,
+        code: ErrorCode,
     },
     #[display(fmt = "Invalid Response: {:?}", reply)]
     InvalidResponse {
         reply: Reply,
     },
+    #[display(fmt = "Unsupported {}: {}", type_name, description)]
+    Unsupported {
+        type_name: String,
+        description: String,
+    },
     Timeout,
     EncodingFailed,
     SendFailed,
     CallbackLost,
+    #[display(
+        fmt = "{} of {} bytes exceeds maximum msg size of {} bytes",
+        request,
+        size,
+        max
+    )]
+    MsgTooLarge {
+        request: String,
+        size: usize,
+        max: usize,
+    },
+    #[display(fmt = "Msg must be signed, but no signing key is available")]
+    SigningKeyRequired,
 }
 
 impl Error for AskError {}
 
+impl AskError {
+    /// Stable code identifying the kind of this error, which is that of the
+    /// error replied by the server if there is one
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Failure { code, .. } => *code,
+            Self::InvalidResponse {
+                reply: Reply::Error(x),
+            } => x.code(),
+            Self::InvalidResponse { .. } => ErrorCode::INVALID_RESPONSE,
+            Self::Unsupported { .. } => ErrorCode::UNSUPPORTED,
+            Self::Timeout => ErrorCode::TIMED_OUT,
+            Self::EncodingFailed => ErrorCode::ENCODING_FAILED,
+            Self::SendFailed => ErrorCode::SEND_FAILED,
+            Self::CallbackLost => ErrorCode::CALLBACK_LOST,
+            Self::MsgTooLarge { .. } => ErrorCode::MSG_TOO_LARGE,
+            Self::SigningKeyRequired => ErrorCode::SIGNING_KEY_REQUIRED,
+        }
+    }
+}
+
 impl From<SendError> for AskError {
     fn from(error: SendError) -> Self {
         match error {
             SendError::EncodingFailed => Self::EncodingFailed,
             SendError::SendFailed => Self::SendFailed,
+            SendError::MsgTooLarge { request, size, max } => {
+                Self::MsgTooLarge { request, size, max }
+            }
+            SendError::SigningKeyRequired => Self::SigningKeyRequired,
         }
     }
 }
@@ -63,10 +144,25 @@ pub enum FileAskError {
 
     #[display(fmt = "File signature changed: {}", id)]
     FileSignatureChanged { id: u32 },
+
+    #[display(fmt = "{}", "_0")]
+    PreconditionFailed(PreconditionFailedArgs),
 }
 
 impl Error for FileAskError {}
 
+impl FileAskError {
+    /// Stable code identifying the kind of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::GeneralAskFailed(x) => x.code(),
+            Self::IoError(x) => ErrorCode::from(x.kind()),
+            Self::FileSignatureChanged { .. } => ErrorCode::FILE_SIG_CHANGED,
+            Self::PreconditionFailed(_) => ErrorCode::PRECONDITION_FAILED,
+        }
+    }
+}
+
 impl From<AskError> for FileAskError {
     fn from(error: AskError) -> Self {
         Self::GeneralAskFailed(error)
@@ -92,6 +188,17 @@ pub enum ExecAskError {
 
 impl Error for ExecAskError {}
 
+impl ExecAskError {
+    /// Stable code identifying the kind of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::GeneralAskFailed(x) => x.code(),
+            Self::IoError(x) => ErrorCode::from(x.kind()),
+            Self::FailedToKill => ErrorCode::GENERIC,
+        }
+    }
+}
+
 impl From<AskError> for ExecAskError {
     fn from(error: AskError) -> Self {
         Self::GeneralAskFailed(error)
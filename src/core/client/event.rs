@@ -0,0 +1,18 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Notable change in the connection of a client, delivered to every stream
+/// returned by `ConnectedClient::events`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// Server at `from` became unreachable and the client is now connected
+    /// to the fallback server at `to`
+    Failover { from: SocketAddr, to: SocketAddr },
+
+    /// Server announced that it is shutting down, giving requests still
+    /// being executed up to `grace` to complete
+    ServerShuttingDown { grace: Duration },
+
+    /// Proc with `id` started by this client exited, as pushed by the server
+    ProcExited { id: u32, exit_code: Option<i32> },
+}
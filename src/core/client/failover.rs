@@ -0,0 +1,61 @@
+use super::{state::ClientState, ConnectedClient};
+use futures::future::BoxFuture;
+use log::warn;
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Connects to the server at an address, sharing the given client state
+pub(super) type Connect = Box<
+    dyn Fn(
+            SocketAddr,
+            Arc<Mutex<ClientState>>,
+        ) -> BoxFuture<'static, io::Result<ConnectedClient>>
+        + Send
+        + Sync,
+>;
+
+/// Fallback servers that a client connects to, in order, once the server it
+/// is connected to becomes unreachable
+pub(super) struct Failover {
+    remaining: VecDeque<SocketAddr>,
+    connect: Connect,
+}
+
+impl Failover {
+    pub fn new(servers: Vec<SocketAddr>, connect: Connect) -> Self {
+        Self {
+            remaining: servers.into(),
+            connect,
+        }
+    }
+
+    /// Fallback servers not yet failed over to, in the order they are tried
+    pub fn remaining(&self) -> Vec<SocketAddr> {
+        self.remaining.iter().copied().collect()
+    }
+
+    pub fn has_remaining(&self) -> bool {
+        !self.remaining.is_empty()
+    }
+
+    /// Connects to the next reachable fallback server, discarding those
+    /// that cannot be reached along the way
+    pub async fn connect_next(
+        &mut self,
+        state: Arc<Mutex<ClientState>>,
+    ) -> Option<ConnectedClient> {
+        while let Some(addr) = self.remaining.pop_front() {
+            match (self.connect)(addr, Arc::clone(&state)).await {
+                Ok(client) => return Some(client),
+                Err(x) => {
+                    warn!("Failed to connect to fallback {}: {}", addr, x)
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -1,10 +1,13 @@
-use crate::core::reply::FileOpenedArgs;
+use crate::core::{
+    reply::{FileOpenedArgs, UploadSession, UploadSessionStatus},
+    RemotePath,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RemoteFile {
     pub(crate) id: u32,
     pub(crate) sig: u32,
-    pub(crate) path: String,
+    pub(crate) path: RemotePath,
 }
 
 impl RemoteFile {
@@ -12,9 +15,62 @@ impl RemoteFile {
         self.id
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> &RemotePath {
         &self.path
     }
+
+    /// Creates a reference to a file opened as part of an upload manifest,
+    /// returning None if the session is not ready to be written
+    pub fn from_upload_session(session: &UploadSession) -> Option<Self> {
+        match session.status {
+            UploadSessionStatus::Ready { id, sig, .. } => Some(Self {
+                id,
+                sig,
+                path: session.path.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Options of downloading a file from the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// Etag of a local copy of the file, where the contents are not
+    /// downloaded if the file still matches it
+    pub if_none_match: Option<String>,
+
+    /// Maximum bytes asked for in each chunk, which is further limited to
+    /// what fits within a single msg
+    pub chunk_size: u64,
+
+    /// Maximum chunks awaiting a reply at once
+    pub window: usize,
+
+    /// Times a chunk is asked for again after its ask times out or fails to
+    /// be sent
+    pub retries: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            if_none_match: None,
+            chunk_size: u64::MAX,
+            window: 8,
+            retries: 3,
+        }
+    }
+}
+
+/// Progress of a download, reported each time a chunk is written
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Bytes of the file written so far
+    pub written: u64,
+
+    /// Total size of the file in bytes
+    pub total: u64,
 }
 
 impl From<FileOpenedArgs> for RemoteFile {
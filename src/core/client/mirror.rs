@@ -0,0 +1,315 @@
+use super::{
+    error::FileAskError,
+    file::DownloadOptions,
+    tree::{local_path, local_tree, remote_parent, remote_path, remote_tree},
+    ConnectedClient,
+};
+use crate::core::RemotePath;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Shortest time waited between passes when mirroring continuously, as
+/// each pass walks the whole of the remote directory
+pub const MIN_MIRROR_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Options of mirroring a directory on the server into a local directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorOptions {
+    /// Whether changes made to the local directory are pushed to the server,
+    /// rather than only pulling the changes made on the server
+    pub two_way: bool,
+
+    /// Time to wait between passes when mirroring continuously, which is
+    /// never less than `MIN_MIRROR_INTERVAL`
+    pub interval: Duration,
+
+    /// Options of downloading each file, where the etag is always that of
+    /// the file when last in sync
+    pub download: DownloadOptions,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        Self {
+            two_way: false,
+            interval: Duration::from_secs(1),
+            download: DownloadOptions::default(),
+        }
+    }
+}
+
+/// Changes applied by a single pass of a mirror, as paths relative to the
+/// mirrored directories
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MirrorPass {
+    /// Files written locally with their contents on the server
+    pub pulled: Vec<RemotePath>,
+
+    /// Files written on the server with their local contents
+    pub pushed: Vec<RemotePath>,
+
+    /// Files removed locally as they were removed on the server
+    pub removed_local: Vec<RemotePath>,
+
+    /// Files removed on the server as they were removed locally
+    pub removed_remote: Vec<RemotePath>,
+
+    /// Files changed both locally and on the server since they were last in
+    /// sync, which are left untouched on both sides
+    pub conflicts: Vec<RemotePath>,
+}
+
+impl MirrorPass {
+    pub fn is_empty(&self) -> bool {
+        self.pulled.is_empty()
+            && self.pushed.is_empty()
+            && self.removed_local.is_empty()
+            && self.removed_remote.is_empty()
+            && self.conflicts.is_empty()
+    }
+}
+
+/// Directory on the server mirrored into a local directory
+///
+/// Each pass compares both sides against the etag of every file as of when
+/// it was last in sync, so a file is only pulled or pushed if one side
+/// changed, and a file changed on both sides is reported as a conflict
+/// rather than overwritten. Files on the server are asked for with the etag
+/// they were last in sync with, so unchanged files are never downloaded.
+/// Empty directories and symlinks are not mirrored.
+///
+/// Changes on the server are found by walking the remote directory each
+/// pass rather than from its recent fs events, as those only cover changes
+/// made through the server and not those made by other processes on the
+/// same machine, so mirroring continuously polls every interval.
+#[derive(Clone, Debug)]
+pub struct Mirror {
+    remote_dir: RemotePath,
+    local_dir: PathBuf,
+    options: MirrorOptions,
+
+    /// Etags of files as of when they were last in sync, keyed by their
+    /// path relative to the mirrored directories
+    synced: HashMap<RemotePath, String>,
+}
+
+/// File found on the server, whose contents are only present if it changed
+/// since it was last in sync
+struct RemoteState {
+    etag: String,
+    contents: Option<Vec<u8>>,
+}
+
+impl Mirror {
+    pub fn new(
+        remote_dir: impl Into<RemotePath>,
+        local_dir: impl Into<PathBuf>,
+        options: MirrorOptions,
+    ) -> Self {
+        Self {
+            remote_dir: remote_dir.into(),
+            local_dir: local_dir.into(),
+            options,
+            synced: HashMap::new(),
+        }
+    }
+
+    pub fn remote_dir(&self) -> &RemotePath {
+        &self.remote_dir
+    }
+
+    pub fn local_dir(&self) -> &Path {
+        &self.local_dir
+    }
+
+    pub fn options(&self) -> &MirrorOptions {
+        &self.options
+    }
+
+    /// Time to wait between passes when mirroring continuously
+    pub fn interval(&self) -> Duration {
+        self.options.interval.max(MIN_MIRROR_INTERVAL)
+    }
+
+    /// Brings both directories in sync once, yielding what was changed
+    pub async fn sync(
+        &mut self,
+        client: &mut ConnectedClient,
+    ) -> Result<MirrorPass, FileAskError> {
+        tokio::fs::create_dir_all(&self.local_dir).await?;
+
+        let remote: BTreeSet<RemotePath> =
+            remote_tree(client, &self.remote_dir)
+                .await?
+                .files
+                .into_iter()
+                .collect();
+        let local: BTreeSet<RemotePath> = local_tree(&self.local_dir)
+            .await?
+            .files
+            .into_iter()
+            .collect();
+        let mut pass = MirrorPass::default();
+
+        for path in remote.union(&local) {
+            let synced = self.synced.get(path).cloned();
+            let local_etag = if local.contains(path) {
+                let contents = tokio::fs::read(self.local_path(path)).await?;
+                Some(etag(&contents))
+            } else {
+                None
+            };
+            let remote_state = if remote.contains(path) {
+                Some(self.download(client, path, synced.clone()).await?)
+            } else {
+                None
+            };
+
+            match (remote_state, local_etag) {
+                (Some(remote), Some(local)) if remote.etag == local => {
+                    self.synced.insert(path.clone(), local);
+                }
+
+                // Only the server changed, or the file is new on both sides
+                // with different contents
+                (Some(remote), Some(local)) => {
+                    let local_changed = synced.as_ref() != Some(&local);
+                    match remote.contents {
+                        Some(_) if local_changed => {
+                            pass.conflicts.push(path.clone())
+                        }
+                        Some(contents) => {
+                            self.pull(path, &contents).await?;
+                            self.synced.insert(path.clone(), remote.etag);
+                            pass.pulled.push(path.clone());
+                        }
+                        None if self.options.two_way => {
+                            self.push(client, path).await?;
+                            pass.pushed.push(path.clone());
+                        }
+                        None => {}
+                    }
+                }
+
+                // Removed locally, which is only pushed if the server has not
+                // changed the file since
+                (Some(remote), None) => match remote.contents {
+                    None if self.options.two_way => {
+                        client
+                            .ask_remove_unopened_file(self.remote_path(path))
+                            .await?;
+                        self.synced.remove(path);
+                        pass.removed_remote.push(path.clone());
+                    }
+                    None => {
+                        let contents = self
+                            .download(client, path, None)
+                            .await?
+                            .contents
+                            .unwrap_or_default();
+                        self.pull(path, &contents).await?;
+                        pass.pulled.push(path.clone());
+                    }
+                    Some(contents) => {
+                        self.pull(path, &contents).await?;
+                        self.synced.insert(path.clone(), remote.etag);
+                        pass.pulled.push(path.clone());
+                    }
+                },
+
+                // Removed on the server, which is only applied locally if the
+                // local file has not changed since
+                (None, Some(local)) => {
+                    if synced.as_ref() == Some(&local) {
+                        tokio::fs::remove_file(self.local_path(path)).await?;
+                        self.synced.remove(path);
+                        pass.removed_local.push(path.clone());
+                    } else if self.options.two_way {
+                        self.push(client, path).await?;
+                        pass.pushed.push(path.clone());
+                    } else {
+                        self.synced.remove(path);
+                    }
+                }
+
+                (None, None) => {}
+            }
+        }
+
+        Ok(pass)
+    }
+
+    /// Asks for the file at `path` on the server, where the contents are
+    /// omitted if it still matches the etag `synced`
+    async fn download(
+        &self,
+        client: &mut ConnectedClient,
+        path: &RemotePath,
+        synced: Option<String>,
+    ) -> Result<RemoteState, FileAskError> {
+        let mut contents = Vec::new();
+        let args = client
+            .download_to(
+                self.remote_path(path),
+                &mut contents,
+                DownloadOptions {
+                    if_none_match: synced,
+                    ..self.options.download.clone()
+                },
+                |_| {},
+            )
+            .await?;
+
+        Ok(RemoteState {
+            etag: args.etag,
+            contents: if args.not_modified {
+                None
+            } else {
+                Some(contents)
+            },
+        })
+    }
+
+    /// Writes `contents` to the local file at `path`
+    async fn pull(&self, path: &RemotePath, contents: &[u8]) -> io::Result<()> {
+        let local_path = self.local_path(path);
+        if let Some(parent) = local_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(local_path, contents).await
+    }
+
+    /// Writes the contents of the local file at `path` to the server
+    async fn push(
+        &mut self,
+        client: &mut ConnectedClient,
+        path: &RemotePath,
+    ) -> Result<(), FileAskError> {
+        let contents = tokio::fs::read(self.local_path(path)).await?;
+        let remote_path = self.remote_path(path);
+        if let Some(parent) = remote_parent(&remote_path) {
+            client.ask_create_dir(parent, true).await?;
+        }
+
+        let args = client
+            .ask_write_file_atomic_by_path(&remote_path, &contents, None)
+            .await?;
+        self.synced.insert(path.clone(), args.hash);
+        Ok(())
+    }
+
+    fn remote_path(&self, path: &RemotePath) -> RemotePath {
+        remote_path(&self.remote_dir, path)
+    }
+
+    fn local_path(&self, path: &RemotePath) -> PathBuf {
+        local_path(&self.local_dir, path)
+    }
+}
+
+fn etag(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
@@ -1,35 +1,54 @@
+mod clock;
 mod connected;
+pub mod copy;
 pub mod error;
+mod event;
+mod failover;
 pub mod file;
 mod inbound;
+pub mod mirror;
+mod preset;
 pub mod proc;
+mod shared;
 pub mod state;
+mod tree;
 
-pub use connected::ConnectedClient;
+pub use clock::ClockOffset;
+pub use connected::{AskOptions, ConnectedClient};
+pub use event::ClientEvent;
+pub use preset::{Preset, PresetValues};
+pub use shared::SharedUdpSocket;
+pub use state::{AskMetrics, AskTiming, ClientStats};
 
 use crate::core::{
     event::{AddrEventManager, EventManager},
-    msg::content::Content,
+    msg::{
+        content::{Content, Reply},
+        Msg,
+    },
     Transport,
 };
+use connected::ClientEventManager;
 use derive_builder::Builder;
+use futures::future::FutureExt;
 use log::warn;
-use crate::utils::Either;
 use crate::core::transport::{
-    self as wire, Authenticator, Bicrypter, NetTransmission, Wire,
+    self as wire, AssemblyConfig, Authenticator, Bicrypter, NetTransmission,
+    SocketOptions, Wire,
 };
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use tokio::{
     io,
     net::{TcpStream, UdpSocket},
     runtime::Handle,
-    sync::{mpsc, Mutex},
+    sync::{mpsc, Mutex, Semaphore},
+    time,
 };
 
 /// Represents a client configuration prior to connecting
-#[derive(Builder)]
+#[derive(Builder, Clone)]
 pub struct Client<A, B>
 where
     A: Authenticator,
@@ -39,6 +58,29 @@ where
     #[builder(default = "crate::core::transport::constants::DEFAULT_TTL")]
     packet_ttl: Duration,
 
+    /// Whether the TTL of a msg is extended each time one of its packets
+    /// arrives, rather than being fixed from the first packet
+    #[builder(default = "true")]
+    adaptive_packet_ttl: bool,
+
+    /// Maximum number of msgs whose packets can be collected at once
+    #[builder(
+        default = "crate::core::transport::constants::DEFAULT_MAX_PACKET_GROUPS"
+    )]
+    max_packet_groups: usize,
+
+    /// Maximum number of packets that can make up a single msg
+    #[builder(
+        default = "crate::core::transport::constants::DEFAULT_MAX_PACKETS_PER_GROUP"
+    )]
+    max_packets_per_msg: usize,
+
+    /// Minimum time between removals of msgs whose packets expired
+    #[builder(
+        default = "crate::core::transport::constants::DEFAULT_PACKET_GC_INTERVAL"
+    )]
+    packet_gc_interval: Duration,
+
     /// Used to sign & verify msgs
     authenticator: A,
 
@@ -51,27 +93,172 @@ where
     /// Internal buffer for cross-thread messaging
     #[builder(default = "1000")]
     buffer: usize,
+
+    /// Time to wait for the reply to an ask before timing out
+    #[builder(default = "ConnectedClient::DEFAULT_TIMEOUT")]
+    timeout: Duration,
+
+    /// Time to keep a callback awaiting a reply before discarding it, which
+    /// covers replies that never arrive such as lost udp packets
+    #[builder(default = "crate::utils::CallbackManager::<Reply>::DEFAULT_TTL")]
+    callback_ttl: Duration,
+
+    /// Maximum number of asks that can await a reply at once, where
+    /// additional asks wait until an earlier ask completes
+    #[builder(default = "ConnectedClient::DEFAULT_MAX_OUTSTANDING_ASKS")]
+    max_outstanding_asks: usize,
+
+    /// Public identity keys of the servers that the client trusts, where
+    /// connecting fails unless the server proves that it holds the secret
+    /// half of one of them; no identity is verified if empty
+    #[builder(default)]
+    pinned_server_keys: Vec<Vec<u8>>,
+
+    /// If true, proves to the server that the client receives replies sent
+    /// to its origin once connected, which a udp server with strict origin
+    /// binding requires before accepting requests that change it
+    #[builder(default)]
+    bind_origin: bool,
+
+    /// If true, negotiates a key for the session once connected, sealing
+    /// every msg sent to and received from the server with it; the server
+    /// must sign the handshake with one of the pinned keys if any are given
+    #[builder(default)]
+    handshake: bool,
+
+    /// Servers to connect to, in order, if the server becomes unreachable
+    /// after connecting, where idempotent asks that were awaiting a reply
+    /// are sent again to the fallback server
+    #[builder(default)]
+    fallback_servers: Vec<SocketAddr>,
+
+    /// Socket-level options such as tcp keepalive and kernel buffer sizes
+    #[builder(default)]
+    socket_options: SocketOptions,
+}
+
+impl<A, B> ClientBuilder<A, B>
+where
+    A: Authenticator + Clone,
+    B: Bicrypter + Clone,
+{
+    /// Applies the settings of `preset`, which can be overridden by setting
+    /// them again afterwards
+    pub fn preset(&mut self, preset: Preset) -> &mut Self {
+        let values = preset.values();
+        self.timeout(values.timeout)
+            .packet_ttl(values.packet_ttl)
+            .adaptive_packet_ttl(values.adaptive_packet_ttl)
+            .callback_ttl(values.callback_ttl)
+            .buffer(values.buffer)
+            .max_outstanding_asks(values.max_outstanding_asks)
+    }
 }
 
+/// Time between checks for callbacks whose ttl has passed
+pub const CALLBACK_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
 impl<A, B> Client<A, B>
 where
     A: Authenticator + Send + Sync + 'static,
     B: Bicrypter + Send + Sync + 'static,
 {
+    fn assembly_config(&self) -> AssemblyConfig {
+        AssemblyConfig {
+            packet_ttl: self.packet_ttl,
+            adaptive_ttl: self.adaptive_packet_ttl,
+            max_groups: self.max_packet_groups,
+            max_packets_per_group: self.max_packets_per_msg,
+            gc_interval: self.packet_gc_interval,
+        }
+    }
+
+    /// Points the client at the server at `addr`, keeping the kind of its
+    /// transport
+    pub fn retarget(&mut self, addr: SocketAddr) {
+        self.transport = match self.transport {
+            Transport::Tcp(_) => Transport::Tcp(vec![addr]),
+            Transport::Udp(_) => Transport::Udp(vec![addr]),
+        };
+    }
+
     /// Starts actively listening for msgs via the specified transport medium
-    pub async fn connect(self) -> io::Result<ConnectedClient> {
-        let state = Arc::new(Mutex::new(state::ClientState::default()));
+    pub async fn connect(self) -> io::Result<ConnectedClient>
+    where
+        A: Clone,
+        B: Clone,
+    {
+        let state =
+            Arc::new(Mutex::new(state::ClientState::new(self.callback_ttl)));
+        Handle::current().spawn(callback_sweep_loop(Arc::downgrade(&state)));
+
+        // Fallback servers are connected to using the same configuration,
+        // only with the transport pointed at the fallback server
+        let failover = if self.fallback_servers.is_empty() {
+            None
+        } else {
+            let template = self.clone();
+            Some(failover::Failover::new(
+                self.fallback_servers.clone(),
+                Box::new(move |addr, state| {
+                    let mut client = template.clone();
+                    client.retarget(addr);
+                    client.connect_with_state(state).boxed()
+                }),
+            ))
+        };
+
+        let mut client = self.connect_with_state(state).await?;
+        client.failover = failover;
+        Ok(client)
+    }
+
+    /// Connects via the specified transport medium, sharing `state` with
+    /// any client previously connected to another server
+    async fn connect_with_state(
+        self,
+        state: Arc<Mutex<state::ClientState>>,
+    ) -> io::Result<ConnectedClient> {
+        let pinned_server_keys = self.pinned_server_keys.clone();
+        let bind_origin = self.bind_origin;
+        let handshake = self.handshake;
 
-        match self.transport.clone() {
+        // NOTE: A session belongs to a single server, so any negotiated
+        //       with a server that was failed over from is dropped
+        state.lock().await.session = None;
+
+        let mut client = match self.transport.clone() {
             Transport::Tcp(addrs) => {
-                build_and_connect_tcp_client(self, Arc::clone(&state), &addrs)
-                    .await
+                build_and_connect_tcp_client(self, state, &addrs).await
             }
             Transport::Udp(addrs) => {
-                build_and_connect_udp_client(self, Arc::clone(&state), &addrs)
-                    .await
+                build_and_connect_udp_client(self, state, &addrs).await
             }
+        }?;
+
+        let denied = |x| io::Error::new(io::ErrorKind::PermissionDenied, x);
+        if bind_origin {
+            client.ask_bind_origin().await.map_err(denied)?;
         }
+
+        if handshake {
+            client
+                .ask_handshake(&pinned_server_keys)
+                .await
+                .map_err(denied)?;
+        }
+
+        if !pinned_server_keys.is_empty() {
+            client.verify_server_identity(&pinned_server_keys).await?;
+        }
+
+        Ok(client)
+    }
+
+    /// Binds a udp socket that can be shared by clients of many servers,
+    /// using the ip version of the first transport address
+    pub async fn bind_shared_udp(self) -> io::Result<SharedUdpSocket> {
+        SharedUdpSocket::bind(self).await
     }
 }
 
@@ -86,6 +273,10 @@ where
 {
     let handle = Handle::current();
 
+    // NOTE: At least one ask must be allowed, otherwise every ask would wait
+    //       forever for a permit
+    let max_outstanding_asks = client.max_outstanding_asks.max(1);
+
     // NOTE: Tokio does not support &[SocketAddr] -> ToSocketAddrs,
     //       so we have to loop through manually
     // See https://github.com/tokio-rs/tokio/pull/1760#discussion_r379120864
@@ -103,13 +294,19 @@ where
         stream
             .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionRefused))?
     };
+    client.socket_options.apply_to_tcp_stream(&stream)?;
     let remote_addr = stream.peer_addr()?;
+    let transmission = NetTransmission::TcpEthernet;
+    let max_msg_size = transmission.max_msg_size();
+    let assembly = client.assembly_config();
     let wire = Wire::new(
-        NetTransmission::TcpEthernet.into(),
+        transmission.into(),
         client.packet_ttl,
         client.authenticator,
         client.bicrypter,
-    );
+    )
+    .with_max_msg_size(max_msg_size)
+    .with_assembly_config(assembly);
 
     let (tx, rx) = mpsc::channel(client.buffer);
     let event_handle = handle.spawn(event_loop(
@@ -127,10 +324,20 @@ where
 
     Ok(ConnectedClient {
         state,
-        event_manager: Either::Left(event_manager),
+        event_manager: ClientEventManager::Stream(event_manager),
         event_handle,
         remote_addr,
-        timeout: ConnectedClient::DEFAULT_TIMEOUT,
+        timeout: client.timeout,
+        trace_id: None,
+        parent_span_id: None,
+        signing_key: None,
+        compression_threshold: None,
+        ask_options: Default::default(),
+        slow_ask_threshold: None,
+        ask_permits: Arc::new(Semaphore::new(max_outstanding_asks)),
+        max_outstanding_asks,
+        max_msg_size,
+        failover: None,
     })
 }
 
@@ -145,13 +352,19 @@ where
 {
     let handle = Handle::current();
 
+    // NOTE: At least one ask must be allowed, otherwise every ask would wait
+    //       forever for a permit
+    let max_outstanding_asks = client.max_outstanding_asks.max(1);
+
     // NOTE: Tokio does not support &[SocketAddr] -> ToSocketAddrs,
     //       so we have to loop through manually
     // See https://github.com/tokio-rs/tokio/pull/1760#discussion_r379120864
     let (socket, remote_addr) = {
         let mut socket_and_addr = None;
         for addr in addrs.iter() {
-            match wire::net::udp::connect(*addr) {
+            match wire::net::udp::connect(*addr)
+                .and_then(|s| client.socket_options.apply_to_udp_socket(s))
+            {
                 Ok(s) => {
                     socket_and_addr = Some((s, *addr));
                     break;
@@ -173,13 +386,17 @@ where
 
     let addr = socket.local_addr()?;
     let transmission = NetTransmission::udp_from_addr(addr);
+    let max_msg_size = transmission.max_msg_size();
 
+    let assembly = client.assembly_config();
     let wire = Wire::new(
         transmission.into(),
         client.packet_ttl,
         client.authenticator,
         client.bicrypter,
-    );
+    )
+    .with_max_msg_size(max_msg_size)
+    .with_assembly_config(assembly);
 
     let (tx, rx) = mpsc::channel(client.buffer);
     let event_handle = handle.spawn(event_loop(
@@ -196,10 +413,20 @@ where
 
     Ok(ConnectedClient {
         state,
-        event_manager: Either::Right(addr_event_manager),
+        event_manager: ClientEventManager::Socket(addr_event_manager),
         event_handle,
         remote_addr,
-        timeout: ConnectedClient::DEFAULT_TIMEOUT,
+        timeout: client.timeout,
+        trace_id: None,
+        parent_span_id: None,
+        signing_key: None,
+        compression_threshold: None,
+        ask_options: Default::default(),
+        slow_ask_threshold: None,
+        ask_permits: Arc::new(Semaphore::new(max_outstanding_asks)),
+        max_outstanding_asks,
+        max_msg_size,
+        failover: None,
     })
 }
 
@@ -211,14 +438,98 @@ async fn event_loop<T>(
         // Update the last time we received a msg from the server
         state.lock().await.last_contact = Instant::now();
 
+        let msg = match unseal(&state, msg).await {
+            Some(msg) => msg,
+            None => continue,
+        };
+
         if let (Some(header), Content::Reply(reply)) =
             (msg.parent_header.as_ref(), &msg.content)
         {
-            state
-                .lock()
-                .await
-                .callback_manager
-                .invoke_callback(header.id, reply)
+            let mut state = state.lock().await;
+
+            // Keep the time reported by the server for the ask awaiting
+            // this reply, which takes it once the reply is received
+            if let Some(micros) = msg.header.processing_micros {
+                if state.callback_manager.has_callback(header.id) {
+                    state
+                        .server_times
+                        .insert(header.id, Duration::from_micros(micros));
+                }
+            }
+
+            state.callback_manager.invoke_callback(header.id, reply)
+        } else if let Content::Reply(Reply::ServerShuttingDown(args)) =
+            &msg.content
+        {
+            warn!("Server is shutting down within {}ms", args.grace_ms);
+            state.lock().await.emit(ClientEvent::ServerShuttingDown {
+                grace: Duration::from_millis(args.grace_ms),
+            });
+        } else if let Content::Reply(Reply::ProcStatus(args)) = &msg.content {
+            // Only exits are pushed by the server without being asked
+            if !args.is_alive {
+                state.lock().await.record_proc_exit(args.clone());
+            }
+        }
+    }
+}
+
+/// Opens `msg` if it was sealed with the session negotiated with the server,
+/// yielding none if it cannot be opened
+///
+/// Once a session is negotiated, replies must be sealed unless they are
+/// errors, which the server sends unsealed when it cannot open a request.
+/// Msgs pushed by the server without being asked are accepted either way.
+async fn unseal(state: &Mutex<state::ClientState>, msg: Msg) -> Option<Msg> {
+    let session = match state.lock().await.session.clone() {
+        Some(session) => session,
+        None => return Some(msg),
+    };
+
+    let args = match msg.content {
+        Content::Reply(Reply::Sealed(ref args)) => args,
+        Content::Reply(Reply::Error(_)) => return Some(msg),
+        _ if msg.parent_header.is_none() => return Some(msg),
+        _ => {
+            warn!(
+                "Dropping unsealed {} from server",
+                msg.content.type_name().unwrap_or_default()
+            );
+            return None;
+        }
+    };
+
+    match session
+        .open(&args.nonce, &args.data)
+        .map_err(|x| x.to_string())
+        .and_then(|data| Msg::from_slice(&data).map_err(|x| x.to_string()))
+    {
+        Ok(msg) => Some(msg),
+        Err(x) => {
+            warn!("Dropping sealed msg from server: {}", x);
+            None
+        }
+    }
+}
+
+/// Periodically discards callbacks that have gone unanswered past their ttl,
+/// stopping once the client state is dropped
+async fn callback_sweep_loop(state: Weak<Mutex<state::ClientState>>) {
+    loop {
+        time::delay_for(CALLBACK_SWEEP_INTERVAL).await;
+
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => break,
+        };
+
+        let removed = state.lock().await.callback_manager.remove_expired();
+        if removed > 0 {
+            warn!(
+                "Discarded {} callbacks that never received a reply",
+                removed
+            );
         }
     }
 }
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+/// Bundle of client settings tuned for a kind of network, applied through
+/// `ClientBuilder::preset`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Fast and reliable local network, where a missing reply is noticed
+    /// quickly
+    Lan,
+
+    /// Internet connection with higher latency but little loss
+    Wan,
+
+    /// Connection that drops or delays packets often, where msgs are given
+    /// much longer to arrive and fewer asks are in flight at once
+    Lossy,
+}
+
+/// Settings applied to a client by a preset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PresetValues {
+    /// Time to wait for the reply to an ask
+    pub timeout: Duration,
+
+    /// Time to collect all packets of a msg
+    pub packet_ttl: Duration,
+
+    /// Whether the packet ttl is extended while packets keep arriving
+    pub adaptive_packet_ttl: bool,
+
+    /// Time to keep a callback awaiting a reply before discarding it
+    pub callback_ttl: Duration,
+
+    /// Size of the internal buffers for cross-thread messaging
+    pub buffer: usize,
+
+    /// Maximum number of asks that can await a reply at once
+    pub max_outstanding_asks: usize,
+}
+
+impl Preset {
+    /// Every preset, ordered from the most to the least reliable network
+    pub const ALL: [Preset; 3] = [Self::Lan, Self::Wan, Self::Lossy];
+
+    /// Name of the preset as accepted on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lan => "lan",
+            Self::Wan => "wan",
+            Self::Lossy => "lossy",
+        }
+    }
+
+    /// Settings applied to a client by the preset
+    pub fn values(self) -> PresetValues {
+        match self {
+            Self::Lan => PresetValues {
+                timeout: Duration::from_secs(2),
+                packet_ttl: Duration::from_secs(10),
+                adaptive_packet_ttl: true,
+                callback_ttl: Duration::from_secs(30),
+                buffer: 1000,
+                max_outstanding_asks: 1000,
+            },
+            Self::Wan => PresetValues {
+                timeout: Duration::from_secs(10),
+                packet_ttl: Duration::from_secs(60),
+                adaptive_packet_ttl: true,
+                callback_ttl: Duration::from_secs(2 * 60),
+                buffer: 1000,
+                max_outstanding_asks: 500,
+            },
+            Self::Lossy => PresetValues {
+                timeout: Duration::from_secs(30),
+                packet_ttl: Duration::from_secs(5 * 60),
+                adaptive_packet_ttl: true,
+                callback_ttl: Duration::from_secs(10 * 60),
+                buffer: 250,
+                max_outstanding_asks: 100,
+            },
+        }
+    }
+
+    /// Describes the settings applied by the preset, such as for help text
+    pub fn describe(self) -> String {
+        let values = self.values();
+        format!(
+            "{}: timeout {}s, packet ttl {}s{}, callback ttl {}s, \
+             buffer {}, max outstanding asks {}",
+            self.name(),
+            values.timeout.as_secs(),
+            values.packet_ttl.as_secs(),
+            if values.adaptive_packet_ttl {
+                " (adaptive)"
+            } else {
+                ""
+            },
+            values.callback_ttl.as_secs(),
+            values.buffer,
+            values.max_outstanding_asks,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_should_be_more_patient_for_less_reliable_networks() {
+        for pair in Preset::ALL.windows(2) {
+            let (a, b) = (pair[0].values(), pair[1].values());
+            assert!(a.timeout < b.timeout, "{:?}", pair);
+            assert!(a.packet_ttl < b.packet_ttl, "{:?}", pair);
+            assert!(
+                a.max_outstanding_asks >= b.max_outstanding_asks,
+                "{:?}",
+                pair
+            );
+        }
+    }
+
+    #[test]
+    fn describe_should_include_name_and_values() {
+        assert_eq!(
+            Preset::Lan.describe(),
+            "lan: timeout 2s, packet ttl 10s (adaptive), callback ttl 30s, \
+             buffer 1000, max outstanding asks 1000"
+        );
+    }
+}
@@ -1,4 +1,12 @@
+use super::{error::ExecAskError, ConnectedClient};
 use crate::core::reply::{ProcStartedArgs, ProcStatusArgs};
+use std::time::Duration;
+use tokio::time;
+
+/// Interval between asking for the status of a proc being waited on in case
+/// the server does not push its exit, such as for a proc started by another
+/// client
+const WAIT_FALLBACK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RemoteProcStatus {
@@ -32,6 +40,29 @@ impl RemoteProc {
     pub fn id(&self) -> u32 {
         self.id
     }
+
+    /// Waits for the process to exit, resolving once the server pushes its
+    /// exit and only asking for its status occasionally in case it does not
+    pub async fn wait(
+        &self,
+        client: &mut ConnectedClient,
+    ) -> Result<ProcStatusArgs, ExecAskError> {
+        let mut exit = client.state.lock().await.watch_proc_exit(self.id);
+        loop {
+            match time::timeout(WAIT_FALLBACK_INTERVAL, &mut exit).await {
+                Ok(Ok(status)) => return Ok(status),
+
+                // Client state was dropped, so only asking remains
+                Ok(Err(_)) => time::delay_for(WAIT_FALLBACK_INTERVAL).await,
+                Err(_) => (),
+            }
+
+            let status = client.ask_read_proc_status(self).await?;
+            if !status.is_alive {
+                return Ok(status);
+            }
+        }
+    }
 }
 
 impl From<ProcStartedArgs> for RemoteProc {
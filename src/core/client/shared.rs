@@ -0,0 +1,229 @@
+use super::{
+    callback_sweep_loop, connected::ClientEventManager, event_loop, inbound,
+    state::ClientState, Client, ConnectedClient,
+};
+use crate::core::transport::{
+    self as wire, Authenticator, Bicrypter, NetTransmission, Wire,
+};
+use crate::core::{event::AddrEventManager, Msg, Transport};
+use log::warn;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::{
+    io,
+    net::UdpSocket,
+    runtime::Handle,
+    sync::{mpsc, Mutex, Semaphore},
+    task::{JoinError, JoinHandle},
+};
+
+type Outbound = mpsc::Sender<(Vec<u8>, SocketAddr)>;
+type Inbound = (Msg, SocketAddr, Outbound);
+
+/// Senders of inbound msgs to the client of each connected server
+type Peers = Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Inbound>>>>;
+
+/// Single UDP socket used by clients of many servers, where msgs received on
+/// the socket are routed to the client of the server that sent them
+///
+/// All servers must use the same authenticator and bicrypter, as packets
+/// are signed and encrypted by a wire shared across the socket
+pub struct SharedUdpSocket {
+    /// Address of the local socket
+    addr: SocketAddr,
+
+    /// Represents the event manager used to send and receive data
+    addr_event_manager: AddrEventManager,
+
+    /// Represents the handle for routing inbound msgs to clients
+    event_handle: JoinHandle<()>,
+
+    peers: Peers,
+    buffer: usize,
+    timeout: Duration,
+    callback_ttl: Duration,
+    max_outstanding_asks: usize,
+    max_msg_size: usize,
+    pinned_server_keys: Vec<Vec<u8>>,
+}
+
+impl SharedUdpSocket {
+    pub(super) async fn bind<A, B>(client: Client<A, B>) -> io::Result<Self>
+    where
+        A: Authenticator + Send + Sync + 'static,
+        B: Bicrypter + Send + Sync + 'static,
+    {
+        let handle = Handle::current();
+        let ipv4 = match &client.transport {
+            Transport::Udp(addrs) => {
+                !matches!(addrs.first(), Some(a) if a.is_ipv6())
+            }
+            Transport::Tcp(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Shared sockets require udp transport",
+                ))
+            }
+        };
+
+        // NOTE: Must use Handle::enter to provide proper runtime when
+        //       using UdpSocket::from_std
+        let socket = handle.enter(|| {
+            wire::net::udp::unconnected(ipv4)
+                .and_then(|s| client.socket_options.apply_to_udp_socket(s))
+                .and_then(UdpSocket::from_std)
+        })?;
+        let addr = socket.local_addr()?;
+        let transmission = NetTransmission::udp_from_addr(addr);
+        let max_msg_size = transmission.max_msg_size();
+
+        let assembly = client.assembly_config();
+        let wire = Wire::new(
+            transmission.into(),
+            client.packet_ttl,
+            client.authenticator,
+            client.bicrypter,
+        )
+        .with_max_msg_size(max_msg_size)
+        .with_assembly_config(assembly);
+
+        let peers: Peers = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::channel(client.buffer);
+        let event_handle = handle.spawn(route_loop(Arc::clone(&peers), rx));
+        let addr_event_manager = AddrEventManager::for_udp_socket(
+            handle,
+            client.buffer,
+            socket,
+            wire,
+            tx,
+        );
+
+        Ok(Self {
+            addr,
+            addr_event_manager,
+            event_handle,
+            peers,
+            buffer: client.buffer,
+            timeout: client.timeout,
+            callback_ttl: client.callback_ttl,
+            // NOTE: At least one ask must be allowed, otherwise every ask
+            //       would wait forever for a permit
+            max_outstanding_asks: client.max_outstanding_asks.max(1),
+            max_msg_size,
+            pinned_server_keys: client.pinned_server_keys,
+        })
+    }
+
+    /// Represents the bound address of the local socket
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Addresses of the servers with a client using the socket
+    pub async fn peers(&self) -> Vec<SocketAddr> {
+        self.peers.lock().await.keys().copied().collect()
+    }
+
+    /// Produces a client of the server at `remote_addr` that communicates
+    /// over the shared socket, failing if the server already has a client,
+    /// uses a different ip version than the socket, or cannot prove a
+    /// pinned identity
+    pub async fn connect(
+        &self,
+        remote_addr: SocketAddr,
+    ) -> io::Result<ConnectedClient> {
+        if remote_addr.is_ipv4() != self.addr.is_ipv4() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} cannot be reached from shared socket {}",
+                    remote_addr, self.addr
+                ),
+            ));
+        }
+
+        let mut peers = self.peers.lock().await;
+        if peers.contains_key(&remote_addr) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already connected", remote_addr),
+            ));
+        }
+
+        let state = Arc::new(Mutex::new(ClientState::new(self.callback_ttl)));
+        let handle = Handle::current();
+        handle.spawn(callback_sweep_loop(Arc::downgrade(&state)));
+
+        let (tx, rx) = mpsc::channel(self.buffer);
+        let event_handle = handle.spawn(event_loop(
+            Arc::clone(&state),
+            inbound::InboundMsgReader::new(rx),
+        ));
+        peers.insert(remote_addr, tx);
+        drop(peers);
+
+        let mut client = ConnectedClient {
+            state,
+            event_manager: ClientEventManager::SharedSocket(
+                self.addr_event_manager.sender(),
+            ),
+            event_handle,
+            remote_addr,
+            timeout: self.timeout,
+            trace_id: None,
+            parent_span_id: None,
+            signing_key: None,
+            compression_threshold: None,
+            ask_options: Default::default(),
+            slow_ask_threshold: None,
+            ask_permits: Arc::new(Semaphore::new(self.max_outstanding_asks)),
+            max_outstanding_asks: self.max_outstanding_asks,
+            max_msg_size: self.max_msg_size,
+            failover: None,
+        };
+
+        if !self.pinned_server_keys.is_empty() {
+            if let Err(x) = client
+                .verify_server_identity(&self.pinned_server_keys)
+                .await
+            {
+                self.disconnect(remote_addr).await;
+                return Err(x);
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// Stops routing msgs from `remote_addr` to its client, which concludes
+    /// any wait on that client, returning whether or not it was connected
+    pub async fn disconnect(&self, remote_addr: SocketAddr) -> bool {
+        self.peers.lock().await.remove(&remote_addr).is_some()
+    }
+
+    /// Waits for the socket to close
+    pub async fn wait(self) -> Result<(), JoinError> {
+        tokio::try_join!(self.addr_event_manager.wait(), self.event_handle)
+            .map(|_| ())
+    }
+}
+
+/// Routes each inbound msg to the client of the server that sent it,
+/// discarding msgs from unknown servers
+async fn route_loop(peers: Peers, mut rx: mpsc::Receiver<Inbound>) {
+    while let Some((msg, addr, tx)) = rx.recv().await {
+        let mut peers = peers.lock().await;
+        match peers.get_mut(&addr) {
+            Some(peer) => {
+                // Once a client's event loop has ended, it no longer needs
+                // to be routed msgs
+                if peer.send((msg, addr, tx)).await.is_err() {
+                    peers.remove(&addr);
+                }
+            }
+            None => warn!("Discarding msg from unknown peer {}", addr),
+        }
+    }
+}
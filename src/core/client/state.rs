@@ -1,30 +1,260 @@
-use super::file::RemoteFile;
-use crate::core::msg::content::Reply;
+use super::event::ClientEvent;
+use crate::core::msg::content::{reply::ProcStatusArgs, Reply};
+use crate::core::transport::crypto::handshake::Session;
 use crate::utils::CallbackManager;
+use futures::channel::{mpsc, oneshot};
 use std::collections::HashMap;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct ClientState {
     /// Contains last time when client received response from server
     pub last_contact: Instant,
 
-    /// Contains the version of the remote instance
-    pub remote_version: String,
+    pub callback_manager: CallbackManager<Reply>,
 
-    /// Contains mapping of ids to remote files
-    pub files: HashMap<u32, RemoteFile>,
+    /// Contains mapping of ids of asks awaiting a reply to the time the
+    /// server reported spending on them, taken once the reply is received
+    pub server_times: HashMap<u32, Duration>,
 
-    pub callback_manager: CallbackManager<Reply>,
+    /// Contains timing of the asks that have been made
+    pub stats: ClientStats,
+
+    /// Senders of every stream of events returned by `events`
+    pub event_txs: Vec<mpsc::UnboundedSender<ClientEvent>>,
+
+    /// Contains mapping of ids of procs to the exits pushed by the server
+    pub proc_exits: HashMap<u32, ProcStatusArgs>,
+
+    /// Contains mapping of ids of procs to senders awaiting their exit
+    pub proc_exit_txs: HashMap<u32, Vec<oneshot::Sender<ProcStatusArgs>>>,
+
+    /// Session negotiated with the server through a handshake, whose key
+    /// seals every msg sent to and received from the server
+    pub session: Option<Session>,
 }
 
-impl Default for ClientState {
-    fn default() -> Self {
+impl ClientState {
+    /// Creates a new state whose callbacks are discarded if no reply is
+    /// received within `callback_ttl`
+    pub fn new(callback_ttl: Duration) -> Self {
         Self {
             last_contact: Instant::now(),
-            remote_version: String::default(),
-            files: HashMap::default(),
-            callback_manager: CallbackManager::default(),
+            callback_manager: CallbackManager::new(callback_ttl),
+            server_times: HashMap::default(),
+            stats: ClientStats::default(),
+            event_txs: Vec::new(),
+            proc_exits: HashMap::default(),
+            proc_exit_txs: HashMap::default(),
+            session: None,
+        }
+    }
+
+    /// Sends `event` to every subscriber, forgetting those that have gone
+    /// away
+    pub fn emit(&mut self, event: ClientEvent) {
+        self.event_txs
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Keeps the exit of a proc pushed by the server, telling everything
+    /// awaiting it
+    pub fn record_proc_exit(&mut self, status: ProcStatusArgs) {
+        for tx in self.proc_exit_txs.remove(&status.id).unwrap_or_default() {
+            let _ = tx.send(status.clone());
+        }
+
+        self.emit(ClientEvent::ProcExited {
+            id: status.id,
+            exit_code: status.exit_code,
+        });
+        self.proc_exits.insert(status.id, status);
+    }
+
+    /// Yields a receiver of the exit of the proc with `id`, which already
+    /// holds it if the server pushed it before
+    pub fn watch_proc_exit(
+        &mut self,
+        id: u32,
+    ) -> oneshot::Receiver<ProcStatusArgs> {
+        let (tx, rx) = oneshot::channel();
+        match self.proc_exits.get(&id) {
+            Some(status) => {
+                let _ = tx.send(status.clone());
+            }
+            None => self.proc_exit_txs.entry(id).or_default().push(tx),
         }
+        rx
+    }
+}
+
+impl Default for ClientState {
+    fn default() -> Self {
+        Self::new(CallbackManager::<Reply>::DEFAULT_TTL)
+    }
+}
+
+/// Snapshot of the asks made by a client
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AskMetrics {
+    /// Total asks currently awaiting a reply
+    pub in_flight: usize,
+
+    /// Maximum asks that can await a reply before new asks must wait
+    pub max_in_flight: usize,
+
+    /// Total callbacks registered for replies, including repeating
+    /// callbacks used to stream replies
+    pub outstanding_callbacks: usize,
+
+    /// Total callbacks discarded for going unanswered past their ttl
+    pub expired_callbacks: usize,
+}
+
+/// Time spent on each stage of a single ask
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AskTiming {
+    /// Time spent waiting for a permit while the maximum number of asks
+    /// were already in flight
+    pub queue: Duration,
+
+    /// Time spent encoding the request and handing it to the transport
+    pub send: Duration,
+
+    /// Time the server reported spending on the request, if it did so
+    pub server: Option<Duration>,
+
+    /// Time from starting to send the request until its reply arrived
+    pub rtt: Duration,
+}
+
+impl AskTiming {
+    /// Time from making the ask until its reply arrived
+    pub fn total(&self) -> Duration {
+        self.queue + self.rtt
+    }
+
+    /// Portion of the round trip not spent by the server, which is mostly
+    /// network latency; none if the server did not report its time
+    pub fn network(&self) -> Option<Duration> {
+        self.server
+            .map(|server| self.rtt.checked_sub(server).unwrap_or_default())
+    }
+}
+
+/// Timing of the asks made by a client, aggregated since it connected
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientStats {
+    /// Total asks that received a reply
+    pub asks: u64,
+
+    /// Total asks that timed out waiting on a reply
+    pub timeouts: u64,
+
+    /// Total asks whose reply took longer than the slow ask threshold
+    pub slow_asks: u64,
+
+    /// Sum of the time spent on each stage of the asks that received a
+    /// reply, where the server time only covers replies that reported it
+    pub total: AskTiming,
+
+    /// Total asks whose reply reported the time spent by the server
+    pub server_reported: u64,
+
+    /// Longest round trip of any ask
+    pub max_rtt: Duration,
+
+    /// Timing of the most recent ask that received a reply
+    pub last: Option<AskTiming>,
+}
+
+impl ClientStats {
+    /// Adds the timing of an ask that received a reply
+    pub fn record(&mut self, timing: AskTiming, slow: bool) {
+        self.asks += 1;
+        if slow {
+            self.slow_asks += 1;
+        }
+
+        self.total.queue += timing.queue;
+        self.total.send += timing.send;
+        self.total.rtt += timing.rtt;
+        if let Some(server) = timing.server {
+            self.server_reported += 1;
+            self.total.server =
+                Some(self.total.server.unwrap_or_default() + server);
+        }
+
+        self.max_rtt = self.max_rtt.max(timing.rtt);
+        self.last = Some(timing);
+    }
+
+    /// Average time spent on each stage of the asks that received a reply,
+    /// or none if no ask has
+    pub fn average(&self) -> Option<AskTiming> {
+        if self.asks == 0 {
+            return None;
+        }
+
+        let avg = |total: Duration, count: u64| {
+            Duration::from_nanos((total.as_nanos() / count as u128) as u64)
+        };
+        Some(AskTiming {
+            queue: avg(self.total.queue, self.asks),
+            send: avg(self.total.send, self.asks),
+            server: self
+                .total
+                .server
+                .map(|server| avg(server, self.server_reported)),
+            rtt: avg(self.total.rtt, self.asks),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(rtt_millis: u64, server_millis: Option<u64>) -> AskTiming {
+        AskTiming {
+            queue: Duration::from_millis(1),
+            send: Duration::from_millis(2),
+            server: server_millis.map(Duration::from_millis),
+            rtt: Duration::from_millis(rtt_millis),
+        }
+    }
+
+    #[test]
+    fn network_should_exclude_server_time_from_rtt() {
+        assert_eq!(
+            timing(10, Some(4)).network(),
+            Some(Duration::from_millis(6))
+        );
+        assert_eq!(timing(10, Some(15)).network(), Some(Duration::default()));
+        assert_eq!(timing(10, None).network(), None);
+        assert_eq!(timing(10, None).total(), Duration::from_millis(11));
+    }
+
+    #[test]
+    fn average_should_only_average_server_time_over_replies_reporting_it() {
+        let mut stats = ClientStats::default();
+        assert_eq!(stats.average(), None);
+
+        stats.record(timing(10, Some(4)), false);
+        stats.record(timing(30, None), true);
+
+        assert_eq!(stats.asks, 2);
+        assert_eq!(stats.slow_asks, 1);
+        assert_eq!(stats.max_rtt, Duration::from_millis(30));
+        assert_eq!(stats.last, Some(timing(30, None)));
+        assert_eq!(
+            stats.average(),
+            Some(AskTiming {
+                queue: Duration::from_millis(1),
+                send: Duration::from_millis(2),
+                server: Some(Duration::from_millis(4)),
+                rtt: Duration::from_millis(20),
+            })
+        );
     }
 }
@@ -0,0 +1,156 @@
+use super::{error::FileAskError, ConnectedClient};
+use crate::core::RemotePath;
+use futures::StreamExt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Dirs and files within a directory, as paths relative to it where each
+/// dir comes before anything within it
+///
+/// Relative paths are kept as the raw bytes of each name joined with `/`,
+/// so names that are not valid UTF-8 are copied as is on both sides.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tree {
+    pub dirs: Vec<RemotePath>,
+    pub files: Vec<RemotePath>,
+}
+
+/// Walks the local directory `root`, skipping symlinks
+pub async fn local_tree(root: &Path) -> io::Result<Tree> {
+    let mut tree = Tree::default();
+    let mut pending = vec![(root.to_path_buf(), RemotePath::default())];
+    while let Some((dir, prefix)) = pending.pop() {
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = RemotePath::from_path(entry.file_name());
+            let path = join(&prefix, name.as_bytes());
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                tree.dirs.push(path.clone());
+                pending.push((entry.path(), path));
+            } else if file_type.is_file() {
+                tree.files.push(path);
+            }
+        }
+    }
+
+    Ok(tree)
+}
+
+/// Walks `root` on the server, skipping symlinks, where the contents of
+/// each dir are streamed so that large dirs never need a single reply
+pub async fn remote_tree(
+    client: &mut ConnectedClient,
+    root: &RemotePath,
+) -> Result<Tree, FileAskError> {
+    let mut tree = Tree::default();
+    let mut pending = vec![(root.clone(), RemotePath::default())];
+    while let Some((dir, prefix)) = pending.pop() {
+        let entries = client.ask_list_dir_stream(dir).await?;
+        futures::pin_mut!(entries);
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let name = match file_name(&entry.path) {
+                Some(x) => x,
+                None => continue,
+            };
+            let path = join(&prefix, name);
+            if entry.is_symlink {
+                continue;
+            } else if entry.is_dir {
+                tree.dirs.push(path.clone());
+                pending.push((entry.path, path));
+            } else if entry.is_file {
+                tree.files.push(path);
+            }
+        }
+    }
+
+    Ok(tree)
+}
+
+/// Path on the server of `path`, relative to `remote_dir`
+pub fn remote_path(remote_dir: &RemotePath, path: &RemotePath) -> RemotePath {
+    let dir = remote_dir.as_bytes();
+    let trimmed = match dir.iter().rposition(|b| *b != b'/') {
+        Some(pos) => &dir[..=pos],
+        None => &dir[..0],
+    };
+
+    if trimmed.is_empty() && !dir.is_empty() {
+        RemotePath::from_bytes([b"/", path.as_bytes()].concat())
+    } else {
+        join(&RemotePath::from_bytes(trimmed), path.as_bytes())
+    }
+}
+
+/// Local path of `path`, relative to `local_dir`, built from the raw bytes
+/// of each name
+pub fn local_path(local_dir: &Path, path: &RemotePath) -> PathBuf {
+    path.as_bytes()
+        .split(|b| *b == b'/')
+        .fold(local_dir.to_path_buf(), |local, x| {
+            local.join(RemotePath::from_bytes(x).to_path_buf())
+        })
+}
+
+/// Parent of a path on the server, or none if it has no parent
+pub fn remote_parent(path: &RemotePath) -> Option<RemotePath> {
+    let bytes = path.as_bytes();
+    match bytes.iter().rposition(|b| *b == b'/')? {
+        0 => Some(RemotePath::from("/")),
+        pos => Some(RemotePath::from_bytes(&bytes[..pos])),
+    }
+}
+
+/// Last name of a path on the server, or none if it has no name
+fn file_name(path: &RemotePath) -> Option<&[u8]> {
+    match path.as_bytes().rsplit(|b| *b == b'/').next()? {
+        b"" | b"." | b".." => None,
+        name => Some(name),
+    }
+}
+
+fn join(prefix: &RemotePath, name: &[u8]) -> RemotePath {
+    if prefix.is_empty() {
+        RemotePath::from_bytes(name)
+    } else {
+        RemotePath::from_bytes([prefix.as_bytes(), b"/", name].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_path_should_join_the_raw_bytes_of_names() {
+        let name = RemotePath::from_bytes(&b"sub/\xff.txt"[..]);
+        assert_eq!(
+            remote_path(&RemotePath::from("/tmp/dir/"), &name),
+            RemotePath::from_bytes(&b"/tmp/dir/sub/\xff.txt"[..])
+        );
+        assert_eq!(
+            remote_path(&RemotePath::from("/"), &name),
+            RemotePath::from_bytes(&b"/sub/\xff.txt"[..])
+        );
+        assert_eq!(
+            remote_parent(&remote_path(&RemotePath::from("dir"), &name)),
+            Some(RemotePath::from("dir/sub"))
+        );
+        assert_eq!(file_name(&name), Some(&b"\xff.txt"[..]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_path_should_keep_names_that_are_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = RemotePath::from_bytes(&b"sub/\xff.txt"[..]);
+        assert_eq!(
+            local_path(Path::new("/tmp"), &name),
+            Path::new("/tmp/sub").join(OsStr::from_bytes(b"\xff.txt"))
+        );
+    }
+}
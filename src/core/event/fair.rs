@@ -0,0 +1,150 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Bytes that each destination is allowed to send per round of a fair
+/// queue, where a destination with larger msgs waits multiple rounds
+/// between them
+pub const DEFAULT_QUANTUM: usize = 16 * 1024;
+
+/// Queue of outbound data that takes turns between destinations using
+/// deficit round robin, so that a destination with a lot of data queued,
+/// such as one receiving a large download, cannot starve the others
+///
+/// Each destination earns a quantum of bytes whenever its turn comes up and
+/// sends data for as long as it has earned enough, carrying over what it
+/// did not spend to its next turn. Data bound for the same destination is
+/// always sent in the order it was pushed.
+#[derive(Debug)]
+pub struct FairQueue<K> {
+    quantum: usize,
+    flows: HashMap<K, Flow>,
+
+    /// Destinations with data queued, in the order of their turns
+    active: VecDeque<K>,
+
+    /// Whether the destination at the front has earned its quantum for the
+    /// current turn
+    turn_started: bool,
+
+    len: usize,
+}
+
+#[derive(Debug, Default)]
+struct Flow {
+    queue: VecDeque<Vec<u8>>,
+    deficit: usize,
+}
+
+impl<K: Eq + Hash + Clone> FairQueue<K> {
+    pub fn new(quantum: usize) -> Self {
+        Self {
+            quantum: quantum.max(1),
+            flows: HashMap::new(),
+            active: VecDeque::new(),
+            turn_started: false,
+            len: 0,
+        }
+    }
+
+    /// Total msgs queued across all destinations
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, key: K, data: Vec<u8>) {
+        let active = &mut self.active;
+        let flow = self.flows.entry(key.clone()).or_insert_with(|| {
+            active.push_back(key);
+            Flow::default()
+        });
+        flow.queue.push_back(data);
+        self.len += 1;
+    }
+
+    /// Removes the next data to send along with its destination
+    pub fn pop(&mut self) -> Option<(K, Vec<u8>)> {
+        loop {
+            let key = self.active.front()?.clone();
+            let flow = self.flows.get_mut(&key)?;
+            if !self.turn_started {
+                flow.deficit = flow.deficit.saturating_add(self.quantum);
+                self.turn_started = true;
+            }
+
+            let size = flow.queue.front().map(Vec::len).unwrap_or_default();
+            if flow.deficit >= size {
+                flow.deficit -= size;
+                let data = flow.queue.pop_front()?;
+                self.len -= 1;
+
+                // A destination with nothing left to send gives up its turn
+                // along with anything it did not spend
+                if flow.queue.is_empty() {
+                    self.flows.remove(&key);
+                    self.active.pop_front();
+                    self.turn_started = false;
+                }
+
+                return Some((key, data));
+            }
+
+            self.active.rotate_left(1);
+            self.turn_started = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &mut FairQueue<char>) -> String {
+        std::iter::from_fn(|| queue.pop()).map(|(k, _)| k).collect()
+    }
+
+    #[test]
+    fn pop_should_alternate_between_destinations_of_equal_msgs() {
+        let mut queue = FairQueue::new(10);
+        for _ in 0..3 {
+            queue.push('a', vec![0; 10]);
+        }
+        queue.push('b', vec![0; 10]);
+        queue.push('c', vec![0; 10]);
+
+        assert_eq!(queue.len(), 5);
+        assert_eq!(drain(&mut queue), "abcaa");
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn pop_should_share_bytes_rather_than_msgs() {
+        // Each turn earns 10 bytes, so `a` sends one of its large msgs for
+        // every three small msgs sent by `b`
+        let mut queue = FairQueue::new(10);
+        for _ in 0..2 {
+            queue.push('a', vec![0; 30]);
+        }
+        for _ in 0..6 {
+            queue.push('b', vec![0; 10]);
+        }
+
+        assert_eq!(drain(&mut queue), "bbabbbab");
+    }
+
+    #[test]
+    fn pop_should_preserve_order_for_same_destination() {
+        let mut queue = FairQueue::new(1);
+        for i in 0..5u8 {
+            queue.push('a', vec![i; 3]);
+        }
+
+        let order: Vec<u8> = std::iter::from_fn(|| queue.pop())
+            .map(|(_, x)| x[0])
+            .collect();
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+    }
+}
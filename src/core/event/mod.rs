@@ -1,15 +1,25 @@
+mod fair;
 mod tcp;
 mod udp;
 
 use crate::core::Msg;
+use crate::utils::TaskTracker;
 
+use futures::future::{self, AbortHandle};
 use log::{error, trace, warn};
 use crate::core::transport::InboundWireError;
+use std::future::Future;
 use std::net::SocketAddr;
-use tokio::{sync::mpsc, task};
+use tokio::{runtime::Handle, sync::mpsc, task};
+
+/// Inbound msg paired with the address it came from and a sender used to
+/// reply to that address
+pub type InboundAddrMsg =
+    (Msg, SocketAddr, mpsc::Sender<(Vec<u8>, SocketAddr)>);
 
 pub struct EventManager {
     inbound_handle: task::JoinHandle<()>,
+    inbound_abort: AbortHandle,
     outbound_handle: task::JoinHandle<()>,
     tx: mpsc::Sender<Vec<u8>>,
 }
@@ -22,12 +32,22 @@ impl EventManager {
     pub async fn wait(self) -> Result<(), task::JoinError> {
         tokio::try_join!(self.inbound_handle, self.outbound_handle).map(|_| ())
     }
+
+    /// Stops reading inbound data and waits for the outbound data already
+    /// queued to be sent, disconnecting once it has been
+    pub async fn close(self) -> Result<(), task::JoinError> {
+        self.inbound_abort.abort();
+        drop(self.tx);
+        tokio::try_join!(self.inbound_handle, self.outbound_handle).map(|_| ())
+    }
 }
 
 pub struct AddrEventManager {
     inbound_handle: task::JoinHandle<()>,
+    inbound_abort: AbortHandle,
     outbound_handle: task::JoinHandle<()>,
     tx: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+    tasks: TaskTracker,
 }
 
 impl AddrEventManager {
@@ -39,9 +59,46 @@ impl AddrEventManager {
         self.tx.send((data, addr)).await.map_err(|x| x.0)
     }
 
+    /// Produces a sender of outbound data that can be used independently
+    /// of the event manager, such as by clients sharing its socket
+    pub fn sender(&self) -> mpsc::Sender<(Vec<u8>, SocketAddr)> {
+        self.tx.clone()
+    }
+
+    /// Tasks spawned to service individual connections, such as each stream
+    /// accepted by a TCP listener
+    pub fn tasks(&self) -> &TaskTracker {
+        &self.tasks
+    }
+
     pub async fn wait(self) -> Result<(), task::JoinError> {
         tokio::try_join!(self.inbound_handle, self.outbound_handle).map(|_| ())
     }
+
+    /// Stops reading inbound data and waits for the outbound data already
+    /// queued to be sent, which completes once no senders produced by
+    /// `sender` remain
+    pub async fn close(self) -> Result<(), task::JoinError> {
+        self.inbound_abort.abort();
+        drop(self.tx);
+        tokio::try_join!(self.inbound_handle, self.outbound_handle).map(|_| ())
+    }
+}
+
+/// Spawns `future` such that it can be stopped early, as inbound loops
+/// otherwise wait on data forever while holding a sender of outbound data
+fn spawn_abortable<F>(
+    handle: &Handle,
+    future: F,
+) -> (task::JoinHandle<()>, AbortHandle)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (future, abort) = future::abortable(future);
+    let handle = handle.spawn(async move {
+        let _ = future.await;
+    });
+    (handle, abort)
 }
 
 /// Process result of receiving data, indicating whether should continue
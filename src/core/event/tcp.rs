@@ -1,9 +1,10 @@
 use super::{AddrEventManager, EventManager};
 use crate::core::Msg;
+use crate::utils::TaskTracker;
 
-use log::error;
+use log::{error, warn};
 use crate::core::transport::{
-    Authenticator, Bicrypter, Decrypter, Encrypter, Signer,
+    Authenticator, Bicrypter, Decrypter, Encrypter, Signer, SocketOptions,
     TcpStreamInboundWire, TcpStreamOutboundWire, Verifier, Wire,
 };
 use std::collections::HashMap;
@@ -34,15 +35,16 @@ impl EventManager {
 
         let (tx, rx) = mpsc::channel::<Vec<u8>>(max_outbound_queue);
 
-        let inbound_handle = handle.spawn(tcp_stream_outbound_loop(rx, writer));
-        let outbound_handle = handle.spawn(tcp_stream_inbound_loop(
-            tx.clone(),
-            reader,
-            on_inbound_tx,
-        ));
+        let outbound_handle =
+            handle.spawn(tcp_stream_outbound_loop(rx, writer));
+        let (inbound_handle, inbound_abort) = super::spawn_abortable(
+            &handle,
+            tcp_stream_inbound_loop(tx.clone(), reader, on_inbound_tx),
+        );
 
         EventManager {
             inbound_handle,
+            inbound_abort,
             outbound_handle,
             tx,
         }
@@ -52,12 +54,16 @@ impl EventManager {
 /// Implementation of AddrEventManager for TCP listener (requires Clone
 /// on Authenticator and Bicrypter)
 impl AddrEventManager {
+    /// Spawns the tasks servicing each accepted connection through `tasks`,
+    /// applying `socket_options` to each connection
     pub fn for_tcp_listener<A, B>(
         handle: Handle,
         max_outbound_queue: usize,
         listener: TcpListener,
         wire: Wire<A, B>,
         on_inbound_tx: mpsc::Sender<(Msg, SocketAddr, mpsc::Sender<Vec<u8>>)>,
+        tasks: TaskTracker,
+        socket_options: SocketOptions,
     ) -> AddrEventManager
     where
         A: Authenticator + Send + Sync + Clone + 'static,
@@ -72,19 +78,26 @@ impl AddrEventManager {
         let outbound_handle = handle
             .spawn(tcp_listener_outbound_loop(rx, Arc::clone(&connections)));
 
-        let inbound_handle = handle.spawn(tcp_listener_inbound_loop(
-            handle.clone(),
-            listener,
-            wire,
-            connections,
-            on_inbound_tx,
-            max_outbound_queue,
-        ));
+        let (inbound_handle, inbound_abort) = super::spawn_abortable(
+            &handle,
+            tcp_listener_inbound_loop(
+                handle.clone(),
+                tasks.clone(),
+                listener,
+                wire,
+                connections,
+                on_inbound_tx,
+                max_outbound_queue,
+                socket_options,
+            ),
+        );
 
         AddrEventManager {
             outbound_handle,
             inbound_handle,
+            inbound_abort,
             tx,
+            tasks,
         }
     }
 }
@@ -107,13 +120,16 @@ async fn tcp_listener_outbound_loop(
 /// Loops continuously accepting new connections and spawning EventManager
 /// instances to process incoming and outgoing msgs over each individual
 /// TcpStream formed by a connection
+#[allow(clippy::too_many_arguments)]
 async fn tcp_listener_inbound_loop<A, B>(
     handle: Handle,
+    tasks: TaskTracker,
     mut listener: TcpListener,
     wire: Wire<A, B>,
     connections: Arc<Mutex<HashMap<SocketAddr, mpsc::Sender<Vec<u8>>>>>,
     on_inbound_tx: mpsc::Sender<(Msg, SocketAddr, mpsc::Sender<Vec<u8>>)>,
     max_outbound_queue: usize,
+    socket_options: SocketOptions,
 ) where
     A: Authenticator + Send + Sync + Clone + 'static,
     B: Bicrypter + Send + Sync + Clone + 'static,
@@ -121,15 +137,24 @@ async fn tcp_listener_inbound_loop<A, B>(
     loop {
         match listener.accept().await {
             Ok((stream, addr)) => {
-                handle.spawn(tcp_listener_spawn_stream(
-                    stream,
-                    addr,
-                    handle.clone(),
-                    wire.clone(),
-                    Arc::clone(&connections),
-                    on_inbound_tx.clone(),
-                    max_outbound_queue,
-                ));
+                // NOTE: A connection whose options cannot be applied is
+                //       still serviced with the defaults of the system
+                if let Err(x) = socket_options.apply_to_tcp_stream(&stream) {
+                    warn!("Failed to apply socket options to {}: {}", addr, x);
+                }
+
+                tasks.spawn(
+                    &handle,
+                    tcp_listener_spawn_stream(
+                        stream,
+                        addr,
+                        handle.clone(),
+                        wire.clone(),
+                        Arc::clone(&connections),
+                        on_inbound_tx.clone(),
+                        max_outbound_queue,
+                    ),
+                );
             }
             Err(x) => {
                 error!("Listening for connections encountered error: {}", x);
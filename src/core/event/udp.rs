@@ -1,5 +1,8 @@
-use super::AddrEventManager;
-use crate::core::Msg;
+use super::{
+    fair::{self, FairQueue},
+    AddrEventManager, InboundAddrMsg,
+};
+use crate::utils::TaskTracker;
 
 use log::error;
 use crate::core::transport::{
@@ -16,11 +19,7 @@ impl AddrEventManager {
         max_outbound_queue: usize,
         socket: UdpSocket,
         wire: Wire<A, B>,
-        on_inbound_tx: mpsc::Sender<(
-            Msg,
-            SocketAddr,
-            mpsc::Sender<(Vec<u8>, SocketAddr)>,
-        )>,
+        on_inbound_tx: mpsc::Sender<InboundAddrMsg>,
     ) -> AddrEventManager
     where
         A: Authenticator + Send + Sync + 'static,
@@ -30,18 +29,22 @@ impl AddrEventManager {
 
         let (tx, rx) =
             mpsc::channel::<(Vec<u8>, SocketAddr)>(max_outbound_queue);
-        let outbound_handle =
-            handle.spawn(udp_socket_outbound_loop(rx, writer));
-        let inbound_handle = handle.spawn(udp_socket_inbound_loop(
-            tx.clone(),
-            reader,
-            on_inbound_tx,
+        let outbound_handle = handle.spawn(udp_socket_outbound_loop(
+            rx,
+            writer,
+            max_outbound_queue,
         ));
+        let (inbound_handle, inbound_abort) = super::spawn_abortable(
+            &handle,
+            udp_socket_inbound_loop(tx.clone(), reader, on_inbound_tx),
+        );
 
         AddrEventManager {
             outbound_handle,
             inbound_handle,
+            inbound_abort,
             tx,
+            tasks: TaskTracker::default(),
         }
     }
 }
@@ -53,11 +56,7 @@ impl AddrEventManager {
         max_outbound_queue: usize,
         socket: UdpSocket,
         wire: Wire<A, B>,
-        on_inbound_tx: mpsc::Sender<(
-            Msg,
-            SocketAddr,
-            mpsc::Sender<(Vec<u8>, SocketAddr)>,
-        )>,
+        on_inbound_tx: mpsc::Sender<InboundAddrMsg>,
     ) -> AddrEventManager
     where
         A: Authenticator + Send + Sync + Clone + 'static,
@@ -67,33 +66,63 @@ impl AddrEventManager {
 
         let (tx, rx) =
             mpsc::channel::<(Vec<u8>, SocketAddr)>(max_outbound_queue);
-        let outbound_handle =
-            handle.spawn(udp_socket_outbound_loop(rx, writer));
-        let inbound_handle = handle.spawn(udp_socket_inbound_loop(
-            tx.clone(),
-            reader,
-            on_inbound_tx,
+        let outbound_handle = handle.spawn(udp_socket_outbound_loop(
+            rx,
+            writer,
+            max_outbound_queue,
         ));
+        let (inbound_handle, inbound_abort) = super::spawn_abortable(
+            &handle,
+            udp_socket_inbound_loop(tx.clone(), reader, on_inbound_tx),
+        );
 
         AddrEventManager {
             outbound_handle,
             inbound_handle,
+            inbound_abort,
             tx,
+            tasks: TaskTracker::default(),
         }
     }
 }
 
+/// Loops continuously, sending outbound data over the socket while taking
+/// turns between destinations, so that one receiving a large transfer does
+/// not hold up replies to the others
+///
+/// Outbound data is taken from `rx` as soon as it is available, up to
+/// `max_outbound_queue` msgs, so that it can be ordered fairly
 async fn udp_socket_outbound_loop<S, E>(
     mut rx: mpsc::Receiver<(Vec<u8>, SocketAddr)>,
     mut writer: UdpSocketOutboundWire<S, E>,
+    max_outbound_queue: usize,
 ) where
     S: Signer,
     E: Encrypter,
 {
-    while let Some((msg, addr)) = rx.recv().await {
-        if let Err(x) = writer.write_to(&msg, addr).await {
-            error!("Failed to send: {}", x);
-            break;
+    let mut queue = FairQueue::new(fair::DEFAULT_QUANTUM);
+    loop {
+        // NOTE: Only wait for more data when there is nothing left to send,
+        //       so that data queued before closing is still sent
+        if queue.is_empty() {
+            match rx.recv().await {
+                Some((msg, addr)) => queue.push(addr, msg),
+                None => break,
+            }
+        }
+
+        while queue.len() < max_outbound_queue {
+            match rx.try_recv() {
+                Ok((msg, addr)) => queue.push(addr, msg),
+                Err(_) => break,
+            }
+        }
+
+        if let Some((addr, msg)) = queue.pop() {
+            if let Err(x) = writer.write_to(&msg, addr).await {
+                error!("Failed to send: {}", x);
+                break;
+            }
         }
     }
 }
@@ -101,11 +130,7 @@ async fn udp_socket_outbound_loop<S, E>(
 async fn udp_socket_inbound_loop<V, D>(
     tx: mpsc::Sender<(Vec<u8>, SocketAddr)>,
     mut reader: UdpSocketInboundWire<V, D>,
-    on_inbound_tx: mpsc::Sender<(
-        Msg,
-        SocketAddr,
-        mpsc::Sender<(Vec<u8>, SocketAddr)>,
-    )>,
+    on_inbound_tx: mpsc::Sender<InboundAddrMsg>,
 ) where
     V: Verifier,
     D: Decrypter,
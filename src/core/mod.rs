@@ -1,31 +1,60 @@
+mod chunk;
 mod client;
 mod event;
 mod msg;
 mod server;
 pub mod transport;
 
+pub use chunk::{
+    Chunk, Chunker, DEFAULT_AVG_CHUNK_SIZE, DEFAULT_MAX_CHUNK_SIZE,
+    DEFAULT_MIN_CHUNK_SIZE,
+};
 pub use client::{
+    copy::DirCopy,
     error::AskError,
     error::ExecAskError,
     error::FileAskError,
     error::SendError,
-    file::RemoteFile,
+    file::{DownloadOptions, DownloadProgress, RemoteFile},
+    mirror::{Mirror, MirrorOptions, MirrorPass, MIN_MIRROR_INTERVAL},
     proc::{RemoteProc, RemoteProcStatus},
-    Client, ClientBuilder, ConnectedClient,
+    AskMetrics, AskOptions, AskTiming, Client, ClientBuilder, ClientEvent,
+    ClientStats, ClockOffset, ConnectedClient, Preset, PresetValues,
+    SharedUdpSocket,
 };
 pub use event::{AddrEventManager, EventManager};
 pub use msg::{
+    compression::DEFAULT_COMPRESSION_THRESHOLD,
     content::{
-        reply, reply::Capability, request, Content, LazilyTransformedRequest,
-        Reply, ReplyError, Request, TransformRequestError, TransformRule,
+        constraints, reply, reply::Capability, request, Content,
+        LazilyTransformedRequest, RemotePath, Reply, ReplyError, Request,
+        TransformRequestError, TransformRule,
     },
-    Header, Msg, MsgError,
+    is_strict_decoding, set_strict_decoding, Header, Msg, MsgError, MsgFlags,
+    MsgSignature,
 };
 pub use server::{
-    fs::{FileSystemManager, LocalDirEntry, LocalFile, LocalFileHandle},
+    config::{ConfigStore, PushedConfig},
+    fs::{
+        FileSystemManager, LocalDirEntry, LocalFile, LocalFileHandle,
+        RetryPolicy,
+    },
+    launcher::ProcLauncher,
+    permissions::Permissions,
+    power::PowerControl,
     proc::{ExitStatus, LocalProc},
+    relay::Relay,
+    signing::{SignatureMode, SignaturePolicy},
+    trusted::TrustedClients,
+    webhook::{Webhook, WebhookEvent},
     ListeningServer, Server, ServerBuilder,
 };
+#[cfg(unix)]
+pub use server::fs::secure::{SecureOpenOptions, SecureRoot};
+#[cfg(feature = "script")]
+pub use server::script::ScriptHooks;
+#[cfg(feature = "wasm")]
+pub use server::wasm::WasmHandlers;
 pub use transport::net;
 
 use std::net::SocketAddr;
@@ -46,9 +75,11 @@ pub enum Transport {
 }
 
 pub trait SchemaInfo: schemars::JsonSchema {
-    /// Outputs schema as a pretty JSON string
+    /// Outputs schema as a pretty JSON string, including the units and
+    /// bounds of constrained fields
     fn schema() -> String {
-        let schema = schemars::schema_for!(Self);
+        let mut schema = schemars::schema_for!(Self);
+        msg::content::constraints::annotate(&mut schema);
         serde_json::to_string_pretty(&schema)
             .expect("Failed to serialize schema")
     }
@@ -0,0 +1,199 @@
+use std::convert::TryFrom;
+
+/// Deepest nesting of arrays, maps, and tags that is walked, matching the
+/// limit that serde_cbor applies while decoding
+const MAX_DEPTH: usize = 128;
+
+/// Break marking the end of an item of indefinite length
+const BREAK: u8 = 0xff;
+
+/// Finds the encoded bytes of each value of the map encoded in `slice` that
+/// has a text key, exactly as they appear in `slice`, or none if `slice` is
+/// not a single well-formed map
+pub fn map_values(slice: &[u8]) -> Option<Vec<(&str, &[u8])>> {
+    let (major, len, mut pos) = head(slice, 0)?;
+    if major != 5 {
+        return None;
+    }
+
+    let mut values = Vec::new();
+    let mut remaining = len;
+    loop {
+        match remaining {
+            Some(0) => break,
+            None if *slice.get(pos)? == BREAK => {
+                pos += 1;
+                break;
+            }
+            _ => {}
+        }
+
+        let key_start = pos;
+        pos = skip(slice, pos, 0)?;
+        let key = match head(slice, key_start)? {
+            (3, Some(len), start) if start + len as usize == pos => {
+                std::str::from_utf8(&slice[start..pos]).ok()
+            }
+            _ => None,
+        };
+
+        let value_start = pos;
+        pos = skip(slice, pos, 0)?;
+        if let Some(key) = key {
+            values.push((key, &slice[value_start..pos]));
+        }
+
+        remaining = remaining.map(|x| x - 1);
+    }
+
+    if pos == slice.len() {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+/// Reads the head of the item at `pos`, yielding its major type, its
+/// argument (or none if of indefinite length), and the position after it
+fn head(slice: &[u8], pos: usize) -> Option<(u8, Option<u64>, usize)> {
+    let initial = *slice.get(pos)?;
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    let pos = pos + 1;
+
+    let size = match info {
+        0..=23 => return Some((major, Some(u64::from(info)), pos)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 if (2..=5).contains(&major) => return Some((major, None, pos)),
+        _ => return None,
+    };
+
+    let bytes = slice.get(pos..pos.checked_add(size)?)?;
+    let arg = bytes.iter().fold(0u64, |arg, b| (arg << 8) | u64::from(*b));
+    Some((major, Some(arg), pos + size))
+}
+
+/// Skips over the item at `pos`, yielding the position after it
+fn skip(slice: &[u8], pos: usize, depth: usize) -> Option<usize> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    let (major, arg, mut pos) = head(slice, pos)?;
+    match (major, arg) {
+        // Integers, simple values, and floats are entirely within the head
+        (0, _) | (1, _) | (7, _) => Some(pos),
+
+        // Byte and text strings
+        (2, Some(len)) | (3, Some(len)) => {
+            let end = pos.checked_add(usize::try_from(len).ok()?)?;
+            if end <= slice.len() {
+                Some(end)
+            } else {
+                None
+            }
+        }
+
+        // Arrays and maps, where each entry of a map is two items
+        (4, Some(len)) | (5, Some(len)) => {
+            let items = if major == 5 { len.checked_mul(2)? } else { len };
+            for _ in 0..items {
+                pos = skip(slice, pos, depth + 1)?;
+            }
+            Some(pos)
+        }
+
+        // Items of indefinite length, which end with a break
+        (_, None) => {
+            while *slice.get(pos)? != BREAK {
+                pos = skip(slice, pos, depth + 1)?;
+            }
+            Some(pos + 1)
+        }
+
+        // Tags, which apply to the single item that follows
+        (6, Some(_)) => skip(slice, pos, depth + 1),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Outer {
+        a: u32,
+        b: Vec<Option<String>>,
+        c: Inner,
+    }
+
+    #[derive(Serialize)]
+    struct Inner {
+        x: f64,
+        y: Vec<u8>,
+    }
+
+    #[test]
+    fn map_values_should_yield_encoded_bytes_of_each_value() {
+        let outer = Outer {
+            a: 100_000,
+            b: vec![Some(String::from("text")), None],
+            c: Inner {
+                x: 1.5,
+                y: vec![1, 2, 3],
+            },
+        };
+        let data = serde_cbor::to_vec(&outer).unwrap();
+
+        let values = map_values(&data).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                ("a", serde_cbor::to_vec(&outer.a).unwrap().as_slice()),
+                ("b", serde_cbor::to_vec(&outer.b).unwrap().as_slice()),
+                ("c", serde_cbor::to_vec(&outer.c).unwrap().as_slice()),
+            ]
+        );
+    }
+
+    #[test]
+    fn map_values_should_support_indefinite_lengths() {
+        // {_ "a": [_ 1, 2], "b": (_ h'01', h'02')}
+        let data = [
+            0xbf, 0x61, b'a', 0x9f, 0x01, 0x02, 0xff, 0x61, b'b', 0x5f, 0x41,
+            0x01, 0x41, 0x02, 0xff, 0xff,
+        ];
+
+        let values = map_values(&data).unwrap();
+        assert_eq!(values, vec![("a", &data[3..7]), ("b", &data[9..15]),]);
+    }
+
+    #[test]
+    fn map_values_should_reject_malformed_input() {
+        let data = serde_cbor::to_vec(&Outer {
+            a: 1,
+            b: vec![None],
+            c: Inner {
+                x: 0.0,
+                y: vec![7; 32],
+            },
+        })
+        .unwrap();
+
+        assert_eq!(map_values(&data[..data.len() - 1]), None);
+        assert_eq!(map_values(&[data.as_slice(), &[0x00]].concat()), None);
+        assert_eq!(map_values(&serde_cbor::to_vec(&[1, 2]).unwrap()), None);
+
+        // Nesting beyond the limit
+        let mut nested = vec![0xa1, 0x61, b'a'];
+        nested.extend(vec![0x81; MAX_DEPTH + 1]);
+        nested.push(0x00);
+        assert_eq!(map_values(&nested), None);
+    }
+}
@@ -0,0 +1,75 @@
+//! Compression of encoded msgs
+//!
+//! An encoded msg is always a CBOR map, so a compressed msg is instead
+//! encoded as a CBOR byte string holding the DEFLATE compression of the
+//! encoded msg, which lets the two be told apart by their first byte.
+
+use miniz_oxide::{deflate::compress_to_vec, inflate};
+use serde_bytes::{ByteBuf, Bytes};
+
+/// Size in bytes below which an encoded msg is not worth compressing
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Level of DEFLATE compression, favoring speed over size
+const COMPRESSION_LEVEL: u8 = 3;
+
+/// CBOR major type of a byte string, found in the top three bits of the
+/// first byte of an encoded item
+const CBOR_BYTE_STRING: u8 = 2;
+
+/// Compresses the encoded msg `data`, yielding none if doing so would not
+/// make it any smaller
+pub fn compress(data: &[u8]) -> Option<Vec<u8>> {
+    let compressed = compress_to_vec(data, COMPRESSION_LEVEL);
+    let compressed = serde_cbor::to_vec(&Bytes::new(&compressed)).ok()?;
+    if compressed.len() < data.len() {
+        Some(compressed)
+    } else {
+        None
+    }
+}
+
+/// Whether `data` is a compressed msg rather than an encoded msg
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.first().map(|b| b >> 5) == Some(CBOR_BYTE_STRING)
+}
+
+/// Decompresses the compressed msg `data`, failing if the encoded msg would
+/// be larger than `max_size`
+pub fn decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>, String> {
+    let compressed: ByteBuf =
+        serde_cbor::from_slice(data).map_err(|x| x.to_string())?;
+    inflate::decompress_to_vec_with_limit(&compressed, max_size).map_err(|x| {
+        match x.status {
+            inflate::TINFLStatus::HasMoreOutput => format!(
+                "Decompressed msg exceeds maximum size of {} bytes",
+                max_size
+            ),
+            status => format!("Failed to decompress msg: {:?}", status),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompress_should_restore_compressed_data() {
+        let data = vec![7; 4096];
+        let compressed = compress(&data).unwrap();
+        assert!(is_compressed(&compressed));
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn compress_should_yield_none_if_data_does_not_shrink() {
+        assert_eq!(compress(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn decompress_should_fail_if_data_exceeds_max_size() {
+        let compressed = compress(&vec![7; 4096]).unwrap();
+        assert!(decompress(&compressed, 4095).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+//! Units and bounds of the numeric fields of msgs, which are emitted into
+//! the JSON schemas of msgs and enforced by the server on the requests it
+//! receives, so that clients generated from the schemas can reject values
+//! the server would reject anyway
+
+use super::request;
+use schemars::{
+    schema::{RootSchema, Schema, SchemaObject},
+    JsonSchema,
+};
+use serde_json::Value;
+use std::io;
+
+/// Name of the schema extension holding the unit of a field
+pub const UNIT_EXTENSION: &str = "x-unit";
+
+/// Unit of a numeric field
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Bytes,
+    Lines,
+    Columns,
+    Seconds,
+    Microseconds,
+
+    /// Unix permission bits, such as 0o644
+    FileMode,
+}
+
+impl Unit {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bytes => "bytes",
+            Self::Lines => "lines",
+            Self::Columns => "columns",
+            Self::Seconds => "seconds",
+            Self::Microseconds => "microseconds",
+            Self::FileMode => "file_mode",
+        }
+    }
+}
+
+/// Unit and bounds of a single field of a msg type
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FieldConstraint {
+    /// Name of the type as it appears in schemas
+    pub type_name: &'static str,
+
+    pub field: &'static str,
+    pub unit: Unit,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub example: Option<u64>,
+}
+
+impl FieldConstraint {
+    const fn new(
+        type_name: &'static str,
+        field: &'static str,
+        unit: Unit,
+    ) -> Self {
+        Self {
+            type_name,
+            field,
+            unit,
+            min: None,
+            max: None,
+            example: None,
+        }
+    }
+
+    const fn min(mut self, min: u64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    const fn max(mut self, max: u64) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    const fn example(mut self, example: u64) -> Self {
+        self.example = Some(example);
+        self
+    }
+
+    /// Fails with `InvalidInput` if `value` is outside of the bounds
+    pub fn check(&self, value: u64) -> io::Result<()> {
+        let invalid = |bound: &str, limit: u64| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}.{} of {} is {} {} {}",
+                    self.type_name,
+                    self.field,
+                    value,
+                    bound,
+                    limit,
+                    self.unit.name()
+                ),
+            ))
+        };
+
+        match (self.min, self.max) {
+            (Some(min), _) if value < min => invalid("below minimum of", min),
+            (_, Some(max)) if value > max => invalid("above maximum of", max),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, schema: &mut SchemaObject) {
+        schema
+            .extensions
+            .insert(UNIT_EXTENSION.to_string(), Value::from(self.unit.name()));
+        if let Some(min) = self.min {
+            schema.number().minimum = Some(min as f64);
+        }
+        if let Some(max) = self.max {
+            schema.number().maximum = Some(max as f64);
+        }
+        if let Some(example) = self.example {
+            schema.metadata().examples = vec![Value::from(example)];
+        }
+    }
+}
+
+/// Largest mode of a file or dir, covering its permission bits along with
+/// the setuid, setgid, and sticky bits
+const MAX_MODE: u64 = 0o7777;
+
+/// Largest size of either file that a diff can be asked to compare, as the
+/// whole of both files is held in memory while diffing
+const MAX_DIFF_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Longest a power action can be delayed
+const MAX_POWER_DELAY_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Constraints on fields of msgs, where only those with bounds are enforced
+pub const FIELD_CONSTRAINTS: &[FieldConstraint] = &[
+    // Requests
+    FieldConstraint::new("CreateDirArgs", "mode", Unit::FileMode)
+        .max(MAX_MODE)
+        .example(0o755),
+    FieldConstraint::new("SniffFileArgs", "max_bytes", Unit::Bytes)
+        .min(1)
+        .example(8192),
+    FieldConstraint::new("DiffFilesArgs", "context_lines", Unit::Lines)
+        .example(3),
+    FieldConstraint::new("DiffFilesArgs", "max_bytes", Unit::Bytes)
+        .max(MAX_DIFF_BYTES)
+        .example(1048576),
+    FieldConstraint::new("OpenFileArgs", "mode", Unit::FileMode)
+        .max(MAX_MODE)
+        .example(0o644),
+    FieldConstraint::new("ReadFileArgs", "offset", Unit::Bytes),
+    FieldConstraint::new("ReadFileArgs", "len", Unit::Bytes),
+    FieldConstraint::new("ReadFilesArgs", "max_total_bytes", Unit::Bytes),
+    FieldConstraint::new("WriteFileAtomicByPathArgs", "mode", Unit::FileMode)
+        .max(MAX_MODE)
+        .example(0o644),
+    FieldConstraint::new("FilePreconditions", "min_size", Unit::Bytes),
+    FieldConstraint::new("FilePreconditions", "max_size", Unit::Bytes),
+    FieldConstraint::new("ManifestFile", "size", Unit::Bytes),
+    FieldConstraint::new("ManifestFile", "mode", Unit::FileMode)
+        .max(MAX_MODE)
+        .example(0o644),
+    FieldConstraint::new("ManifestChunk", "len", Unit::Bytes)
+        .min(1)
+        .example(8192),
+    FieldConstraint::new("ExecProcArgs", "umask", Unit::FileMode)
+        .max(0o777)
+        .example(0o022),
+    FieldConstraint::new("ExecProcPtyArgs", "rows", Unit::Lines)
+        .min(1)
+        .example(24),
+    FieldConstraint::new("ExecProcPtyArgs", "cols", Unit::Columns)
+        .min(1)
+        .example(80),
+    FieldConstraint::new("ResizePtyArgs", "rows", Unit::Lines)
+        .min(1)
+        .example(24),
+    FieldConstraint::new("ResizePtyArgs", "cols", Unit::Columns)
+        .min(1)
+        .example(80),
+    FieldConstraint::new("ProcOutputFilter", "max_bytes", Unit::Bytes),
+    FieldConstraint::new("ListProcsArgs", "min_age_secs", Unit::Seconds),
+    FieldConstraint::new("ListProcsArgs", "max_age_secs", Unit::Seconds),
+    FieldConstraint::new("ScheduleTrigger", "secs", Unit::Seconds)
+        .min(1)
+        .example(3600),
+    FieldConstraint::new("ReadLogRangeArgs", "max_lines", Unit::Lines),
+    FieldConstraint::new("ListConnectionsArgs", "max_idle_secs", Unit::Seconds),
+    FieldConstraint::new(
+        "ListPendingEvictionsArgs",
+        "within_secs",
+        Unit::Seconds,
+    ),
+    FieldConstraint::new("PowerControlArgs", "delay_secs", Unit::Seconds)
+        .max(MAX_POWER_DELAY_SECS)
+        .example(60),
+    FieldConstraint::new("TimeInfoArgs", "sent_at_micros", Unit::Microseconds),
+    // Replies
+    FieldConstraint::new("FileChecksumArgs", "size", Unit::Bytes),
+    FieldConstraint::new(
+        "TimeInfoArgs",
+        "server_received_at_micros",
+        Unit::Microseconds,
+    ),
+    FieldConstraint::new(
+        "TimeInfoArgs",
+        "server_sent_at_micros",
+        Unit::Microseconds,
+    ),
+    FieldConstraint::new(
+        "TimeInfoArgs",
+        "client_sent_at_micros",
+        Unit::Microseconds,
+    ),
+    FieldConstraint::new("PowerControlArgs", "expires_in_secs", Unit::Seconds),
+];
+
+/// Fails with `InvalidInput` if `value` of `field` of `T` is outside of its
+/// bounds, where a missing value is always within them
+pub fn check<T: JsonSchema>(field: &str, value: Option<u64>) -> io::Result<()> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+
+    let type_name = T::schema_name();
+    match FIELD_CONSTRAINTS
+        .iter()
+        .find(|c| c.type_name == type_name && c.field == field)
+    {
+        Some(constraint) => constraint.check(value),
+        None => Ok(()),
+    }
+}
+
+/// Emits the unit, bounds, and example of every constrained field of the
+/// types within `schema`
+pub fn annotate(schema: &mut RootSchema) {
+    annotate_with(schema, FIELD_CONSTRAINTS);
+}
+
+/// Applies `constraints` to the types within `schema`, yielding the number
+/// of fields they were applied to
+fn annotate_with(
+    schema: &mut RootSchema,
+    constraints: &[FieldConstraint],
+) -> usize {
+    let mut count = 0;
+    let title = schema
+        .schema
+        .metadata
+        .as_ref()
+        .and_then(|m| m.title.clone());
+    if let Some(name) = title {
+        count += annotate_type(&name, &mut schema.schema, constraints);
+    }
+
+    for (name, definition) in schema.definitions.iter_mut() {
+        if let Schema::Object(definition) = definition {
+            count += annotate_type(name, definition, constraints);
+        }
+    }
+
+    count
+}
+
+/// Annotates the fields of `schema`, including those of its variants if it
+/// is an enum
+fn annotate_type(
+    type_name: &str,
+    schema: &mut SchemaObject,
+    constraints: &[FieldConstraint],
+) -> usize {
+    let mut count = 0;
+
+    if let Some(object) = schema.object.as_mut() {
+        for constraint in
+            constraints.iter().filter(|c| c.type_name == type_name)
+        {
+            if let Some(Schema::Object(field)) =
+                object.properties.get_mut(constraint.field)
+            {
+                constraint.apply(field);
+                count += 1;
+            }
+        }
+    }
+
+    if let Some(subschemas) = schema.subschemas.as_mut() {
+        let variants = subschemas
+            .one_of
+            .iter_mut()
+            .chain(subschemas.any_of.iter_mut())
+            .chain(subschemas.all_of.iter_mut())
+            .flatten();
+        for variant in variants {
+            if let Schema::Object(variant) = variant {
+                count += annotate_type(type_name, variant, constraints);
+            }
+        }
+    }
+
+    count
+}
+
+/// Checks that the fields of a msg are within their bounds
+pub trait Validate {
+    /// Fails with `InvalidInput` naming the first field out of its bounds
+    fn validate(&self) -> io::Result<()>;
+}
+
+impl Validate for request::CreateDirArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("mode", self.mode.map(u64::from))
+    }
+}
+
+impl Validate for request::SniffFileArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("max_bytes", self.max_bytes)
+    }
+}
+
+impl Validate for request::DiffFilesArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("max_bytes", self.max_bytes)
+    }
+}
+
+impl Validate for request::OpenFileArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("mode", self.mode.map(u64::from))
+    }
+}
+
+impl Validate for request::WriteFileAtomicByPathArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("mode", self.mode.map(u64::from))
+    }
+}
+
+impl Validate for request::ManifestFile {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("mode", self.mode.map(u64::from))?;
+        self.chunks.iter().try_for_each(Validate::validate)
+    }
+}
+
+impl Validate for request::ManifestChunk {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("len", Some(self.len))
+    }
+}
+
+impl Validate for request::ExecProcArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("umask", self.umask.map(u64::from))
+    }
+}
+
+impl Validate for request::ExecProcPtyArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("rows", Some(u64::from(self.rows)))?;
+        check::<Self>("cols", Some(u64::from(self.cols)))
+    }
+}
+
+impl Validate for request::ResizePtyArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("rows", Some(u64::from(self.rows)))?;
+        check::<Self>("cols", Some(u64::from(self.cols)))
+    }
+}
+
+impl Validate for request::PowerControlArgs {
+    fn validate(&self) -> io::Result<()> {
+        check::<Self>("delay_secs", Some(self.delay_secs))
+    }
+}
+
+impl Validate for request::UploadManifestArgs {
+    fn validate(&self) -> io::Result<()> {
+        self.files.iter().try_for_each(Validate::validate)
+    }
+}
+
+impl Validate for request::CreateScheduleArgs {
+    fn validate(&self) -> io::Result<()> {
+        match self.trigger {
+            request::ScheduleTrigger::Interval { secs } => {
+                check::<request::ScheduleTrigger>("secs", Some(secs))
+            }
+            request::ScheduleTrigger::Cron { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{request::*, Reply, Request};
+
+    #[test]
+    fn annotate_should_find_every_constrained_field() {
+        let request = schemars::schema_for!(Request);
+        let reply = schemars::schema_for!(Reply);
+
+        // Catches constraints naming a type or field that does not exist
+        for constraint in FIELD_CONSTRAINTS {
+            let count = annotate_with(&mut request.clone(), &[*constraint])
+                + annotate_with(&mut reply.clone(), &[*constraint]);
+            assert!(count > 0, "{:?} was not found", constraint);
+        }
+
+        let mut request = request;
+        annotate(&mut request);
+        let value = serde_json::to_value(&request).unwrap();
+        let mode = &value["definitions"]["CreateDirArgs"]["properties"]["mode"];
+        assert_eq!(mode[UNIT_EXTENSION], "file_mode");
+        assert_eq!(mode["maximum"], 4095.0);
+        assert_eq!(mode["examples"][0], 493);
+    }
+
+    #[test]
+    fn validate_should_reject_values_out_of_bounds() {
+        let request = Request::CreateDir(CreateDirArgs {
+            path: "dir".into(),
+            mode: Some(0o755),
+            ..Default::default()
+        });
+        request.validate().unwrap();
+
+        let request = Request::CreateDir(CreateDirArgs {
+            path: "dir".into(),
+            mode: Some(0o10000),
+            ..Default::default()
+        });
+        let err = request.validate().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let request = Request::CreateSchedule(CreateScheduleArgs {
+            spec: Default::default(),
+            trigger: ScheduleTrigger::Interval { secs: 0 },
+        });
+        let err = request.validate().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let request = Request::DiffFiles(DiffFilesArgs {
+            path_a: "a".into(),
+            path_b: "b".into(),
+            max_bytes: Some(MAX_DIFF_BYTES + 1),
+            ..Default::default()
+        });
+        let err = request.validate().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Missing values are always within bounds
+        let request = Request::SniffFile(SniffFileArgs {
+            path: "file".into(),
+            max_bytes: None,
+        });
+        request.validate().unwrap();
+    }
+}
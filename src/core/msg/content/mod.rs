@@ -1,6 +1,9 @@
+pub mod constraints;
+mod path;
 pub mod reply;
 pub mod request;
 
+pub use path::RemotePath;
 pub use reply::{Reply, ReplyError};
 pub use request::{
     LazilyTransformedRequest, Request, TransformRequestError, TransformRule,
@@ -9,6 +12,30 @@ pub use request::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+/// Serializes socket addresses as text regardless of the format
+///
+/// Addresses are otherwise encoded compactly in binary formats like cbor,
+/// which cannot be decoded again once buffered by the untagged `Content`
+pub(crate) mod socket_addr {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::net::SocketAddr;
+
+    pub fn serialize<S: Serializer>(
+        addr: &SocketAddr,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(addr)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SocketAddr, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum Content {
@@ -31,6 +58,16 @@ impl Content {
         }
     }
 
+    /// Name of the request or reply as it appears when serialized, such as
+    /// `read_file_request`
+    pub fn type_name(&self) -> Option<String> {
+        serde_json::to_value(self)
+            .ok()?
+            .get("type")?
+            .as_str()
+            .map(String::from)
+    }
+
     pub fn into_reply_error(self) -> Option<ReplyError> {
         match self.into_reply() {
             Some(Reply::Error(x)) => Some(x),
@@ -0,0 +1,298 @@
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, ObjectValidation, Schema, SchemaObject},
+    JsonSchema,
+};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Key of the object holding the hex-encoded bytes of a path that is not
+/// valid UTF-8
+const HEX_KEY: &str = "hex";
+
+/// Path on the remote machine as the raw bytes of the filename, which are
+/// not required to be valid UTF-8
+///
+/// Paths that are valid UTF-8 are serialized as plain strings, so they look
+/// the same as they always have to clients and tooling. Any other path is
+/// serialized losslessly as an object holding its hex-encoded bytes, such
+/// as `{"hex": "2f746d702fff"}`.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RemotePath(Vec<u8>);
+
+impl RemotePath {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    /// Converts a local path, where the bytes of the path are kept as is on
+    /// unix and any path that is not valid unicode is converted lossily
+    /// elsewhere
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        Self(path_to_bytes(path.as_ref()))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Yields the path as a str if it is valid UTF-8
+    pub fn to_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    /// Yields the path as a str, replacing any bytes that are not valid
+    /// UTF-8 with U+FFFD
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+
+    /// Converts to a local path, where the bytes of the path are kept as is
+    /// on unix and any path that is not valid UTF-8 is converted lossily
+    /// elsewhere
+    pub fn to_path_buf(&self) -> PathBuf {
+        bytes_to_path(&self.0)
+    }
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+impl fmt::Debug for RemotePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl fmt::Display for RemotePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+impl From<&RemotePath> for RemotePath {
+    fn from(path: &RemotePath) -> Self {
+        path.clone()
+    }
+}
+
+impl From<String> for RemotePath {
+    fn from(path: String) -> Self {
+        Self(path.into_bytes())
+    }
+}
+
+impl From<&String> for RemotePath {
+    fn from(path: &String) -> Self {
+        Self::from(path.as_str())
+    }
+}
+
+impl From<&str> for RemotePath {
+    fn from(path: &str) -> Self {
+        Self(path.as_bytes().to_vec())
+    }
+}
+
+impl From<PathBuf> for RemotePath {
+    fn from(path: PathBuf) -> Self {
+        Self::from_path(path)
+    }
+}
+
+impl From<&Path> for RemotePath {
+    fn from(path: &Path) -> Self {
+        Self::from_path(path)
+    }
+}
+
+impl PartialEq<str> for RemotePath {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for RemotePath {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<String> for RemotePath {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl Serialize for RemotePath {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self.to_str() {
+            Some(path) => serializer.serialize_str(path),
+            None => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(HEX_KEY, &hex::encode(&self.0))?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RemotePath {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(RemotePathVisitor)
+    }
+}
+
+struct RemotePathVisitor;
+
+impl<'de> Visitor<'de> for RemotePathVisitor {
+    type Value = RemotePath;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a path string or an object of its hex-encoded bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(RemotePath::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(RemotePath::from(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(RemotePath::from_bytes(v))
+    }
+
+    fn visit_byte_buf<E: de::Error>(
+        self,
+        v: Vec<u8>,
+    ) -> Result<Self::Value, E> {
+        Ok(RemotePath::from_bytes(v))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> Result<Self::Value, A::Error> {
+        let mut bytes = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == HEX_KEY {
+                let value: String = map.next_value()?;
+                bytes = Some(hex::decode(value).map_err(de::Error::custom)?);
+            } else {
+                map.next_value::<de::IgnoredAny>()?;
+            }
+        }
+
+        bytes
+            .map(RemotePath::from_bytes)
+            .ok_or_else(|| de::Error::missing_field(HEX_KEY))
+    }
+}
+
+impl JsonSchema for RemotePath {
+    fn schema_name() -> String {
+        String::from("RemotePath")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut hex = SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            ..Default::default()
+        };
+        let mut object = ObjectValidation::default();
+        object.required.insert(HEX_KEY.to_string());
+        object
+            .properties
+            .insert(HEX_KEY.to_string(), gen.subschema_for::<String>());
+        hex.object = Some(Box::new(object));
+        hex.metadata().description = Some(String::from(
+            "Hex-encoded bytes of a path that is not valid UTF-8",
+        ));
+
+        let mut schema = SchemaObject::default();
+        schema.subschemas().any_of =
+            Some(vec![gen.subschema_for::<String>(), hex.into()]);
+        schema.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_should_use_a_string_if_valid_utf8() {
+        let path = RemotePath::from("/tmp/file");
+        assert_eq!(serde_json::to_string(&path).unwrap(), "\"/tmp/file\"");
+
+        let path: RemotePath = serde_json::from_str("\"/tmp/file\"").unwrap();
+        assert_eq!(path, "/tmp/file");
+    }
+
+    #[test]
+    fn serialize_should_hex_encode_bytes_if_not_valid_utf8() {
+        let path = RemotePath::from_bytes(b"/tmp/\xff".to_vec());
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, "{\"hex\":\"2f746d702fff\"}");
+
+        let decoded: RemotePath = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, path);
+
+        let decoded: RemotePath =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&path).unwrap())
+                .unwrap();
+        assert_eq!(decoded, path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn to_path_buf_should_keep_bytes_on_unix() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = RemotePath::from_bytes(b"/tmp/\xff".to_vec());
+        let local = path.to_path_buf();
+        assert_eq!(local.as_os_str().as_bytes(), b"/tmp/\xff");
+        assert_eq!(RemotePath::from_path(&local), path);
+        assert_eq!(path.to_string_lossy(), "/tmp/\u{FFFD}");
+    }
+}
@@ -0,0 +1,40 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct StateExportedArgs {
+    /// Archive of the durable metadata of the server, such as its jobs,
+    /// schedules, config, and trusted clients
+    pub archive: Vec<u8>,
+
+    /// Version of the format of the archive
+    pub version: u32,
+}
+
+impl crate::core::SchemaInfo for StateExportedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct StateImportedArgs {
+    /// Total jobs taken from the archive
+    pub jobs: u32,
+
+    /// Total jobs left out as the server already has jobs with their ids
+    pub jobs_skipped: u32,
+
+    /// Total schedules taken from the archive, replacing any with the same
+    /// ids
+    pub schedules: u32,
+
+    /// Whether the config of the archive replaced that of the server, which
+    /// only happens if it is newer
+    pub config: bool,
+
+    /// Total client keys newly trusted by the server
+    pub trusted_clients: u32,
+}
+
+impl crate::core::SchemaInfo for StateImportedArgs {}
@@ -16,3 +16,17 @@ impl From<Vec<Reply>> for BatchArgs {
         Self { results }
     }
 }
+
+/// Result of a single operation within a batch whose results are streamed
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BatchResultArgs {
+    /// Position of the operation within the batch
+    pub index: usize,
+
+    pub result: Box<Reply>,
+
+    /// Whether or not this is the final result of the batch
+    pub last: bool,
+}
+
+impl crate::core::SchemaInfo for BatchResultArgs {}
@@ -14,6 +14,9 @@ pub enum Capability {
 
     /// Can forward msgs
     Forward,
+
+    /// Can serve custom msgs with uploaded wasm modules
+    Wasm,
 }
 
 impl crate::core::SchemaInfo for Capability {}
@@ -23,6 +26,48 @@ impl crate::core::SchemaInfo for Capability {}
 )]
 pub struct CapabilitiesArgs {
     pub capabilities: Vec<Capability>,
+
+    /// Limits of each transport, allowing large requests to be split into
+    /// smaller requests before being sent
+    #[serde(default)]
+    pub transport_limits: Vec<TransportLimits>,
+
+    /// Limits on the operations nested within a single request, or none if
+    /// not reported by the remote instance
+    #[serde(default)]
+    pub request_limits: Option<RequestLimits>,
 }
 
 impl crate::core::SchemaInfo for CapabilitiesArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct TransportLimits {
+    /// Name of the transport, such as tcp_ethernet or udp_ipv4
+    pub transport: String,
+
+    /// Maximum size in bytes of each packet sent over the transport
+    pub max_packet_size: usize,
+
+    /// Maximum size in bytes of an encoded msg (request or reply) before it
+    /// is split into packets
+    pub max_msg_size: usize,
+}
+
+impl crate::core::SchemaInfo for TransportLimits {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct RequestLimits {
+    /// Maximum depth of sequence and batch operations nested within a
+    /// request
+    pub max_depth: u8,
+
+    /// Maximum total of operations nested within a request across all
+    /// depths
+    pub max_nested_operations: usize,
+}
+
+impl crate::core::SchemaInfo for RequestLimits {}
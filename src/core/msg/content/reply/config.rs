@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ConfigPushedArgs {
+    /// Version of the config now stored by the server
+    pub version: u64,
+}
+
+impl crate::core::SchemaInfo for ConfigPushedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ConfigArgs {
+    /// Opaque configuration document last pushed to the server
+    pub blob: Vec<u8>,
+
+    /// Version of the document
+    pub version: u64,
+}
+
+impl crate::core::SchemaInfo for ConfigArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WebhookArgs {
+    /// Url of the endpoint that events are sent to
+    pub url: String,
+
+    /// Whether the body of each event sent is signed with a secret
+    pub signed: bool,
+
+    /// Names of the events sent, or empty if all events are sent
+    pub events: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for WebhookArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WebhooksArgs {
+    /// Webhooks that events on the server are sent to
+    pub webhooks: Vec<WebhookArgs>,
+}
+
+impl crate::core::SchemaInfo for WebhooksArgs {}
@@ -0,0 +1,52 @@
+use super::PageInfoArgs;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ConnectionEntryArgs {
+    /// Address (<ip>:<port>) of the client
+    pub addr: String,
+
+    /// Time since the client last communicated with the server
+    pub idle_millis: u64,
+}
+
+impl crate::core::SchemaInfo for ConnectionEntryArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ConnectionsListArgs {
+    pub connections: Vec<ConnectionEntryArgs>,
+    pub page: PageInfoArgs,
+}
+
+impl crate::core::SchemaInfo for ConnectionsListArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct OriginBindingArgs {
+    /// Whether the server accepts requests that change it from the origin
+    pub bound: bool,
+
+    /// Nonce that must be echoed back to bind the origin, which is empty
+    /// once the origin is bound
+    #[serde(default)]
+    pub nonce: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for OriginBindingArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ServerShuttingDownArgs {
+    /// Time given to requests still being executed to complete before the
+    /// server stops
+    pub grace_ms: u64,
+}
+
+impl crate::core::SchemaInfo for ServerShuttingDownArgs {}
@@ -0,0 +1,299 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Diagnostics of a remote instance, where each section is only provided
+/// if it was requested
+///
+/// Sections are boxed so that diagnostics do not inflate the size of every
+/// reply.
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticsArgs {
+    #[serde(default)]
+    pub state_counters: Option<Box<DiagnosticStateCountersArgs>>,
+
+    #[serde(default)]
+    pub config: Option<Box<DiagnosticConfigArgs>>,
+
+    #[serde(default)]
+    pub tasks: Option<Box<DiagnosticTasksArgs>>,
+
+    #[serde(default)]
+    pub buffers: Option<Box<DiagnosticBuffersArgs>>,
+
+    #[serde(default)]
+    pub transfers: Option<Box<DiagnosticTransfersArgs>>,
+}
+
+impl crate::core::SchemaInfo for DiagnosticsArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticStateCountersArgs {
+    /// Total clients that have communicated with the server
+    pub conns: usize,
+
+    /// Total files currently open
+    pub open_files: usize,
+
+    /// Total procs being tracked, including those that have exited but
+    /// are still queryable
+    pub procs: usize,
+
+    /// Total file ids being tracked for eviction
+    pub tracked_file_ids: usize,
+
+    /// Total proc ids being tracked for eviction
+    pub tracked_proc_ids: usize,
+
+    /// Total jobs still running
+    pub running_jobs: usize,
+
+    /// Total files closed for going untouched
+    #[serde(default)]
+    pub evicted_files: u64,
+
+    /// Total procs killed or forgotten for going untouched
+    #[serde(default)]
+    pub evicted_procs: u64,
+
+    /// Total session keys forgotten for going untouched
+    #[serde(default)]
+    pub evicted_sessions: u64,
+}
+
+impl crate::core::SchemaInfo for DiagnosticStateCountersArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticConfigArgs {
+    /// Time a file can go untouched before being closed
+    pub file_ttl_millis: u64,
+
+    /// Time a proc can go untouched before being killed
+    pub proc_ttl_millis: u64,
+
+    /// Time an exited proc can go untouched before no longer being queryable
+    pub dead_proc_ttl_millis: u64,
+
+    /// Total webhooks that are notified of events
+    pub webhooks: usize,
+
+    /// Maximum depth of sequence and batch operations nested within a
+    /// request
+    #[serde(default)]
+    pub max_request_depth: u8,
+
+    /// Maximum total of operations nested within a request across all
+    /// depths
+    #[serde(default)]
+    pub max_nested_operations: usize,
+
+    /// Maximum bytes any single identity can transfer, if limited
+    #[serde(default)]
+    pub transfer_quota: Option<u64>,
+
+    /// Number of recent changes to the filesystem kept for clients
+    #[serde(default)]
+    pub fs_event_history: usize,
+
+    /// How signatures of msgs are treated (ignore, verify, or require)
+    #[serde(default)]
+    pub signature_mode: String,
+
+    /// Fingerprints of the keys whose msg signatures are trusted, where any
+    /// key is trusted if empty
+    #[serde(default)]
+    pub trusted_signers: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for DiagnosticConfigArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticTasksArgs {
+    pub procs: Vec<DiagnosticProcArgs>,
+
+    /// Ids of jobs still running
+    pub running_jobs: Vec<u32>,
+
+    /// Requests still being executed, including tasks they spawned
+    #[serde(default)]
+    pub active_request_tasks: usize,
+
+    /// Tasks still servicing individual connections
+    #[serde(default)]
+    pub active_connection_tasks: usize,
+}
+
+impl crate::core::SchemaInfo for DiagnosticTasksArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticProcArgs {
+    pub id: u32,
+    pub detached: bool,
+    pub is_alive: bool,
+    pub exit_code: Option<i32>,
+}
+
+impl crate::core::SchemaInfo for DiagnosticProcArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticBuffersArgs {
+    pub procs: Vec<DiagnosticProcBufferArgs>,
+
+    /// Total bytes buffered across all procs
+    pub total_bytes: usize,
+}
+
+impl crate::core::SchemaInfo for DiagnosticBuffersArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticProcBufferArgs {
+    pub id: u32,
+
+    /// Bytes of stdout captured that have yet to be read
+    pub stdout_bytes: usize,
+
+    /// Bytes of stderr captured that have yet to be read
+    pub stderr_bytes: usize,
+}
+
+impl crate::core::SchemaInfo for DiagnosticProcBufferArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticTransfersArgs {
+    /// Bytes transferred by each identity, being the fingerprint of the key
+    /// that signed its msgs or otherwise the address they came from
+    pub identities: Vec<DiagnosticTransferTotalsArgs>,
+
+    /// Bytes transferred by each class of request, such as file or proc
+    pub classes: Vec<DiagnosticTransferTotalsArgs>,
+
+    /// Maximum bytes that any single identity can transfer, if limited
+    pub quota: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for DiagnosticTransfersArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticTransferTotalsArgs {
+    pub name: String,
+
+    /// Bytes of msgs received by the remote instance
+    pub bytes_in: u64,
+
+    /// Bytes of msgs sent by the remote instance
+    pub bytes_out: u64,
+}
+
+impl crate::core::SchemaInfo for DiagnosticTransferTotalsArgs {}
+
+/// Renders the diagnostics as human-readable text
+impl fmt::Display for DiagnosticsArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(x) = &self.state_counters {
+            writeln!(f, "State Counters")?;
+            writeln!(f, "  Conns: {}", x.conns)?;
+            writeln!(f, "  Open Files: {}", x.open_files)?;
+            writeln!(f, "  Procs: {}", x.procs)?;
+            writeln!(f, "  Tracked File IDs: {}", x.tracked_file_ids)?;
+            writeln!(f, "  Tracked Proc IDs: {}", x.tracked_proc_ids)?;
+            writeln!(f, "  Running Jobs: {}", x.running_jobs)?;
+        }
+
+        if let Some(x) = &self.config {
+            writeln!(f, "Config")?;
+            writeln!(f, "  File Untouched TTL: {}ms", x.file_ttl_millis)?;
+            writeln!(f, "  Proc Untouched TTL: {}ms", x.proc_ttl_millis)?;
+            writeln!(
+                f,
+                "  Dead Proc Untouched TTL: {}ms",
+                x.dead_proc_ttl_millis
+            )?;
+            writeln!(f, "  Webhooks: {}", x.webhooks)?;
+            writeln!(f, "  Max Request Depth: {}", x.max_request_depth)?;
+            writeln!(
+                f,
+                "  Max Nested Operations: {}",
+                x.max_nested_operations
+            )?;
+            match x.transfer_quota {
+                Some(quota) => writeln!(f, "  Transfer Quota: {}B", quota)?,
+                None => writeln!(f, "  Transfer Quota: none")?,
+            }
+            writeln!(f, "  Fs Event History: {}", x.fs_event_history)?;
+            writeln!(f, "  Msg Signatures: {}", x.signature_mode)?;
+            for fingerprint in x.trusted_signers.iter() {
+                writeln!(f, "  Trusted Signer: {}", fingerprint)?;
+            }
+        }
+
+        if let Some(x) = &self.tasks {
+            writeln!(f, "Tasks")?;
+            writeln!(f, "  Active Requests: {}", x.active_request_tasks)?;
+            writeln!(f, "  Active Connections: {}", x.active_connection_tasks)?;
+            for p in x.procs.iter() {
+                let status = match (p.is_alive, p.exit_code) {
+                    (true, _) => String::from("running"),
+                    (false, Some(code)) => format!("exited with {}", code),
+                    (false, None) => String::from("exited"),
+                };
+                let detached = if p.detached { ", detached" } else { "" };
+                writeln!(f, "  Proc {}: {}{}", p.id, status, detached)?;
+            }
+            for id in x.running_jobs.iter() {
+                writeln!(f, "  Job {}: running", id)?;
+            }
+        }
+
+        if let Some(x) = &self.buffers {
+            writeln!(f, "Buffers")?;
+            for p in x.procs.iter() {
+                writeln!(
+                    f,
+                    "  Proc {}: {} bytes stdout, {} bytes stderr",
+                    p.id, p.stdout_bytes, p.stderr_bytes
+                )?;
+            }
+            writeln!(f, "  Total: {} bytes", x.total_bytes)?;
+        }
+
+        if let Some(x) = &self.transfers {
+            writeln!(f, "Transfers")?;
+            match x.quota {
+                Some(quota) => writeln!(f, "  Quota: {} bytes", quota)?,
+                None => writeln!(f, "  Quota: unlimited")?,
+            }
+            for (kind, totals) in x
+                .identities
+                .iter()
+                .map(|t| ("Identity", t))
+                .chain(x.classes.iter().map(|t| ("Class", t)))
+            {
+                writeln!(
+                    f,
+                    "  {} {}: {} bytes in, {} bytes out",
+                    kind, totals.name, totals.bytes_in, totals.bytes_out
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use super::SerErrorKind;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Stable numeric code identifying the kind of an error, which never changes
+/// meaning between versions so that clients can branch on it rather than on
+/// the description of the error
+///
+/// Every code is below 126 so that it can also be used as the exit code of
+/// a process:
+///
+/// * 1 - generic error without a more specific code
+/// * 2 - invalid response to a request
+/// * 3 - unsupported request or reply
+/// * 4 - request exceeds a limit of the server
+/// * 5 - msg exceeds the maximum msg size
+/// * 6 - msg signature rejected
+/// * 7 - transfer quota exceeded
+/// * 8 - origin not bound
+/// * 9 - client not trusted
+/// * 10 - timed out
+/// * 11 - failed to encode msg
+/// * 12 - failed to send msg
+/// * 13 - reply callback lost
+/// * 14 - signing key required
+/// * 15 - msg not sealed with the key of the session
+/// * 16 - sealed msg rejected
+/// * 20 - file signature changed
+/// * 21 - precondition failed
+/// * 30 - io error of another kind
+/// * 31 - not found
+/// * 32 - permission denied
+/// * 33 - connection refused
+/// * 34 - connection reset
+/// * 35 - connection aborted
+/// * 36 - not connected
+/// * 37 - address in use
+/// * 38 - address not available
+/// * 39 - broken pipe
+/// * 40 - already exists
+/// * 41 - would block
+/// * 42 - invalid input
+/// * 43 - invalid data
+/// * 44 - write zero
+/// * 45 - interrupted
+/// * 46 - unexpected eof
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash,
+)]
+pub struct ErrorCode(pub u16);
+
+impl crate::core::SchemaInfo for ErrorCode {}
+
+impl ErrorCode {
+    pub const GENERIC: Self = Self(1);
+    pub const INVALID_RESPONSE: Self = Self(2);
+    pub const UNSUPPORTED: Self = Self(3);
+    pub const LIMIT_EXCEEDED: Self = Self(4);
+    pub const MSG_TOO_LARGE: Self = Self(5);
+    pub const SIGNATURE_REJECTED: Self = Self(6);
+    pub const QUOTA_EXCEEDED: Self = Self(7);
+    pub const ORIGIN_UNBOUND: Self = Self(8);
+    pub const CLIENT_NOT_TRUSTED: Self = Self(9);
+
+    pub const TIMED_OUT: Self = Self(10);
+    pub const ENCODING_FAILED: Self = Self(11);
+    pub const SEND_FAILED: Self = Self(12);
+    pub const CALLBACK_LOST: Self = Self(13);
+    pub const SIGNING_KEY_REQUIRED: Self = Self(14);
+    pub const SESSION_REQUIRED: Self = Self(15);
+    pub const SEAL_REJECTED: Self = Self(16);
+
+    pub const FILE_SIG_CHANGED: Self = Self(20);
+    pub const PRECONDITION_FAILED: Self = Self(21);
+
+    pub const IO_OTHER: Self = Self(30);
+    pub const NOT_FOUND: Self = Self(31);
+    pub const PERMISSION_DENIED: Self = Self(32);
+    pub const CONNECTION_REFUSED: Self = Self(33);
+    pub const CONNECTION_RESET: Self = Self(34);
+    pub const CONNECTION_ABORTED: Self = Self(35);
+    pub const NOT_CONNECTED: Self = Self(36);
+    pub const ADDR_IN_USE: Self = Self(37);
+    pub const ADDR_NOT_AVAILABLE: Self = Self(38);
+    pub const BROKEN_PIPE: Self = Self(39);
+    pub const ALREADY_EXISTS: Self = Self(40);
+    pub const WOULD_BLOCK: Self = Self(41);
+    pub const INVALID_INPUT: Self = Self(42);
+    pub const INVALID_DATA: Self = Self(43);
+    pub const WRITE_ZERO: Self = Self(44);
+    pub const INTERRUPTED: Self = Self(45);
+    pub const UNEXPECTED_EOF: Self = Self(46);
+
+    /// Exit code of a process that fails with an error of this code
+    pub fn exit_code(self) -> i32 {
+        i32::from(self.0)
+    }
+}
+
+impl Default for ErrorCode {
+    fn default() -> Self {
+        Self::GENERIC
+    }
+}
+
+impl From<&SerErrorKind> for ErrorCode {
+    fn from(error_kind: &SerErrorKind) -> Self {
+        match error_kind {
+            SerErrorKind::NotFound => Self::NOT_FOUND,
+            SerErrorKind::PermissionDenied => Self::PERMISSION_DENIED,
+            SerErrorKind::ConnectionRefused => Self::CONNECTION_REFUSED,
+            SerErrorKind::ConnectionReset => Self::CONNECTION_RESET,
+            SerErrorKind::ConnectionAborted => Self::CONNECTION_ABORTED,
+            SerErrorKind::NotConnected => Self::NOT_CONNECTED,
+            SerErrorKind::AddrInUse => Self::ADDR_IN_USE,
+            SerErrorKind::AddrNotAvailable => Self::ADDR_NOT_AVAILABLE,
+            SerErrorKind::BrokenPipe => Self::BROKEN_PIPE,
+            SerErrorKind::AlreadyExists => Self::ALREADY_EXISTS,
+            SerErrorKind::WouldBlock => Self::WOULD_BLOCK,
+            SerErrorKind::InvalidInput => Self::INVALID_INPUT,
+            SerErrorKind::InvalidData => Self::INVALID_DATA,
+            SerErrorKind::TimedOut => Self::TIMED_OUT,
+            SerErrorKind::WriteZero => Self::WRITE_ZERO,
+            SerErrorKind::Interrupted => Self::INTERRUPTED,
+            SerErrorKind::UnexpectedEof => Self::UNEXPECTED_EOF,
+            SerErrorKind::Other | SerErrorKind::NonExhaustive => Self::IO_OTHER,
+        }
+    }
+}
+
+impl From<io::ErrorKind> for ErrorCode {
+    fn from(error_kind: io::ErrorKind) -> Self {
+        Self::from(&SerErrorKind::from(error_kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        reply::{FileSigChangedArgs, GenericErrorArgs},
+        ReplyError,
+    };
+
+    #[test]
+    fn reply_error_code_should_reflect_kind_of_error() {
+        let error = ReplyError::from("something failed");
+        assert_eq!(error.code(), ErrorCode::GENERIC);
+
+        let error = ReplyError::with_code("too big", ErrorCode::LIMIT_EXCEEDED);
+        assert_eq!(error.code(), ErrorCode::LIMIT_EXCEEDED);
+
+        let error = ReplyError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(error.code(), ErrorCode::NOT_FOUND);
+        assert_eq!(error.code().exit_code(), 31);
+
+        let error =
+            ReplyError::FileSigChanged(FileSigChangedArgs { id: 1, sig: 2 });
+        assert_eq!(error.code(), ErrorCode::FILE_SIG_CHANGED);
+    }
+
+    #[test]
+    fn generic_error_should_default_to_generic_code_when_decoded() {
+        let args: GenericErrorArgs =
+            serde_json::from_str(r#"{"msg": "failed"}"#).unwrap();
+        assert_eq!(args.code, ErrorCode::GENERIC);
+    }
+}
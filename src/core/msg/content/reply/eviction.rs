@@ -0,0 +1,61 @@
+use crate::core::request::EvictionKind;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Why the server evicts a resource
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum EvictionReason {
+    /// Resource went untouched for longer than its ttl
+    #[serde(rename = "expired")]
+    Expired,
+
+    /// Proc exited and its status went untouched for longer than the ttl
+    /// of exited procs
+    #[serde(rename = "exited")]
+    Exited,
+}
+
+impl crate::core::SchemaInfo for EvictionReason {}
+
+impl EvictionReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Expired => "expired",
+            Self::Exited => "exited",
+        }
+    }
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingEvictionArgs {
+    pub kind: EvictionKind,
+
+    /// Id of the file or proc, or address (<ip>:<port>) of the connection
+    /// of the session
+    pub id: String,
+
+    /// Why the resource will be evicted
+    pub reason: EvictionReason,
+
+    /// Time until the resource is evicted unless touched, which is zero if
+    /// it is due to be evicted by the next cleanup
+    pub evicted_in_millis: u64,
+}
+
+impl crate::core::SchemaInfo for PendingEvictionArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PendingEvictionsArgs {
+    /// Resources that will be evicted, soonest first
+    pub evictions: Vec<PendingEvictionArgs>,
+
+    /// Total resources requested to be touched that were kept alive, where
+    /// those missing or already due to be evicted are not counted
+    pub touched: usize,
+}
+
+impl crate::core::SchemaInfo for PendingEvictionsArgs {}
@@ -0,0 +1,21 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FaultInjectedArgs {
+    pub request_type: String,
+}
+
+impl crate::core::SchemaInfo for FaultInjectedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FaultsClearedArgs {
+    /// Total faults that were removed
+    pub cleared: usize,
+}
+
+impl crate::core::SchemaInfo for FaultsClearedArgs {}
@@ -5,6 +5,8 @@ use std::net::SocketAddr;
 
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct ForwardArgs {
+    #[serde(with = "crate::core::msg::content::socket_addr")]
+    #[schemars(with = "String")]
     pub address: SocketAddr,
     pub reply: Box<Reply>,
 }
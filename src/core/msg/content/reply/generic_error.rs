@@ -1,18 +1,25 @@
+use super::ErrorCode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct GenericErrorArgs {
     pub msg: String,
+
+    /// Code of the error, which is generic unless the error is known to be
+    /// of a more specific kind
+    #[serde(default)]
+    pub code: ErrorCode,
 }
 
 impl crate::core::SchemaInfo for GenericErrorArgs {}
 
-impl ToString for GenericErrorArgs {
-    fn to_string(&self) -> String {
-        self.msg.clone()
+impl fmt::Display for GenericErrorArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
     }
 }
 
@@ -20,13 +27,17 @@ impl From<Box<dyn std::error::Error>> for GenericErrorArgs {
     fn from(x: Box<dyn std::error::Error>) -> Self {
         Self {
             msg: format!("{}", x),
+            code: ErrorCode::GENERIC,
         }
     }
 }
 
 impl From<String> for GenericErrorArgs {
     fn from(text: String) -> Self {
-        Self { msg: text }
+        Self {
+            msg: text,
+            code: ErrorCode::GENERIC,
+        }
     }
 }
 
@@ -0,0 +1,57 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct IdentityArgs {
+    /// Public half of the server's identity key
+    pub public_key: Vec<u8>,
+
+    /// Challenge of the request signed with the server's identity key
+    pub proof: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for IdentityArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct TrustedClientsArgs {
+    /// Fingerprints of the client keys trusted by the server
+    pub fingerprints: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for TrustedClientsArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct HandshakeArgs {
+    /// Public half of the ephemeral x25519 key of the server
+    pub public_key: Vec<u8>,
+
+    /// Public half of the server's identity key, empty if it has none
+    #[serde(default)]
+    pub identity_key: Vec<u8>,
+
+    /// Public keys of the client and server signed with the server's
+    /// identity key, empty if it has none
+    #[serde(default)]
+    pub signature: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for HandshakeArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct SealedArgs {
+    /// Nonce the msg was sealed with
+    pub nonce: Vec<u8>,
+
+    /// Msg of the server encrypted with the key negotiated for the session
+    pub data: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for SealedArgs {}
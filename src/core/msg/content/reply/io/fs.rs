@@ -1,11 +1,14 @@
+use super::IoErrorArgs;
+use crate::core::{request::ChecksumAlgorithm, RemotePath};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct DirCreatedArgs {
-    pub path: String,
+    pub path: RemotePath,
 }
 
 impl crate::core::SchemaInfo for DirCreatedArgs {}
@@ -14,8 +17,8 @@ impl crate::core::SchemaInfo for DirCreatedArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct DirRenamedArgs {
-    pub from: String,
-    pub to: String,
+    pub from: RemotePath,
+    pub to: RemotePath,
 }
 
 impl crate::core::SchemaInfo for DirRenamedArgs {}
@@ -24,26 +27,273 @@ impl crate::core::SchemaInfo for DirRenamedArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct DirRemovedArgs {
-    pub path: String,
+    pub path: RemotePath,
 }
 
 impl crate::core::SchemaInfo for DirRemovedArgs {}
 
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PathsCreatedArgs {
+    pub dirs: Vec<PathCreateResult>,
+    pub files: Vec<PathCreateResult>,
+}
+
+impl crate::core::SchemaInfo for PathsCreatedArgs {}
+
+/// Represents the result of creating a single dir or file as part of many
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PathCreateResult {
+    pub path: RemotePath,
+    pub status: PathCreateStatus,
+}
+
+impl crate::core::SchemaInfo for PathCreateResult {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum PathCreateStatus {
+    #[serde(rename = "created")]
+    Created,
+
+    /// Dir or file of the same kind already existed and was left untouched
+    #[serde(rename = "already_exists")]
+    AlreadyExists,
+
+    /// Dir or file could not be created
+    #[serde(rename = "failed")]
+    Failed { error: IoErrorArgs },
+}
+
+impl crate::core::SchemaInfo for PathCreateStatus {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct DirContentsListArgs {
-    pub path: String,
+    pub path: RemotePath,
     pub entries: Vec<DirEntry>,
 }
 
 impl crate::core::SchemaInfo for DirContentsListArgs {}
 
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DirContentsListChunkArgs {
+    pub path: RemotePath,
+    pub entries: Vec<DirEntry>,
+
+    /// Whether or not this is the final chunk for the directory
+    pub last: bool,
+}
+
+impl crate::core::SchemaInfo for DirContentsListChunkArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PathResolvedArgs {
+    /// Canonicalized, absolute form of the path
+    pub path: RemotePath,
+
+    pub exists: bool,
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+
+    /// Whether or not the path is within the root directory of the server,
+    /// which is the directory the server is running from
+    pub in_root: bool,
+}
+
+impl crate::core::SchemaInfo for PathResolvedArgs {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileSniffedArgs {
+    pub path: RemotePath,
+
+    /// Total bytes examined from the start of the file
+    pub bytes_read: u64,
+
+    /// MIME type detected from the file's magic number, falling back to a
+    /// generic text or binary type
+    pub mime_type: String,
+
+    pub is_binary: bool,
+    pub line_ending: LineEnding,
+}
+
+impl crate::core::SchemaInfo for FileSniffedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FileChecksumArgs {
+    pub path: RemotePath,
+    pub algorithm: ChecksumAlgorithm,
+
+    /// Lowercase hex digest of the contents of the file
+    pub digest: String,
+
+    /// Total bytes of the file covered by the checksum
+    pub size: u64,
+}
+
+impl crate::core::SchemaInfo for FileChecksumArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FilesDiffedArgs {
+    pub path_a: RemotePath,
+    pub path_b: RemotePath,
+
+    /// Whether or not the contents of both files are the same
+    pub identical: bool,
+
+    /// Whether or not either file is binary, in which case no diff is
+    /// produced and only `identical` is reported
+    pub is_binary: bool,
+
+    /// Changes in the unified diff format, empty if the files are identical
+    /// or binary
+    pub diff: String,
+
+    pub lines_added: u64,
+    pub lines_removed: u64,
+}
+
+impl crate::core::SchemaInfo for FilesDiffedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum FsEventOp {
+    #[serde(rename = "created")]
+    Created,
+
+    #[serde(rename = "modified")]
+    Modified,
+
+    /// Path was renamed, where the new path is reported alongside it
+    #[serde(rename = "renamed")]
+    Renamed,
+
+    #[serde(rename = "removed")]
+    Removed,
+}
+
+impl crate::core::SchemaInfo for FsEventOp {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FsEventArgs {
+    /// Id of the event, which increases with each change
+    pub id: u64,
+
+    pub op: FsEventOp,
+    pub path: RemotePath,
+
+    /// New path of a renamed file or directory
+    #[serde(default)]
+    pub to: Option<RemotePath>,
+
+    /// Milliseconds since the unix epoch when the change was made
+    pub timestamp_millis: u64,
+
+    /// Fingerprint of the key that signed the request making the change, or
+    /// otherwise the ip address it came from
+    pub identity: String,
+}
+
+impl crate::core::SchemaInfo for FsEventArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FsEventsArgs {
+    /// Events after the one requested, oldest first
+    pub events: Vec<FsEventArgs>,
+
+    /// Id of the most recent event, which can be used as `since` when
+    /// asking again, or zero if no changes have been made
+    pub latest_id: u64,
+
+    /// Whether events after the one requested are no longer held by the
+    /// server (or the server restarted), meaning that changes were missed
+    /// and the client must resynchronize some other way
+    pub missed: bool,
+}
+
+impl crate::core::SchemaInfo for FsEventsArgs {}
+
+/// Filesystem mounted on the server (or drive on Windows), which is a
+/// starting point for navigating its files
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct MountArgs {
+    /// Directory where the filesystem is mounted, or the root of the drive
+    pub path: RemotePath,
+
+    /// Device or other source of the filesystem, such as /dev/sda1, if known
+    pub device: Option<String>,
+
+    /// Type of the filesystem, such as ext4 or tmpfs, if known
+    pub fs_type: Option<String>,
+
+    /// Whether the filesystem is mounted read-only
+    pub read_only: bool,
+
+    /// Total bytes of the filesystem, if known
+    pub total_bytes: Option<u64>,
+
+    /// Bytes of the filesystem available to the server's user, if known
+    pub available_bytes: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for MountArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct MountsListArgs {
+    /// Mounts ordered by path
+    pub mounts: Vec<MountArgs>,
+}
+
+impl crate::core::SchemaInfo for MountsListArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum LineEnding {
+    /// No line endings were found
+    #[serde(rename = "none")]
+    None,
+
+    #[serde(rename = "lf")]
+    Lf,
+
+    #[serde(rename = "crlf")]
+    CrLf,
+
+    #[serde(rename = "cr")]
+    Cr,
+
+    /// More than one style of line ending was found
+    #[serde(rename = "mixed")]
+    Mixed,
+}
+
+impl crate::core::SchemaInfo for LineEnding {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct DirEntry {
-    pub path: String,
+    pub path: RemotePath,
     pub is_file: bool,
     pub is_dir: bool,
     pub is_symlink: bool,
@@ -57,7 +307,7 @@ impl crate::core::SchemaInfo for DirEntry {}
 pub struct FileOpenedArgs {
     pub id: u32,
     pub sig: u32,
-    pub path: String,
+    pub path: RemotePath,
     pub read: bool,
     pub write: bool,
 }
@@ -77,8 +327,8 @@ impl crate::core::SchemaInfo for FileClosedArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct UnopenedFileRenamedArgs {
-    pub from: String,
-    pub to: String,
+    pub from: RemotePath,
+    pub to: RemotePath,
 }
 
 impl crate::core::SchemaInfo for UnopenedFileRenamedArgs {}
@@ -97,7 +347,7 @@ impl crate::core::SchemaInfo for FileRenamedArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct UnopenedFileRemovedArgs {
-    pub path: String,
+    pub path: RemotePath,
 }
 
 impl crate::core::SchemaInfo for UnopenedFileRemovedArgs {}
@@ -118,20 +368,124 @@ impl crate::core::SchemaInfo for FileRemovedArgs {}
 pub struct FileContentsArgs {
     pub id: u32,
     pub contents: Vec<u8>,
+
+    /// Signature of the file when it was read
+    #[serde(default)]
+    pub sig: u32,
+
+    /// Hex-encoded SHA-256 hash of the contents of the file, which for a
+    /// ranged read is only provided if the request included an etag to
+    /// compare against
+    #[serde(default)]
+    pub etag: String,
+
+    /// Last time the file was modified as seconds since the unix epoch, if
+    /// supported by the platform
+    #[serde(default)]
+    pub modified: Option<u64>,
+
+    /// If true, the file matched the etag the client already has, so the
+    /// contents were omitted
+    #[serde(default)]
+    pub not_modified: bool,
+
+    /// Total size of the file in bytes when it was read, which may be more
+    /// than the contents returned by a ranged read
+    #[serde(default)]
+    pub size: Option<u64>,
 }
 
 impl crate::core::SchemaInfo for FileContentsArgs {}
 
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FilesContentsArgs {
+    pub files: Vec<FileReadResult>,
+}
+
+impl crate::core::SchemaInfo for FilesContentsArgs {}
+
+/// Represents the result of reading a single file as part of many
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileReadResult {
+    pub path: RemotePath,
+    pub status: FileReadStatus,
+}
+
+impl crate::core::SchemaInfo for FileReadResult {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum FileReadStatus {
+    /// File was read in full
+    #[serde(rename = "read")]
+    Read { contents: Vec<u8> },
+
+    /// File was not read as doing so would exceed the maximum total bytes
+    #[serde(rename = "limit_exceeded")]
+    LimitExceeded,
+
+    /// File could not be read
+    #[serde(rename = "failed")]
+    Failed { error: IoErrorArgs },
+}
+
+impl crate::core::SchemaInfo for FileReadStatus {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct FileWrittenArgs {
     pub id: u32,
     pub sig: u32,
+
+    /// Hex-encoded SHA-256 hash of the contents of the file after the write,
+    /// which is empty if a file opened to append could not be read back
+    #[serde(default)]
+    pub etag: String,
+
+    /// Last time the file was modified as seconds since the unix epoch, if
+    /// supported by the platform
+    #[serde(default)]
+    pub modified: Option<u64>,
 }
 
 impl crate::core::SchemaInfo for FileWrittenArgs {}
 
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct AtomicFileWrittenArgs {
+    pub path: RemotePath,
+
+    /// Signature of the file after being written
+    pub sig: u32,
+
+    /// Hex-encoded SHA-256 hash of the data written
+    pub hash: String,
+}
+
+impl crate::core::SchemaInfo for AtomicFileWrittenArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FileLinesPatchedArgs {
+    pub path: RemotePath,
+
+    /// Signature of the file after being patched
+    pub sig: u32,
+
+    /// Hex-encoded SHA-256 hash of the patched contents
+    pub hash: String,
+
+    /// Total lines in the file after being patched
+    pub line_count: u64,
+}
+
+impl crate::core::SchemaInfo for FileLinesPatchedArgs {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
@@ -142,8 +496,138 @@ pub struct FileSigChangedArgs {
 
 impl crate::core::SchemaInfo for FileSigChangedArgs {}
 
-impl ToString for FileSigChangedArgs {
-    fn to_string(&self) -> String {
-        format!("File {} signature changed", self.id)
+impl fmt::Display for FileSigChangedArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "File {} signature changed", self.id)
+    }
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum FailedPrecondition {
+    /// File was not open with the expected signature
+    #[serde(rename = "sig")]
+    Sig { expected: u32, actual: Option<u32> },
+
+    /// File (or destination) already exists
+    #[serde(rename = "must_not_exist")]
+    MustNotExist,
+
+    /// File was smaller than the minimum size
+    #[serde(rename = "min_size")]
+    MinSize { expected: u64, actual: u64 },
+
+    /// File was larger than the maximum size
+    #[serde(rename = "max_size")]
+    MaxSize { expected: u64, actual: u64 },
+
+    /// File contents did not have the expected hash
+    #[serde(rename = "hash")]
+    Hash { expected: String, actual: String },
+}
+
+impl crate::core::SchemaInfo for FailedPrecondition {}
+
+impl fmt::Display for FailedPrecondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Sig {
+                expected,
+                actual: Some(actual),
+            } => {
+                write!(f, "expected signature {}, but was {}", expected, actual)
+            }
+            Self::Sig { expected, .. } => {
+                write!(f, "expected signature {}, but was not open", expected)
+            }
+            Self::MustNotExist => write!(f, "already exists"),
+            Self::MinSize { expected, actual } => write!(
+                f,
+                "expected at least {} bytes, but was {} bytes",
+                expected, actual
+            ),
+            Self::MaxSize { expected, actual } => write!(
+                f,
+                "expected at most {} bytes, but was {} bytes",
+                expected, actual
+            ),
+            Self::Hash { expected, actual } => {
+                write!(f, "expected hash {}, but was {}", expected, actual)
+            }
+        }
+    }
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PreconditionFailedArgs {
+    pub path: RemotePath,
+    pub precondition: FailedPrecondition,
+}
+
+impl crate::core::SchemaInfo for PreconditionFailedArgs {}
+
+impl fmt::Display for PreconditionFailedArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Precondition failed for {}: {}",
+            self.path, self.precondition
+        )
     }
 }
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UploadManifestPreparedArgs {
+    pub sessions: Vec<UploadSession>,
+}
+
+impl crate::core::SchemaInfo for UploadManifestPreparedArgs {}
+
+/// Represents the server-side state of a single file within a manifest
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UploadSession {
+    pub path: RemotePath,
+    pub status: UploadSessionStatus,
+}
+
+impl crate::core::SchemaInfo for UploadSession {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum UploadSessionStatus {
+    /// File has been created and is open for writing using the id and sig
+    #[serde(rename = "ready")]
+    Ready {
+        id: u32,
+        sig: u32,
+
+        /// Positions of the chunks the server already placed in the file,
+        /// which do not need to be sent when uploading in chunks
+        #[serde(default)]
+        have_chunks: Vec<u32>,
+    },
+
+    /// File already exists with the expected size and hash, so no upload
+    /// is needed
+    #[serde(rename = "up_to_date")]
+    UpToDate,
+
+    /// File could not be prepared for upload
+    #[serde(rename = "failed")]
+    Failed { error: IoErrorArgs },
+}
+
+impl crate::core::SchemaInfo for UploadSessionStatus {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UploadChunkWrittenArgs {
+    pub id: u32,
+    pub sig: u32,
+    pub index: u32,
+}
+
+impl crate::core::SchemaInfo for UploadChunkWrittenArgs {}
@@ -0,0 +1,111 @@
+use crate::core::request::{JobSpec, ScheduleTrigger};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct JobSubmittedArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for JobSubmittedArgs {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum JobState {
+    #[serde(rename = "running")]
+    Running,
+
+    #[serde(rename = "exited")]
+    Exited,
+
+    /// The server stopped tracking the job before it finished, such as when
+    /// the server was restarted, so its outcome is unknown
+    #[serde(rename = "interrupted")]
+    Interrupted,
+}
+
+impl crate::core::SchemaInfo for JobState {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JobStatusArgs {
+    pub id: u32,
+    pub spec: JobSpec,
+    pub state: JobState,
+
+    /// Time (in milliseconds since the unix epoch) when the job was submitted
+    pub submitted_at_millis: u64,
+
+    /// Time (in milliseconds since the unix epoch) when the job finished,
+    /// if it has exited
+    pub finished_at_millis: Option<u64>,
+
+    /// Exit code of the job, if it has exited and was not killed by a signal
+    pub exit_code: Option<i32>,
+}
+
+impl crate::core::SchemaInfo for JobStatusArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct JobOutputArgs {
+    pub id: u32,
+
+    /// All stdout captured so far for the job
+    pub stdout: Vec<u8>,
+
+    /// All stderr captured so far for the job
+    pub stderr: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for JobOutputArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ScheduleCreatedArgs {
+    pub id: u32,
+
+    /// Time (in milliseconds since the unix epoch) when the schedule will
+    /// first submit a job
+    pub next_run_at_millis: u64,
+}
+
+impl crate::core::SchemaInfo for ScheduleCreatedArgs {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleEntry {
+    pub id: u32,
+    pub spec: JobSpec,
+    pub trigger: ScheduleTrigger,
+
+    /// Time (in milliseconds since the unix epoch) when the schedule will
+    /// next submit a job
+    pub next_run_at_millis: u64,
+
+    /// Status of the most recent jobs submitted by the schedule, oldest
+    /// first, excluding any whose records no longer exist
+    pub runs: Vec<JobStatusArgs>,
+}
+
+impl crate::core::SchemaInfo for ScheduleEntry {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct SchedulesListArgs {
+    pub schedules: Vec<ScheduleEntry>,
+}
+
+impl crate::core::SchemaInfo for SchedulesListArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ScheduleDeletedArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for ScheduleDeletedArgs {}
@@ -0,0 +1,32 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct LogAppendedArgs {
+    pub name: String,
+
+    /// Number of lines appended to the log
+    pub appended: usize,
+}
+
+impl crate::core::SchemaInfo for LogAppendedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct LogRangeArgs {
+    pub name: String,
+
+    /// Number of the first line returned
+    pub start: u64,
+
+    pub lines: Vec<String>,
+
+    /// Total lines kept in the log, which can be used to read further lines
+    /// as they are appended
+    pub total: u64,
+}
+
+impl crate::core::SchemaInfo for LogRangeArgs {}
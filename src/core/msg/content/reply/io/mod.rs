@@ -1,11 +1,16 @@
 mod fs;
+mod job;
+mod logs;
 mod proc;
 
 pub use fs::*;
+pub use job::*;
+pub use logs::*;
 pub use proc::*;
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::io;
 
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -17,9 +22,9 @@ pub struct IoErrorArgs {
 
 impl crate::core::SchemaInfo for IoErrorArgs {}
 
-impl ToString for IoErrorArgs {
-    fn to_string(&self) -> String {
-        self.description.clone()
+impl fmt::Display for IoErrorArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description)
     }
 }
 
@@ -81,12 +86,12 @@ impl From<io::Error> for IoErrorArgs {
     }
 }
 
-impl Into<io::Error> for IoErrorArgs {
-    fn into(self) -> io::Error {
-        if let Some(code) = self.os_code {
+impl From<IoErrorArgs> for io::Error {
+    fn from(val: IoErrorArgs) -> Self {
+        if let Some(code) = val.os_code {
             io::Error::from_raw_os_error(code)
         } else {
-            io::Error::new(self.error_kind.into(), self.description)
+            io::Error::new(val.error_kind.into(), val.description)
         }
     }
 }
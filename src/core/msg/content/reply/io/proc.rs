@@ -1,11 +1,21 @@
+use crate::core::reply::PageInfoArgs;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct ProcStartedArgs {
     pub id: u32,
+
+    /// Absolute path to the file receiving stdout, if redirected
+    #[serde(default)]
+    pub stdout_path: Option<String>,
+
+    /// Absolute path to the file receiving stderr, if redirected
+    #[serde(default)]
+    pub stderr_path: Option<String>,
 }
 
 impl crate::core::SchemaInfo for ProcStartedArgs {}
@@ -19,12 +29,31 @@ pub struct ProcStdinWrittenArgs {
 
 impl crate::core::SchemaInfo for ProcStdinWrittenArgs {}
 
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PtyResizedArgs {
+    pub id: u32,
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl crate::core::SchemaInfo for PtyResizedArgs {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct ProcStdoutContentsArgs {
     pub id: u32,
     pub output: Vec<u8>,
+
+    /// Offset within all output of the proc where the next read continues
+    #[serde(default)]
+    pub next_offset: u64,
+
+    /// Bytes of output captured that have yet to be read
+    #[serde(default)]
+    pub remaining: u64,
 }
 
 impl crate::core::SchemaInfo for ProcStdoutContentsArgs {}
@@ -35,6 +64,14 @@ impl crate::core::SchemaInfo for ProcStdoutContentsArgs {}
 pub struct ProcStderrContentsArgs {
     pub id: u32,
     pub output: Vec<u8>,
+
+    /// Offset within all output of the proc where the next read continues
+    #[serde(default)]
+    pub next_offset: u64,
+
+    /// Bytes of output captured that have yet to be read
+    #[serde(default)]
+    pub remaining: u64,
 }
 
 impl crate::core::SchemaInfo for ProcStderrContentsArgs {}
@@ -59,3 +96,109 @@ pub struct ProcStatusArgs {
 }
 
 impl crate::core::SchemaInfo for ProcStatusArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ProcTreeArgs {
+    pub id: u32,
+
+    /// The proc itself followed by all of its descendants, ordered
+    /// breadth-first
+    pub procs: Vec<ProcTreeEntry>,
+}
+
+impl crate::core::SchemaInfo for ProcTreeArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ProcTreeEntry {
+    pub pid: u32,
+    pub parent_pid: u32,
+    pub command: String,
+
+    /// Total CPU time (user and system) consumed by the process
+    pub cpu_time_millis: u64,
+
+    /// Resident set size of the process in bytes
+    pub rss_bytes: u64,
+}
+
+impl crate::core::SchemaInfo for ProcTreeEntry {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq,
+)]
+pub struct ProcResourcesArgs {
+    pub id: u32,
+
+    /// Average CPU usage over the lifetime of the process as a percentage of
+    /// a single core
+    pub cpu_percent: f32,
+
+    /// Total CPU time (user and system) consumed by the process
+    pub cpu_time_millis: u64,
+
+    /// Resident set size of the process in bytes
+    pub rss_bytes: u64,
+
+    /// Time since the process was started
+    pub elapsed_millis: u64,
+}
+
+// NOTE: The server never reports a NaN cpu percent, so equality is total
+impl Eq for ProcResourcesArgs {}
+
+impl crate::core::SchemaInfo for ProcResourcesArgs {}
+
+/// How a process was spawned, as recorded by the server at the time
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ProcInfoArgs {
+    pub id: u32,
+
+    /// Command as requested, which is either a path or a program looked up
+    /// through the PATH of the server
+    pub command: String,
+    pub args: Vec<String>,
+
+    /// Absolute path to the binary that the command resolved to, if it
+    /// could be determined
+    pub program: Option<String>,
+
+    /// Absolute path to the directory the process was started in
+    pub current_dir: String,
+
+    /// Environment variables the process inherited from the server
+    pub env: BTreeMap<String, String>,
+}
+
+impl crate::core::SchemaInfo for ProcInfoArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ProcEntryArgs {
+    pub id: u32,
+    pub labels: Vec<String>,
+    pub detached: bool,
+    pub is_alive: bool,
+    pub exit_code: Option<i32>,
+
+    /// Time since the proc was started
+    pub age_millis: u64,
+}
+
+impl crate::core::SchemaInfo for ProcEntryArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ProcsListArgs {
+    pub procs: Vec<ProcEntryArgs>,
+    pub page: PageInfoArgs,
+}
+
+impl crate::core::SchemaInfo for ProcsListArgs {}
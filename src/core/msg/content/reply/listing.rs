@@ -0,0 +1,16 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Position of a page within the full listing it was taken from
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PageInfoArgs {
+    /// Total entries matching the filters of the listing across all pages
+    pub total: u64,
+
+    /// Offset of the next page, or none if this is the last page
+    pub next_offset: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for PageInfoArgs {}
@@ -1,25 +1,58 @@
+mod backup;
 mod batch;
 mod capabilities;
+mod config;
+mod connection;
 mod custom;
+mod diagnostics;
+mod error_code;
+mod eviction;
+#[cfg(feature = "fault-injection")]
+mod fault;
 mod forward;
 mod generic_error;
-mod internal_debug;
+mod identity;
 mod io;
+mod listing;
+mod power;
+mod quota;
 mod sequence;
+mod time;
+mod transaction;
+mod unsupported;
 mod version;
+#[cfg(feature = "wasm")]
+mod wasm;
 
+pub use backup::*;
 pub use batch::*;
 pub use capabilities::*;
+pub use config::*;
+pub use connection::*;
 pub use custom::*;
+pub use diagnostics::*;
+pub use error_code::*;
+pub use eviction::*;
+#[cfg(feature = "fault-injection")]
+pub use fault::*;
 pub use forward::*;
 pub use generic_error::*;
-pub use internal_debug::*;
+pub use identity::*;
 pub use io::*;
+pub use listing::*;
+pub use power::*;
+pub use quota::*;
 pub use sequence::*;
+pub use time::*;
+pub use transaction::*;
+pub use unsupported::*;
 pub use version::*;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 // NOTE: Cannot adjacently tag as JsonSchema does not support it and
 //       it leads to deserialization errors with enum variants without
@@ -50,6 +83,66 @@ pub enum Reply {
     #[serde(rename = "capabilities_reply")]
     Capabilities(CapabilitiesArgs),
 
+    // ------------------------------------------------------------------------
+    // Time on the clock of the remote instance, used to estimate how far it
+    // has drifted from the local clock
+    #[serde(rename = "time_info_reply")]
+    TimeInfo(TimeInfoArgs),
+
+    // ------------------------------------------------------------------------
+    // Identity of the remote instance, proven using a key pinned by clients
+    /// This will be returned containing the fingerprint of the server's
+    /// identity key and its proof of holding the key
+    #[serde(rename = "identify_reply")]
+    Identity(IdentityArgs),
+
+    /// This will be returned containing the client keys trusted by the
+    /// server after changing them
+    #[serde(rename = "update_trusted_clients_reply")]
+    TrustedClients(TrustedClientsArgs),
+
+    /// This will be returned containing the public half of the ephemeral key
+    /// of the server, completing the negotiation of a session key
+    #[serde(rename = "handshake_reply")]
+    Handshake(HandshakeArgs),
+
+    /// This will be returned in place of any other reply to a sealed
+    /// request, wrapping the msg of that reply
+    #[serde(rename = "sealed_reply")]
+    Sealed(SealedArgs),
+
+    // ------------------------------------------------------------------------
+    // Configuration distributed by operators through the remote instance
+    /// This will be returned upon storing a configuration document
+    #[serde(rename = "push_config_reply")]
+    ConfigPushed(ConfigPushedArgs),
+
+    /// This will be returned containing the configuration document last
+    /// pushed to the server
+    #[serde(rename = "get_config_reply")]
+    Config(ConfigArgs),
+
+    /// This will be returned containing the webhooks that the server sends
+    /// events to after changing them
+    #[serde(rename = "update_webhooks_reply")]
+    Webhooks(WebhooksArgs),
+
+    // ------------------------------------------------------------------------
+    // Administration of the host running the remote instance
+    /// This will be returned containing the token to confirm a power action
+    /// with, or upon confirming the action
+    #[serde(rename = "power_control_reply")]
+    PowerControl(PowerControlArgs),
+
+    /// This will be returned containing the archived metadata of the server
+    #[serde(rename = "export_state_reply")]
+    StateExported(StateExportedArgs),
+
+    /// This will be returned upon importing an archive, containing the
+    /// totals of what was taken from it
+    #[serde(rename = "import_state_reply")]
+    StateImported(StateImportedArgs),
+
     // ------------------------------------------------------------------------
     // Dir-based operations such as creating and listing entries
     /// This will be returned upon creating a directory
@@ -64,11 +157,50 @@ pub enum Reply {
     #[serde(rename = "remove_dir_reply")]
     DirRemoved(DirRemovedArgs),
 
+    /// This will be returned upon creating many dirs and empty files,
+    /// containing the result of creating each of them
+    #[serde(rename = "create_paths_reply")]
+    PathsCreated(PathsCreatedArgs),
+
     /// This will be returned upon collecting the list of files and directories
     /// at the provided path
     #[serde(rename = "list_dir_contents_reply")]
     DirContentsList(DirContentsListArgs),
 
+    /// This will be returned one or more times when streaming the files and
+    /// directories at the provided path, with the last chunk marked as such
+    #[serde(rename = "list_dir_contents_chunk_reply")]
+    DirContentsListChunk(DirContentsListChunkArgs),
+
+    /// This will be returned upon resolving a path, indicating its
+    /// canonicalized form and what (if anything) exists there
+    #[serde(rename = "resolve_path_reply")]
+    PathResolved(PathResolvedArgs),
+
+    /// This will be returned upon sniffing a file, indicating its detected
+    /// type and text characteristics
+    #[serde(rename = "sniff_file_reply")]
+    FileSniffed(FileSniffedArgs),
+
+    /// This will be returned upon computing the checksum of a file
+    #[serde(rename = "get_file_checksum_reply")]
+    FileChecksum(FileChecksumArgs),
+
+    /// This will be returned upon comparing two files, containing the
+    /// unified diff between them
+    #[serde(rename = "diff_files_reply")]
+    FilesDiffed(FilesDiffedArgs),
+
+    /// This will be returned upon asking for recent changes to the
+    /// filesystem, containing those made after the last one seen
+    #[serde(rename = "recent_fs_events_reply")]
+    FsEvents(FsEventsArgs),
+
+    /// This will be returned upon listing the filesystems mounted on the
+    /// server
+    #[serde(rename = "list_mounts_reply")]
+    MountsList(MountsListArgs),
+
     // ------------------------------------------------------------------------
     // File-based operations such as reading and writing
     /// This will be returned upon a file being opened or refreshed
@@ -99,11 +231,36 @@ pub enum Reply {
     #[serde(rename = "read_file_reply")]
     FileContents(FileContentsArgs),
 
+    /// This will be returned upon reading many files, containing a result
+    /// per file in the same order as requested
+    #[serde(rename = "read_files_reply")]
+    FilesContents(FilesContentsArgs),
+
     /// This will be returned upon writing a file's contents
     /// Contains the updated signature for the file
     #[serde(rename = "write_file_reply")]
     FileWritten(FileWrittenArgs),
 
+    /// This will be returned upon writing a file by its path in a single
+    /// operation, containing the final signature and hash of the file
+    #[serde(rename = "write_file_atomic_by_path_reply")]
+    AtomicFileWritten(AtomicFileWrittenArgs),
+
+    /// This will be returned upon patching the lines of a file, containing
+    /// the final signature and hash of the file
+    #[serde(rename = "patch_file_lines_reply")]
+    FileLinesPatched(FileLinesPatchedArgs),
+
+    /// This will be returned upon preparing the files of a manifest, containing
+    /// an upload session per file in the same order as the manifest
+    #[serde(rename = "upload_manifest_reply")]
+    UploadManifestPrepared(UploadManifestPreparedArgs),
+
+    /// This will be returned upon writing a chunk of a file being uploaded
+    /// in chunks
+    #[serde(rename = "write_upload_chunk_reply")]
+    UploadChunkWritten(UploadChunkWrittenArgs),
+
     // ------------------------------------------------------------------------
     // Program execution operations such as running and streaming
     /// This will be returned upon starting a process on the server, indicating
@@ -115,6 +272,10 @@ pub enum Reply {
     #[serde(rename = "write_proc_stdin_reply")]
     ProcStdinWritten(ProcStdinWrittenArgs),
 
+    /// This will be returned upon resizing the pseudo-terminal of a process
+    #[serde(rename = "resize_pty_reply")]
+    PtyResized(PtyResizedArgs),
+
     /// This will be returned upon receiving stdout from a remote process on
     /// the server, if enabled when first executing
     #[serde(rename = "read_proc_stdout_reply")]
@@ -134,6 +295,64 @@ pub enum Reply {
     #[serde(rename = "read_proc_status_reply")]
     ProcStatus(ProcStatusArgs),
 
+    /// This will be returned reporting a process and its descendants,
+    /// including the resources used by each
+    #[serde(rename = "read_proc_tree_reply")]
+    ProcTree(ProcTreeArgs),
+
+    /// This will be returned reporting the resources used by a process as
+    /// sampled by the server
+    #[serde(rename = "read_proc_resources_reply")]
+    ProcResources(ProcResourcesArgs),
+
+    /// This will be returned reporting how a process was spawned
+    #[serde(rename = "read_proc_info_reply")]
+    ProcInfo(ProcInfoArgs),
+
+    /// This will be returned containing a page of the procs tracked by the
+    /// server
+    #[serde(rename = "list_procs_reply")]
+    ProcsList(ProcsListArgs),
+
+    // ------------------------------------------------------------------------
+    // Job-based operations whose results persist beyond a connection
+    /// This will be returned upon submitting a job, providing an id that can
+    /// be used to retrieve its status and output later
+    #[serde(rename = "submit_job_reply")]
+    JobSubmitted(JobSubmittedArgs),
+
+    /// This will be returned reporting the status of a job, indicating if
+    /// still running or when it finished (and the exit code)
+    #[serde(rename = "query_job_reply")]
+    JobStatus(JobStatusArgs),
+
+    /// This will be returned containing all output captured for a job
+    #[serde(rename = "collect_job_output_reply")]
+    JobOutput(JobOutputArgs),
+
+    /// This will be returned upon creating a schedule, providing an id that
+    /// can be used to delete it later
+    #[serde(rename = "create_schedule_reply")]
+    ScheduleCreated(ScheduleCreatedArgs),
+
+    /// This will be returned upon listing schedules
+    #[serde(rename = "list_schedules_reply")]
+    SchedulesList(SchedulesListArgs),
+
+    /// This will be returned upon deleting a schedule
+    #[serde(rename = "delete_schedule_reply")]
+    ScheduleDeleted(ScheduleDeletedArgs),
+
+    // ------------------------------------------------------------------------
+    // Logs collected from clients into files managed by the server
+    /// This will be returned upon appending lines to a log
+    #[serde(rename = "append_log_reply")]
+    LogAppended(LogAppendedArgs),
+
+    /// This will be returned containing the requested lines of a log
+    #[serde(rename = "read_log_range_reply")]
+    LogRange(LogRangeArgs),
+
     // ------------------------------------------------------------------------
     // Miscellaneous, adhoc messages
     /// This will be returned upon encountering an error during evaluation
@@ -148,6 +367,16 @@ pub enum Reply {
     #[serde(rename = "batch_reply")]
     Batch(BatchArgs),
 
+    /// This will be returned once per operation of a batch whose results are
+    /// streamed, in the order that operations complete
+    #[serde(rename = "batch_result_reply")]
+    BatchResult(BatchResultArgs),
+
+    /// This will be returned upon completing a transaction, describing the
+    /// outcome of each of its operations
+    #[serde(rename = "transaction_reply")]
+    Transaction(TransactionArgs),
+
     /// This will be sent to either the client or server and the msg will be
     /// passed along to the associated address (if possible)
     #[serde(rename = "forward_reply")]
@@ -158,9 +387,59 @@ pub enum Reply {
     #[serde(rename = "custom_reply")]
     Custom(CustomArgs),
 
-    /// For debugging purposes when needing to query the state of client/server
-    #[serde(rename = "internal_debug_reply")]
-    InternalDebug(InternalDebugArgs),
+    /// This will be returned containing the requested sections of the
+    /// diagnostics of the remote instance
+    #[serde(rename = "diagnostics_reply")]
+    Diagnostics(DiagnosticsArgs),
+
+    /// This will be returned containing a page of the clients that have
+    /// communicated with the server
+    #[serde(rename = "list_connections_reply")]
+    ConnectionsList(ConnectionsListArgs),
+
+    /// This will be returned containing the resources that the server will
+    /// evict unless they are touched
+    #[serde(rename = "list_pending_evictions_reply")]
+    PendingEvictions(PendingEvictionsArgs),
+
+    /// This will be returned upon binding an origin, containing the nonce
+    /// to echo back if the origin is not yet bound
+    #[serde(rename = "bind_origin_reply")]
+    OriginBinding(OriginBindingArgs),
+
+    /// This will be sent unprompted to every client that has communicated
+    /// with the server once it begins shutting down
+    #[serde(rename = "server_shutting_down_reply")]
+    ServerShuttingDown(ServerShuttingDownArgs),
+
+    /// This will be returned upon receiving a request that cannot be decoded,
+    /// such as one of a type introduced by a newer version
+    #[serde(rename = "unsupported_reply")]
+    Unsupported(UnsupportedArgs),
+
+    // ------------------------------------------------------------------------
+    // Fault injection used to exercise error paths when testing
+    /// This will be returned upon injecting a fault
+    #[cfg(feature = "fault-injection")]
+    #[serde(rename = "inject_fault_reply")]
+    FaultInjected(FaultInjectedArgs),
+
+    /// This will be returned upon removing all injected faults
+    #[cfg(feature = "fault-injection")]
+    #[serde(rename = "clear_faults_reply")]
+    FaultsCleared(FaultsClearedArgs),
+
+    // ------------------------------------------------------------------------
+    // Wasm modules that serve custom requests within a sandbox
+    /// This will be returned upon loading a wasm module
+    #[cfg(feature = "wasm")]
+    #[serde(rename = "load_wasm_handler_reply")]
+    WasmHandlerLoaded(WasmHandlerLoadedArgs),
+
+    /// This will be returned upon unloading a wasm module
+    #[cfg(feature = "wasm")]
+    #[serde(rename = "unload_wasm_handler_reply")]
+    WasmHandlerUnloaded(WasmHandlerUnloadedArgs),
 }
 
 impl crate::core::SchemaInfo for Reply {}
@@ -180,24 +459,59 @@ impl From<Box<dyn std::error::Error>> for Reply {
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(tag = "type")]
 pub enum ReplyError {
+    /// Error whose code is included with it, defaulting to 1 (generic)
     #[serde(rename = "generic_error")]
     Generic(GenericErrorArgs),
 
+    /// Error whose code is derived from its error kind, such as 31 (not
+    /// found) for NotFound
     #[serde(rename = "io_error")]
     Io(IoErrorArgs),
 
+    /// Error whose code is always 20 (file signature changed)
     #[serde(rename = "file_sig_changed_error")]
     FileSigChanged(FileSigChangedArgs),
+
+    /// Error whose code is always 21 (precondition failed)
+    #[serde(rename = "precondition_failed_error")]
+    PreconditionFailed(PreconditionFailedArgs),
+
+    /// Error whose code is always 7 (quota exceeded)
+    #[serde(rename = "quota_exceeded_error")]
+    QuotaExceeded(QuotaExceededArgs),
 }
 
 impl crate::core::SchemaInfo for ReplyError {}
 
-impl ToString for ReplyError {
-    fn to_string(&self) -> String {
+impl ReplyError {
+    /// Creates a generic error with a more specific code
+    pub fn with_code(msg: impl Into<String>, code: ErrorCode) -> Self {
+        Self::Generic(GenericErrorArgs {
+            msg: msg.into(),
+            code,
+        })
+    }
+
+    /// Stable code identifying the kind of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Generic(args) => args.code,
+            Self::Io(args) => ErrorCode::from(&args.error_kind),
+            Self::FileSigChanged(_) => ErrorCode::FILE_SIG_CHANGED,
+            Self::PreconditionFailed(_) => ErrorCode::PRECONDITION_FAILED,
+            Self::QuotaExceeded(_) => ErrorCode::QUOTA_EXCEEDED,
+        }
+    }
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Generic(args) => args.to_string(),
-            Self::Io(args) => args.to_string(),
-            Self::FileSigChanged(args) => args.to_string(),
+            Self::Generic(args) => write!(f, "{}", args),
+            Self::Io(args) => write!(f, "{}", args),
+            Self::FileSigChanged(args) => write!(f, "{}", args),
+            Self::PreconditionFailed(args) => write!(f, "{}", args),
+            Self::QuotaExceeded(args) => write!(f, "{}", args),
         }
     }
 }
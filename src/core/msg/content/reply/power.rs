@@ -0,0 +1,27 @@
+use crate::core::request::PowerAction;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PowerControlArgs {
+    /// Action that was requested or confirmed
+    pub action: PowerAction,
+
+    /// Seconds the server waits once confirmed before performing the action
+    pub delay_secs: u64,
+
+    /// Token that must be sent back to confirm the action
+    pub token: String,
+
+    /// Whether the action was confirmed and is now scheduled, rather than
+    /// awaiting confirmation
+    pub confirmed: bool,
+
+    /// Seconds left to confirm the action before the token expires, which is
+    /// zero once confirmed
+    pub expires_in_secs: u64,
+}
+
+impl crate::core::SchemaInfo for PowerControlArgs {}
@@ -0,0 +1,30 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct QuotaExceededArgs {
+    /// Identity whose quota was exceeded, being the fingerprint of the key
+    /// that signed the request or otherwise the address it came from
+    pub identity: String,
+
+    /// Bytes already transferred by the identity
+    pub used: u64,
+
+    /// Maximum bytes the identity can transfer
+    pub quota: u64,
+}
+
+impl crate::core::SchemaInfo for QuotaExceededArgs {}
+
+impl fmt::Display for QuotaExceededArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Transfer quota of {} bytes exceeded by {} ({} bytes used)",
+            self.quota, self.identity, self.used
+        )
+    }
+}
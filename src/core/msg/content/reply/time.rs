@@ -0,0 +1,21 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Timestamps of a single exchange, each in microseconds since the unix
+/// epoch, from which a client estimates the offset of the clock of the
+/// server from its own
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct TimeInfoArgs {
+    /// Time on the clock of the client when it sent the request
+    pub client_sent_at_micros: u64,
+
+    /// Time on the clock of the server when it received the request
+    pub server_received_at_micros: u64,
+
+    /// Time on the clock of the server when it replied
+    pub server_sent_at_micros: u64,
+}
+
+impl crate::core::SchemaInfo for TimeInfoArgs {}
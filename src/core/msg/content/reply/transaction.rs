@@ -0,0 +1,59 @@
+use crate::core::Reply;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Represents arguments to a response of executing a transaction
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct TransactionArgs {
+    /// Whether every operation was applied and kept
+    pub committed: bool,
+
+    /// Outcome of each operation, in the order they were given
+    pub steps: Vec<TransactionStepArgs>,
+}
+
+impl crate::core::SchemaInfo for TransactionArgs {}
+
+/// Outcome of a single operation within a transaction
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TransactionStepArgs {
+    pub status: TransactionStepStatus,
+
+    /// Reply of the operation, which is absent if it was never executed
+    #[serde(default)]
+    pub result: Option<Reply>,
+
+    /// Why undoing the operation failed, leaving its changes in place
+    #[serde(default)]
+    pub undo_error: Option<String>,
+}
+
+impl crate::core::SchemaInfo for TransactionStepArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum TransactionStepStatus {
+    /// Operation was applied and kept as every operation succeeded
+    #[serde(rename = "committed")]
+    Committed,
+
+    /// Operation was applied, but undone as a later operation failed
+    #[serde(rename = "rolled_back")]
+    RolledBack,
+
+    /// Operation was applied, but could not be undone as a later operation
+    /// failed
+    #[serde(rename = "undo_failed")]
+    UndoFailed,
+
+    /// Operation failed, causing every operation before it to be undone
+    #[serde(rename = "failed")]
+    Failed,
+
+    /// Operation was never executed as an earlier operation failed
+    #[serde(rename = "skipped")]
+    Skipped,
+}
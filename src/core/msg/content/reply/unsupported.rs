@@ -0,0 +1,17 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Reports that a request could not be decoded, such as one of a type
+/// introduced by a newer version
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UnsupportedArgs {
+    /// Type of the content as it appeared when serialized
+    pub type_name: String,
+
+    /// Reason that the content could not be decoded
+    pub description: String,
+}
+
+impl crate::core::SchemaInfo for UnsupportedArgs {}
@@ -0,0 +1,20 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WasmHandlerLoadedArgs {
+    pub namespace: String,
+}
+
+impl crate::core::SchemaInfo for WasmHandlerLoadedArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WasmHandlerUnloadedArgs {
+    pub namespace: String,
+}
+
+impl crate::core::SchemaInfo for WasmHandlerUnloadedArgs {}
@@ -0,0 +1,24 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ExportStateArgs {
+    /// If true, the stdout and stderr captured by each job are left out of
+    /// the archive, keeping it small
+    #[serde(default)]
+    pub skip_job_output: bool,
+}
+
+impl crate::core::SchemaInfo for ExportStateArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ImportStateArgs {
+    /// Archive produced by exporting the state of a server
+    pub archive: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for ImportStateArgs {}
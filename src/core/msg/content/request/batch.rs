@@ -8,12 +8,21 @@ use serde::{Deserialize, Serialize};
 )]
 pub struct BatchArgs {
     pub operations: Vec<Request>,
+
+    /// If true, each result is sent as its own reply as soon as its
+    /// operation completes rather than waiting for all operations, which
+    /// only applies to batches that are not nested in other operations
+    #[serde(default)]
+    pub stream_results: bool,
 }
 
 impl crate::core::SchemaInfo for BatchArgs {}
 
 impl From<Vec<Request>> for BatchArgs {
     fn from(operations: Vec<Request>) -> Self {
-        Self { operations }
+        Self {
+            operations,
+            stream_results: false,
+        }
     }
 }
@@ -0,0 +1,50 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PushConfigArgs {
+    /// Opaque configuration document that the server stores on behalf of
+    /// the operator
+    pub blob: Vec<u8>,
+
+    /// Version of the document, which must be newer than that of the
+    /// config already stored by the server
+    pub version: u64,
+}
+
+impl crate::core::SchemaInfo for PushConfigArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WebhookArgs {
+    /// Url of the endpoint to send events to, which must use http
+    pub url: String,
+
+    /// If provided, used to sign the body of each event sent
+    #[serde(default)]
+    pub secret: Option<Vec<u8>>,
+
+    /// Names of the events to send, or all events if empty
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for WebhookArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UpdateWebhooksArgs {
+    /// Webhooks to add, replacing any existing webhooks with the same url
+    #[serde(default)]
+    pub add: Vec<WebhookArgs>,
+
+    /// Urls of webhooks to remove
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for UpdateWebhooksArgs {}
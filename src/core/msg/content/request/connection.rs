@@ -0,0 +1,64 @@
+use super::PageArgs;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ListConnectionsArgs {
+    /// If provided, only clients at this ip address (or exact address
+    /// including the port) are listed
+    #[serde(default)]
+    pub origin: Option<String>,
+
+    /// If provided, only clients that communicated with the server within
+    /// this many seconds are listed
+    #[serde(default)]
+    pub max_idle_secs: Option<u64>,
+
+    /// Page of clients to return, ordered by address
+    #[serde(default)]
+    pub page: PageArgs,
+}
+
+impl crate::core::SchemaInfo for ListConnectionsArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct BindOriginArgs {
+    /// Nonce from the challenge of the server being echoed back, or empty
+    /// to request a new challenge
+    #[serde(default)]
+    pub nonce: Vec<u8>,
+
+    /// Public identity key of the client that signed the echoed nonce
+    #[serde(default)]
+    pub identity_key: Vec<u8>,
+
+    /// Signature of the echoed nonce by the identity key, as produced by
+    /// signing the data from `signed_nonce`
+    #[serde(default)]
+    pub signature: Vec<u8>,
+
+    /// Ignored bytes that make the request large enough to be answered by
+    /// servers that ignore small requests from unbound origins
+    #[serde(default)]
+    pub padding: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for BindOriginArgs {}
+
+/// Bytes of padding that clients add to requests to bind their origin
+pub const BIND_ORIGIN_PADDING: usize = 512;
+
+/// Context signed along with the nonce of a challenge when echoing it back,
+/// so that the signature cannot be passed off as proof of anything else
+const SIGNED_NONCE_CONTEXT: &[u8] = b"over-there origin nonce v1";
+
+/// Data signed by the identity key of a client to echo back `nonce`
+pub fn signed_nonce(nonce: &[u8]) -> Vec<u8> {
+    let mut data = SIGNED_NONCE_CONTEXT.to_vec();
+    data.extend_from_slice(nonce);
+    data
+}
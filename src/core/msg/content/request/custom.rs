@@ -6,13 +6,21 @@ use serde::{Deserialize, Serialize};
 )]
 pub struct CustomArgs {
     pub data: Vec<u8>,
+
+    /// Namespace of the handler to evaluate the data, such as one served by
+    /// a wasm module, or the user-implemented handler if not provided
+    #[serde(default)]
+    pub namespace: Option<String>,
 }
 
 impl crate::core::SchemaInfo for CustomArgs {}
 
 impl From<Vec<u8>> for CustomArgs {
     fn from(data: Vec<u8>) -> Self {
-        Self { data }
+        Self {
+            data,
+            namespace: None,
+        }
     }
 }
 
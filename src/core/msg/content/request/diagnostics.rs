@@ -0,0 +1,81 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Part of the diagnostics of a remote instance that can be requested
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum DiagnosticSection {
+    /// Counts of connections, files, procs, and other tracked state
+    #[serde(rename = "state_counters")]
+    StateCounters,
+
+    /// Configuration that the remote instance is running with
+    #[serde(rename = "config")]
+    Config,
+
+    /// Procs and jobs being run by the remote instance
+    #[serde(rename = "tasks")]
+    Tasks,
+
+    /// Output buffered by the remote instance that has yet to be read
+    #[serde(rename = "buffers")]
+    Buffers,
+
+    /// Bytes transferred by each identity and class of request
+    #[serde(rename = "transfers")]
+    Transfers,
+}
+
+impl crate::core::SchemaInfo for DiagnosticSection {}
+
+impl DiagnosticSection {
+    pub const ALL: [Self; 5] = [
+        Self::StateCounters,
+        Self::Config,
+        Self::Tasks,
+        Self::Buffers,
+        Self::Transfers,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::StateCounters => "state_counters",
+            Self::Config => "config",
+            Self::Tasks => "tasks",
+            Self::Buffers => "buffers",
+            Self::Transfers => "transfers",
+        }
+    }
+}
+
+impl FromStr for DiagnosticSection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| format!("Unknown diagnostic section: {}", s))
+    }
+}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiagnosticsArgs {
+    /// Sections to include in the diagnostics, or all sections if empty
+    #[serde(default)]
+    pub sections: Vec<DiagnosticSection>,
+}
+
+impl crate::core::SchemaInfo for DiagnosticsArgs {}
+
+impl DiagnosticsArgs {
+    /// Whether or not `section` was requested
+    pub fn includes(&self, section: DiagnosticSection) -> bool {
+        self.sections.is_empty() || self.sections.contains(&section)
+    }
+}
@@ -0,0 +1,70 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Kind of resource that the server evicts once it goes untouched
+#[derive(
+    JsonSchema,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+)]
+pub enum EvictionKind {
+    /// Open file, which is closed when evicted
+    #[serde(rename = "file")]
+    File,
+
+    /// Proc, which is killed when evicted unless detached or exited
+    #[serde(rename = "proc")]
+    Proc,
+
+    /// Key of the session of a connection, which is forgotten when evicted
+    #[serde(rename = "session")]
+    Session,
+}
+
+impl crate::core::SchemaInfo for EvictionKind {}
+
+impl EvictionKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Proc => "proc",
+            Self::Session => "session",
+        }
+    }
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EvictionTargetArgs {
+    pub kind: EvictionKind,
+
+    /// Id of the file or proc, or address (<ip>:<port>) of the connection
+    /// of the session
+    pub id: String,
+}
+
+impl crate::core::SchemaInfo for EvictionTargetArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ListPendingEvictionsArgs {
+    /// If provided, only resources that will be evicted within this many
+    /// seconds are listed
+    #[serde(default)]
+    pub within_secs: Option<u64>,
+
+    /// Resources to touch before listing, keeping them alive for another
+    /// full ttl
+    #[serde(default)]
+    pub touch: Vec<EvictionTargetArgs>,
+}
+
+impl crate::core::SchemaInfo for ListPendingEvictionsArgs {}
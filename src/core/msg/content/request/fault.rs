@@ -0,0 +1,48 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Misbehavior forced upon a request in place of handling it normally
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum Fault {
+    /// Fail the request with a generic error containing `msg`
+    #[serde(rename = "fail")]
+    Fail { msg: String },
+
+    /// Wait `millis` before handling the request as usual
+    #[serde(rename = "delay")]
+    Delay { millis: u64 },
+
+    /// Panic while handling the request, meaning that no reply is sent
+    /// although the server continues to handle other requests
+    #[serde(rename = "panic")]
+    Panic,
+}
+
+impl crate::core::SchemaInfo for Fault {}
+
+impl Default for Fault {
+    fn default() -> Self {
+        Self::Fail {
+            msg: String::from("Injected fault"),
+        }
+    }
+}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct InjectFaultArgs {
+    /// Type of request to affect as it appears when serialized, such as
+    /// `read_file_request`
+    pub request_type: String,
+
+    pub fault: Fault,
+
+    /// Total requests to affect before the fault is removed, or all
+    /// requests if not provided
+    #[serde(default)]
+    pub times: Option<u32>,
+}
+
+impl crate::core::SchemaInfo for InjectFaultArgs {}
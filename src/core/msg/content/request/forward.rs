@@ -1,12 +1,17 @@
-use super::Request;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
 #[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct ForwardArgs {
+    #[serde(with = "crate::core::msg::content::socket_addr")]
+    #[schemars(with = "String")]
     pub address: SocketAddr,
-    pub request: Box<Request>,
+
+    /// Msg containing the request to send to the server at `address`,
+    /// encoded and signed (if at all) by the original sender so that it
+    /// reaches that server unchanged
+    pub msg: Vec<u8>,
 }
 
 impl crate::core::SchemaInfo for ForwardArgs {}
@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct IdentifyArgs {
+    /// Random bytes that the server signs with its identity key, proving
+    /// that it holds the key
+    pub challenge: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for IdentifyArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UpdateTrustedClientsArgs {
+    /// Fingerprints of client keys to start trusting
+    #[serde(default)]
+    pub add: Vec<String>,
+
+    /// Fingerprints of client keys to stop trusting
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for UpdateTrustedClientsArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct HandshakeArgs {
+    /// Public half of the ephemeral x25519 key of the client
+    pub public_key: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for HandshakeArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct SealedArgs {
+    /// Nonce the msg was sealed with
+    pub nonce: Vec<u8>,
+
+    /// Msg of the client encrypted with the key negotiated for the session
+    pub data: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for SealedArgs {}
@@ -1,11 +0,0 @@
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-
-#[derive(
-    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
-)]
-pub struct InternalDebugArgs {
-    pub input: Vec<u8>,
-}
-
-impl crate::core::SchemaInfo for InternalDebugArgs {}
@@ -1,3 +1,4 @@
+use crate::core::RemotePath;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -5,8 +6,12 @@ use serde::{Deserialize, Serialize};
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct CreateDirArgs {
-    pub path: String,
+    pub path: RemotePath,
     pub include_components: bool,
+
+    /// If provided, unix permission bits to apply to each created directory
+    /// instead of the server's default
+    pub mode: Option<u32>,
 }
 
 impl crate::core::SchemaInfo for CreateDirArgs {}
@@ -15,8 +20,8 @@ impl crate::core::SchemaInfo for CreateDirArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct RenameDirArgs {
-    pub from: String,
-    pub to: String,
+    pub from: RemotePath,
+    pub to: RemotePath,
 }
 
 impl crate::core::SchemaInfo for RenameDirArgs {}
@@ -25,29 +30,176 @@ impl crate::core::SchemaInfo for RenameDirArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct RemoveDirArgs {
-    pub path: String,
+    pub path: RemotePath,
     pub non_empty: bool,
 }
 
 impl crate::core::SchemaInfo for RemoveDirArgs {}
 
+/// Represents arguments to a request to create many dirs and empty files at
+/// once, along with any of their missing parent dirs
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct CreatePathsArgs {
+    pub dirs: Vec<RemotePath>,
+
+    /// Files created empty after every dir has been created, where files
+    /// that already exist are left untouched
+    pub files: Vec<RemotePath>,
+}
+
+impl crate::core::SchemaInfo for CreatePathsArgs {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct ListDirContentsArgs {
-    pub path: String,
+    pub path: RemotePath,
+
+    /// If true, entries are sent back in successive chunks as the directory
+    /// is read rather than in a single reply; this is only honored when not
+    /// nested within a sequence or batch
+    #[serde(default)]
+    pub stream: bool,
 }
 
 impl crate::core::SchemaInfo for ListDirContentsArgs {}
 
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ResolvePathArgs {
+    pub path: RemotePath,
+}
+
+impl crate::core::SchemaInfo for ResolvePathArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct SniffFileArgs {
+    pub path: RemotePath,
+
+    /// If provided, the maximum bytes to read from the start of the file
+    /// instead of the server's default
+    pub max_bytes: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for SniffFileArgs {}
+
+/// Represents the algorithm used to compute the checksum of a file
+#[derive(
+    JsonSchema,
+    Serialize,
+    Deserialize,
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    #[serde(rename = "sha256")]
+    Sha256,
+
+    #[serde(rename = "sha512")]
+    Sha512,
+
+    /// CRC-32 as used by zip, gzip, and png, which only guards against
+    /// accidental corruption
+    #[serde(rename = "crc32")]
+    Crc32,
+}
+
+impl crate::core::SchemaInfo for ChecksumAlgorithm {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct GetFileChecksumArgs {
+    pub path: RemotePath,
+
+    /// Algorithm used to compute the checksum, defaulting to sha256
+    #[serde(default)]
+    pub algorithm: ChecksumAlgorithm,
+}
+
+impl crate::core::SchemaInfo for GetFileChecksumArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DiffFilesArgs {
+    /// Path to the original file
+    pub path_a: RemotePath,
+
+    /// Path to the changed file, or only its label in the diff if
+    /// `contents_b` is provided
+    pub path_b: RemotePath,
+
+    /// If provided, contents compared against the original file instead of
+    /// those of the file at `path_b`, such as a local copy held by the
+    /// client
+    #[serde(default)]
+    pub contents_b: Option<Vec<u8>>,
+
+    /// If provided, the number of unchanged lines included around each
+    /// change instead of the default of 3
+    #[serde(default)]
+    pub context_lines: Option<u32>,
+
+    /// If provided, the maximum size in bytes of either file instead of the
+    /// server's default, beyond which the files are not compared; at most
+    /// 16 MiB
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for DiffFilesArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct RecentFsEventsArgs {
+    /// Id of the last event already seen, where only events after it are
+    /// returned; all events still held by the server are returned if zero
+    #[serde(default)]
+    pub since: u64,
+}
+
+impl crate::core::SchemaInfo for RecentFsEventsArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ListMountsArgs {
+    /// If true, includes pseudo filesystems such as proc and sysfs, which
+    /// have no storage of their own and are skipped otherwise
+    #[serde(default)]
+    pub include_pseudo: bool,
+}
+
+impl crate::core::SchemaInfo for ListMountsArgs {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct OpenFileArgs {
-    pub path: String,
+    pub path: RemotePath,
     pub create_if_missing: bool,
     pub write_access: bool,
     pub read_access: bool,
+
+    /// If provided, unix permission bits to apply if the file is created
+    /// instead of the server's default
+    pub mode: Option<u32>,
+
+    /// How the file is opened beyond the access requested
+    #[serde(default)]
+    pub modes: FileOpenModes,
 }
 
 impl crate::core::SchemaInfo for OpenFileArgs {}
@@ -55,14 +207,51 @@ impl crate::core::SchemaInfo for OpenFileArgs {}
 impl From<String> for OpenFileArgs {
     fn from(path: String) -> Self {
         Self {
-            path,
+            path: path.into(),
             create_if_missing: true,
             write_access: true,
             read_access: true,
+            mode: None,
+            modes: FileOpenModes::default(),
         }
     }
 }
 
+/// Modes of opening a file that go beyond reading and writing, mapped onto
+/// those of the server's `OpenOptions`
+///
+/// Files opened with different `append` modes are separate open files on
+/// the server, even when they have the same path
+#[derive(
+    JsonSchema,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+)]
+pub struct FileOpenModes {
+    /// If true, each write adds its contents to the end of the file rather
+    /// than replacing the contents of the file
+    #[serde(default)]
+    pub append: bool,
+
+    /// If true, existing contents of the file are discarded when it is
+    /// opened, even if the file is already open
+    #[serde(default)]
+    pub truncate: bool,
+
+    /// If true, opening fails if the file already exists, where it is
+    /// created otherwise
+    #[serde(default)]
+    pub create_new: bool,
+}
+
+impl crate::core::SchemaInfo for FileOpenModes {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
@@ -77,8 +266,12 @@ impl crate::core::SchemaInfo for CloseFileArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct RenameUnopenedFileArgs {
-    pub from: String,
-    pub to: String,
+    pub from: RemotePath,
+    pub to: RemotePath,
+
+    /// If provided, conditions that must hold before the server will
+    /// perform the operation
+    pub preconditions: Option<FilePreconditions>,
 }
 
 impl crate::core::SchemaInfo for RenameUnopenedFileArgs {}
@@ -89,7 +282,11 @@ impl crate::core::SchemaInfo for RenameUnopenedFileArgs {}
 pub struct RenameFileArgs {
     pub id: u32,
     pub sig: u32,
-    pub to: String,
+    pub to: RemotePath,
+
+    /// If provided, conditions that must hold before the server will
+    /// perform the operation
+    pub preconditions: Option<FilePreconditions>,
 }
 
 impl crate::core::SchemaInfo for RenameFileArgs {}
@@ -98,7 +295,11 @@ impl crate::core::SchemaInfo for RenameFileArgs {}
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct RemoveUnopenedFileArgs {
-    pub path: String,
+    pub path: RemotePath,
+
+    /// If provided, conditions that must hold before the server will
+    /// perform the operation
+    pub preconditions: Option<FilePreconditions>,
 }
 
 impl crate::core::SchemaInfo for RemoveUnopenedFileArgs {}
@@ -109,6 +310,10 @@ impl crate::core::SchemaInfo for RemoveUnopenedFileArgs {}
 pub struct RemoveFileArgs {
     pub id: u32,
     pub sig: u32,
+
+    /// If provided, conditions that must hold before the server will
+    /// perform the operation
+    pub preconditions: Option<FilePreconditions>,
 }
 
 impl crate::core::SchemaInfo for RemoveFileArgs {}
@@ -119,10 +324,45 @@ impl crate::core::SchemaInfo for RemoveFileArgs {}
 pub struct ReadFileArgs {
     pub id: u32,
     pub sig: u32,
+
+    /// If provided, etag of the contents the client already has, where the
+    /// contents are omitted from the reply if the file still matches it
+    #[serde(default)]
+    pub if_none_match: Option<String>,
+
+    /// Position in bytes from the start of the file to begin reading at,
+    /// allowing large files to be read in chunks
+    #[serde(default)]
+    pub offset: u64,
+
+    /// If provided, the maximum bytes to read starting at the offset rather
+    /// than reading to the end of the file
+    #[serde(default)]
+    pub len: Option<u64>,
 }
 
 impl crate::core::SchemaInfo for ReadFileArgs {}
 
+impl ReadFileArgs {
+    /// Whether or not only part of the file is read
+    pub fn is_ranged(&self) -> bool {
+        self.offset > 0 || self.len.is_some()
+    }
+}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ReadFilesArgs {
+    pub paths: Vec<RemotePath>,
+
+    /// If provided, the maximum bytes to return across all files, where any
+    /// file that would exceed the limit is skipped rather than read
+    pub max_total_bytes: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for ReadFilesArgs {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
@@ -130,6 +370,166 @@ pub struct WriteFileArgs {
     pub id: u32,
     pub sig: u32,
     pub contents: Vec<u8>,
+
+    /// If provided, conditions that must hold before the server will
+    /// perform the operation
+    pub preconditions: Option<FilePreconditions>,
 }
 
 impl crate::core::SchemaInfo for WriteFileArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WriteFileAtomicByPathArgs {
+    pub path: RemotePath,
+    pub data: Vec<u8>,
+
+    /// If provided, unix permission bits to apply to the file once written
+    pub mode: Option<u32>,
+}
+
+impl crate::core::SchemaInfo for WriteFileAtomicByPathArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PatchFileLinesArgs {
+    pub path: RemotePath,
+
+    /// Edits to apply in order, where the lines of each edit refer to the
+    /// file after all prior edits have been applied
+    pub edits: Vec<LineEdit>,
+
+    /// If provided, hex-encoded SHA-256 hash that the file's contents must
+    /// have before the server will apply any edits
+    pub expected_hash: Option<String>,
+}
+
+impl crate::core::SchemaInfo for PatchFileLinesArgs {}
+
+/// Represents a change to a range of lines within a file, where lines are
+/// zero-indexed and ranges exclude the `end` line
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum LineEdit {
+    /// Inserts lines before `line`, or at the end of the file if `line` is
+    /// the total number of lines
+    #[serde(rename = "insert")]
+    Insert { line: u64, lines: Vec<String> },
+
+    /// Removes the lines from `start` up to `end`
+    #[serde(rename = "delete")]
+    Delete { start: u64, end: u64 },
+
+    /// Replaces the lines from `start` up to `end` with `lines`
+    #[serde(rename = "replace")]
+    Replace {
+        start: u64,
+        end: u64,
+        lines: Vec<String>,
+    },
+}
+
+impl crate::core::SchemaInfo for LineEdit {}
+
+/// Represents conditions that must hold for a file before the server will
+/// perform a mutating operation against it
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct FilePreconditions {
+    /// If provided, the file must currently be open with this signature,
+    /// meaning that this is only satisfiable for operations on open files
+    pub sig: Option<u32>,
+
+    /// If true, the destination of a rename (or the file itself for any
+    /// other operation) must not exist
+    #[serde(default)]
+    pub must_not_exist: bool,
+
+    /// If provided, the file must be at least this many bytes in size
+    pub min_size: Option<u64>,
+
+    /// If provided, the file must be at most this many bytes in size
+    pub max_size: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for FilePreconditions {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UploadManifestArgs {
+    pub files: Vec<ManifestFile>,
+}
+
+impl crate::core::SchemaInfo for UploadManifestArgs {}
+
+/// Describes a single file to be uploaded as part of a manifest
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ManifestFile {
+    /// Destination path of the file on the server
+    pub path: RemotePath,
+
+    /// Total size of the file in bytes
+    pub size: u64,
+
+    /// If provided, hex-encoded SHA-256 hash of the file's contents, used to
+    /// skip files that already exist on the server with the same contents
+    pub hash: Option<String>,
+
+    /// If provided, unix permission bits to apply to the file once created
+    pub mode: Option<u32>,
+
+    /// If provided, each chunk of the file's contents in order as cut by a
+    /// `Chunker`, where the file is then uploaded one chunk at a time and
+    /// chunks the server already holds are not sent
+    #[serde(default)]
+    pub chunks: Vec<ManifestChunk>,
+}
+
+impl crate::core::SchemaInfo for ManifestFile {}
+
+/// Describes a single chunk of a file to be uploaded in chunks, which starts
+/// where the chunk before it ends
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ManifestChunk {
+    /// Size of the chunk in bytes
+    pub len: u64,
+
+    /// Hex-encoded SHA-256 hash of the chunk's contents
+    pub hash: String,
+}
+
+impl crate::core::SchemaInfo for ManifestChunk {}
+
+impl From<crate::core::Chunk> for ManifestChunk {
+    fn from(chunk: crate::core::Chunk) -> Self {
+        Self {
+            len: chunk.len as u64,
+            hash: chunk.hash,
+        }
+    }
+}
+
+/// Writes a chunk of a file being uploaded in chunks, which must match the
+/// hash listed for it in the manifest
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct WriteUploadChunkArgs {
+    pub id: u32,
+    pub sig: u32,
+
+    /// Position of the chunk within the chunks of the file
+    pub index: u32,
+
+    pub data: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for WriteUploadChunkArgs {}
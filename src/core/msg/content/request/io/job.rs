@@ -0,0 +1,74 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct JobSpec {
+    pub command: String,
+    pub args: Vec<String>,
+
+    /// If provided, sets the current directory where the job will be executed
+    pub current_dir: Option<String>,
+}
+
+impl crate::core::SchemaInfo for JobSpec {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct SubmitJobArgs {
+    pub spec: JobSpec,
+}
+
+impl crate::core::SchemaInfo for SubmitJobArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct QueryJobArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for QueryJobArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct CollectJobOutputArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for CollectJobOutputArgs {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum ScheduleTrigger {
+    /// Runs repeatedly with the given number of seconds between runs
+    #[serde(rename = "interval")]
+    Interval { secs: u64 },
+
+    /// Runs whenever the time (in UTC) matches the five-field cron expression
+    /// (minute, hour, day of month, month, and day of week)
+    #[serde(rename = "cron")]
+    Cron { expr: String },
+}
+
+impl crate::core::SchemaInfo for ScheduleTrigger {}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CreateScheduleArgs {
+    pub spec: JobSpec,
+    pub trigger: ScheduleTrigger,
+}
+
+impl crate::core::SchemaInfo for CreateScheduleArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct DeleteScheduleArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for DeleteScheduleArgs {}
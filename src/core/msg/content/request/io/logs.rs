@@ -0,0 +1,34 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct AppendLogArgs {
+    /// Name of the log, which can only contain alphanumeric characters,
+    /// dashes, underscores, and dots
+    pub name: String,
+
+    /// Lines to append, none of which can contain a newline
+    pub lines: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for AppendLogArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ReadLogRangeArgs {
+    pub name: String,
+
+    /// Number of the first line to read, where lines are numbered from the
+    /// oldest line still kept by the server
+    #[serde(default)]
+    pub start: u64,
+
+    /// If provided, the maximum number of lines to read
+    #[serde(default)]
+    pub max_lines: Option<u64>,
+}
+
+impl crate::core::SchemaInfo for ReadLogRangeArgs {}
@@ -1,5 +1,9 @@
 mod fs;
+mod job;
+mod logs;
 mod proc;
 
 pub use fs::*;
+pub use job::*;
+pub use logs::*;
 pub use proc::*;
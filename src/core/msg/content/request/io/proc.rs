@@ -1,3 +1,4 @@
+use crate::core::request::PageArgs;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -13,10 +14,211 @@ pub struct ExecProcArgs {
 
     /// If provided, sets the current directory where the proc will be executed
     pub current_dir: Option<String>,
+
+    /// If provided, unix file mode creation mask applied to the proc instead
+    /// of inheriting the server's mask
+    pub umask: Option<u32>,
+
+    /// If true, the proc will continue running when the server stops
+    /// tracking it (such as when it has not been touched within its TTL)
+    /// rather than being killed
+    #[serde(default)]
+    pub detached: bool,
+
+    /// If provided, stdout is written to this file on the server instead of
+    /// being buffered for the client to read
+    #[serde(default)]
+    pub stdout_file: Option<OutputFile>,
+
+    /// If provided, stderr is written to this file on the server instead of
+    /// being buffered for the client to read
+    #[serde(default)]
+    pub stderr_file: Option<OutputFile>,
+
+    /// How stdout and stderr of the proc are split into the contents
+    /// returned when read
+    #[serde(default)]
+    pub io_mode: ProcIoMode,
+
+    /// If provided while in line mode, line endings of stdin written to the
+    /// proc and of stdout and stderr read from it are converted to this
+    #[serde(default)]
+    pub newline: Option<Newline>,
+
+    /// Labels attached to the proc, used to find it when listing procs
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// If provided, written to stdin of the proc as soon as it is spawned,
+    /// before any other request can write to it; stdin is piped even if not
+    /// requested
+    #[serde(default)]
+    pub initial_stdin: Option<Vec<u8>>,
+
+    /// If true, stdin of the proc is closed once any initial stdin is
+    /// written, so the proc sees the end of its input without another
+    /// request
+    #[serde(default)]
+    pub close_stdin: bool,
 }
 
 impl crate::core::SchemaInfo for ExecProcArgs {}
 
+/// Represents how output of a proc is framed by the server
+#[derive(
+    JsonSchema,
+    Serialize,
+    Deserialize,
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+)]
+pub enum ProcIoMode {
+    /// Output is returned as it was captured, with chunk boundaries depending
+    /// on how much the proc has written at the time of reading, which suits
+    /// binary output
+    #[default]
+    #[serde(rename = "raw")]
+    Raw,
+
+    /// Only complete lines are returned, holding back a partial line until
+    /// it is finished or the output is closed, which suits interactive and
+    /// line-based protocols
+    #[serde(rename = "line")]
+    Line,
+}
+
+impl crate::core::SchemaInfo for ProcIoMode {}
+
+/// Represents the line ending that text exchanged with a proc is converted to
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum Newline {
+    /// Line feed (\n)
+    #[serde(rename = "lf")]
+    Lf,
+
+    /// Carriage return followed by line feed (\r\n)
+    #[serde(rename = "crlf")]
+    Crlf,
+}
+
+impl crate::core::SchemaInfo for Newline {}
+
+impl Newline {
+    /// Converts every line ending (\n or \r\n) within `data` to this one,
+    /// leaving lone carriage returns untouched
+    pub fn normalize(self, data: &[u8]) -> Vec<u8> {
+        let mut normalized = Vec::with_capacity(data.len());
+        for (i, b) in data.iter().enumerate() {
+            match b {
+                b'\r' if data.get(i + 1) == Some(&b'\n') => {}
+                b'\n' => {
+                    if self == Self::Crlf {
+                        normalized.push(b'\r');
+                    }
+                    normalized.push(b'\n');
+                }
+                b => normalized.push(*b),
+            }
+        }
+        normalized
+    }
+}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct OutputFile {
+    /// Path to the file, which is created if missing and is relative to the
+    /// proc's current directory if not absolute
+    pub path: String,
+
+    /// If true, output is added to the end of the file rather than replacing
+    /// its contents
+    #[serde(default)]
+    pub append: bool,
+}
+
+impl crate::core::SchemaInfo for OutputFile {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ExecScriptArgs {
+    /// Program used to run the script, which receives the path to the
+    /// script as its first argument
+    pub interpreter: String,
+
+    /// Contents of the script
+    pub body: String,
+
+    /// Additional arguments passed after the path to the script
+    pub args: Vec<String>,
+    pub stdin: bool,
+    pub stdout: bool,
+    pub stderr: bool,
+
+    /// If provided, sets the current directory where the proc will be executed
+    pub current_dir: Option<String>,
+}
+
+impl crate::core::SchemaInfo for ExecScriptArgs {}
+
+/// Runs a proc attached to a pseudo-terminal on the server rather than to
+/// pipes, so that interactive programs such as editors and shells behave as
+/// they would in a terminal; everything the proc writes to the terminal is
+/// read as stdout
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ExecProcPtyArgs {
+    pub command: String,
+    pub args: Vec<String>,
+
+    /// If provided, sets the current directory where the proc will be executed
+    pub current_dir: Option<String>,
+
+    /// Rows of the terminal
+    pub rows: u16,
+
+    /// Columns of the terminal
+    pub cols: u16,
+
+    /// If provided, value of TERM for the proc instead of inheriting the
+    /// server's
+    #[serde(default)]
+    pub term: Option<String>,
+
+    /// If true, the proc will continue running when the server stops
+    /// tracking it rather than being killed
+    #[serde(default)]
+    pub detached: bool,
+
+    /// Labels attached to the proc, used to find it when listing procs
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl crate::core::SchemaInfo for ExecProcPtyArgs {}
+
+/// Changes the size of the pseudo-terminal of a proc started with
+/// `ExecProcPtyArgs`, which notifies the proc of the new size
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ResizePtyArgs {
+    pub id: u32,
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl crate::core::SchemaInfo for ResizePtyArgs {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
@@ -32,6 +234,10 @@ impl crate::core::SchemaInfo for WriteProcStdinArgs {}
 )]
 pub struct ReadProcStdoutArgs {
     pub id: u32,
+
+    /// Filters applied by the server to the output before returning it
+    #[serde(default)]
+    pub filter: ProcOutputFilter,
 }
 
 impl crate::core::SchemaInfo for ReadProcStdoutArgs {}
@@ -41,15 +247,49 @@ impl crate::core::SchemaInfo for ReadProcStdoutArgs {}
 )]
 pub struct ReadProcStderrArgs {
     pub id: u32,
+
+    /// Filters applied by the server to the output before returning it
+    #[serde(default)]
+    pub filter: ProcOutputFilter,
 }
 
 impl crate::core::SchemaInfo for ReadProcStderrArgs {}
 
+/// Filters on the output of a proc, letting a client page through or skip
+/// over output without it all being sent
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ProcOutputFilter {
+    /// If provided, offset within all output of the proc to skip ahead to,
+    /// discarding any output before it that has yet to be read
+    #[serde(default)]
+    pub offset: Option<u64>,
+
+    /// If provided, maximum bytes of output to consume, where the rest is
+    /// left to be read later; output is cut at the last line that fits when
+    /// reading lines
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+
+    /// If provided, regular expression that lines must match to be
+    /// returned, where other lines are consumed and discarded
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+impl crate::core::SchemaInfo for ProcOutputFilter {}
+
 #[derive(
     JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
 )]
 pub struct KillProcArgs {
     pub id: u32,
+
+    /// If true, any descendants of the proc (such as those forked by a
+    /// shell wrapper) are also killed
+    #[serde(default)]
+    pub tree: bool,
 }
 
 impl crate::core::SchemaInfo for KillProcArgs {}
@@ -62,3 +302,73 @@ pub struct ReadProcStatusArgs {
 }
 
 impl crate::core::SchemaInfo for ReadProcStatusArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ReadProcTreeArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for ReadProcTreeArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ReadProcResourcesArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for ReadProcResourcesArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ReadProcInfoArgs {
+    pub id: u32,
+}
+
+impl crate::core::SchemaInfo for ReadProcInfoArgs {}
+
+/// Status of the procs included when listing procs
+#[derive(
+    JsonSchema, Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq,
+)]
+pub enum ProcStatusFilter {
+    #[serde(rename = "running")]
+    Running,
+
+    #[serde(rename = "exited")]
+    Exited,
+}
+
+impl crate::core::SchemaInfo for ProcStatusFilter {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct ListProcsArgs {
+    /// If provided, only procs with this status are listed
+    #[serde(default)]
+    pub status: Option<ProcStatusFilter>,
+
+    /// If provided, only procs with this label are listed
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// If provided, only procs started at least this many seconds ago are
+    /// listed
+    #[serde(default)]
+    pub min_age_secs: Option<u64>,
+
+    /// If provided, only procs started at most this many seconds ago are
+    /// listed
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+
+    /// Page of procs to return, ordered from oldest to newest
+    #[serde(default)]
+    pub page: PageArgs,
+}
+
+impl crate::core::SchemaInfo for ListProcsArgs {}
@@ -0,0 +1,47 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Order in which the entries of a listing are returned
+#[derive(
+    JsonSchema,
+    Serialize,
+    Deserialize,
+    Default,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+)]
+pub enum SortOrder {
+    #[default]
+    #[serde(rename = "ascending")]
+    Ascending,
+
+    #[serde(rename = "descending")]
+    Descending,
+}
+
+impl crate::core::SchemaInfo for SortOrder {}
+
+/// Window of a listing to return once its entries have been filtered and
+/// sorted, where entries with equal sort keys keep a stable order so that
+/// consecutive pages neither repeat nor skip entries
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PageArgs {
+    /// Total entries to skip from the start of the listing
+    #[serde(default)]
+    pub offset: u64,
+
+    /// If provided, maximum entries to return, otherwise every entry after
+    /// the offset is returned
+    #[serde(default)]
+    pub limit: Option<u32>,
+
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl crate::core::SchemaInfo for PageArgs {}
@@ -1,21 +1,50 @@
+mod backup;
 mod batch;
 mod capabilities;
+mod config;
+mod connection;
 mod custom;
+mod diagnostics;
+mod eviction;
+#[cfg(feature = "fault-injection")]
+mod fault;
 mod forward;
-mod internal_debug;
+mod identity;
 mod io;
+mod listing;
+mod power;
 mod sequence;
+mod time;
+mod transaction;
 mod transform;
+mod unsupported;
+#[cfg(feature = "wasm")]
+mod wasm;
 
+pub use backup::*;
 pub use batch::*;
 pub use capabilities::*;
+pub use config::*;
+pub use connection::*;
 pub use custom::*;
+pub use diagnostics::*;
+pub use eviction::*;
+#[cfg(feature = "fault-injection")]
+pub use fault::*;
 pub use forward::*;
-pub use internal_debug::*;
+pub use identity::*;
 pub use io::*;
+pub use listing::*;
+pub use power::*;
 pub use sequence::*;
+pub use time::*;
+pub use transaction::*;
 pub use transform::*;
+pub use unsupported::*;
+#[cfg(feature = "wasm")]
+pub use wasm::*;
 
+use super::constraints::Validate;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -46,6 +75,68 @@ pub enum Request {
     #[allow(dead_code)]
     Capabilities,
 
+    // ------------------------------------------------------------------------
+    // Time on the clock of the remote instance, used to estimate how far it
+    // has drifted from the local clock
+    #[serde(rename = "time_info_request")]
+    TimeInfo(TimeInfoArgs),
+
+    // ------------------------------------------------------------------------
+    // Identity of the remote instance, proven using a key pinned by clients
+    /// This will be sent to challenge the server to prove that it holds its
+    /// identity key
+    #[serde(rename = "identify_request")]
+    Identify(IdentifyArgs),
+
+    /// This will be sent to change which client keys the server trusts,
+    /// yielding the keys trusted afterward
+    #[serde(rename = "update_trusted_clients_request")]
+    UpdateTrustedClients(UpdateTrustedClientsArgs),
+
+    /// This will be sent to negotiate a key for the session of the client
+    /// through an exchange of ephemeral public keys
+    #[serde(rename = "handshake_request")]
+    Handshake(HandshakeArgs),
+
+    /// This will be sent in place of any other request once the client has
+    /// negotiated a key for its session, wrapping the msg of that request
+    #[serde(rename = "sealed_request")]
+    Sealed(SealedArgs),
+
+    // ------------------------------------------------------------------------
+    // Configuration distributed by operators through the remote instance
+    /// This will be sent to store an opaque configuration document on the
+    /// server, replacing any older version
+    #[serde(rename = "push_config_request")]
+    PushConfig(PushConfigArgs),
+
+    /// This will be sent to request the configuration document last pushed
+    /// to the server
+    #[serde(rename = "get_config_request")]
+    GetConfig,
+
+    /// This will be sent to add or remove the webhooks that the server sends
+    /// events to, yielding the webhooks afterward
+    #[serde(rename = "update_webhooks_request")]
+    UpdateWebhooks(UpdateWebhooksArgs),
+
+    // ------------------------------------------------------------------------
+    // Administration of the host running the remote instance
+    /// This will be sent to reboot or shut down the host, first to request
+    /// a token for the action and then to confirm it with that token
+    #[serde(rename = "power_control_request")]
+    PowerControl(PowerControlArgs),
+
+    /// This will be sent to archive the durable metadata of the server, such
+    /// as its jobs and schedules, to migrate it to another host
+    #[serde(rename = "export_state_request")]
+    ExportState(ExportStateArgs),
+
+    /// This will be sent to add the metadata of an archive exported from
+    /// another server to that of the server
+    #[serde(rename = "import_state_request")]
+    ImportState(ImportStateArgs),
+
     // ------------------------------------------------------------------------
     // Dir-based operations such as creating and listing entries
     /// This will be sent to indicate the desire to create a new directory
@@ -60,11 +151,45 @@ pub enum Request {
     #[serde(rename = "remove_dir_request")]
     RemoveDir(RemoveDirArgs),
 
+    /// This will be sent to create many dirs and empty files at once
+    #[serde(rename = "create_paths_request")]
+    CreatePaths(CreatePathsArgs),
+
     /// This will be sent to indicate the desire to list all files/directories
     /// at the provided path
     #[serde(rename = "list_dir_contents_request")]
     ListDirContents(ListDirContentsArgs),
 
+    /// This will be sent to canonicalize a path and probe whether it exists
+    /// without needing to open or list it
+    #[serde(rename = "resolve_path_request")]
+    ResolvePath(ResolvePathArgs),
+
+    /// This will be sent to detect the type of a file from its leading bytes
+    /// without needing to download its contents
+    #[serde(rename = "sniff_file_request")]
+    SniffFile(SniffFileArgs),
+
+    /// This will be sent to compute the checksum of a file, letting the
+    /// client verify it without needing to download its contents
+    #[serde(rename = "get_file_checksum_request")]
+    GetFileChecksum(GetFileChecksumArgs),
+
+    /// This will be sent to compare two files (or a file and provided
+    /// contents) without needing to download either of them
+    #[serde(rename = "diff_files_request")]
+    DiffFiles(DiffFilesArgs),
+
+    /// This will be sent to catch up on the changes the server made to the
+    /// filesystem, such as after reconnecting
+    #[serde(rename = "recent_fs_events_request")]
+    RecentFsEvents(RecentFsEventsArgs),
+
+    /// This will be sent to list the filesystems mounted on the server (or
+    /// its drives on Windows) as starting points for navigation
+    #[serde(rename = "list_mounts_request")]
+    ListMounts(ListMountsArgs),
+
     // ------------------------------------------------------------------------
     // File-based operations such as reading and writing
     /// This will be sent to indicate the desire to read/write a file,
@@ -96,16 +221,55 @@ pub enum Request {
     #[serde(rename = "read_file_request")]
     ReadFile(ReadFileArgs),
 
+    /// This will be sent to read the contents of many unopened files at once
+    #[serde(rename = "read_files_request")]
+    ReadFiles(ReadFilesArgs),
+
     /// This will be sent to indicate the desire to write a file's contents
     #[serde(rename = "write_file_request")]
     WriteFile(WriteFileArgs),
 
+    /// This will be sent to open (creating if missing), write, and close a
+    /// file by its path in a single operation
+    #[serde(rename = "write_file_atomic_by_path_request")]
+    WriteFileAtomicByPath(WriteFileAtomicByPathArgs),
+
+    /// This will be sent to insert, delete, or replace ranges of lines within
+    /// a file by its path, applying all edits or none of them
+    #[serde(rename = "patch_file_lines_request")]
+    PatchFileLines(PatchFileLinesArgs),
+
+    /// This will be sent to prepare many files for upload at once, creating
+    /// any missing parent directories and opening each file for writing
+    #[serde(rename = "upload_manifest_request")]
+    UploadManifest(UploadManifestArgs),
+
+    /// This will be sent to write a chunk of a file being uploaded in
+    /// chunks, which is only needed for chunks the server does not have
+    #[serde(rename = "write_upload_chunk_request")]
+    WriteUploadChunk(WriteUploadChunkArgs),
+
     // ------------------------------------------------------------------------
     // Program execution operations such as running and streaming
     /// This will be sent to execute a remote proccess on the server
     #[serde(rename = "exec_proc_request")]
     ExecProc(ExecProcArgs),
 
+    /// This will be sent to run a script on the server with an interpreter,
+    /// where the server manages a temporary file containing the script
+    #[serde(rename = "exec_script_request")]
+    ExecScript(ExecScriptArgs),
+
+    /// This will be sent to execute a remote process on the server that is
+    /// attached to a pseudo-terminal rather than to pipes
+    #[serde(rename = "exec_proc_pty_request")]
+    ExecProcPty(ExecProcPtyArgs),
+
+    /// This will be sent to change the size of the pseudo-terminal of a
+    /// remote process on the server
+    #[serde(rename = "resize_pty_request")]
+    ResizePty(ResizePtyArgs),
+
     /// This will be sent to feed input to a remote process on the server, if
     /// enabled when first executing
     #[serde(rename = "write_proc_stdin_request")]
@@ -130,6 +294,67 @@ pub enum Request {
     #[serde(rename = "read_proc_status_request")]
     ReadProcStatus(ReadProcStatusArgs),
 
+    /// This will be sent to request a running process on the server along
+    /// with any children it has forked
+    #[serde(rename = "read_proc_tree_request")]
+    ReadProcTree(ReadProcTreeArgs),
+
+    /// This will be sent to request the CPU and memory used by a running
+    /// process on the server
+    #[serde(rename = "read_proc_resources_request")]
+    ReadProcResources(ReadProcResourcesArgs),
+
+    /// This will be sent to request how a process on the server was spawned,
+    /// including the environment and directory it was started with
+    #[serde(rename = "read_proc_info_request")]
+    ReadProcInfo(ReadProcInfoArgs),
+
+    /// This will be sent to request a page of the procs tracked by the
+    /// server, filtered by status, label, and age
+    #[serde(rename = "list_procs_request")]
+    ListProcs(ListProcsArgs),
+
+    // ------------------------------------------------------------------------
+    // Job-based operations whose results persist beyond a connection
+    /// This will be sent to run a process on the server whose output and
+    /// outcome are recorded so they can be retrieved later
+    #[serde(rename = "submit_job_request")]
+    SubmitJob(SubmitJobArgs),
+
+    /// This will be sent to request the status of a previously-submitted job
+    #[serde(rename = "query_job_request")]
+    QueryJob(QueryJobArgs),
+
+    /// This will be sent to request all output captured for a
+    /// previously-submitted job
+    #[serde(rename = "collect_job_output_request")]
+    CollectJobOutput(CollectJobOutputArgs),
+
+    /// This will be sent to submit a job on a recurring basis, either at a
+    /// fixed interval or whenever a cron expression matches
+    #[serde(rename = "create_schedule_request")]
+    CreateSchedule(CreateScheduleArgs),
+
+    /// This will be sent to request all schedules along with the recent jobs
+    /// each has submitted
+    #[serde(rename = "list_schedules_request")]
+    ListSchedules,
+
+    /// This will be sent to stop a schedule from submitting further jobs
+    #[serde(rename = "delete_schedule_request")]
+    DeleteSchedule(DeleteScheduleArgs),
+
+    // ------------------------------------------------------------------------
+    // Logs collected from clients into files managed by the server
+    /// This will be sent to append lines to a log on the server, which are
+    /// never interleaved with the lines of other appends
+    #[serde(rename = "append_log_request")]
+    AppendLog(AppendLogArgs),
+
+    /// This will be sent to request a range of lines of a log on the server
+    #[serde(rename = "read_log_range_request")]
+    ReadLogRange(ReadLogRangeArgs),
+
     // ------------------------------------------------------------------------
     // Miscellaneous, adhoc messages
     /// This will be sent to execute a collection of operations sequentially
@@ -140,6 +365,11 @@ pub enum Request {
     #[serde(rename = "batch_request")]
     Batch(BatchArgs),
 
+    /// This will be sent to execute a collection of fs mutations
+    /// sequentially, undoing all of them if any fails
+    #[serde(rename = "transaction_request")]
+    Transaction(TransactionArgs),
+
     /// This will be sent to either the client or server and the msg will be
     /// passed along to the associated address (if possible)
     #[serde(rename = "forward_request")]
@@ -150,12 +380,221 @@ pub enum Request {
     #[serde(rename = "custom_request")]
     Custom(CustomArgs),
 
-    /// For debugging purposes when needing to query the state of client/server
-    #[serde(rename = "internal_debug_request")]
-    InternalDebug(InternalDebugArgs),
+    /// This will be sent to request structured diagnostics of the state of
+    /// the remote instance, limited to the requested sections
+    #[serde(rename = "diagnostics_request")]
+    Diagnostics(DiagnosticsArgs),
+
+    /// This will be sent to request a page of the clients that have
+    /// communicated with the server, filtered by origin and idle time
+    #[serde(rename = "list_connections_request")]
+    ListConnections(ListConnectionsArgs),
+
+    /// This will be sent to request the files, procs, and sessions that the
+    /// server will evict once they go untouched, optionally touching some
+    /// of them to keep them alive
+    #[serde(rename = "list_pending_evictions_request")]
+    ListPendingEvictions(ListPendingEvictionsArgs),
+
+    /// This will be sent over udp to prove that the client receives replies
+    /// sent to its origin, first to request a nonce from the server and then
+    /// to echo that nonce back
+    #[serde(rename = "bind_origin_request")]
+    BindOrigin(BindOriginArgs),
+
+    /// This will be produced when receiving a request that cannot be decoded,
+    /// such as one of a type introduced by a newer version, and is never sent
+    #[serde(skip)]
+    Unsupported(UnsupportedArgs),
+
+    // ------------------------------------------------------------------------
+    // Fault injection used to exercise error paths when testing
+    /// This will be sent to force requests of a specific type to fail, be
+    /// delayed, or panic when handled by the server
+    #[cfg(feature = "fault-injection")]
+    #[serde(rename = "inject_fault_request")]
+    InjectFault(InjectFaultArgs),
+
+    /// This will be sent to remove all faults injected into the server
+    #[cfg(feature = "fault-injection")]
+    #[serde(rename = "clear_faults_request")]
+    ClearFaults,
+
+    // ------------------------------------------------------------------------
+    // Wasm modules that serve custom requests within a sandbox
+    /// This will be sent to upload a wasm module that serves the custom
+    /// requests of a namespace
+    #[cfg(feature = "wasm")]
+    #[serde(rename = "load_wasm_handler_request")]
+    LoadWasmHandler(LoadWasmHandlerArgs),
+
+    /// This will be sent to remove the wasm module serving a namespace
+    #[cfg(feature = "wasm")]
+    #[serde(rename = "unload_wasm_handler_request")]
+    UnloadWasmHandler(UnloadWasmHandlerArgs),
 }
 
 impl Request {
+    /// Total operations nested within the request at any depth, such as the
+    /// operations of a sequence or batch, excluding the request itself
+    pub fn nested_operation_count(&self) -> usize {
+        match self {
+            Self::Sequence(args) => args
+                .operations
+                .iter()
+                .map(|op| 1 + op.raw_request.nested_operation_count())
+                .sum(),
+            Self::Batch(args) => args
+                .operations
+                .iter()
+                .map(|op| 1 + op.nested_operation_count())
+                .sum(),
+            Self::Transaction(args) => args
+                .operations
+                .iter()
+                .map(|op| 1 + op.nested_operation_count())
+                .sum(),
+            // NOTE: Operations within a forwarded request are counted by the
+            //       server it is forwarded to
+            Self::Forward(_) => 1,
+            _ => 0,
+        }
+    }
+
+    /// Broad class of the request, used to group requests when accounting
+    /// for the resources they consume
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::Heartbeat
+            | Self::Version
+            | Self::Capabilities
+            | Self::TimeInfo(_)
+            | Self::Identify(_)
+            | Self::Diagnostics(_)
+            | Self::ListConnections(_)
+            | Self::ListPendingEvictions(_)
+            | Self::BindOrigin(_)
+            | Self::Handshake(_)
+            | Self::Sealed(_) => "meta",
+            Self::PushConfig(_)
+            | Self::GetConfig
+            | Self::UpdateTrustedClients(_)
+            | Self::UpdateWebhooks(_) => "config",
+            Self::CreateDir(_)
+            | Self::RenameDir(_)
+            | Self::RemoveDir(_)
+            | Self::CreatePaths(_)
+            | Self::ListDirContents(_)
+            | Self::ResolvePath(_)
+            | Self::SniffFile(_)
+            | Self::GetFileChecksum(_)
+            | Self::DiffFiles(_)
+            | Self::RecentFsEvents(_)
+            | Self::ListMounts(_) => "dir",
+            Self::OpenFile(_)
+            | Self::CloseFile(_)
+            | Self::RenameUnopenedFile(_)
+            | Self::RenameFile(_)
+            | Self::RemoveUnopenedFile(_)
+            | Self::RemoveFile(_)
+            | Self::ReadFile(_)
+            | Self::ReadFiles(_)
+            | Self::WriteFile(_)
+            | Self::WriteFileAtomicByPath(_)
+            | Self::PatchFileLines(_)
+            | Self::UploadManifest(_)
+            | Self::WriteUploadChunk(_) => "file",
+            Self::ExecProc(_)
+            | Self::ExecScript(_)
+            | Self::ExecProcPty(_)
+            | Self::ResizePty(_)
+            | Self::WriteProcStdin(_)
+            | Self::ReadProcStdout(_)
+            | Self::ReadProcStderr(_)
+            | Self::KillProc(_)
+            | Self::ReadProcStatus(_)
+            | Self::ReadProcTree(_)
+            | Self::ReadProcResources(_)
+            | Self::ReadProcInfo(_)
+            | Self::ListProcs(_) => "proc",
+            Self::SubmitJob(_)
+            | Self::QueryJob(_)
+            | Self::CollectJobOutput(_)
+            | Self::CreateSchedule(_)
+            | Self::ListSchedules
+            | Self::DeleteSchedule(_) => "job",
+            Self::AppendLog(_) | Self::ReadLogRange(_) => "log",
+            Self::PowerControl(_)
+            | Self::ExportState(_)
+            | Self::ImportState(_) => "admin",
+            Self::Sequence(_)
+            | Self::Batch(_)
+            | Self::Transaction(_)
+            | Self::Forward(_) => "composite",
+            Self::Custom(_) => "custom",
+            Self::Unsupported(_) => "unsupported",
+            #[cfg(feature = "fault-injection")]
+            Self::InjectFault(_) | Self::ClearFaults => "fault",
+            #[cfg(feature = "wasm")]
+            Self::LoadWasmHandler(_) | Self::UnloadWasmHandler(_) => "custom",
+        }
+    }
+
+    /// Whether the request can be sent again, even to another server,
+    /// without changing anything; requests that modify the server or refer
+    /// to files and procs opened on a specific server are never idempotent
+    pub fn is_idempotent(&self) -> bool {
+        match self {
+            Self::Heartbeat
+            | Self::Version
+            | Self::Capabilities
+            | Self::TimeInfo(_)
+            | Self::Identify(_)
+            | Self::Diagnostics(_)
+            | Self::ListConnections(_)
+            | Self::GetConfig
+            | Self::ListDirContents(_)
+            | Self::ResolvePath(_)
+            | Self::SniffFile(_)
+            | Self::GetFileChecksum(_)
+            | Self::DiffFiles(_)
+            | Self::RecentFsEvents(_)
+            | Self::ListMounts(_)
+            | Self::ReadFiles(_)
+            | Self::ListProcs(_)
+            | Self::ListSchedules
+            | Self::ReadLogRange(_)
+            | Self::ExportState(_) => true,
+            Self::Sequence(args) => args
+                .operations
+                .iter()
+                .all(|op| op.raw_request.is_idempotent()),
+            Self::Batch(args) => {
+                args.operations.iter().all(Request::is_idempotent)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that the fields of the request are within the bounds emitted
+    /// into its schema, not counting any requests nested within it
+    pub fn validate(&self) -> std::io::Result<()> {
+        match self {
+            Self::CreateDir(args) => args.validate(),
+            Self::SniffFile(args) => args.validate(),
+            Self::DiffFiles(args) => args.validate(),
+            Self::OpenFile(args) => args.validate(),
+            Self::WriteFileAtomicByPath(args) => args.validate(),
+            Self::UploadManifest(args) => args.validate(),
+            Self::ExecProc(args) => args.validate(),
+            Self::ExecProcPty(args) => args.validate(),
+            Self::ResizePty(args) => args.validate(),
+            Self::CreateSchedule(args) => args.validate(),
+            Self::PowerControl(args) => args.validate(),
+            _ => Ok(()),
+        }
+    }
+
     /// Converts a request into a lazily transformed request using the
     /// provided rules as transformation specifications
     pub fn into_lazily_transformed(
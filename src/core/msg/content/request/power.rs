@@ -0,0 +1,47 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct PowerControlArgs {
+    /// What to do to the host running the server
+    pub action: PowerAction,
+
+    /// Seconds to wait once confirmed before performing the action
+    #[serde(default)]
+    pub delay_secs: u64,
+
+    /// Token yielded by the server when the action was first requested,
+    /// which confirms the action; the action is only ever performed once
+    /// confirmed with the same action and delay that were requested
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl crate::core::SchemaInfo for PowerControlArgs {}
+
+/// Action performed on the host running the server
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+#[serde(tag = "type")]
+pub enum PowerAction {
+    /// Restarts the host
+    #[default]
+    #[serde(rename = "reboot")]
+    Reboot,
+
+    /// Powers off the host
+    #[serde(rename = "shutdown")]
+    Shutdown,
+
+    /// Runs a command of the operator's choosing, such as one that restarts
+    /// the host in a way specific to its platform
+    #[serde(rename = "custom")]
+    Custom {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
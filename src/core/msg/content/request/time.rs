@@ -0,0 +1,13 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct TimeInfoArgs {
+    /// Microseconds since the unix epoch on the clock of the client when it
+    /// sent the request, echoed back by the server
+    pub sent_at_micros: u64,
+}
+
+impl crate::core::SchemaInfo for TimeInfoArgs {}
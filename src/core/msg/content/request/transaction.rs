@@ -0,0 +1,24 @@
+use super::Request;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Represents arguments to a request for a sequence of fs mutations that are
+/// either all applied or, should any of them fail, all undone
+///
+/// Only mutations of paths are supported, namely creating, renaming, and
+/// removing dirs, renaming and removing unopened files, and writing or
+/// patching files by path.
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct TransactionArgs {
+    pub operations: Vec<Request>,
+}
+
+impl crate::core::SchemaInfo for TransactionArgs {}
+
+impl From<Vec<Request>> for TransactionArgs {
+    fn from(operations: Vec<Request>) -> Self {
+        Self { operations }
+    }
+}
@@ -68,7 +68,7 @@ impl LazilyTransformedRequest {
                     path: rule.value.clone(),
                 });
             }
-            let new_value = new_values.drain(0..=0).last();
+            let new_value = new_values.drain(0..=0).next_back();
 
             value = jsonpath::replace_with(value, &rule.path, &mut |_| {
                 new_value.cloned()
@@ -0,0 +1,25 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct LoadWasmHandlerArgs {
+    /// Namespace of the custom requests that the module will serve
+    pub namespace: String,
+
+    /// WebAssembly module in binary or text format
+    pub module: Vec<u8>,
+}
+
+impl crate::core::SchemaInfo for LoadWasmHandlerArgs {}
+
+#[derive(
+    JsonSchema, Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq,
+)]
+pub struct UnloadWasmHandlerArgs {
+    /// Namespace of the custom requests that the module serves
+    pub namespace: String,
+}
+
+impl crate::core::SchemaInfo for UnloadWasmHandlerArgs {}
@@ -1,32 +1,142 @@
+mod cbor;
+pub mod compression;
 pub mod content;
 
+use crate::core::transport::{
+    auth::identity::{self, IdentityKey},
+    constants::DEFAULT_MAX_MSG_SIZE,
+};
 use chrono::prelude::{DateTime, Utc};
-use content::{Content, Reply, Request};
+use content::{reply, request, Content, Reply, Request};
 use derive_more::{Display, Error};
 use rand::random;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[derive(Debug, Display, Error)]
 pub enum MsgError {
     AssembleMsg(serde_cbor::Error),
     DisassembleMsg(serde_cbor::Error),
+
+    /// A field unknown to this version was found while decoding strictly
+    #[display(fmt = "Unknown field {}", _0)]
+    UnknownField(#[error(not(source))] String),
+
+    /// The signature of a msg does not match its header and content
+    InvalidSignature,
+
+    /// A compressed msg could not be decompressed
+    #[display(fmt = "{}", _0)]
+    DecompressMsg(#[error(not(source))] String),
+}
+
+/// Whether msgs are decoded strictly, see `set_strict_decoding`
+static STRICT_DECODING: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether msgs are decoded strictly, where content of an unknown type
+/// or with fields unknown to this version fails to decode instead of being
+/// tolerated, which helps tests catch incompatible changes to the protocol
+pub fn set_strict_decoding(strict: bool) {
+    STRICT_DECODING.store(strict, Ordering::Relaxed);
+}
+
+/// Whether msgs are decoded strictly, see `set_strict_decoding`
+pub fn is_strict_decoding() -> bool {
+    STRICT_DECODING.load(Ordering::Relaxed)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Header {
     /// ID associated with a request or reply
     pub id: u32,
 
     /// The time at which the message was created
+    #[schemars(with = "String")]
     pub creation_date: DateTime<Utc>,
+
+    /// If provided, ID shared by every message of a workflow that spans
+    /// multiple requests or hops, used to correlate them when observing
+    #[serde(default)]
+    pub trace_id: Option<String>,
+
+    /// If provided, ID of the operation on the sender's side that caused
+    /// this message to be sent
+    #[serde(default)]
+    pub parent_span_id: Option<String>,
+
+    /// Options of this message that alter how it is sent and processed
+    #[serde(default, skip_serializing_if = "MsgFlags::is_empty")]
+    pub flags: MsgFlags,
+
+    /// If provided, microseconds that the sender spent processing the
+    /// message that this one replies to, letting the recipient tell the
+    /// time spent by the sender apart from the time spent on the network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processing_micros: Option<u64>,
+}
+
+/// Options of a single message, set by its sender
+#[derive(
+    JsonSchema,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+)]
+pub struct MsgFlags {
+    /// Whether the message is sent as is rather than compressed, such as
+    /// when its content is already compressed
+    #[serde(default)]
+    pub no_compression: bool,
+
+    /// Whether the sender of the message accepts a compressed reply
+    #[serde(default)]
+    pub accepts_compression: bool,
+
+    /// Whether the recipient must reject the message unless it carries a
+    /// valid signature, regardless of the signature policy of the recipient
+    #[serde(default)]
+    pub require_signature: bool,
+}
+
+impl MsgFlags {
+    /// Whether no option is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 impl Header {
     /// Creates a new Header with the provided ID
     pub fn with_id(id: u32) -> Self {
-        let mut header = Header::default();
-        header.id = id;
-        header
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the tracing IDs of this header
+    pub fn with_trace(
+        &mut self,
+        trace_id: Option<String>,
+        parent_span_id: Option<String>,
+    ) -> &mut Self {
+        self.trace_id = trace_id;
+        self.parent_span_id = parent_span_id;
+        self
+    }
+
+    /// Sets the tracing IDs of this header to those of the provided header,
+    /// placing a message caused by another within the same trace
+    pub fn inherit_trace(&mut self, header: &Header) -> &mut Self {
+        self.with_trace(header.trace_id.clone(), header.parent_span_id.clone())
     }
 }
 
@@ -36,11 +146,27 @@ impl Default for Header {
         Self {
             id: random(),
             creation_date: Utc::now(),
+            trace_id: None,
+            parent_span_id: None,
+            flags: MsgFlags::default(),
+            processing_micros: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+/// Signature made with an identity key over the header, parent header, and
+/// content of a msg, which unlike the signature of each packet survives the
+/// msg being relayed by other hops
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MsgSignature {
+    /// Public half of the identity key that signed the msg
+    pub public_key: Vec<u8>,
+
+    /// Signature of the msg made with the secret half of the identity key
+    pub signature: Vec<u8>,
+}
+
+#[derive(JsonSchema, Serialize, Deserialize, Clone, Debug)]
 pub struct Msg {
     /// Information associated with this message
     pub header: Header,
@@ -51,14 +177,46 @@ pub struct Msg {
 
     /// Content within the message
     pub content: Content,
+
+    /// If provided, signature proving who produced this message, which
+    /// is independent of any hop the message passes through
+    #[serde(default)]
+    pub signature: Option<MsgSignature>,
+
+    /// Bytes covered by the signature exactly as they were received, which
+    /// include any fields unknown to this version that are dropped when
+    /// decoding
+    #[serde(skip)]
+    signed: Option<Vec<u8>>,
+}
+
+/// Msgs are equal if what they contain is, regardless of how they were
+/// received
+impl PartialEq for Msg {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header
+            && self.parent_header == other.parent_header
+            && self.content == other.content
+            && self.signature == other.signature
+    }
 }
 
+impl Eq for Msg {}
+
 impl Msg {
+    /// Creates a new msg, which belongs to the same trace as its parent
     pub fn new(content: Content, parent_header: Option<Header>) -> Self {
+        let mut header = Header::default();
+        if let Some(parent_header) = parent_header.as_ref() {
+            header.inherit_trace(parent_header);
+        }
+
         Self {
-            header: Header::default(),
+            header,
             parent_header,
             content,
+            signature: None,
+            signed: None,
         }
     }
 
@@ -67,12 +225,208 @@ impl Msg {
         serde_cbor::ser::to_vec(&self).map_err(MsgError::AssembleMsg)
     }
 
+    /// Encodes the msg, compressing it if it is at least `threshold` bytes
+    /// and its flags do not rule out compression
+    pub fn to_vec_compressed(
+        &self,
+        threshold: usize,
+    ) -> Result<Vec<u8>, MsgError> {
+        let data = self.to_vec()?;
+        if self.header.flags.no_compression || data.len() < threshold {
+            return Ok(data);
+        }
+
+        Ok(compression::compress(&data).unwrap_or(data))
+    }
+
+    /// Size in bytes of this msg once encoded, calculated without keeping
+    /// the encoded msg around
+    pub fn encoded_len(&self) -> Result<usize, MsgError> {
+        struct Counter(usize);
+
+        impl std::io::Write for Counter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0 += buf.len();
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut counter = Counter(0);
+        serde_cbor::to_writer(&mut counter, &self)
+            .map_err(MsgError::AssembleMsg)?;
+        Ok(counter.0)
+    }
+
+    /// Decodes a msg, where content from other versions is tolerated unless
+    /// decoding strictly (see `set_strict_decoding`)
+    ///
+    /// Unknown fields are ignored and content of an unknown type is replaced
+    /// with unsupported content, so that peers of mixed versions can still
+    /// reply to each other
+    ///
+    /// Compressed msgs are decompressed first, up to the default maximum
+    /// size of a msg
     pub fn from_slice(slice: &[u8]) -> Result<Self, MsgError> {
-        serde_cbor::from_slice(slice).map_err(MsgError::DisassembleMsg)
+        if compression::is_compressed(slice) {
+            let data = compression::decompress(slice, DEFAULT_MAX_MSG_SIZE)
+                .map_err(MsgError::DecompressMsg)?;
+            return Self::decode_signed(&data, is_strict_decoding());
+        }
+
+        Self::decode_signed(slice, is_strict_decoding())
+    }
+
+    /// Decodes a msg, keeping the bytes covered by its signature as they
+    /// appear in `slice` if it is signed
+    fn decode_signed(slice: &[u8], strict: bool) -> Result<Self, MsgError> {
+        let mut msg = Self::decode(slice, strict)?;
+        if msg.signature.is_some() {
+            msg.signed = received_signed_bytes(slice);
+        }
+        Ok(msg)
     }
 
-    /// Sets the parent header of this msg with that of the provided header
+    fn decode(slice: &[u8], strict: bool) -> Result<Self, MsgError> {
+        if !strict {
+            return serde_cbor::from_slice(slice).or_else(|x| {
+                Self::decode_unsupported(slice)
+                    .ok_or(MsgError::DisassembleMsg(x))
+            });
+        }
+
+        // Any field dropped while decoding is unknown, so it will be missing
+        // once the msg is encoded again
+        let raw: Value =
+            serde_cbor::from_slice(slice).map_err(MsgError::DisassembleMsg)?;
+        let msg: Self = serde_cbor::value::from_value(raw.clone())
+            .map_err(MsgError::DisassembleMsg)?;
+        let known =
+            serde_cbor::value::to_value(&msg).map_err(MsgError::AssembleMsg)?;
+
+        match find_unknown_field(&raw, &known, "") {
+            Some(path) => Err(MsgError::UnknownField(path)),
+            None => Ok(msg),
+        }
+    }
+
+    /// Decodes a msg whose content cannot be decoded, such as content of a
+    /// type introduced by a newer version, replacing it with content that
+    /// reports it as unsupported
+    fn decode_unsupported(slice: &[u8]) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct RawMsg {
+            header: Header,
+            parent_header: Option<Header>,
+            content: Value,
+            #[serde(default)]
+            signature: Option<MsgSignature>,
+        }
+
+        let raw: RawMsg = serde_cbor::from_slice(slice).ok()?;
+        let type_name = match &raw.content {
+            Value::Map(map) => {
+                match map.get(&Value::Text(String::from("type"))) {
+                    Some(Value::Text(x)) => x.clone(),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        // Avoid the description of an unknown type, which lists every type
+        let description = |x: serde_cbor::Error| {
+            if x.to_string().starts_with("unknown variant") {
+                format!("Unknown type {}", type_name)
+            } else {
+                x.to_string()
+            }
+        };
+
+        let content = if type_name.ends_with("_request") {
+            let description =
+                serde_cbor::value::from_value::<Request>(raw.content)
+                    .err()
+                    .map(description)?;
+            Content::Request(Request::Unsupported(request::UnsupportedArgs {
+                type_name,
+                description,
+            }))
+        } else if type_name.ends_with("_reply") {
+            let description =
+                serde_cbor::value::from_value::<Reply>(raw.content)
+                    .err()
+                    .map(description)?;
+            Content::Reply(Reply::Unsupported(reply::UnsupportedArgs {
+                type_name,
+                description,
+            }))
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            header: raw.header,
+            parent_header: raw.parent_header,
+            content,
+            signature: raw.signature,
+            signed: None,
+        })
+    }
+
+    /// Signs the header, parent header, and content of this msg with `key`,
+    /// which must happen after any of them are last changed
+    pub fn sign(&mut self, key: &IdentityKey) -> Result<&mut Self, MsgError> {
+        self.signed = None;
+        let signature = key.sign(&self.signed_bytes()?);
+        self.signature = Some(MsgSignature {
+            public_key: key.public_key().to_vec(),
+            signature,
+        });
+        Ok(self)
+    }
+
+    /// Verifies the signature of this msg, yielding the public key of the
+    /// signer or none if the msg is not signed
+    pub fn verify_signature(&self) -> Result<Option<&[u8]>, MsgError> {
+        let signature = match self.signature.as_ref() {
+            Some(signature) => signature,
+            None => return Ok(None),
+        };
+
+        let data = self.signed_bytes()?;
+        if identity::verify(&signature.public_key, &data, &signature.signature)
+        {
+            Ok(Some(&signature.public_key))
+        } else {
+            Err(MsgError::InvalidSignature)
+        }
+    }
+
+    /// Yields the portions of this msg covered by its signature as they were
+    /// received, or otherwise encodes them
+    fn signed_bytes(&self) -> Result<Cow<'_, [u8]>, MsgError> {
+        if let Some(signed) = self.signed.as_ref() {
+            return Ok(Cow::Borrowed(signed));
+        }
+
+        serde_cbor::ser::to_vec(&(
+            &self.header,
+            &self.parent_header,
+            &self.content,
+        ))
+        .map(Cow::Owned)
+        .map_err(MsgError::AssembleMsg)
+    }
+
+    /// Sets the parent header of this msg with that of the provided header,
+    /// placing this msg within the same trace as the parent
     pub fn with_parent_header(&mut self, header: Header) -> &mut Self {
+        self.signed = None;
+        self.header.inherit_trace(&header);
         self.parent_header = Some(header);
         self
     }
@@ -90,6 +444,8 @@ impl From<Content> for Msg {
             header: Header::default(),
             parent_header: None,
             content,
+            signature: None,
+            signed: None,
         }
     }
 }
@@ -106,6 +462,63 @@ impl From<Reply> for Msg {
     }
 }
 
+/// Pieces together the bytes that a signature of the msg encoded in `slice`
+/// covers from the header, parent header, and content as they appear in
+/// `slice`, which is how they are encoded when signed
+fn received_signed_bytes(slice: &[u8]) -> Option<Vec<u8>> {
+    let values = cbor::map_values(slice)?;
+    let value = |name| {
+        let mut matches = values.iter().filter(|(key, _)| *key == name);
+        match (matches.next(), matches.next()) {
+            (Some((_, value)), None) => Some(*value),
+            _ => None,
+        }
+    };
+
+    // Encoded as an array of three items
+    let mut signed = vec![0x83];
+    signed.extend_from_slice(value("header")?);
+    signed.extend_from_slice(value("parent_header")?);
+    signed.extend_from_slice(value("content")?);
+    Some(signed)
+}
+
+/// Finds the path of a field within `raw` that is missing from `known`,
+/// which is a field that was dropped when decoding as it is unknown
+///
+/// A null field is never reported, as a known optional field that is not
+/// encoded when empty goes missing once encoded again just the same, and a
+/// null carries nothing that could be lost by dropping it.
+fn find_unknown_field(
+    raw: &Value,
+    known: &Value,
+    path: &str,
+) -> Option<String> {
+    match (raw, known) {
+        (Value::Map(raw), Value::Map(known)) => {
+            raw.iter().find_map(|(key, value)| {
+                let path = match key {
+                    Value::Text(x) => format!("{}/{}", path, x),
+                    x => format!("{}/{:?}", path, x),
+                };
+                match known.get(key) {
+                    Some(known) => find_unknown_field(value, known, &path),
+                    None if *value == Value::Null => None,
+                    None => Some(path),
+                }
+            })
+        }
+        (Value::Array(raw), Value::Array(known)) => raw
+            .iter()
+            .zip(known)
+            .enumerate()
+            .find_map(|(i, (raw, known))| {
+                find_unknown_field(raw, known, &format!("{}/{}", path, i))
+            }),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,6 +550,12 @@ mod tests {
         }
     }
 
+    #[test]
+    fn encoded_len_should_match_length_of_encoded_msg() {
+        let msg = Msg::from(Request::Heartbeat);
+        assert_eq!(msg.encoded_len().unwrap(), msg.to_vec().unwrap().len());
+    }
+
     #[test]
     fn with_parent_header_should_set_header() {
         let mut msg = Msg::from(Reply::Heartbeat);
@@ -156,4 +575,234 @@ mod tests {
 
         assert_eq!(msg.parent_header, Some(parent.header));
     }
+
+    #[test]
+    fn new_should_inherit_trace_of_parent() {
+        let mut parent = Header::default();
+        parent.with_trace(
+            Some(String::from("trace")),
+            Some(String::from("span")),
+        );
+
+        let msg = Msg::new(Content::from(Reply::Heartbeat), Some(parent));
+
+        assert_eq!(msg.header.trace_id, Some(String::from("trace")));
+        assert_eq!(msg.header.parent_span_id, Some(String::from("span")));
+    }
+
+    #[test]
+    fn from_slice_should_decode_forwarded_request_and_reply() {
+        let address = "127.0.0.1:60123".parse().unwrap();
+        let msg = Msg::from(Request::Forward(request::ForwardArgs {
+            address,
+            msg: Msg::from(Request::Heartbeat).to_vec().unwrap(),
+        }));
+        assert_eq!(Msg::from_slice(&msg.to_vec().unwrap()).unwrap(), msg);
+
+        let msg = Msg::from(Reply::Forward(reply::ForwardArgs {
+            address,
+            reply: Box::new(Reply::Heartbeat),
+        }));
+        assert_eq!(Msg::from_slice(&msg.to_vec().unwrap()).unwrap(), msg);
+    }
+
+    /// Encodes the msg after modifying the map of its content
+    fn to_vec_with_content(
+        msg: &Msg,
+        f: impl FnOnce(&mut std::collections::BTreeMap<Value, Value>),
+    ) -> Vec<u8> {
+        let mut value = serde_cbor::value::to_value(msg).unwrap();
+        if let Value::Map(map) = &mut value {
+            match map.get_mut(&Value::Text(String::from("content"))) {
+                Some(Value::Map(content)) => f(content),
+                x => panic!("Unexpected content: {:?}", x),
+            }
+        }
+        serde_cbor::to_vec(&value).unwrap()
+    }
+
+    fn text(x: &str) -> Value {
+        Value::Text(String::from(x))
+    }
+
+    #[test]
+    fn from_slice_should_replace_content_of_unknown_type_with_unsupported() {
+        let msg = Msg::from(Request::Heartbeat);
+        let data = to_vec_with_content(&msg, |content| {
+            content.insert(text("type"), text("future_request"));
+        });
+
+        let decoded = Msg::decode(&data, false).unwrap();
+        assert_eq!(decoded.header, msg.header);
+        assert_eq!(
+            decoded.content,
+            Content::Request(Request::Unsupported(request::UnsupportedArgs {
+                type_name: String::from("future_request"),
+                description: String::from("Unknown type future_request"),
+            }))
+        );
+
+        let data = to_vec_with_content(&Msg::from(Reply::Heartbeat), |c| {
+            c.insert(text("type"), text("future_reply"));
+        });
+        match Msg::decode(&data, false).unwrap().content {
+            Content::Reply(Reply::Unsupported(args)) => {
+                assert_eq!(args.type_name, "future_reply")
+            }
+            x => panic!("Unexpected content: {:?}", x),
+        }
+
+        assert!(Msg::decode(&data, true).is_err());
+    }
+
+    #[test]
+    fn from_slice_should_ignore_unknown_fields_unless_strict() {
+        let msg = Msg::from(Reply::Version(reply::VersionArgs {
+            version: String::from("1.0"),
+        }));
+        let data = to_vec_with_content(&msg, |content| {
+            if let Some(Value::Map(payload)) = content.get_mut(&text("payload"))
+            {
+                payload.insert(text("future_field"), Value::Bool(true));
+            }
+        });
+
+        assert_eq!(Msg::decode(&msg.to_vec().unwrap(), true).unwrap(), msg);
+        assert_eq!(Msg::decode(&data, false).unwrap(), msg);
+        match Msg::decode(&data, true) {
+            Err(MsgError::UnknownField(path)) => {
+                assert_eq!(path, "/content/payload/future_field")
+            }
+            x => panic!("Unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn from_slice_should_accept_null_optional_fields_if_strict() {
+        let msg = Msg::from(Request::Heartbeat);
+        let mut value = serde_cbor::value::to_value(&msg).unwrap();
+        if let Value::Map(map) = &mut value {
+            match map.get_mut(&text("header")) {
+                Some(Value::Map(header)) => {
+                    header.insert(text("processing_micros"), Value::Null);
+                }
+                x => panic!("Unexpected header: {:?}", x),
+            }
+        }
+        let data = serde_cbor::to_vec(&value).unwrap();
+
+        assert_eq!(Msg::decode(&data, true).unwrap(), msg);
+    }
+
+    #[test]
+    fn verify_signature_should_detect_changes_after_signing() {
+        let key = IdentityKey::generate();
+        let mut msg = Msg::from(Request::Heartbeat);
+        assert_eq!(msg.verify_signature().unwrap(), None);
+
+        msg.sign(&key).unwrap();
+        let decoded = Msg::from_slice(&msg.to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.verify_signature().unwrap(), Some(key.public_key()));
+
+        msg.with_parent_header(Header::default());
+        match msg.verify_signature() {
+            Err(MsgError::InvalidSignature) => (),
+            x => panic!("Unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn verify_signature_should_cover_fields_unknown_to_this_version() {
+        let key = IdentityKey::generate();
+        let msg = Msg::from(Request::Heartbeat);
+
+        // Content as a newer version might encode it, with an extra field
+        let mut content = serde_cbor::value::to_value(&msg.content).unwrap();
+        match &mut content {
+            Value::Map(map) => {
+                map.insert(Value::Text("extra".into()), Value::Integer(1));
+            }
+            x => panic!("Unexpected value: {:?}", x),
+        }
+        let encode = |content: &Value, signature: &[u8]| {
+            let mut map = std::collections::BTreeMap::new();
+            map.insert(
+                Value::Text("header".into()),
+                serde_cbor::value::to_value(&msg.header).unwrap(),
+            );
+            map.insert(Value::Text("parent_header".into()), Value::Null);
+            map.insert(Value::Text("content".into()), content.clone());
+            map.insert(
+                Value::Text("signature".into()),
+                serde_cbor::value::to_value(&MsgSignature {
+                    public_key: key.public_key().to_vec(),
+                    signature: signature.to_vec(),
+                })
+                .unwrap(),
+            );
+            serde_cbor::to_vec(&Value::Map(map)).unwrap()
+        };
+        let signature = key.sign(
+            &serde_cbor::to_vec(&(
+                serde_cbor::value::to_value(&msg.header).unwrap(),
+                Value::Null,
+                &content,
+            ))
+            .unwrap(),
+        );
+
+        let decoded = Msg::from_slice(&encode(&content, &signature)).unwrap();
+        assert_eq!(decoded.content, msg.content);
+        assert_eq!(decoded.verify_signature().unwrap(), Some(key.public_key()));
+
+        // Changing the unknown field breaks the signature even though the
+        // decoded msg is the same
+        match &mut content {
+            Value::Map(map) => {
+                map.insert(Value::Text("extra".into()), Value::Integer(2));
+            }
+            x => panic!("Unexpected value: {:?}", x),
+        }
+        let decoded = Msg::from_slice(&encode(&content, &signature)).unwrap();
+        assert_eq!(decoded.content, msg.content);
+        match decoded.verify_signature() {
+            Err(MsgError::InvalidSignature) => (),
+            x => panic!("Unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn to_vec_compressed_should_only_compress_large_msgs_allowing_it() {
+        let data = vec![0; 4096];
+        let mut msg = Msg::from(Request::Custom(data.into()));
+
+        let data = msg.to_vec_compressed(usize::MAX).unwrap();
+        assert_eq!(data, msg.to_vec().unwrap());
+
+        let data = msg.to_vec_compressed(1024).unwrap();
+        assert!(data.len() < 1024, "Compressed to {} bytes", data.len());
+        assert_eq!(Msg::from_slice(&data).unwrap(), msg);
+
+        msg.header.flags.no_compression = true;
+        let data = msg.to_vec_compressed(1024).unwrap();
+        assert_eq!(data, msg.to_vec().unwrap());
+        assert_eq!(Msg::from_slice(&data).unwrap(), msg);
+    }
+
+    #[test]
+    fn header_flags_should_only_be_encoded_if_set() {
+        let mut msg = Msg::from(Request::Heartbeat);
+        let encoded =
+            |msg: &Msg| serde_cbor::value::to_value(&msg.header).unwrap();
+        match encoded(&msg) {
+            Value::Map(map) => {
+                assert!(!map.contains_key(&Value::Text("flags".into())))
+            }
+            x => panic!("Unexpected value: {:?}", x),
+        }
+
+        msg.header.flags.require_signature = true;
+        let decoded = Msg::from_slice(&msg.to_vec().unwrap()).unwrap();
+        assert!(decoded.header.flags.require_signature);
+    }
 }
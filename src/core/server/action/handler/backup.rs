@@ -0,0 +1,122 @@
+use crate::core::{
+    reply,
+    request::{ExportStateArgs, ImportStateArgs},
+    server::{
+        backup::{self, StateArchive},
+        state::ServerState,
+    },
+};
+use log::debug;
+use std::io;
+use std::sync::Arc;
+
+/// Fails unless `identity` is that of an admin, as the state of the server
+/// includes the jobs and config of every client
+fn require_admin(state: &ServerState, identity: &str) -> io::Result<()> {
+    if state.power.is_admin(identity) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "Exporting or importing state requires a msg signed by an admin key",
+        ))
+    }
+}
+
+pub async fn export_state(
+    state: Arc<ServerState>,
+    identity: &str,
+    args: &ExportStateArgs,
+) -> Result<reply::StateExportedArgs, io::Error> {
+    debug!("handler::export_state: {:?} for {}", args, identity);
+    require_admin(&state, identity)?;
+
+    let archive = backup::export(&state, args.skip_job_output).await?;
+    Ok(reply::StateExportedArgs {
+        archive: archive.to_vec()?,
+        version: archive.version,
+    })
+}
+
+pub async fn import_state(
+    state: Arc<ServerState>,
+    identity: &str,
+    args: &ImportStateArgs,
+) -> Result<reply::StateImportedArgs, io::Error> {
+    debug!(
+        "handler::import_state: {} bytes for {}",
+        args.archive.len(),
+        identity
+    );
+    require_admin(&state, identity)?;
+
+    let archive = StateArchive::from_slice(&args.archive)?;
+    let summary = backup::import(&state, archive).await?;
+    Ok(reply::StateImportedArgs {
+        jobs: summary.jobs,
+        jobs_skipped: summary.jobs_skipped,
+        schedules: summary.schedules,
+        config: summary.config,
+        trusted_clients: summary.trusted_clients,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::server::power::PowerControl;
+
+    const ADMIN: &str =
+        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[tokio::test]
+    async fn export_and_import_state_should_require_admin() {
+        let mut state = ServerState::default();
+        state.set_power(PowerControl::new(vec![String::from(ADMIN)]).unwrap());
+        let state = Arc::new(state);
+
+        let err = export_state(
+            Arc::clone(&state),
+            "127.0.0.1",
+            &ExportStateArgs::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let exported = export_state(
+            Arc::clone(&state),
+            ADMIN,
+            &ExportStateArgs::default(),
+        )
+        .await
+        .unwrap();
+
+        let args = ImportStateArgs {
+            archive: exported.archive,
+        };
+        let err = import_state(Arc::clone(&state), "127.0.0.1", &args)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let imported = import_state(Arc::clone(&state), ADMIN, &args)
+            .await
+            .unwrap();
+        assert_eq!(imported.jobs, 0);
+    }
+
+    #[tokio::test]
+    async fn import_state_should_reject_malformed_archive() {
+        let mut state = ServerState::default();
+        state.set_power(PowerControl::new(vec![String::from(ADMIN)]).unwrap());
+
+        let args = ImportStateArgs {
+            archive: vec![1, 2, 3],
+        };
+        let err = import_state(Arc::new(state), ADMIN, &args)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,15 +1,37 @@
-use crate::core::reply::{CapabilitiesArgs, Capability};
+use crate::core::reply::{
+    CapabilitiesArgs, Capability, RequestLimits, TransportLimits,
+};
+use crate::core::{server::state::ServerState, transport::NetTransmission};
 use log::debug;
+use std::sync::Arc;
 
-pub async fn capabilities() -> CapabilitiesArgs {
+pub async fn capabilities(state: Arc<ServerState>) -> CapabilitiesArgs {
     debug!("handler::capabilities");
+    #[allow(unused_mut)]
+    let mut capabilities = vec![
+        Capability::Custom,
+        Capability::Exec,
+        Capability::FileSystem,
+        Capability::Forward,
+    ];
+
+    #[cfg(feature = "wasm")]
+    capabilities.push(Capability::Wasm);
+
     CapabilitiesArgs {
-        capabilities: vec![
-            Capability::Custom,
-            Capability::Exec,
-            Capability::FileSystem,
-            Capability::Forward,
-        ],
+        capabilities,
+        transport_limits: NetTransmission::all()
+            .iter()
+            .map(|t| TransportLimits {
+                transport: String::from(t.name()),
+                max_packet_size: t.size(),
+                max_msg_size: t.max_msg_size(),
+            })
+            .collect(),
+        request_limits: Some(RequestLimits {
+            max_depth: state.max_request_depth(),
+            max_nested_operations: state.max_nested_operations(),
+        }),
     }
 }
 
@@ -19,16 +41,48 @@ mod tests {
 
     #[tokio::test]
     async fn capabilities_should_return_capabilities() {
-        let results = capabilities().await;
+        let results = capabilities(Arc::new(ServerState::default())).await;
+
+        #[allow(unused_mut)]
+        let mut expected = vec![
+            Capability::Custom,
+            Capability::Exec,
+            Capability::FileSystem,
+            Capability::Forward,
+        ];
+
+        #[cfg(feature = "wasm")]
+        expected.push(Capability::Wasm);
+
+        assert_eq!(results.capabilities, expected);
+    }
+
+    #[tokio::test]
+    async fn capabilities_should_return_limits_of_each_transport() {
+        let results = capabilities(Arc::new(ServerState::default())).await;
+
+        let udp = results
+            .transport_limits
+            .iter()
+            .find(|l| l.transport == "udp_ipv4")
+            .expect("Missing udp limits");
+        assert_eq!(udp.max_packet_size, NetTransmission::UdpIpv4.size());
+        assert_eq!(udp.max_msg_size, NetTransmission::UdpIpv4.max_msg_size());
+        assert_eq!(results.transport_limits.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn capabilities_should_return_request_limits() {
+        let mut state = ServerState::default();
+        state.set_request_limits(3, 10);
 
+        let results = capabilities(Arc::new(state)).await;
         assert_eq!(
-            results.capabilities,
-            vec![
-                Capability::Custom,
-                Capability::Exec,
-                Capability::FileSystem,
-                Capability::Forward
-            ],
+            results.request_limits,
+            Some(RequestLimits {
+                max_depth: 3,
+                max_nested_operations: 10,
+            })
         );
     }
 }
@@ -0,0 +1,219 @@
+use crate::core::{
+    reply::{self, ConfigArgs, ConfigPushedArgs, WebhooksArgs},
+    request::{PushConfigArgs, UpdateWebhooksArgs},
+    server::{
+        state::ServerState,
+        webhook::{Webhook, WebhookEvent},
+    },
+};
+use log::debug;
+use std::io;
+use std::sync::Arc;
+
+pub async fn push_config(
+    state: Arc<ServerState>,
+    args: &PushConfigArgs,
+) -> Result<ConfigPushedArgs, io::Error> {
+    debug!(
+        "handler::push_config: {} bytes, version {}",
+        args.blob.len(),
+        args.version
+    );
+
+    state.config.push(args.blob.clone(), args.version).await?;
+    state.webhooks.fire(
+        &state.tasks,
+        WebhookEvent::ConfigChanged {
+            version: args.version,
+        },
+    );
+
+    Ok(ConfigPushedArgs {
+        version: args.version,
+    })
+}
+
+pub async fn get_config(
+    state: Arc<ServerState>,
+) -> Result<ConfigArgs, io::Error> {
+    debug!("handler::get_config");
+
+    state
+        .config
+        .get()
+        .await
+        .map(|config| ConfigArgs {
+            blob: config.blob,
+            version: config.version,
+        })
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No config has been pushed")
+        })
+}
+
+/// Adds and removes the webhooks that events are sent to, yielding the
+/// webhooks afterward without revealing their secrets
+pub async fn update_webhooks(
+    state: Arc<ServerState>,
+    args: &UpdateWebhooksArgs,
+) -> Result<WebhooksArgs, io::Error> {
+    debug!(
+        "handler::update_webhooks: {} added, {:?} removed",
+        args.add.len(),
+        args.remove
+    );
+
+    let add = args
+        .add
+        .iter()
+        .map(|x| Webhook {
+            url: x.url.clone(),
+            secret: x.secret.clone(),
+            events: x.events.clone(),
+        })
+        .collect();
+    let webhooks = state
+        .webhooks
+        .update(add, &args.remove)?
+        .into_iter()
+        .map(|x| reply::WebhookArgs {
+            url: x.url,
+            signed: x.secret.is_some(),
+            events: x.events,
+        })
+        .collect();
+
+    Ok(WebhooksArgs { webhooks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::request;
+
+    #[tokio::test]
+    async fn push_config_should_store_config_for_get_config() {
+        let state = Arc::new(ServerState::default());
+
+        let args = push_config(
+            Arc::clone(&state),
+            &PushConfigArgs {
+                blob: b"config".to_vec(),
+                version: 1,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(args.version, 1);
+
+        let args = get_config(state).await.unwrap();
+        assert_eq!(
+            args,
+            ConfigArgs {
+                blob: b"config".to_vec(),
+                version: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn push_config_should_fail_if_version_is_stale() {
+        let state = Arc::new(ServerState::default());
+        state.config.push(b"newer".to_vec(), 5).await.unwrap();
+
+        let err = push_config(
+            Arc::clone(&state),
+            &PushConfigArgs {
+                blob: b"older".to_vec(),
+                version: 4,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(get_config(state).await.unwrap().blob, b"newer");
+    }
+
+    #[tokio::test]
+    async fn get_config_should_fail_if_nothing_pushed() {
+        let err = get_config(Arc::new(ServerState::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn update_webhooks_should_add_and_remove_hooks_without_secrets() {
+        let state = Arc::new(ServerState::default());
+
+        let args = update_webhooks(
+            Arc::clone(&state),
+            &UpdateWebhooksArgs {
+                add: vec![
+                    request::WebhookArgs {
+                        url: String::from("http://localhost/a"),
+                        secret: Some(b"secret".to_vec()),
+                        events: vec![String::from("auth_failed")],
+                    },
+                    request::WebhookArgs {
+                        url: String::from("http://localhost/b"),
+                        ..Default::default()
+                    },
+                ],
+                remove: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            args.webhooks,
+            vec![
+                reply::WebhookArgs {
+                    url: String::from("http://localhost/a"),
+                    signed: true,
+                    events: vec![String::from("auth_failed")],
+                },
+                reply::WebhookArgs {
+                    url: String::from("http://localhost/b"),
+                    signed: false,
+                    events: Vec::new(),
+                },
+            ]
+        );
+
+        let args = update_webhooks(
+            Arc::clone(&state),
+            &UpdateWebhooksArgs {
+                add: Vec::new(),
+                remove: vec![String::from("http://localhost/a")],
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(args.webhooks.len(), 1);
+        assert_eq!(
+            state.webhooks.update(Vec::new(), &[]).unwrap(),
+            vec![Webhook::new("http://localhost/b")]
+        );
+    }
+
+    #[tokio::test]
+    async fn update_webhooks_should_fail_if_url_is_unsupported() {
+        let state = Arc::new(ServerState::default());
+
+        let err = update_webhooks(
+            Arc::clone(&state),
+            &UpdateWebhooksArgs {
+                add: vec![request::WebhookArgs {
+                    url: String::from("https://localhost"),
+                    ..Default::default()
+                }],
+                remove: Vec::new(),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(state.webhooks.is_empty());
+    }
+}
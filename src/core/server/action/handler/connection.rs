@@ -0,0 +1,112 @@
+use crate::core::{
+    reply::{ConnectionEntryArgs, ConnectionsListArgs},
+    request::ListConnectionsArgs,
+    server::{listing, state::ServerState},
+};
+use log::debug;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub async fn list_connections(
+    state: Arc<ServerState>,
+    args: &ListConnectionsArgs,
+) -> ConnectionsListArgs {
+    debug!("handler::list_connections: {:?}", args);
+
+    let now = Instant::now();
+    let conns: Vec<_> = state
+        .conns
+        .lock()
+        .await
+        .iter()
+        .map(|(addr, x)| {
+            (*addr, now.saturating_duration_since(*x.last_touched()))
+        })
+        .collect();
+
+    let (conns, page) = listing::list(
+        conns,
+        &args.page,
+        |(addr, idle)| {
+            let origin = match args.origin.as_ref() {
+                Some(origin) => {
+                    addr.to_string() == *origin
+                        || addr.ip().to_string() == *origin
+                }
+                None => true,
+            };
+
+            origin && listing::within_age(*idle, None, args.max_idle_secs)
+        },
+        |(addr, _)| *addr,
+    );
+
+    ConnectionsListArgs {
+        connections: conns
+            .into_iter()
+            .map(|(addr, idle)| ConnectionEntryArgs {
+                addr: addr.to_string(),
+                idle_millis: idle.as_millis() as u64,
+            })
+            .collect(),
+        page,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::request::PageArgs;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn list_connections_should_filter_and_page_clients_by_address() {
+        let state = Arc::new(ServerState::default());
+        {
+            let mut conns = state.conns.lock().await;
+            let now = Instant::now();
+            let idle = now - Duration::from_secs(60);
+            for addr in &["127.0.0.2:2", "127.0.0.1:2", "127.0.0.1:1"] {
+                conns.insert(addr.parse().unwrap(), ());
+            }
+
+            let addr = "127.0.0.1:3".parse().unwrap();
+            conns.insert(addr, ());
+            conns.get_mut(&addr).unwrap().touch_at(idle);
+        }
+
+        let reply = list_connections(
+            Arc::clone(&state),
+            &ListConnectionsArgs {
+                origin: Some(String::from("127.0.0.1")),
+                max_idle_secs: None,
+                page: PageArgs {
+                    limit: Some(2),
+                    ..Default::default()
+                },
+            },
+        )
+        .await;
+
+        let addrs: Vec<&str> =
+            reply.connections.iter().map(|c| c.addr.as_str()).collect();
+        assert_eq!(addrs, vec!["127.0.0.1:1", "127.0.0.1:2"]);
+        assert_eq!(reply.page.total, 3);
+        assert_eq!(reply.page.next_offset, Some(2));
+
+        let reply = list_connections(
+            Arc::clone(&state),
+            &ListConnectionsArgs {
+                origin: None,
+                max_idle_secs: Some(30),
+                page: PageArgs::default(),
+            },
+        )
+        .await;
+
+        let addrs: Vec<&str> =
+            reply.connections.iter().map(|c| c.addr.as_str()).collect();
+        assert_eq!(addrs, vec!["127.0.0.1:1", "127.0.0.1:2", "127.0.0.2:2"]);
+        assert_eq!(reply.page.next_offset, None);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::core::{
+    reply::{
+        DiagnosticBuffersArgs, DiagnosticConfigArgs, DiagnosticProcArgs,
+        DiagnosticProcBufferArgs, DiagnosticStateCountersArgs,
+        DiagnosticTasksArgs, DiagnosticTransferTotalsArgs,
+        DiagnosticTransfersArgs, DiagnosticsArgs,
+    },
+    request::{self, DiagnosticSection, EvictionKind},
+    server::{state::ServerState, transfers::TransferTotals},
+    transport::auth::identity,
+};
+use log::debug;
+use std::sync::Arc;
+
+pub async fn diagnostics(
+    state: Arc<ServerState>,
+    args: &request::DiagnosticsArgs,
+) -> DiagnosticsArgs {
+    debug!("handler::diagnostics: {:?}", args);
+
+    let mut reply = DiagnosticsArgs::default();
+
+    if args.includes(DiagnosticSection::StateCounters) {
+        reply.state_counters = Some(Box::new(state_counters(&state).await));
+    }
+
+    if args.includes(DiagnosticSection::Config) {
+        reply.config = Some(Box::new(DiagnosticConfigArgs {
+            file_ttl_millis: state.file_ttl().as_millis() as u64,
+            proc_ttl_millis: state.proc_ttl().as_millis() as u64,
+            dead_proc_ttl_millis: state.dead_proc_ttl.as_millis() as u64,
+            webhooks: state.webhooks.len(),
+            max_request_depth: state.max_request_depth(),
+            max_nested_operations: state.max_nested_operations(),
+            transfer_quota: state.transfers.quota(),
+            fs_event_history: state.fs_events.capacity(),
+            signature_mode: state.signature_policy.mode.name().to_string(),
+            trusted_signers: state
+                .signature_policy
+                .trusted_keys
+                .iter()
+                .map(|key| identity::fingerprint(key))
+                .collect(),
+        }));
+    }
+
+    if args.includes(DiagnosticSection::Tasks) {
+        reply.tasks = Some(Box::new(tasks(&state).await));
+    }
+
+    if args.includes(DiagnosticSection::Buffers) {
+        reply.buffers = Some(Box::new(buffers(&state).await));
+    }
+
+    if args.includes(DiagnosticSection::Transfers) {
+        reply.transfers = Some(Box::new(transfers(&state).await));
+    }
+
+    reply
+}
+
+async fn state_counters(state: &ServerState) -> DiagnosticStateCountersArgs {
+    DiagnosticStateCountersArgs {
+        conns: state.conns.lock().await.len(),
+        open_files: state.fs_manager.lock().await.file_cnt(),
+        procs: state.procs.lock().await.len(),
+        tracked_file_ids: state.file_ids.lock().await.len(),
+        tracked_proc_ids: state.proc_ids.lock().await.len(),
+        running_jobs: state.jobs.running_ids().await.len(),
+        evicted_files: state.evictions.total(EvictionKind::File),
+        evicted_procs: state.evictions.total(EvictionKind::Proc),
+        evicted_sessions: state.evictions.total(EvictionKind::Session),
+    }
+}
+
+async fn tasks(state: &ServerState) -> DiagnosticTasksArgs {
+    let mut procs = Vec::new();
+    for (id, local_proc) in state.procs.lock().await.iter_mut() {
+        let exit_status = local_proc.exit_status().await;
+        procs.push(DiagnosticProcArgs {
+            id: *id,
+            detached: local_proc.is_detached(),
+            is_alive: exit_status.is_none(),
+            exit_code: exit_status.and_then(|s| s.exit_code),
+        });
+    }
+    procs.sort_unstable_by_key(|p| p.id);
+
+    DiagnosticTasksArgs {
+        procs,
+        running_jobs: state.jobs.running_ids().await,
+        active_request_tasks: state.tasks.active(),
+        active_connection_tasks: state.connection_tasks.active(),
+    }
+}
+
+async fn buffers(state: &ServerState) -> DiagnosticBuffersArgs {
+    let mut procs = Vec::new();
+    for (id, local_proc) in state.procs.lock().await.iter() {
+        let (stdout_bytes, stderr_bytes) = local_proc.buffered_len().await;
+        procs.push(DiagnosticProcBufferArgs {
+            id: *id,
+            stdout_bytes,
+            stderr_bytes,
+        });
+    }
+    procs.sort_unstable_by_key(|p| p.id);
+
+    let total_bytes =
+        procs.iter().map(|p| p.stdout_bytes + p.stderr_bytes).sum();
+
+    DiagnosticBuffersArgs { procs, total_bytes }
+}
+
+async fn transfers(state: &ServerState) -> DiagnosticTransfersArgs {
+    fn to_args<'a>(
+        totals: impl Iterator<Item = (&'a str, &'a TransferTotals)>,
+    ) -> Vec<DiagnosticTransferTotalsArgs> {
+        let mut args: Vec<DiagnosticTransferTotalsArgs> = totals
+            .map(|(name, totals)| DiagnosticTransferTotalsArgs {
+                name: name.to_string(),
+                bytes_in: totals.bytes_in,
+                bytes_out: totals.bytes_out,
+            })
+            .collect();
+        args.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        args
+    }
+
+    let by_identity = state.transfers.by_identity().await;
+    let by_class = state.transfers.by_class().await;
+
+    DiagnosticTransfersArgs {
+        identities: to_args(by_identity.iter().map(|(k, v)| (k.as_str(), v))),
+        classes: to_args(by_class.iter().map(|(k, v)| (*k, v))),
+        quota: state.transfers.quota(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::server::proc::LocalProc;
+    use std::process::Stdio;
+    use tokio::process::Command;
+
+    #[tokio::test]
+    async fn diagnostics_should_only_include_requested_sections() {
+        let state = Arc::new(ServerState::default());
+
+        let reply = diagnostics(
+            Arc::clone(&state),
+            &request::DiagnosticsArgs {
+                sections: vec![DiagnosticSection::Config],
+            },
+        )
+        .await;
+
+        assert_eq!(
+            reply.config,
+            Some(Box::new(DiagnosticConfigArgs {
+                file_ttl_millis: state.file_ttl().as_millis() as u64,
+                proc_ttl_millis: state.proc_ttl().as_millis() as u64,
+                dead_proc_ttl_millis: state.dead_proc_ttl.as_millis() as u64,
+                webhooks: 0,
+                max_request_depth: state.max_request_depth(),
+                max_nested_operations: state.max_nested_operations(),
+                transfer_quota: None,
+                fs_event_history: state.fs_events.capacity(),
+                signature_mode: String::from("ignore"),
+                trusted_signers: vec![],
+            }))
+        );
+        assert!(reply.state_counters.is_none(), "Unexpected state counters");
+        assert!(reply.tasks.is_none(), "Unexpected tasks");
+        assert!(reply.buffers.is_none(), "Unexpected buffers");
+    }
+
+    #[tokio::test]
+    async fn diagnostics_should_include_all_sections_if_none_requested() {
+        let state = Arc::new(ServerState::default());
+
+        let child = Command::new("sleep")
+            .arg("60")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let local_proc = LocalProc::new(child);
+        let id = local_proc.id();
+        state.procs.lock().await.insert(id, local_proc);
+
+        let reply = diagnostics(
+            Arc::clone(&state),
+            &request::DiagnosticsArgs::default(),
+        )
+        .await;
+
+        assert_eq!(reply.state_counters.unwrap().procs, 1);
+        assert!(reply.config.is_some(), "Missing config");
+        assert_eq!(
+            reply.tasks.unwrap().procs,
+            vec![DiagnosticProcArgs {
+                id,
+                detached: false,
+                is_alive: true,
+                exit_code: None,
+            }]
+        );
+        assert_eq!(reply.buffers.unwrap().total_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn diagnostics_should_count_active_tasks() {
+        let state = Arc::new(ServerState::default());
+        let _request = state.tasks.track();
+        let _conn_1 = state.connection_tasks.track();
+        let _conn_2 = state.connection_tasks.track();
+
+        let reply = diagnostics(
+            Arc::clone(&state),
+            &request::DiagnosticsArgs {
+                sections: vec![DiagnosticSection::Tasks],
+            },
+        )
+        .await;
+
+        let tasks = reply.tasks.unwrap();
+        assert_eq!(tasks.active_request_tasks, 1);
+        assert_eq!(tasks.active_connection_tasks, 2);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::core::{
+    reply::{EvictionReason, PendingEvictionArgs, PendingEvictionsArgs},
+    request::{EvictionKind, EvictionTargetArgs, ListPendingEvictionsArgs},
+    server::state::ServerState,
+};
+use log::debug;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub async fn list_pending_evictions(
+    state: Arc<ServerState>,
+    args: &ListPendingEvictionsArgs,
+) -> Result<PendingEvictionsArgs, io::Error> {
+    debug!("handler::list_pending_evictions: {:?}", args);
+
+    let mut touched = 0;
+    for target in args.touch.iter() {
+        if touch(&state, target).await? {
+            touched += 1;
+        }
+    }
+
+    let within = args
+        .within_secs
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::MAX);
+    let mut evictions = Vec::new();
+
+    for (id, remaining) in state.file_ids.lock().await.expiring_within(within) {
+        evictions.push(pending(
+            EvictionKind::File,
+            id.to_string(),
+            EvictionReason::Expired,
+            remaining,
+        ));
+    }
+
+    // NOTE: Procs are locked before their ids as is done when evicting them
+    let mut procs = state.procs.lock().await;
+    let expiring: Vec<(u32, Duration)> = state
+        .proc_ids
+        .lock()
+        .await
+        .expiring_within(within)
+        .map(|(id, remaining)| (*id, remaining))
+        .collect();
+    for (id, remaining) in expiring {
+        let exited = match procs.get_mut(&id) {
+            Some(proc) => proc.exit_status().await.is_some(),
+            None => false,
+        };
+        let reason = if exited {
+            EvictionReason::Exited
+        } else {
+            EvictionReason::Expired
+        };
+        evictions.push(pending(
+            EvictionKind::Proc,
+            id.to_string(),
+            reason,
+            remaining,
+        ));
+    }
+    drop(procs);
+
+    for (addr, remaining) in state.sessions.lock().await.expiring_within(within)
+    {
+        evictions.push(pending(
+            EvictionKind::Session,
+            addr.to_string(),
+            EvictionReason::Expired,
+            remaining,
+        ));
+    }
+
+    evictions.sort_by(|a, b| {
+        (a.evicted_in_millis, a.kind, &a.id).cmp(&(
+            b.evicted_in_millis,
+            b.kind,
+            &b.id,
+        ))
+    });
+
+    Ok(PendingEvictionsArgs { evictions, touched })
+}
+
+/// Renews the ttl of the resource targeted by `target`, yielding whether it
+/// was tracked and not yet due to be evicted
+async fn touch(
+    state: &ServerState,
+    target: &EvictionTargetArgs,
+) -> Result<bool, io::Error> {
+    let invalid_id = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid {} id: {}", target.kind.name(), target.id),
+        )
+    };
+
+    Ok(match target.kind {
+        EvictionKind::File => {
+            let id: u32 = target.id.parse().map_err(|_| invalid_id())?;
+            state.file_ids.lock().await.touch(&id)
+        }
+        EvictionKind::Proc => {
+            let id: u32 = target.id.parse().map_err(|_| invalid_id())?;
+            state.proc_ids.lock().await.touch(&id)
+        }
+        EvictionKind::Session => {
+            let addr: SocketAddr =
+                target.id.parse().map_err(|_| invalid_id())?;
+            state.sessions.lock().await.touch(&addr)
+        }
+    })
+}
+
+fn pending(
+    kind: EvictionKind,
+    id: String,
+    reason: EvictionReason,
+    remaining: Duration,
+) -> PendingEvictionArgs {
+    PendingEvictionArgs {
+        kind,
+        id,
+        reason,
+        evicted_in_millis: remaining.as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn list_pending_evictions_should_list_resources_expiring_soonest_first(
+    ) {
+        let state = Arc::new(ServerState::default());
+        state
+            .touch_file_id_with_ttl(1, Duration::from_secs(5))
+            .await;
+        state
+            .touch_file_id_with_ttl(2, Duration::from_secs(600))
+            .await;
+        state.touch_proc_id_with_ttl(3, Duration::new(0, 0)).await;
+
+        let reply = list_pending_evictions(
+            Arc::clone(&state),
+            &ListPendingEvictionsArgs {
+                within_secs: Some(60),
+                touch: vec![],
+            },
+        )
+        .await
+        .unwrap();
+
+        let ids: Vec<(EvictionKind, &str)> = reply
+            .evictions
+            .iter()
+            .map(|x| (x.kind, x.id.as_str()))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![(EvictionKind::Proc, "3"), (EvictionKind::File, "1")]
+        );
+        assert_eq!(reply.evictions[0].evicted_in_millis, 0);
+        assert_eq!(reply.evictions[0].reason, EvictionReason::Expired);
+        assert_eq!(reply.touched, 0);
+    }
+
+    #[tokio::test]
+    async fn list_pending_evictions_should_touch_resources_before_listing() {
+        let state = Arc::new(ServerState::default());
+        state
+            .touch_file_id_with_ttl(1, Duration::from_secs(5))
+            .await;
+        state.touch_proc_id_with_ttl(2, Duration::new(0, 0)).await;
+
+        let target = |kind, id: &str| EvictionTargetArgs {
+            kind,
+            id: id.to_string(),
+        };
+        let reply = list_pending_evictions(
+            Arc::clone(&state),
+            &ListPendingEvictionsArgs {
+                within_secs: Some(4),
+                touch: vec![
+                    target(EvictionKind::File, "1"),
+                    target(EvictionKind::Proc, "2"),
+                    target(EvictionKind::Session, "127.0.0.1:1"),
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+        // Expired proc can no longer be kept alive
+        assert_eq!(reply.touched, 1);
+        assert_eq!(reply.evictions.len(), 1);
+        assert_eq!(reply.evictions[0].id, "2");
+
+        let err = list_pending_evictions(
+            Arc::clone(&state),
+            &ListPendingEvictionsArgs {
+                within_secs: None,
+                touch: vec![target(EvictionKind::File, "abc")],
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
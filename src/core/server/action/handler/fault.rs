@@ -0,0 +1,158 @@
+use crate::core::{
+    reply::{FaultInjectedArgs, FaultsClearedArgs},
+    request::{Fault, InjectFaultArgs},
+    server::state::ServerState,
+    Content, Reply, ReplyError, Request,
+};
+use log::{debug, error, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub async fn inject_fault(
+    state: Arc<ServerState>,
+    args: &InjectFaultArgs,
+) -> FaultInjectedArgs {
+    debug!("handler::inject_fault: {:?}", args);
+
+    state
+        .faults
+        .lock()
+        .await
+        .insert(args.request_type.clone(), args.clone());
+
+    FaultInjectedArgs {
+        request_type: args.request_type.clone(),
+    }
+}
+
+pub async fn clear_faults(state: Arc<ServerState>) -> FaultsClearedArgs {
+    debug!("handler::clear_faults");
+
+    let mut faults = state.faults.lock().await;
+    let cleared = faults.len();
+    faults.clear();
+
+    FaultsClearedArgs { cleared }
+}
+
+/// Applies any fault injected for the type of `request`, yielding a reply
+/// to send in place of handling the request or none if it should be
+/// handled as usual
+///
+/// Requests that manage faults are never affected, ensuring that faults
+/// can always be cleared
+pub async fn apply_fault(
+    state: &ServerState,
+    request: &Request,
+) -> Option<Reply> {
+    if let Request::InjectFault(_) | Request::ClearFaults = request {
+        return None;
+    }
+
+    let (request_type, fault) = {
+        let mut faults = state.faults.lock().await;
+        if faults.is_empty() {
+            return None;
+        }
+
+        let request_type = Content::Request(request.clone()).type_name()?;
+        let args = faults.get_mut(&request_type)?;
+        let fault = args.fault.clone();
+        match args.times {
+            Some(times) if times <= 1 => {
+                faults.remove(&request_type);
+            }
+            Some(times) => args.times = Some(times - 1),
+            None => {}
+        }
+
+        (request_type, fault)
+    };
+
+    warn!("Applying injected fault to {}: {:?}", request_type, fault);
+    match fault {
+        Fault::Fail { msg } => Some(Reply::Error(ReplyError::from(msg))),
+        Fault::Delay { millis } => {
+            tokio::time::delay_for(Duration::from_millis(millis)).await;
+            None
+        }
+        Fault::Panic => {
+            // Panic within a separate task so that the server keeps handling
+            // other requests, while this request never receives a reply
+            let result = tokio::spawn(async move {
+                panic!("Injected fault for {}", request_type)
+            })
+            .await;
+            if let Err(x) = result {
+                error!("Injected panic: {}", x);
+            }
+            Some(Reply::Ignore)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fault_args(fault: Fault, times: Option<u32>) -> InjectFaultArgs {
+        InjectFaultArgs {
+            request_type: String::from("heartbeat_request"),
+            fault,
+            times,
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_fault_should_only_affect_matching_requests() {
+        let state = Arc::new(ServerState::default());
+        let args = fault_args(Fault::default(), None);
+        inject_fault(Arc::clone(&state), &args).await;
+
+        assert_eq!(apply_fault(&state, &Request::Version).await, None);
+        for _ in 0..2 {
+            assert_eq!(
+                apply_fault(&state, &Request::Heartbeat).await,
+                Some(Reply::Error(ReplyError::from("Injected fault")))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_fault_should_remove_fault_once_times_exhausted() {
+        let state = Arc::new(ServerState::default());
+        let args = fault_args(Fault::Delay { millis: 0 }, Some(2));
+        inject_fault(Arc::clone(&state), &args).await;
+
+        assert_eq!(apply_fault(&state, &Request::Heartbeat).await, None);
+        assert_eq!(
+            state.faults.lock().await[&args.request_type].times,
+            Some(1)
+        );
+
+        assert_eq!(apply_fault(&state, &Request::Heartbeat).await, None);
+        assert!(state.faults.lock().await.is_empty(), "Fault not removed");
+    }
+
+    #[tokio::test]
+    async fn apply_fault_should_ignore_request_if_fault_is_panic() {
+        let state = Arc::new(ServerState::default());
+        let args = fault_args(Fault::Panic, None);
+        inject_fault(Arc::clone(&state), &args).await;
+
+        assert_eq!(
+            apply_fault(&state, &Request::Heartbeat).await,
+            Some(Reply::Ignore)
+        );
+    }
+
+    #[tokio::test]
+    async fn clear_faults_should_remove_all_faults() {
+        let state = Arc::new(ServerState::default());
+        let args = fault_args(Fault::Panic, None);
+        inject_fault(Arc::clone(&state), &args).await;
+
+        assert_eq!(clear_faults(Arc::clone(&state)).await.cleared, 1);
+        assert_eq!(apply_fault(&state, &Request::Heartbeat).await, None);
+    }
+}
@@ -2,19 +2,33 @@ use crate::core::{
     reply::*,
     request::*,
     server::{
-        fs::{LocalDirEntry, LocalFileError, LocalFileHandle},
+        fs::{
+            checksum,
+            chunks::{hash_chunk, ChunkedUpload},
+            diff,
+            events::FsChange,
+            mounts, sniff, AllowedPaths, FileSystemManager, LocalDirEntry,
+            LocalFile, LocalFileError, LocalFileHandle, LocalFileModes,
+            SharedLocalFile,
+        },
         state::ServerState,
     },
+    RemotePath,
 };
+use futures::future;
 use log::debug;
-use std::convert::TryFrom;
+use sha2::{Digest, Sha256};
 use std::io;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tokio::{io::AsyncReadExt, sync::mpsc};
 
 #[derive(Debug)]
 pub enum FileIoError {
     Io(io::Error),
     SigMismatch { id: u32, sig: u32 },
+    PreconditionFailed(PreconditionFailedArgs),
 }
 
 impl From<FileIoError> for ReplyError {
@@ -24,6 +38,9 @@ impl From<FileIoError> for ReplyError {
             FileIoError::SigMismatch { id, sig } => {
                 ReplyError::FileSigChanged(FileSigChangedArgs { id, sig })
             }
+            FileIoError::PreconditionFailed(args) => {
+                ReplyError::PreconditionFailed(args)
+            }
         }
     }
 }
@@ -34,6 +51,97 @@ impl From<FileIoError> for Reply {
     }
 }
 
+/// Verifies that all `preconditions` hold for the file at `path`, where `to`
+/// is the destination of a rename and `sig` is the signature of the file if
+/// it is open
+///
+/// Callers are expected to hold the file system manager lock while checking
+/// and acting so that no other request can invalidate the preconditions
+async fn check_preconditions(
+    preconditions: Option<&FilePreconditions>,
+    path: &Path,
+    to: Option<&Path>,
+    sig: Option<u32>,
+) -> Result<(), FileIoError> {
+    let preconditions = match preconditions {
+        Some(preconditions) => preconditions,
+        None => return Ok(()),
+    };
+
+    let failed = |path: &Path, precondition| {
+        FileIoError::PreconditionFailed(PreconditionFailedArgs {
+            path: RemotePath::from_path(path),
+            precondition,
+        })
+    };
+
+    if let Some(expected) = preconditions.sig {
+        if sig != Some(expected) {
+            return Err(failed(
+                path,
+                FailedPrecondition::Sig {
+                    expected,
+                    actual: sig,
+                },
+            ));
+        }
+    }
+
+    if preconditions.must_not_exist {
+        let target = to.unwrap_or(path);
+        if tokio::fs::symlink_metadata(target).await.is_ok() {
+            return Err(failed(target, FailedPrecondition::MustNotExist));
+        }
+    }
+
+    if preconditions.min_size.is_some() || preconditions.max_size.is_some() {
+        let actual = tokio::fs::metadata(path)
+            .await
+            .map_err(FileIoError::Io)?
+            .len();
+
+        if let Some(expected) = preconditions.min_size {
+            if actual < expected {
+                return Err(failed(
+                    path,
+                    FailedPrecondition::MinSize { expected, actual },
+                ));
+            }
+        }
+
+        if let Some(expected) = preconditions.max_size {
+            if actual > expected {
+                return Err(failed(
+                    path,
+                    FailedPrecondition::MaxSize { expected, actual },
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Dirs that paths of requests are confined to when used directly, rather
+/// than through the file system manager
+pub(super) async fn allowed_paths(state: &ServerState) -> AllowedPaths {
+    state.fs_manager.lock().await.allowed_paths()
+}
+
+/// Looks up the open file with `id`, releasing the lock on the file system
+/// manager so that only operations on the same file wait on each other
+async fn shared_file(
+    state: &ServerState,
+    id: u32,
+) -> Result<SharedLocalFile, FileIoError> {
+    state
+        .fs_manager
+        .lock()
+        .await
+        .get(id)
+        .ok_or_else(|| FileIoError::Io(IoErrorArgs::invalid_file_id(id).into()))
+}
+
 pub async fn open_file(
     state: Arc<ServerState>,
     args: &OpenFileArgs,
@@ -44,11 +152,17 @@ pub async fn open_file(
         .fs_manager
         .lock()
         .await
-        .open_file(
-            &args.path,
+        .open_file_with_modes(
+            args.path.to_path_buf(),
             args.create_if_missing,
             args.write_access,
             args.read_access,
+            LocalFileModes {
+                append: args.modes.append,
+                truncate: args.modes.truncate,
+                create_new: args.modes.create_new,
+            },
+            args.mode,
         )
         .await?;
 
@@ -75,8 +189,9 @@ pub async fn close_file(
         sig: args.sig,
     };
 
-    let _ = state.fs_manager.lock().await.close_file(handle)?;
+    let _ = state.fs_manager.lock().await.close_file(handle).await?;
 
+    state.chunks.finish_upload(args.id).await;
     state.remove_file_id(args.id).await;
     Ok(FileClosedArgs { id: args.id })
 }
@@ -84,15 +199,23 @@ pub async fn close_file(
 pub async fn rename_unopened_file(
     state: Arc<ServerState>,
     args: &RenameUnopenedFileArgs,
-) -> Result<UnopenedFileRenamedArgs, io::Error> {
+) -> Result<UnopenedFileRenamedArgs, FileIoError> {
     debug!("handler::rename_unopened_file: {:?}", args);
 
-    state
-        .fs_manager
-        .lock()
+    let mut fs_manager = state.fs_manager.lock().await;
+
+    check_preconditions(
+        args.preconditions.as_ref(),
+        &args.from.to_path_buf(),
+        Some(&args.to.to_path_buf()),
+        None,
+    )
+    .await?;
+
+    fs_manager
+        .rename_file(args.from.to_path_buf(), args.to.to_path_buf())
         .await
-        .rename_file(&args.from, &args.to)
-        .await?;
+        .map_err(FileIoError::Io)?;
 
     Ok(UnopenedFileRenamedArgs {
         from: args.from.clone(),
@@ -107,36 +230,50 @@ pub async fn rename_file(
     debug!("handler::rename_file: {:?}", args);
     state.touch_file_id(args.id).await;
 
-    match state.fs_manager.lock().await.get_mut(args.id) {
-        Some(local_file) => match local_file.rename(args.sig, &args.to).await {
-            Ok(_) => Ok(FileRenamedArgs {
-                id: args.id,
-                sig: local_file.sig(),
-            }),
-            Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
-                id: args.id,
-                sig: local_file.sig(),
-            }),
-            Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
-        },
-        None => Err(FileIoError::Io(
-            IoErrorArgs::invalid_file_id(args.id).into(),
-        )),
+    let file = shared_file(&state, args.id).await?;
+    let mut local_file = file.lock().await;
+
+    check_preconditions(
+        args.preconditions.as_ref(),
+        &local_file.path(),
+        Some(&args.to.to_path_buf()),
+        Some(local_file.sig()),
+    )
+    .await?;
+
+    match local_file.rename(args.sig, args.to.to_path_buf()).await {
+        Ok(_) => Ok(FileRenamedArgs {
+            id: args.id,
+            sig: local_file.sig(),
+        }),
+        Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+            id: args.id,
+            sig: local_file.sig(),
+        }),
+        Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
     }
 }
 
 pub async fn remove_unopened_file(
     state: Arc<ServerState>,
     args: &RemoveUnopenedFileArgs,
-) -> Result<UnopenedFileRemovedArgs, io::Error> {
+) -> Result<UnopenedFileRemovedArgs, FileIoError> {
     debug!("handler::remove_unopened_file: {:?}", args);
 
-    state
-        .fs_manager
-        .lock()
+    let mut fs_manager = state.fs_manager.lock().await;
+
+    check_preconditions(
+        args.preconditions.as_ref(),
+        &args.path.to_path_buf(),
+        None,
+        None,
+    )
+    .await?;
+
+    fs_manager
+        .remove_file(args.path.to_path_buf())
         .await
-        .remove_file(&args.path)
-        .await?;
+        .map_err(FileIoError::Io)?;
 
     Ok(UnopenedFileRemovedArgs {
         path: args.path.clone(),
@@ -150,27 +287,43 @@ pub async fn remove_file(
     debug!("handler::remove_file: {:?}", args);
     state.touch_file_id(args.id).await;
 
-    match state.fs_manager.lock().await.get_mut(args.id) {
-        Some(local_file) => match local_file.remove(args.sig).await {
-            Ok(_) => {
-                state.remove_file_id(args.id).await;
-                Ok(FileRemovedArgs {
-                    id: args.id,
-                    sig: local_file.sig(),
-                })
-            }
-            Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+    let file = shared_file(&state, args.id).await?;
+    let mut local_file = file.lock().await;
+
+    check_preconditions(
+        args.preconditions.as_ref(),
+        &local_file.path(),
+        None,
+        Some(local_file.sig()),
+    )
+    .await?;
+
+    match local_file.remove(args.sig).await {
+        Ok(_) => {
+            state.remove_file_id(args.id).await;
+            Ok(FileRemovedArgs {
                 id: args.id,
                 sig: local_file.sig(),
-            }),
-            Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
-        },
-        None => Err(FileIoError::Io(
-            IoErrorArgs::invalid_file_id(args.id).into(),
-        )),
+            })
+        }
+        Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+            id: args.id,
+            sig: local_file.sig(),
+        }),
+        Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
     }
 }
 
+/// Last time the file at `path` was modified as seconds since the unix
+/// epoch, or none if not supported by the platform
+async fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|x| x.as_secs())
+}
+
 pub async fn read_file(
     state: Arc<ServerState>,
     args: &ReadFileArgs,
@@ -178,22 +331,99 @@ pub async fn read_file(
     debug!("handler::read_file: {:?}", args);
     state.touch_file_id(args.id).await;
 
-    match state.fs_manager.lock().await.get_mut(args.id) {
-        Some(local_file) => match local_file.read_all(args.sig).await {
-            Ok(contents) => Ok(FileContentsArgs {
-                id: args.id,
-                contents,
-            }),
-            Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+    let file = shared_file(&state, args.id).await?;
+    let mut local_file = file.lock().await;
+
+    match local_file.read_range(args.sig, args.offset, args.len).await {
+        Ok((contents, size)) => {
+            // Hashing the entire file for every chunk of a ranged read would
+            // be wasteful, so it is only done when there is an etag to
+            // compare against
+            let etag = if !args.is_ranged() {
+                format!("{:x}", Sha256::digest(&contents))
+            } else if args.if_none_match.is_some() {
+                let data = local_file
+                    .allowed_paths()
+                    .read(local_file.path())
+                    .await
+                    .map_err(FileIoError::Io)?;
+                format!("{:x}", Sha256::digest(&data))
+            } else {
+                String::new()
+            };
+            let not_modified = args.if_none_match.as_ref() == Some(&etag);
+            Ok(FileContentsArgs {
                 id: args.id,
+                contents: if not_modified { Vec::new() } else { contents },
                 sig: local_file.sig(),
-            }),
-            Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
-        },
-        None => Err(FileIoError::Io(
-            IoErrorArgs::invalid_file_id(args.id).into(),
-        )),
+                etag,
+                modified: modified_secs(&local_file.path()).await,
+                not_modified,
+                size: Some(size),
+            })
+        }
+        Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+            id: args.id,
+            sig: local_file.sig(),
+        }),
+        Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
+    }
+}
+
+pub async fn read_files(
+    state: Arc<ServerState>,
+    args: &ReadFilesArgs,
+) -> Result<FilesContentsArgs, io::Error> {
+    debug!("handler::read_files: {:?}", args);
+    let paths = &allowed_paths(&state).await;
+
+    // Determine up front which files fit within our limit so that we can
+    // read all of the remaining files concurrently
+    let mut remaining = args.max_total_bytes;
+    let mut within_limit = Vec::new();
+    for path in args.paths.iter() {
+        let fits = match remaining {
+            Some(bytes) => {
+                let metadata = match paths.open_read(path.to_path_buf()).await {
+                    Ok(file) => file.metadata().await,
+                    Err(x) => Err(x),
+                };
+                match metadata {
+                    Ok(metadata) if metadata.len() <= bytes => {
+                        remaining = Some(bytes - metadata.len());
+                        true
+                    }
+                    Ok(_) => false,
+
+                    // Let the read itself report the error
+                    Err(_) => true,
+                }
+            }
+            None => true,
+        };
+        within_limit.push(fits);
     }
+
+    let files = future::join_all(args.paths.iter().zip(within_limit).map(
+        |(path, fits)| async move {
+            let status = if fits {
+                match paths.read(path.to_path_buf()).await {
+                    Ok(contents) => FileReadStatus::Read { contents },
+                    Err(x) => FileReadStatus::Failed { error: x.into() },
+                }
+            } else {
+                FileReadStatus::LimitExceeded
+            };
+
+            FileReadResult {
+                path: path.clone(),
+                status,
+            }
+        },
+    ))
+    .await;
+
+    Ok(FilesContentsArgs { files })
 }
 
 pub async fn write_file(
@@ -203,25 +433,472 @@ pub async fn write_file(
     debug!("handler::write_file: {:?}", args);
     state.touch_file_id(args.id).await;
 
-    match state.fs_manager.lock().await.get_mut(args.id) {
-        Some(local_file) => {
-            match local_file.write_all(args.sig, &args.contents).await {
-                Ok(_) => Ok(FileWrittenArgs {
-                    id: args.id,
-                    sig: local_file.sig(),
-                }),
-                Err(LocalFileError::SigMismatch) => {
-                    Err(FileIoError::SigMismatch {
-                        id: args.id,
-                        sig: local_file.sig(),
-                    })
-                }
-                Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
+    let file = shared_file(&state, args.id).await?;
+    let mut local_file = file.lock().await;
+
+    check_preconditions(
+        args.preconditions.as_ref(),
+        &local_file.path(),
+        None,
+        Some(local_file.sig()),
+    )
+    .await?;
+
+    match local_file.write_all(args.sig, &args.contents).await {
+        Ok(_) => Ok(FileWrittenArgs {
+            id: args.id,
+            sig: local_file.sig(),
+            etag: written_etag(&local_file, &args.contents).await,
+            modified: modified_secs(&local_file.path()).await,
+        }),
+        Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+            id: args.id,
+            sig: local_file.sig(),
+        }),
+        Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
+    }
+}
+
+/// Produces the etag of `local_file` after `contents` were written to it,
+/// which for a file opened to append means reading it back in full
+///
+/// As the write itself succeeded, a file that cannot be read back yields an
+/// empty etag, which never matches, rather than failing
+async fn written_etag(local_file: &LocalFile, contents: &[u8]) -> String {
+    if !local_file.modes().append {
+        return format!("{:x}", Sha256::digest(contents));
+    }
+
+    match local_file.allowed_paths().read(local_file.path()).await {
+        Ok(data) => format!("{:x}", Sha256::digest(&data)),
+        Err(_) => String::new(),
+    }
+}
+
+pub async fn write_file_atomic_by_path(
+    state: Arc<ServerState>,
+    args: &WriteFileAtomicByPathArgs,
+) -> Result<AtomicFileWrittenArgs, FileIoError> {
+    debug!("handler::write_file_atomic_by_path: {:?}", args);
+
+    let mut fs_manager = state.fs_manager.lock().await;
+    let path = args.path.to_path_buf();
+    let sig =
+        write_all_by_path(&state, &mut fs_manager, &path, &args.data).await?;
+
+    if let Some(mode) = args.mode {
+        fs_manager
+            .allowed_paths()
+            .set_mode(&path, mode)
+            .await
+            .map_err(FileIoError::Io)?;
+    }
+
+    Ok(AtomicFileWrittenArgs {
+        path: args.path.clone(),
+        sig,
+        hash: format!("{:x}", Sha256::digest(&args.data)),
+    })
+}
+
+/// Replaces the contents of the file at `path` with `data`, returning the new
+/// signature of the file
+///
+/// If the file is already open elsewhere, we write through the existing
+/// handle and leave it open; otherwise, we close it once finished
+async fn write_all_by_path(
+    state: &ServerState,
+    fs_manager: &mut FileSystemManager,
+    path: &Path,
+    data: &[u8],
+) -> Result<u32, FileIoError> {
+    let (handle, was_open) =
+        open_by_path(fs_manager, path, true, false).await?;
+
+    let result = match fs_manager.get(handle.id) {
+        Some(file) => {
+            let mut local_file = file.lock().await;
+            match local_file.write_all(handle.sig, data).await {
+                Ok(_) => Ok(local_file.sig()),
+                Err(x) => Err(local_file_error(handle.id, &local_file, x)),
             }
         }
         None => Err(FileIoError::Io(
-            IoErrorArgs::invalid_file_id(args.id).into(),
+            IoErrorArgs::invalid_file_id(handle.id).into(),
+        )),
+    };
+
+    release_by_path(state, fs_manager, handle.id, was_open).await?;
+    result
+}
+
+/// Opens the file at `path` for writing (and reading if `read`), using the
+/// handle already open to the file if there is one, and yields the handle
+/// alongside whether it was already open
+async fn open_by_path(
+    fs_manager: &mut FileSystemManager,
+    path: &Path,
+    create: bool,
+    read: bool,
+) -> Result<(LocalFileHandle, bool), FileIoError> {
+    // NOTE: A file only open to append is not reused, so rather than looking
+    //       up the path, check whether opening the file added a new handle
+    let file_cnt = fs_manager.file_cnt();
+    let handle = fs_manager
+        .open_file(path, create, true, read)
+        .await
+        .map_err(FileIoError::Io)?;
+    Ok((handle, fs_manager.file_cnt() == file_cnt))
+}
+
+/// Closes the file with `id` opened by `open_by_path` unless it was already
+/// open, in which case it is only touched so that it does not expire
+///
+/// NOTE: The manager stays locked while waiting on the file to close it,
+///       which is fine as no operation on a file waits on the manager
+async fn release_by_path(
+    state: &ServerState,
+    fs_manager: &mut FileSystemManager,
+    id: u32,
+    was_open: bool,
+) -> Result<(), FileIoError> {
+    if was_open {
+        state.touch_file_id(id).await;
+    } else if let Some(file) = fs_manager.get(id) {
+        let handle = file.lock().await.handle();
+        fs_manager
+            .close_file(handle)
+            .await
+            .map_err(FileIoError::Io)?;
+    }
+
+    Ok(())
+}
+
+/// Converts an error from an operation on `local_file` with `id`
+fn local_file_error(
+    id: u32,
+    local_file: &LocalFile,
+    error: LocalFileError,
+) -> FileIoError {
+    match error {
+        LocalFileError::SigMismatch => FileIoError::SigMismatch {
+            id,
+            sig: local_file.sig(),
+        },
+        LocalFileError::IoError(x) => FileIoError::Io(x),
+    }
+}
+
+pub async fn patch_file_lines(
+    state: Arc<ServerState>,
+    args: &PatchFileLinesArgs,
+) -> Result<FileLinesPatchedArgs, FileIoError> {
+    debug!("handler::patch_file_lines: {:?}", args);
+
+    // NOTE: The file is read and written through the same handle, which stays
+    //       locked in between, so a write through a handle already open to
+    //       the file (which does not need the manager once it has the file)
+    //       either lands before the patch reads the file or after it is done
+    let mut fs_manager = state.fs_manager.lock().await;
+    let path = args.path.to_path_buf();
+    let (handle, was_open) =
+        open_by_path(&mut fs_manager, &path, false, true).await?;
+
+    let result = match fs_manager.get(handle.id) {
+        Some(file) => {
+            let mut local_file = file.lock().await;
+            patch_local_file(&mut local_file, handle.id, args).await
+        }
+        None => Err(FileIoError::Io(
+            IoErrorArgs::invalid_file_id(handle.id).into(),
         )),
+    };
+
+    release_by_path(&state, &mut fs_manager, handle.id, was_open).await?;
+    result
+}
+
+/// Applies the edits of `args` to `local_file`, which is already locked
+async fn patch_local_file(
+    local_file: &mut LocalFile,
+    id: u32,
+    args: &PatchFileLinesArgs,
+) -> Result<FileLinesPatchedArgs, FileIoError> {
+    // NOTE: The patch applies to the file as it is once locked, which may
+    //       differ from when it was opened if another write got there first
+    let sig = local_file.sig();
+    let data = local_file
+        .read_all(sig)
+        .await
+        .map_err(|x| local_file_error(id, local_file, x))?;
+
+    if let Some(expected) = &args.expected_hash {
+        let actual = format!("{:x}", Sha256::digest(&data));
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(FileIoError::PreconditionFailed(
+                PreconditionFailedArgs {
+                    path: args.path.clone(),
+                    precondition: FailedPrecondition::Hash {
+                        expected: expected.clone(),
+                        actual,
+                    },
+                },
+            ));
+        }
+    }
+
+    let text = String::from_utf8(data).map_err(|x| {
+        FileIoError::Io(io::Error::new(io::ErrorKind::InvalidData, x))
+    })?;
+    let (patched, line_count) =
+        apply_line_edits(&text, &args.edits).map_err(FileIoError::Io)?;
+
+    local_file
+        .write_all(sig, patched.as_bytes())
+        .await
+        .map_err(|x| local_file_error(id, local_file, x))?;
+
+    Ok(FileLinesPatchedArgs {
+        path: args.path.clone(),
+        sig: local_file.sig(),
+        hash: format!("{:x}", Sha256::digest(patched.as_bytes())),
+        line_count: line_count as u64,
+    })
+}
+
+/// Applies `edits` in order to the lines of `text`, returning the patched
+/// text and its total lines
+///
+/// Inserted lines use the line ending style of the text, defaulting to LF,
+/// and the presence of a trailing line ending is preserved
+fn apply_line_edits(
+    text: &str,
+    edits: &[LineEdit],
+) -> io::Result<(String, usize)> {
+    let newline = match sniff::detect_line_ending(text.as_bytes()) {
+        sniff::LineEndingStyle::CrLf => "\r\n",
+        sniff::LineEndingStyle::Cr => "\r",
+        _ => "\n",
+    };
+    let trailing_newline = text.is_empty() || text.ends_with(newline);
+
+    let mut lines: Vec<String> =
+        text.split(newline).map(String::from).collect();
+    if trailing_newline {
+        lines.pop();
+    }
+
+    for edit in edits {
+        let (start, end, new_lines) = match edit {
+            LineEdit::Insert { line, lines } => {
+                (*line, *line, lines.as_slice())
+            }
+            LineEdit::Delete { start, end } => (*start, *end, &[][..]),
+            LineEdit::Replace { start, end, lines } => {
+                (*start, *end, lines.as_slice())
+            }
+        };
+
+        if start > end || end > lines.len() as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Line range {}..{} is out of bounds for {} lines",
+                    start,
+                    end,
+                    lines.len()
+                ),
+            ));
+        }
+
+        lines.splice(start as usize..end as usize, new_lines.iter().cloned());
+    }
+
+    let mut patched = lines.join(newline);
+    if trailing_newline && !lines.is_empty() {
+        patched.push_str(newline);
+    }
+
+    Ok((patched, lines.len()))
+}
+
+pub async fn upload_manifest(
+    state: Arc<ServerState>,
+    args: &UploadManifestArgs,
+) -> Result<UploadManifestPreparedArgs, io::Error> {
+    debug!("handler::upload_manifest: {:?}", args);
+
+    let mut sessions = Vec::new();
+    for file in args.files.iter() {
+        let status = prepare_upload(Arc::clone(&state), file)
+            .await
+            .unwrap_or_else(|x| UploadSessionStatus::Failed {
+                error: x.into(),
+            });
+
+        sessions.push(UploadSession {
+            path: file.path.clone(),
+            status,
+        });
+    }
+
+    Ok(UploadManifestPreparedArgs { sessions })
+}
+
+/// Creates the parent directories of a manifest file and opens the file for
+/// writing, unless it already exists with the expected size and hash
+///
+/// When the file lists its chunks, the chunks that the server already holds
+/// are placed in it once it is opened so that only the rest need to be sent.
+async fn prepare_upload(
+    state: Arc<ServerState>,
+    file: &ManifestFile,
+) -> io::Result<UploadSessionStatus> {
+    let path = file.path.to_path_buf();
+    let paths = allowed_paths(&state).await;
+
+    if let Some(hash) = file.hash.as_ref() {
+        if is_up_to_date(&paths, &path, file.size, hash).await {
+            return Ok(UploadSessionStatus::UpToDate);
+        }
+    }
+
+    let upload = if file.chunks.is_empty() {
+        None
+    } else {
+        Some(ChunkedUpload::new(file.size, &file.chunks)?)
+    };
+
+    let mut have_chunks = Vec::new();
+    let handle = {
+        let mut fs_manager = state.fs_manager.lock().await;
+
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs_manager.create_dir(parent, true).await?;
+            }
+        }
+
+        let file_cnt = fs_manager.file_cnt();
+        let mut handle = fs_manager.open_file(&path, true, true, true).await?;
+
+        // NOTE: Chunks are placed through the handle shared by every request
+        //       on the file, which stays locked until every chunk is in place
+        let file = fs_manager.get(handle.id);
+        if let (Some(upload), Some(file)) = (upload.as_ref(), file) {
+            let mut local_file = file.lock().await;
+            let result = state
+                .chunks
+                .place_known_chunks(&mut local_file, upload)
+                .await;
+            handle = local_file.handle();
+            drop(local_file);
+
+            match result {
+                Ok(x) => have_chunks = x,
+                Err(x) => {
+                    if fs_manager.file_cnt() != file_cnt {
+                        fs_manager.close_file(handle).await?;
+                    }
+                    return Err(x);
+                }
+            }
+        }
+
+        handle
+    };
+
+    if let Some(mode) = file.mode {
+        paths.set_mode(&path, mode).await?;
+    }
+
+    if let Some(upload) = upload {
+        state.chunks.start_upload(handle.id, upload).await;
+    }
+
+    state.touch_file_id(handle.id).await;
+
+    Ok(UploadSessionStatus::Ready {
+        id: handle.id,
+        sig: handle.sig,
+        have_chunks,
+    })
+}
+
+/// Writes a chunk of a file being uploaded in chunks at its offset, after
+/// verifying that it has the size and hash the manifest listed for it
+pub async fn write_upload_chunk(
+    state: Arc<ServerState>,
+    args: &WriteUploadChunkArgs,
+) -> Result<UploadChunkWrittenArgs, FileIoError> {
+    debug!("handler::write_upload_chunk: {:?}", args);
+    state.touch_file_id(args.id).await;
+
+    let file = shared_file(&state, args.id).await?;
+    let (offset, len, hash) =
+        match state.chunks.expected_chunk(args.id, args.index).await {
+            Some(chunk) => chunk,
+            None => {
+                return Err(FileIoError::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "File {} is not expecting a chunk at {}",
+                        args.id, args.index
+                    ),
+                )))
+            }
+        };
+
+    if args.data.len() as u64 != len || hash_chunk(&args.data) != hash {
+        return Err(FileIoError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Chunk {} does not match its hash", args.index),
+        )));
+    }
+
+    let mut local_file = file.lock().await;
+    match local_file.write_at(args.sig, offset, &args.data).await {
+        Ok(_) => {
+            state
+                .chunks
+                .remember(&hash, local_file.path(), offset, len)
+                .await;
+            Ok(UploadChunkWrittenArgs {
+                id: args.id,
+                sig: local_file.sig(),
+                index: args.index,
+            })
+        }
+        Err(LocalFileError::SigMismatch) => Err(FileIoError::SigMismatch {
+            id: args.id,
+            sig: local_file.sig(),
+        }),
+        Err(LocalFileError::IoError(x)) => Err(FileIoError::Io(x)),
+    }
+}
+
+/// Determines if the file at `path` already has the given size and
+/// hex-encoded SHA-256 `hash`
+async fn is_up_to_date(
+    paths: &AllowedPaths,
+    path: &Path,
+    size: u64,
+    hash: &str,
+) -> bool {
+    let metadata = match paths.open_read(path).await {
+        Ok(file) => file.metadata().await,
+        Err(x) => Err(x),
+    };
+    match metadata {
+        Ok(metadata) if metadata.is_file() && metadata.len() == size => {
+            match paths.read(path).await {
+                Ok(contents) => format!("{:x}", Sha256::digest(&contents))
+                    .eq_ignore_ascii_case(hash),
+                Err(_) => false,
+            }
+        }
+        _ => false,
     }
 }
 
@@ -235,7 +912,11 @@ pub async fn create_dir(
         .fs_manager
         .lock()
         .await
-        .create_dir(&args.path, args.include_components)
+        .create_dir_with_mode(
+            args.path.to_path_buf(),
+            args.include_components,
+            args.mode,
+        )
         .await?;
 
     Ok(DirCreatedArgs {
@@ -253,7 +934,7 @@ pub async fn rename_dir(
         .fs_manager
         .lock()
         .await
-        .rename_dir(&args.from, &args.to)
+        .rename_dir(args.from.to_path_buf(), args.to.to_path_buf())
         .await?;
 
     Ok(DirRenamedArgs {
@@ -272,7 +953,7 @@ pub async fn remove_dir(
         .fs_manager
         .lock()
         .await
-        .remove_dir(&args.path, args.non_empty)
+        .remove_dir(args.path.to_path_buf(), args.non_empty)
         .await?;
 
     Ok(DirRemovedArgs {
@@ -280,23 +961,100 @@ pub async fn remove_dir(
     })
 }
 
+pub async fn create_paths(
+    state: Arc<ServerState>,
+    args: &CreatePathsArgs,
+) -> Result<PathsCreatedArgs, io::Error> {
+    debug!("handler::create_paths: {:?}", args);
+
+    let mut fs_manager = state.fs_manager.lock().await;
+    let paths = fs_manager.allowed_paths();
+
+    let mut dirs = Vec::new();
+    for path in args.dirs.iter() {
+        let local_path = path.to_path_buf();
+        let status = if paths.is_dir(&local_path).await {
+            PathCreateStatus::AlreadyExists
+        } else {
+            match fs_manager.create_dir(&local_path, true).await {
+                Ok(_) => PathCreateStatus::Created,
+                Err(x) => PathCreateStatus::Failed { error: x.into() },
+            }
+        };
+        dirs.push(PathCreateResult {
+            path: path.clone(),
+            status,
+        });
+    }
+
+    let mut files = Vec::new();
+    for path in args.files.iter() {
+        let local_path = path.to_path_buf();
+        let status = match create_empty_file(&mut fs_manager, &local_path).await
+        {
+            Ok(_) => PathCreateStatus::Created,
+            Err(x) if x.kind() == io::ErrorKind::AlreadyExists => {
+                if paths.is_file(&local_path).await {
+                    PathCreateStatus::AlreadyExists
+                } else {
+                    PathCreateStatus::Failed { error: x.into() }
+                }
+            }
+            Err(x) => PathCreateStatus::Failed { error: x.into() },
+        };
+        files.push(PathCreateResult {
+            path: path.clone(),
+            status,
+        });
+    }
+
+    Ok(PathsCreatedArgs { dirs, files })
+}
+
+/// Creates an empty file at `path` along with its missing parent dirs,
+/// failing if anything already exists at `path`
+async fn create_empty_file(
+    fs_manager: &mut FileSystemManager,
+    path: &Path,
+) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs_manager.create_dir(parent, true).await?;
+        }
+    }
+
+    let handle = fs_manager
+        .open_file_with_modes(
+            path,
+            true,
+            true,
+            false,
+            LocalFileModes {
+                create_new: true,
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+    fs_manager.close_file(handle).await?;
+    Ok(())
+}
+
 pub async fn list_dir_contents(
     state: Arc<ServerState>,
     args: &ListDirContentsArgs,
 ) -> Result<DirContentsListArgs, io::Error> {
     debug!("handler::list_dir_contents: {:?}", args);
 
-    let local_entries = state
+    let entries = state
         .fs_manager
         .lock()
         .await
-        .dir_entries(&args.path)
-        .await?;
-
-    let entries = local_entries
+        .dir_entries(args.path.to_path_buf())
+        .await?
         .into_iter()
-        .map(DirEntry::try_from)
-        .collect::<io::Result<Vec<DirEntry>>>()?;
+        .map(DirEntry::from)
+        .collect();
 
     Ok(DirContentsListArgs {
         path: args.path.clone(),
@@ -304,63 +1062,358 @@ pub async fn list_dir_contents(
     })
 }
 
-impl TryFrom<LocalDirEntry> for DirEntry {
-    type Error = io::Error;
-
-    fn try_from(local_dir_entry: LocalDirEntry) -> Result<Self, Self::Error> {
-        Ok(Self {
-            path: local_dir_entry
-                .path
-                .into_os_string()
-                .into_string()
-                .map_err(|_| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "OS String does not contain valid unicode",
-                    )
-                })?,
-            is_file: local_dir_entry.is_file,
-            is_dir: local_dir_entry.is_dir,
-            is_symlink: local_dir_entry.is_symlink,
-        })
-    }
+pub async fn resolve_path(
+    _state: Arc<ServerState>,
+    args: &ResolvePathArgs,
+) -> Result<PathResolvedArgs, io::Error> {
+    debug!("handler::resolve_path: {:?}", args);
+
+    let path =
+        crate::core::server::fs::resolve_path(args.path.to_path_buf()).await?;
+    let root = crate::core::server::fs::resolve_path(".").await?;
+    let metadata = tokio::fs::metadata(&path).await.ok();
+    let is_symlink = tokio::fs::symlink_metadata(args.path.to_path_buf())
+        .await
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or_default();
+
+    Ok(PathResolvedArgs {
+        path: RemotePath::from_path(&path),
+        exists: metadata.is_some(),
+        is_file: metadata.as_ref().map(|m| m.is_file()).unwrap_or_default(),
+        is_dir: metadata.as_ref().map(|m| m.is_dir()).unwrap_or_default(),
+        is_symlink,
+        in_root: path.starts_with(root),
+    })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io;
-    use tokio::fs;
+/// Default number of bytes read from the start of a file when sniffing
+pub const DEFAULT_SNIFF_MAX_BYTES: u64 = 8192;
 
-    #[tokio::test]
-    async fn open_file_should_return_success_if_create_flag_set_and_opening_new_file(
-    ) {
-        let state = Arc::new(ServerState::default());
-        let tmp_path = tempfile::NamedTempFile::new()
-            .unwrap()
-            .into_temp_path()
-            .to_string_lossy()
-            .to_string();
+pub async fn sniff_file(
+    state: Arc<ServerState>,
+    args: &SniffFileArgs,
+) -> Result<FileSniffedArgs, io::Error> {
+    debug!("handler::sniff_file: {:?}", args);
 
-        let args = open_file(
-            Arc::clone(&state),
-            &OpenFileArgs {
-                path: tmp_path.clone(),
-                create_if_missing: true,
-                write_access: true,
-                read_access: true,
-            },
-        )
+    let max_bytes = args.max_bytes.unwrap_or(DEFAULT_SNIFF_MAX_BYTES);
+    let mut bytes = Vec::new();
+    allowed_paths(&state)
         .await
-        .unwrap();
+        .open_read(args.path.to_path_buf())
+        .await?
+        .take(max_bytes)
+        .read_to_end(&mut bytes)
+        .await?;
 
-        let x = state.fs_manager.lock().await;
-        let local_file = x.get(args.id).unwrap();
-        assert_eq!(args.sig, local_file.sig());
-        assert_eq!(args.path, tmp_path);
-        assert!(args.write);
-        assert!(args.read);
-    }
+    Ok(FileSniffedArgs {
+        path: args.path.clone(),
+        bytes_read: bytes.len() as u64,
+        mime_type: sniff::detect_mime_type(&bytes).to_string(),
+        is_binary: sniff::is_binary(&bytes),
+        line_ending: From::from(sniff::detect_line_ending(&bytes)),
+    })
+}
+
+pub async fn get_file_checksum(
+    state: Arc<ServerState>,
+    args: &GetFileChecksumArgs,
+) -> Result<FileChecksumArgs, io::Error> {
+    debug!("handler::get_file_checksum: {:?}", args);
+
+    let (digest, size) = checksum::checksum_file(
+        &allowed_paths(&state).await,
+        args.path.to_path_buf(),
+        args.algorithm,
+    )
+    .await?;
+
+    Ok(FileChecksumArgs {
+        path: args.path.clone(),
+        algorithm: args.algorithm,
+        digest,
+        size,
+    })
+}
+
+/// Default maximum size of either file compared when diffing
+pub const DEFAULT_DIFF_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Default number of unchanged lines included around each change of a diff
+pub const DEFAULT_DIFF_CONTEXT_LINES: u32 = 3;
+
+pub async fn diff_files(
+    state: Arc<ServerState>,
+    args: &DiffFilesArgs,
+) -> Result<FilesDiffedArgs, io::Error> {
+    debug!("handler::diff_files: {:?}", args);
+    let paths = allowed_paths(&state).await;
+
+    let max_bytes = args.max_bytes.unwrap_or(DEFAULT_DIFF_MAX_BYTES);
+    let check_size = |path: &RemotePath, len: u64| {
+        if len > max_bytes {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is {} bytes, exceeding the diff limit of {} bytes",
+                    path, len, max_bytes
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    let mut file_a = paths.open_read(args.path_a.to_path_buf()).await?;
+    check_size(&args.path_a, file_a.metadata().await?.len())?;
+    let mut contents_a = Vec::new();
+    file_a.read_to_end(&mut contents_a).await?;
+    let contents_b = match args.contents_b.as_ref() {
+        Some(contents) => {
+            check_size(&args.path_b, contents.len() as u64)?;
+            contents.clone()
+        }
+        None => {
+            let mut file_b = paths.open_read(args.path_b.to_path_buf()).await?;
+            check_size(&args.path_b, file_b.metadata().await?.len())?;
+            let mut contents_b = Vec::new();
+            file_b.read_to_end(&mut contents_b).await?;
+            contents_b
+        }
+    };
+
+    let identical = contents_a == contents_b;
+    let is_binary =
+        sniff::is_binary(&contents_a) || sniff::is_binary(&contents_b);
+    let diff = if identical || is_binary {
+        diff::UnifiedDiff::default()
+    } else {
+        let label_a = args.path_a.to_string_lossy().into_owned();
+        let label_b = args.path_b.to_string_lossy().into_owned();
+        let context_lines =
+            args.context_lines.unwrap_or(DEFAULT_DIFF_CONTEXT_LINES) as usize;
+
+        // Diffing is quadratic in the worst case, so keep it off the runtime
+        tokio::task::spawn_blocking(move || {
+            diff::unified_diff(
+                &String::from_utf8_lossy(&contents_a),
+                &String::from_utf8_lossy(&contents_b),
+                &label_a,
+                &label_b,
+                context_lines,
+            )
+        })
+        .await
+        .map_err(io::Error::other)?
+    };
+
+    Ok(FilesDiffedArgs {
+        path_a: args.path_a.clone(),
+        path_b: args.path_b.clone(),
+        identical,
+        is_binary,
+        diff: diff.text,
+        lines_added: diff.lines_added,
+        lines_removed: diff.lines_removed,
+    })
+}
+
+pub async fn recent_fs_events(
+    state: Arc<ServerState>,
+    args: &RecentFsEventsArgs,
+) -> FsEventsArgs {
+    debug!("handler::recent_fs_events: {:?}", args);
+
+    state.fs_events.since(args.since).await
+}
+
+pub async fn list_mounts(
+    _state: Arc<ServerState>,
+    args: &ListMountsArgs,
+) -> Result<MountsListArgs, io::Error> {
+    debug!("handler::list_mounts: {:?}", args);
+
+    Ok(MountsListArgs {
+        mounts: mounts::list_mounts(args.include_pseudo)?,
+    })
+}
+
+/// Determines the change that `request` would make to the filesystem if it
+/// succeeds, resolving the paths of open files and checking whether files
+/// created by the request exist beforehand
+pub async fn fs_change_of(
+    state: &ServerState,
+    request: &Request,
+) -> Option<FsChange> {
+    let path_of_file = |id: u32| async move {
+        let path = state.fs_manager.lock().await.path_of(id)?;
+        Some(RemotePath::from_path(path))
+    };
+    let exists = |path: &RemotePath| {
+        let path = path.to_path_buf();
+        async move { tokio::fs::metadata(path).await.is_ok() }
+    };
+
+    match request {
+        Request::CreateDir(args) => {
+            Some(FsChange::new(FsEventOp::Created, &args.path))
+        }
+        Request::RenameDir(args) => {
+            Some(FsChange::renamed(&args.from, &args.to))
+        }
+        Request::RemoveDir(args) => {
+            Some(FsChange::new(FsEventOp::Removed, &args.path))
+        }
+        Request::OpenFile(args)
+            if (args.create_if_missing || args.modes.create_new)
+                && !exists(&args.path).await =>
+        {
+            Some(FsChange::new(FsEventOp::Created, &args.path))
+        }
+        Request::OpenFile(args) if args.modes.truncate => {
+            Some(FsChange::new(FsEventOp::Modified, &args.path))
+        }
+        Request::WriteFile(args) => path_of_file(args.id)
+            .await
+            .map(|path| FsChange::new(FsEventOp::Modified, path)),
+        Request::WriteUploadChunk(args) => path_of_file(args.id)
+            .await
+            .map(|path| FsChange::new(FsEventOp::Modified, path)),
+        Request::WriteFileAtomicByPath(args) => {
+            let op = if exists(&args.path).await {
+                FsEventOp::Modified
+            } else {
+                FsEventOp::Created
+            };
+            Some(FsChange::new(op, &args.path))
+        }
+        Request::PatchFileLines(args) => {
+            Some(FsChange::new(FsEventOp::Modified, &args.path))
+        }
+        Request::RenameFile(args) => path_of_file(args.id)
+            .await
+            .map(|path| FsChange::renamed(path, &args.to)),
+        Request::RenameUnopenedFile(args) => {
+            Some(FsChange::renamed(&args.from, &args.to))
+        }
+        Request::RemoveFile(args) => path_of_file(args.id)
+            .await
+            .map(|path| FsChange::new(FsEventOp::Removed, path)),
+        Request::RemoveUnopenedFile(args) => {
+            Some(FsChange::new(FsEventOp::Removed, &args.path))
+        }
+        _ => None,
+    }
+}
+
+impl From<sniff::LineEndingStyle> for LineEnding {
+    fn from(style: sniff::LineEndingStyle) -> Self {
+        match style {
+            sniff::LineEndingStyle::None => Self::None,
+            sniff::LineEndingStyle::Lf => Self::Lf,
+            sniff::LineEndingStyle::CrLf => Self::CrLf,
+            sniff::LineEndingStyle::Cr => Self::Cr,
+            sniff::LineEndingStyle::Mixed => Self::Mixed,
+        }
+    }
+}
+
+/// Default number of entries sent per chunk when streaming dir contents
+pub const DEFAULT_DIR_STREAM_CHUNK_SIZE: usize = 1000;
+
+/// Lists the contents of a directory, sending every `chunk_size` entries as
+/// a partial reply via `partial_tx` as the directory is read and returning
+/// the final chunk
+pub async fn list_dir_contents_stream(
+    state: Arc<ServerState>,
+    args: &ListDirContentsArgs,
+    chunk_size: usize,
+    mut partial_tx: mpsc::Sender<Reply>,
+) -> Result<DirContentsListChunkArgs, io::Error> {
+    debug!("handler::list_dir_contents_stream: {:?}", args);
+
+    let mut entries = Vec::new();
+    let mut dir_entries = allowed_paths(&state)
+        .await
+        .read_dir(args.path.to_path_buf())
+        .await?;
+    while let Some(local_entry) = dir_entries.next_entry().await? {
+        entries.push(DirEntry::from(local_entry));
+
+        if entries.len() >= chunk_size {
+            let chunk = DirContentsListChunkArgs {
+                path: args.path.clone(),
+                entries: std::mem::take(&mut entries),
+                last: false,
+            };
+
+            partial_tx
+                .send(Reply::DirContentsListChunk(chunk))
+                .await
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "Failed to send partial reply",
+                    )
+                })?;
+        }
+    }
+
+    Ok(DirContentsListChunkArgs {
+        path: args.path.clone(),
+        entries,
+        last: true,
+    })
+}
+
+impl From<LocalDirEntry> for DirEntry {
+    fn from(local_dir_entry: LocalDirEntry) -> Self {
+        Self {
+            path: RemotePath::from_path(local_dir_entry.path),
+            is_file: local_dir_entry.is_file,
+            is_dir: local_dir_entry.is_dir,
+            is_symlink: local_dir_entry.is_symlink,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use tokio::fs;
+
+    #[tokio::test]
+    async fn open_file_should_return_success_if_create_flag_set_and_opening_new_file(
+    ) {
+        let state = Arc::new(ServerState::default());
+        let tmp_path = tempfile::NamedTempFile::new()
+            .unwrap()
+            .into_temp_path()
+            .to_string_lossy()
+            .to_string();
+
+        let args = open_file(
+            Arc::clone(&state),
+            &OpenFileArgs {
+                path: tmp_path.clone().into(),
+                create_if_missing: true,
+                write_access: true,
+                read_access: true,
+                mode: None,
+                modes: Default::default(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let file = state.fs_manager.lock().await.get(args.id).unwrap();
+        let local_file = file.lock().await;
+        assert_eq!(args.sig, local_file.sig());
+        assert_eq!(args.path, tmp_path);
+        assert!(args.write);
+        assert!(args.read);
+    }
 
     #[tokio::test]
     async fn open_file_should_return_success_opening_existing_file() {
@@ -372,17 +1425,19 @@ mod tests {
         let args = open_file(
             Arc::clone(&state),
             &OpenFileArgs {
-                path: tmp_file_path.clone(),
+                path: tmp_file_path.clone().into(),
                 create_if_missing: false,
                 write_access: true,
                 read_access: true,
+                mode: None,
+                modes: Default::default(),
             },
         )
         .await
         .unwrap();
 
-        let x = state.fs_manager.lock().await;
-        let local_file = x.get(args.id).unwrap();
+        let file = state.fs_manager.lock().await.get(args.id).unwrap();
+        let local_file = file.lock().await;
         assert_eq!(args.sig, local_file.sig());
         assert_eq!(args.path, tmp_file_path);
         assert!(args.write);
@@ -403,10 +1458,12 @@ mod tests {
         let err = open_file(
             Arc::clone(&state),
             &OpenFileArgs {
-                path: tmp_path,
+                path: tmp_path.into(),
                 create_if_missing: false,
                 write_access: true,
                 read_access: true,
+                mode: None,
+                modes: Default::default(),
             },
         )
         .await
@@ -500,14 +1557,20 @@ mod tests {
         let err = rename_unopened_file(
             Arc::clone(&state),
             &RenameUnopenedFileArgs {
-                from: file.as_ref().to_string_lossy().to_string(),
-                to: file.as_ref().to_string_lossy().to_string(),
+                from: file.as_ref().into(),
+                to: file.as_ref().into(),
+                preconditions: None,
             },
         )
         .await
         .unwrap_err();
 
-        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        match err {
+            FileIoError::Io(x) => {
+                assert_eq!(x.kind(), io::ErrorKind::InvalidData);
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
     }
 
     #[tokio::test]
@@ -521,8 +1584,9 @@ mod tests {
         let args = rename_unopened_file(
             Arc::clone(&state),
             &RenameUnopenedFileArgs {
-                from: from_path_str.clone(),
-                to: to_path_str.clone(),
+                from: from_path_str.clone().into(),
+                to: to_path_str.clone().into(),
+                preconditions: None,
             },
         )
         .await
@@ -560,7 +1624,8 @@ mod tests {
             &RenameFileArgs {
                 id: handle.id + 1,
                 sig: handle.sig,
-                to: new_path_str.clone(),
+                to: new_path_str.clone().into(),
+                preconditions: None,
             },
         )
         .await
@@ -603,7 +1668,8 @@ mod tests {
             &RenameFileArgs {
                 id: handle.id,
                 sig: handle.sig + 1,
-                to: new_path_str.clone(),
+                to: new_path_str.clone().into(),
+                preconditions: None,
             },
         )
         .await
@@ -640,15 +1706,15 @@ mod tests {
             .open_file(file.as_ref(), false, false, true)
             .await
             .expect("Failed to open file");
-        let new_path_str =
-            format!("{}.2", file.as_ref().to_string_lossy().to_string());
+        let new_path_str = format!("{}.2", file.as_ref().to_string_lossy());
 
         let args = rename_file(
             Arc::clone(&state),
             &RenameFileArgs {
                 id: handle.id,
                 sig: handle.sig,
-                to: new_path_str.clone(),
+                to: new_path_str.clone().into(),
+                preconditions: None,
             },
         )
         .await
@@ -684,7 +1750,8 @@ mod tests {
         let err = remove_unopened_file(
             Arc::clone(&state),
             &RemoveUnopenedFileArgs {
-                path: file.as_ref().to_string_lossy().to_string(),
+                path: file.as_ref().into(),
+                preconditions: None,
             },
         )
         .await
@@ -695,7 +1762,12 @@ mod tests {
             "File unexpectedly removed"
         );
 
-        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        match err {
+            FileIoError::Io(x) => {
+                assert_eq!(x.kind(), io::ErrorKind::InvalidData);
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
     }
 
     #[tokio::test]
@@ -707,7 +1779,8 @@ mod tests {
         let args = remove_unopened_file(
             Arc::clone(&state),
             &RemoveUnopenedFileArgs {
-                path: file.as_ref().to_string_lossy().to_string(),
+                path: file.as_ref().into(),
+                preconditions: None,
             },
         )
         .await
@@ -739,6 +1812,7 @@ mod tests {
             &RemoveFileArgs {
                 id: handle.id + 1,
                 sig: handle.sig,
+                preconditions: None,
             },
         )
         .await
@@ -775,6 +1849,7 @@ mod tests {
             &RemoveFileArgs {
                 id: handle.id,
                 sig: handle.sig + 1,
+                preconditions: None,
             },
         )
         .await
@@ -812,6 +1887,7 @@ mod tests {
             &RemoveFileArgs {
                 id: handle.id,
                 sig: handle.sig,
+                preconditions: None,
             },
         )
         .await
@@ -847,19 +1923,144 @@ mod tests {
         let id = handle.id;
         let sig = handle.sig;
 
-        let args = read_file(Arc::clone(&state), &ReadFileArgs { id, sig })
-            .await
-            .unwrap();
+        let args = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id,
+                sig,
+                if_none_match: None,
+                offset: 0,
+                len: None,
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(args.id, id, "Wrong id returned");
         assert_eq!(args.contents, file_contents);
+        assert_eq!(args.sig, sig);
+        assert_eq!(args.etag, format!("{:x}", Sha256::digest(&file_contents)));
+        assert!(args.modified.is_some(), "Missing modified time");
+        assert!(!args.not_modified, "Unexpectedly not modified");
+    }
+
+    #[tokio::test]
+    async fn read_file_should_omit_contents_if_etag_matches() {
+        let state = Arc::new(ServerState::default());
+        let file_contents = vec![1, 2, 3];
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+
+        use std::io::Write;
+        file.write_all(&file_contents).unwrap();
+        file.flush().unwrap();
+
+        let handle = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file.as_ref(), true, true, true)
+            .await
+            .expect("Unable to open file");
+        let etag = format!("{:x}", Sha256::digest(&file_contents));
+
+        let args = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                if_none_match: Some(etag.clone()),
+                offset: 0,
+                len: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert!(args.not_modified, "Contents unexpectedly modified");
+        assert!(args.contents.is_empty(), "Contents not omitted");
+        assert_eq!(args.etag, etag);
+
+        let args = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                if_none_match: Some(String::from("stale")),
+                offset: 0,
+                len: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!args.not_modified, "Contents unexpectedly not modified");
+        assert_eq!(args.contents, file_contents);
+    }
+
+    #[tokio::test]
+    async fn read_file_should_only_read_range_if_provided() {
+        let state = Arc::new(ServerState::default());
+        let file_contents = b"some contents".to_vec();
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+
+        use std::io::Write;
+        file.write_all(&file_contents).unwrap();
+        file.flush().unwrap();
+
+        let handle = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file.as_ref(), true, true, true)
+            .await
+            .expect("Unable to open file");
+
+        let args = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                if_none_match: None,
+                offset: 5,
+                len: Some(3),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(args.contents, b"con");
+        assert_eq!(args.size, Some(13));
+        assert!(args.etag.is_empty(), "Unexpectedly hashed entire file");
+
+        // Etag of a ranged read is that of the entire file when compared
+        let etag = format!("{:x}", Sha256::digest(&file_contents));
+        let args = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                if_none_match: Some(etag.clone()),
+                offset: 0,
+                len: Some(3),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(args.not_modified, "Contents unexpectedly modified");
+        assert!(args.contents.is_empty(), "Contents not omitted");
+        assert_eq!(args.etag, etag);
     }
 
     #[tokio::test]
     async fn read_file_should_return_error_if_file_not_open() {
         let err = read_file(
             Arc::new(ServerState::default()),
-            &ReadFileArgs { id: 0, sig: 0 },
+            &ReadFileArgs {
+                id: 0,
+                sig: 0,
+                if_none_match: None,
+                offset: 0,
+                len: None,
+            },
         )
         .await
         .unwrap_err();
@@ -883,9 +2084,10 @@ mod tests {
             .read(false)
             .write(true)
             .create(true)
+            .truncate(true)
             .open(tmp_file.path())
             .unwrap();
-        file.write_all(&vec![1, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        file.write_all(&[1, 2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
         file.flush().unwrap();
 
         let handle = state
@@ -898,9 +2100,18 @@ mod tests {
         let id = handle.id;
         let sig = handle.sig;
 
-        let err = read_file(Arc::clone(&state), &ReadFileArgs { id, sig })
-            .await
-            .unwrap_err();
+        let err = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id,
+                sig,
+                if_none_match: None,
+                offset: 0,
+                len: None,
+            },
+        )
+        .await
+        .unwrap_err();
 
         match err {
             FileIoError::Io(x) => {
@@ -925,10 +2136,18 @@ mod tests {
         let id = handle.id;
         let sig = handle.sig;
 
-        let err =
-            read_file(Arc::clone(&state), &ReadFileArgs { id, sig: sig + 1 })
-                .await
-                .unwrap_err();
+        let err = read_file(
+            Arc::clone(&state),
+            &ReadFileArgs {
+                id,
+                sig: sig + 1,
+                if_none_match: None,
+                offset: 0,
+                len: None,
+            },
+        )
+        .await
+        .unwrap_err();
 
         match err {
             FileIoError::SigMismatch {
@@ -942,6 +2161,76 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn read_files_should_return_contents_or_error_per_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"abc").unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let missing = root.as_ref().join("missing");
+
+        let args = read_files(
+            Arc::new(ServerState::default()),
+            &ReadFilesArgs {
+                paths: vec![file.as_ref().into(), missing.as_path().into()],
+                max_total_bytes: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.files.len(), 2);
+        assert_eq!(
+            args.files[0].status,
+            FileReadStatus::Read {
+                contents: b"abc".to_vec()
+            }
+        );
+        match &args.files[1].status {
+            FileReadStatus::Failed { error } => {
+                assert_eq!(error.error_kind, io::ErrorKind::NotFound.into());
+            }
+            x => panic!("Unexpected status: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn read_files_should_skip_files_that_would_exceed_max_total_bytes() {
+        let mut file_1 = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file_1, b"abc").unwrap();
+        let mut file_2 = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file_2, b"defg").unwrap();
+        let mut file_3 = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file_3, b"h").unwrap();
+
+        let args = read_files(
+            Arc::new(ServerState::default()),
+            &ReadFilesArgs {
+                paths: vec![
+                    file_1.as_ref().into(),
+                    file_2.as_ref().into(),
+                    file_3.as_ref().into(),
+                ],
+                max_total_bytes: Some(5),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            args.files[0].status,
+            FileReadStatus::Read {
+                contents: b"abc".to_vec()
+            }
+        );
+        assert_eq!(args.files[1].status, FileReadStatus::LimitExceeded);
+        assert_eq!(
+            args.files[2].status,
+            FileReadStatus::Read {
+                contents: b"h".to_vec()
+            }
+        );
+    }
+
     #[tokio::test]
     async fn write_file_should_return_success_if_write_successful() {
         let state = Arc::new(ServerState::default());
@@ -965,6 +2254,7 @@ mod tests {
                 id,
                 sig,
                 contents: contents.clone(),
+                preconditions: None,
             },
         )
         .await
@@ -972,6 +2262,8 @@ mod tests {
 
         assert_eq!(args.id, id, "Wrong id returned");
         assert_ne!(args.sig, sig);
+        assert_eq!(args.etag, format!("{:x}", Sha256::digest(&contents)));
+        assert!(args.modified.is_some(), "Missing modified time");
 
         use std::io::{Seek, SeekFrom};
         file.seek(SeekFrom::Start(0)).unwrap();
@@ -987,7 +2279,45 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn write_file_should_return_error_if_not_writeable() {
+    async fn write_file_should_return_etag_of_whole_file_if_appending() {
+        let state = Arc::new(ServerState::default());
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.as_ref(), b"abc").await.unwrap();
+
+        let handle = open_file(
+            Arc::clone(&state),
+            &OpenFileArgs {
+                path: file.as_ref().into(),
+                write_access: true,
+                modes: FileOpenModes {
+                    append: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let args = write_file(
+            Arc::clone(&state),
+            &WriteFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                contents: b"def".to_vec(),
+                preconditions: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(fs::read(file.as_ref()).await.unwrap(), b"abcdef");
+        assert_eq!(args.etag, format!("{:x}", Sha256::digest(b"abcdef")));
+    }
+
+    #[tokio::test]
+    async fn write_file_should_return_error_if_not_writeable() {
         let state = Arc::new(ServerState::default());
         let contents = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
 
@@ -1005,7 +2335,12 @@ mod tests {
 
         let err = write_file(
             Arc::clone(&state),
-            &WriteFileArgs { id, sig, contents },
+            &WriteFileArgs {
+                id,
+                sig,
+                contents,
+                preconditions: None,
+            },
         )
         .await
         .unwrap_err();
@@ -1042,6 +2377,7 @@ mod tests {
                 id,
                 sig: sig + 1,
                 contents: contents.clone(),
+                preconditions: None,
             },
         )
         .await
@@ -1060,365 +2396,1820 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn create_dir_should_return_error_if_part_of_path_missing_and_flag_not_set(
-    ) {
-        let root_path = tempfile::tempdir().unwrap();
+    async fn write_file_should_return_error_if_precondition_fails() {
         let state = Arc::new(ServerState::default());
 
-        let dir_path = root_path.as_ref().join("test").join("dir");
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, &[1, 2, 3]).unwrap();
 
-        let err = create_dir(
+        let handle = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file.as_ref(), true, true, true)
+            .await
+            .expect("Unable to open file");
+        let id = handle.id;
+        let sig = handle.sig;
+
+        let err = write_file(
             Arc::clone(&state),
-            &CreateDirArgs {
-                path: dir_path.as_path().to_string_lossy().to_string(),
-                include_components: false,
+            &WriteFileArgs {
+                id,
+                sig,
+                contents: vec![4, 5, 6],
+                preconditions: Some(FilePreconditions {
+                    max_size: Some(2),
+                    ..Default::default()
+                }),
             },
         )
         .await
         .unwrap_err();
 
-        assert!(
-            fs::metadata(&dir_path).await.is_err(),
-            "Dir was unexpectedly created"
-        );
+        match err {
+            FileIoError::PreconditionFailed(args) => {
+                assert_eq!(
+                    args.precondition,
+                    FailedPrecondition::MaxSize {
+                        expected: 2,
+                        actual: 3
+                    }
+                );
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
 
-        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            fs::read(file.as_ref()).await.unwrap(),
+            vec![1, 2, 3],
+            "File unexpectedly written"
+        );
     }
 
     #[tokio::test]
-    async fn create_dir_should_return_confirmation_if_single_level_directory_created(
-    ) {
-        let root_path = tempfile::tempdir().unwrap();
+    async fn write_file_should_succeed_if_all_preconditions_hold() {
         let state = Arc::new(ServerState::default());
 
-        let dir_path = root_path.as_ref().join("test");
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, &[1, 2, 3]).unwrap();
 
-        let args = create_dir(
+        let handle = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file.as_ref(), true, true, true)
+            .await
+            .expect("Unable to open file");
+        let id = handle.id;
+        let sig = handle.sig;
+
+        let args = write_file(
             Arc::clone(&state),
-            &CreateDirArgs {
-                path: dir_path.as_path().to_string_lossy().to_string(),
-                include_components: false,
+            &WriteFileArgs {
+                id,
+                sig,
+                contents: vec![4, 5, 6],
+                preconditions: Some(FilePreconditions {
+                    sig: Some(sig),
+                    min_size: Some(3),
+                    max_size: Some(3),
+                    ..Default::default()
+                }),
             },
         )
         .await
         .unwrap();
 
-        assert!(
-            fs::metadata(&dir_path).await.is_ok(),
-            "Dir was unexpectedly not created"
-        );
-
-        assert_eq!(
-            args.path,
-            dir_path.to_string_lossy().to_string(),
-            "Wrong path returned"
-        );
+        assert_eq!(args.id, id, "Wrong id returned");
+        assert_ne!(args.sig, sig);
     }
 
     #[tokio::test]
-    async fn create_dir_should_return_confirmation_if_multi_level_directory_created(
+    async fn rename_unopened_file_should_return_error_if_destination_exists_and_must_not(
     ) {
-        let root_path = tempfile::tempdir().unwrap();
         let state = Arc::new(ServerState::default());
 
-        let dir_path = root_path.as_ref().join("test").join("dir");
+        let from = tempfile::NamedTempFile::new().unwrap();
+        let to = tempfile::NamedTempFile::new().unwrap();
+        let to_path_str = to.as_ref().to_string_lossy().to_string();
 
-        let args = create_dir(
+        let err = rename_unopened_file(
             Arc::clone(&state),
-            &CreateDirArgs {
-                path: dir_path.as_path().to_string_lossy().to_string(),
-                include_components: true,
+            &RenameUnopenedFileArgs {
+                from: from.as_ref().into(),
+                to: to_path_str.clone().into(),
+                preconditions: Some(FilePreconditions {
+                    must_not_exist: true,
+                    ..Default::default()
+                }),
             },
         )
         .await
-        .unwrap();
+        .unwrap_err();
+
+        match err {
+            FileIoError::PreconditionFailed(args) => {
+                assert_eq!(args.path, to_path_str);
+                assert_eq!(args.precondition, FailedPrecondition::MustNotExist);
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
 
         assert!(
-            fs::metadata(&dir_path).await.is_ok(),
-            "Dir was unexpectedly not created"
-        );
-        assert_eq!(
-            args.path,
-            dir_path.to_string_lossy().to_string(),
-            "Wrong path returned"
+            fs::metadata(from.as_ref()).await.is_ok(),
+            "File unexpectedly renamed"
         );
     }
 
     #[tokio::test]
-    async fn rename_dir_should_return_error_if_a_file_open_in_directory() {
+    async fn remove_unopened_file_should_return_error_if_sig_precondition_set()
+    {
         let state = Arc::new(ServerState::default());
 
-        let from_dir = tempfile::tempdir().unwrap();
-        let to_dir = tempfile::tempdir().unwrap().into_path();
-        fs::remove_dir(to_dir.as_path())
-            .await
-            .expect("Failed to clean up temp dir");
-
-        let file_path = from_dir.as_ref().join("test-file");
-        let _ = state
-            .fs_manager
-            .lock()
-            .await
-            .open_file(file_path.as_path(), true, true, true)
-            .await
-            .expect("Failed to open file");
+        let file = tempfile::NamedTempFile::new().unwrap();
 
-        let err = rename_dir(
+        let err = remove_unopened_file(
             Arc::clone(&state),
-            &RenameDirArgs {
-                from: from_dir.as_ref().to_string_lossy().to_string(),
-                to: to_dir.as_path().to_string_lossy().to_string(),
+            &RemoveUnopenedFileArgs {
+                path: file.as_ref().into(),
+                preconditions: Some(FilePreconditions {
+                    sig: Some(123),
+                    ..Default::default()
+                }),
             },
         )
         .await
         .unwrap_err();
 
-        assert!(
-            fs::metadata(file_path.as_path()).await.is_ok(),
-            "Open file unexpectedly renamed to something else"
-        );
-
-        assert!(
-            fs::metadata(from_dir.as_ref()).await.is_ok(),
-            "Dir was unexpectedly renamed"
-        );
+        match err {
+            FileIoError::PreconditionFailed(args) => {
+                assert_eq!(
+                    args.precondition,
+                    FailedPrecondition::Sig {
+                        expected: 123,
+                        actual: None
+                    }
+                );
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
 
         assert!(
-            fs::metadata(to_dir.as_path()).await.is_err(),
-            "Destination of rename unexpectedly exists"
+            fs::metadata(file.as_ref()).await.is_ok(),
+            "File unexpectedly removed"
         );
-
-        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 
     #[tokio::test]
-    async fn rename_dir_should_return_confirmation_if_directory_renamed() {
+    async fn write_file_atomic_by_path_should_create_write_and_close_file() {
         let state = Arc::new(ServerState::default());
 
-        let from_dir = tempfile::tempdir().unwrap();
-        let to_dir = tempfile::tempdir().unwrap().into_path();
-        fs::remove_dir(to_dir.as_path())
-            .await
-            .expect("Failed to clean up temp dir");
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("file");
+        let path_str = path.to_string_lossy().to_string();
 
-        let args = rename_dir(
+        let args = write_file_atomic_by_path(
             Arc::clone(&state),
-            &RenameDirArgs {
-                from: from_dir.as_ref().to_string_lossy().to_string(),
-                to: to_dir.as_path().to_string_lossy().to_string(),
+            &WriteFileAtomicByPathArgs {
+                path: path_str.clone().into(),
+                data: b"abc".to_vec(),
+                mode: None,
             },
         )
         .await
         .unwrap();
 
-        assert!(
-            fs::metadata(from_dir.as_ref()).await.is_err(),
-            "Dir was unexpectedly not renamed"
-        );
-
-        assert!(
-            fs::metadata(to_dir.as_path()).await.is_ok(),
-            "Destination of rename unexpectedly missing"
-        );
-
+        assert_eq!(args.path, path_str);
         assert_eq!(
-            args.from,
-            from_dir.as_ref().to_string_lossy().to_string(),
-            "Wrong from path returned"
+            args.hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
         );
+        assert_eq!(fs::read(&path).await.unwrap(), b"abc".to_vec());
         assert_eq!(
-            args.to,
-            to_dir.to_string_lossy().to_string(),
-            "Wrong to path returned"
+            state.fs_manager.lock().await.file_cnt(),
+            0,
+            "File unexpectedly left open"
         );
     }
 
     #[tokio::test]
-    async fn remove_dir_should_return_error_if_a_file_open_in_directory() {
+    async fn write_file_atomic_by_path_should_leave_already_open_file_open() {
         let state = Arc::new(ServerState::default());
 
-        let dir = tempfile::tempdir().unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
 
-        let file_path = dir.as_ref().join("test-file");
-        let _ = state
+        let handle = state
             .fs_manager
             .lock()
             .await
-            .open_file(file_path.as_path(), true, true, true)
+            .open_file(file.as_ref(), false, false, true)
             .await
-            .expect("Failed to open file");
+            .expect("Unable to open file");
 
-        let err = remove_dir(
+        let args = write_file_atomic_by_path(
             Arc::clone(&state),
-            &RemoveDirArgs {
-                path: dir.as_ref().to_string_lossy().to_string(),
-                non_empty: true,
+            &WriteFileAtomicByPathArgs {
+                path: file.as_ref().into(),
+                data: b"abc".to_vec(),
+                mode: None,
             },
         )
         .await
-        .unwrap_err();
-
-        assert!(
-            fs::metadata(file_path.as_path()).await.is_ok(),
-            "Open file unexpectedly removed"
-        );
+        .unwrap();
 
-        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let shared = state.fs_manager.lock().await.get(handle.id);
+        let shared = shared.expect("File was closed");
+        let local_file = shared.lock().await;
+        assert_eq!(local_file.sig(), args.sig);
+        assert_ne!(args.sig, handle.sig);
+        assert_eq!(fs::read(file.as_ref()).await.unwrap(), b"abc".to_vec());
     }
 
     #[tokio::test]
-    async fn remove_dir_should_return_confirmation_if_empty_directory_removed()
-    {
+    async fn patch_file_lines_should_apply_edits_in_order() {
         let state = Arc::new(ServerState::default());
 
-        let dir = tempfile::tempdir().unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.as_ref(), b"a\r\nb\r\nc\r\n").await.unwrap();
 
-        let args = remove_dir(
+        let args = patch_file_lines(
             Arc::clone(&state),
-            &RemoveDirArgs {
-                path: dir.as_ref().to_string_lossy().to_string(),
-                non_empty: false,
+            &PatchFileLinesArgs {
+                path: file.as_ref().into(),
+                edits: vec![
+                    LineEdit::Replace {
+                        start: 1,
+                        end: 2,
+                        lines: vec![String::from("x"), String::from("y")],
+                    },
+                    LineEdit::Delete { start: 0, end: 1 },
+                    LineEdit::Insert {
+                        line: 3,
+                        lines: vec![String::from("z")],
+                    },
+                ],
+                expected_hash: None,
             },
         )
         .await
         .unwrap();
 
-        assert!(
-            fs::metadata(dir.as_ref()).await.is_err(),
-            "Dir still exists"
-        );
-
+        let contents = fs::read(file.as_ref()).await.unwrap();
+        assert_eq!(contents, b"x\r\ny\r\nc\r\nz\r\n".to_vec());
+        assert_eq!(args.line_count, 4);
+        assert_eq!(args.hash, format!("{:x}", Sha256::digest(&contents)));
         assert_eq!(
-            args.path,
-            dir.as_ref().to_string_lossy().to_string(),
-            "Wrong path returned"
+            state.fs_manager.lock().await.file_cnt(),
+            0,
+            "File unexpectedly left open"
         );
     }
 
     #[tokio::test]
-    async fn remove_dir_should_return_error_if_nonempty_directory_removed_with_flag_not_set(
-    ) {
+    async fn patch_file_lines_should_not_lose_write_through_open_handle() {
         let state = Arc::new(ServerState::default());
 
-        let dir = tempfile::tempdir().unwrap();
-        let file = tempfile::NamedTempFile::new_in(dir.as_ref()).unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.as_ref(), b"one\n").await.unwrap();
 
-        let err = remove_dir(
+        let handle = open_file(
             Arc::clone(&state),
-            &RemoveDirArgs {
-                path: dir.as_ref().to_string_lossy().to_string(),
-                non_empty: false,
+            &OpenFileArgs {
+                path: file.as_ref().into(),
+                write_access: true,
+                read_access: true,
+                ..Default::default()
             },
         )
         .await
-        .unwrap_err();
+        .unwrap();
 
-        assert!(
-            fs::metadata(file.as_ref()).await.is_ok(),
-            "File in dir unexpectedly removed"
-        );
+        // Hold the file as a write through the open handle would, having
+        // looked it up before the patch locks the manager
+        let shared = shared_file(&state, handle.id).await.unwrap();
+        let mut local_file = shared.lock().await;
+
+        let patch = tokio::spawn({
+            let state = Arc::clone(&state);
+            let path = file.as_ref().into();
+            async move {
+                patch_file_lines(
+                    state,
+                    &PatchFileLinesArgs {
+                        path,
+                        edits: vec![LineEdit::Insert {
+                            line: 0,
+                            lines: vec![String::from("zero")],
+                        }],
+                        expected_hash: None,
+                    },
+                )
+                .await
+            }
+        });
 
-        assert!(
-            fs::metadata(dir.as_ref()).await.is_ok(),
-            "Dir unexpected removed"
-        );
+        tokio::time::delay_for(std::time::Duration::from_millis(50)).await;
+        let sig = local_file.sig();
+        local_file.write_all(sig, b"two\n").await.unwrap();
+        drop(local_file);
+
+        let args = patch.await.unwrap().unwrap();
+        assert_eq!(fs::read(file.as_ref()).await.unwrap(), b"zero\ntwo\n");
+        assert_eq!(args.line_count, 2);
 
-        assert_eq!(err.kind(), io::ErrorKind::Other);
+        // The patch went through the handle that was already open, which
+        // stays open
+        assert_eq!(state.fs_manager.lock().await.file_cnt(), 1);
     }
 
     #[tokio::test]
-    async fn remove_dir_should_return_confirmation_if_nonempty_directory_removed_with_flag_set(
-    ) {
-        let state = Arc::new(ServerState::default());
-
-        let dir = tempfile::tempdir().unwrap();
-        let file = tempfile::NamedTempFile::new_in(dir.as_ref()).unwrap();
+    async fn patch_file_lines_should_not_modify_file_if_hash_differs() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.as_ref(), b"a\nb\n").await.unwrap();
 
-        let args = remove_dir(
+        let err = patch_file_lines(
+            Arc::new(ServerState::default()),
+            &PatchFileLinesArgs {
+                path: file.as_ref().into(),
+                edits: vec![LineEdit::Delete { start: 0, end: 1 }],
+                expected_hash: Some(String::from("abc")),
+            },
+        )
+        .await
+        .unwrap_err();
+
+        match err {
+            FileIoError::PreconditionFailed(PreconditionFailedArgs {
+                precondition: FailedPrecondition::Hash { .. },
+                ..
+            }) => (),
+            x => panic!("Unexpected error: {:?}", x),
+        }
+        assert_eq!(fs::read(file.as_ref()).await.unwrap(), b"a\nb\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn patch_file_lines_should_not_modify_file_if_any_edit_invalid() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.as_ref(), b"a\nb\n").await.unwrap();
+
+        let err = patch_file_lines(
+            Arc::new(ServerState::default()),
+            &PatchFileLinesArgs {
+                path: file.as_ref().into(),
+                edits: vec![
+                    LineEdit::Delete { start: 0, end: 1 },
+                    LineEdit::Delete { start: 1, end: 2 },
+                ],
+                expected_hash: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        match err {
+            FileIoError::Io(x) => {
+                assert_eq!(x.kind(), io::ErrorKind::InvalidInput)
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
+        assert_eq!(fs::read(file.as_ref()).await.unwrap(), b"a\nb\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn upload_manifest_should_create_parent_dirs_and_open_files() {
+        let state = Arc::new(ServerState::default());
+
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("a/b/file");
+        let path_str = path.to_string_lossy().to_string();
+
+        let args = upload_manifest(
+            Arc::clone(&state),
+            &UploadManifestArgs {
+                files: vec![ManifestFile {
+                    path: path_str.clone().into(),
+                    size: 3,
+                    hash: None,
+                    mode: Some(0o600),
+                    chunks: Vec::new(),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.sessions.len(), 1);
+        assert_eq!(args.sessions[0].path, path_str);
+        match args.sessions[0].status {
+            UploadSessionStatus::Ready { id, sig, .. } => {
+                let file = state.fs_manager.lock().await.get(id);
+                let file = file.expect("File not open");
+                let mut local_file = file.lock().await;
+                assert_eq!(local_file.sig(), sig);
+                local_file.write_all(sig, &[1, 2, 3]).await.unwrap();
+            }
+            ref x => panic!("Unexpected status: {:?}", x),
+        }
+
+        assert_eq!(fs::read(&path).await.unwrap(), vec![1, 2, 3]);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).await.unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_manifest_should_skip_files_with_matching_size_and_hash() {
+        let state = Arc::new(ServerState::default());
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"abc").unwrap();
+
+        let args = upload_manifest(
+            Arc::clone(&state),
+            &UploadManifestArgs {
+                files: vec![ManifestFile {
+                    path: file.as_ref().into(),
+                    size: 3,
+                    hash: Some(String::from(
+                        "ba7816bf8f01cfea414140de5dae2223\
+                         b00361a396177a9cb410ff61f20015ad",
+                    )),
+                    mode: None,
+                    chunks: Vec::new(),
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.sessions[0].status, UploadSessionStatus::UpToDate);
+        assert_eq!(state.fs_manager.lock().await.file_cnt(), 0);
+    }
+
+    #[tokio::test]
+    async fn upload_manifest_should_report_failures_per_file() {
+        let state = Arc::new(ServerState::default());
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let root = tempfile::tempdir().unwrap();
+
+        let args = upload_manifest(
+            Arc::clone(&state),
+            &UploadManifestArgs {
+                files: vec![
+                    ManifestFile {
+                        path: file.as_ref().join("not-a-dir").into(),
+                        ..Default::default()
+                    },
+                    ManifestFile {
+                        path: root.as_ref().join("file").into(),
+                        ..Default::default()
+                    },
+                ],
+            },
+        )
+        .await
+        .unwrap();
+
+        match &args.sessions[0].status {
+            UploadSessionStatus::Failed { .. } => {}
+            x => panic!("Unexpected status: {:?}", x),
+        }
+
+        match &args.sessions[1].status {
+            UploadSessionStatus::Ready { .. } => {}
+            x => panic!("Unexpected status: {:?}", x),
+        }
+    }
+
+    /// Prepares `path` to be uploaded with `data` in chunks of 3 bytes,
+    /// returning the file and the chunks the server already holds
+    async fn prepare_chunked_upload(
+        state: &Arc<ServerState>,
+        path: &Path,
+        data: &[u8],
+    ) -> (u32, u32, Vec<u32>) {
+        let args = upload_manifest(
+            Arc::clone(state),
+            &UploadManifestArgs {
+                files: vec![ManifestFile {
+                    path: path.into(),
+                    size: data.len() as u64,
+                    chunks: data
+                        .chunks(3)
+                        .map(|chunk| ManifestChunk {
+                            len: chunk.len() as u64,
+                            hash: hash_chunk(chunk),
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+            },
+        )
+        .await
+        .unwrap();
+
+        match &args.sessions[0].status {
+            UploadSessionStatus::Ready {
+                id,
+                sig,
+                have_chunks,
+            } => (*id, *sig, have_chunks.clone()),
+            x => panic!("Unexpected status: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_manifest_should_report_chunks_already_in_place() {
+        let state = Arc::new(ServerState::default());
+
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("file");
+        fs::write(&path, b"abcXXXg").await.unwrap();
+
+        let (id, sig, have_chunks) =
+            prepare_chunked_upload(&state, &path, b"abcdefg").await;
+        assert_eq!(have_chunks, vec![0, 2]);
+
+        let args = write_upload_chunk(
+            Arc::clone(&state),
+            &WriteUploadChunkArgs {
+                id,
+                sig,
+                index: 1,
+                data: b"def".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(args.index, 1);
+        assert_ne!(args.sig, sig, "Sig was not updated after write");
+
+        assert_eq!(fs::read(&path).await.unwrap(), b"abcdefg".to_vec());
+    }
+
+    #[tokio::test]
+    async fn upload_manifest_should_reuse_chunks_written_to_other_files() {
+        let state = Arc::new(ServerState::default());
+
+        let root = tempfile::tempdir().unwrap();
+        let first_path = root.as_ref().join("first");
+        let second_path = root.as_ref().join("second");
+
+        let (id, sig, _) =
+            prepare_chunked_upload(&state, &first_path, b"abcdef").await;
+        write_upload_chunk(
+            Arc::clone(&state),
+            &WriteUploadChunkArgs {
+                id,
+                sig,
+                index: 1,
+                data: b"def".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let (_, _, have_chunks) =
+            prepare_chunked_upload(&state, &second_path, b"xyzdef").await;
+        assert_eq!(have_chunks, vec![1]);
+        assert_eq!(&fs::read(&second_path).await.unwrap()[3..], b"def");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn upload_manifest_should_place_chunks_through_shared_handle() {
+        use std::os::unix::fs::PermissionsExt;
+        let mut fs_manager = FileSystemManager::new();
+        fs_manager.set_default_file_mode(Some(0o640));
+        let mut state = ServerState::default();
+        state.set_fs_manager(fs_manager);
+        let state = Arc::new(state);
+
+        let root = tempfile::tempdir().unwrap();
+        let first_path = root.as_ref().join("first");
+        let second_path = root.as_ref().join("second");
+        fs::write(&first_path, b"abc").await.unwrap();
+        state
+            .chunks
+            .remember(&hash_chunk(b"abc"), &first_path, 0, 3)
+            .await;
+
+        // The file is created by the manager, so it gets the default mode
+        let (id, sig, have_chunks) =
+            prepare_chunked_upload(&state, &second_path, b"abcdef").await;
+        assert_eq!(have_chunks, vec![0]);
+        let mode = fs::metadata(&second_path)
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        // Preparing the file again goes through the handle already open to
+        // it, whose signature reflects the chunks placed since
+        let (same_id, same_sig, have_chunks) =
+            prepare_chunked_upload(&state, &second_path, b"abcdef").await;
+        assert_eq!(have_chunks, vec![0]);
+        assert_eq!(same_id, id);
+        assert_ne!(same_sig, sig, "Sig was not updated after placing chunks");
+
+        write_upload_chunk(
+            Arc::clone(&state),
+            &WriteUploadChunkArgs {
+                id,
+                sig: same_sig,
+                index: 1,
+                data: b"def".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read(&second_path).await.unwrap(), b"abcdef".to_vec());
+    }
+
+    #[tokio::test]
+    async fn write_upload_chunk_should_return_error_if_chunk_does_not_match() {
+        let state = Arc::new(ServerState::default());
+
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("file");
+
+        let (id, sig, _) =
+            prepare_chunked_upload(&state, &path, b"abcdef").await;
+        let err = write_upload_chunk(
+            Arc::clone(&state),
+            &WriteUploadChunkArgs {
+                id,
+                sig,
+                index: 1,
+                data: b"abc".to_vec(),
+            },
+        )
+        .await
+        .unwrap_err();
+
+        match err {
+            FileIoError::Io(x) => {
+                assert_eq!(x.kind(), io::ErrorKind::InvalidData)
+            }
+            x => panic!("Unexpected error: {:?}", x),
+        }
+        assert_eq!(fs::read(&path).await.unwrap(), vec![0; 6]);
+    }
+
+    #[tokio::test]
+    async fn create_dir_should_return_error_if_part_of_path_missing_and_flag_not_set(
+    ) {
+        let root_path = tempfile::tempdir().unwrap();
+        let state = Arc::new(ServerState::default());
+
+        let dir_path = root_path.as_ref().join("test").join("dir");
+
+        let err = create_dir(
+            Arc::clone(&state),
+            &CreateDirArgs {
+                path: dir_path.as_path().into(),
+                include_components: false,
+                mode: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            fs::metadata(&dir_path).await.is_err(),
+            "Dir was unexpectedly created"
+        );
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn create_dir_should_return_confirmation_if_single_level_directory_created(
+    ) {
+        let root_path = tempfile::tempdir().unwrap();
+        let state = Arc::new(ServerState::default());
+
+        let dir_path = root_path.as_ref().join("test");
+
+        let args = create_dir(
+            Arc::clone(&state),
+            &CreateDirArgs {
+                path: dir_path.as_path().into(),
+                include_components: false,
+                mode: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            fs::metadata(&dir_path).await.is_ok(),
+            "Dir was unexpectedly not created"
+        );
+
+        assert_eq!(
+            args.path,
+            dir_path.to_string_lossy().to_string(),
+            "Wrong path returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_dir_should_return_confirmation_if_multi_level_directory_created(
+    ) {
+        let root_path = tempfile::tempdir().unwrap();
+        let state = Arc::new(ServerState::default());
+
+        let dir_path = root_path.as_ref().join("test").join("dir");
+
+        let args = create_dir(
+            Arc::clone(&state),
+            &CreateDirArgs {
+                path: dir_path.as_path().into(),
+                include_components: true,
+                mode: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            fs::metadata(&dir_path).await.is_ok(),
+            "Dir was unexpectedly not created"
+        );
+        assert_eq!(
+            args.path,
+            dir_path.to_string_lossy().to_string(),
+            "Wrong path returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_dir_should_return_error_if_a_file_open_in_directory() {
+        let state = Arc::new(ServerState::default());
+
+        let from_dir = tempfile::tempdir().unwrap();
+        let to_dir = tempfile::tempdir().unwrap().into_path();
+        fs::remove_dir(to_dir.as_path())
+            .await
+            .expect("Failed to clean up temp dir");
+
+        let file_path = from_dir.as_ref().join("test-file");
+        let _ = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file_path.as_path(), true, true, true)
+            .await
+            .expect("Failed to open file");
+
+        let err = rename_dir(
+            Arc::clone(&state),
+            &RenameDirArgs {
+                from: from_dir.as_ref().into(),
+                to: to_dir.as_path().into(),
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            fs::metadata(file_path.as_path()).await.is_ok(),
+            "Open file unexpectedly renamed to something else"
+        );
+
+        assert!(
+            fs::metadata(from_dir.as_ref()).await.is_ok(),
+            "Dir was unexpectedly renamed"
+        );
+
+        assert!(
+            fs::metadata(to_dir.as_path()).await.is_err(),
+            "Destination of rename unexpectedly exists"
+        );
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn rename_dir_should_return_confirmation_if_directory_renamed() {
+        let state = Arc::new(ServerState::default());
+
+        let from_dir = tempfile::tempdir().unwrap();
+        let to_dir = tempfile::tempdir().unwrap().into_path();
+        fs::remove_dir(to_dir.as_path())
+            .await
+            .expect("Failed to clean up temp dir");
+
+        let args = rename_dir(
+            Arc::clone(&state),
+            &RenameDirArgs {
+                from: from_dir.as_ref().into(),
+                to: to_dir.as_path().into(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            fs::metadata(from_dir.as_ref()).await.is_err(),
+            "Dir was unexpectedly not renamed"
+        );
+
+        assert!(
+            fs::metadata(to_dir.as_path()).await.is_ok(),
+            "Destination of rename unexpectedly missing"
+        );
+
+        assert_eq!(
+            args.from,
+            from_dir.as_ref().to_string_lossy().to_string(),
+            "Wrong from path returned"
+        );
+        assert_eq!(
+            args.to,
+            to_dir.to_string_lossy().to_string(),
+            "Wrong to path returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_dir_should_return_error_if_a_file_open_in_directory() {
+        let state = Arc::new(ServerState::default());
+
+        let dir = tempfile::tempdir().unwrap();
+
+        let file_path = dir.as_ref().join("test-file");
+        let _ = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file_path.as_path(), true, true, true)
+            .await
+            .expect("Failed to open file");
+
+        let err = remove_dir(
+            Arc::clone(&state),
+            &RemoveDirArgs {
+                path: dir.as_ref().into(),
+                non_empty: true,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            fs::metadata(file_path.as_path()).await.is_ok(),
+            "Open file unexpectedly removed"
+        );
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn remove_dir_should_return_confirmation_if_empty_directory_removed()
+    {
+        let state = Arc::new(ServerState::default());
+
+        let dir = tempfile::tempdir().unwrap();
+
+        let args = remove_dir(
+            Arc::clone(&state),
+            &RemoveDirArgs {
+                path: dir.as_ref().into(),
+                non_empty: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            fs::metadata(dir.as_ref()).await.is_err(),
+            "Dir still exists"
+        );
+
+        assert_eq!(
+            args.path,
+            dir.as_ref().to_string_lossy().to_string(),
+            "Wrong path returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_dir_should_return_error_if_nonempty_directory_removed_with_flag_not_set(
+    ) {
+        let state = Arc::new(ServerState::default());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = tempfile::NamedTempFile::new_in(dir.as_ref()).unwrap();
+
+        let err = remove_dir(
+            Arc::clone(&state),
+            &RemoveDirArgs {
+                path: dir.as_ref().into(),
+                non_empty: false,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            fs::metadata(file.as_ref()).await.is_ok(),
+            "File in dir unexpectedly removed"
+        );
+
+        assert!(
+            fs::metadata(dir.as_ref()).await.is_ok(),
+            "Dir unexpected removed"
+        );
+
+        assert_eq!(err.raw_os_error(), Some(libc::ENOTEMPTY));
+    }
+
+    #[tokio::test]
+    async fn remove_dir_should_return_confirmation_if_nonempty_directory_removed_with_flag_set(
+    ) {
+        let state = Arc::new(ServerState::default());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = tempfile::NamedTempFile::new_in(dir.as_ref()).unwrap();
+
+        let args = remove_dir(
             Arc::clone(&state),
             &RemoveDirArgs {
-                path: dir.as_ref().to_string_lossy().to_string(),
+                path: dir.as_ref().into(),
                 non_empty: true,
             },
         )
         .await
         .unwrap();
 
-        assert!(
-            fs::metadata(file.as_ref()).await.is_err(),
-            "File in dir still exists"
-        );
+        assert!(
+            fs::metadata(file.as_ref()).await.is_err(),
+            "File in dir still exists"
+        );
+
+        assert!(
+            fs::metadata(dir.as_ref()).await.is_err(),
+            "Dir still exists"
+        );
+
+        assert_eq!(
+            args.path,
+            dir.as_ref().to_string_lossy().to_string(),
+            "Wrong path returned"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_paths_should_create_dirs_and_files_with_result_per_path() {
+        let root = tempfile::tempdir().unwrap();
+        let path = |x: &str| RemotePath::from_path(root.as_ref().join(x));
+        std::fs::create_dir(root.as_ref().join("existing")).unwrap();
+        std::fs::write(root.as_ref().join("kept"), b"abc").unwrap();
+
+        let args = create_paths(
+            Arc::new(ServerState::default()),
+            &CreatePathsArgs {
+                dirs: vec![path("a/b/c"), path("existing"), path("kept")],
+                files: vec![path("a/file"), path("d/e/file"), path("kept")],
+            },
+        )
+        .await
+        .unwrap();
+
+        let statuses = |results: &[PathCreateResult]| {
+            results
+                .iter()
+                .map(|x| match &x.status {
+                    PathCreateStatus::Failed { .. } => "failed",
+                    PathCreateStatus::AlreadyExists => "already_exists",
+                    PathCreateStatus::Created => "created",
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statuses(&args.dirs),
+            vec!["created", "already_exists", "failed"]
+        );
+        assert_eq!(
+            statuses(&args.files),
+            vec!["created", "created", "already_exists"]
+        );
+
+        assert!(root.as_ref().join("a/b/c").is_dir());
+        assert_eq!(std::fs::read(root.as_ref().join("d/e/file")).unwrap(), b"");
+        assert_eq!(std::fs::read(root.as_ref().join("kept")).unwrap(), b"abc");
+    }
+
+    #[tokio::test]
+    async fn list_dir_contents_should_return_entries_if_successful() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.path().to_string_lossy().to_string();
+
+        let tmp_file = tempfile::NamedTempFile::new_in(&dir).unwrap();
+        let tmp_file_path = fs::canonicalize(tmp_file.path())
+            .await
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let tmp_dir = tempfile::tempdir_in(&dir).unwrap();
+        let tmp_dir_path = fs::canonicalize(tmp_dir.path())
+            .await
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let args = list_dir_contents(
+            Arc::new(ServerState::default()),
+            &ListDirContentsArgs {
+                path: dir_path.clone().into(),
+                stream: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        std::fs::remove_dir_all(dir_path).unwrap();
+
+        assert_eq!(args.entries.len(), 2, "Unexpected number of entries");
+
+        assert!(args.entries.contains(&DirEntry {
+            path: tmp_file_path.into(),
+            is_file: true,
+            is_dir: false,
+            is_symlink: false
+        }));
+
+        assert!(args.entries.contains(&DirEntry {
+            path: tmp_dir_path.into(),
+            is_file: false,
+            is_dir: true,
+            is_symlink: false
+        }));
+    }
+
+    #[tokio::test]
+    async fn list_dir_contents_should_return_error_if_path_invalid() {
+        let err = list_dir_contents(
+            Arc::new(ServerState::default()),
+            &ListDirContentsArgs {
+                path: "".into(),
+                stream: false,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn list_dir_contents_should_keep_paths_that_are_not_valid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir
+            .as_ref()
+            .join(std::ffi::OsStr::from_bytes(b"caf\xe9.txt"));
+        std::fs::write(&file, b"abc").unwrap();
+
+        let args = list_dir_contents(
+            Arc::new(ServerState::default()),
+            &ListDirContentsArgs {
+                path: dir.as_ref().into(),
+                stream: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.entries.len(), 1);
+        let path = &args.entries[0].path;
+        assert_eq!(path, &RemotePath::from_path(&file));
+        assert_eq!(path.to_str(), None);
+
+        // Path as listed must refer to the same file when sent back
+        let args = read_files(
+            Arc::new(ServerState::default()),
+            &ReadFilesArgs {
+                paths: vec![path.clone()],
+                max_total_bytes: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        match &args.files[0].status {
+            FileReadStatus::Read { contents } => assert_eq!(contents, b"abc"),
+            x => panic!("Unexpected status: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn sniff_file_should_only_examine_up_to_max_bytes() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"line 1\r\nline 2\n").unwrap();
+
+        let args = sniff_file(
+            Arc::new(ServerState::default()),
+            &SniffFileArgs {
+                path: file.as_ref().into(),
+                max_bytes: Some(8),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.bytes_read, 8);
+        assert_eq!(args.mime_type, "text/plain");
+        assert!(!args.is_binary, "Text file reported as binary");
+        assert_eq!(args.line_ending, LineEnding::CrLf);
+    }
+
+    #[tokio::test]
+    async fn sniff_file_should_return_error_if_file_missing() {
+        let err = sniff_file(
+            Arc::new(ServerState::default()),
+            &SniffFileArgs {
+                path: "".into(),
+                max_bytes: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn get_file_checksum_should_digest_contents_with_algorithm() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"123456789").unwrap();
+        let path = file.as_ref().to_string_lossy().to_string();
+
+        let args = get_file_checksum(
+            Arc::new(ServerState::default()),
+            &GetFileChecksumArgs {
+                path: path.clone().into(),
+                algorithm: ChecksumAlgorithm::Crc32,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.path, path);
+        assert_eq!(args.algorithm, ChecksumAlgorithm::Crc32);
+        assert_eq!(args.digest, "cbf43926");
+        assert_eq!(args.size, 9);
+    }
+
+    #[tokio::test]
+    async fn diff_files_should_return_unified_diff_of_text_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path_a, path_b) = (dir.as_ref().join("a"), dir.as_ref().join("b"));
+        fs::write(&path_a, b"a\nb\nc\n").await.unwrap();
+        fs::write(&path_b, b"a\nB\nc\n").await.unwrap();
+        let path_a = path_a.to_string_lossy().to_string();
+        let path_b = path_b.to_string_lossy().to_string();
+
+        let args = diff_files(
+            Arc::new(ServerState::default()),
+            &DiffFilesArgs {
+                path_a: path_a.clone().into(),
+                path_b: path_b.clone().into(),
+                context_lines: Some(0),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(!args.identical, "Different files reported as identical");
+        assert!(!args.is_binary, "Text files reported as binary");
+        assert_eq!(
+            args.diff,
+            format!("--- {}\n+++ {}\n@@ -2 +2 @@\n-b\n+B\n", path_a, path_b)
+        );
+        assert_eq!((args.lines_added, args.lines_removed), (1, 1));
+
+        // Provided contents take the place of the second file
+        let args = diff_files(
+            Arc::new(ServerState::default()),
+            &DiffFilesArgs {
+                path_a: path_a.into(),
+                path_b: "local".into(),
+                contents_b: Some(b"a\nb\nc\n".to_vec()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(args.identical, "Same contents reported as different");
+        assert!(args.diff.is_empty(), "Diff of same contents not empty");
+    }
+
+    #[tokio::test]
+    async fn diff_files_should_not_diff_binary_or_oversized_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.as_ref().join("a");
+        fs::write(&path, b"\x00\x01\x02").await.unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let args = diff_files(
+            Arc::new(ServerState::default()),
+            &DiffFilesArgs {
+                path_a: path.clone().into(),
+                path_b: "local".into(),
+                contents_b: Some(b"\x00\x01".to_vec()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(args.is_binary, "Binary file not detected");
+        assert!(!args.identical, "Different files reported as identical");
+        assert!(args.diff.is_empty(), "Diff produced for binary file");
+
+        let err = diff_files(
+            Arc::new(ServerState::default()),
+            &DiffFilesArgs {
+                path_a: path.into(),
+                path_b: "local".into(),
+                contents_b: Some(Vec::new()),
+                max_bytes: Some(2),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn fs_change_of_should_describe_change_made_by_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.as_ref().join("existing");
+        fs::write(&existing, b"").await.unwrap();
+        let existing = existing.to_string_lossy().to_string();
+        let missing = dir.as_ref().join("missing");
+        let missing = missing.to_string_lossy().to_string();
+
+        let state = ServerState::default();
+
+        let request =
+            Request::WriteFileAtomicByPath(WriteFileAtomicByPathArgs {
+                path: existing.clone().into(),
+                ..Default::default()
+            });
+        assert_eq!(
+            fs_change_of(&state, &request).await,
+            Some(FsChange::new(FsEventOp::Modified, &existing))
+        );
+
+        let request =
+            Request::WriteFileAtomicByPath(WriteFileAtomicByPathArgs {
+                path: missing.clone().into(),
+                ..Default::default()
+            });
+        assert_eq!(
+            fs_change_of(&state, &request).await,
+            Some(FsChange::new(FsEventOp::Created, &missing))
+        );
+
+        // Opening an existing file changes nothing
+        let request = Request::OpenFile(OpenFileArgs {
+            path: existing.clone().into(),
+            create_if_missing: true,
+            ..Default::default()
+        });
+        assert_eq!(fs_change_of(&state, &request).await, None);
+
+        let request = Request::RenameUnopenedFile(RenameUnopenedFileArgs {
+            from: existing.clone().into(),
+            to: missing.clone().into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            fs_change_of(&state, &request).await,
+            Some(FsChange::renamed(&existing, &missing))
+        );
+
+        // Files that are not open have no path to record
+        let request = Request::RemoveFile(RemoveFileArgs {
+            id: 999,
+            ..Default::default()
+        });
+        assert_eq!(fs_change_of(&state, &request).await, None);
+
+        assert_eq!(fs_change_of(&state, &Request::Heartbeat).await, None);
+    }
+
+    #[tokio::test]
+    async fn list_dir_contents_stream_should_send_entries_in_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in &["a", "b", "c", "d", "e"] {
+            fs::write(dir.as_ref().join(name), b"").await.unwrap();
+        }
+
+        let (tx, mut rx) = mpsc::channel(10);
+        let last = list_dir_contents_stream(
+            Arc::new(ServerState::default()),
+            &ListDirContentsArgs {
+                path: dir.as_ref().into(),
+                stream: true,
+            },
+            2,
+            tx,
+        )
+        .await
+        .unwrap();
+
+        let mut chunks = Vec::new();
+        while let Some(reply) = rx.recv().await {
+            match reply {
+                Reply::DirContentsListChunk(args) => chunks.push(args),
+                x => panic!("Unexpected reply: {:?}", x),
+            }
+        }
 
-        assert!(
-            fs::metadata(dir.as_ref()).await.is_err(),
-            "Dir still exists"
-        );
+        assert_eq!(chunks.len(), 2, "Unexpected number of partial chunks");
+        assert!(chunks.iter().all(|c| !c.last && c.entries.len() == 2));
+        assert!(last.last, "Final chunk not marked as last");
+        assert_eq!(last.entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resolve_path_should_report_existing_file_details() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        let args = resolve_path(
+            Arc::new(ServerState::default()),
+            &ResolvePathArgs {
+                path: file.as_ref().into(),
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(
             args.path,
-            dir.as_ref().to_string_lossy().to_string(),
-            "Wrong path returned"
+            fs::canonicalize(file.as_ref())
+                .await
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
         );
+        assert!(args.exists);
+        assert!(args.is_file);
+        assert!(!args.is_dir);
+        assert!(!args.is_symlink);
     }
 
     #[tokio::test]
-    async fn list_dir_contents_should_return_entries_if_successful() {
-        let dir = tempfile::tempdir().unwrap();
-        let dir_path = dir.path().to_string_lossy().to_string();
+    async fn resolve_path_should_report_missing_path_within_root() {
+        let args = resolve_path(
+            Arc::new(ServerState::default()),
+            &ResolvePathArgs {
+                path: "some/missing/path".into(),
+            },
+        )
+        .await
+        .unwrap();
 
-        let tmp_file = tempfile::NamedTempFile::new_in(&dir).unwrap();
-        let tmp_file_path = fs::canonicalize(tmp_file.path())
-            .await
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
+        assert!(args.path.to_string_lossy().ends_with("missing/path"));
+        assert!(!args.exists);
+        assert!(!args.is_file);
+        assert!(!args.is_dir);
+        assert!(args.in_root);
+    }
 
-        let tmp_dir = tempfile::tempdir_in(&dir).unwrap();
-        let tmp_dir_path = fs::canonicalize(tmp_dir.path())
+    /// Creates a server whose file system is confined to a new dir, which
+    /// holds links to a file and to a dir that are both outside of it
+    #[cfg(unix)]
+    async fn confined_state(
+    ) -> (Arc<ServerState>, tempfile::TempDir, tempfile::TempDir) {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.as_ref().join("secret"), b"secret")
             .await
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
+            .unwrap();
+        std::os::unix::fs::symlink(
+            outside.as_ref(),
+            root.as_ref().join("dir-link"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            outside.as_ref().join("secret"),
+            root.as_ref().join("file-link"),
+        )
+        .unwrap();
 
-        let args = list_dir_contents(
-            Arc::new(ServerState::default()),
-            &ListDirContentsArgs {
-                path: dir_path.clone(),
+        let mut fs_manager = FileSystemManager::new();
+        fs_manager.set_allowed_paths(vec![root.as_ref().to_path_buf()]);
+        let mut state = ServerState::default();
+        state.set_fs_manager(fs_manager);
+
+        (Arc::new(state), root, outside)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rename_unopened_file_should_not_follow_symlinks_out_of_allowed_paths(
+    ) {
+        let (state, root, outside) = confined_state().await;
+
+        let result = rename_unopened_file(
+            Arc::clone(&state),
+            &RenameUnopenedFileArgs {
+                from: root.as_ref().join("dir-link/secret").into(),
+                to: root.as_ref().join("moved").into(),
+                preconditions: None,
+            },
+        )
+        .await;
+        assert!(result.is_err(), "Renamed file outside of allowed paths");
+
+        let result = rename_unopened_file(
+            state,
+            &RenameUnopenedFileArgs {
+                from: root.as_ref().join("file-link").into(),
+                to: root.as_ref().join("dir-link/moved").into(),
+                preconditions: None,
+            },
+        )
+        .await;
+        assert!(result.is_err(), "Renamed file outside of allowed paths");
+
+        assert!(outside.as_ref().join("secret").exists());
+        assert!(!outside.as_ref().join("moved").exists());
+        assert!(!root.as_ref().join("moved").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rename_file_should_not_move_file_out_of_allowed_paths() {
+        let (state, root, outside) = confined_state().await;
+        let handle = open_file(
+            Arc::clone(&state),
+            &OpenFileArgs {
+                path: root.as_ref().join("file").into(),
+                create_if_missing: true,
+                write_access: true,
+                read_access: true,
+                ..Default::default()
             },
         )
         .await
         .unwrap();
 
-        std::fs::remove_dir_all(dir_path).unwrap();
+        let result = rename_file(
+            state,
+            &RenameFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                to: root.as_ref().join("dir-link/moved").into(),
+                preconditions: None,
+            },
+        )
+        .await;
 
-        assert_eq!(args.entries.len(), 2, "Unexpected number of entries");
+        assert!(result.is_err(), "Moved file outside of allowed paths");
+        assert!(root.as_ref().join("file").exists());
+        assert!(!outside.as_ref().join("moved").exists());
+    }
 
-        assert!(args.entries.contains(&DirEntry {
-            path: tmp_file_path,
-            is_file: true,
-            is_dir: false,
-            is_symlink: false
-        }));
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn remove_unopened_file_should_not_follow_symlinks_out_of_allowed_paths(
+    ) {
+        let (state, root, outside) = confined_state().await;
 
-        assert!(args.entries.contains(&DirEntry {
-            path: tmp_dir_path,
-            is_file: false,
-            is_dir: true,
-            is_symlink: false
-        }));
+        let result = remove_unopened_file(
+            state,
+            &RemoveUnopenedFileArgs {
+                path: root.as_ref().join("dir-link/secret").into(),
+                preconditions: None,
+            },
+        )
+        .await;
+
+        assert!(result.is_err(), "Removed file outside of allowed paths");
+        assert!(outside.as_ref().join("secret").exists());
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn list_dir_contents_should_return_error_if_path_invalid() {
-        let err = list_dir_contents(
-            Arc::new(ServerState::default()),
+    async fn remove_file_should_remove_file_within_allowed_paths() {
+        let (state, root, _outside) = confined_state().await;
+        let handle = open_file(
+            Arc::clone(&state),
+            &OpenFileArgs {
+                path: root.as_ref().join("file").into(),
+                create_if_missing: true,
+                write_access: true,
+                read_access: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        remove_file(
+            state,
+            &RemoveFileArgs {
+                id: handle.id,
+                sig: handle.sig,
+                preconditions: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(!root.as_ref().join("file").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn create_dir_should_not_follow_symlinks_out_of_allowed_paths() {
+        let (state, root, outside) = confined_state().await;
+
+        let err = create_dir(
+            state,
+            &CreateDirArgs {
+                path: root.as_ref().join("dir-link/new/dir").into(),
+                include_components: true,
+                mode: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+        assert!(!outside.as_ref().join("new").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn remove_dir_should_not_follow_symlinks_out_of_allowed_paths() {
+        let (state, root, outside) = confined_state().await;
+        fs::create_dir(root.as_ref().join("dir")).await.unwrap();
+        std::os::unix::fs::symlink(
+            outside.as_ref(),
+            root.as_ref().join("dir/link"),
+        )
+        .unwrap();
+
+        let result = remove_dir(
+            Arc::clone(&state),
+            &RemoveDirArgs {
+                path: root.as_ref().join("dir-link").into(),
+                non_empty: true,
+            },
+        )
+        .await;
+        assert!(result.is_err(), "Removed dir outside of allowed paths");
+
+        // Removing a dir removes the links within it, not what they point to
+        remove_dir(
+            state,
+            &RemoveDirArgs {
+                path: root.as_ref().join("dir").into(),
+                non_empty: true,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(!root.as_ref().join("dir").exists());
+        assert!(outside.as_ref().join("secret").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rename_dir_should_not_move_dir_out_of_allowed_paths() {
+        let (state, root, outside) = confined_state().await;
+        fs::create_dir(root.as_ref().join("dir")).await.unwrap();
+
+        let result = rename_dir(
+            state,
+            &RenameDirArgs {
+                from: root.as_ref().join("dir").into(),
+                to: root.as_ref().join("dir-link/dir").into(),
+            },
+        )
+        .await;
+
+        assert!(result.is_err(), "Moved dir outside of allowed paths");
+        assert!(root.as_ref().join("dir").exists());
+        assert!(!outside.as_ref().join("dir").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn list_dir_contents_should_not_follow_symlinks_out_of_allowed_paths()
+    {
+        let (state, root, _outside) = confined_state().await;
+
+        let result = list_dir_contents(
+            Arc::clone(&state),
+            &ListDirContentsArgs {
+                path: root.as_ref().join("dir-link").into(),
+                stream: false,
+            },
+        )
+        .await;
+        assert!(result.is_err(), "Listed dir outside of allowed paths");
+
+        let mut entries = list_dir_contents(
+            state,
+            &ListDirContentsArgs {
+                path: root.as_ref().into(),
+                stream: false,
+            },
+        )
+        .await
+        .unwrap()
+        .entries;
+        entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        // Links are reported as they are rather than as what they point to
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .all(|x| x.is_symlink && !x.is_dir && !x.is_file));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn list_dir_contents_stream_should_not_follow_symlinks_out_of_allowed_paths(
+    ) {
+        let (state, root, _outside) = confined_state().await;
+
+        let (tx, _rx) = mpsc::channel(10);
+        let err = list_dir_contents_stream(
+            state,
             &ListDirContentsArgs {
-                path: String::from(""),
+                path: root.as_ref().join("dir-link").into(),
+                stream: true,
             },
+            2,
+            tx,
         )
         .await
         .unwrap_err();
 
-        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_files_should_not_follow_symlinks_out_of_allowed_paths() {
+        let (state, root, _outside) = confined_state().await;
+
+        let args = read_files(
+            state,
+            &ReadFilesArgs {
+                paths: vec![
+                    root.as_ref().join("file-link").into(),
+                    root.as_ref().join("dir-link/secret").into(),
+                ],
+                max_total_bytes: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.files.len(), 2);
+        for file in args.files {
+            match file.status {
+                FileReadStatus::Failed { .. } => {}
+                x => panic!("Unexpected status: {:?}", x),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn patch_file_lines_should_not_follow_symlinks_out_of_allowed_paths()
+    {
+        let (state, root, outside) = confined_state().await;
+
+        let result = patch_file_lines(
+            state,
+            &PatchFileLinesArgs {
+                path: root.as_ref().join("file-link").into(),
+                edits: vec![LineEdit::Replace {
+                    start: 0,
+                    end: 1,
+                    lines: vec![String::from("patched")],
+                }],
+                expected_hash: None,
+            },
+        )
+        .await;
+
+        assert!(result.is_err(), "Patched file outside of allowed paths");
+        assert_eq!(
+            fs::read(outside.as_ref().join("secret")).await.unwrap(),
+            b"secret"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn sniff_file_should_not_follow_symlinks_out_of_allowed_paths() {
+        let (state, root, _outside) = confined_state().await;
+
+        let err = sniff_file(
+            state,
+            &SniffFileArgs {
+                path: root.as_ref().join("dir-link/secret").into(),
+                max_bytes: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn get_file_checksum_should_not_follow_symlinks_out_of_allowed_paths()
+    {
+        let (state, root, _outside) = confined_state().await;
+
+        let result = get_file_checksum(
+            state,
+            &GetFileChecksumArgs {
+                path: root.as_ref().join("file-link").into(),
+                algorithm: ChecksumAlgorithm::Crc32,
+            },
+        )
+        .await;
+
+        assert!(result.is_err(), "Digested file outside of allowed paths");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn diff_files_should_not_follow_symlinks_out_of_allowed_paths() {
+        let (state, root, _outside) = confined_state().await;
+        fs::write(root.as_ref().join("file"), b"secret")
+            .await
+            .unwrap();
+
+        let result = diff_files(
+            state,
+            &DiffFilesArgs {
+                path_a: root.as_ref().join("file").into(),
+                path_b: root.as_ref().join("dir-link/secret").into(),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert!(result.is_err(), "Diffed file outside of allowed paths");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn write_file_atomic_by_path_should_not_follow_symlinks_out_of_allowed_paths(
+    ) {
+        use std::os::unix::fs::PermissionsExt;
+        let (state, root, outside) = confined_state().await;
+        let secret = outside.as_ref().join("secret");
+        let mode = fs::metadata(&secret).await.unwrap().permissions().mode();
+
+        let result = write_file_atomic_by_path(
+            state,
+            &WriteFileAtomicByPathArgs {
+                path: root.as_ref().join("file-link").into(),
+                data: b"patched".to_vec(),
+                mode: Some(0o777),
+            },
+        )
+        .await;
+
+        assert!(result.is_err(), "Wrote file outside of allowed paths");
+        assert_eq!(fs::read(&secret).await.unwrap(), b"secret");
+        assert_eq!(
+            fs::metadata(&secret).await.unwrap().permissions().mode(),
+            mode
+        );
     }
 }
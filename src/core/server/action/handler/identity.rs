@@ -0,0 +1,207 @@
+use crate::core::{
+    reply::{self, IdentityArgs, TrustedClientsArgs},
+    request::{self, IdentifyArgs, UpdateTrustedClientsArgs},
+    server::state::ServerState,
+    transport::crypto::handshake::{self, Handshake, Role, Session},
+};
+use log::debug;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+pub async fn identify(
+    state: Arc<ServerState>,
+    args: &IdentifyArgs,
+) -> Result<IdentityArgs, io::Error> {
+    debug!("handler::identify: {:?}", args);
+
+    let key = state.identity_key().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "Server has no identity key")
+    })?;
+
+    if args.challenge.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Identity challenge is empty",
+        ));
+    }
+
+    Ok(IdentityArgs {
+        public_key: key.public_key().to_vec(),
+        proof: key.prove(&args.challenge),
+    })
+}
+
+pub async fn update_trusted_clients(
+    state: Arc<ServerState>,
+    args: &UpdateTrustedClientsArgs,
+) -> Result<TrustedClientsArgs, io::Error> {
+    debug!("handler::update_trusted_clients: {:?}", args);
+
+    let fingerprints = state
+        .trusted_clients
+        .update(&args.add, &args.remove)
+        .await?;
+
+    Ok(TrustedClientsArgs { fingerprints })
+}
+
+/// Negotiates a key for the session of `origin`, replacing any key it
+/// negotiated before
+///
+/// The public keys of both sides are signed with the identity key of the
+/// server if it has one, letting clients that pin the key tell that the
+/// handshake was not intercepted. The session is pinned to the `signer` of
+/// the handshake, if it was signed, which is then the only identity that can
+/// replace the session without sealing the new handshake with it.
+pub async fn handshake(
+    state: Arc<ServerState>,
+    origin: SocketAddr,
+    signer: Option<Vec<u8>>,
+    args: &request::HandshakeArgs,
+) -> Result<reply::HandshakeArgs, io::Error> {
+    debug!("handler::handshake: {} {:?}", origin, args);
+
+    let handshake = Handshake::new(Role::Responder);
+    let public_key = handshake.public_key().to_vec();
+    let key = handshake.finish(&args.public_key).map_err(|x| {
+        io::Error::new(io::ErrorKind::InvalidInput, x.to_string())
+    })?;
+
+    let (identity_key, signature) = match state.identity_key() {
+        Some(identity_key) => (
+            identity_key.public_key().to_vec(),
+            identity_key
+                .sign(&handshake::signed_keys(&args.public_key, &public_key)),
+        ),
+        None => (Vec::new(), Vec::new()),
+    };
+
+    state
+        .sessions
+        .lock()
+        .await
+        .insert(origin, Session::new(&key).with_identity(signer));
+    Ok(reply::HandshakeArgs {
+        public_key,
+        identity_key,
+        signature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transport::auth::identity;
+
+    #[tokio::test]
+    async fn identify_should_prove_identity_key() {
+        let key = identity::IdentityKey::generate();
+        let mut state = ServerState::default();
+        state.set_identity_key(Some(key.clone()));
+
+        let challenge = identity::new_challenge();
+        let args = identify(
+            Arc::new(state),
+            &IdentifyArgs {
+                challenge: challenge.clone(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.public_key, key.public_key());
+        assert!(identity::verify(key.public_key(), &challenge, &args.proof));
+    }
+
+    #[tokio::test]
+    async fn identify_should_fail_if_server_has_no_identity_key() {
+        let err = identify(
+            Arc::new(ServerState::default()),
+            &IdentifyArgs {
+                challenge: identity::new_challenge(),
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn handshake_should_negotiate_session_key_of_origin() {
+        let state = Arc::new(ServerState::default());
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+
+        let client = Handshake::new(Role::Initiator);
+        let args = handshake(
+            Arc::clone(&state),
+            origin,
+            None,
+            &request::HandshakeArgs {
+                public_key: client.public_key().to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+        let key = client.finish(&args.public_key).unwrap();
+        assert!(args.identity_key.is_empty());
+        assert!(args.signature.is_empty());
+
+        // Data sealed by the client can be opened with the session of the
+        // origin
+        let (nonce, data) = Session::new(&key).seal(b"some data").unwrap();
+        let session = state.sessions.lock().await.get(&origin).cloned();
+        assert_eq!(
+            session.unwrap().open(&nonce, &data).unwrap(),
+            b"some data".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_should_sign_public_keys_with_identity_key() {
+        let key = identity::IdentityKey::generate();
+        let mut state = ServerState::default();
+        state.set_identity_key(Some(key.clone()));
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+
+        let client_public_key = Handshake::new(Role::Initiator).public_key();
+        let args = handshake(
+            Arc::new(state),
+            origin,
+            None,
+            &request::HandshakeArgs {
+                public_key: client_public_key.to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.identity_key, key.public_key());
+        assert!(identity::verify(
+            key.public_key(),
+            &handshake::signed_keys(&client_public_key, &args.public_key),
+            &args.signature
+        ));
+    }
+
+    #[tokio::test]
+    async fn handshake_should_fail_if_public_key_is_malformed() {
+        let state = Arc::new(ServerState::default());
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+
+        let err = handshake(
+            Arc::clone(&state),
+            origin,
+            None,
+            &request::HandshakeArgs {
+                public_key: vec![1, 2, 3],
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(state.sessions.lock().await.is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::core::{
+    reply::*,
+    request::*,
+    server::{
+        job::{JobRecord, LocalJobSpec, LocalJobState},
+        schedule::{LocalSchedule, LocalTrigger},
+        state::ServerState,
+    },
+};
+use log::debug;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub async fn submit_job(
+    state: Arc<ServerState>,
+    args: &SubmitJobArgs,
+) -> Result<JobSubmittedArgs, io::Error> {
+    debug!("handler::submit_job: {:?}", args);
+
+    let record = state
+        .jobs
+        .submit(&state.tasks, args.spec.clone().into())
+        .await?;
+    Ok(JobSubmittedArgs { id: record.id })
+}
+
+pub async fn query_job(
+    state: Arc<ServerState>,
+    args: &QueryJobArgs,
+) -> Result<JobStatusArgs, io::Error> {
+    debug!("handler::query_job: {:?}", args);
+
+    state.jobs.get(args.id).await.map(JobStatusArgs::from)
+}
+
+pub async fn collect_job_output(
+    state: Arc<ServerState>,
+    args: &CollectJobOutputArgs,
+) -> Result<JobOutputArgs, io::Error> {
+    debug!("handler::collect_job_output: {:?}", args);
+
+    let (stdout, stderr) = state.jobs.read_output(args.id).await?;
+    Ok(JobOutputArgs {
+        id: args.id,
+        stdout,
+        stderr,
+    })
+}
+
+pub async fn create_schedule(
+    state: Arc<ServerState>,
+    args: &CreateScheduleArgs,
+) -> Result<ScheduleCreatedArgs, io::Error> {
+    debug!("handler::create_schedule: {:?}", args);
+
+    let schedule = state
+        .schedules
+        .create(args.spec.clone().into(), args.trigger.clone().into())
+        .await?;
+    Ok(ScheduleCreatedArgs {
+        id: schedule.id,
+        next_run_at_millis: schedule.next_run_at,
+    })
+}
+
+pub async fn list_schedules(
+    state: Arc<ServerState>,
+) -> Result<SchedulesListArgs, io::Error> {
+    debug!("handler::list_schedules");
+
+    let mut schedules = Vec::new();
+    for schedule in state.schedules.list().await? {
+        let mut runs = Vec::new();
+        for run in schedule.runs.iter() {
+            if let Ok(record) = state.jobs.get(run.job_id).await {
+                runs.push(JobStatusArgs::from(record));
+            }
+        }
+
+        schedules.push(ScheduleEntry {
+            runs,
+            ..ScheduleEntry::from(schedule)
+        });
+    }
+
+    Ok(SchedulesListArgs { schedules })
+}
+
+pub async fn delete_schedule(
+    state: Arc<ServerState>,
+    args: &DeleteScheduleArgs,
+) -> Result<ScheduleDeletedArgs, io::Error> {
+    debug!("handler::delete_schedule: {:?}", args);
+
+    let schedule = state.schedules.delete(args.id).await?;
+    Ok(ScheduleDeletedArgs { id: schedule.id })
+}
+
+impl From<JobSpec> for LocalJobSpec {
+    fn from(spec: JobSpec) -> Self {
+        Self {
+            command: spec.command,
+            args: spec.args,
+            current_dir: spec.current_dir,
+        }
+    }
+}
+
+impl From<LocalJobSpec> for JobSpec {
+    fn from(spec: LocalJobSpec) -> Self {
+        Self {
+            command: spec.command,
+            args: spec.args,
+            current_dir: spec.current_dir,
+        }
+    }
+}
+
+impl From<LocalJobState> for JobState {
+    fn from(state: LocalJobState) -> Self {
+        match state {
+            LocalJobState::Running => Self::Running,
+            LocalJobState::Exited => Self::Exited,
+            LocalJobState::Interrupted => Self::Interrupted,
+        }
+    }
+}
+
+impl From<JobRecord> for JobStatusArgs {
+    fn from(record: JobRecord) -> Self {
+        Self {
+            id: record.id,
+            spec: record.spec.into(),
+            state: record.state.into(),
+            submitted_at_millis: record.submitted_at,
+            finished_at_millis: record.finished_at,
+            exit_code: record.exit_code,
+        }
+    }
+}
+
+impl From<ScheduleTrigger> for LocalTrigger {
+    fn from(trigger: ScheduleTrigger) -> Self {
+        match trigger {
+            ScheduleTrigger::Interval { secs } => {
+                Self::Interval(Duration::from_secs(secs))
+            }
+            ScheduleTrigger::Cron { expr } => Self::Cron(expr),
+        }
+    }
+}
+
+impl From<LocalTrigger> for ScheduleTrigger {
+    fn from(trigger: LocalTrigger) -> Self {
+        match trigger {
+            LocalTrigger::Interval(interval) => Self::Interval {
+                secs: interval.as_secs(),
+            },
+            LocalTrigger::Cron(expr) => Self::Cron { expr },
+        }
+    }
+}
+
+/// Converts without resolving runs, which requires looking up each job
+impl From<LocalSchedule> for ScheduleEntry {
+    fn from(schedule: LocalSchedule) -> Self {
+        Self {
+            id: schedule.id,
+            spec: schedule.spec.into(),
+            trigger: schedule.trigger.into(),
+            next_run_at_millis: schedule.next_run_at,
+            runs: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::server::{job::JobManager, schedule::ScheduleManager};
+    use std::time::Duration;
+    use tokio::time::delay_for;
+
+    fn make_state(dir: &tempfile::TempDir) -> Arc<ServerState> {
+        let mut state = ServerState::default();
+        state
+            .set_jobs(JobManager::new(dir.as_ref()))
+            .set_schedules(ScheduleManager::new(dir.as_ref()));
+        Arc::new(state)
+    }
+
+    #[tokio::test]
+    async fn submit_job_should_allow_querying_status_and_output_later() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = make_state(&dir);
+
+        let spec = JobSpec {
+            command: String::from("echo"),
+            args: vec![String::from("hello")],
+            current_dir: None,
+        };
+        let id = submit_job(
+            Arc::clone(&state),
+            &SubmitJobArgs { spec: spec.clone() },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        let mut status = None;
+        for _ in 0..50 {
+            let args = query_job(Arc::clone(&state), &QueryJobArgs { id })
+                .await
+                .unwrap();
+            if args.state != JobState::Running {
+                status = Some(args);
+                break;
+            }
+            delay_for(Duration::from_millis(10)).await;
+        }
+
+        let status = status.expect("Job never exited");
+        assert_eq!(status.id, id);
+        assert_eq!(status.spec, spec);
+        assert_eq!(status.state, JobState::Exited);
+        assert_eq!(status.exit_code, Some(0));
+
+        let output = collect_job_output(
+            Arc::clone(&state),
+            &CollectJobOutputArgs { id },
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, b"hello\n".to_vec());
+        assert!(output.stderr.is_empty(), "Unexpected stderr");
+    }
+
+    #[tokio::test]
+    async fn query_job_should_fail_if_job_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = make_state(&dir);
+
+        let err = query_job(state, &QueryJobArgs { id: 999 })
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn schedules_should_be_listable_until_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = make_state(&dir);
+
+        let args = CreateScheduleArgs {
+            spec: JobSpec {
+                command: String::from("true"),
+                args: vec![],
+                current_dir: None,
+            },
+            trigger: ScheduleTrigger::Cron {
+                expr: String::from("0 * * * *"),
+            },
+        };
+        let created = create_schedule(Arc::clone(&state), &args).await.unwrap();
+
+        let list = list_schedules(Arc::clone(&state)).await.unwrap();
+        assert_eq!(
+            list.schedules,
+            vec![ScheduleEntry {
+                id: created.id,
+                spec: args.spec.clone(),
+                trigger: args.trigger.clone(),
+                next_run_at_millis: created.next_run_at_millis,
+                runs: vec![],
+            }]
+        );
+
+        delete_schedule(
+            Arc::clone(&state),
+            &DeleteScheduleArgs { id: created.id },
+        )
+        .await
+        .unwrap();
+
+        let list = list_schedules(Arc::clone(&state)).await.unwrap();
+        assert!(list.schedules.is_empty(), "Schedule not deleted");
+    }
+
+    #[tokio::test]
+    async fn create_schedule_should_fail_if_trigger_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = make_state(&dir);
+
+        let args = CreateScheduleArgs {
+            spec: JobSpec::default(),
+            trigger: ScheduleTrigger::Interval { secs: 0 },
+        };
+        let err = create_schedule(state, &args).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::core::{reply::*, request::*, server::state::ServerState};
+use log::debug;
+use std::io;
+use std::sync::Arc;
+
+pub async fn append_log(
+    state: Arc<ServerState>,
+    args: &AppendLogArgs,
+) -> Result<LogAppendedArgs, io::Error> {
+    debug!(
+        "handler::append_log: {} ({} lines)",
+        args.name,
+        args.lines.len()
+    );
+
+    state.logs.append(&args.name, &args.lines).await?;
+    Ok(LogAppendedArgs {
+        name: args.name.clone(),
+        appended: args.lines.len(),
+    })
+}
+
+pub async fn read_log_range(
+    state: Arc<ServerState>,
+    args: &ReadLogRangeArgs,
+) -> Result<LogRangeArgs, io::Error> {
+    debug!("handler::read_log_range: {:?}", args);
+
+    let (lines, total) = state
+        .logs
+        .read_range(&args.name, args.start, args.max_lines)
+        .await?;
+    Ok(LogRangeArgs {
+        name: args.name.clone(),
+        start: args.start,
+        lines,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::server::logs::LogSinks;
+
+    #[tokio::test]
+    async fn read_log_range_should_return_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = ServerState::default();
+        state.set_logs(LogSinks::new(dir.path()));
+        let state = Arc::new(state);
+
+        let args = append_log(
+            Arc::clone(&state),
+            &AppendLogArgs {
+                name: String::from("app"),
+                lines: vec![String::from("first"), String::from("second")],
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(args.appended, 2);
+
+        let args = read_log_range(
+            state,
+            &ReadLogRangeArgs {
+                name: String::from("app"),
+                start: 1,
+                max_lines: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            args,
+            LogRangeArgs {
+                name: String::from("app"),
+                start: 1,
+                lines: vec![String::from("second")],
+                total: 2,
+            }
+        );
+    }
+}
@@ -1,6 +1,20 @@
+pub mod backup;
 pub mod capabilities;
+pub mod config;
+pub mod connection;
+pub mod diagnostics;
+pub mod eviction;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 pub mod fs;
 pub mod heartbeat;
-pub mod internal_debug;
+pub mod identity;
+pub mod job;
+pub mod logs;
+pub mod power;
 pub mod proc;
+pub mod time;
+pub mod transaction;
 pub mod version;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,96 @@
+use crate::core::{
+    reply, request::PowerControlArgs, server::state::ServerState,
+};
+use log::debug;
+use std::io;
+use std::sync::Arc;
+
+/// Requests the power action of `args` on behalf of `identity`, or performs
+/// it once confirmed with the token yielded when it was requested
+pub async fn power_control(
+    state: Arc<ServerState>,
+    identity: &str,
+    args: &PowerControlArgs,
+) -> Result<reply::PowerControlArgs, io::Error> {
+    debug!("handler::power_control: {:?} for {}", args, identity);
+
+    match args.token.as_ref() {
+        None => {
+            let token = state
+                .power
+                .request(identity, &args.action, args.delay_secs)
+                .await?;
+
+            Ok(reply::PowerControlArgs {
+                action: args.action.clone(),
+                delay_secs: args.delay_secs,
+                token,
+                confirmed: false,
+                expires_in_secs: state.power.confirm_ttl().as_secs(),
+            })
+        }
+        Some(token) => {
+            state
+                .power
+                .confirm(identity, token, &args.action, args.delay_secs)
+                .await?;
+            state.power.schedule(
+                &state.tasks,
+                args.action.clone(),
+                args.delay_secs,
+            );
+
+            Ok(reply::PowerControlArgs {
+                action: args.action.clone(),
+                delay_secs: args.delay_secs,
+                token: token.clone(),
+                confirmed: true,
+                expires_in_secs: 0,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{request::PowerAction, server::power::PowerControl};
+
+    const ADMIN: &str =
+        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[tokio::test]
+    async fn power_control_should_require_confirmation_with_token() {
+        let mut state = ServerState::default();
+        state.set_power(PowerControl::new(vec![String::from(ADMIN)]).unwrap());
+        let state = Arc::new(state);
+
+        let mut args = PowerControlArgs {
+            action: PowerAction::Custom {
+                command: String::from("true"),
+                args: Vec::new(),
+            },
+            delay_secs: 0,
+            token: None,
+        };
+        let requested = power_control(Arc::clone(&state), ADMIN, &args)
+            .await
+            .unwrap();
+        assert!(!requested.confirmed);
+        assert!(requested.expires_in_secs > 0);
+
+        // Unsigned msgs are accounted to their address, which is never an
+        // admin, so they cannot confirm the action
+        args.token = Some(requested.token.clone());
+        let err = power_control(Arc::clone(&state), "127.0.0.1", &args)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let confirmed = power_control(Arc::clone(&state), ADMIN, &args)
+            .await
+            .unwrap();
+        assert!(confirmed.confirmed);
+        assert_eq!(confirmed.token, requested.token);
+    }
+}
@@ -1,12 +1,22 @@
 use crate::core::{
     reply::*,
     request::*,
-    server::{proc::LocalProc, state::ServerState},
+    server::{
+        listing,
+        proc::{LocalProc, OutputFilter, SpawnInfo},
+        proc_info::{self, ProcInfo},
+        pty::{self, Pty},
+        state::ServerState,
+    },
 };
-use log::debug;
+use log::{debug, warn};
+use rand::{rngs::OsRng, RngCore};
 use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::process::Command;
 
 pub async fn exec_proc(
@@ -14,6 +24,159 @@ pub async fn exec_proc(
     args: &ExecProcArgs,
 ) -> Result<ProcStartedArgs, io::Error> {
     debug!("handler::exec_proc: {:?}", args);
+
+    let (local_proc, started) = spawn_proc(&state, args).await?;
+    track_proc(state, local_proc).await;
+    Ok(started)
+}
+
+pub async fn exec_script(
+    state: Arc<ServerState>,
+    args: &ExecScriptArgs,
+) -> Result<ProcStartedArgs, io::Error> {
+    debug!("handler::exec_script: {:?}", args);
+
+    let path = write_script(&args.body)?;
+    let mut proc_args = vec![path.to_string_lossy().to_string()];
+    proc_args.extend(args.args.iter().cloned());
+
+    let result = spawn_proc(
+        &state,
+        &ExecProcArgs {
+            command: args.interpreter.clone(),
+            args: proc_args,
+            stdin: args.stdin,
+            stdout: args.stdout,
+            stderr: args.stderr,
+            current_dir: args.current_dir.clone(),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    match result {
+        Ok((mut local_proc, started)) => {
+            local_proc.set_temp_file(path);
+            track_proc(state, local_proc).await;
+            Ok(started)
+        }
+        Err(x) => {
+            let _ = std::fs::remove_file(&path);
+            Err(x)
+        }
+    }
+}
+
+pub async fn exec_proc_pty(
+    state: Arc<ServerState>,
+    args: &ExecProcPtyArgs,
+) -> Result<ProcStartedArgs, io::Error> {
+    debug!("handler::exec_proc_pty: {:?}", args);
+
+    let ExecProcPtyArgs {
+        command,
+        args,
+        current_dir,
+        rows,
+        cols,
+        term,
+        detached,
+        labels,
+    } = args;
+
+    // What the proc launcher of the server is told is being launched
+    let launch_args = ExecProcArgs {
+        command: command.clone(),
+        args: args.clone(),
+        stdin: true,
+        stdout: true,
+        current_dir: current_dir.clone(),
+        detached: *detached,
+        labels: labels.clone(),
+        ..Default::default()
+    };
+
+    let current_dir = match current_dir {
+        Some(dir) => Some(tokio::fs::canonicalize(dir).await?),
+        None => None,
+    };
+
+    let (pty, terminal) = Pty::open(*rows, *cols)?;
+
+    let mut cmd = Command::new(command);
+    cmd.args(args)
+        .stdin(terminal.try_clone()?)
+        .stdout(terminal.try_clone()?)
+        .stderr(terminal)
+        .kill_on_drop(!*detached);
+
+    // NOTE: The proc is always placed in a session of its own so that the
+    //       terminal can become its controlling terminal
+    pty::set_controlling_terminal(&mut cmd);
+
+    if let Some(term) = term {
+        cmd.env("TERM", term);
+    }
+
+    if let Some(dir) = current_dir.as_ref() {
+        cmd.current_dir(dir);
+    }
+
+    // NOTE: The command is dropped by the launcher once spawned, closing
+    //       our copies of the terminal so that only the proc holds it
+    let child = state.proc_launcher.launch(&launch_args, cmd)?;
+
+    let mut local_proc = LocalProc::new(child);
+    local_proc.set_pty(pty);
+    let mut local_proc = local_proc.spawn();
+    local_proc.set_detached(*detached);
+    local_proc.set_labels(labels.clone());
+
+    match SpawnInfo::capture(command, args, current_dir.as_deref()) {
+        Ok(spawn_info) => local_proc.set_spawn_info(spawn_info),
+        Err(x) => warn!("Failed to capture how {} was spawned: {}", command, x),
+    }
+
+    let started = ProcStartedArgs {
+        id: local_proc.id(),
+        stdout_path: None,
+        stderr_path: None,
+    };
+    track_proc(state, local_proc).await;
+    Ok(started)
+}
+
+/// Writes a script to a new file in the temp directory that only the
+/// server's user can access
+fn write_script(body: &str) -> io::Result<PathBuf> {
+    use std::io::Write;
+
+    let path = std::env::temp_dir()
+        .join(format!("over-there-script-{}", OsRng.next_u32()));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&path)?;
+    if let Err(x) = file.write_all(body.as_bytes()) {
+        let _ = std::fs::remove_file(&path);
+        return Err(x);
+    }
+
+    Ok(path)
+}
+
+/// Spawns the proc of `request` through the proc launcher of the server
+async fn spawn_proc(
+    state: &ServerState,
+    request: &ExecProcArgs,
+) -> io::Result<(LocalProc, ProcStartedArgs)> {
     let ExecProcArgs {
         command,
         args,
@@ -21,32 +184,222 @@ pub async fn exec_proc(
         stdout,
         stderr,
         current_dir,
-    } = args;
+        umask,
+        detached,
+        stdout_file,
+        stderr_file,
+        io_mode,
+        newline,
+        labels,
+        initial_stdin,
+        close_stdin,
+    } = request;
 
     let make_pipe = |yes| if yes { Stdio::piped() } else { Stdio::null() };
 
+    // NOTE: It is recommended to canonicalize the path before applying
+    //       it to ensure that it is absolute as platforms can apply
+    //       relative or absolute differently otherwise
+    let current_dir = match current_dir {
+        Some(dir) => Some(tokio::fs::canonicalize(dir).await?),
+        None => None,
+    };
+
     let mut cmd = Command::new(command);
     cmd.args(args)
-        .stdin(make_pipe(*stdin))
+        .stdin(make_pipe(*stdin || initial_stdin.is_some()))
         .stdout(make_pipe(*stdout))
         .stderr(make_pipe(*stderr))
-        .kill_on_drop(true);
+        .kill_on_drop(!*detached);
+
+    configure_proc(&mut cmd, *umask, *detached);
 
     // If provided a directory to change to, set that with the command
-    if let Some(dir) = current_dir {
-        // NOTE: It is recommended to canonicalize the path before applying
-        //       it to ensure that it is absolute as platforms can apply
-        //       relative or absolute differently otherwise
-        let dir = tokio::fs::canonicalize(dir).await?;
+    if let Some(dir) = current_dir.as_ref() {
         cmd.current_dir(dir);
     }
 
-    let child = cmd.spawn()?;
-    let local_proc = LocalProc::new(child).spawn();
+    let mut stdout_path = None;
+    if let Some(output_file) = stdout_file {
+        let (file, path) = open_output_file(output_file, current_dir.as_ref())?;
+        cmd.stdout(file);
+        stdout_path = Some(path);
+    }
+
+    let mut stderr_path = None;
+    if let Some(output_file) = stderr_file {
+        let (file, path) = open_output_file(output_file, current_dir.as_ref())?;
+        cmd.stderr(file);
+        stderr_path = Some(path);
+    }
+
+    let child = state.proc_launcher.launch(request, cmd)?;
+    let mut local_proc = LocalProc::new(child).spawn();
+    local_proc.set_detached(*detached);
+    local_proc.set_framing(*io_mode, *newline);
+    local_proc.set_labels(labels.clone());
+
+    // Written before the proc is tracked so no other request can write to
+    // stdin ahead of it
+    if let Some(input) = initial_stdin {
+        if let Err(x) = local_proc.write_stdin(input).await {
+            let _ = local_proc.kill();
+            return Err(x);
+        }
+    }
+    if *close_stdin {
+        local_proc.close_stdin();
+    }
+
+    match SpawnInfo::capture(command, args, current_dir.as_deref()) {
+        Ok(spawn_info) => local_proc.set_spawn_info(spawn_info),
+        Err(x) => warn!("Failed to capture how {} was spawned: {}", command, x),
+    }
+
+    let started = ProcStartedArgs {
+        id: local_proc.id(),
+        stdout_path,
+        stderr_path,
+    };
+    Ok((local_proc, started))
+}
+
+/// Opens a file to receive output from a proc, returning the file alongside
+/// its absolute path
+fn open_output_file(
+    output_file: &OutputFile,
+    current_dir: Option<&PathBuf>,
+) -> io::Result<(std::fs::File, String)> {
+    let path = match current_dir {
+        Some(dir) => dir.join(&output_file.path),
+        None => PathBuf::from(&output_file.path),
+    };
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(output_file.append)
+        .truncate(!output_file.append)
+        .open(&path)?;
+    let path = std::fs::canonicalize(&path)?;
+
+    Ok((file, path.to_string_lossy().to_string()))
+}
+
+/// Tells `origin` of the exit of the proc with `id` once it exits, doing
+/// nothing if there is no origin or the proc is no longer tracked
+pub async fn tell_origin_on_exit(
+    state: Arc<ServerState>,
+    id: u32,
+    origin: Option<SocketAddr>,
+) {
+    if let Some(origin) = origin {
+        if let Some(local_proc) = state.procs.lock().await.get_mut(&id) {
+            local_proc.set_origin(origin);
+        }
+    }
+}
+
+async fn track_proc(state: Arc<ServerState>, local_proc: LocalProc) {
     let id = local_proc.id();
     state.procs.lock().await.insert(id, local_proc);
     state.touch_proc_id(id).await;
-    Ok(ProcStartedArgs { id })
+}
+
+/// Applies the file mode creation mask to the proc and, if detached, places
+/// the proc in its own session so it is unaffected by signals sent to the
+/// server's process group
+#[cfg(unix)]
+fn configure_proc(cmd: &mut Command, umask: Option<u32>, detached: bool) {
+    if umask.is_none() && !detached {
+        return;
+    }
+
+    // NOTE: This runs in the forked child prior to exec, so we only call
+    //       async-signal-safe functions
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(mask) = umask {
+                libc::umask(mask as libc::mode_t);
+            }
+
+            if detached && libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+}
+
+/// Detaches the proc from the server's console, as there is no umask to
+/// apply outside of unix
+#[cfg(windows)]
+fn configure_proc(cmd: &mut Command, _umask: Option<u32>, detached: bool) {
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+
+    if detached {
+        cmd.creation_flags(DETACHED_PROCESS);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn configure_proc(_cmd: &mut Command, _umask: Option<u32>, _detached: bool) {}
+
+pub async fn list_procs(
+    state: Arc<ServerState>,
+    args: &ListProcsArgs,
+) -> ProcsListArgs {
+    debug!("handler::list_procs: {:?}", args);
+
+    let now = Instant::now();
+    let mut entries = Vec::new();
+    for (id, local_proc) in state.procs.lock().await.iter_mut() {
+        let exit_status = local_proc.exit_status().await;
+        let started = local_proc.started();
+        entries.push((
+            started,
+            ProcEntryArgs {
+                id: *id,
+                labels: local_proc.labels().to_vec(),
+                detached: local_proc.is_detached(),
+                is_alive: exit_status.is_none(),
+                exit_code: exit_status.and_then(|s| s.exit_code),
+                age_millis: now.duration_since(started).as_millis() as u64,
+            },
+        ));
+    }
+
+    let (entries, page) = listing::list(
+        entries,
+        &args.page,
+        |(started, entry)| {
+            let status = match args.status {
+                Some(ProcStatusFilter::Running) => entry.is_alive,
+                Some(ProcStatusFilter::Exited) => !entry.is_alive,
+                None => true,
+            };
+            let label = match args.label.as_ref() {
+                Some(label) => entry.labels.contains(label),
+                None => true,
+            };
+
+            status
+                && label
+                && listing::within_age(
+                    now.duration_since(*started),
+                    args.min_age_secs,
+                    args.max_age_secs,
+                )
+        },
+        // Oldest procs first, falling back to ids for procs started together
+        |(started, entry)| (*started, entry.id),
+    );
+
+    ProcsListArgs {
+        procs: entries.into_iter().map(|(_, entry)| entry).collect(),
+        page,
+    }
 }
 
 pub async fn write_proc_stdin(
@@ -65,6 +418,26 @@ pub async fn write_proc_stdin(
     }
 }
 
+pub async fn resize_pty(
+    state: Arc<ServerState>,
+    args: &ResizePtyArgs,
+) -> Result<PtyResizedArgs, io::Error> {
+    debug!("handler::resize_pty: {:?}", args);
+    state.touch_proc_id(args.id).await;
+
+    match state.procs.lock().await.get(&args.id) {
+        Some(local_proc) => {
+            local_proc.resize_pty(args.rows, args.cols)?;
+            Ok(PtyResizedArgs {
+                id: args.id,
+                rows: args.rows,
+                cols: args.cols,
+            })
+        }
+        None => Err(IoErrorArgs::invalid_proc_id(args.id).into()),
+    }
+}
+
 pub async fn read_proc_stdout(
     state: Arc<ServerState>,
     args: &ReadProcStdoutArgs,
@@ -72,20 +445,25 @@ pub async fn read_proc_stdout(
     debug!("handler::read_proc_stdout: {:?}", args);
     state.touch_proc_id(args.id).await;
 
+    let filter = OutputFilter::compile(&args.filter)?;
     match state.procs.lock().await.get_mut(&args.id) {
-        Some(local_proc) => match local_proc.read_stdout().await {
-            Ok(output) => Ok(ProcStdoutContentsArgs {
-                id: args.id,
-                output,
-            }),
-            Err(x) if x.kind() == io::ErrorKind::WouldBlock => {
-                Ok(ProcStdoutContentsArgs {
+        Some(local_proc) => {
+            match local_proc.read_stdout_filtered(&filter).await {
+                Ok(x) => Ok(ProcStdoutContentsArgs {
                     id: args.id,
-                    output: vec![],
-                })
+                    output: x.output,
+                    next_offset: x.next_offset,
+                    remaining: x.remaining,
+                }),
+                Err(x) if x.kind() == io::ErrorKind::WouldBlock => {
+                    Ok(ProcStdoutContentsArgs {
+                        id: args.id,
+                        ..Default::default()
+                    })
+                }
+                Err(x) => Err(x),
             }
-            Err(x) => Err(x),
-        },
+        }
         None => Err(IoErrorArgs::invalid_proc_id(args.id).into()),
     }
 }
@@ -97,20 +475,25 @@ pub async fn read_proc_stderr(
     debug!("handler::read_proc_stderr: {:?}", args);
     state.touch_proc_id(args.id).await;
 
+    let filter = OutputFilter::compile(&args.filter)?;
     match state.procs.lock().await.get_mut(&args.id) {
-        Some(local_proc) => match local_proc.read_stderr().await {
-            Ok(output) => Ok(ProcStderrContentsArgs {
-                id: args.id,
-                output,
-            }),
-            Err(x) if x.kind() == io::ErrorKind::WouldBlock => {
-                Ok(ProcStderrContentsArgs {
+        Some(local_proc) => {
+            match local_proc.read_stderr_filtered(&filter).await {
+                Ok(x) => Ok(ProcStderrContentsArgs {
                     id: args.id,
-                    output: vec![],
-                })
+                    output: x.output,
+                    next_offset: x.next_offset,
+                    remaining: x.remaining,
+                }),
+                Err(x) if x.kind() == io::ErrorKind::WouldBlock => {
+                    Ok(ProcStderrContentsArgs {
+                        id: args.id,
+                        ..Default::default()
+                    })
+                }
+                Err(x) => Err(x),
             }
-            Err(x) => Err(x),
-        },
+        }
         None => Err(IoErrorArgs::invalid_proc_id(args.id).into()),
     }
 }
@@ -147,6 +530,89 @@ pub async fn read_proc_status(
     }
 }
 
+pub async fn read_proc_tree(
+    state: Arc<ServerState>,
+    args: &ReadProcTreeArgs,
+) -> Result<ProcTreeArgs, io::Error> {
+    debug!("handler::read_proc_tree: {:?}", args);
+    state.touch_proc_id(args.id).await;
+
+    if !state.procs.lock().await.contains_key(&args.id) {
+        return Err(IoErrorArgs::invalid_proc_id(args.id).into());
+    }
+
+    let procs = proc_info::read_proc_tree(args.id).await?;
+    Ok(ProcTreeArgs {
+        id: args.id,
+        procs: procs.into_iter().map(ProcTreeEntry::from).collect(),
+    })
+}
+
+pub async fn read_proc_resources(
+    state: Arc<ServerState>,
+    args: &ReadProcResourcesArgs,
+) -> Result<ProcResourcesArgs, io::Error> {
+    debug!("handler::read_proc_resources: {:?}", args);
+    state.touch_proc_id(args.id).await;
+
+    if !state.procs.lock().await.contains_key(&args.id) {
+        return Err(IoErrorArgs::invalid_proc_id(args.id).into());
+    }
+
+    let info = proc_info::read_proc_info(args.id).await?;
+    Ok(ProcResourcesArgs {
+        id: args.id,
+        cpu_percent: info.cpu_percent(),
+        cpu_time_millis: info.cpu_time.as_millis() as u64,
+        rss_bytes: info.rss_bytes,
+        elapsed_millis: info.elapsed.as_millis() as u64,
+    })
+}
+
+pub async fn read_proc_info(
+    state: Arc<ServerState>,
+    args: &ReadProcInfoArgs,
+) -> Result<ProcInfoArgs, io::Error> {
+    debug!("handler::read_proc_info: {:?}", args);
+    state.touch_proc_id(args.id).await;
+
+    let procs = state.procs.lock().await;
+    let local_proc = match procs.get(&args.id) {
+        Some(x) => x,
+        None => return Err(IoErrorArgs::invalid_proc_id(args.id).into()),
+    };
+    let spawn_info = local_proc.spawn_info().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Spawn of proc {} was not recorded", args.id),
+        )
+    })?;
+
+    Ok(ProcInfoArgs {
+        id: args.id,
+        command: spawn_info.command.clone(),
+        args: spawn_info.args.clone(),
+        program: spawn_info
+            .program
+            .as_ref()
+            .map(|x| x.to_string_lossy().to_string()),
+        current_dir: spawn_info.current_dir.to_string_lossy().to_string(),
+        env: spawn_info.env.clone(),
+    })
+}
+
+impl From<ProcInfo> for ProcTreeEntry {
+    fn from(info: ProcInfo) -> Self {
+        Self {
+            pid: info.pid,
+            parent_pid: info.parent_pid,
+            command: info.command,
+            cpu_time_millis: info.cpu_time.as_millis() as u64,
+            rss_bytes: info.rss_bytes,
+        }
+    }
+}
+
 pub async fn kill_proc(
     state: Arc<ServerState>,
     args: &KillProcArgs,
@@ -159,6 +625,14 @@ pub async fn kill_proc(
         //       would block, but seems to be required in order to properly
         //       have the process clean up -- try_wait doesn't seem to work
         Some(local_proc) => {
+            // Descendants are killed first as they are reparented once the
+            // proc itself dies, at which point we can no longer find them
+            if args.tree {
+                for info in proc_info::list_descendants(args.id).await? {
+                    proc_info::kill_pid(info.pid)?;
+                }
+            }
+
             let output = local_proc.kill_and_wait().await?;
             state.remove_proc_id(args.id).await;
 
@@ -183,6 +657,167 @@ mod tests {
         time::{delay_for, timeout},
     };
 
+    #[tokio::test]
+    async fn exec_proc_should_write_output_to_files_if_provided() {
+        let state = Arc::new(ServerState::default());
+        let dir = tempfile::tempdir().unwrap();
+        let stdout_path = dir.as_ref().join("out.txt");
+        std::fs::write(&stdout_path, "previous\n").unwrap();
+
+        let args = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("sh"),
+                args: vec![
+                    String::from("-c"),
+                    String::from("echo a; echo b >&2"),
+                ],
+                stdin: false,
+                stdout: true,
+                stderr: true,
+                current_dir: Some(dir.as_ref().to_string_lossy().to_string()),
+                umask: None,
+                detached: false,
+                stdout_file: Some(OutputFile {
+                    path: String::from("out.txt"),
+                    append: true,
+                }),
+                stderr_file: Some(OutputFile {
+                    path: String::from("err.txt"),
+                    append: false,
+                }),
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        let stdout_path = std::fs::canonicalize(stdout_path).unwrap();
+        let stderr_path =
+            std::fs::canonicalize(dir.as_ref()).unwrap().join("err.txt");
+        assert_eq!(
+            args.stdout_path,
+            Some(stdout_path.to_string_lossy().to_string())
+        );
+        assert_eq!(
+            args.stderr_path,
+            Some(stderr_path.to_string_lossy().to_string())
+        );
+
+        // Give the proc time to run and complete
+        delay_for(Duration::from_millis(100)).await;
+
+        assert_eq!(
+            std::fs::read_to_string(stdout_path).unwrap(),
+            "previous\na\n"
+        );
+        assert_eq!(std::fs::read_to_string(stderr_path).unwrap(), "b\n");
+
+        // Output was redirected, so there is nothing for the client to read
+        let err = read_proc_stdout(
+            Arc::clone(&state),
+            &ReadProcStdoutArgs {
+                id: args.id,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[tokio::test]
+    async fn exec_script_should_run_body_and_remove_script_after_exit() {
+        let state = Arc::new(ServerState::default());
+
+        let id = exec_script(
+            Arc::clone(&state),
+            &ExecScriptArgs {
+                interpreter: String::from("sh"),
+                body: String::from("echo \"$0\"; echo \"$1\""),
+                args: vec![String::from("hello")],
+                stdin: false,
+                stdout: true,
+                stderr: false,
+                current_dir: None,
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        // Give the script time to run and complete
+        delay_for(Duration::from_millis(100)).await;
+
+        let status = state
+            .procs
+            .lock()
+            .await
+            .get_mut(&id)
+            .unwrap()
+            .exit_status()
+            .await;
+        assert!(status.unwrap().is_success, "Script failed");
+
+        let stdout = read_proc_stdout(
+            Arc::clone(&state),
+            &ReadProcStdoutArgs {
+                id,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .output;
+        let stdout = String::from_utf8(stdout).unwrap();
+        let mut lines = stdout.lines();
+        let path = lines.next().unwrap();
+        assert_eq!(lines.next(), Some("hello"));
+        assert!(
+            !std::path::Path::new(path).exists(),
+            "Script not removed after exit"
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_proc_should_write_initial_stdin_and_close_it_if_requested() {
+        let state = Arc::new(ServerState::default());
+
+        let id = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("wc"),
+                args: vec![String::from("-l")],
+                stdout: true,
+                initial_stdin: Some(b"a\nb\nc\n".to_vec()),
+                close_stdin: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        // Give the proc time to see the end of its input and exit
+        delay_for(Duration::from_millis(100)).await;
+
+        let mut procs = state.procs.lock().await;
+        let local_proc = procs.get_mut(&id).unwrap();
+        let status = local_proc.exit_status().await;
+        assert!(status.unwrap().is_success, "Proc did not exit");
+
+        let stdout = local_proc.read_stdout().await.unwrap();
+        assert_eq!(String::from_utf8(stdout).unwrap().trim(), "3");
+
+        // Stdin is no longer available once closed
+        let err = local_proc.write_stdin(b"d\n").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
     #[tokio::test]
     async fn exec_proc_should_return_success_if_can_execute_process() {
         let state = Arc::new(ServerState::default());
@@ -196,6 +831,15 @@ mod tests {
                 stdout: false,
                 stderr: false,
                 current_dir: None,
+                umask: None,
+                detached: false,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
             },
         )
         .await
@@ -222,6 +866,15 @@ mod tests {
                 current_dir: Some(
                     tempdir.as_ref().to_string_lossy().to_string(),
                 ),
+                umask: None,
+                detached: false,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
             },
         )
         .await
@@ -234,6 +887,179 @@ mod tests {
         assert!(path.exists());
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_proc_should_apply_umask_if_provided() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempfile::tempdir().expect("Failed to create temp dir");
+        let state = Arc::new(ServerState::default());
+
+        let _ = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("touch"),
+                args: vec![String::from("test-file")],
+                stdin: false,
+                stdout: false,
+                stderr: false,
+                current_dir: Some(
+                    tempdir.as_ref().to_string_lossy().to_string(),
+                ),
+                umask: Some(0o077),
+                detached: false,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        // Give above some time to fully execute
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+
+        let metadata = std::fs::metadata(tempdir.as_ref().join("test-file"))
+            .expect("File was not created");
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exec_proc_pty_should_attach_proc_to_resizable_terminal() {
+        async fn read_until(
+            state: &ServerState,
+            id: u32,
+            text: &str,
+        ) -> String {
+            let mut output = Vec::new();
+            let _ = timeout(Duration::from_secs(2), async {
+                while !String::from_utf8_lossy(&output).contains(text) {
+                    if let Some(local_proc) =
+                        state.procs.lock().await.get_mut(&id)
+                    {
+                        output.extend(local_proc.read_stdout().await.unwrap());
+                    }
+                    delay_for(Duration::from_millis(10)).await;
+                }
+            })
+            .await;
+            String::from_utf8_lossy(&output).to_string()
+        }
+
+        let state = Arc::new(ServerState::default());
+
+        let id = exec_proc_pty(
+            Arc::clone(&state),
+            &ExecProcPtyArgs {
+                command: String::from("sh"),
+                args: vec![
+                    String::from("-c"),
+                    String::from("stty size; read x; stty size"),
+                ],
+                rows: 24,
+                cols: 80,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        let output = read_until(&state, id, "24 80").await;
+        assert!(output.contains("24 80"), "Unexpected output: {:?}", output);
+
+        let args = resize_pty(
+            Arc::clone(&state),
+            &ResizePtyArgs {
+                id,
+                rows: 30,
+                cols: 100,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!((args.rows, args.cols), (30, 100));
+
+        write_proc_stdin(
+            Arc::clone(&state),
+            &WriteProcStdinArgs {
+                id,
+                input: b"\n".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let output = read_until(&state, id, "30 100").await;
+        assert!(output.contains("30 100"), "Unexpected output: {:?}", output);
+    }
+
+    #[tokio::test]
+    async fn resize_pty_should_return_error_if_proc_has_no_terminal() {
+        let state = Arc::new(ServerState::default());
+
+        let id = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("sleep"),
+                args: vec![String::from("1")],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        let err = resize_pty(
+            Arc::clone(&state),
+            &ResizePtyArgs {
+                id,
+                rows: 30,
+                cols: 100,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn exec_proc_should_mark_proc_as_detached_if_requested() {
+        let state = Arc::new(ServerState::default());
+
+        let args = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("sleep"),
+                args: vec![String::from("0")],
+                stdin: false,
+                stdout: false,
+                stderr: false,
+                current_dir: None,
+                umask: None,
+                detached: true,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        let x = state.procs.lock().await;
+        let proc = x.get(&args.id).unwrap();
+        assert!(proc.is_detached(), "Proc was not marked as detached");
+    }
+
     #[tokio::test]
     async fn exec_proc_should_return_error_if_process_does_not_exist() {
         let state = Arc::new(ServerState::default());
@@ -247,6 +1073,15 @@ mod tests {
                 stdout: false,
                 stderr: false,
                 current_dir: None,
+                umask: None,
+                detached: false,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
             },
         )
         .await
@@ -255,6 +1090,35 @@ mod tests {
         assert_eq!(err.kind(), io::ErrorKind::NotFound);
     }
 
+    #[tokio::test]
+    async fn exec_proc_should_spawn_through_proc_launcher_of_server() {
+        let mut state = ServerState::default();
+        state.set_proc_launcher(From::from(
+            |args: &ExecProcArgs, mut cmd: Command| {
+                if args.command == "echo" {
+                    cmd.spawn()
+                } else {
+                    Err(io::Error::from(io::ErrorKind::PermissionDenied))
+                }
+            },
+        ));
+        let state = Arc::new(state);
+
+        let args = ExecProcArgs {
+            command: String::from("echo"),
+            ..Default::default()
+        };
+        assert!(exec_proc(Arc::clone(&state), &args).await.is_ok());
+
+        let args = ExecProcArgs {
+            command: String::from("cat"),
+            ..Default::default()
+        };
+        let err = exec_proc(Arc::clone(&state), &args).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(state.procs.lock().await.len(), 1);
+    }
+
     #[tokio::test]
     async fn write_proc_stdin_should_return_data_to_running_process() {
         let state = Arc::new(ServerState::default());
@@ -378,10 +1242,15 @@ mod tests {
         // Give process some time to run and complete
         delay_for(Duration::from_millis(50)).await;
 
-        let args =
-            read_proc_stdout(Arc::clone(&state), &ReadProcStdoutArgs { id })
-                .await
-                .unwrap();
+        let args = read_proc_stdout(
+            Arc::clone(&state),
+            &ReadProcStdoutArgs {
+                id,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(args.id, id, "Wrong id returned");
         assert_eq!(args.output, b"test\n");
@@ -408,10 +1277,15 @@ mod tests {
         // Give process some time to start
         delay_for(Duration::from_millis(50)).await;
 
-        let args =
-            read_proc_stdout(Arc::clone(&state), &ReadProcStdoutArgs { id })
-                .await
-                .unwrap();
+        let args = read_proc_stdout(
+            Arc::clone(&state),
+            &ReadProcStdoutArgs {
+                id,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(args.id, id, "Wrong id returned");
         assert!(args.output.is_empty());
@@ -422,10 +1296,15 @@ mod tests {
     {
         let state = Arc::new(ServerState::default());
 
-        let err =
-            read_proc_stdout(Arc::clone(&state), &ReadProcStdoutArgs { id: 0 })
-                .await
-                .unwrap_err();
+        let err = read_proc_stdout(
+            Arc::clone(&state),
+            &ReadProcStdoutArgs {
+                id: 0,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
 
         assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
     }
@@ -451,13 +1330,18 @@ mod tests {
         // Give process some time to run and complete
         delay_for(Duration::from_millis(50)).await;
 
-        let args =
-            read_proc_stderr(Arc::clone(&state), &ReadProcStderrArgs { id })
-                .await
-                .unwrap();
+        let args = read_proc_stderr(
+            Arc::clone(&state),
+            &ReadProcStderrArgs {
+                id,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(args.id, id, "Wrong id returned");
-        assert!(args.output.len() > 0);
+        assert!(!args.output.is_empty());
     }
 
     #[tokio::test]
@@ -481,10 +1365,15 @@ mod tests {
         // Give process some time to start
         delay_for(Duration::from_millis(50)).await;
 
-        let args =
-            read_proc_stderr(Arc::clone(&state), &ReadProcStderrArgs { id })
-                .await
-                .unwrap();
+        let args = read_proc_stderr(
+            Arc::clone(&state),
+            &ReadProcStderrArgs {
+                id,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
 
         assert_eq!(args.id, id, "Wrong id returned");
         assert!(args.output.is_empty());
@@ -495,10 +1384,15 @@ mod tests {
     {
         let state = Arc::new(ServerState::default());
 
-        let err =
-            read_proc_stderr(Arc::clone(&state), &ReadProcStderrArgs { id: 0 })
-                .await
-                .unwrap_err();
+        let err = read_proc_stderr(
+            Arc::clone(&state),
+            &ReadProcStderrArgs {
+                id: 0,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
 
         assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
     }
@@ -585,9 +1479,10 @@ mod tests {
         // Give process some time to start
         delay_for(Duration::from_millis(50)).await;
 
-        let args = kill_proc(Arc::clone(&state), &KillProcArgs { id })
-            .await
-            .unwrap();
+        let args =
+            kill_proc(Arc::clone(&state), &KillProcArgs { id, tree: false })
+                .await
+                .unwrap();
 
         assert_eq!(args.id, id);
     }
@@ -612,10 +1507,267 @@ mod tests {
         // Give process some time to run and complete
         delay_for(Duration::from_millis(50)).await;
 
-        let args = kill_proc(Arc::clone(&state), &KillProcArgs { id })
+        let args =
+            kill_proc(Arc::clone(&state), &KillProcArgs { id, tree: false })
+                .await
+                .unwrap();
+
+        assert_eq!(args.exit_code, Some(0))
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn read_proc_tree_and_kill_proc_should_include_forked_children() {
+        let state = Arc::new(ServerState::default());
+
+        let id = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("sh"),
+                args: vec![String::from("-c"), String::from("sleep 60; :")],
+                stdin: false,
+                stdout: false,
+                stderr: false,
+                current_dir: None,
+                umask: None,
+                detached: false,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        // Give the shell some time to fork
+        delay_for(Duration::from_millis(100)).await;
+
+        let args = read_proc_tree(Arc::clone(&state), &ReadProcTreeArgs { id })
             .await
             .unwrap();
 
-        assert_eq!(args.exit_code, Some(0))
+        assert_eq!(args.procs[0].pid, id);
+        let child = args
+            .procs
+            .iter()
+            .find(|p| p.parent_pid == id && p.command.starts_with("sleep"))
+            .expect("Missing forked child")
+            .pid;
+
+        kill_proc(Arc::clone(&state), &KillProcArgs { id, tree: true })
+            .await
+            .unwrap();
+
+        // Give the child some time to die, where it may linger as a zombie
+        delay_for(Duration::from_millis(50)).await;
+
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", child))
+            .unwrap_or_default();
+        assert!(
+            stat.is_empty() || stat.contains(") Z"),
+            "Child still running: {}",
+            stat
+        );
+    }
+
+    #[tokio::test]
+    async fn read_proc_tree_should_return_error_if_proc_not_found() {
+        let state = Arc::new(ServerState::default());
+
+        let err = read_proc_tree(state, &ReadProcTreeArgs { id: 999 })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn list_procs_should_filter_and_page_procs_oldest_first() {
+        let state = Arc::new(ServerState::default());
+
+        let mut ids = Vec::new();
+        for (command, labels) in &[
+            ("sleep", vec!["web"]),
+            ("sleep", vec!["web", "db"]),
+            ("true", vec!["db"]),
+        ] {
+            let args = exec_proc(
+                Arc::clone(&state),
+                &ExecProcArgs {
+                    command: String::from(*command),
+                    args: vec![String::from("60")],
+                    stdin: false,
+                    stdout: false,
+                    stderr: false,
+                    current_dir: None,
+                    umask: None,
+                    detached: false,
+                    stdout_file: None,
+                    stderr_file: None,
+                    io_mode: ProcIoMode::Raw,
+                    newline: None,
+                    labels: labels.iter().map(|l| l.to_string()).collect(),
+                    initial_stdin: None,
+                    close_stdin: false,
+                },
+            )
+            .await
+            .unwrap();
+            ids.push(args.id);
+        }
+
+        let ids_of = |list: &ProcsListArgs| {
+            list.procs.iter().map(|p| p.id).collect::<Vec<u32>>()
+        };
+
+        let reply = list_procs(
+            Arc::clone(&state),
+            &ListProcsArgs {
+                label: Some(String::from("web")),
+                page: PageArgs {
+                    limit: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(ids_of(&reply), vec![ids[0]]);
+        assert_eq!(reply.page.total, 2);
+        assert_eq!(reply.page.next_offset, Some(1));
+
+        // Wait for the proc that exits immediately to be observed as exited
+        let mut exited = Vec::new();
+        for _ in 0..100 {
+            exited = ids_of(
+                &list_procs(
+                    Arc::clone(&state),
+                    &ListProcsArgs {
+                        status: Some(ProcStatusFilter::Exited),
+                        ..Default::default()
+                    },
+                )
+                .await,
+            );
+            if !exited.is_empty() {
+                break;
+            }
+            delay_for(Duration::from_millis(10)).await;
+        }
+        assert_eq!(exited, vec![ids[2]]);
+
+        let reply = list_procs(
+            Arc::clone(&state),
+            &ListProcsArgs {
+                status: Some(ProcStatusFilter::Running),
+                page: PageArgs {
+                    order: SortOrder::Descending,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .await;
+        assert_eq!(ids_of(&reply), vec![ids[1], ids[0]]);
+
+        let reply = list_procs(
+            Arc::clone(&state),
+            &ListProcsArgs {
+                min_age_secs: Some(60),
+                ..Default::default()
+            },
+        )
+        .await;
+        assert!(reply.procs.is_empty(), "Unexpected procs: {:?}", reply);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn read_proc_resources_should_sample_running_process() {
+        let state = Arc::new(ServerState::default());
+
+        let id = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("sleep"),
+                args: vec![String::from("60")],
+                stdin: false,
+                stdout: false,
+                stderr: false,
+                current_dir: None,
+                umask: None,
+                detached: false,
+                stdout_file: None,
+                stderr_file: None,
+                io_mode: ProcIoMode::Raw,
+                newline: None,
+                labels: vec![],
+                initial_stdin: None,
+                close_stdin: false,
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        let args = read_proc_resources(
+            Arc::clone(&state),
+            &ReadProcResourcesArgs { id },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(args.id, id);
+        assert!(args.rss_bytes > 0, "Resident memory was not reported");
+        assert!(args.cpu_percent >= 0.0, "Invalid cpu percent");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_proc_info_should_report_how_proc_was_spawned() {
+        let state = Arc::new(ServerState::default());
+        let dir = tempfile::tempdir().unwrap();
+
+        let id = exec_proc(
+            Arc::clone(&state),
+            &ExecProcArgs {
+                command: String::from("sh"),
+                args: vec![String::from("-c"), String::from("true")],
+                current_dir: Some(dir.path().to_string_lossy().to_string()),
+                io_mode: ProcIoMode::Raw,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        let args = read_proc_info(Arc::clone(&state), &ReadProcInfoArgs { id })
+            .await
+            .unwrap();
+
+        assert_eq!(args.id, id);
+        assert_eq!(args.command, "sh");
+        assert_eq!(args.args, vec!["-c", "true"]);
+        assert_eq!(
+            PathBuf::from(&args.current_dir),
+            dir.path().canonicalize().unwrap()
+        );
+        assert!(args.env.contains_key("PATH"), "Missing env: {:?}", args);
+
+        let program = PathBuf::from(args.program.expect("Missing program"));
+        assert!(program.is_absolute(), "Unresolved program: {:?}", program);
+
+        let reply = read_proc_info(
+            Arc::clone(&state),
+            &ReadProcInfoArgs { id: id + 1 },
+        )
+        .await;
+        assert!(reply.is_err(), "Unexpectedly found proc");
     }
 }
@@ -0,0 +1,34 @@
+use crate::core::{reply, request::TimeInfoArgs};
+use crate::utils::now_micros;
+use log::debug;
+
+pub async fn time_info(args: &TimeInfoArgs) -> reply::TimeInfoArgs {
+    let received_at = now_micros();
+    debug!("handler::time_info: {:?}", args);
+
+    reply::TimeInfoArgs {
+        client_sent_at_micros: args.sent_at_micros,
+        server_received_at_micros: received_at,
+        server_sent_at_micros: now_micros(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn time_info_should_echo_client_time_and_sample_server_clock() {
+        let before = now_micros();
+        let args = time_info(&TimeInfoArgs {
+            sent_at_micros: 123,
+        })
+        .await;
+        let after = now_micros();
+
+        assert_eq!(args.client_sent_at_micros, 123);
+        assert!(args.server_received_at_micros >= before);
+        assert!(args.server_sent_at_micros >= args.server_received_at_micros);
+        assert!(args.server_sent_at_micros <= after);
+    }
+}
@@ -0,0 +1,754 @@
+use super::fs::allowed_paths;
+use crate::core::{
+    reply::{self, TransactionStepArgs, TransactionStepStatus},
+    request,
+    server::{fs::AllowedPaths, state::ServerState},
+    RemotePath, Reply, Request,
+};
+use futures::future::BoxFuture;
+use log::{debug, warn};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Change to the fs that undoes part of an operation
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum UndoAction {
+    /// Creates an empty dir, doing nothing if it already exists
+    CreateDir(PathBuf),
+
+    /// Removes an empty dir, doing nothing if it no longer exists
+    RemoveDir(PathBuf),
+
+    /// Removes a file, doing nothing if it no longer exists
+    RemoveFile(PathBuf),
+
+    /// Renames a file or dir, replacing any file at the destination
+    Rename { from: PathBuf, to: PathBuf },
+}
+
+impl UndoAction {
+    async fn apply(&self, paths: &AllowedPaths) -> io::Result<()> {
+        let result = match self {
+            Self::CreateDir(path) => {
+                match paths.create_dir(path, false, None).await {
+                    Err(x) if x.kind() == io::ErrorKind::AlreadyExists => {
+                        Ok(())
+                    }
+                    x => x,
+                }
+            }
+            Self::RemoveDir(path) => paths.remove_dir(path, false).await,
+            Self::RemoveFile(path) => paths.remove_file(path).await,
+            Self::Rename { from, to } => paths.rename(from, to).await,
+        };
+
+        match result {
+            Err(x) if x.kind() == io::ErrorKind::NotFound => match self {
+                Self::RemoveDir(_) | Self::RemoveFile(_) => Ok(()),
+                _ => Err(x),
+            },
+            x => x,
+        }
+    }
+}
+
+/// How to undo a single operation of a transaction, recorded before the
+/// operation is executed
+#[derive(Debug, Default)]
+struct Undo {
+    /// Actions that undo the operation once it has been applied
+    applied: Vec<UndoAction>,
+
+    /// Actions that undo the preparation of the operation should the
+    /// operation itself fail
+    failed: Vec<UndoAction>,
+}
+
+/// Record of everything needed to put the fs back the way it was before a
+/// transaction began, including copies of files that were overwritten or
+/// removed and dirs that were moved aside rather than removed
+///
+/// Every path that the journal touches is confined to `paths`.
+#[derive(Debug, Default)]
+struct UndoJournal {
+    paths: AllowedPaths,
+    steps: Vec<Undo>,
+    file_backups: Vec<PathBuf>,
+    dir_backups: Vec<PathBuf>,
+}
+
+impl UndoJournal {
+    /// Records how to undo `request` before it is executed, which may
+    /// involve copying or moving aside what the request changes
+    async fn prepare(&mut self, request: &Request) -> io::Result<()> {
+        match self.undo_of(request).await {
+            Ok(undo) => {
+                self.steps.push(undo);
+                Ok(())
+            }
+            Err(x) => {
+                self.steps.push(Undo::default());
+                Err(x)
+            }
+        }
+    }
+
+    async fn undo_of(&mut self, request: &Request) -> io::Result<Undo> {
+        let mut undo = Undo::default();
+        match request {
+            Request::CreateDir(args) => {
+                let path = args.path.to_path_buf();
+                let mut missing = if args.include_components {
+                    path.ancestors().collect()
+                } else {
+                    vec![path.as_path()]
+                };
+                missing.retain(|x| !x.as_os_str().is_empty());
+
+                for dir in missing {
+                    if self.paths.exists(dir).await {
+                        break;
+                    }
+                    undo.applied.push(UndoAction::RemoveDir(dir.into()));
+                }
+            }
+            Request::RenameDir(args) => {
+                undo.applied.push(UndoAction::Rename {
+                    from: args.to.to_path_buf(),
+                    to: args.from.to_path_buf(),
+                });
+                if self.paths.is_dir(args.to.to_path_buf()).await {
+                    undo.applied
+                        .push(UndoAction::CreateDir(args.to.to_path_buf()));
+                }
+            }
+            Request::RemoveDir(args) if !args.non_empty => {
+                undo.applied
+                    .push(UndoAction::CreateDir(args.path.to_path_buf()));
+            }
+
+            // NOTE: Dir is moved aside and replaced with an empty one for
+            //       the request to remove, keeping its contents around
+            //       without having to copy them
+            Request::RemoveDir(args) => {
+                let path = args.path.to_path_buf();
+                if self.paths.is_dir(&path).await {
+                    let backup = backup_path(&path);
+                    self.paths.rename(&path, &backup).await?;
+                    if let Err(x) =
+                        self.paths.create_dir(&path, false, None).await
+                    {
+                        if let Err(x) = self.paths.rename(&backup, &path).await
+                        {
+                            warn!(
+                                "Failed to restore {}: {}",
+                                path.to_string_lossy(),
+                                x
+                            );
+                        }
+                        return Err(x);
+                    }
+
+                    self.dir_backups.push(backup.clone());
+                    undo.applied.push(UndoAction::RemoveDir(path.clone()));
+                    undo.applied.push(UndoAction::Rename {
+                        from: backup,
+                        to: path,
+                    });
+                    undo.failed = undo.applied.clone();
+                }
+            }
+            Request::RenameUnopenedFile(args) => {
+                undo.applied.push(UndoAction::Rename {
+                    from: args.to.to_path_buf(),
+                    to: args.from.to_path_buf(),
+                });
+                if let Some(backup) = self.save_copy(&args.to).await? {
+                    undo.applied.push(UndoAction::Rename {
+                        from: backup,
+                        to: args.to.to_path_buf(),
+                    });
+                }
+            }
+            Request::RemoveUnopenedFile(args) => {
+                if let Some(backup) = self.save_copy(&args.path).await? {
+                    undo.applied.push(UndoAction::Rename {
+                        from: backup,
+                        to: args.path.to_path_buf(),
+                    });
+                }
+            }
+
+            // NOTE: Writing a file can fail after it has been truncated, so
+            //       the file is put back even if the operation fails
+            Request::PatchFileLines(request::PatchFileLinesArgs {
+                path,
+                ..
+            }) => {
+                if let Some(backup) = self.save_copy(path).await? {
+                    undo.applied.push(UndoAction::Rename {
+                        from: backup,
+                        to: path.to_path_buf(),
+                    });
+                }
+                undo.failed = undo.applied.clone();
+            }
+            Request::WriteFileAtomicByPath(args) => {
+                match self.save_copy(&args.path).await? {
+                    Some(backup) => undo.applied.push(UndoAction::Rename {
+                        from: backup,
+                        to: args.path.to_path_buf(),
+                    }),
+                    None => undo
+                        .applied
+                        .push(UndoAction::RemoveFile(args.path.to_path_buf())),
+                }
+                undo.failed = undo.applied.clone();
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Unsupported operation in transaction",
+                ))
+            }
+        }
+
+        Ok(undo)
+    }
+
+    /// Copies the file at `path` next to it, returning the path of the copy
+    /// or none if there is no file to copy
+    async fn save_copy(
+        &mut self,
+        path: &RemotePath,
+    ) -> io::Result<Option<PathBuf>> {
+        let path = path.to_path_buf();
+        if !self.paths.is_file(&path).await {
+            return Ok(None);
+        }
+
+        let backup = backup_path(&path);
+        self.paths.copy(&path, &backup).await?;
+        self.file_backups.push(backup.clone());
+        Ok(Some(backup))
+    }
+
+    /// Undoes the preparation of the last operation, which failed, followed
+    /// by every operation before it, yielding for each operation from the
+    /// first why undoing it failed
+    async fn rollback(&mut self) -> Vec<Option<String>> {
+        let mut errors = Vec::new();
+        if let Some(undo) = self.steps.pop() {
+            errors.push(apply_all(&self.paths, &undo.failed).await);
+        }
+        while let Some(undo) = self.steps.pop() {
+            errors.push(apply_all(&self.paths, &undo.applied).await);
+        }
+
+        errors.reverse();
+        errors
+    }
+
+    /// Removes the copies of files and dirs that remain, which are no
+    /// longer needed once the transaction has been committed or rolled back
+    async fn cleanup(&mut self) {
+        for path in self.file_backups.drain(..) {
+            let action = UndoAction::RemoveFile(path.clone());
+            if let Err(x) = action.apply(&self.paths).await {
+                warn!("Failed to remove {}: {}", path.to_string_lossy(), x);
+            }
+        }
+        for path in self.dir_backups.drain(..) {
+            match self.paths.remove_dir(&path, true).await {
+                Err(x) if x.kind() != io::ErrorKind::NotFound => {
+                    warn!("Failed to remove {}: {}", path.to_string_lossy(), x)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Applies every action in order, stopping at the first that fails
+async fn apply_all(
+    paths: &AllowedPaths,
+    actions: &[UndoAction],
+) -> Option<String> {
+    for action in actions {
+        if let Err(x) = action.apply(paths).await {
+            return Some(format!("{:?}: {}", action, x));
+        }
+    }
+    None
+}
+
+/// Whether `request` is a mutation of the fs that a transaction can undo
+fn is_supported(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::CreateDir(_)
+            | Request::RenameDir(_)
+            | Request::RemoveDir(_)
+            | Request::RenameUnopenedFile(_)
+            | Request::RemoveUnopenedFile(_)
+            | Request::WriteFileAtomicByPath(_)
+            | Request::PatchFileLines(_)
+    )
+}
+
+/// Path next to `path` that a copy of it can be kept at, which is hidden
+/// and unlikely to already exist
+fn backup_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{:08x}.undo", name, rand::random::<u32>()))
+}
+
+/// Executes each operation in order using `execute`, undoing all of those
+/// that were applied once any fails
+///
+/// Every operation is checked against the permissions of the server before
+/// any is prepared, as preparing to undo an operation can itself change the
+/// fs.
+///
+/// Other requests are not kept from observing the fs part way through, so
+/// a transaction only guarantees that its own changes are applied in full
+/// or not at all.
+pub async fn transaction<F>(
+    state: Arc<ServerState>,
+    args: request::TransactionArgs,
+    mut execute: F,
+) -> Result<reply::TransactionArgs, io::Error>
+where
+    F: FnMut(Request) -> BoxFuture<'static, Reply>,
+{
+    debug!("handler::transaction: {:?}", args);
+
+    if let Some(x) = args.operations.iter().find(|x| !is_supported(x)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Transaction only supports mutations of paths, but got {} \
+                 request",
+                x.class()
+            ),
+        ));
+    }
+
+    for operation in args.operations.iter() {
+        state.permissions.check(operation).await?;
+    }
+
+    let total = args.operations.len();
+    let mut journal = UndoJournal {
+        paths: allowed_paths(&state).await,
+        ..Default::default()
+    };
+    let mut results = Vec::new();
+    let mut failed = false;
+    for operation in args.operations {
+        let result = match journal.prepare(&operation).await {
+            Ok(()) => execute(operation).await,
+            Err(x) => Reply::from(x),
+        };
+
+        failed = matches!(result, Reply::Error(_));
+        results.push(result);
+        if failed {
+            break;
+        }
+    }
+
+    let mut steps: Vec<TransactionStepArgs> = if failed {
+        let undo_errors = journal.rollback().await;
+        let last = results.len() - 1;
+        results
+            .into_iter()
+            .zip(undo_errors)
+            .enumerate()
+            .map(|(i, (result, undo_error))| TransactionStepArgs {
+                status: if i == last {
+                    TransactionStepStatus::Failed
+                } else if undo_error.is_some() {
+                    TransactionStepStatus::UndoFailed
+                } else {
+                    TransactionStepStatus::RolledBack
+                },
+                result: Some(result),
+                undo_error,
+            })
+            .collect()
+    } else {
+        results
+            .into_iter()
+            .map(|result| TransactionStepArgs {
+                status: TransactionStepStatus::Committed,
+                result: Some(result),
+                undo_error: None,
+            })
+            .collect()
+    };
+    journal.cleanup().await;
+
+    steps.resize(
+        total,
+        TransactionStepArgs {
+            status: TransactionStepStatus::Skipped,
+            result: None,
+            undo_error: None,
+        },
+    );
+
+    Ok(reply::TransactionArgs {
+        committed: !failed,
+        steps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::server::{
+        action::route_and_execute, fs::FileSystemManager,
+        permissions::Permissions,
+    };
+
+    async fn execute(
+        operations: Vec<Request>,
+    ) -> Result<reply::TransactionArgs, io::Error> {
+        execute_with_permissions(operations, Permissions::default()).await
+    }
+
+    async fn execute_with_permissions(
+        operations: Vec<Request>,
+        permissions: Permissions,
+    ) -> Result<reply::TransactionArgs, io::Error> {
+        let mut fs_manager = FileSystemManager::default();
+        fs_manager.set_allowed_paths(permissions.allowed_paths.clone());
+
+        let mut state = ServerState::default();
+        state.set_fs_manager(fs_manager);
+        state.set_permissions(permissions);
+        let state = Arc::new(state);
+
+        transaction(
+            Arc::clone(&state),
+            request::TransactionArgs::from(operations),
+            |req| {
+                route_and_execute(
+                    Arc::clone(&state),
+                    req,
+                    2,
+                    Default::default(),
+                    Default::default(),
+                )
+            },
+        )
+        .await
+    }
+
+    fn path_str(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    async fn dir_names(path: &Path) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut entries = tokio::fs::read_dir(path).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn transaction_should_apply_every_operation_if_all_succeed() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("a").join("b");
+        let file = dir.join("file");
+        let renamed = dir.join("renamed");
+
+        let args = execute(vec![
+            Request::CreateDir(request::CreateDirArgs {
+                path: path_str(&dir).into(),
+                include_components: true,
+                ..Default::default()
+            }),
+            Request::WriteFileAtomicByPath(
+                request::WriteFileAtomicByPathArgs {
+                    path: path_str(&file).into(),
+                    data: b"data".to_vec(),
+                    ..Default::default()
+                },
+            ),
+            Request::RenameUnopenedFile(request::RenameUnopenedFileArgs {
+                from: path_str(&file).into(),
+                to: path_str(&renamed).into(),
+                ..Default::default()
+            }),
+        ])
+        .await
+        .unwrap();
+
+        assert!(args.committed);
+        assert!(args
+            .steps
+            .iter()
+            .all(|x| x.status == TransactionStepStatus::Committed));
+        assert_eq!(tokio::fs::read(&renamed).await.unwrap(), b"data");
+        assert_eq!(dir_names(&dir).await, vec!["renamed"]);
+    }
+
+    #[tokio::test]
+    async fn transaction_should_undo_every_operation_if_one_fails() {
+        let root = tempfile::tempdir().unwrap();
+        let written = root.path().join("written");
+        let removed = root.path().join("removed");
+        let dir = root.path().join("dir");
+        let from = root.path().join("from");
+        let to = root.path().join("to");
+        tokio::fs::write(&written, b"old").await.unwrap();
+        tokio::fs::write(&removed, b"removed").await.unwrap();
+        tokio::fs::create_dir(&dir).await.unwrap();
+        tokio::fs::write(dir.join("inner"), b"inner").await.unwrap();
+        tokio::fs::write(&from, b"from").await.unwrap();
+        tokio::fs::write(&to, b"to").await.unwrap();
+
+        let args = execute(vec![
+            Request::WriteFileAtomicByPath(
+                request::WriteFileAtomicByPathArgs {
+                    path: path_str(&written).into(),
+                    data: b"new".to_vec(),
+                    ..Default::default()
+                },
+            ),
+            Request::RemoveUnopenedFile(request::RemoveUnopenedFileArgs {
+                path: path_str(&removed).into(),
+                ..Default::default()
+            }),
+            Request::RemoveDir(request::RemoveDirArgs {
+                path: path_str(&dir).into(),
+                non_empty: true,
+            }),
+            Request::RenameUnopenedFile(request::RenameUnopenedFileArgs {
+                from: path_str(&from).into(),
+                to: path_str(&to).into(),
+                ..Default::default()
+            }),
+            Request::CreateDir(request::CreateDirArgs {
+                path: path_str(&root.path().join("new")).into(),
+                ..Default::default()
+            }),
+            Request::RemoveUnopenedFile(request::RemoveUnopenedFileArgs {
+                path: path_str(&root.path().join("missing")).into(),
+                ..Default::default()
+            }),
+            Request::CreateDir(request::CreateDirArgs {
+                path: path_str(&root.path().join("never")).into(),
+                ..Default::default()
+            }),
+        ])
+        .await
+        .unwrap();
+
+        assert!(!args.committed);
+        let statuses: Vec<_> = args.steps.iter().map(|x| x.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                TransactionStepStatus::RolledBack,
+                TransactionStepStatus::RolledBack,
+                TransactionStepStatus::RolledBack,
+                TransactionStepStatus::RolledBack,
+                TransactionStepStatus::RolledBack,
+                TransactionStepStatus::Failed,
+                TransactionStepStatus::Skipped,
+            ]
+        );
+        assert!(matches!(args.steps[5].result, Some(Reply::Error(_))));
+        assert_eq!(args.steps[6].result, None);
+
+        assert_eq!(tokio::fs::read(&written).await.unwrap(), b"old");
+        assert_eq!(tokio::fs::read(&removed).await.unwrap(), b"removed");
+        assert_eq!(tokio::fs::read(dir.join("inner")).await.unwrap(), b"inner");
+        assert_eq!(tokio::fs::read(&from).await.unwrap(), b"from");
+        assert_eq!(tokio::fs::read(&to).await.unwrap(), b"to");
+
+        // Nothing is left behind by the transaction, including backups
+        assert_eq!(
+            dir_names(root.path()).await,
+            vec!["dir", "from", "removed", "to", "written"]
+        );
+    }
+
+    #[tokio::test]
+    async fn transaction_should_restore_file_truncated_by_failed_write() {
+        let root = tempfile::tempdir().unwrap();
+        let written = root.path().join("written");
+        let patched = root.path().join("patched");
+        let created = root.path().join("created");
+        tokio::fs::write(&written, b"old").await.unwrap();
+        tokio::fs::write(&patched, b"line\n").await.unwrap();
+
+        let operations = vec![
+            Request::WriteFileAtomicByPath(
+                request::WriteFileAtomicByPathArgs {
+                    path: path_str(&written).into(),
+                    data: b"new".to_vec(),
+                    ..Default::default()
+                },
+            ),
+            Request::PatchFileLines(request::PatchFileLinesArgs {
+                path: path_str(&patched).into(),
+                ..Default::default()
+            }),
+            Request::WriteFileAtomicByPath(
+                request::WriteFileAtomicByPathArgs {
+                    path: path_str(&created).into(),
+                    data: b"new".to_vec(),
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        // Each operation truncates (or creates) its file and then fails, as
+        // if the write was cut short
+        for operation in operations {
+            let args = transaction(
+                Arc::new(ServerState::default()),
+                request::TransactionArgs::from(vec![operation]),
+                |req| {
+                    let path = match &req {
+                        Request::WriteFileAtomicByPath(args) => {
+                            args.path.to_path_buf()
+                        }
+                        Request::PatchFileLines(args) => {
+                            args.path.to_path_buf()
+                        }
+                        _ => unreachable!(),
+                    };
+                    Box::pin(async move {
+                        tokio::fs::write(&path, b"").await.unwrap();
+                        Reply::from(io::Error::from(io::ErrorKind::WriteZero))
+                    })
+                },
+            )
+            .await
+            .unwrap();
+
+            assert!(!args.committed);
+            assert_eq!(args.steps[0].status, TransactionStepStatus::Failed);
+            assert_eq!(args.steps[0].undo_error, None);
+        }
+
+        assert_eq!(tokio::fs::read(&written).await.unwrap(), b"old");
+        assert_eq!(tokio::fs::read(&patched).await.unwrap(), b"line\n");
+        assert_eq!(dir_names(root.path()).await, vec!["patched", "written"]);
+    }
+
+    #[tokio::test]
+    async fn transaction_should_reject_unsupported_operations_up_front() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("dir");
+
+        let err = execute(vec![
+            Request::CreateDir(request::CreateDirArgs {
+                path: path_str(&dir).into(),
+                ..Default::default()
+            }),
+            Request::Heartbeat,
+        ])
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn transaction_should_check_permissions_before_changing_anything() {
+        let root = tempfile::tempdir().unwrap();
+        let allowed = root.path().join("allowed");
+        let dir = allowed.join("dir");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(dir.join("inner"), b"inner").await.unwrap();
+        tokio::fs::write(root.path().join("outside"), b"old")
+            .await
+            .unwrap();
+
+        let err = execute_with_permissions(
+            vec![
+                Request::RemoveDir(request::RemoveDirArgs {
+                    path: path_str(&dir).into(),
+                    non_empty: true,
+                }),
+                Request::WriteFileAtomicByPath(
+                    request::WriteFileAtomicByPathArgs {
+                        path: path_str(&root.path().join("outside")).into(),
+                        data: b"new".to_vec(),
+                        ..Default::default()
+                    },
+                ),
+            ],
+            Permissions {
+                allowed_paths: vec![allowed.clone()],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(dir_names(&allowed).await, vec!["dir"]);
+        assert_eq!(dir_names(&dir).await, vec!["inner"]);
+        assert_eq!(
+            tokio::fs::read(root.path().join("outside")).await.unwrap(),
+            b"old"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn undo_journal_should_not_back_up_through_symlinks() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        tokio::fs::write(outside.path().join("secret"), b"secret")
+            .await
+            .unwrap();
+        std::os::unix::fs::symlink(
+            outside.path(),
+            root.path().join("dir-link"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret"),
+            root.path().join("file-link"),
+        )
+        .unwrap();
+
+        let mut journal = UndoJournal {
+            paths: AllowedPaths::new(vec![root.path().to_path_buf()]),
+            ..Default::default()
+        };
+        for path in &["file-link", "dir-link/secret"] {
+            let path = path_str(&root.path().join(path));
+            journal
+                .prepare(&Request::WriteFileAtomicByPath(
+                    request::WriteFileAtomicByPathArgs {
+                        path: path.into(),
+                        data: b"new".to_vec(),
+                        ..Default::default()
+                    },
+                ))
+                .await
+                .unwrap();
+        }
+
+        assert!(journal.file_backups.is_empty());
+        assert_eq!(dir_names(root.path()).await, vec!["dir-link", "file-link"]);
+        assert_eq!(dir_names(outside.path()).await, vec!["secret"]);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::core::{
+    reply::{self, WasmHandlerLoadedArgs, WasmHandlerUnloadedArgs},
+    request::{CustomArgs, LoadWasmHandlerArgs, UnloadWasmHandlerArgs},
+    server::state::ServerState,
+    Reply,
+};
+use log::debug;
+use std::io;
+use std::sync::Arc;
+
+pub async fn load_wasm_handler(
+    state: Arc<ServerState>,
+    args: &LoadWasmHandlerArgs,
+) -> Result<WasmHandlerLoadedArgs, io::Error> {
+    debug!(
+        "handler::load_wasm_handler: {} ({} bytes)",
+        args.namespace,
+        args.module.len()
+    );
+
+    state
+        .wasm_handlers
+        .load(&args.namespace, &args.module)
+        .await?;
+
+    Ok(WasmHandlerLoadedArgs {
+        namespace: args.namespace.clone(),
+    })
+}
+
+pub async fn unload_wasm_handler(
+    state: Arc<ServerState>,
+    args: &UnloadWasmHandlerArgs,
+) -> Result<WasmHandlerUnloadedArgs, io::Error> {
+    debug!("handler::unload_wasm_handler: {:?}", args);
+
+    if state.wasm_handlers.unload(&args.namespace).await {
+        Ok(WasmHandlerUnloadedArgs {
+            namespace: args.namespace.clone(),
+        })
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No wasm handler serves {}", args.namespace),
+        ))
+    }
+}
+
+/// Evaluates a custom request with the wasm module serving its namespace,
+/// yielding none if no module serves it
+pub async fn custom(state: &ServerState, args: &CustomArgs) -> Option<Reply> {
+    let namespace = args.namespace.as_ref()?;
+    let result = state.wasm_handlers.invoke(namespace, &args.data).await?;
+
+    Some(
+        result
+            .map(|data| Reply::Custom(reply::CustomArgs { data }))
+            .unwrap_or_else(Reply::from),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ECHO_MODULE: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+        (func (export "handle") (param $ptr i32) (param $len i32) (result i64)
+            (i64.or
+                (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                (i64.extend_i32_u (local.get $len)))))"#;
+
+    #[tokio::test]
+    async fn custom_should_be_evaluated_by_loaded_module() {
+        let state = Arc::new(ServerState::default());
+        load_wasm_handler(
+            Arc::clone(&state),
+            &LoadWasmHandlerArgs {
+                namespace: String::from("echo"),
+                module: ECHO_MODULE.as_bytes().to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut args = CustomArgs::from(b"data".to_vec());
+        assert_eq!(custom(&state, &args).await, None);
+
+        args.namespace = Some(String::from("echo"));
+        assert_eq!(
+            custom(&state, &args).await,
+            Some(Reply::Custom(reply::CustomArgs {
+                data: b"data".to_vec()
+            }))
+        );
+
+        unload_wasm_handler(
+            Arc::clone(&state),
+            &UnloadWasmHandlerArgs {
+                namespace: String::from("echo"),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(custom(&state, &args).await, None);
+    }
+
+    #[tokio::test]
+    async fn unload_wasm_handler_should_fail_if_namespace_not_served() {
+        let err = unload_wasm_handler(
+            Arc::new(ServerState::default()),
+            &UnloadWasmHandlerArgs {
+                namespace: String::from("missing"),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
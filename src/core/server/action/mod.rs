@@ -1,22 +1,35 @@
 mod handler;
 
 use crate::core::{
-    reply, server::state::ServerState, Content, Header,
-    LazilyTransformedRequest, Msg, MsgError, Reply, ReplyError, Request,
-    TransformRequestError,
+    reply::{self, ErrorCode},
+    server::{
+        state::ServerState, transfers::TransferAccount, webhook::WebhookEvent,
+    },
+    transport::{
+        auth::identity,
+        crypto::{
+            handshake::{Session, SEALED_MSG_OVERHEAD},
+            CryptError,
+        },
+    },
+    Content, Header, LazilyTransformedRequest, Msg, MsgError, Reply,
+    ReplyError, Request, TransformRequestError, DEFAULT_COMPRESSION_THRESHOLD,
 };
 use derive_more::{Display, Error};
-use futures::future::{BoxFuture, FutureExt};
+use futures::{
+    future::{BoxFuture, FutureExt},
+    stream::{FuturesUnordered, StreamExt},
+};
 use log::trace;
-use std::collections::hash_map::Entry;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::{runtime::Handle, sync::mpsc};
 
 #[derive(Debug, Display, Error)]
 pub enum ActionError {
     MsgError(MsgError),
+    SealFailed(CryptError),
     RespondFailed,
     UnexpectedContent,
 }
@@ -24,11 +37,19 @@ pub enum ActionError {
 struct OriginSender<T> {
     tx: mpsc::Sender<T>,
     addr: SocketAddr,
+
+    /// Session that replies are sealed with, being that of the origin when
+    /// it sent a sealed request
+    session: Option<Session>,
 }
 
 impl OriginSender<Vec<u8>> {
     pub fn new(tx: mpsc::Sender<Vec<u8>>, addr: SocketAddr) -> Self {
-        Self { tx, addr }
+        Self {
+            tx,
+            addr,
+            session: None,
+        }
     }
 
     pub async fn send(
@@ -44,7 +65,11 @@ impl OriginSender<(Vec<u8>, SocketAddr)> {
         tx: mpsc::Sender<(Vec<u8>, SocketAddr)>,
         addr: SocketAddr,
     ) -> Self {
-        Self { tx, addr }
+        Self {
+            tx,
+            addr,
+            session: None,
+        }
     }
 
     pub async fn send(
@@ -57,24 +82,19 @@ impl OriginSender<(Vec<u8>, SocketAddr)> {
 
 pub struct Executor<T> {
     origin_sender: OriginSender<T>,
-    max_depth: u8,
-}
-
-impl<T> Executor<T> {
-    /// Represents the maximum depth to process nested sequential and batch operations
-    pub const DEFAULT_MAX_DEPTH: u8 = 5;
+    max_msg_size: usize,
 }
 
 impl Executor<Vec<u8>> {
     pub fn new(
         tx: mpsc::Sender<Vec<u8>>,
         origin_addr: SocketAddr,
-        max_depth: u8,
+        max_msg_size: usize,
     ) -> Self {
         let origin_sender = OriginSender::<Vec<u8>>::new(tx, origin_addr);
         Self {
             origin_sender,
-            max_depth,
+            max_msg_size,
         }
     }
 
@@ -83,36 +103,113 @@ impl Executor<Vec<u8>> {
         state: Arc<ServerState>,
         msg: Msg,
     ) -> Result<(), ActionError> {
-        let header = msg.header.clone();
-        let origin_sender = self.origin_sender;
+        let received = Instant::now();
+        let mut origin_sender = self.origin_sender;
         let addr = origin_sender.addr;
+        let max_msg_size = self.max_msg_size;
 
-        let reply = validate_route_and_execute(
-            state,
-            msg.content,
-            addr,
-            self.max_depth,
-        )
-        .await?;
+        let msg = match unseal(&state, &mut origin_sender, msg).await {
+            Ok(msg) => msg,
+            Err((msg, x)) => {
+                return Self::respond(
+                    Reply::Error(x),
+                    msg.header.clone(),
+                    received,
+                    max_msg_size,
+                    &mut origin_sender,
+                    &state,
+                    &TransferAccount::of(
+                        &msg,
+                        verified_signer(&msg).as_deref(),
+                        addr,
+                    ),
+                )
+                .await;
+            }
+        };
+        let header = msg.header.clone();
+        let account =
+            TransferAccount::of(&msg, verified_signer(&msg).as_deref(), addr);
+        let (partial_tx, mut partial_rx) = mpsc::channel(1);
+
+        // Forward any partial replies (such as streamed chunks) as they are
+        // produced, all tied to the header of the original request
+        let (reply, forwarded) = tokio::join!(
+            validate_route_and_execute(
+                Arc::clone(&state),
+                msg,
+                &account,
+                addr,
+                partial_tx
+            ),
+            async {
+                while let Some(reply) = partial_rx.recv().await {
+                    Self::respond(
+                        reply,
+                        header.clone(),
+                        received,
+                        max_msg_size,
+                        &mut origin_sender,
+                        &state,
+                        &account,
+                    )
+                    .await?;
+                }
+                Ok::<(), ActionError>(())
+            }
+        );
+        let reply = reply?;
+        forwarded?;
 
         match reply {
             Reply::Ignore => Ok(()),
-            _ => Self::respond(reply, header, origin_sender).await,
+            _ => {
+                Self::respond(
+                    reply,
+                    header,
+                    received,
+                    max_msg_size,
+                    &mut origin_sender,
+                    &state,
+                    &account,
+                )
+                .await
+            }
         }
     }
 
     async fn respond(
         reply: Reply,
         parent_header: Header,
-        mut origin_sender: OriginSender<Vec<u8>>,
+        received: Instant,
+        max_msg_size: usize,
+        origin_sender: &mut OriginSender<Vec<u8>>,
+        state: &ServerState,
+        account: &TransferAccount,
     ) -> Result<(), ActionError> {
-        let new_msg = Msg::new(Content::Reply(reply), Some(parent_header));
-        let data = new_msg.to_vec().map_err(ActionError::MsgError)?;
+        let data = match encode_reply_within_limit(
+            state,
+            origin_sender.addr,
+            reply,
+            parent_header,
+            received.elapsed(),
+            max_msg_size,
+            origin_sender.session.as_ref(),
+        )
+        .await?
+        {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        let len = data.len() as u64;
 
         origin_sender
             .send(data)
             .await
-            .map_err(|_| ActionError::RespondFailed)
+            .map_err(|_| ActionError::RespondFailed)?;
+
+        state.transfers.record_out(account, len).await;
+        Ok(())
     }
 }
 
@@ -120,13 +217,13 @@ impl Executor<(Vec<u8>, SocketAddr)> {
     pub fn new(
         tx: mpsc::Sender<(Vec<u8>, SocketAddr)>,
         origin_addr: SocketAddr,
-        max_depth: u8,
+        max_msg_size: usize,
     ) -> Self {
         let origin_sender =
             OriginSender::<(Vec<u8>, SocketAddr)>::new(tx, origin_addr);
         Self {
             origin_sender,
-            max_depth,
+            max_msg_size,
         }
     }
 
@@ -135,55 +232,665 @@ impl Executor<(Vec<u8>, SocketAddr)> {
         state: Arc<ServerState>,
         msg: Msg,
     ) -> Result<(), ActionError> {
-        let header = msg.header.clone();
-        let origin_sender = self.origin_sender;
+        let received = Instant::now();
+        let mut origin_sender = self.origin_sender;
         let addr = origin_sender.addr;
+        let max_msg_size = self.max_msg_size;
 
-        let reply = validate_route_and_execute(
-            state,
-            msg.content,
-            addr,
-            self.max_depth,
-        )
-        .await?;
+        let msg = match unseal(&state, &mut origin_sender, msg).await {
+            Ok(msg) => msg,
+            Err((msg, x)) => {
+                return Self::respond(
+                    Reply::Error(x),
+                    msg.header.clone(),
+                    received,
+                    max_msg_size,
+                    &mut origin_sender,
+                    &state,
+                    &TransferAccount::of(
+                        &msg,
+                        verified_signer(&msg).as_deref(),
+                        addr,
+                    ),
+                )
+                .await;
+            }
+        };
+        let header = msg.header.clone();
+        let account =
+            TransferAccount::of(&msg, verified_signer(&msg).as_deref(), addr);
+        let (partial_tx, mut partial_rx) = mpsc::channel(1);
+
+        // Forward any partial replies (such as streamed chunks) as they are
+        // produced, all tied to the header of the original request
+        let (reply, forwarded) = tokio::join!(
+            validate_route_and_execute(
+                Arc::clone(&state),
+                msg,
+                &account,
+                addr,
+                partial_tx
+            ),
+            async {
+                while let Some(reply) = partial_rx.recv().await {
+                    Self::respond(
+                        reply,
+                        header.clone(),
+                        received,
+                        max_msg_size,
+                        &mut origin_sender,
+                        &state,
+                        &account,
+                    )
+                    .await?;
+                }
+                Ok::<(), ActionError>(())
+            }
+        );
+        let reply = reply?;
+        forwarded?;
 
         match reply {
             Reply::Ignore => Ok(()),
-            _ => Self::respond(reply, header, origin_sender).await,
+            _ => {
+                Self::respond(
+                    reply,
+                    header,
+                    received,
+                    max_msg_size,
+                    &mut origin_sender,
+                    &state,
+                    &account,
+                )
+                .await
+            }
         }
     }
 
     async fn respond(
         reply: Reply,
         parent_header: Header,
-        mut origin_sender: OriginSender<(Vec<u8>, SocketAddr)>,
+        received: Instant,
+        max_msg_size: usize,
+        origin_sender: &mut OriginSender<(Vec<u8>, SocketAddr)>,
+        state: &ServerState,
+        account: &TransferAccount,
     ) -> Result<(), ActionError> {
-        let new_msg = Msg::new(Content::Reply(reply), Some(parent_header));
-        let data = new_msg.to_vec().map_err(ActionError::MsgError)?;
+        let data = match encode_reply_within_limit(
+            state,
+            origin_sender.addr,
+            reply,
+            parent_header,
+            received.elapsed(),
+            max_msg_size,
+            origin_sender.session.as_ref(),
+        )
+        .await?
+        {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        let len = data.len() as u64;
 
         origin_sender
             .send(data)
             .await
-            .map_err(|_| ActionError::RespondFailed)
+            .map_err(|_| ActionError::RespondFailed)?;
+
+        state.transfers.record_out(account, len).await;
+        Ok(())
+    }
+}
+
+/// Opens `msg` if it was sealed with the session negotiated by the origin of
+/// `origin_sender`, yielding the msg it wraps and keeping the session so
+/// that every reply to it is sealed too
+///
+/// Once an origin negotiates a session, everything it sends must be sealed,
+/// so that what it asks of the server can neither be read nor altered along
+/// the way. The only exception is a new handshake signed by the identity
+/// the session is pinned to, which lets that identity recover a session
+/// whose key it lost. The msg is given back along with an error if it cannot
+/// be opened.
+async fn unseal<T>(
+    state: &ServerState,
+    origin_sender: &mut OriginSender<T>,
+    msg: Msg,
+) -> Result<Msg, (Msg, ReplyError)> {
+    let origin = origin_sender.addr;
+    let session = state
+        .sessions
+        .lock()
+        .await
+        .get(&origin)
+        .map(|session| Session::clone(session));
+
+    let args = match (&msg.content, session.as_ref()) {
+        (Content::Request(Request::Sealed(args)), _) => args,
+        (_, None) => return Ok(msg),
+        (Content::Request(Request::Handshake(_)), Some(session))
+            if signed_by_identity_of(&msg, session) =>
+        {
+            return Ok(msg)
+        }
+        _ => {
+            let error = ReplyError::with_code(
+                format!("Origin {} must seal msgs with its session", origin),
+                ErrorCode::SESSION_REQUIRED,
+            );
+            return Err((msg, error));
+        }
+    };
+
+    let session = match session {
+        Some(session) => session,
+        None => {
+            let error = ReplyError::with_code(
+                format!("Origin {} has no session to open msg with", origin),
+                ErrorCode::SESSION_REQUIRED,
+            );
+            return Err((msg, error));
+        }
+    };
+
+    let opened = session
+        .open(&args.nonce, &args.data)
+        .map_err(|x| x.to_string())
+        .and_then(|data| Msg::from_slice(&data).map_err(|x| x.to_string()))
+        .and_then(|inner| match &inner.content {
+            Content::Request(Request::Sealed(_)) => {
+                Err(String::from("Sealed msg cannot hold another seal"))
+            }
+            _ if inner.header.id != msg.header.id => {
+                Err(String::from("Sealed msg does not match its wrapper"))
+            }
+            _ => Ok(inner),
+        });
+
+    match opened {
+        Ok(inner) => {
+            origin_sender.session = Some(session);
+            Ok(inner)
+        }
+        Err(x) => {
+            let error = ReplyError::with_code(
+                format!("Failed to open sealed msg: {}", x),
+                ErrorCode::SEAL_REJECTED,
+            );
+            Err((msg, error))
+        }
+    }
+}
+
+/// Public key that `msg` carries a valid signature by, if any
+fn verified_signer(msg: &Msg) -> Option<Vec<u8>> {
+    msg.verify_signature().ok().flatten().map(<[u8]>::to_vec)
+}
+
+/// Whether `msg` carries a valid signature by the identity that `session` is
+/// pinned to
+fn signed_by_identity_of(msg: &Msg, session: &Session) -> bool {
+    match (msg.verify_signature(), session.identity()) {
+        (Ok(Some(signer)), Some(identity)) => signer == identity,
+        _ => false,
+    }
+}
+
+/// Encodes a reply to the msg with `parent_header`, substituting an error
+/// if the reply is too large to be sent so the origin is not left waiting
+///
+/// The reply is compressed if the origin accepts compressed replies and did
+/// not opt out of compression for the msg, and carries the `processing` time
+/// spent on the msg so far so that the origin can tell it apart from network
+/// latency. If a `session` is provided, the reply is then sealed with it.
+fn encode_reply(
+    reply: Reply,
+    parent_header: Header,
+    processing: Duration,
+    max_msg_size: usize,
+    session: Option<&Session>,
+) -> Result<Vec<u8>, ActionError> {
+    let session = match session {
+        Some(session) => session,
+        None => {
+            return encode_unsealed_reply(
+                reply,
+                parent_header,
+                processing,
+                max_msg_size,
+            )
+        }
+    };
+
+    // NOTE: Leave room for the sealed reply to be wrapped in a msg of its own
+    let data = encode_unsealed_reply(
+        reply,
+        parent_header.clone(),
+        processing,
+        max_msg_size.saturating_sub(SEALED_MSG_OVERHEAD),
+    )?;
+    let (nonce, data) = session.seal(&data).map_err(ActionError::SealFailed)?;
+    Msg::new(
+        Content::Reply(Reply::Sealed(reply::SealedArgs { nonce, data })),
+        Some(parent_header),
+    )
+    .to_vec()
+    .map_err(ActionError::MsgError)
+}
+
+fn encode_unsealed_reply(
+    reply: Reply,
+    parent_header: Header,
+    processing: Duration,
+    max_msg_size: usize,
+) -> Result<Vec<u8>, ActionError> {
+    let flags = parent_header.flags;
+    let encode = |msg: &mut Msg| {
+        msg.header.processing_micros = Some(processing.as_micros() as u64);
+        if flags.accepts_compression && !flags.no_compression {
+            msg.to_vec_compressed(DEFAULT_COMPRESSION_THRESHOLD)
+        } else {
+            msg.to_vec()
+        }
+    };
+
+    let mut new_msg =
+        Msg::new(Content::Reply(reply), Some(parent_header.clone()));
+    let data = encode(&mut new_msg).map_err(ActionError::MsgError)?;
+    if data.len() <= max_msg_size {
+        return Ok(data);
     }
+
+    let error = ReplyError::with_code(
+        format!(
+            "{} of {} bytes exceeds maximum msg size of {} bytes",
+            new_msg.content.type_name().unwrap_or_default(),
+            data.len(),
+            max_msg_size,
+        ),
+        ErrorCode::MSG_TOO_LARGE,
+    );
+    encode(&mut Msg::new(
+        Content::Reply(Reply::Error(error)),
+        Some(parent_header),
+    ))
+    .map_err(ActionError::MsgError)
+}
+
+/// Encodes a reply as with `encode_reply`, substituting an error if the
+/// reply would send `origin` more than it is allowed to be sent before it is
+/// bound, or yielding none if even the error cannot be sent
+async fn encode_reply_within_limit(
+    state: &ServerState,
+    origin: SocketAddr,
+    reply: Reply,
+    parent_header: Header,
+    processing: Duration,
+    max_msg_size: usize,
+    session: Option<&Session>,
+) -> Result<Option<Vec<u8>>, ActionError> {
+    let data = encode_reply(
+        reply,
+        parent_header.clone(),
+        processing,
+        max_msg_size,
+        session,
+    )?;
+    if state.origins.record_reply(origin, data.len() as u64).await {
+        return Ok(Some(data));
+    }
+
+    let error = ReplyError::with_code(
+        format!(
+            "Reply of {} bytes exceeds what origin {} can be sent until it \
+             is bound",
+            data.len(),
+            origin
+        ),
+        ErrorCode::ORIGIN_UNBOUND,
+    );
+    let data = encode_reply(
+        Reply::Error(error),
+        parent_header,
+        processing,
+        max_msg_size,
+        session,
+    )?;
+    if state.origins.record_reply(origin, data.len() as u64).await {
+        Ok(Some(data))
+    } else {
+        trace!("Dropping reply to unbound origin {}", origin);
+        Ok(None)
+    }
+}
+
+/// Reports a msg from `origin` that was rejected for `reason` by the
+/// signature policy or list of trusted clients to webhooks
+fn fire_auth_failed(state: &ServerState, origin: SocketAddr, reason: &str) {
+    state.webhooks.fire(
+        &state.tasks,
+        WebhookEvent::AuthFailed {
+            origin: origin.to_string(),
+            reason: reason.to_string(),
+        },
+    );
 }
 
 async fn validate_route_and_execute(
     state: Arc<ServerState>,
-    content: Content,
+    msg: Msg,
+    account: &TransferAccount,
     origin: SocketAddr,
-    max_depth: u8,
+    partial_tx: mpsc::Sender<Reply>,
 ) -> Result<Reply, ActionError> {
-    trace!("Executing content: {:?}", content);
+    // Reject msgs whose signature does not satisfy the policy of the server
+    // before anything else sees them
+    if let Err(x) = state.signature_policy.check(&msg) {
+        fire_auth_failed(&state, origin, &x);
+        return Ok(Reply::Error(ReplyError::with_code(
+            x,
+            ErrorCode::SIGNATURE_REJECTED,
+        )));
+    }
+
+    // Reject msgs from clients whose keys are not trusted before they are
+    // accounted for or routed
+    if let Err(x) = state.trusted_clients.check(&msg).await {
+        fire_auth_failed(&state, origin, &x);
+        return Ok(Reply::Error(ReplyError::with_code(
+            x,
+            ErrorCode::CLIENT_NOT_TRUSTED,
+        )));
+    }
+
+    // Account for the bytes received from the identity, rejecting the msg
+    // if the identity has used up its quota
+    let len = msg.encoded_len().map_err(ActionError::MsgError)? as u64;
+    if let Err(x) = state.transfers.record_in(account, len).await {
+        state.webhooks.fire(
+            &state.tasks,
+            WebhookEvent::QuotaExceeded {
+                identity: account.identity.clone(),
+                class: account.class.to_string(),
+            },
+        );
+        return Ok(Reply::Error(ReplyError::QuotaExceeded(x)));
+    }
+
+    // Requests too small to be worth answering from an unbound origin are
+    // ignored, as they could be spoofed to reflect replies at a victim
+    if !state.origins.record_request(origin, len).await {
+        trace!("Ignoring undersized request from unbound origin {}", origin);
+        return Ok(Reply::Ignore);
+    }
+
+    // NOTE: The identity that signs a handshake is pinned to the session it
+    //       negotiates, so only check the signature of handshakes here
+    let signer = match &msg.content {
+        Content::Request(Request::Handshake(_)) => {
+            msg.verify_signature().ok().flatten().map(<[u8]>::to_vec)
+        }
+        _ => None,
+    };
+
+    let Msg {
+        header, content, ..
+    } = msg;
+    trace!(
+        "Executing content (trace {:?}, parent span {:?}): {:?}",
+        header.trace_id,
+        header.parent_span_id,
+        content
+    );
 
     let request = content
         .into_request()
         .ok_or(ActionError::UnexpectedContent)?;
     update_origin_last_touched(Arc::clone(&state), origin).await;
-    Ok(route_and_execute(state, request, max_depth).await)
+
+    // An origin that has not proven it receives replies may be spoofed, so
+    // it cannot change anything on the server until it is bound
+    let strict = state.origins.is_strict();
+    match &request {
+        Request::BindOrigin(args)
+            if state.origins.is_challenging()
+                && !args.nonce.is_empty()
+                && !state.signature_policy.trusts(&args.identity_key) =>
+        {
+            return Ok(Reply::Error(ReplyError::with_code(
+                format!(
+                    "Origin {} signed its nonce with untrusted key {}",
+                    origin,
+                    identity::fingerprint(&args.identity_key)
+                ),
+                ErrorCode::ORIGIN_UNBOUND,
+            )));
+        }
+        Request::BindOrigin(args) if state.origins.is_challenging() => {
+            return Ok(state
+                .origins
+                .bind(origin, args)
+                .await
+                .map(Reply::OriginBinding)
+                .unwrap_or_else(|x| {
+                    Reply::Error(ReplyError::with_code(
+                        x,
+                        ErrorCode::LIMIT_EXCEEDED,
+                    ))
+                }));
+        }
+        Request::BindOrigin(_) => {
+            return Ok(Reply::OriginBinding(reply::OriginBindingArgs {
+                bound: true,
+                nonce: Vec::new(),
+            }));
+        }
+        request
+            if strict
+                && !request.is_idempotent()
+                && !state.origins.is_bound(origin).await =>
+        {
+            return Ok(Reply::Error(ReplyError::with_code(
+                format!(
+                    "Origin {} must be bound before sending {}",
+                    origin,
+                    request.class()
+                ),
+                ErrorCode::ORIGIN_UNBOUND,
+            )));
+        }
+
+        // NOTE: Sessions belong to origins, which nested requests lack
+        Request::Handshake(args) => {
+            return Ok(handler::identity::handshake(
+                state, origin, signer, args,
+            )
+            .await
+            .map(Reply::Handshake)
+            .unwrap_or_else(Reply::from));
+        }
+        _ => {}
+    }
+
+    // Give any script the chance to rewrite or block the request before it
+    // is checked and executed
+    #[cfg(feature = "script")]
+    let script = state.script.clone();
+    #[cfg(feature = "script")]
+    let request = match script.as_ref() {
+        Some(script) => match script.on_request(request, &header) {
+            Ok(request) => request,
+            Err(x) => return Ok(Reply::Error(ReplyError::from(x))),
+        },
+        None => request,
+    };
+
+    // Reject requests that would fan out into too many operations before
+    // executing any of them
+    let max_depth = state.max_request_depth();
+    let max_operations = state.max_nested_operations();
+    let operations = request.nested_operation_count();
+    if operations > max_operations {
+        let msg = format!(
+            "Request contains {} nested operations, exceeding maximum of {}",
+            operations, max_operations
+        );
+        return Ok(Reply::Error(ReplyError::with_code(
+            msg,
+            ErrorCode::LIMIT_EXCEEDED,
+        )));
+    }
+
+    // Every operation nested within the request belongs to its trace and is
+    // made on behalf of the same caller
+    let header = Arc::new(header);
+    let caller = Arc::new(Caller {
+        identity: account.identity.clone(),
+        origin: Some(origin),
+    });
+
+    // Streaming is only supported for top-level requests, as nested requests
+    // are collected into a single reply
+    let reply = match request {
+        Request::ListDirContents(args) if args.stream => {
+            // NOTE: Streamed listings are not routed, so are checked here
+            let request = Request::ListDirContents(args.clone());
+            match check_request(&state, &request).await {
+                Ok(()) => handler::fs::list_dir_contents_stream(
+                    state,
+                    &args,
+                    handler::fs::DEFAULT_DIR_STREAM_CHUNK_SIZE,
+                    partial_tx,
+                )
+                .await
+                .map(Reply::DirContentsListChunk)
+                .unwrap_or_else(Reply::from),
+                Err(reply) => reply,
+            }
+        }
+        Request::Batch(args) if args.stream_results && max_depth > 0 => {
+            execute_batch_streaming(
+                state,
+                args.operations,
+                max_depth - 1,
+                Arc::clone(&header),
+                caller,
+                partial_tx,
+            )
+            .await
+        }
+        request => {
+            route_and_execute(
+                state,
+                request,
+                max_depth,
+                Arc::clone(&header),
+                caller,
+            )
+            .await
+        }
+    };
+
+    // NOTE: Partial replies are sent as they are produced, so only the final
+    //       reply passes through the script
+    #[cfg(feature = "script")]
+    let reply = match script.as_ref() {
+        Some(script) if !matches!(reply, Reply::Ignore) => {
+            script.on_reply(reply, &header)
+        }
+        _ => reply,
+    };
+
+    Ok(reply)
+}
+
+/// Checks that `request` is allowed by the permissions of the server and
+/// within bounds, yielding the reply to send instead of executing it if not
+async fn check_request(
+    state: &ServerState,
+    request: &Request,
+) -> Result<(), Reply> {
+    state
+        .permissions
+        .check(request)
+        .await
+        .map_err(Reply::from)?;
+    request.validate().map_err(Reply::from)
+}
+
+/// Who a request and every operation nested within it is made on behalf of
+#[derive(Clone, Debug, Default)]
+struct Caller {
+    /// Identity that any change to the filesystem is recorded for
+    identity: String,
+
+    /// Origin of the msg containing the request, told of the exit of any
+    /// proc started by the request
+    origin: Option<SocketAddr>,
+}
+
+/// Executes a batch of operations in parallel, sending the result of each
+/// as a partial reply once it completes and yielding the final result
+///
+/// An empty batch has no results to stream, so it yields an empty batch
+async fn execute_batch_streaming(
+    state: Arc<ServerState>,
+    operations: Vec<Request>,
+    max_depth: u8,
+    header: Arc<Header>,
+    caller: Arc<Caller>,
+    mut partial_tx: mpsc::Sender<Reply>,
+) -> Reply {
+    let mut remaining = operations.len();
+    let mut pending: FuturesUnordered<_> = operations
+        .into_iter()
+        .enumerate()
+        .map(|(index, req)| {
+            state
+                .tasks
+                .spawn(
+                    &Handle::current(),
+                    route_and_execute(
+                        Arc::clone(&state),
+                        req,
+                        max_depth,
+                        Arc::clone(&header),
+                        Arc::clone(&caller),
+                    ),
+                )
+                .map(move |r| {
+                    let result = r.unwrap_or_else(|x| {
+                        Reply::Error(From::from(format!("{}", x)))
+                    });
+                    (index, result)
+                })
+        })
+        .collect();
+
+    let mut final_reply = Reply::Batch(reply::BatchArgs::default());
+    while let Some((index, result)) = pending.next().await {
+        remaining -= 1;
+        let reply = Reply::BatchResult(reply::BatchResultArgs {
+            index,
+            result: Box::new(result),
+            last: remaining == 0,
+        });
+
+        if remaining == 0 {
+            final_reply = reply;
+        } else if partial_tx.send(reply).await.is_err() {
+            return Reply::Error(ReplyError::from(
+                "Failed to send partial reply",
+            ));
+        }
+    }
+
+    final_reply
 }
 
-/// Determines the appropriate handler for a request and executes it
+/// Determines the appropriate handler for a request and executes it, where
+/// `header` is that of the msg containing the top-level request and
+/// `caller` is who the request is made on behalf of
 ///
 /// Returns a boxed future as requests like Sequence and Batch will
 /// recursively call this function
@@ -191,12 +898,41 @@ fn route_and_execute(
     state: Arc<ServerState>,
     request: Request,
     max_depth: u8,
+    header: Arc<Header>,
+    caller: Arc<Caller>,
 ) -> BoxFuture<'static, Reply> {
     async move {
+        if let Some(trace_id) = header.trace_id.as_ref() {
+            trace!("Executing request in trace {}: {:?}", trace_id, request);
+        }
+
         if max_depth == 0 {
-            Reply::Error(ReplyError::from("Reached maximum nested depth"))
+            Reply::Error(ReplyError::with_code(
+                "Reached maximum nested depth",
+                ErrorCode::LIMIT_EXCEEDED,
+            ))
         } else {
-            match request {
+            #[cfg(feature = "fault-injection")]
+            {
+                if let Some(reply) =
+                    handler::fault::apply_fault(&state, &request).await
+                {
+                    return reply;
+                }
+            }
+
+            // NOTE: Checked here rather than before routing so that requests
+            //       nested within composite requests are checked too
+            if let Err(reply) = check_request(&state, &request).await {
+                return reply;
+            }
+
+            // Whether a file exists decides how a change to it is recorded,
+            // so the change is determined before executing the request
+            let fs_change = handler::fs::fs_change_of(&state, &request).await;
+            let fs_state = Arc::clone(&state);
+
+            let reply = match request {
                 Request::Heartbeat => {
                     handler::heartbeat::heartbeat().await;
                     Reply::Heartbeat
@@ -205,8 +941,69 @@ fn route_and_execute(
                     Reply::Version(handler::version::version().await)
                 }
                 Request::Capabilities => Reply::Capabilities(
-                    handler::capabilities::capabilities().await,
+                    handler::capabilities::capabilities(state).await,
                 ),
+                Request::TimeInfo(args) => {
+                    Reply::TimeInfo(handler::time::time_info(&args).await)
+                }
+                Request::Identify(args) => {
+                    handler::identity::identify(state, &args)
+                        .await
+                        .map(Reply::Identity)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::UpdateTrustedClients(args) => {
+                    handler::identity::update_trusted_clients(state, &args)
+                        .await
+                        .map(Reply::TrustedClients)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::PushConfig(args) => {
+                    handler::config::push_config(state, &args)
+                        .await
+                        .map(Reply::ConfigPushed)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::GetConfig => handler::config::get_config(state)
+                    .await
+                    .map(Reply::Config)
+                    .unwrap_or_else(Reply::from),
+                Request::UpdateWebhooks(args) => {
+                    handler::config::update_webhooks(state, &args)
+                        .await
+                        .map(Reply::Webhooks)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::PowerControl(args) => {
+                    handler::power::power_control(
+                        state,
+                        &caller.identity,
+                        &args,
+                    )
+                    .await
+                    .map(Reply::PowerControl)
+                    .unwrap_or_else(Reply::from)
+                }
+                Request::ExportState(args) => {
+                    handler::backup::export_state(
+                        state,
+                        &caller.identity,
+                        &args,
+                    )
+                    .await
+                    .map(Reply::StateExported)
+                    .unwrap_or_else(Reply::from)
+                }
+                Request::ImportState(args) => {
+                    handler::backup::import_state(
+                        state,
+                        &caller.identity,
+                        &args,
+                    )
+                    .await
+                    .map(Reply::StateImported)
+                    .unwrap_or_else(Reply::from)
+                }
                 Request::OpenFile(args) => handler::fs::open_file(state, &args)
                     .await
                     .map(Reply::FileOpened)
@@ -245,12 +1042,42 @@ fn route_and_execute(
                     .await
                     .map(Reply::FileContents)
                     .unwrap_or_else(Reply::from),
+                Request::ReadFiles(args) => {
+                    handler::fs::read_files(state, &args)
+                        .await
+                        .map(Reply::FilesContents)
+                        .unwrap_or_else(Reply::from)
+                }
                 Request::WriteFile(args) => {
                     handler::fs::write_file(state, &args)
                         .await
                         .map(Reply::FileWritten)
                         .unwrap_or_else(Reply::from)
                 }
+                Request::WriteFileAtomicByPath(args) => {
+                    handler::fs::write_file_atomic_by_path(state, &args)
+                        .await
+                        .map(Reply::AtomicFileWritten)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::PatchFileLines(args) => {
+                    handler::fs::patch_file_lines(state, &args)
+                        .await
+                        .map(Reply::FileLinesPatched)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::UploadManifest(args) => {
+                    handler::fs::upload_manifest(state, &args)
+                        .await
+                        .map(Reply::UploadManifestPrepared)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::WriteUploadChunk(args) => {
+                    handler::fs::write_upload_chunk(state, &args)
+                        .await
+                        .map(Reply::UploadChunkWritten)
+                        .unwrap_or_else(Reply::from)
+                }
                 Request::CreateDir(args) => {
                     handler::fs::create_dir(state, &args)
                         .await
@@ -269,16 +1096,106 @@ fn route_and_execute(
                         .map(Reply::DirRemoved)
                         .unwrap_or_else(Reply::from)
                 }
+                Request::CreatePaths(args) => {
+                    handler::fs::create_paths(state, &args)
+                        .await
+                        .map(Reply::PathsCreated)
+                        .unwrap_or_else(Reply::from)
+                }
                 Request::ListDirContents(args) => {
                     handler::fs::list_dir_contents(state, &args)
                         .await
                         .map(Reply::DirContentsList)
                         .unwrap_or_else(Reply::from)
                 }
+                Request::ResolvePath(args) => {
+                    handler::fs::resolve_path(state, &args)
+                        .await
+                        .map(Reply::PathResolved)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::SniffFile(args) => {
+                    handler::fs::sniff_file(state, &args)
+                        .await
+                        .map(Reply::FileSniffed)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::GetFileChecksum(args) => {
+                    handler::fs::get_file_checksum(state, &args)
+                        .await
+                        .map(Reply::FileChecksum)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::DiffFiles(args) => {
+                    handler::fs::diff_files(state, &args)
+                        .await
+                        .map(Reply::FilesDiffed)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::RecentFsEvents(args) => Reply::FsEvents(
+                    handler::fs::recent_fs_events(state, &args).await,
+                ),
+                Request::ListMounts(args) => {
+                    handler::fs::list_mounts(state, &args)
+                        .await
+                        .map(Reply::MountsList)
+                        .unwrap_or_else(Reply::from)
+                }
                 Request::ExecProc(args) => {
-                    handler::proc::exec_proc(state, &args)
+                    match handler::proc::exec_proc(Arc::clone(&state), &args)
+                        .await
+                    {
+                        Ok(started) => {
+                            handler::proc::tell_origin_on_exit(
+                                state,
+                                started.id,
+                                caller.origin,
+                            )
+                            .await;
+                            Reply::ProcStarted(started)
+                        }
+                        Err(x) => Reply::from(x),
+                    }
+                }
+                Request::ExecScript(args) => {
+                    match handler::proc::exec_script(Arc::clone(&state), &args)
+                        .await
+                    {
+                        Ok(started) => {
+                            handler::proc::tell_origin_on_exit(
+                                state,
+                                started.id,
+                                caller.origin,
+                            )
+                            .await;
+                            Reply::ProcStarted(started)
+                        }
+                        Err(x) => Reply::from(x),
+                    }
+                }
+                Request::ExecProcPty(args) => {
+                    match handler::proc::exec_proc_pty(
+                        Arc::clone(&state),
+                        &args,
+                    )
+                    .await
+                    {
+                        Ok(started) => {
+                            handler::proc::tell_origin_on_exit(
+                                state,
+                                started.id,
+                                caller.origin,
+                            )
+                            .await;
+                            Reply::ProcStarted(started)
+                        }
+                        Err(x) => Reply::from(x),
+                    }
+                }
+                Request::ResizePty(args) => {
+                    handler::proc::resize_pty(state, &args)
                         .await
-                        .map(Reply::ProcStarted)
+                        .map(Reply::PtyResized)
                         .unwrap_or_else(Reply::from)
                 }
                 Request::WriteProcStdin(args) => {
@@ -305,15 +1222,122 @@ fn route_and_execute(
                         .map(Reply::ProcStatus)
                         .unwrap_or_else(Reply::from)
                 }
+                Request::ReadProcTree(args) => {
+                    handler::proc::read_proc_tree(state, &args)
+                        .await
+                        .map(Reply::ProcTree)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::ReadProcResources(args) => {
+                    handler::proc::read_proc_resources(state, &args)
+                        .await
+                        .map(Reply::ProcResources)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::ReadProcInfo(args) => {
+                    handler::proc::read_proc_info(state, &args)
+                        .await
+                        .map(Reply::ProcInfo)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::ListProcs(args) => Reply::ProcsList(
+                    handler::proc::list_procs(state, &args).await,
+                ),
                 Request::KillProc(args) => {
                     handler::proc::kill_proc(state, &args)
                         .await
                         .map(Reply::ProcKilled)
                         .unwrap_or_else(Reply::from)
                 }
-                Request::InternalDebug(args) => Reply::InternalDebug(
-                    handler::internal_debug::internal_debug(state, &args).await,
+                Request::SubmitJob(args) => {
+                    handler::job::submit_job(state, &args)
+                        .await
+                        .map(Reply::JobSubmitted)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::QueryJob(args) => {
+                    handler::job::query_job(state, &args)
+                        .await
+                        .map(Reply::JobStatus)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::CollectJobOutput(args) => {
+                    handler::job::collect_job_output(state, &args)
+                        .await
+                        .map(Reply::JobOutput)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::CreateSchedule(args) => {
+                    handler::job::create_schedule(state, &args)
+                        .await
+                        .map(Reply::ScheduleCreated)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::ListSchedules => handler::job::list_schedules(state)
+                    .await
+                    .map(Reply::SchedulesList)
+                    .unwrap_or_else(Reply::from),
+                Request::DeleteSchedule(args) => {
+                    handler::job::delete_schedule(state, &args)
+                        .await
+                        .map(Reply::ScheduleDeleted)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::AppendLog(args) => {
+                    handler::logs::append_log(state, &args)
+                        .await
+                        .map(Reply::LogAppended)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::ReadLogRange(args) => {
+                    handler::logs::read_log_range(state, &args)
+                        .await
+                        .map(Reply::LogRange)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::Diagnostics(args) => Reply::Diagnostics(
+                    handler::diagnostics::diagnostics(state, &args).await,
+                ),
+                Request::ListConnections(args) => Reply::ConnectionsList(
+                    handler::connection::list_connections(state, &args).await,
+                ),
+                Request::ListPendingEvictions(args) => {
+                    handler::eviction::list_pending_evictions(state, &args)
+                        .await
+                        .map(Reply::PendingEvictions)
+                        .unwrap_or_else(Reply::from)
+                }
+                Request::BindOrigin(_) => Reply::Error(ReplyError::from(
+                    "Origin can only be bound by a top-level request",
+                )),
+                Request::Handshake(_) => Reply::Error(ReplyError::from(
+                    "Handshake can only be made by a top-level request",
+                )),
+                Request::Sealed(_) => Reply::Error(ReplyError::from(
+                    "Sealed request can only be sent at the top level",
+                )),
+                #[cfg(feature = "fault-injection")]
+                Request::InjectFault(args) => Reply::FaultInjected(
+                    handler::fault::inject_fault(state, &args).await,
                 ),
+                #[cfg(feature = "fault-injection")]
+                Request::ClearFaults => Reply::FaultsCleared(
+                    handler::fault::clear_faults(state).await,
+                ),
+                #[cfg(feature = "wasm")]
+                Request::LoadWasmHandler(args) => {
+                    handler::wasm::load_wasm_handler(state, &args)
+                        .await
+                        .map(Reply::WasmHandlerLoaded)
+                        .unwrap_or_else(Reply::from)
+                }
+                #[cfg(feature = "wasm")]
+                Request::UnloadWasmHandler(args) => {
+                    handler::wasm::unload_wasm_handler(state, &args)
+                        .await
+                        .map(Reply::WasmHandlerUnloaded)
+                        .unwrap_or_else(Reply::from)
+                }
                 Request::Sequence(mut args) => {
                     let mut results: Vec<Reply> = vec![];
                     for op in args.operations.drain(..) {
@@ -324,6 +1348,8 @@ fn route_and_execute(
                                         Arc::clone(&state),
                                         req,
                                         max_depth - 1,
+                                        Arc::clone(&header),
+                                        Arc::clone(&caller),
                                     )
                                     .await
                                 }
@@ -341,11 +1367,16 @@ fn route_and_execute(
 
                     let results: Vec<Reply> =
                         join_all(args.operations.drain(..).map(|req| {
-                            Handle::current().spawn(route_and_execute(
-                                Arc::clone(&state),
-                                req,
-                                max_depth - 1,
-                            ))
+                            state.tasks.spawn(
+                                &Handle::current(),
+                                route_and_execute(
+                                    Arc::clone(&state),
+                                    req,
+                                    max_depth - 1,
+                                    Arc::clone(&header),
+                                    Arc::clone(&caller),
+                                ),
+                            )
                         }))
                         .await
                         .drain(..)
@@ -357,21 +1388,69 @@ fn route_and_execute(
                         .collect();
                     Reply::Batch(reply::BatchArgs { results })
                 }
+                Request::Transaction(args) => {
+                    handler::transaction::transaction(
+                        Arc::clone(&state),
+                        args,
+                        |req| {
+                            route_and_execute(
+                                Arc::clone(&state),
+                                req,
+                                max_depth - 1,
+                                Arc::clone(&header),
+                                Arc::clone(&caller),
+                            )
+                        },
+                    )
+                    .await
+                    .map(Reply::Transaction)
+                    .unwrap_or_else(Reply::from)
+                }
 
                 // TODO: Move to handler function that can be tested
                 //       and have logging
-                Request::Custom(args) => match &state.custom_handler.as_ref() {
-                    Some(ch) => ch
-                        .invoke(args)
-                        .await
-                        .map(Reply::Custom)
-                        .unwrap_or_else(Reply::from),
-                    None => Reply::Ignore,
-                },
+                Request::Custom(args) => {
+                    #[cfg(feature = "wasm")]
+                    {
+                        if let Some(reply) =
+                            handler::wasm::custom(&state, &args).await
+                        {
+                            return reply;
+                        }
+                    }
 
-                // TODO: Implement forwarding support
-                Request::Forward(_) => Reply::Ignore,
+                    match &state.custom_handler.as_ref() {
+                        Some(ch) => ch
+                            .invoke(args)
+                            .await
+                            .map(Reply::Custom)
+                            .unwrap_or_else(Reply::from),
+                        None => Reply::Ignore,
+                    }
+                }
+
+                Request::Unsupported(args) => {
+                    Reply::Unsupported(reply::UnsupportedArgs {
+                        type_name: args.type_name,
+                        description: args.description,
+                    })
+                }
+
+                // NOTE: The forwarded msg is signed by this server rather
+                //       than keeping the signature of the original msg, as
+                //       only the request within it is forwarded
+                Request::Forward(args) => {
+                    state.relay.forward(args.address, args.msg).await
+                }
+            };
+
+            if let Some(change) = fs_change {
+                if !matches!(reply, Reply::Error(_)) {
+                    fs_state.fs_events.record(change, &caller.identity).await;
+                }
             }
+
+            reply
         }
     }
     .boxed()
@@ -401,19 +1480,29 @@ async fn update_origin_last_touched(
     state: Arc<ServerState>,
     origin: SocketAddr,
 ) -> Option<Instant> {
-    match state.conns.lock().await.entry(origin) {
-        Entry::Occupied(mut e) => Some(e.insert(Instant::now())),
-        Entry::Vacant(e) => {
-            e.insert(Instant::now());
-            None
-        }
-    }
+    let mut conns = state.conns.lock().await;
+    let last_touched = conns.get(&origin).map(|x| *x.last_touched());
+    conns.insert(origin, ());
+    state.sessions.lock().await.touch(&origin);
+    state.origins.touch(origin).await;
+    last_touched
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::request;
+    use crate::core::{
+        msg::content::RemotePath,
+        request,
+        server::{
+            origins::OriginBindings, permissions::Permissions,
+            transfers::TransferAccounting,
+        },
+        transport::{
+            auth::identity::IdentityKey,
+            crypto::handshake::{Handshake, Role},
+        },
+    };
     use std::sync::mpsc;
 
     #[tokio::test]
@@ -444,6 +1533,8 @@ mod tests {
                     .into_lazily_transformed(vec![]),
             ])),
             2,
+            Default::default(),
+            Default::default(),
         )
         .await;
 
@@ -498,6 +1589,8 @@ mod tests {
                     .into_lazily_transformed(vec![]),
             ])),
             2,
+            Default::default(),
+            Default::default(),
         )
         .await;
 
@@ -520,6 +1613,62 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn route_and_execute_should_check_permissions_of_nested_requests() {
+        let mut state = ServerState::default();
+        state.set_permissions(Permissions {
+            allow_exec: false,
+            ..Default::default()
+        });
+
+        let reply = route_and_execute(
+            Arc::new(state),
+            Request::Sequence(From::from(vec![
+                Request::Heartbeat.into_lazily_transformed(vec![]),
+                Request::ExecProc(Default::default())
+                    .into_lazily_transformed(vec![]),
+            ])),
+            2,
+            Default::default(),
+            Default::default(),
+        )
+        .await;
+
+        match reply {
+            Reply::Sequence(args) => {
+                assert_eq!(args.results[0], Reply::Heartbeat);
+                match &args.results[1] {
+                    Reply::Error(x) => {
+                        assert_eq!(x.code(), ErrorCode::PERMISSION_DENIED)
+                    }
+                    x => panic!("Unexpected reply in sequence[1]: {:?}", x),
+                }
+            }
+            x => panic!("Unexpected reply: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn route_and_execute_should_reject_requests_out_of_bounds() {
+        let reply = route_and_execute(
+            Arc::new(ServerState::default()),
+            Request::ExecProc(request::ExecProcArgs {
+                command: String::from("true"),
+                umask: Some(0o1000),
+                ..Default::default()
+            }),
+            2,
+            Default::default(),
+            Default::default(),
+        )
+        .await;
+
+        match reply {
+            Reply::Error(x) => assert_eq!(x.code(), ErrorCode::INVALID_INPUT),
+            x => panic!("Unexpected reply: {:?}", x),
+        }
+    }
+
     // TODO: Batch operations may run concurrently, but the delay_for tactic
     //       appears to not let other tasks start, even when using
     //       Handle.spawn(...); so, we aren't able to validate that batching
@@ -539,6 +1688,8 @@ mod tests {
                 Request::Custom(From::from(Vec::<u8>::new())),
             ])),
             2,
+            Default::default(),
+            Default::default(),
         )
         .await;
 
@@ -593,6 +1744,8 @@ mod tests {
                 Request::Custom(From::from(Vec::<u8>::new())),
             ])),
             2,
+            Default::default(),
+            Default::default(),
         )
         .await;
 
@@ -615,6 +1768,485 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn route_and_execute_with_batch_should_serialize_writes_to_same_file()
+    {
+        let state = Arc::new(ServerState::default());
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let handle = state
+            .fs_manager
+            .lock()
+            .await
+            .open_file(file.path(), false, true, true)
+            .await
+            .unwrap();
+
+        // Every write uses the original signature, so only the first to run
+        // can succeed, and it must have finished before any other runs
+        let requests = (0..20u8)
+            .map(|i| {
+                Request::WriteFile(request::WriteFileArgs {
+                    id: handle.id,
+                    sig: handle.sig,
+                    contents: vec![i; 64 * 1024],
+                    preconditions: None,
+                })
+            })
+            .collect::<Vec<Request>>();
+
+        let reply = route_and_execute(
+            Arc::clone(&state),
+            Request::Batch(From::from(requests)),
+            2,
+            Default::default(),
+            Default::default(),
+        )
+        .await;
+
+        let results = match reply {
+            Reply::Batch(args) => args.results,
+            x => panic!("Unexpected reply: {:?}", x),
+        };
+        let written = results
+            .iter()
+            .filter(|r| matches!(r, Reply::FileWritten(_)))
+            .count();
+        assert_eq!(written, 1, "Unexpected results: {:?}", results);
+
+        let contents = tokio::fs::read(file.path()).await.unwrap();
+        assert_eq!(contents.len(), 64 * 1024);
+        assert!(
+            contents.iter().all(|b| *b == contents[0]),
+            "Writes were interleaved"
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_stream_batch_results_as_completed(
+    ) {
+        let mut state = ServerState::default();
+
+        // Set custom handler to delay so that it completes after others
+        state.set_custom_handler(From::from(
+            move |req: request::CustomArgs| async move {
+                tokio::time::delay_for(std::time::Duration::from_millis(50))
+                    .await;
+                Ok(reply::CustomArgs { data: req.data })
+            },
+        ));
+
+        let (partial_tx, mut partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::new(state),
+            Msg::from(Request::Batch(request::BatchArgs {
+                operations: vec![
+                    Request::Custom(From::from(vec![1, 2, 3])),
+                    Request::Heartbeat,
+                ],
+                stream_results: true,
+            })),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            partial_rx.recv().await,
+            Some(Reply::BatchResult(reply::BatchResultArgs {
+                index: 1,
+                result: Box::new(Reply::Heartbeat),
+                last: false,
+            }))
+        );
+        assert_eq!(partial_rx.recv().await, None);
+        assert_eq!(
+            reply,
+            Reply::BatchResult(reply::BatchResultArgs {
+                index: 0,
+                result: Box::new(Reply::Custom(From::from(vec![1, 2, 3]))),
+                last: true,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_not_stream_empty_batch() {
+        let (partial_tx, mut partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::new(ServerState::default()),
+            Msg::from(Request::Batch(request::BatchArgs {
+                operations: vec![],
+                stream_results: true,
+            })),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(partial_rx.recv().await, None);
+        assert_eq!(reply, Reply::Batch(reply::BatchArgs::default()));
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_check_permissions_of_streamed_listing(
+    ) {
+        let allowed = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        std::fs::write(other.path().join("secret"), b"").unwrap();
+
+        let no_read = Permissions {
+            allow_fs_read: false,
+            ..Default::default()
+        };
+        let confined = Permissions {
+            allowed_paths: vec![allowed.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        for permissions in [no_read, confined].iter().cloned() {
+            let mut state = ServerState::default();
+            state.set_permissions(permissions);
+
+            let (partial_tx, mut partial_rx) = tokio::sync::mpsc::channel(10);
+            let reply = validate_route_and_execute(
+                Arc::new(state),
+                Msg::from(Request::ListDirContents(
+                    request::ListDirContentsArgs {
+                        path: RemotePath::from_path(other.path()),
+                        stream: true,
+                    },
+                )),
+                &test_account(),
+                "127.0.0.1:60123".parse().unwrap(),
+                partial_tx,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(partial_rx.recv().await, None);
+            match reply {
+                Reply::Error(x) => {
+                    assert_eq!(x.code(), ErrorCode::PERMISSION_DENIED)
+                }
+                x => panic!("Unexpected reply: {:?}", x),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_reject_requests_over_budget() {
+        let mut state = ServerState::default();
+        state.set_request_limits(5, 3);
+        let state = Arc::new(state);
+
+        // Nested operations are counted across all depths
+        let request = Request::Sequence(From::from(vec![
+            Request::Heartbeat.into_lazily_transformed(vec![]),
+            Request::Batch(From::from(vec![
+                Request::Heartbeat,
+                Request::Heartbeat,
+            ]))
+            .into_lazily_transformed(vec![]),
+        ]));
+        assert_eq!(request.nested_operation_count(), 4);
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::clone(&state),
+            Msg::from(request),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            reply,
+            Reply::Error(ReplyError::with_code(
+                "Request contains 4 nested operations, exceeding maximum of 3",
+                ErrorCode::LIMIT_EXCEEDED,
+            ))
+        );
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            state,
+            Msg::from(Request::Batch(From::from(vec![
+                Request::Heartbeat,
+                Request::Heartbeat,
+                Request::Heartbeat,
+            ]))),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Reply::Batch(From::from(vec![Reply::Heartbeat; 3])));
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_reject_msgs_over_quota() {
+        let msg = Msg::from(Request::Heartbeat);
+        let len = msg.encoded_len().unwrap() as u64;
+
+        let mut state = ServerState::default();
+        state.set_transfers(TransferAccounting::new(Some(len)));
+        let state = Arc::new(state);
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::clone(&state),
+            msg.clone(),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Reply::Heartbeat);
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::clone(&state),
+            msg,
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            reply,
+            Reply::Error(ReplyError::QuotaExceeded(reply::QuotaExceededArgs {
+                identity: String::from("127.0.0.1"),
+                used: len,
+                quota: len,
+            }))
+        );
+        assert_eq!(state.transfers.by_class().await["meta"].bytes_in, len);
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_require_bound_origin_if_strict()
+    {
+        let mut state = ServerState::default();
+        state.set_origins(OriginBindings::new(true));
+        let state = Arc::new(state);
+
+        let execute = |request: Request| {
+            let state = Arc::clone(&state);
+            async move {
+                let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+                validate_route_and_execute(
+                    state,
+                    Msg::from(request),
+                    &test_account(),
+                    "127.0.0.1:60123".parse().unwrap(),
+                    partial_tx,
+                )
+                .await
+                .unwrap()
+            }
+        };
+        let custom = || Request::Custom(From::from(vec![1, 2, 3]));
+
+        // Requests that change nothing are still accepted
+        assert_eq!(execute(Request::Heartbeat).await, Reply::Heartbeat);
+        match execute(custom()).await {
+            Reply::Error(x) => assert_eq!(x.code(), ErrorCode::ORIGIN_UNBOUND),
+            x => panic!("Unexpected reply: {:?}", x),
+        }
+
+        let nonce = match execute(Request::BindOrigin(Default::default())).await
+        {
+            Reply::OriginBinding(args) if !args.bound => args.nonce,
+            x => panic!("Unexpected reply: {:?}", x),
+        };
+
+        // An echo that is not signed only yields a new challenge
+        let nonce = match execute(Request::BindOrigin(request::BindOriginArgs {
+            nonce,
+            ..Default::default()
+        }))
+        .await
+        {
+            Reply::OriginBinding(args) if !args.bound => args.nonce,
+            x => panic!("Unexpected reply: {:?}", x),
+        };
+
+        let key = IdentityKey::generate();
+        assert_eq!(
+            execute(Request::BindOrigin(request::BindOriginArgs {
+                signature: key.sign(&request::signed_nonce(&nonce)),
+                identity_key: key.public_key().to_vec(),
+                nonce,
+                ..Default::default()
+            }))
+            .await,
+            Reply::OriginBinding(reply::OriginBindingArgs {
+                bound: true,
+                nonce: Vec::new(),
+            })
+        );
+
+        if let Reply::Error(x) = execute(custom()).await {
+            assert_ne!(x.code(), ErrorCode::ORIGIN_UNBOUND, "Origin rejected");
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_use_configured_max_depth() {
+        let mut state = ServerState::default();
+        state.set_request_limits(2, 10);
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::new(state),
+            Msg::from(Request::Batch(From::from(vec![
+                Request::Heartbeat,
+                Request::Batch(From::from(vec![Request::Heartbeat])),
+            ]))),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+
+        // Operations of the nested batch are beyond the configured depth
+        match reply {
+            Reply::Batch(args) => {
+                assert_eq!(args.results[0], Reply::Heartbeat);
+                match &args.results[1] {
+                    Reply::Batch(args) => match &args.results[0] {
+                        Reply::Error(_) => (),
+                        x => panic!("Unexpected nested reply: {:?}", x),
+                    },
+                    x => panic!("Unexpected reply in batch[1]: {:?}", x),
+                }
+            }
+            x => panic!("Unexpected reply: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_route_and_execute_should_record_successful_fs_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file").to_string_lossy().to_string();
+        let state = Arc::new(ServerState::default());
+
+        let write = || {
+            Msg::from(Request::Sequence(From::from(vec![
+                Request::WriteFileAtomicByPath(
+                    request::WriteFileAtomicByPathArgs {
+                        path: path.clone().into(),
+                        data: b"data".to_vec(),
+                        ..Default::default()
+                    },
+                )
+                .into_lazily_transformed(vec![]),
+            ])))
+        };
+
+        for _ in 0..2 {
+            let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+            validate_route_and_execute(
+                Arc::clone(&state),
+                write(),
+                &test_account(),
+                "127.0.0.1:60123".parse().unwrap(),
+                partial_tx,
+            )
+            .await
+            .unwrap();
+        }
+
+        // Failed requests change nothing, so they are not recorded
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::clone(&state),
+            Msg::from(Request::RemoveDir(request::RemoveDirArgs {
+                path: dir.path().join("missing").into(),
+                non_empty: false,
+            })),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(reply, Reply::Error(_)), "Unexpected reply");
+
+        let events = state.fs_events.since(0).await;
+        let ops: Vec<reply::FsEventOp> =
+            events.events.iter().map(|e| e.op).collect();
+        assert_eq!(
+            ops,
+            vec![reply::FsEventOp::Created, reply::FsEventOp::Modified]
+        );
+        assert_eq!(events.events[0].path, path);
+        assert_eq!(events.events[0].identity, "127.0.0.1");
+    }
+
+    #[cfg(feature = "script")]
+    #[tokio::test]
+    async fn validate_route_and_execute_should_apply_script_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hooks.rhai");
+        std::fs::write(
+            &path,
+            r#"
+            fn on_request(request) {
+                if request.type == "version_request" {
+                    throw "version is hidden";
+                }
+            }
+
+            fn on_reply(reply) {
+                if reply.type == "heartbeat_reply" {
+                    audit("heartbeat");
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let mut state = ServerState::default();
+        state.set_script(Some(
+            crate::core::server::script::ScriptHooks::open(path).unwrap(),
+        ));
+        let state = Arc::new(state);
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            Arc::clone(&state),
+            Msg::from(Request::Version),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Reply::Error(ReplyError::from("version is hidden")));
+
+        let (partial_tx, _partial_rx) = tokio::sync::mpsc::channel(10);
+        let reply = validate_route_and_execute(
+            state,
+            Msg::from(Request::Heartbeat),
+            &test_account(),
+            "127.0.0.1:60123".parse().unwrap(),
+            partial_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply, Reply::Heartbeat);
+    }
+
     #[tokio::test]
     async fn update_origin_last_touched_should_create_a_new_entry_if_missing() {
         let state = Arc::new(ServerState::default());
@@ -629,7 +2261,8 @@ mod tests {
             .lock()
             .await
             .get(&origin)
-            .expect("No entry was made");
+            .expect("No entry was made")
+            .last_touched();
         assert!(new_touched >= now, "Inserted time was in the past");
     }
 
@@ -638,8 +2271,7 @@ mod tests {
         let state = Arc::new(ServerState::default());
         let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
 
-        let now = Instant::now();
-        state.conns.lock().await.insert(origin, now);
+        state.conns.lock().await.insert(origin, ());
 
         let state_2 = Arc::clone(&state);
         let old_touched = update_origin_last_touched(state_2, origin).await;
@@ -649,13 +2281,296 @@ mod tests {
             .lock()
             .await
             .get(&origin)
-            .expect("No entry was made");
+            .expect("No entry was made")
+            .last_touched();
         assert!(
             new_touched >= old_touched.expect("Old entry was not returned"),
             "Inserted time was in the past"
         );
     }
 
+    #[test]
+    fn encode_reply_should_compress_only_if_origin_accepts_it() {
+        use crate::core::msg::compression;
+
+        let reply = || Reply::Custom(reply::CustomArgs::from(vec![0; 4096]));
+        let mut header = Header::default();
+
+        let data = encode_reply(
+            reply(),
+            header.clone(),
+            Duration::default(),
+            usize::MAX,
+            None,
+        )
+        .unwrap();
+        assert!(!compression::is_compressed(&data));
+
+        header.flags.accepts_compression = true;
+        let data = encode_reply(
+            reply(),
+            header.clone(),
+            Duration::default(),
+            usize::MAX,
+            None,
+        )
+        .unwrap();
+        assert!(compression::is_compressed(&data));
+        match Msg::from_slice(&data).unwrap().content {
+            Content::Reply(Reply::Custom(args)) => {
+                assert_eq!(args.data, vec![0; 4096])
+            }
+            x => panic!("Unexpected content: {:?}", x),
+        }
+
+        header.flags.no_compression = true;
+        let data = encode_reply(
+            reply(),
+            header,
+            Duration::default(),
+            usize::MAX,
+            None,
+        )
+        .unwrap();
+        assert!(!compression::is_compressed(&data));
+    }
+
+    #[test]
+    fn encode_reply_should_echo_processing_time() {
+        let data = encode_reply(
+            Reply::Heartbeat,
+            Header::default(),
+            Duration::from_millis(3),
+            usize::MAX,
+            None,
+        )
+        .unwrap();
+        let msg = Msg::from_slice(&data).unwrap();
+        assert_eq!(msg.header.processing_micros, Some(3000));
+    }
+
+    #[tokio::test]
+    async fn encode_reply_within_limit_should_substitute_error_if_too_large() {
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+        let mut state = ServerState::default();
+        state.set_origins(
+            OriginBindings::new(false).with_max_amplification(Some(1)),
+        );
+        assert!(state.origins.record_request(origin, 1024).await);
+
+        let reply = || Reply::Custom(reply::CustomArgs::from(vec![1; 4096]));
+        let data = encode_reply_within_limit(
+            &state,
+            origin,
+            reply(),
+            Header::default(),
+            Duration::default(),
+            usize::MAX,
+            None,
+        )
+        .await
+        .unwrap()
+        .expect("Error not sent");
+        match Msg::from_slice(&data).unwrap().content {
+            Content::Reply(Reply::Error(x)) => {
+                assert_eq!(x.code(), ErrorCode::ORIGIN_UNBOUND)
+            }
+            x => panic!("Unexpected content: {:?}", x),
+        }
+
+        // An origin whose budget cannot cover even the error is sent nothing
+        let other: SocketAddr = "127.0.0.1:60124".parse().unwrap();
+        assert!(state.origins.record_request(other, 1).await);
+        let data = encode_reply_within_limit(
+            &state,
+            other,
+            reply(),
+            Header::default(),
+            Duration::default(),
+            usize::MAX,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(data, None);
+    }
+
+    #[test]
+    fn encode_reply_should_seal_reply_with_session() {
+        let key = [7; 32];
+        let header = Header::default();
+        let data = encode_reply(
+            Reply::Heartbeat,
+            header.clone(),
+            Duration::default(),
+            usize::MAX,
+            Some(&Session::new(&key)),
+        )
+        .unwrap();
+
+        let args = match Msg::from_slice(&data).unwrap().content {
+            Content::Reply(Reply::Sealed(args)) => args,
+            x => panic!("Unexpected content: {:?}", x),
+        };
+        let data = Session::new(&key).open(&args.nonce, &args.data).unwrap();
+        let msg = Msg::from_slice(&data).unwrap();
+        assert_eq!(msg.parent_header.map(|x| x.id), Some(header.id));
+        match msg.content {
+            Content::Reply(Reply::Heartbeat) => {}
+            x => panic!("Unexpected content: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_should_require_origin_with_session_to_seal_msgs() {
+        let state = Arc::new(ServerState::default());
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+        let key = [7; 32];
+        state
+            .sessions
+            .lock()
+            .await
+            .insert(origin, Session::new(&key));
+        let client = Session::new(&key);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+
+        // A sealed request is opened and its reply sealed
+        let inner = Msg::from(Request::Heartbeat);
+        let (nonce, data) = client.seal(&inner.to_vec().unwrap()).unwrap();
+        let mut msg =
+            Msg::from(Request::Sealed(request::SealedArgs { nonce, data }));
+        msg.header.id = inner.header.id;
+        Executor::<Vec<u8>>::new(tx.clone(), origin, usize::MAX)
+            .execute(Arc::clone(&state), msg)
+            .await
+            .unwrap();
+
+        let args =
+            match Msg::from_slice(&rx.recv().await.unwrap()).unwrap().content {
+                Content::Reply(Reply::Sealed(args)) => args,
+                x => panic!("Unexpected content: {:?}", x),
+            };
+        let data = client.open(&args.nonce, &args.data).unwrap();
+        let msg = Msg::from_slice(&data).unwrap();
+        assert_eq!(msg.parent_header.map(|x| x.id), Some(inner.header.id));
+        match msg.content {
+            Content::Reply(Reply::Heartbeat) => {}
+            x => panic!("Unexpected content: {:?}", x),
+        }
+
+        // An unsealed request is refused
+        Executor::<Vec<u8>>::new(tx, origin, usize::MAX)
+            .execute(Arc::clone(&state), Msg::from(Request::Heartbeat))
+            .await
+            .unwrap();
+        match Msg::from_slice(&rx.recv().await.unwrap()).unwrap().content {
+            Content::Reply(Reply::Error(x)) => {
+                assert_eq!(x.code(), ErrorCode::SESSION_REQUIRED)
+            }
+            x => panic!("Unexpected content: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_should_only_replace_session_through_it_or_its_identity() {
+        let state = Arc::new(ServerState::default());
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+        let identity_key = IdentityKey::generate();
+        let key = [7; 32];
+        state.sessions.lock().await.insert(
+            origin,
+            Session::new(&key)
+                .with_identity(Some(identity_key.public_key().to_vec())),
+        );
+        let client = Session::new(&key);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+
+        let handshake = || {
+            Msg::from(Request::Handshake(request::HandshakeArgs {
+                public_key: Handshake::new(Role::Initiator)
+                    .public_key()
+                    .to_vec(),
+            }))
+        };
+        let session_is_replaced = || async {
+            let (nonce, data) = client.seal(b"data").unwrap();
+            let session = state.sessions.lock().await.get(&origin).cloned();
+            session.unwrap().open(&nonce, &data).is_err()
+        };
+
+        // A handshake that is neither sealed nor signed by the identity of
+        // the session is refused, leaving the session in place
+        let mut signed_by_other = handshake();
+        signed_by_other.sign(&IdentityKey::generate()).unwrap();
+        for msg in [handshake(), signed_by_other].iter().cloned() {
+            Executor::<Vec<u8>>::new(tx.clone(), origin, usize::MAX)
+                .execute(Arc::clone(&state), msg)
+                .await
+                .unwrap();
+            match Msg::from_slice(&rx.recv().await.unwrap()).unwrap().content {
+                Content::Reply(Reply::Error(x)) => {
+                    assert_eq!(x.code(), ErrorCode::SESSION_REQUIRED)
+                }
+                x => panic!("Unexpected content: {:?}", x),
+            }
+            assert!(!session_is_replaced().await, "Session was replaced");
+        }
+
+        // A handshake sealed with the session replaces it, with the reply
+        // sealed by the session being replaced
+        let inner = handshake();
+        let (nonce, data) = client.seal(&inner.to_vec().unwrap()).unwrap();
+        let mut msg =
+            Msg::from(Request::Sealed(request::SealedArgs { nonce, data }));
+        msg.header.id = inner.header.id;
+        Executor::<Vec<u8>>::new(tx.clone(), origin, usize::MAX)
+            .execute(Arc::clone(&state), msg)
+            .await
+            .unwrap();
+        let args =
+            match Msg::from_slice(&rx.recv().await.unwrap()).unwrap().content {
+                Content::Reply(Reply::Sealed(args)) => args,
+                x => panic!("Unexpected content: {:?}", x),
+            };
+        let data = client.open(&args.nonce, &args.data).unwrap();
+        match Msg::from_slice(&data).unwrap().content {
+            Content::Reply(Reply::Handshake(_)) => {}
+            x => panic!("Unexpected content: {:?}", x),
+        }
+        assert!(session_is_replaced().await, "Session was not replaced");
+
+        // A handshake signed by the identity of the session replaces it
+        // without being sealed
+        state.sessions.lock().await.insert(
+            origin,
+            Session::new(&key)
+                .with_identity(Some(identity_key.public_key().to_vec())),
+        );
+        let mut msg = handshake();
+        msg.sign(&identity_key).unwrap();
+        Executor::<Vec<u8>>::new(tx, origin, usize::MAX)
+            .execute(Arc::clone(&state), msg)
+            .await
+            .unwrap();
+        match Msg::from_slice(&rx.recv().await.unwrap()).unwrap().content {
+            Content::Reply(Reply::Handshake(_)) => {}
+            x => panic!("Unexpected content: {:?}", x),
+        }
+        assert!(session_is_replaced().await, "Session was not replaced");
+        let session = state.sessions.lock().await.get(&origin).cloned();
+        assert_eq!(
+            session.unwrap().identity(),
+            Some(identity_key.public_key())
+        );
+    }
+
+    fn test_account() -> TransferAccount {
+        TransferAccount {
+            identity: String::from("127.0.0.1"),
+            class: "meta",
+        }
+    }
+
     fn time_diff(time1: u128, time2: u128) -> u128 {
         time1
             .checked_sub(time2)
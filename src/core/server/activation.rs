@@ -0,0 +1,243 @@
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+
+/// First file descriptor passed by systemd to an activated service
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Takes the pre-bound tcp listener handed to the server by the service
+/// manager that started it, such as through systemd socket activation or
+/// the sockets of a launchd job
+///
+/// With `name`, only a socket of that name is taken, which launchd always
+/// requires; otherwise, the first stream socket passed is taken. The socket
+/// is owned by the listener once taken, so this should only be done once.
+pub fn tcp_listener(name: Option<&str>) -> io::Result<TcpListener> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::FromRawFd;
+        let fd = take_fd(name, libc::SOCK_STREAM)?;
+
+        // NOTE: Safe as the descriptor was passed to this process to own
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = name;
+        Err(unsupported())
+    }
+}
+
+/// Takes the pre-bound udp socket handed to the server by the service
+/// manager that started it, in the same way as `tcp_listener`
+pub fn udp_socket(name: Option<&str>) -> io::Result<UdpSocket> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::FromRawFd;
+        let fd = take_fd(name, libc::SOCK_DGRAM)?;
+
+        // NOTE: Safe as the descriptor was passed to this process to own
+        let socket = unsafe { UdpSocket::from_raw_fd(fd) };
+        socket.set_nonblocking(true)?;
+        Ok(socket)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = name;
+        Err(unsupported())
+    }
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "Socket activation is not supported on this platform",
+    )
+}
+
+/// Finds the descriptor of the first inherited socket of `socket_type` with
+/// `name`, if provided
+#[cfg(unix)]
+fn take_fd(name: Option<&str>, socket_type: libc::c_int) -> io::Result<i32> {
+    let fds = inherited_fds(name)?;
+    let fd = fds
+        .into_iter()
+        .find(|fd| self::socket_type(*fd).ok() == Some(socket_type))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "No inherited {} socket{}",
+                    if socket_type == libc::SOCK_STREAM {
+                        "tcp"
+                    } else {
+                        "udp"
+                    },
+                    name.map(|x| format!(" named {}", x)).unwrap_or_default()
+                ),
+            )
+        })?;
+
+    // Inherited descriptors are not closed on exec by default, which would
+    // otherwise leak them into procs spawned by the server
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(fd)
+}
+
+#[cfg(unix)]
+fn socket_type(fd: i32) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    // NOTE: Safe as value is large enough to hold the option being read
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(value)
+    }
+}
+
+/// Descriptors of the sockets passed to this process, from launchd when
+/// running under it with a socket name or otherwise from systemd
+#[cfg(unix)]
+fn inherited_fds(name: Option<&str>) -> io::Result<Vec<i32>> {
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(name) = name {
+            if std::env::var_os("LISTEN_FDS").is_none() {
+                return launchd_fds(name);
+            }
+        }
+    }
+
+    let env = |key| std::env::var(key).ok();
+    systemd_fds(
+        std::process::id(),
+        env("LISTEN_PID").as_deref(),
+        env("LISTEN_FDS").as_deref(),
+        env("LISTEN_FDNAMES").as_deref(),
+        name,
+    )
+}
+
+/// Descriptors passed by systemd according to its environment variables,
+/// which only apply to the process with the pid they name
+#[cfg(unix)]
+fn systemd_fds(
+    pid: u32,
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    fd_names: Option<&str>,
+    name: Option<&str>,
+) -> io::Result<Vec<i32>> {
+    let not_activated = || {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Server was not started through socket activation",
+        )
+    };
+
+    match listen_pid.and_then(|x| x.parse::<u32>().ok()) {
+        Some(x) if x == pid => {}
+        _ => return Err(not_activated()),
+    }
+
+    let count = listen_fds
+        .and_then(|x| x.parse::<i32>().ok())
+        .filter(|x| *x > 0)
+        .ok_or_else(not_activated)?;
+    let names: Vec<&str> =
+        fd_names.map(|x| x.split(':').collect()).unwrap_or_default();
+
+    Ok((0..count)
+        .filter(|i| match name {
+            Some(name) => names.get(*i as usize) == Some(&name),
+            None => true,
+        })
+        .map(|i| SD_LISTEN_FDS_START + i)
+        .collect())
+}
+
+#[cfg(target_os = "macos")]
+fn launchd_fds(name: &str) -> io::Result<Vec<i32>> {
+    extern "C" {
+        fn launch_activate_socket(
+            name: *const libc::c_char,
+            fds: *mut *mut libc::c_int,
+            cnt: *mut libc::size_t,
+        ) -> libc::c_int;
+    }
+
+    let name = std::ffi::CString::new(name)
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?;
+    let mut fds: *mut libc::c_int = std::ptr::null_mut();
+    let mut cnt: libc::size_t = 0;
+
+    // NOTE: Safe as launchd allocates the array of descriptors, which is
+    //       copied before being freed
+    unsafe {
+        let err = launch_activate_socket(name.as_ptr(), &mut fds, &mut cnt);
+        if err != 0 {
+            return Err(io::Error::from_raw_os_error(err));
+        }
+
+        let result = std::slice::from_raw_parts(fds, cnt).to_vec();
+        libc::free(fds as *mut libc::c_void);
+        Ok(result)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn systemd_fds_should_only_apply_to_process_named() {
+        let err =
+            systemd_fds(10, Some("11"), Some("2"), None, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let err = systemd_fds(10, None, Some("2"), None, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let fds = systemd_fds(10, Some("10"), Some("2"), None, None).unwrap();
+        assert_eq!(fds, vec![3, 4]);
+    }
+
+    #[test]
+    fn systemd_fds_should_filter_by_name() {
+        let names = Some("web:agent:agent");
+        let fds = systemd_fds(10, Some("10"), Some("3"), names, Some("agent"))
+            .unwrap();
+        assert_eq!(fds, vec![4, 5]);
+
+        let fds = systemd_fds(10, Some("10"), Some("3"), names, Some("other"))
+            .unwrap();
+        assert!(fds.is_empty());
+    }
+
+    #[test]
+    fn tcp_listener_should_fail_when_not_activated() {
+        // NOTE: Tests are never run through socket activation
+        let err = tcp_listener(None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,274 @@
+use super::{
+    job::{now_millis, JobRecord},
+    schedule::LocalSchedule,
+    state::ServerState,
+};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Version of the archive format produced by `export`, which is increased
+/// whenever the format changes in a way older servers cannot read
+pub const STATE_ARCHIVE_VERSION: u32 = 1;
+
+/// Durable metadata managed by a server, used to migrate it to a new host
+/// without losing what clients have set up through it
+///
+/// Only metadata that outlives connections is included, such as jobs and
+/// schedules; open files and procs belong to the host they were opened on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StateArchive {
+    pub version: u32,
+
+    /// Milliseconds since the unix epoch when the archive was produced
+    pub exported_at: u64,
+
+    pub jobs: Vec<ArchivedJob>,
+    pub schedules: Vec<LocalSchedule>,
+
+    /// Config last pushed to the server, if any
+    pub config: Option<ArchivedConfig>,
+
+    /// Fingerprints of the client keys trusted by the server
+    pub trusted_clients: Vec<String>,
+}
+
+/// Job along with the output it captured, which is empty if output was left
+/// out of the archive
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedJob {
+    pub record: JobRecord,
+
+    #[serde(with = "serde_bytes")]
+    pub stdout: Vec<u8>,
+
+    #[serde(with = "serde_bytes")]
+    pub stderr: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ArchivedConfig {
+    #[serde(with = "serde_bytes")]
+    pub blob: Vec<u8>,
+    pub version: u64,
+}
+
+/// Leading fields of an archive of any version, read before the rest so
+/// that archives of unsupported versions are reported as such
+#[derive(Deserialize)]
+struct ArchiveHeader {
+    version: u32,
+}
+
+/// Totals of what was taken from an archive when importing it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub jobs: u32,
+
+    /// Jobs left out as a job with the same id already exists
+    pub jobs_skipped: u32,
+
+    pub schedules: u32,
+
+    /// Whether the config of the archive replaced that of the server, which
+    /// only happens if it is newer
+    pub config: bool,
+
+    /// Fingerprints added to the client keys trusted by the server, which
+    /// are only added if the server has a list of trusted clients
+    pub trusted_clients: u32,
+}
+
+impl StateArchive {
+    pub fn to_vec(&self) -> io::Result<Vec<u8>> {
+        serde_cbor::to_vec(self)
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+    }
+
+    pub fn from_slice(slice: &[u8]) -> io::Result<Self> {
+        let invalid = |x| io::Error::new(io::ErrorKind::InvalidData, x);
+        let header: ArchiveHeader =
+            serde_cbor::from_slice(slice).map_err(invalid)?;
+        if header.version != STATE_ARCHIVE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "State archive is version {}, but only version {} is \
+                     supported",
+                    header.version, STATE_ARCHIVE_VERSION
+                ),
+            ));
+        }
+
+        serde_cbor::from_slice(slice).map_err(invalid)
+    }
+}
+
+/// Collects the durable metadata of the server into an archive, leaving out
+/// the output of jobs if `skip_job_output`
+pub async fn export(
+    state: &ServerState,
+    skip_job_output: bool,
+) -> io::Result<StateArchive> {
+    let mut jobs = Vec::new();
+    for record in state.jobs.list().await? {
+        let (stdout, stderr) = if skip_job_output {
+            (Vec::new(), Vec::new())
+        } else {
+            state.jobs.read_output(record.id).await?
+        };
+
+        jobs.push(ArchivedJob {
+            record,
+            stdout,
+            stderr,
+        });
+    }
+
+    Ok(StateArchive {
+        version: STATE_ARCHIVE_VERSION,
+        exported_at: now_millis(),
+        jobs,
+        schedules: state.schedules.list().await?,
+        config: state.config.get().await.map(|c| ArchivedConfig {
+            blob: c.blob,
+            version: c.version,
+        }),
+        trusted_clients: state.trusted_clients.fingerprints().await,
+    })
+}
+
+/// Adds the metadata of `archive` to that of the server, keeping what the
+/// server already has wherever the two conflict
+pub async fn import(
+    state: &ServerState,
+    archive: StateArchive,
+) -> io::Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+
+    for job in archive.jobs {
+        match state
+            .jobs
+            .restore(job.record, &job.stdout, &job.stderr)
+            .await
+        {
+            Ok(()) => summary.jobs += 1,
+            Err(x) if x.kind() == io::ErrorKind::AlreadyExists => {
+                summary.jobs_skipped += 1
+            }
+            Err(x) => return Err(x),
+        }
+    }
+
+    summary.schedules = archive.schedules.len() as u32;
+    state.schedules.restore(archive.schedules).await?;
+
+    if let Some(config) = archive.config {
+        if config.version > state.config.version() {
+            state.config.push(config.blob, config.version).await?;
+            summary.config = true;
+        }
+    }
+
+    if state.trusted_clients.is_enabled() {
+        let before = state.trusted_clients.fingerprints().await.len();
+        let after = state
+            .trusted_clients
+            .update(&archive.trusted_clients, &[])
+            .await?
+            .len();
+        summary.trusted_clients = (after - before) as u32;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::server::{
+        job::{JobManager, LocalJobSpec, LocalJobState},
+        schedule::{LocalTrigger, ScheduleManager},
+    };
+    use std::time::Duration;
+
+    fn make_state(dir: &std::path::Path) -> ServerState {
+        let mut state = ServerState::default();
+        state
+            .set_jobs(JobManager::new(dir))
+            .set_schedules(ScheduleManager::new(dir));
+        state
+    }
+
+    fn make_spec() -> LocalJobSpec {
+        LocalJobSpec {
+            command: String::from("sh"),
+            args: vec![String::from("-c"), String::from("echo hi")],
+            current_dir: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn import_should_restore_exported_state_on_another_server() {
+        let old_dir = tempfile::tempdir().unwrap();
+        let old = make_state(old_dir.as_ref());
+        let job = old.jobs.submit(&old.tasks, make_spec()).await.unwrap();
+        for _ in 0..50 {
+            if old.jobs.get(job.id).await.unwrap().state
+                == LocalJobState::Exited
+            {
+                break;
+            }
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        let schedule = old
+            .schedules
+            .create(
+                make_spec(),
+                LocalTrigger::Interval(Duration::from_secs(60)),
+            )
+            .await
+            .unwrap();
+        old.config.push(b"config".to_vec(), 3).await.unwrap();
+
+        let archive = export(&old, false).await.unwrap();
+        let archive =
+            StateArchive::from_slice(&archive.to_vec().unwrap()).unwrap();
+
+        let new_dir = tempfile::tempdir().unwrap();
+        let new = make_state(new_dir.as_ref());
+        let summary = import(&new, archive.clone()).await.unwrap();
+        assert_eq!(summary.jobs, 1);
+        assert_eq!(summary.schedules, 1);
+        assert!(summary.config);
+
+        assert_eq!(new.jobs.get(job.id).await.unwrap().spec, job.spec);
+        assert_eq!(
+            new.jobs.read_output(job.id).await.unwrap().0,
+            b"hi\n".to_vec()
+        );
+        assert_eq!(new.schedules.list().await.unwrap(), vec![schedule]);
+        assert_eq!(new.config.get().await.unwrap().blob, b"config".to_vec());
+
+        // Importing again keeps what the server already has
+        let summary = import(&new, archive).await.unwrap();
+        assert_eq!(summary.jobs, 0);
+        assert_eq!(summary.jobs_skipped, 1);
+        assert!(!summary.config);
+    }
+
+    #[test]
+    fn from_slice_should_reject_unsupported_versions() {
+        let archive = StateArchive {
+            version: STATE_ARCHIVE_VERSION + 1,
+            exported_at: 0,
+            jobs: Vec::new(),
+            schedules: Vec::new(),
+            config: None,
+            trusted_clients: Vec::new(),
+        };
+
+        let err =
+            StateArchive::from_slice(&archive.to_vec().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,249 @@
+use super::job::write_json_atomic;
+use crate::core::transport::crypto::{
+    Aes256GcmBicrypter, AssociatedData, Decrypter, Encrypter,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+
+/// Configuration document pushed by an operator, whose contents are opaque
+/// to the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PushedConfig {
+    pub blob: Vec<u8>,
+    pub version: u64,
+}
+
+/// Form of a config as stored on disk, where only the version is readable
+#[derive(Serialize, Deserialize)]
+struct SealedConfig {
+    version: u64,
+    associated_data: AssociatedData,
+    ciphertext: Vec<u8>,
+}
+
+/// Holds the latest config pushed to the server, optionally persisting it
+/// to disk encrypted, and notifies subscribers of each new version
+///
+/// Clones share the same config, so a store can be captured by custom
+/// handlers to read what operators have pushed
+#[derive(Clone)]
+pub struct ConfigStore {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: Option<PathBuf>,
+    bicrypter: Aes256GcmBicrypter,
+    current: Mutex<Option<PushedConfig>>,
+    tx: watch::Sender<u64>,
+    rx: watch::Receiver<u64>,
+}
+
+impl Default for ConfigStore {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+impl fmt::Debug for ConfigStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigStore")
+            .field("path", &self.inner.path)
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+impl ConfigStore {
+    /// Creates a store that only keeps its config in memory, losing it when
+    /// the server exits
+    pub fn in_memory() -> Self {
+        let key = crate::core::transport::crypto::key::new_256bit_key();
+        Self::with_state(None, Aes256GcmBicrypter::new(&key), None)
+    }
+
+    /// Creates a store that persists its config at `path`, encrypted with a
+    /// key derived from `secret`, loading any config already stored there
+    pub fn open(path: impl Into<PathBuf>, secret: &[u8]) -> io::Result<Self> {
+        let path = path.into();
+        let mut key = [0; 32];
+        key.copy_from_slice(&Sha256::digest(secret));
+
+        let bicrypter = Aes256GcmBicrypter::new(&key);
+        let current = if path.exists() {
+            Some(load(&path, &bicrypter)?)
+        } else {
+            None
+        };
+
+        Ok(Self::with_state(Some(path), bicrypter, current))
+    }
+
+    fn with_state(
+        path: Option<PathBuf>,
+        bicrypter: Aes256GcmBicrypter,
+        current: Option<PushedConfig>,
+    ) -> Self {
+        let version = current.as_ref().map(|c| c.version).unwrap_or_default();
+        let (tx, rx) = watch::channel(version);
+
+        Self {
+            inner: Arc::new(Inner {
+                path,
+                bicrypter,
+                current: Mutex::new(current),
+                tx,
+                rx,
+            }),
+        }
+    }
+
+    /// Path where the config is persisted, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.path.as_deref()
+    }
+
+    /// Version of the latest config, or 0 if none has been pushed
+    pub fn version(&self) -> u64 {
+        *self.inner.rx.borrow()
+    }
+
+    /// Retrieves the latest config, if any has been pushed
+    pub async fn get(&self) -> Option<PushedConfig> {
+        self.inner.current.lock().await.clone()
+    }
+
+    /// Replaces the config with `blob`, failing if `version` is not newer
+    /// than that of the current config so that stale pushes are rejected
+    pub async fn push(&self, blob: Vec<u8>, version: u64) -> io::Result<()> {
+        let mut current = self.inner.current.lock().await;
+        let current_version =
+            current.as_ref().map(|c| c.version).unwrap_or_default();
+        if version <= current_version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Config version {} is not newer than {}",
+                    version, current_version
+                ),
+            ));
+        }
+
+        if let Some(path) = self.inner.path.as_ref() {
+            let associated_data =
+                self.inner.bicrypter.new_encrypt_associated_data();
+            let ciphertext = self
+                .inner
+                .bicrypter
+                .encrypt(&blob, &associated_data)
+                .map_err(io::Error::other)?;
+            write_json_atomic(
+                path,
+                &SealedConfig {
+                    version,
+                    associated_data,
+                    ciphertext,
+                },
+            )
+            .await?;
+        }
+
+        *current = Some(PushedConfig { blob, version });
+
+        // NOTE: Fails only when there are no receivers, which cannot happen
+        //       as the store holds one of its own
+        let _ = self.inner.tx.broadcast(version);
+
+        Ok(())
+    }
+
+    /// Produces a receiver of the version of each config pushed from now on,
+    /// starting with the current version
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.inner.rx.clone()
+    }
+}
+
+fn load(
+    path: &Path,
+    bicrypter: &Aes256GcmBicrypter,
+) -> io::Result<PushedConfig> {
+    let sealed: SealedConfig = serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+    let blob = bicrypter
+        .decrypt(&sealed.ciphertext, &sealed.associated_data)
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+
+    Ok(PushedConfig {
+        blob,
+        version: sealed.version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn push_should_reject_versions_that_are_not_newer() {
+        let store = ConfigStore::in_memory();
+        store.push(b"first".to_vec(), 2).await.unwrap();
+
+        let err = store.push(b"second".to_vec(), 2).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = store.push(b"second".to_vec(), 1).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        assert_eq!(
+            store.get().await,
+            Some(PushedConfig {
+                blob: b"first".to_vec(),
+                version: 2,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn push_should_notify_subscribers() {
+        let store = ConfigStore::in_memory();
+        let mut rx = store.subscribe();
+        assert_eq!(rx.recv().await, Some(0));
+
+        store.clone().push(b"config".to_vec(), 7).await.unwrap();
+        assert_eq!(rx.recv().await, Some(7));
+        assert_eq!(store.version(), 7);
+    }
+
+    #[tokio::test]
+    async fn open_should_load_config_encrypted_at_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+
+        let store = ConfigStore::open(&path, b"secret").unwrap();
+        store.push(b"plaintext config".to_vec(), 3).await.unwrap();
+
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw
+            .windows(b"plaintext config".len())
+            .any(|w| w == b"plaintext config"));
+
+        let store = ConfigStore::open(&path, b"secret").unwrap();
+        assert_eq!(
+            store.get().await,
+            Some(PushedConfig {
+                blob: b"plaintext config".to_vec(),
+                version: 3,
+            })
+        );
+        assert_eq!(store.version(), 3);
+
+        let err = ConfigStore::open(&path, b"wrong secret").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
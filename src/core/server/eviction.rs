@@ -0,0 +1,48 @@
+use crate::core::request::EvictionKind;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Totals of the resources evicted by the server for going untouched, kept
+/// for the lifetime of the server
+#[derive(Debug, Default)]
+pub struct EvictionCounters {
+    files: AtomicU64,
+    procs: AtomicU64,
+    sessions: AtomicU64,
+}
+
+impl EvictionCounters {
+    /// Counts a single eviction of a resource of `kind`
+    pub fn record(&self, kind: EvictionKind) {
+        self.counter(kind).fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Total resources of `kind` that have been evicted
+    pub fn total(&self, kind: EvictionKind) -> u64 {
+        self.counter(kind).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, kind: EvictionKind) -> &AtomicU64 {
+        match kind {
+            EvictionKind::File => &self.files,
+            EvictionKind::Proc => &self.procs,
+            EvictionKind::Session => &self.sessions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_should_only_count_evictions_of_the_same_kind() {
+        let counters = EvictionCounters::default();
+        counters.record(EvictionKind::Proc);
+        counters.record(EvictionKind::Proc);
+        counters.record(EvictionKind::Session);
+
+        assert_eq!(counters.total(EvictionKind::File), 0);
+        assert_eq!(counters.total(EvictionKind::Proc), 2);
+        assert_eq!(counters.total(EvictionKind::Session), 1);
+    }
+}
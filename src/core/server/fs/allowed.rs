@@ -0,0 +1,398 @@
+#[cfg(unix)]
+use super::secure::{SecureOpenOptions, SecureRoot};
+use super::LocalDirEntry;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+/// Kind of entry at a path
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// Entries of a dir, either still being read or read in full
+#[derive(Debug)]
+pub enum DirEntries {
+    Stream(tokio::fs::ReadDir),
+    Read(std::vec::IntoIter<LocalDirEntry>),
+}
+
+impl DirEntries {
+    /// Yields the next entry of the dir, or none once all have been yielded
+    pub async fn next_entry(&mut self) -> io::Result<Option<LocalDirEntry>> {
+        match self {
+            Self::Stream(stream) => match stream.next_entry().await? {
+                Some(entry) => {
+                    Ok(Some(LocalDirEntry::from_dir_entry(&entry).await?))
+                }
+                None => Ok(None),
+            },
+            Self::Read(entries) => Ok(entries.next()),
+        }
+    }
+}
+
+/// Dirs that paths on disk are confined to, where each operation on a path
+/// goes through the secure root of the dir that the path falls within so
+/// that no symlink is followed on the way to (or at) the path
+///
+/// Operations act on paths directly if there are no allowed paths, or on
+/// platforms without secure path resolution. Clones share the same dirs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AllowedPaths(Arc<Vec<PathBuf>>);
+
+impl AllowedPaths {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self(Arc::new(paths))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Opens the file at `path` for reading
+    pub async fn open_read(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<tokio::fs::File> {
+        #[cfg(unix)]
+        {
+            let file = self
+                .within(path.as_ref(), |root, path| {
+                    root.open_file(
+                        path,
+                        SecureOpenOptions {
+                            read: true,
+                            ..Default::default()
+                        },
+                        false,
+                    )
+                })
+                .await;
+            if let Some(file) = file {
+                return file.map(tokio::fs::File::from_std);
+            }
+        }
+
+        tokio::fs::File::open(path).await
+    }
+
+    /// Reads the entire contents of the file at `path`
+    pub async fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        let mut file = self.open_read(path).await?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        Ok(contents)
+    }
+
+    /// Whether anything, including a symlink, exists at `path`
+    pub async fn exists(&self, path: impl AsRef<Path>) -> bool {
+        #[cfg(unix)]
+        {
+            let kind = self.within(path.as_ref(), |root, path| root.kind(path));
+            if let Some(kind) = kind.await {
+                return kind.is_ok();
+            }
+        }
+
+        tokio::fs::symlink_metadata(path).await.is_ok()
+    }
+
+    /// Looks up the kind of entry at `path`, which (when confined) is never
+    /// followed if it is a symlink
+    pub async fn kind(&self, path: impl AsRef<Path>) -> io::Result<EntryKind> {
+        #[cfg(unix)]
+        {
+            let kind = self.within(path.as_ref(), |root, path| root.kind(path));
+            if let Some(kind) = kind.await {
+                return kind;
+            }
+        }
+
+        let file_type = tokio::fs::metadata(path).await?.file_type();
+        Ok(if file_type.is_file() {
+            EntryKind::File
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::Other
+        })
+    }
+
+    /// Whether the entry at `path` is a dir
+    pub async fn is_dir(&self, path: impl AsRef<Path>) -> bool {
+        matches!(self.kind(path).await, Ok(EntryKind::Dir))
+    }
+
+    /// Whether the entry at `path` is a regular file
+    pub async fn is_file(&self, path: impl AsRef<Path>) -> bool {
+        matches!(self.kind(path).await, Ok(EntryKind::File))
+    }
+
+    /// Retrieves the immediate entries of the dir at `path`, without
+    /// following any symlinks within it
+    pub async fn entries(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Vec<LocalDirEntry>> {
+        let mut entries = Vec::new();
+        let mut dir_entries = self.read_dir(path).await?;
+        while let Some(entry) = dir_entries.next_entry().await? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Reads the immediate entries of the dir at `path` as they are asked
+    /// for, or all at once up front when confined
+    pub async fn read_dir(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<DirEntries> {
+        #[cfg(unix)]
+        {
+            let entries =
+                self.within(path.as_ref(), |root, path| root.read_dir(path));
+            if let Some(entries) = entries.await {
+                let entries = entries?
+                    .into_iter()
+                    .map(|(name, kind)| LocalDirEntry {
+                        path: path.as_ref().join(name),
+                        is_file: kind == EntryKind::File,
+                        is_dir: kind == EntryKind::Dir,
+                        is_symlink: kind == EntryKind::Symlink,
+                    })
+                    .collect::<Vec<_>>();
+                return Ok(DirEntries::Read(entries.into_iter()));
+            }
+        }
+
+        Ok(DirEntries::Stream(tokio::fs::read_dir(path).await?))
+    }
+
+    /// Creates the dir at `path`, along with any missing parent dirs if
+    /// `create_components`, applying `mode` (or the default permissions if
+    /// not provided) to each dir that is created
+    pub async fn create_dir(
+        &self,
+        path: impl AsRef<Path>,
+        create_components: bool,
+        mode: Option<u32>,
+    ) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            let result = self.within(path.as_ref(), move |root, path| {
+                if create_components {
+                    root.create_dir_all(path, mode).map(drop)
+                } else {
+                    root.create_dir(path, mode)
+                }
+            });
+            if let Some(result) = result.await {
+                return result;
+            }
+        }
+
+        // Track the directories that do not yet exist so we only change the
+        // permissions of those that we create
+        let path = path.as_ref();
+        let mut missing = Vec::new();
+        if mode.is_some() {
+            let mut current = Some(path);
+            while let Some(p) = current {
+                if tokio::fs::metadata(p).await.is_ok() {
+                    break;
+                }
+                missing.push(p.to_path_buf());
+                current = p.parent();
+            }
+        }
+
+        if create_components {
+            tokio::fs::create_dir_all(path).await?;
+        } else {
+            tokio::fs::create_dir(path).await?;
+        }
+
+        if let Some(mode) = mode {
+            for p in missing {
+                self.set_mode(p, mode).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renames the file or dir at `from` to `to`, replacing any file at `to`
+    pub async fn rename(
+        &self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            if !self.is_empty() {
+                let paths = Arc::clone(&self.0);
+                let from = from.as_ref().to_path_buf();
+                let to = to.as_ref().to_path_buf();
+                return blocking(move || {
+                    let (from_root, from) = root_for(&paths, &from)?;
+                    let (to_root, to) = root_for(&paths, &to)?;
+                    from_root.rename_into(from, &to_root, to)
+                })
+                .await;
+            }
+        }
+
+        tokio::fs::rename(from, to).await
+    }
+
+    /// Removes the file at `path`, or the symlink itself if it is one
+    pub async fn remove_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            let result =
+                self.within(path.as_ref(), |root, path| root.remove_file(path));
+            if let Some(result) = result.await {
+                return result;
+            }
+        }
+
+        tokio::fs::remove_file(path).await
+    }
+
+    /// Removes the dir at `path`, along with everything within it if
+    /// `non_empty`
+    pub async fn remove_dir(
+        &self,
+        path: impl AsRef<Path>,
+        non_empty: bool,
+    ) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            let result = self.within(path.as_ref(), move |root, path| {
+                root.remove_dir(path, non_empty)
+            });
+            if let Some(result) = result.await {
+                return result;
+            }
+        }
+
+        if non_empty {
+            tokio::fs::remove_dir_all(path).await
+        } else {
+            tokio::fs::remove_dir(path).await
+        }
+    }
+
+    /// Applies the unix permission bits `mode` to the file or dir at `path`,
+    /// doing nothing on platforms without unix permissions
+    pub async fn set_mode(
+        &self,
+        path: impl AsRef<Path>,
+        mode: u32,
+    ) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            let result = self.within(path.as_ref(), move |root, path| {
+                root.set_mode(path, mode)
+            });
+            if let Some(result) = result.await {
+                return result;
+            }
+        }
+
+        super::set_mode(path, mode).await
+    }
+
+    /// Copies the contents and permissions of the file at `from` to `to`,
+    /// yielding the total bytes copied
+    pub async fn copy(
+        &self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> io::Result<u64> {
+        #[cfg(unix)]
+        {
+            if !self.is_empty() {
+                let paths = Arc::clone(&self.0);
+                let from = from.as_ref().to_path_buf();
+                let to = to.as_ref().to_path_buf();
+                return blocking(move || {
+                    let (from_root, from) = root_for(&paths, &from)?;
+                    let (to_root, to) = root_for(&paths, &to)?;
+                    from_root.copy_into(from, &to_root, to)
+                })
+                .await;
+            }
+        }
+
+        tokio::fs::copy(from, to).await
+    }
+
+    /// Runs `op` on the blocking pool with the secure root that `path` falls
+    /// within and the absolute form of `path`, or yields none if there are
+    /// no allowed paths to confine `path` to
+    #[cfg(unix)]
+    pub(super) async fn within<T, F>(
+        &self,
+        path: &Path,
+        op: F,
+    ) -> Option<io::Result<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SecureRoot, &Path) -> io::Result<T> + Send + 'static,
+    {
+        if self.is_empty() {
+            return None;
+        }
+
+        let paths = Arc::clone(&self.0);
+        let path = path.to_path_buf();
+        Some(
+            blocking(move || {
+                let (root, path) = root_for(&paths, &path)?;
+                op(&root, &path)
+            })
+            .await,
+        )
+    }
+}
+
+/// Opens the first of `paths` that `path` falls within as a secure root,
+/// yielding it alongside the absolute form of `path`
+#[cfg(unix)]
+fn root_for(
+    paths: &[PathBuf],
+    path: &Path,
+) -> io::Result<(SecureRoot, PathBuf)> {
+    let path = std::env::current_dir()?.join(path);
+    let root = paths
+        .iter()
+        .filter_map(|x| SecureRoot::open(x).ok())
+        .find(|x| x.confine(path.as_path()).is_ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{:?} is outside of the allowed paths", path),
+            )
+        })?;
+    Ok((root, path))
+}
+
+/// Runs `f`, which makes blocking syscalls, on the blocking pool
+#[cfg(unix)]
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
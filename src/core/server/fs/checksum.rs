@@ -0,0 +1,141 @@
+use super::AllowedPaths;
+use crate::core::request::ChecksumAlgorithm;
+use sha2::{Digest, Sha256, Sha512};
+use std::io;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Size of each chunk read from a file while computing its checksum
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reflected polynomial of CRC-32 as used by zip, gzip, and png
+const CRC32_POLYNOMIAL: u32 = 0xedb8_8320;
+
+/// Checksum being computed over content fed to it in chunks
+pub enum Checksum {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Crc32 { table: Box<[u32; 256]>, crc: u32 },
+}
+
+impl Checksum {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Self::Sha512(Sha512::new()),
+            ChecksumAlgorithm::Crc32 => Self::Crc32 {
+                table: Box::new(crc32_table()),
+                crc: !0,
+            },
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sha256(x) => x.input(bytes),
+            Self::Sha512(x) => x.input(bytes),
+            Self::Crc32 { table, crc } => {
+                for b in bytes {
+                    *crc = table[((*crc ^ u32::from(*b)) & 0xff) as usize]
+                        ^ (*crc >> 8);
+                }
+            }
+        }
+    }
+
+    /// Lowercase hex digest of all content fed to the checksum
+    pub fn finish(self) -> String {
+        match self {
+            Self::Sha256(x) => format!("{:x}", x.result()),
+            Self::Sha512(x) => format!("{:x}", x.result()),
+            Self::Crc32 { crc, .. } => format!("{:08x}", !crc),
+        }
+    }
+}
+
+/// Computes the checksum of the file at `path` without holding more than a
+/// chunk of it in memory, yielding its hex digest along with the total
+/// bytes read
+pub async fn checksum_file(
+    paths: &AllowedPaths,
+    path: impl AsRef<Path>,
+    algorithm: ChecksumAlgorithm,
+) -> io::Result<(String, u64)> {
+    let mut file = paths.open_read(path).await?;
+    let mut checksum = Checksum::new(algorithm);
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut size = 0;
+
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        checksum.update(&buf[..n]);
+        size += n as u64;
+    }
+
+    Ok((checksum.finish(), size))
+}
+
+fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                CRC32_POLYNOMIAL ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+        *entry = crc;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest_of(algorithm: ChecksumAlgorithm, bytes: &[u8]) -> String {
+        let mut checksum = Checksum::new(algorithm);
+        checksum.update(bytes);
+        checksum.finish()
+    }
+
+    #[test]
+    fn checksum_should_match_known_digests() {
+        assert_eq!(
+            digest_of(ChecksumAlgorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            digest_of(ChecksumAlgorithm::Sha512, b"abc"),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assert_eq!(
+            digest_of(ChecksumAlgorithm::Crc32, b"123456789"),
+            "cbf43926"
+        );
+        assert_eq!(digest_of(ChecksumAlgorithm::Crc32, b""), "00000000");
+    }
+
+    #[tokio::test]
+    async fn checksum_file_should_span_chunks() {
+        let contents = vec![7u8; CHUNK_SIZE * 2 + 3];
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &contents).unwrap();
+
+        let (digest, size) = checksum_file(
+            &AllowedPaths::default(),
+            file.path(),
+            ChecksumAlgorithm::Crc32,
+        )
+        .await
+        .unwrap();
+        assert_eq!(size, contents.len() as u64);
+        assert_eq!(digest, digest_of(ChecksumAlgorithm::Crc32, &contents));
+    }
+}
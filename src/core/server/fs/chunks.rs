@@ -0,0 +1,398 @@
+use super::{AllowedPaths, LocalFile, LocalFileError};
+use crate::core::request::ManifestChunk;
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use tokio::{io::AsyncReadExt, sync::Mutex};
+
+/// Default number of chunks whose location the server remembers
+pub const DEFAULT_CHUNK_LOCATIONS: usize = 65536;
+
+/// Hex-encoded SHA-256 hash of a chunk, as listed in upload manifests
+pub fn hash_chunk(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// File being uploaded in chunks cut by a `Chunker`, each with a known
+/// length and hash
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkedUpload {
+    size: u64,
+    chunks: Vec<(u64, u64, String)>,
+}
+
+impl ChunkedUpload {
+    /// Describes a file of `size` bytes made up of `chunks` laid end to end,
+    /// failing if any chunk is empty or the chunks do not add up to `size`
+    pub fn new(size: u64, chunks: &[ManifestChunk]) -> io::Result<Self> {
+        let invalid =
+            |msg: String| Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+
+        let mut offset = 0u64;
+        let mut placed = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            if chunk.len == 0 {
+                return invalid(format!("Chunk {} is empty", index));
+            }
+
+            placed.push((offset, chunk.len, chunk.hash.to_lowercase()));
+            offset = match offset.checked_add(chunk.len) {
+                Some(offset) => offset,
+                None => return invalid(format!("Chunk {} is too big", index)),
+            };
+        }
+
+        if offset != size {
+            return invalid(format!(
+                "{} chunks cover {} bytes instead of {}",
+                chunks.len(),
+                offset,
+                size
+            ));
+        }
+
+        Ok(Self {
+            size,
+            chunks: placed,
+        })
+    }
+
+    /// Total bytes of the file once uploaded
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of chunks making up the file
+    pub fn chunk_cnt(&self) -> u32 {
+        self.chunks.len() as u32
+    }
+
+    /// Offset, length, and hash of the chunk at `index`, if it exists
+    pub fn chunk(&self, index: u32) -> Option<(u64, u64, &str)> {
+        let (offset, len, hash) = self.chunks.get(index as usize)?;
+        Some((*offset, *len, hash))
+    }
+}
+
+/// Where a chunk with some hash was last written by the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ChunkLocation {
+    path: PathBuf,
+    offset: u64,
+    len: u64,
+}
+
+/// Chunks expected by files being uploaded in chunks, along with the
+/// locations of chunks the server has seen before so that a file can be
+/// built from chunks that already exist on the server rather than sending
+/// them again
+///
+/// Locations are only hints, as the files holding them can change at any
+/// time, so a chunk is verified against its hash before being reused.
+#[derive(Debug)]
+pub struct ChunkStore {
+    uploads: Mutex<HashMap<u32, ChunkedUpload>>,
+    locations: Mutex<LruCache<String, ChunkLocation>>,
+}
+
+impl Default for ChunkStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_LOCATIONS)
+    }
+}
+
+impl ChunkStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            uploads: Mutex::new(HashMap::new()),
+            locations: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Associates `upload` with the open file with `id`, whose chunks are
+    /// then written one at a time
+    pub async fn start_upload(&self, id: u32, upload: ChunkedUpload) {
+        self.uploads.lock().await.insert(id, upload);
+    }
+
+    /// Forgets the chunks expected by the file with `id`, such as once the
+    /// file is closed
+    pub async fn finish_upload(&self, id: u32) {
+        self.uploads.lock().await.remove(&id);
+    }
+
+    /// Offset, length, and hash of the chunk at `index` of the file with
+    /// `id`, if the file is being uploaded in chunks and has that chunk
+    pub async fn expected_chunk(
+        &self,
+        id: u32,
+        index: u32,
+    ) -> Option<(u64, u64, String)> {
+        let uploads = self.uploads.lock().await;
+        let (offset, len, hash) = uploads.get(&id)?.chunk(index)?;
+        Some((offset, len, hash.to_string()))
+    }
+
+    /// Records that the chunk with `hash` can be found at `offset` within
+    /// the file at `path`
+    pub async fn remember(
+        &self,
+        hash: &str,
+        path: impl Into<PathBuf>,
+        offset: u64,
+        len: u64,
+    ) {
+        self.locations.lock().await.put(
+            hash.to_lowercase(),
+            ChunkLocation {
+                path: path.into(),
+                offset,
+                len,
+            },
+        );
+    }
+
+    /// Prepares `local_file` to receive `upload`, placing every chunk the
+    /// server already holds at its offset and sizing the file to that of the
+    /// upload, yielding the positions of those chunks
+    ///
+    /// A chunk is held if the file already has it in place, such as when
+    /// resuming an upload, or if it was seen before anywhere else within the
+    /// paths that the file is confined to.
+    pub async fn place_known_chunks(
+        &self,
+        local_file: &mut LocalFile,
+        upload: &ChunkedUpload,
+    ) -> io::Result<Vec<u32>> {
+        let path = local_file.path();
+        let paths = local_file.allowed_paths();
+
+        let mut have_chunks = Vec::new();
+        for index in 0..upload.chunk_cnt() {
+            let (offset, len, hash) = match upload.chunk(index) {
+                Some(chunk) => chunk,
+                None => break,
+            };
+
+            let (data, size) = local_file
+                .read_range(local_file.sig(), offset, Some(len))
+                .await
+                .map_err(io_error)?;
+            let in_place = offset + len <= size && hash_chunk(&data) == hash;
+
+            if in_place
+                || self
+                    .copy_known_chunk(&paths, local_file, offset, hash)
+                    .await
+            {
+                self.remember(hash, &path, offset, len).await;
+                have_chunks.push(index);
+            }
+        }
+
+        local_file
+            .set_len(local_file.sig(), upload.size())
+            .await
+            .map_err(io_error)?;
+
+        Ok(have_chunks)
+    }
+
+    /// Writes the chunk with `hash` at `offset` of `local_file` if its
+    /// location within `paths` is known and it still has that hash there,
+    /// returning whether it did
+    async fn copy_known_chunk(
+        &self,
+        paths: &AllowedPaths,
+        local_file: &mut LocalFile,
+        offset: u64,
+        hash: &str,
+    ) -> bool {
+        let location = match self.locations.lock().await.get(&hash.to_string())
+        {
+            Some(location) => location.clone(),
+            None => return false,
+        };
+
+        let data = match read_chunk_at(paths, &location).await {
+            Ok(data) if hash_chunk(&data) == hash => data,
+            _ => {
+                self.locations.lock().await.pop(&hash.to_string());
+                return false;
+            }
+        };
+
+        local_file
+            .write_at(local_file.sig(), offset, &data)
+            .await
+            .is_ok()
+    }
+}
+
+/// Reads the chunk at `location` within `paths`, failing if the file no
+/// longer holds that many bytes there
+async fn read_chunk_at(
+    paths: &AllowedPaths,
+    location: &ChunkLocation,
+) -> io::Result<Vec<u8>> {
+    let mut file = paths.open_read(&location.path).await?;
+    if file.metadata().await?.len() < location.offset + location.len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+
+    let mut data = vec![0; location.len as usize];
+    file.seek(io::SeekFrom::Start(location.offset)).await?;
+    file.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+fn io_error(x: LocalFileError) -> io::Error {
+    match x {
+        LocalFileError::IoError(x) => x,
+        LocalFileError::SigMismatch => {
+            io::Error::new(io::ErrorKind::InvalidData, "Signature mismatch")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::fs;
+
+    fn chunks_of(data: &[u8], lens: &[usize]) -> Vec<ManifestChunk> {
+        let mut offset = 0;
+        lens.iter()
+            .map(|&len| {
+                let hash = hash_chunk(&data[offset..offset + len]);
+                offset += len;
+                ManifestChunk {
+                    len: len as u64,
+                    hash,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunked_upload_should_fail_if_chunks_do_not_cover_size() {
+        let chunks = chunks_of(b"abcdefg", &[3, 3, 1]);
+        assert!(ChunkedUpload::new(7, &chunks).is_ok());
+
+        let err = ChunkedUpload::new(10, &chunks).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let chunks = chunks_of(b"abcdefg", &[3, 0, 4]);
+        let err = ChunkedUpload::new(7, &chunks).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn chunked_upload_should_place_chunks_end_to_end() {
+        let chunks = chunks_of(b"abcdefg", &[2, 4, 1]);
+        let upload = ChunkedUpload::new(7, &chunks).unwrap();
+
+        assert_eq!(upload.chunk(0), Some((0, 2, chunks[0].hash.as_str())));
+        assert_eq!(upload.chunk(1), Some((2, 4, chunks[1].hash.as_str())));
+        assert_eq!(upload.chunk(2), Some((6, 1, chunks[2].hash.as_str())));
+        assert_eq!(upload.chunk(3), None);
+    }
+
+    #[tokio::test]
+    async fn place_known_chunks_should_keep_chunks_already_in_place() {
+        let store = ChunkStore::default();
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("file");
+        fs::write(&path, b"abcXXXg and more").await.unwrap();
+
+        let chunks = chunks_of(b"abcdefg", &[3, 3, 1]);
+        let upload = ChunkedUpload::new(7, &chunks).unwrap();
+        let mut local_file =
+            LocalFile::open(&path, true, true, true).await.unwrap();
+        let have_chunks = store
+            .place_known_chunks(&mut local_file, &upload)
+            .await
+            .unwrap();
+
+        assert_eq!(have_chunks, vec![0, 2]);
+        assert_eq!(fs::read(&path).await.unwrap(), b"abcXXXg".to_vec());
+    }
+
+    #[tokio::test]
+    async fn place_known_chunks_should_copy_chunks_seen_elsewhere() {
+        let store = ChunkStore::default();
+        let root = tempfile::tempdir().unwrap();
+        let other_path = root.as_ref().join("other");
+        let path = root.as_ref().join("file");
+        fs::write(&other_path, b"xxdefxx").await.unwrap();
+        store.remember(&hash_chunk(b"def"), &other_path, 2, 3).await;
+
+        let chunks = chunks_of(b"abcdefg", &[3, 3, 1]);
+        let upload = ChunkedUpload::new(7, &chunks).unwrap();
+        let mut local_file =
+            LocalFile::open(&path, true, true, true).await.unwrap();
+        let have_chunks = store
+            .place_known_chunks(&mut local_file, &upload)
+            .await
+            .unwrap();
+
+        assert_eq!(have_chunks, vec![1]);
+        assert_eq!(&fs::read(&path).await.unwrap()[3..6], b"def");
+    }
+
+    #[tokio::test]
+    async fn place_known_chunks_should_skip_chunks_changed_since_seen() {
+        let store = ChunkStore::default();
+        let root = tempfile::tempdir().unwrap();
+        let other_path = root.as_ref().join("other");
+        let path = root.as_ref().join("file");
+        fs::write(&other_path, b"xxDEFxx").await.unwrap();
+        store.remember(&hash_chunk(b"def"), &other_path, 2, 3).await;
+
+        let chunks = chunks_of(b"abcdefg", &[3, 3, 1]);
+        let upload = ChunkedUpload::new(7, &chunks).unwrap();
+        let mut local_file =
+            LocalFile::open(&path, true, true, true).await.unwrap();
+        let have_chunks = store
+            .place_known_chunks(&mut local_file, &upload)
+            .await
+            .unwrap();
+
+        assert!(have_chunks.is_empty(), "Unexpected: {:?}", have_chunks);
+        assert_eq!(fs::metadata(&path).await.unwrap().len(), 7);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn place_known_chunks_should_not_copy_chunks_outside_allowed_paths() {
+        let store = ChunkStore::default();
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let other_path = outside.as_ref().join("other");
+        fs::write(&other_path, b"xxdefxx").await.unwrap();
+        store.remember(&hash_chunk(b"def"), &other_path, 2, 3).await;
+
+        let mut fsm = super::super::FileSystemManager::new();
+        fsm.set_allowed_paths(vec![root.as_ref().to_path_buf()]);
+        let handle = fsm
+            .open_file(root.as_ref().join("file"), true, true, true)
+            .await
+            .unwrap();
+        let file = fsm.get(handle).unwrap();
+
+        let chunks = chunks_of(b"abcdefg", &[3, 3, 1]);
+        let upload = ChunkedUpload::new(7, &chunks).unwrap();
+        let have_chunks = store
+            .place_known_chunks(&mut *file.lock().await, &upload)
+            .await
+            .unwrap();
+
+        assert!(have_chunks.is_empty(), "Unexpected: {:?}", have_chunks);
+        assert_eq!(
+            fs::read(root.as_ref().join("file")).await.unwrap(),
+            vec![0; 7]
+        );
+    }
+}
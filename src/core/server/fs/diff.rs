@@ -0,0 +1,288 @@
+/// Maximum number of edits explored when searching for the shortest diff,
+/// beyond which the differing lines are reported as entirely replaced
+/// rather than spending quadratic time and memory on a minimal diff
+pub const MAX_EDIT_DISTANCE: usize = 1000;
+
+/// Operation applied to a single line to turn one text into another
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Unified diff between two texts
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnifiedDiff {
+    /// Diff in the unified format, empty if the texts are identical
+    pub text: String,
+
+    /// Total lines only found in the second text
+    pub lines_added: u64,
+
+    /// Total lines only found in the first text
+    pub lines_removed: u64,
+}
+
+/// Computes the unified diff turning text `a` into text `b`, labeling them
+/// `label_a` and `label_b` and including up to `context` unchanged lines
+/// around each change
+pub fn unified_diff(
+    a: &str,
+    b: &str,
+    label_a: &str,
+    label_b: &str,
+    context: usize,
+) -> UnifiedDiff {
+    let a: Vec<&str> = a.split_inclusive('\n').collect();
+    let b: Vec<&str> = b.split_inclusive('\n').collect();
+    let ops = diff_lines(&a, &b);
+
+    // Pair each operation with the position in each text it applies at
+    let mut entries = Vec::with_capacity(ops.len());
+    let (mut i, mut j) = (0, 0);
+    for op in ops {
+        entries.push((op, i, j));
+        match op {
+            Op::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+
+    // Group changes whose context would overlap into the same hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (k, (op, _, _)) in entries.iter().enumerate() {
+        if *op == Op::Equal {
+            continue;
+        }
+
+        match hunks.last_mut() {
+            Some((_, last)) if k <= *last + 2 * context + 1 => *last = k,
+            _ => hunks.push((k, k)),
+        }
+    }
+
+    let mut diff = UnifiedDiff::default();
+    if hunks.is_empty() {
+        return diff;
+    }
+
+    diff.text
+        .push_str(&format!("--- {}\n+++ {}\n", label_a, label_b));
+    for (first, last) in hunks {
+        let start = first.saturating_sub(context);
+        let end = (last + context).min(entries.len() - 1);
+        let hunk = &entries[start..=end];
+
+        let count_a =
+            hunk.iter().filter(|(op, _, _)| *op != Op::Insert).count();
+        let count_b =
+            hunk.iter().filter(|(op, _, _)| *op != Op::Delete).count();
+        let (_, i, j) = hunk[0];
+        diff.text.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(i, count_a),
+            hunk_range(j, count_b)
+        ));
+
+        for (op, i, j) in hunk {
+            let (prefix, line) = match op {
+                Op::Equal => (' ', a[*i]),
+                Op::Delete => {
+                    diff.lines_removed += 1;
+                    ('-', a[*i])
+                }
+                Op::Insert => {
+                    diff.lines_added += 1;
+                    ('+', b[*j])
+                }
+            };
+
+            diff.text.push(prefix);
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    diff.text.push_str(line);
+                    diff.text.push('\n');
+                }
+                None => {
+                    diff.text.push_str(line);
+                    diff.text.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+
+    diff
+}
+
+/// Formats the range of a hunk within one text, where an empty range refers
+/// to the line before it
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Finds the operations turning lines `a` into lines `b`, skipping their
+/// common prefix and suffix before searching for the shortest edit
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut ops = vec![Op::Equal; prefix];
+    let (a_mid, b_mid) =
+        (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    match shortest_edit(a_mid, b_mid) {
+        Some(mid) => ops.extend(mid),
+        None => {
+            ops.resize(ops.len() + a_mid.len(), Op::Delete);
+            ops.resize(ops.len() + b_mid.len(), Op::Insert);
+        }
+    }
+    ops.resize(ops.len() + suffix, Op::Equal);
+    ops
+}
+
+/// Finds the shortest sequence of operations turning `a` into `b` using
+/// Myers' algorithm, yielding none if more than `MAX_EDIT_DISTANCE` edits
+/// would be needed
+fn shortest_edit(a: &[&str], b: &[&str]) -> Option<Vec<Op>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m).min(MAX_EDIT_DISTANCE as isize);
+    let offset = max + 1;
+    let index = |k: isize| (k + offset) as usize;
+
+    // Whether diagonal k is reached in round d by an insertion from diagonal
+    // k + 1 rather than a deletion from diagonal k - 1
+    let is_insertion = |v: &[isize], k: isize, d: isize| {
+        k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)])
+    };
+
+    // Furthest reaching x on each diagonal k (x - y), recorded before each
+    // round so that the path can be traced back afterwards
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    let mut done = false;
+
+    for d in 0..=max {
+        trace.push(v.clone());
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if is_insertion(&v, k, d) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                done = true;
+                break;
+            }
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    if !done {
+        return None;
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if is_insertion(v, k, d) { k + 1 } else { k - 1 };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_should_be_empty_if_texts_are_identical() {
+        let diff = unified_diff("a\nb\n", "a\nb\n", "x", "y", 3);
+        assert_eq!(diff, UnifiedDiff::default());
+    }
+
+    #[test]
+    fn unified_diff_should_include_context_around_changes() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let b = "1\n2\n3\nfour\n5\n6\n7\n8\n9\nten\n";
+        let diff = unified_diff(a, b, "a.txt", "b.txt", 1);
+        assert_eq!(
+            diff.text,
+            "--- a.txt\n+++ b.txt\n\
+             @@ -3,3 +3,3 @@\n 3\n-4\n+four\n 5\n\
+             @@ -9 +9,2 @@\n 9\n+ten\n"
+        );
+        assert_eq!(diff.lines_added, 2);
+        assert_eq!(diff.lines_removed, 1);
+    }
+
+    #[test]
+    fn unified_diff_should_merge_changes_with_overlapping_context() {
+        let diff = unified_diff("a\nb\nc\nd\n", "A\nb\nc\nD\n", "x", "y", 1);
+        assert_eq!(
+            diff.text,
+            "--- x\n+++ y\n@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_should_mark_missing_newline_at_end_of_file() {
+        let diff = unified_diff("a\nb", "a\nb\n", "x", "y", 3);
+        assert_eq!(
+            diff.text,
+            "--- x\n+++ y\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_should_support_empty_texts() {
+        let diff = unified_diff("", "a\n", "x", "y", 3);
+        assert_eq!(diff.text, "--- x\n+++ y\n@@ -0,0 +1 @@\n+a\n");
+
+        let diff = unified_diff("a\n", "", "x", "y", 3);
+        assert_eq!(diff.text, "--- x\n+++ y\n@@ -1 +0,0 @@\n-a\n");
+    }
+}
@@ -1,3 +1,4 @@
+use super::allowed::{AllowedPaths, EntryKind};
 use std::io;
 use std::path::{Path, PathBuf};
 use tokio::fs;
@@ -16,51 +17,52 @@ impl LocalDirEntry {
     }
 }
 
-pub async fn entries(path: impl AsRef<Path>) -> io::Result<Vec<LocalDirEntry>> {
-    let mut entries = Vec::new();
-    let mut dir_stream = fs::read_dir(path).await?;
-    while let Some(entry) = dir_stream.next_entry().await? {
+impl LocalDirEntry {
+    /// Converts an entry from reading a directory, looking up its file type
+    pub async fn from_dir_entry(entry: &fs::DirEntry) -> io::Result<Self> {
         let file_type = entry.file_type().await?;
-        entries.push(LocalDirEntry {
+        Ok(Self {
             path: entry.path(),
             is_file: file_type.is_file(),
             is_dir: file_type.is_dir(),
             is_symlink: file_type.is_symlink(),
-        });
+        })
     }
-    Ok(entries)
+}
+
+pub async fn entries(
+    paths: &AllowedPaths,
+    path: impl AsRef<Path>,
+) -> io::Result<Vec<LocalDirEntry>> {
+    paths.entries(path).await
 }
 
 pub async fn rename(
+    paths: &AllowedPaths,
     from: impl AsRef<Path>,
     to: impl AsRef<Path>,
 ) -> io::Result<()> {
-    let metadata = fs::metadata(from.as_ref()).await?;
-
-    if metadata.is_dir() {
-        fs::rename(from, to).await
-    } else {
-        Err(io::Error::new(io::ErrorKind::Other, "Not a directory"))
+    match paths.kind(from.as_ref()).await? {
+        EntryKind::Dir => paths.rename(from, to).await,
+        _ => Err(io::Error::other("Not a directory")),
     }
 }
 
 pub async fn create(
+    paths: &AllowedPaths,
     path: impl AsRef<Path>,
     create_components: bool,
+    mode: Option<u32>,
 ) -> io::Result<()> {
-    if create_components {
-        fs::create_dir_all(path).await
-    } else {
-        fs::create_dir(path).await
-    }
+    paths.create_dir(path, create_components, mode).await
 }
 
-pub async fn remove(path: impl AsRef<Path>, non_empty: bool) -> io::Result<()> {
-    if non_empty {
-        fs::remove_dir_all(path).await
-    } else {
-        fs::remove_dir(path).await
-    }
+pub async fn remove(
+    paths: &AllowedPaths,
+    path: impl AsRef<Path>,
+    non_empty: bool,
+) -> io::Result<()> {
+    paths.remove_dir(path, non_empty).await
 }
 
 #[cfg(test)]
@@ -71,11 +73,11 @@ mod tests {
     async fn entries_should_yield_error_if_not_a_directory() {
         let result = {
             let file = tempfile::NamedTempFile::new().unwrap();
-            entries(file.as_ref()).await
+            entries(&AllowedPaths::default(), file.as_ref()).await
         };
 
         match result {
-            Err(x) if x.kind() == io::ErrorKind::Other => (),
+            Err(x) if x.raw_os_error() == Some(libc::ENOTDIR) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
@@ -93,7 +95,7 @@ mod tests {
                 .await
                 .expect("Failed to create dir");
 
-            let result = entries(dir.as_ref()).await;
+            let result = entries(&AllowedPaths::default(), dir.as_ref()).await;
 
             (dir.into_path(), result)
         };
@@ -134,7 +136,7 @@ mod tests {
             let to_dir = tempfile::tempdir().unwrap();
             let to = to_dir.as_ref();
 
-            rename(from, to).await
+            rename(&AllowedPaths::default(), from, to).await
         };
 
         match result {
@@ -151,7 +153,7 @@ mod tests {
             let to_dir = tempfile::tempdir().unwrap();
             let to = to_dir.as_ref();
 
-            rename(from, to).await
+            rename(&AllowedPaths::default(), from, to).await
         };
 
         match result {
@@ -165,7 +167,13 @@ mod tests {
         let result = {
             let parent_dir = tempfile::tempdir().unwrap();
 
-            create(parent_dir.as_ref().join("test-dir"), false).await
+            create(
+                &AllowedPaths::default(),
+                parent_dir.as_ref().join("test-dir"),
+                false,
+                None,
+            )
+            .await
         };
 
         assert!(result.is_ok(), "Failed unexpectedly: {:?}", result);
@@ -173,7 +181,13 @@ mod tests {
         let result = {
             let parent_dir = tempfile::tempdir().unwrap();
 
-            create(parent_dir.as_ref().join("test-dir"), true).await
+            create(
+                &AllowedPaths::default(),
+                parent_dir.as_ref().join("test-dir"),
+                true,
+                None,
+            )
+            .await
         };
 
         assert!(result.is_ok(), "Failed unexpectedly: {:?}", result);
@@ -191,7 +205,7 @@ mod tests {
                     .as_path(),
             );
 
-            create(new_dir, false).await
+            create(&AllowedPaths::default(), new_dir, false, None).await
         };
 
         assert!(result.is_err(), "Unexpectedly succeeded: {:?}", result);
@@ -202,24 +216,34 @@ mod tests {
     ) {
         let parent_dir = tempfile::tempdir().unwrap();
 
-        create(parent_dir.as_ref().join("test-dir"), false)
-            .await
-            .expect("Failed to create directory");
-
-        create(parent_dir.as_ref().join("test-dir"), true)
-            .await
-            .expect("Failed to create directory");
+        create(
+            &AllowedPaths::default(),
+            parent_dir.as_ref().join("test-dir"),
+            false,
+            None,
+        )
+        .await
+        .expect("Failed to create directory");
+
+        create(
+            &AllowedPaths::default(),
+            parent_dir.as_ref().join("test-dir"),
+            true,
+            None,
+        )
+        .await
+        .expect("Failed to create directory");
     }
 
     #[tokio::test]
     async fn remove_should_yield_error_if_not_a_directory() {
         let result = {
             let file = tempfile::NamedTempFile::new().unwrap();
-            remove(file.as_ref(), false).await
+            remove(&AllowedPaths::default(), file.as_ref(), false).await
         };
 
         match result {
-            Err(x) if x.kind() == io::ErrorKind::Other => (),
+            Err(x) if x.raw_os_error() == Some(libc::ENOTDIR) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
@@ -229,7 +253,7 @@ mod tests {
         // Remove an empty directory with non-empty flag not set
         let result = {
             let dir = tempfile::tempdir().unwrap();
-            remove(dir.as_ref(), false).await
+            remove(&AllowedPaths::default(), dir.as_ref(), false).await
         };
 
         match result {
@@ -240,7 +264,7 @@ mod tests {
         // Remove an empty directory with non-empty flag set
         let result = {
             let dir = tempfile::tempdir().unwrap();
-            remove(dir.as_ref(), true).await
+            remove(&AllowedPaths::default(), dir.as_ref(), true).await
         };
 
         match result {
@@ -259,11 +283,11 @@ mod tests {
                 .await
                 .expect("Failed to create file");
 
-            remove(dir.as_ref(), false).await
+            remove(&AllowedPaths::default(), dir.as_ref(), false).await
         };
 
         match result {
-            Err(x) if x.kind() == io::ErrorKind::Other => (),
+            Err(x) if x.raw_os_error() == Some(libc::ENOTEMPTY) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
@@ -278,7 +302,7 @@ mod tests {
                 .await
                 .expect("Failed to create file");
 
-            remove(dir.as_ref(), true).await
+            remove(&AllowedPaths::default(), dir.as_ref(), true).await
         };
 
         match result {
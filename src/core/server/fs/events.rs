@@ -0,0 +1,198 @@
+use crate::core::{
+    reply::{FsEventArgs, FsEventOp, FsEventsArgs},
+    server::job::now_millis,
+    RemotePath,
+};
+use std::collections::VecDeque;
+use tokio::sync::Mutex;
+
+/// Default number of changes to the filesystem kept by the server
+pub const DEFAULT_FS_EVENT_HISTORY: usize = 1000;
+
+/// Change made to the filesystem, before it is recorded as an event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FsChange {
+    pub op: FsEventOp,
+    pub path: RemotePath,
+
+    /// New path of a renamed file or directory
+    pub to: Option<RemotePath>,
+}
+
+impl FsChange {
+    pub fn new(op: FsEventOp, path: impl Into<RemotePath>) -> Self {
+        Self {
+            op,
+            path: path.into(),
+            to: None,
+        }
+    }
+
+    pub fn renamed(
+        from: impl Into<RemotePath>,
+        to: impl Into<RemotePath>,
+    ) -> Self {
+        Self {
+            op: FsEventOp::Renamed,
+            path: from.into(),
+            to: Some(to.into()),
+        }
+    }
+}
+
+/// Bounded history of the most recent changes the server made to the
+/// filesystem, letting clients that reconnect after a gap catch up on what
+/// changed without hashing entire trees
+#[derive(Debug)]
+pub struct FsEventHistory {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    events: VecDeque<FsEventArgs>,
+    next_id: u64,
+}
+
+impl Default for FsEventHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_FS_EVENT_HISTORY)
+    }
+}
+
+impl FsEventHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner {
+                events: VecDeque::new(),
+                next_id: 1,
+            }),
+        }
+    }
+
+    /// Maximum number of events kept, beyond which the oldest are dropped
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Records `change` as made on behalf of `identity`, dropping the
+    /// oldest event once the history is full
+    pub async fn record(&self, change: FsChange, identity: &str) {
+        let mut inner = self.inner.lock().await;
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        while inner.events.len() >= self.capacity {
+            inner.events.pop_front();
+        }
+
+        inner.events.push_back(FsEventArgs {
+            id,
+            op: change.op,
+            path: change.path,
+            to: change.to,
+            timestamp_millis: now_millis(),
+            identity: identity.to_string(),
+        });
+    }
+
+    /// Events recorded after the event with id `since`, reporting whether
+    /// any of them are no longer kept
+    pub async fn since(&self, since: u64) -> FsEventsArgs {
+        let inner = self.inner.lock().await;
+        let latest_id = inner.next_id - 1;
+        let oldest_id = inner
+            .events
+            .front()
+            .map(|event| event.id)
+            .unwrap_or(inner.next_id);
+
+        FsEventsArgs {
+            events: inner
+                .events
+                .iter()
+                .filter(|event| event.id > since)
+                .cloned()
+                .collect(),
+            latest_id,
+
+            // NOTE: An id newer than any recorded means that the ids were
+            //       handed out by an earlier run of the server
+            missed: since > latest_id || since.saturating_add(1) < oldest_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn record(history: &FsEventHistory, path: &str) {
+        history
+            .record(FsChange::new(FsEventOp::Modified, path), "a")
+            .await
+    }
+
+    fn paths(events: &FsEventsArgs) -> Vec<&str> {
+        events
+            .events
+            .iter()
+            .filter_map(|e| e.path.to_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn since_should_return_events_after_the_one_seen() {
+        let history = FsEventHistory::new(10);
+        let events = history.since(0).await;
+        assert!(events.events.is_empty());
+        assert_eq!(events.latest_id, 0);
+        assert!(!events.missed);
+
+        record(&history, "x").await;
+        record(&history, "y").await;
+        record(&history, "z").await;
+
+        let events = history.since(0).await;
+        assert_eq!(paths(&events), vec!["x", "y", "z"]);
+        assert_eq!(events.latest_id, 3);
+        assert!(!events.missed);
+
+        let events = history.since(2).await;
+        assert_eq!(paths(&events), vec!["z"]);
+        assert_eq!(events.events[0].identity, "a");
+        assert!(!events.missed);
+
+        let events = history.since(3).await;
+        assert!(events.events.is_empty());
+        assert!(!events.missed);
+    }
+
+    #[tokio::test]
+    async fn since_should_report_events_dropped_from_history_as_missed() {
+        let history = FsEventHistory::new(2);
+        record(&history, "x").await;
+        record(&history, "y").await;
+        record(&history, "z").await;
+
+        // Event 1 was dropped, so only a client that saw it is caught up
+        let events = history.since(0).await;
+        assert_eq!(paths(&events), vec!["y", "z"]);
+        assert!(events.missed);
+
+        let events = history.since(1).await;
+        assert_eq!(paths(&events), vec!["y", "z"]);
+        assert!(!events.missed);
+
+        // Ids beyond the latest came from an earlier run of the server
+        let events = history.since(10).await;
+        assert!(events.events.is_empty());
+        assert!(events.missed);
+    }
+}
@@ -1,7 +1,14 @@
+use super::allowed::{AllowedPaths, EntryKind};
+#[cfg(unix)]
+use super::secure::{SecureOpenOptions, SecureRoot};
 use derive_more::{Display, Error};
 use rand::{rngs::OsRng, RngCore};
 use std::io::{self, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex, RwLock,
+};
 use tokio::{
     fs::{self, File, OpenOptions},
     io::{AsyncReadExt, AsyncWriteExt},
@@ -23,9 +30,9 @@ pub struct LocalFileHandle {
 }
 
 /// Converts handle into its id
-impl Into<u32> for LocalFileHandle {
-    fn into(self) -> u32 {
-        self.id
+impl From<LocalFileHandle> for u32 {
+    fn from(handle: LocalFileHandle) -> Self {
+        handle.id
     }
 }
 
@@ -35,26 +42,109 @@ pub struct LocalFilePermissions {
     pub read: bool,
 }
 
-#[derive(Debug)]
-pub struct LocalFile {
-    /// Represents a unique id with which to lookup the file
-    pub(super) id: u32,
+/// Modes that a file was opened with beyond its permissions
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LocalFileModes {
+    /// Whether writes are added to the end of the file
+    pub append: bool,
+
+    /// Whether existing contents were discarded when opening the file
+    pub truncate: bool,
 
+    /// Whether opening failed if the file already existed
+    pub create_new: bool,
+}
+
+/// Parts of an open file that change as it is operated on, shared with the
+/// manager of the file so that they can be looked up without waiting on an
+/// operation in progress on the file
+#[derive(Debug)]
+pub struct LocalFileState {
     /// Represents a unique signature that acts as a barrier to prevent
     /// unexpected operations on the file from a client with an outdated
     /// understanding of the file
-    pub(super) sig: u32,
+    sig: AtomicU32,
+
+    /// Represents the absolute path to the file; any movement
+    /// of the file will result in changing the path
+    path: RwLock<PathBuf>,
+
+    /// Represents the permissions that the file is open with
+    permissions: RwLock<LocalFilePermissions>,
+
+    /// Represents a file descriptor that replaces the current one of the file
+    /// before its next operation
+    reopened: Mutex<Option<File>>,
+}
+
+impl LocalFileState {
+    fn new(sig: u32, path: PathBuf, permissions: LocalFilePermissions) -> Self {
+        Self {
+            sig: AtomicU32::new(sig),
+            path: RwLock::new(path),
+            permissions: RwLock::new(permissions),
+            reopened: Mutex::new(None),
+        }
+    }
+
+    pub fn sig(&self) -> u32 {
+        self.sig.load(Ordering::SeqCst)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path.read().unwrap().clone()
+    }
+
+    pub fn permissions(&self) -> LocalFilePermissions {
+        *self.permissions.read().unwrap()
+    }
+
+    /// Has the file replace its descriptor with that of `other` before its
+    /// next operation, taking on the permissions of `other` and, if
+    /// `truncate` as its contents were discarded, changing its signature
+    ///
+    /// Any operation already in progress on the file finishes using the
+    /// current descriptor.
+    pub fn reopen(&self, other: LocalFile, truncate: bool) {
+        *self.permissions.write().unwrap() = other.permissions();
+        *self.reopened.lock().unwrap() = Some(other.file);
+        if truncate {
+            self.sig.store(OsRng.next_u32(), Ordering::SeqCst);
+        }
+    }
+
+    /// Replaces the signature of the file if it is still `sig`, which leaves
+    /// any signature assigned by reopening the file in the meantime in place
+    fn rotate_sig(&self, sig: u32) -> u32 {
+        let new_sig = OsRng.next_u32();
+        let _ = self.sig.compare_exchange(
+            sig,
+            new_sig,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+        self.sig()
+    }
+}
+
+#[derive(Debug)]
+pub struct LocalFile {
+    /// Represents a unique id with which to lookup the file
+    pub(super) id: u32,
 
     /// Represents an underlying file descriptor with which we can read,
     /// write, and perform other operations
     file: File,
 
-    /// Represents the permissions associated with the file when it was opened
-    permissions: LocalFilePermissions,
+    /// Represents the modes that the file was opened with
+    modes: LocalFileModes,
 
-    /// Represents the absolute path to the file; any movement
-    /// of the file will result in changing the path
-    path: PathBuf,
+    /// Represents the signature, path, and permissions of the file
+    state: Arc<LocalFileState>,
+
+    /// Represents the dirs that the file is confined to when it is renamed
+    /// or removed by its path
+    pub(super) allowed_paths: AllowedPaths,
 }
 
 impl LocalFile {
@@ -68,10 +158,14 @@ impl LocalFile {
 
         Self {
             id,
-            sig,
             file,
-            permissions,
-            path: path.as_ref().to_path_buf(),
+            modes: LocalFileModes::default(),
+            state: Arc::new(LocalFileState::new(
+                sig,
+                path.as_ref().to_path_buf(),
+                permissions,
+            )),
+            allowed_paths: AllowedPaths::default(),
         }
     }
 
@@ -88,44 +182,117 @@ impl LocalFile {
         create: bool,
         write: bool,
         read: bool,
+    ) -> io::Result<Self> {
+        Self::open_with_modes(path, create, write, read, Default::default())
+            .await
+    }
+
+    /// Opens up a file at `path` like `open`, additionally applying `modes`
+    pub async fn open_with_modes(
+        path: impl AsRef<Path>,
+        create: bool,
+        write: bool,
+        read: bool,
+        modes: LocalFileModes,
     ) -> io::Result<Self> {
         match OpenOptions::new()
             .create(create)
             .write(write)
             .read(read)
+            .append(modes.append)
+            .truncate(modes.truncate)
+            .create_new(modes.create_new)
             .open(&path)
             .await
         {
             Ok(file) => {
                 let cpath = fs::canonicalize(path).await?;
                 let permissions = LocalFilePermissions { write, read };
-                Ok(Self::new(file, permissions, cpath))
+                let mut local_file = Self::new(file, permissions, cpath);
+                local_file.modes = modes;
+                Ok(local_file)
             }
             Err(x) => Err(x),
         }
     }
 
+    /// Opens up a file at `path` like `open_with_modes`, but only within
+    /// `root` and without following any symlink on the way to the file
+    #[cfg(unix)]
+    pub fn open_within(
+        root: &SecureRoot,
+        path: impl AsRef<Path>,
+        create: bool,
+        write: bool,
+        read: bool,
+        modes: LocalFileModes,
+    ) -> io::Result<Self> {
+        let options = SecureOpenOptions {
+            read,
+            write,
+            append: modes.append,
+            truncate: modes.truncate,
+            create,
+            create_new: modes.create_new,
+            mode: 0o666,
+        };
+        let file = root.open_file(path.as_ref(), options, false)?;
+        let path = root.confine(path)?;
+        let permissions = LocalFilePermissions { write, read };
+        let mut local_file = Self::new(File::from_std(file), permissions, path);
+        local_file.modes = modes;
+        Ok(local_file)
+    }
+
     pub fn id(&self) -> u32 {
         self.id
     }
 
     pub fn sig(&self) -> u32 {
-        self.sig
+        self.state.sig()
     }
 
     pub fn handle(&self) -> LocalFileHandle {
         LocalFileHandle {
             id: self.id,
-            sig: self.sig,
+            sig: self.sig(),
         }
     }
 
     pub fn permissions(&self) -> LocalFilePermissions {
-        self.permissions
+        self.state.permissions()
+    }
+
+    pub fn modes(&self) -> LocalFileModes {
+        self.modes
+    }
+
+    pub fn allowed_paths(&self) -> AllowedPaths {
+        self.allowed_paths.clone()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.state.path()
     }
 
-    pub fn path(&self) -> &Path {
-        self.path.as_path()
+    /// Shares the signature, path, and permissions of the file, which stay
+    /// current as the file is operated on
+    pub fn state(&self) -> Arc<LocalFileState> {
+        Arc::clone(&self.state)
+    }
+
+    /// Checks that `sig` is the signature of the file before an operation,
+    /// first swapping in any descriptor left by reopening the file
+    fn begin(&mut self, sig: u32) -> Result<()> {
+        if self.sig() != sig {
+            return Err(LocalFileError::SigMismatch);
+        }
+
+        if let Some(file) = self.state.reopened.lock().unwrap().take() {
+            self.file = file;
+        }
+
+        Ok(())
     }
 
     /// Renames a file (if possible) using its underlying path as the origin
@@ -134,20 +301,17 @@ impl LocalFile {
         sig: u32,
         to: impl AsRef<Path>,
     ) -> Result<u32> {
-        if self.sig != sig {
-            return Err(LocalFileError::SigMismatch);
-        }
+        self.begin(sig)?;
 
-        rename(self.path.as_path(), to.as_ref())
+        rename(&self.allowed_paths, self.path(), to.as_ref())
             .await
             .map_err(LocalFileError::IoError)?;
 
         // Update signature to reflect the change and update our internal
         // path so that we can continue to do renames/removals properly
-        self.sig = OsRng.next_u32();
-        self.path = to.as_ref().to_path_buf();
+        *self.state.path.write().unwrap() = to.as_ref().to_path_buf();
 
-        Ok(self.sig)
+        Ok(self.state.rotate_sig(sig))
     }
 
     /// Removes the file (if possible) using its underlying path
@@ -155,25 +319,21 @@ impl LocalFile {
     /// NOTE: If successful, this makes the local file reference no longer
     ///       usable for the majority of its functionality
     pub async fn remove(&mut self, sig: u32) -> Result<()> {
-        if self.sig != sig {
-            return Err(LocalFileError::SigMismatch);
-        }
+        self.begin(sig)?;
 
-        remove(self.path.as_path())
+        remove(&self.allowed_paths, self.path())
             .await
             .map_err(LocalFileError::IoError)?;
 
         // Update signature to reflect the change
-        self.sig = OsRng.next_u32();
+        self.state.rotate_sig(sig);
 
         Ok(())
     }
 
     /// Reads all contents of file from beginning to end
     pub async fn read_all(&mut self, sig: u32) -> Result<Vec<u8>> {
-        if self.sig != sig {
-            return Err(LocalFileError::SigMismatch);
-        }
+        self.begin(sig)?;
 
         let mut buf = Vec::new();
 
@@ -190,25 +350,88 @@ impl LocalFile {
         Ok(buf)
     }
 
-    /// Overwrites contents of file with provided contents
+    /// Reads up to `len` bytes of the file starting at `offset`, or every
+    /// byte after `offset` if no length is provided, alongside the total size
+    /// of the file
+    pub async fn read_range(
+        &mut self,
+        sig: u32,
+        offset: u64,
+        len: Option<u64>,
+    ) -> Result<(Vec<u8>, u64)> {
+        self.begin(sig)?;
+
+        let size = self
+            .file
+            .metadata()
+            .await
+            .map_err(LocalFileError::IoError)?
+            .len();
+        let mut buf = Vec::new();
+
+        self.file
+            .seek(SeekFrom::Start(offset))
+            .await
+            .map_err(LocalFileError::IoError)?;
+
+        match len {
+            Some(len) => (&mut self.file).take(len).read_to_end(&mut buf).await,
+            None => self.file.read_to_end(&mut buf).await,
+        }
+        .map_err(LocalFileError::IoError)?;
+
+        Ok((buf, size))
+    }
+
+    /// Overwrites contents of file with provided contents, or adds them to
+    /// the end of the file if it was opened to append
     pub async fn write_all(&mut self, sig: u32, buf: &[u8]) -> Result<()> {
-        if self.sig != sig {
-            return Err(LocalFileError::SigMismatch);
+        self.begin(sig)?;
+
+        // Writes to a file opened to append always land at its end, so its
+        // existing contents are kept
+        if !self.modes.append {
+            self.file
+                .seek(SeekFrom::Start(0))
+                .await
+                .map_err(LocalFileError::IoError)?;
+
+            self.file
+                .set_len(0)
+                .await
+                .map_err(LocalFileError::IoError)?;
         }
 
+        // Update our sig after we first touch the file so we guarantee
+        // that any modification (even partial) is reflected as a change
+        self.state.rotate_sig(sig);
+
         self.file
-            .seek(SeekFrom::Start(0))
+            .write_all(buf)
             .await
             .map_err(LocalFileError::IoError)?;
 
+        self.file.flush().await.map_err(LocalFileError::IoError)
+    }
+
+    /// Overwrites the contents of the file starting at `offset` with the
+    /// provided contents, leaving the rest of the file as it is
+    pub async fn write_at(
+        &mut self,
+        sig: u32,
+        offset: u64,
+        buf: &[u8],
+    ) -> Result<()> {
+        self.begin(sig)?;
+
         self.file
-            .set_len(0)
+            .seek(SeekFrom::Start(offset))
             .await
             .map_err(LocalFileError::IoError)?;
 
         // Update our sig after we first touch the file so we guarantee
         // that any modification (even partial) is reflected as a change
-        self.sig = OsRng.next_u32();
+        self.state.rotate_sig(sig);
 
         self.file
             .write_all(buf)
@@ -217,23 +440,39 @@ impl LocalFile {
 
         self.file.flush().await.map_err(LocalFileError::IoError)
     }
+
+    /// Truncates or extends the file to exactly `len` bytes, where any bytes
+    /// added are zeros
+    pub async fn set_len(&mut self, sig: u32, len: u64) -> Result<()> {
+        self.begin(sig)?;
+
+        // Update our sig before touching the file so we guarantee that any
+        // modification (even partial) is reflected as a change
+        self.state.rotate_sig(sig);
+
+        self.file
+            .set_len(len)
+            .await
+            .map_err(LocalFileError::IoError)
+    }
 }
 
 pub async fn rename(
+    paths: &AllowedPaths,
     from: impl AsRef<Path>,
     to: impl AsRef<Path>,
 ) -> io::Result<()> {
-    let metadata = fs::metadata(from.as_ref()).await?;
-
-    if metadata.is_file() {
-        fs::rename(from.as_ref(), to.as_ref()).await
-    } else {
-        Err(io::Error::new(io::ErrorKind::Other, "Not a file"))
+    match paths.kind(from.as_ref()).await? {
+        EntryKind::File => paths.rename(from, to).await,
+        _ => Err(io::Error::other("Not a file")),
     }
 }
 
-pub async fn remove(path: impl AsRef<Path>) -> io::Result<()> {
-    fs::remove_file(path.as_ref()).await
+pub async fn remove(
+    paths: &AllowedPaths,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    paths.remove_file(path).await
 }
 
 #[cfg(test)]
@@ -296,7 +535,7 @@ mod tests {
     async fn sig_should_return_associated_sig() {
         let lf = create_test_local_file(tempfile::tempfile().unwrap(), "");
 
-        assert_eq!(lf.sig, lf.sig());
+        assert_eq!(lf.state.sig(), lf.sig());
     }
 
     #[tokio::test]
@@ -345,7 +584,7 @@ mod tests {
 
         match lf.read_all(sig).await {
             Err(LocalFileError::IoError(x))
-                if x.kind() == io::ErrorKind::Other =>
+                if x.raw_os_error() == Some(libc::EBADF) =>
             {
                 assert_eq!(
                     sig,
@@ -379,6 +618,25 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn read_range_should_return_bytes_within_range_and_total_size() {
+        let mut f = tempfile::tempfile().unwrap();
+        f.write_all(b"some contents").unwrap();
+
+        let mut lf = create_test_local_file(f, "");
+        let sig = lf.sig();
+
+        let (contents, size) = lf.read_range(sig, 5, Some(3)).await.unwrap();
+        assert_eq!(contents, b"con");
+        assert_eq!(size, 13);
+
+        let (contents, _) = lf.read_range(sig, 5, None).await.unwrap();
+        assert_eq!(contents, b"contents");
+
+        let (contents, _) = lf.read_range(sig, 20, Some(3)).await.unwrap();
+        assert!(contents.is_empty(), "Read past end of file: {:?}", contents);
+    }
+
     #[tokio::test]
     async fn read_all_should_return_all_file_content_from_start() {
         let contents = b"some contents";
@@ -481,6 +739,67 @@ mod tests {
         assert_eq!(buf, data);
     }
 
+    #[tokio::test]
+    async fn write_all_should_add_to_end_of_file_if_opened_to_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        std::fs::write(&path, b"abc").unwrap();
+
+        let mut lf = LocalFile::open_with_modes(
+            &path,
+            false,
+            true,
+            false,
+            LocalFileModes {
+                append: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let sig = lf.sig();
+        lf.write_all(sig, b"def").await.unwrap();
+        assert_ne!(sig, lf.sig(), "Sig was not updated after write");
+
+        let sig = lf.sig();
+        lf.write_all(sig, b"ghi").await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdefghi".to_vec());
+    }
+
+    #[tokio::test]
+    async fn write_at_should_overwrite_only_contents_at_offset() {
+        let mut f = tempfile::tempfile().unwrap();
+        let mut buf = Vec::new();
+
+        let mut lf = create_test_local_file(f.try_clone().unwrap(), "");
+        f.write_all(b"abcdefghi").unwrap();
+
+        let sig = lf.sig();
+        lf.write_at(sig, 3, b"DEF").await.unwrap();
+
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_end(&mut buf).unwrap();
+        assert_ne!(sig, lf.sig(), "Sig was not updated after write");
+        assert_eq!(buf, b"abcDEFghi".to_vec());
+    }
+
+    #[tokio::test]
+    async fn open_with_modes_should_fail_if_new_file_exists() {
+        let f = tempfile::NamedTempFile::new().unwrap();
+        let modes = LocalFileModes {
+            create_new: true,
+            ..Default::default()
+        };
+
+        match LocalFile::open_with_modes(f.path(), true, true, false, modes)
+            .await
+        {
+            Err(x) => assert_eq!(x.kind(), io::ErrorKind::AlreadyExists),
+            Ok(_) => panic!("Unexpectedly opened existing file"),
+        }
+    }
+
     #[tokio::test]
     async fn rename_should_yield_error_if_provided_sig_is_different() {
         let mut lf = create_test_local_file(tempfile::tempfile().unwrap(), "");
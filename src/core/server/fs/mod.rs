@@ -1,18 +1,73 @@
+mod allowed;
+pub mod checksum;
+pub mod chunks;
+pub mod diff;
 mod dir;
+pub mod events;
 mod file;
+pub mod mounts;
+mod retry;
+#[cfg(unix)]
+pub mod secure;
+pub mod sniff;
 
+pub use allowed::AllowedPaths;
 pub use dir::LocalDirEntry;
 pub use file::{
-    LocalFile, LocalFileError, LocalFileHandle, LocalFilePermissions,
+    LocalFile, LocalFileError, LocalFileHandle, LocalFileModes,
+    LocalFilePermissions, LocalFileState,
 };
+pub use retry::RetryPolicy;
 
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::HashMap;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Open file shared by the manager and the requests operating on it
+///
+/// Operations on the same file are serialized by its lock and are applied in
+/// the order that they acquire it, while operations on different files can
+/// run concurrently. Requests look up the file while holding the lock of the
+/// manager but release it before locking the file, so a lock on a file must
+/// never be held while waiting on the lock of the manager. The manager itself
+/// never waits on the lock of a file.
+pub type SharedLocalFile = Arc<Mutex<LocalFile>>;
+
+/// File open within a manager, alongside the parts of it that the manager
+/// looks up without locking the file
+#[derive(Clone, Debug)]
+struct OpenFile {
+    file: SharedLocalFile,
+    state: Arc<LocalFileState>,
+    append: bool,
+}
 
-#[derive(Debug)]
+/// Manages the files opened by a server and the directories it changes
+///
+/// Clones share the files that are open, so a manager given to a server is
+/// expected to have none open yet.
+#[derive(Clone, Debug)]
 pub struct FileSystemManager {
-    files: HashMap<u32, LocalFile>,
+    files: HashMap<u32, OpenFile>,
+
+    /// Permission bits applied to files created by the manager, rather than
+    /// relying on the umask of the process
+    default_file_mode: Option<u32>,
+
+    /// Permission bits applied to directories created by the manager, rather
+    /// than relying on the umask of the process
+    default_dir_mode: Option<u32>,
+
+    /// Policy for retrying operations on paths that fail with transient
+    /// errors, such as a file briefly locked by another process
+    retry_policy: RetryPolicy,
+
+    /// Dirs that files must be opened within and dirs changed within, each
+    /// opened as a secure root so that no symlink is followed on the way to
+    /// a path; any path is used if empty
+    allowed_paths: AllowedPaths,
 }
 
 impl Default for FileSystemManager {
@@ -25,17 +80,70 @@ impl FileSystemManager {
     pub fn new() -> Self {
         Self {
             files: HashMap::new(),
+            default_file_mode: None,
+            default_dir_mode: None,
+            retry_policy: RetryPolicy::default(),
+            allowed_paths: AllowedPaths::default(),
         }
     }
 
+    pub fn set_default_file_mode(&mut self, mode: Option<u32>) -> &mut Self {
+        self.default_file_mode = mode;
+        self
+    }
+
+    pub fn set_default_dir_mode(&mut self, mode: Option<u32>) -> &mut Self {
+        self.default_dir_mode = mode;
+        self
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn set_allowed_paths(&mut self, paths: Vec<PathBuf>) -> &mut Self {
+        self.allowed_paths = AllowedPaths::new(paths);
+        self
+    }
+
+    /// Dirs that paths used by the manager are confined to, which other
+    /// operations on paths are expected to be confined to as well
+    pub fn allowed_paths(&self) -> AllowedPaths {
+        self.allowed_paths.clone()
+    }
+
     /// Creates a new directory
     pub async fn create_dir(
         &self,
         path: impl AsRef<Path>,
         create_components: bool,
+    ) -> io::Result<()> {
+        self.create_dir_with_mode(path, create_components, None)
+            .await
+    }
+
+    /// Creates a new directory, applying `mode` (or the default dir mode if
+    /// not provided) to each directory that is created
+    pub async fn create_dir_with_mode(
+        &self,
+        path: impl AsRef<Path>,
+        create_components: bool,
+        mode: Option<u32>,
     ) -> io::Result<()> {
         let path = clean_path(path.as_ref()).await;
-        dir::create(path, create_components).await
+        let mode = mode.or(self.default_dir_mode);
+
+        self.retry_policy
+            .run(|| {
+                dir::create(
+                    &self.allowed_paths,
+                    path.as_path(),
+                    create_components,
+                    mode,
+                )
+            })
+            .await
     }
 
     /// Attempts to rename an entire directory.
@@ -52,7 +160,11 @@ impl FileSystemManager {
         self.check_no_open_files(from.as_path())?;
 
         // No open file is within this directory, so good to attempt to rename
-        dir::rename(from.as_path(), to.as_path()).await?;
+        self.retry_policy
+            .run(|| {
+                dir::rename(&self.allowed_paths, from.as_path(), to.as_path())
+            })
+            .await?;
 
         Ok(())
     }
@@ -69,7 +181,9 @@ impl FileSystemManager {
         self.check_no_open_files(path.as_path())?;
 
         // No open file is within this directory, so good to attempt to remove
-        dir::remove(path, non_empty).await
+        self.retry_policy
+            .run(|| dir::remove(&self.allowed_paths, path.as_path(), non_empty))
+            .await
     }
 
     /// Retrieves all entries within the directory `path`.
@@ -86,7 +200,9 @@ impl FileSystemManager {
     ) -> io::Result<Vec<LocalDirEntry>> {
         let path = clean_path(path.as_ref()).await;
 
-        dir::entries(path).await
+        self.retry_policy
+            .run(|| dir::entries(&self.allowed_paths, path.as_path()))
+            .await
     }
 
     /// Opens a file, creating it if `create` true, using `write` and `read`
@@ -97,88 +213,195 @@ impl FileSystemManager {
     /// access and the request asks for it, the current instance of the file
     /// will be closed and a new instance with the same id will be opened with
     /// the new permissions where existing and requested permissions align.
+    /// Any operation already in progress on the file finishes using the
+    /// current instance.
     pub async fn open_file(
         &mut self,
         path: impl AsRef<Path>,
         create: bool,
         write: bool,
         read: bool,
+    ) -> io::Result<LocalFileHandle> {
+        self.open_file_with_mode(path, create, write, read, None)
+            .await
+    }
+
+    /// Opens a file like `open_file`, applying `mode` (or the default file
+    /// mode if not provided) if the file is created
+    pub async fn open_file_with_mode(
+        &mut self,
+        path: impl AsRef<Path>,
+        create: bool,
+        write: bool,
+        read: bool,
+        mode: Option<u32>,
+    ) -> io::Result<LocalFileHandle> {
+        self.open_file_with_modes(
+            path,
+            create,
+            write,
+            read,
+            LocalFileModes::default(),
+            mode,
+        )
+        .await
+    }
+
+    /// Opens a file like `open_file_with_mode`, additionally applying
+    /// `modes` when opening the file.
+    ///
+    /// An open file is only returned if it was opened with the same append
+    /// mode. Truncating always reopens the file, discarding its contents and
+    /// changing its signature, while creating a new file never returns an
+    /// open file as it must not exist.
+    pub async fn open_file_with_modes(
+        &mut self,
+        path: impl AsRef<Path>,
+        create: bool,
+        write: bool,
+        read: bool,
+        modes: LocalFileModes,
+        mode: Option<u32>,
     ) -> io::Result<LocalFileHandle> {
         let path = clean_path(path.as_ref()).await;
+        let mode = if create {
+            mode.or(self.default_file_mode)
+        } else {
+            None
+        };
+        let existed = mode.is_some() && self.allowed_paths.exists(&path).await;
 
         let mut new_permissions = LocalFilePermissions { read, write };
-        let mut maybe_id_and_sig = None;
 
         // TODO: Perform more optimal lookup by filtering down open files
         //       using a path tree?
-        let search =
-            self.files.values_mut().find(|f| f.path() == path.as_path());
+        let existing = if modes.create_new {
+            None
+        } else {
+            self.find_open_file(path.as_path(), modes.append)
+        };
 
         // If we found a match, check the permissions to see if we can return
         // it or if we need to open a new copy with the proper merged
         // permissions
-        if let Some(file) = search {
-            let id = file.id();
-            let sig = file.sig();
-            let permissions = file.permissions();
+        if let Some((id, state)) = existing.as_ref() {
+            let permissions = state.permissions();
 
             // We already have read permission or are not asking for it and
             // we already have write permission or are not asking for it
-            if (permissions.read || !read) && (permissions.write || !write) {
-                return Ok(file.handle());
+            if !modes.truncate
+                && (permissions.read || !read)
+                && (permissions.write || !write)
+            {
+                return Ok(LocalFileHandle {
+                    id: *id,
+                    sig: state.sig(),
+                });
             } else {
                 // Otherwise, we now need to open a new file pointer with the
                 // proper permissions to support both cases and, if successful,
                 // close the existing file
                 new_permissions.read = permissions.read || read;
                 new_permissions.write = permissions.write || write;
-                maybe_id_and_sig = Some((id, sig));
             }
         }
 
         // Open the file with the specified path
-        let mut new_file = LocalFile::open(
-            path,
-            create,
-            new_permissions.write,
-            new_permissions.read,
-        )
-        .await?;
+        let new_file = self
+            .retry_policy
+            .run(|| {
+                self.open_local_file(
+                    path.as_path(),
+                    create,
+                    new_permissions.write,
+                    new_permissions.read,
+                    modes,
+                )
+            })
+            .await?;
+
+        if let Some(mode) = mode {
+            if !existed {
+                self.allowed_paths.set_mode(new_file.path(), mode).await?;
+            }
+        }
 
-        // If we already had a file open with this path, we want to assign
-        // the previously-used id and sig
-        if let Some((id, sig)) = maybe_id_and_sig {
-            new_file.id = id;
-            new_file.sig = sig;
+        // If we already had a file open with this path, we want to keep its
+        // id and sig (unless its contents were discarded) and have it switch
+        // to the new file pointer before its next operation so that any
+        // request waiting on the existing file uses the new one
+        if let Some((id, state)) = existing {
+            state.reopen(new_file, modes.truncate);
+            return Ok(LocalFileHandle {
+                id,
+                sig: state.sig(),
+            });
         }
 
-        // Insert the file & permissions, overwriting the
-        // existing file/permissions
         let handle = new_file.handle();
-        self.files.insert(new_file.id(), new_file);
+        self.files.insert(
+            new_file.id(),
+            OpenFile {
+                state: new_file.state(),
+                append: new_file.modes().append,
+                file: Arc::new(Mutex::new(new_file)),
+            },
+        );
 
         Ok(handle)
     }
 
-    /// Closes an open file by `handle`.
+    /// Opens the file at the already-cleaned `path`, within one of the
+    /// allowed paths if there are any
+    async fn open_local_file(
+        &self,
+        path: &Path,
+        create: bool,
+        write: bool,
+        read: bool,
+        modes: LocalFileModes,
+    ) -> io::Result<LocalFile> {
+        #[cfg(unix)]
+        {
+            let file = self.allowed_paths.within(path, move |root, path| {
+                LocalFile::open_within(root, path, create, write, read, modes)
+            });
+            if let Some(file) = file.await {
+                let mut file = file?;
+                file.allowed_paths = self.allowed_paths.clone();
+                return Ok(file);
+            }
+        }
+
+        LocalFile::open_with_modes(path, create, write, read, modes).await
+    }
+
+    /// Closes an open file by `handle`, letting any operation already in
+    /// progress on the file finish.
     ///
     /// Will fail if no file with `handle` id is open, or if the signature
     /// on the file is different than that of `handle`.
-    pub fn close_file(
+    pub async fn close_file(
         &mut self,
         handle: LocalFileHandle,
-    ) -> io::Result<LocalFile> {
-        match self.files.entry(handle.id) {
-            Entry::Occupied(x) if x.get().sig == handle.sig => Ok(x.remove()),
-            Entry::Occupied(_) => Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("Signature invalid for file with id {}", handle.id),
-            )),
-            Entry::Vacant(_) => Err(io::Error::new(
+    ) -> io::Result<SharedLocalFile> {
+        let open_file = self.files.get(&handle.id).ok_or_else(|| {
+            io::Error::new(
                 io::ErrorKind::NotFound,
                 format!("No open file with id {}", handle.id),
-            )),
+            )
+        })?;
+
+        if open_file.state.sig() != handle.sig {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Signature invalid for file with id {}", handle.id),
+            ));
         }
+
+        let file = Arc::clone(&open_file.file);
+        self.files.remove(&handle.id);
+        Ok(file)
     }
 
     /// Attempts to rename a file at `from` into `to`.
@@ -194,7 +417,11 @@ impl FileSystemManager {
 
         self.check_no_open_files(from.as_path())?;
 
-        file::rename(from.as_path(), to.as_path()).await
+        self.retry_policy
+            .run(|| {
+                file::rename(&self.allowed_paths, from.as_path(), to.as_path())
+            })
+            .await
     }
 
     /// Attempts to remove a file, failing if the file is currently open.
@@ -206,7 +433,9 @@ impl FileSystemManager {
 
         self.check_no_open_files(path.as_path())?;
 
-        file::remove(path).await
+        self.retry_policy
+            .run(|| file::remove(&self.allowed_paths, path.as_path()))
+            .await
     }
 
     /// Represents the total files that are open within the manager
@@ -214,37 +443,58 @@ impl FileSystemManager {
         self.files.len()
     }
 
-    /// Looks up an open file by its associated `id`
-    pub fn get_mut(&mut self, id: impl Into<u32>) -> Option<&mut LocalFile> {
-        match self.files.get_mut(&id.into()) {
-            Some(file) => Some(file),
-            None => None,
-        }
+    /// Looks up an open file by its associated `id`, which should be locked
+    /// only after releasing the lock on the manager
+    pub fn get(&self, id: impl Into<u32>) -> Option<SharedLocalFile> {
+        self.files.get(&id.into()).map(|x| Arc::clone(&x.file))
     }
 
-    /// Looks up an open file by its associated `id`
-    pub fn get(&self, id: impl Into<u32>) -> Option<&LocalFile> {
-        match self.files.get(&id.into()) {
-            Some(file) => Some(file),
-            None => None,
-        }
+    /// Looks up the current path of the open file with `id`
+    pub fn path_of(&self, id: impl Into<u32>) -> Option<PathBuf> {
+        self.files.get(&id.into()).map(|x| x.state.path())
+    }
+
+    /// Looks up an open file by its `path`
+    pub async fn get_by_path(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Option<SharedLocalFile> {
+        let path = clean_path(path.as_ref()).await;
+        self.files
+            .values()
+            .find(|x| x.state.path() == path)
+            .map(|x| Arc::clone(&x.file))
+    }
+
+    /// Looks up the id and state of a file open at the already-cleaned
+    /// `path` with the `append` mode
+    fn find_open_file(
+        &self,
+        path: &Path,
+        append: bool,
+    ) -> Option<(u32, Arc<LocalFileState>)> {
+        self.files
+            .iter()
+            .find(|(_, x)| x.state.path() == path && x.append == append)
+            .map(|(id, x)| (*id, Arc::clone(&x.state)))
     }
 
     /// Determines if a file is open with the specified `id`
     pub fn exists(&self, id: impl Into<u32>) -> bool {
-        self.get(id).is_some()
+        self.files.contains_key(&id.into())
     }
 
     /// Checks that `path` is not an open file or (if dir) does not contain any
     /// open files managed by the file system manager
     fn check_no_open_files(&self, path: impl AsRef<Path>) -> io::Result<()> {
-        for f in self.files.values() {
-            if f.path().starts_with(path.as_ref()) {
+        for file in self.files.values() {
+            let file_path = file.state.path();
+            if file_path.starts_with(path.as_ref()) {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
                     format!(
                         "File at {:?} is open and must be closed",
-                        f.path()
+                        file_path
                     ),
                 ));
             }
@@ -254,6 +504,59 @@ impl FileSystemManager {
     }
 }
 
+/// Applies the unix permission bits `mode` to the file or directory at
+/// `path`, doing nothing on platforms without unix permissions
+#[cfg(unix)]
+pub async fn set_mode(path: impl AsRef<Path>, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .await
+}
+
+#[cfg(not(unix))]
+pub async fn set_mode(_path: impl AsRef<Path>, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Resolves `path` into an absolute, canonicalized form. Unlike
+/// canonicalization, this supports paths that do not exist by canonicalizing
+/// the nearest existing ancestor and appending the remaining components.
+pub async fn resolve_path(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let path = if path.as_ref().is_absolute() {
+        path.as_ref().to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path.as_ref())
+    };
+
+    let mut remaining = Vec::new();
+    let mut current = path.as_path();
+    loop {
+        match tokio::fs::canonicalize(current).await {
+            Ok(mut resolved) => {
+                for component in remaining.into_iter().rev() {
+                    match component {
+                        Component::ParentDir => {
+                            resolved.pop();
+                        }
+                        Component::CurDir => {}
+                        x => resolved.push(x),
+                    }
+                }
+                return Ok(resolved);
+            }
+            Err(x) => {
+                match (current.parent(), current.components().next_back()) {
+                    (Some(parent), Some(component)) => {
+                        remaining.push(component);
+                        current = parent;
+                    }
+                    _ => return Err(x),
+                }
+            }
+        }
+    }
+}
+
 /// Attempts to canonicalize the path, returning the canonicalized form
 /// or the original form if failed.
 async fn clean_path(path: impl AsRef<Path>) -> PathBuf {
@@ -266,8 +569,16 @@ async fn clean_path(path: impl AsRef<Path>) -> PathBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
     use tokio::fs;
 
+    async fn permissions_of(
+        fsm: &FileSystemManager,
+        handle: LocalFileHandle,
+    ) -> Option<LocalFilePermissions> {
+        Some(fsm.get(handle)?.lock().await.permissions())
+    }
+
     #[tokio::test]
     async fn create_dir_should_yield_error_if_parent_dirs_missing_and_flag_not_set(
     ) {
@@ -383,7 +694,7 @@ mod tests {
         let file = tempfile::NamedTempFile::new_in(root.as_ref()).unwrap();
 
         match fsm.dir_entries(file.path()).await {
-            Err(x) if x.kind() == io::ErrorKind::Other => (),
+            Err(x) if x.raw_os_error() == Some(libc::ENOTDIR) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
@@ -448,7 +759,7 @@ mod tests {
         let _file = tempfile::NamedTempFile::new_in(root.as_ref()).unwrap();
 
         match fsm.remove_dir(root.as_ref(), false).await {
-            Err(x) if x.kind() == io::ErrorKind::Other => (),
+            Err(x) if x.raw_os_error() == Some(libc::ENOTEMPTY) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
@@ -491,7 +802,7 @@ mod tests {
         let not_a_file = tempfile::tempdir_in(root.as_ref()).unwrap();
 
         match fsm.open_file(not_a_file.as_ref(), true, true, true).await {
-            Err(x) if x.kind() == io::ErrorKind::Other => (),
+            Err(x) if x.raw_os_error() == Some(libc::EISDIR) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
@@ -514,7 +825,7 @@ mod tests {
             fsm.file_cnt()
         );
         assert_eq!(
-            fsm.get(handle).map(|f| f.permissions()),
+            permissions_of(&fsm, handle).await,
             Some(LocalFilePermissions {
                 read: true,
                 write: true
@@ -542,7 +853,7 @@ mod tests {
         assert_eq!(handle, handle_2);
 
         assert_eq!(
-            fsm.get(handle_2).map(|f| f.permissions()),
+            permissions_of(&fsm, handle_2).await,
             Some(LocalFilePermissions {
                 read: true,
                 write: true
@@ -565,7 +876,7 @@ mod tests {
         assert_eq!(handle, handle_3);
 
         assert_eq!(
-            fsm.get(handle_3).map(|f| f.permissions()),
+            permissions_of(&fsm, handle_3).await,
             Some(LocalFilePermissions {
                 read: true,
                 write: true
@@ -593,7 +904,7 @@ mod tests {
         );
 
         assert_eq!(
-            fsm.get(handle).map(|f| f.permissions()),
+            permissions_of(&fsm, handle).await,
             Some(LocalFilePermissions {
                 read: false,
                 write: true
@@ -616,7 +927,7 @@ mod tests {
         assert_eq!(handle, handle_2);
 
         assert_eq!(
-            fsm.get(handle_2).map(|f| f.permissions()),
+            permissions_of(&fsm, handle_2).await,
             Some(LocalFilePermissions {
                 read: true,
                 write: true
@@ -650,6 +961,80 @@ mod tests {
         assert_ne!(handle, handle_2, "Two open files have same handle");
     }
 
+    #[tokio::test]
+    async fn open_file_with_modes_should_match_append_mode() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("test-file");
+        let mut fsm = FileSystemManager::new();
+        let append = LocalFileModes {
+            append: true,
+            ..Default::default()
+        };
+        let truncate = LocalFileModes {
+            truncate: true,
+            ..Default::default()
+        };
+
+        let handle = fsm
+            .open_file(&path, true, true, true)
+            .await
+            .expect("Failed to create file");
+        let file = fsm.get(handle).unwrap();
+        let sig = file.lock().await.sig();
+        file.lock().await.write_all(sig, b"abc").await.unwrap();
+        let sig = file.lock().await.sig();
+
+        let append_handle = fsm
+            .open_file_with_modes(&path, false, true, false, append, None)
+            .await
+            .expect("Failed to open file to append");
+        assert_ne!(handle.id, append_handle.id);
+        assert_eq!(fsm.file_cnt(), 2);
+
+        // Truncating reopens the matching open file in place
+        let truncated_handle = fsm
+            .open_file_with_modes(&path, false, true, true, truncate, None)
+            .await
+            .expect("Failed to truncate file");
+        assert_eq!(fsm.file_cnt(), 2);
+        assert_eq!(truncated_handle.id, handle.id);
+        assert_ne!(truncated_handle.sig, sig);
+        assert_eq!(fs::read(&path).await.unwrap(), Vec::<u8>::new());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_file_should_only_open_files_within_allowed_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.as_ref().join("secret"), b"secret")
+            .await
+            .unwrap();
+        std::os::unix::fs::symlink(
+            outside.as_ref(),
+            root.as_ref().join("link"),
+        )
+        .unwrap();
+
+        let mut fsm = FileSystemManager::new();
+        fsm.set_allowed_paths(vec![root.as_ref().to_path_buf()]);
+
+        let handle = fsm
+            .open_file(root.as_ref().join("test-file"), true, true, true)
+            .await
+            .expect("Failed to open file within allowed paths");
+        assert!(fsm.exists(handle));
+
+        for path in &[
+            outside.as_ref().join("secret"),
+            root.as_ref().join("link/secret"),
+        ] {
+            let err =
+                fsm.open_file(path, false, false, true).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+        }
+    }
+
     #[tokio::test]
     async fn close_file_should_yield_error_if_no_file_open_with_id() {
         let root = tempfile::tempdir().unwrap();
@@ -660,10 +1045,13 @@ mod tests {
             .await
             .expect("Failed to create file");
 
-        match fsm.close_file(LocalFileHandle {
-            id: handle.id + 1,
-            sig: handle.sig,
-        }) {
+        match fsm
+            .close_file(LocalFileHandle {
+                id: handle.id + 1,
+                sig: handle.sig,
+            })
+            .await
+        {
             Err(x) if x.kind() == io::ErrorKind::NotFound => (),
             x => panic!("Unexpected result: {:?}", x),
         }
@@ -679,10 +1067,13 @@ mod tests {
             .await
             .expect("Failed to create file");
 
-        match fsm.close_file(LocalFileHandle {
-            id: handle.id,
-            sig: handle.sig + 1,
-        }) {
+        match fsm
+            .close_file(LocalFileHandle {
+                id: handle.id,
+                sig: handle.sig + 1,
+            })
+            .await
+        {
             Err(x) if x.kind() == io::ErrorKind::InvalidInput => (),
             x => panic!("Unexpected result: {:?}", x),
         }
@@ -698,12 +1089,58 @@ mod tests {
             .await
             .expect("Failed to create file");
 
-        match fsm.close_file(handle) {
+        match fsm.close_file(handle).await {
             Ok(_) => (),
             x => panic!("Unexpected result: {:?}", x),
         }
     }
 
+    #[tokio::test]
+    async fn manager_should_not_wait_for_operation_in_progress_on_file() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.as_ref().join("test-file");
+        let other_path = root.as_ref().join("other-file");
+        let mut fsm = FileSystemManager::new();
+
+        let handle = fsm
+            .open_file(path.as_path(), true, true, false)
+            .await
+            .expect("Failed to create file");
+
+        // Hold the file as an operation on it would
+        let file = fsm.get(handle).unwrap();
+        let guard = file.lock().await;
+        let result = tokio::time::timeout(Duration::from_millis(50), async {
+            assert_eq!(
+                fsm.open_file(path.as_path(), false, true, false)
+                    .await
+                    .unwrap(),
+                handle
+            );
+            let reopened_handle = fsm
+                .open_file(path.as_path(), false, true, true)
+                .await
+                .unwrap();
+            assert_eq!(reopened_handle, handle);
+            assert!(fsm.get_by_path(path.as_path()).await.is_some());
+            assert!(fsm.remove_file(path.as_path()).await.is_err());
+
+            fsm.open_file(other_path.as_path(), true, true, true)
+                .await
+                .unwrap();
+            fsm.close_file(handle).await.unwrap();
+        })
+        .await;
+        assert!(result.is_ok(), "Manager waited on operation in progress");
+
+        // The operation in progress still has the file, which uses the
+        // permissions it was reopened with from its next operation on
+        assert!(!fsm.exists(handle));
+        let mut local_file = guard;
+        local_file.write_all(handle.sig, b"abc").await.unwrap();
+        assert_eq!(fs::read(path).await.unwrap(), b"abc");
+    }
+
     #[tokio::test]
     async fn rename_file_should_yield_error_if_origin_path_does_not_exist() {
         let root = tempfile::tempdir().unwrap();
@@ -802,4 +1239,93 @@ mod tests {
             x => panic!("Unexpected result: {:?}", x),
         }
     }
+
+    #[tokio::test]
+    async fn resolve_path_should_canonicalize_existing_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.as_ref().join("dir");
+        fs::create_dir(&dir).await.unwrap();
+
+        let resolved = resolve_path(dir.join("..").join("dir")).await.unwrap();
+        assert_eq!(resolved, fs::canonicalize(&dir).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn resolve_path_should_support_paths_that_do_not_exist() {
+        let root = tempfile::tempdir().unwrap();
+        let canonical_root = fs::canonicalize(root.as_ref()).await.unwrap();
+
+        let resolved = resolve_path(
+            root.as_ref().join("a").join("..").join("b").join("c"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resolved, canonical_root.join("b").join("c"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn open_file_should_apply_default_file_mode_only_to_created_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let new_path = root.as_ref().join("new-file");
+        let existing = tempfile::NamedTempFile::new().unwrap();
+        let existing_mode = fs::metadata(existing.as_ref())
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+
+        let mut fsm = FileSystemManager::new();
+        fsm.set_default_file_mode(Some(0o640));
+
+        fsm.open_file(&new_path, true, true, true).await.unwrap();
+        fsm.open_file(existing.as_ref(), true, true, true)
+            .await
+            .unwrap();
+
+        let mode = fs::metadata(&new_path).await.unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        let mode = fs::metadata(existing.as_ref())
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode, existing_mode);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn create_dir_with_mode_should_apply_mode_to_all_created_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let root_mode = fs::metadata(root.as_ref())
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+
+        let mut fsm = FileSystemManager::new();
+        fsm.set_default_dir_mode(Some(0o700));
+
+        let path = root.as_ref().join("a").join("b");
+        fsm.create_dir_with_mode(&path, true, Some(0o750))
+            .await
+            .unwrap();
+
+        for p in &[root.as_ref().join("a"), path] {
+            let mode = fs::metadata(p).await.unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750);
+        }
+
+        let mode = fs::metadata(root.as_ref())
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode, root_mode, "Existing dir unexpectedly changed");
+    }
 }
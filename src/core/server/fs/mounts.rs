@@ -0,0 +1,212 @@
+use crate::core::{reply::MountArgs, RemotePath};
+use std::io;
+
+/// Types of pseudo filesystems that have no storage of their own, used to
+/// recognize them when their capacity cannot be determined
+#[cfg(target_os = "linux")]
+const PSEUDO_FS_TYPES: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "securityfs",
+    "selinuxfs",
+    "sysfs",
+    "tracefs",
+];
+
+/// Lists the filesystems mounted on the server ordered by path, skipping
+/// pseudo filesystems unless `include_pseudo` is true
+///
+/// Only Linux reports every mount; other unix platforms report the root
+/// filesystem and Windows reports the drives that exist, without capacity.
+pub fn list_mounts(include_pseudo: bool) -> io::Result<Vec<MountArgs>> {
+    let mut mounts: Vec<MountArgs> = read_mounts()?
+        .into_iter()
+        .filter(|mount| include_pseudo || !is_pseudo(mount))
+        .collect();
+
+    mounts.sort_by(|a, b| a.path.as_bytes().cmp(b.path.as_bytes()));
+    Ok(mounts)
+}
+
+/// Whether `mount` is a pseudo filesystem, which reports no capacity at all
+fn is_pseudo(mount: &MountArgs) -> bool {
+    match mount.total_bytes {
+        Some(total_bytes) => total_bytes == 0,
+
+        #[cfg(target_os = "linux")]
+        None => mount
+            .fs_type
+            .as_ref()
+            .map(|fs_type| PSEUDO_FS_TYPES.contains(&fs_type.as_str()))
+            .unwrap_or_default(),
+
+        #[cfg(not(target_os = "linux"))]
+        None => false,
+    }
+}
+
+/// Reads the mount table of the server's mount namespace
+#[cfg(target_os = "linux")]
+fn read_mounts() -> io::Result<Vec<MountArgs>> {
+    let table = std::fs::read("/proc/self/mounts")?;
+    Ok(table
+        .split(|b| *b == b'\n')
+        .filter_map(parse_mount)
+        .collect())
+}
+
+/// Reports only the root filesystem, as there is no mount table to read in
+/// a portable way
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_mounts() -> io::Result<Vec<MountArgs>> {
+    let (total_bytes, available_bytes) = capacity_of(b"/");
+    Ok(vec![MountArgs {
+        path: RemotePath::from("/"),
+        device: None,
+        fs_type: None,
+        read_only: false,
+        total_bytes,
+        available_bytes,
+    }])
+}
+
+/// Reports each drive letter whose root exists
+#[cfg(windows)]
+fn read_mounts() -> io::Result<Vec<MountArgs>> {
+    Ok((b'A'..=b'Z')
+        .map(|letter| format!("{}:\\", letter as char))
+        .filter(|root| std::path::Path::new(root).exists())
+        .map(|root| MountArgs {
+            path: RemotePath::from(root),
+            ..Default::default()
+        })
+        .collect())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_mounts() -> io::Result<Vec<MountArgs>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Listing mounts is not supported on this platform",
+    ))
+}
+
+/// Parses a line of /proc/self/mounts, which holds the device, mount
+/// point, type, and options of a mount separated by spaces
+#[cfg(target_os = "linux")]
+fn parse_mount(line: &[u8]) -> Option<MountArgs> {
+    let mut fields = line.split(|b| *b == b' ');
+    let device = unescape(fields.next()?);
+    let path = unescape(fields.next()?);
+    let fs_type = String::from_utf8_lossy(fields.next()?).to_string();
+    let read_only = fields
+        .next()?
+        .split(|b| *b == b',')
+        .any(|option| option == b"ro");
+
+    let (total_bytes, available_bytes) = capacity_of(&path);
+    Some(MountArgs {
+        path: RemotePath::from_bytes(path),
+        device: Some(String::from_utf8_lossy(&device).to_string()),
+        fs_type: Some(fs_type),
+        read_only,
+        total_bytes,
+        available_bytes,
+    })
+}
+
+/// Reverses the octal escapes (such as \040 for a space) that the mount
+/// table uses for whitespace and backslashes within a field
+#[cfg(target_os = "linux")]
+fn unescape(field: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let octal = field.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match octal {
+            Some(b) if field[i] == b'\\' => {
+                bytes.push(b);
+                i += 4;
+            }
+            _ => {
+                bytes.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    bytes
+}
+
+/// Total bytes and bytes available to the server's user of the filesystem
+/// mounted at `path`, if they can be determined
+#[cfg(unix)]
+fn capacity_of(path: &[u8]) -> (Option<u64>, Option<u64>) {
+    let path = match std::ffi::CString::new(path) {
+        Ok(path) => path,
+        Err(_) => return (None, None),
+    };
+
+    let mut stat = unsafe { std::mem::zeroed::<libc::statvfs>() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } < 0 {
+        return (None, None);
+    }
+
+    // NOTE: Field widths differ between platforms, which would otherwise be
+    //       flagged as unnecessary casts on those where they are 64 bits
+    #[allow(clippy::unnecessary_cast)]
+    let block_size = stat.f_frsize as u64;
+    #[allow(clippy::unnecessary_cast)]
+    let (blocks, available_blocks) =
+        (stat.f_blocks as u64, stat.f_bavail as u64);
+
+    (
+        Some(blocks.saturating_mul(block_size)),
+        Some(available_blocks.saturating_mul(block_size)),
+    )
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mount_should_unescape_fields_and_read_options() {
+        let mount =
+            parse_mount(b"/dev/sdb1 /mnt/my\\040disk ext4 ro,relatime 0 0")
+                .unwrap();
+
+        assert_eq!(mount.path, "/mnt/my disk");
+        assert_eq!(mount.device.as_deref(), Some("/dev/sdb1"));
+        assert_eq!(mount.fs_type.as_deref(), Some("ext4"));
+        assert!(mount.read_only);
+    }
+
+    #[test]
+    fn list_mounts_should_include_root_and_skip_pseudo_filesystems() {
+        let mounts = list_mounts(false).unwrap();
+        let root = mounts.iter().find(|mount| mount.path == "/").unwrap();
+        assert!(root.total_bytes.unwrap() > 0);
+        assert!(mounts.iter().all(|mount| !is_pseudo(mount)));
+
+        let all_mounts = list_mounts(true).unwrap();
+        assert!(all_mounts
+            .iter()
+            .any(|mount| mount.fs_type.as_deref() == Some("proc")));
+    }
+}
@@ -0,0 +1,174 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+/// Policy for retrying filesystem operations that fail with errors likely
+/// to clear up on their own, such as a file briefly locked by another
+/// process, rather than reporting them to the client
+///
+/// The delay between attempts doubles after each retry up to a maximum,
+/// with jitter so that operations failing together do not retry in lockstep.
+/// The default policy makes a single attempt and never retries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts made of an operation, including the first
+    pub max_attempts: u32,
+
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+
+    /// Maximum delay between any two attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that makes up to `max_attempts` attempts, waiting
+    /// `initial_backoff` before the first retry
+    pub fn new(max_attempts: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Runs the operation produced by `f`, running it again after a delay
+    /// each time it fails with a transient error until attempts run out
+    pub async fn run<T, F, Fut>(&self, mut f: F) -> io::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Err(x) if attempt < self.max_attempts && is_transient(&x) => {
+                    tokio::time::delay_for(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Delay before the retry following `attempt`, which is between half of
+    /// and the full exponential backoff for the attempt
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        backoff / 2 + backoff.mul_f64(rand::random::<f64>() / 2.0)
+    }
+}
+
+/// Whether `err` is likely to clear up if the operation is tried again
+fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => true,
+        _ => matches!(
+            err.raw_os_error(),
+            Some(code) if is_transient_os_error(code)
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn is_transient_os_error(code: i32) -> bool {
+    code == libc::EAGAIN || code == libc::EBUSY || code == libc::ETXTBSY
+}
+
+#[cfg(windows)]
+fn is_transient_os_error(code: i32) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION, raised when another
+    // process such as a virus scanner or indexer holds the file open
+    code == 32 || code == 33
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_transient_os_error(_code: i32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn busy() -> io::Error {
+        io::Error::from(io::ErrorKind::WouldBlock)
+    }
+
+    #[tokio::test]
+    async fn run_should_retry_transient_errors_until_attempts_run_out() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+
+        let attempts = &Cell::new(0);
+        let result = policy
+            .run(|| async move {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() < 3 {
+                    Err(busy())
+                } else {
+                    Ok(attempts.get())
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+
+        attempts.set(0);
+        let result: io::Result<()> = policy
+            .run(|| async move {
+                attempts.set(attempts.get() + 1);
+                Err(busy())
+            })
+            .await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn run_should_not_retry_other_errors() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+
+        let attempts = &Cell::new(0);
+        let result: io::Result<()> = policy
+            .run(|| async move {
+                attempts.set(attempts.get() + 1);
+                Err(io::Error::from(io::ErrorKind::NotFound))
+            })
+            .await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn backoff_should_grow_with_jitter_up_to_max() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+        };
+
+        let first = policy.backoff(1);
+        assert!(first >= Duration::from_millis(50), "{:?}", first);
+        assert!(first <= Duration::from_millis(100), "{:?}", first);
+
+        let last = policy.backoff(9);
+        assert!(last >= Duration::from_millis(150), "{:?}", last);
+        assert!(last <= Duration::from_millis(300), "{:?}", last);
+    }
+}
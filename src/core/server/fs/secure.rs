@@ -0,0 +1,916 @@
+use super::allowed::EntryKind;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::os::unix::{
+    ffi::OsStrExt,
+    io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+};
+use std::path::{Component, Path, PathBuf};
+
+/// Flags that are applied whenever a directory is opened while walking a path
+const DIR_FLAGS: libc::c_int =
+    libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+
+/// Describes how to open a file within a secure root
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecureOpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub truncate: bool,
+    pub create: bool,
+    pub create_new: bool,
+
+    /// Permission bits of the file if it is created
+    pub mode: u32,
+}
+
+impl SecureOpenOptions {
+    fn flags(&self) -> libc::c_int {
+        let mut flags = libc::O_NOFOLLOW | libc::O_CLOEXEC;
+
+        flags |= match (self.read, self.write || self.append) {
+            (true, true) => libc::O_RDWR,
+            (false, true) => libc::O_WRONLY,
+            _ => libc::O_RDONLY,
+        };
+
+        if self.append {
+            flags |= libc::O_APPEND;
+        }
+        if self.truncate {
+            flags |= libc::O_TRUNC;
+        }
+        if self.create_new {
+            flags |= libc::O_CREAT | libc::O_EXCL;
+        } else if self.create {
+            flags |= libc::O_CREAT;
+        }
+
+        flags
+    }
+}
+
+/// Directory that paths are confined to, where every path is resolved by
+/// opening one component at a time relative to its parent directory and
+/// refusing to follow symlinks.
+///
+/// As each step operates on an open directory rather than a path, a symlink
+/// swapped in after a path was checked cannot redirect an operation outside
+/// of the root. Parent components (`..`) are applied lexically, which is
+/// equivalent to following them since no component can be a symlink.
+///
+/// All operations are blocking.
+#[derive(Debug)]
+pub struct SecureRoot {
+    /// Canonicalized path of the root
+    path: PathBuf,
+
+    /// Path that the root was opened with, which may lead to it through
+    /// symlinks and is accepted in place of the canonicalized path
+    alias: PathBuf,
+
+    /// Open handle to the root directory that all paths are resolved from
+    dir: File,
+}
+
+impl SecureRoot {
+    /// Opens the directory at `path` as a root, following any symlinks in
+    /// `path` itself as it is trusted
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let alias = path.as_ref().to_path_buf();
+        let path = std::fs::canonicalize(path)?;
+        let c_path = to_c_string(path.as_os_str())?;
+
+        // NOTE: Safe as the path is a valid, nul-terminated string
+        let fd = check_fd(unsafe { libc::open(c_path.as_ptr(), DIR_FLAGS) })?;
+
+        // NOTE: Safe as we exclusively own the newly-opened descriptor
+        let dir = unsafe { File::from_raw_fd(fd) };
+
+        Ok(Self { path, alias, dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Produces the path within the root that `path` refers to, which can be
+    /// relative to the root or absolute (beginning with the root's path).
+    ///
+    /// This is purely lexical; nothing on disk is checked.
+    pub fn confine(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let components = self.components(path.as_ref())?;
+        Ok(components.iter().fold(self.path.clone(), |p, c| p.join(c)))
+    }
+
+    /// Checks that `path` is within the root without passing through a
+    /// symlink, where any part of `path` that does not exist yet is fine as
+    /// it can only be created within the directory before it
+    pub fn check(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let components = self.components(path.as_ref())?;
+        let mut dir = self.dir.try_clone()?;
+
+        for name in components {
+            let c_name = to_c_string(&name)?;
+            match open_dir_at(dir.as_raw_fd(), &c_name) {
+                // NOTE: Safe as we exclusively own the newly-opened descriptor
+                Ok(fd) => dir = unsafe { File::from_raw_fd(fd) },
+                Err(x) if x.kind() == io::ErrorKind::NotFound => break,
+                Err(x) => {
+                    // Anything other than a symlink, such as a file, ends
+                    // the path where it is
+                    let x = symlink_error(x, dir.as_raw_fd(), &c_name);
+                    if x.kind() == io::ErrorKind::PermissionDenied {
+                        return Err(x);
+                    }
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Opens the directory at `path` within the root
+    pub fn open_dir(&self, path: impl AsRef<Path>) -> io::Result<File> {
+        let components = self.components(path.as_ref())?;
+        self.walk(&components, false, None)
+    }
+
+    /// Creates the directory at `path` within the root along with any
+    /// missing parent directories, applying `mode` (or the default
+    /// permissions if not provided) to each directory that is created, and
+    /// returns the opened directory
+    pub fn create_dir_all(
+        &self,
+        path: impl AsRef<Path>,
+        mode: Option<u32>,
+    ) -> io::Result<File> {
+        let components = self.components(path.as_ref())?;
+        self.walk(&components, true, mode)
+    }
+
+    /// Creates the directory at `path` within the root, failing if it or any
+    /// directory leading to it already exists or is missing respectively,
+    /// and applies `mode` if provided
+    pub fn create_dir(
+        &self,
+        path: impl AsRef<Path>,
+        mode: Option<u32>,
+    ) -> io::Result<()> {
+        let (parent, name) = self.parent_of(path.as_ref(), false)?;
+
+        // NOTE: Safe as the descriptor is open and the name is a valid,
+        //       nul-terminated string
+        check_fd(unsafe {
+            libc::mkdirat(parent.as_raw_fd(), name.as_ptr(), 0o777)
+        })?;
+
+        if let Some(mode) = mode {
+            // NOTE: Safe as we exclusively own the newly-opened descriptor
+            let dir = unsafe {
+                File::from_raw_fd(open_dir_at(parent.as_raw_fd(), &name)?)
+            };
+            set_permissions(&dir, mode)?;
+        }
+
+        Ok(())
+    }
+
+    /// Looks up the kind of entry at `path` within the root, which is never
+    /// followed if it is a symlink
+    pub fn kind(&self, path: impl AsRef<Path>) -> io::Result<EntryKind> {
+        let components = self.components(path.as_ref())?;
+        if components.is_empty() {
+            return Ok(EntryKind::Dir);
+        }
+
+        let (parent, name) = self.parent_of(path.as_ref(), false)?;
+        stat_at(parent.as_raw_fd(), &name).map(|x| EntryKind::from(&x))
+    }
+
+    /// Reads the names and kinds of the entries within the directory at
+    /// `path`, not including `.` and `..`
+    pub fn read_dir(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Vec<(OsString, EntryKind)>> {
+        let dir = self.open_dir(path)?;
+        let mut entries = Vec::new();
+        for name in names_at(&dir)? {
+            let c_name = to_c_string(&name)?;
+            let kind = EntryKind::from(&stat_at(dir.as_raw_fd(), &c_name)?);
+            entries.push((name, kind));
+        }
+        Ok(entries)
+    }
+
+    /// Renames the entry at `from` to `to`, both within the root, where a
+    /// symlink at either is renamed or replaced rather than followed
+    pub fn rename(
+        &self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        self.rename_into(from, self, to)
+    }
+
+    /// Renames the entry at `from` within the root to `to` within `to_root`
+    /// like `rename`
+    pub fn rename_into(
+        &self,
+        from: impl AsRef<Path>,
+        to_root: &SecureRoot,
+        to: impl AsRef<Path>,
+    ) -> io::Result<()> {
+        let (from_parent, from_name) = self.parent_of(from.as_ref(), false)?;
+        let (to_parent, to_name) = to_root.parent_of(to.as_ref(), false)?;
+
+        // NOTE: Safe as the descriptors are open and the names are valid,
+        //       nul-terminated strings
+        check_fd(unsafe {
+            libc::renameat(
+                from_parent.as_raw_fd(),
+                from_name.as_ptr(),
+                to_parent.as_raw_fd(),
+                to_name.as_ptr(),
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Removes the file (or symlink, which is not followed) at `path`
+    pub fn remove_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let (parent, name) = self.parent_of(path.as_ref(), false)?;
+        unlink_at(parent.as_raw_fd(), &name, 0)
+    }
+
+    /// Removes the directory at `path`, along with everything within it if
+    /// `non_empty`, never following a symlink out of the directory
+    pub fn remove_dir(
+        &self,
+        path: impl AsRef<Path>,
+        non_empty: bool,
+    ) -> io::Result<()> {
+        let (parent, name) = self.parent_of(path.as_ref(), false)?;
+        if non_empty {
+            remove_dir_all_at(parent.as_raw_fd(), &name)
+        } else {
+            unlink_at(parent.as_raw_fd(), &name, libc::AT_REMOVEDIR)
+        }
+    }
+
+    /// Applies the permission bits `mode` to the file or directory at `path`
+    pub fn set_mode(
+        &self,
+        path: impl AsRef<Path>,
+        mode: u32,
+    ) -> io::Result<()> {
+        let components = self.components(path.as_ref())?;
+        if components.is_empty() {
+            return set_permissions(&self.dir, mode);
+        }
+
+        let (parent, name) = self.parent_of(path.as_ref(), false)?;
+
+        // Opening only to change permissions, which does not need read
+        // access, so a file that can only be written is opened for that
+        // instead
+        let flags = libc::O_NOFOLLOW | libc::O_NONBLOCK | libc::O_CLOEXEC;
+        let fd = match open_at(parent.as_raw_fd(), &name, flags) {
+            Err(x) if x.kind() == io::ErrorKind::PermissionDenied => {
+                open_at(parent.as_raw_fd(), &name, flags | libc::O_WRONLY)
+            }
+            x => x,
+        }
+        .map_err(|x| symlink_error(x, parent.as_raw_fd(), &name))?;
+
+        // NOTE: Safe as we exclusively own the newly-opened descriptor
+        set_permissions(&unsafe { File::from_raw_fd(fd) }, mode)
+    }
+
+    /// Copies the contents and permissions of the file at `from` to the file
+    /// at `to`, which is created if missing and truncated otherwise
+    pub fn copy(
+        &self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> io::Result<u64> {
+        self.copy_into(from, self, to)
+    }
+
+    /// Copies the file at `from` within the root to `to` within `to_root`
+    /// like `copy`
+    pub fn copy_into(
+        &self,
+        from: impl AsRef<Path>,
+        to_root: &SecureRoot,
+        to: impl AsRef<Path>,
+    ) -> io::Result<u64> {
+        let mut reader = self.open_file(
+            from,
+            SecureOpenOptions {
+                read: true,
+                ..Default::default()
+            },
+            false,
+        )?;
+        let mut writer = to_root.open_file(
+            to,
+            SecureOpenOptions {
+                write: true,
+                create: true,
+                truncate: true,
+                mode: 0o600,
+                ..Default::default()
+            },
+            false,
+        )?;
+
+        let bytes = io::copy(&mut reader, &mut writer)?;
+        writer.set_permissions(reader.metadata()?.permissions())?;
+        Ok(bytes)
+    }
+
+    /// Opens the file at `path` within the root, failing if the file itself
+    /// or any directory leading to it is a symlink. If `create_parents` is
+    /// true, missing parent directories are created with permissions 0o755.
+    pub fn open_file(
+        &self,
+        path: impl AsRef<Path>,
+        options: SecureOpenOptions,
+        create_parents: bool,
+    ) -> io::Result<File> {
+        let (parent, c_name) = self.parent_of(path.as_ref(), create_parents)?;
+
+        // NOTE: Safe as the descriptor is open and the name is a valid,
+        //       nul-terminated string
+        let fd = check_fd(unsafe {
+            libc::openat(
+                parent.as_raw_fd(),
+                c_name.as_ptr(),
+                options.flags(),
+                options.mode as libc::c_uint,
+            )
+        })
+        .map_err(|x| symlink_error(x, parent.as_raw_fd(), &c_name))?;
+
+        // NOTE: Safe as we exclusively own the newly-opened descriptor
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+
+    /// Opens the directory containing the entry at `path`, creating missing
+    /// directories with permissions 0o755 if `create_parents`, and yields it
+    /// along with the name of the entry
+    fn parent_of(
+        &self,
+        path: &Path,
+        create_parents: bool,
+    ) -> io::Result<(File, CString)> {
+        let mut components = self.components(path)?;
+        let name = components.pop().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot use root as an entry within itself",
+            )
+        })?;
+
+        let mode = if create_parents { Some(0o755) } else { None };
+        let parent = self.walk(&components, create_parents, mode)?;
+        Ok((parent, to_c_string(&name)?))
+    }
+
+    /// Opens each directory named by `components` in turn, starting from the
+    /// root, creating missing directories if `create` and applying `mode`
+    /// (or the default permissions if not provided) to each one created
+    fn walk(
+        &self,
+        components: &[OsString],
+        create: bool,
+        mode: Option<u32>,
+    ) -> io::Result<File> {
+        let mut dir = self.dir.try_clone()?;
+
+        for name in components {
+            let c_name = to_c_string(name)?;
+            let mut created = false;
+            let fd = match open_dir_at(dir.as_raw_fd(), &c_name) {
+                Err(x) if x.kind() == io::ErrorKind::NotFound && create => {
+                    created = make_dir_at(dir.as_raw_fd(), &c_name)?;
+                    open_dir_at(dir.as_raw_fd(), &c_name)
+                }
+                x => x,
+            }
+            .map_err(|x| symlink_error(x, dir.as_raw_fd(), &c_name))?;
+
+            // NOTE: Safe as we exclusively own the newly-opened descriptor
+            dir = unsafe { File::from_raw_fd(fd) };
+
+            if let (true, Some(mode)) = (created, mode) {
+                set_permissions(&dir, mode)?;
+            }
+        }
+
+        Ok(dir)
+    }
+
+    /// Splits `path` into the names of each entry beneath the root, failing
+    /// if the path would leave the root
+    fn components(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let relative = if path.is_absolute() {
+            path.strip_prefix(&self.path)
+                .or_else(|_| path.strip_prefix(&self.alias))
+                .map_err(|_| escape_error(path))?
+        } else {
+            path
+        };
+
+        let mut components = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => components.push(name.to_os_string()),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    components.pop().ok_or_else(|| escape_error(path))?;
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(escape_error(path))
+                }
+            }
+        }
+
+        Ok(components)
+    }
+}
+
+impl From<&libc::stat> for EntryKind {
+    fn from(stat: &libc::stat) -> Self {
+        match stat.st_mode & libc::S_IFMT {
+            libc::S_IFREG => Self::File,
+            libc::S_IFDIR => Self::Dir,
+            libc::S_IFLNK => Self::Symlink,
+            _ => Self::Other,
+        }
+    }
+}
+
+fn open_dir_at(dir: RawFd, name: &CString) -> io::Result<RawFd> {
+    open_at(dir, name, DIR_FLAGS)
+}
+
+fn open_at(
+    dir: RawFd,
+    name: &CString,
+    flags: libc::c_int,
+) -> io::Result<RawFd> {
+    // NOTE: Safe as the descriptor is open and the name is a valid,
+    //       nul-terminated string
+    check_fd(unsafe { libc::openat(dir, name.as_ptr(), flags) })
+}
+
+/// Creates the directory `name` within `dir` with the default permissions,
+/// yielding whether it was created rather than already existing
+fn make_dir_at(dir: RawFd, name: &CString) -> io::Result<bool> {
+    // NOTE: Safe as the descriptor is open and the name is a valid,
+    //       nul-terminated string
+    let result = unsafe { libc::mkdirat(dir, name.as_ptr(), 0o777) };
+
+    // Another process creating the same directory first is fine, as we will
+    // still refuse to follow it if it is a symlink
+    match check_fd(result) {
+        Ok(_) => Ok(true),
+        Err(x) if x.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(x) => Err(x),
+    }
+}
+
+fn unlink_at(dir: RawFd, name: &CString, flags: libc::c_int) -> io::Result<()> {
+    // NOTE: Safe as the descriptor is open and the name is a valid,
+    //       nul-terminated string
+    check_fd(unsafe { libc::unlinkat(dir, name.as_ptr(), flags) })?;
+    Ok(())
+}
+
+/// Removes the directory `name` within `dir` after removing everything
+/// within it, where nested directories are opened without following
+/// symlinks so that only entries beneath `dir` are ever removed
+fn remove_dir_all_at(dir: RawFd, name: &CString) -> io::Result<()> {
+    let fd = open_dir_at(dir, name).map_err(|x| symlink_error(x, dir, name))?;
+
+    // NOTE: Safe as we exclusively own the newly-opened descriptor
+    let inner = unsafe { File::from_raw_fd(fd) };
+    for entry in names_at(&inner)? {
+        let c_entry = to_c_string(&entry)?;
+        match EntryKind::from(&stat_at(inner.as_raw_fd(), &c_entry)?) {
+            EntryKind::Dir => remove_dir_all_at(inner.as_raw_fd(), &c_entry)?,
+            _ => unlink_at(inner.as_raw_fd(), &c_entry, 0)?,
+        }
+    }
+
+    unlink_at(dir, name, libc::AT_REMOVEDIR)
+}
+
+/// Reads the names of the entries within the open directory `dir`, not
+/// including `.` and `..`
+fn names_at(dir: &File) -> io::Result<Vec<OsString>> {
+    // NOTE: The stream takes ownership of the descriptor it is given, so it
+    //       is given a duplicate, which is safe as we exclusively own it
+    let fd = dir.try_clone()?.into_raw_fd();
+    let stream = unsafe { libc::fdopendir(fd) };
+    if stream.is_null() {
+        let error = io::Error::last_os_error();
+
+        // NOTE: Safe as the descriptor was not taken by the stream
+        unsafe { libc::close(fd) };
+        return Err(error);
+    }
+
+    let mut names = Vec::new();
+    loop {
+        // NOTE: Safe as the stream is open, and each entry is only read
+        //       before the next call to read the stream
+        let entry = unsafe { libc::readdir(stream) };
+        if entry.is_null() {
+            break;
+        }
+
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+        let name = OsStr::from_bytes(name.to_bytes());
+        if name != "." && name != ".." {
+            names.push(name.to_os_string());
+        }
+    }
+
+    // NOTE: Safe as the stream is open, and closing it closes its descriptor
+    unsafe { libc::closedir(stream) };
+    Ok(names)
+}
+
+/// Looks up the status of `name` within `dir` without following it if it
+/// is a symlink
+fn stat_at(dir: RawFd, name: &CString) -> io::Result<libc::stat> {
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+
+    // NOTE: Safe as the descriptor is open, the name is a valid,
+    //       nul-terminated string, and the stat is only read if filled in
+    unsafe {
+        check_fd(libc::fstatat(
+            dir,
+            name.as_ptr(),
+            stat.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        ))?;
+        Ok(stat.assume_init())
+    }
+}
+
+fn set_permissions(file: &File, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(std::fs::Permissions::from_mode(mode))
+}
+
+fn check_fd(fd: libc::c_int) -> io::Result<RawFd> {
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(fd)
+    }
+}
+
+fn to_c_string(s: &OsStr) -> io::Result<CString> {
+    CString::new(s.as_bytes())
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))
+}
+
+fn escape_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{:?} is outside of the root", path),
+    )
+}
+
+/// Converts the error from refusing to follow a symlink, which is reported
+/// as a loop or (when opening a directory) not being a directory, into a
+/// permission error
+fn symlink_error(error: io::Error, dir: RawFd, name: &CString) -> io::Error {
+    let is_symlink = match error.raw_os_error() {
+        Some(libc::ELOOP) => true,
+        Some(libc::ENOTDIR) => is_symlink_at(dir, name),
+        _ => false,
+    };
+
+    if is_symlink {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{:?} is a symlink, which cannot be followed", name),
+        )
+    } else {
+        error
+    }
+}
+
+fn is_symlink_at(dir: RawFd, name: &CString) -> bool {
+    matches!(
+        stat_at(dir, name).map(|x| EntryKind::from(&x)),
+        Ok(EntryKind::Symlink)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::unix::fs::symlink;
+
+    fn read_options() -> SecureOpenOptions {
+        SecureOpenOptions {
+            read: true,
+            ..Default::default()
+        }
+    }
+
+    fn create_options() -> SecureOpenOptions {
+        SecureOpenOptions {
+            write: true,
+            create: true,
+            mode: 0o644,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn open_file_should_support_paths_within_root() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/file"), b"abc").unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        for path in &[
+            PathBuf::from("sub/file"),
+            PathBuf::from("./sub/../sub/file"),
+            root.path().join("sub/file"),
+        ] {
+            let mut contents = String::new();
+            root.open_file(path, read_options(), false)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "abc", "Wrong contents for {:?}", path);
+        }
+    }
+
+    #[test]
+    fn open_file_should_reject_paths_leaving_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = SecureRoot::open(dir.path().join(".")).unwrap();
+
+        for path in &[
+            PathBuf::from("../file"),
+            PathBuf::from("sub/../../file"),
+            PathBuf::from("/etc/passwd"),
+        ] {
+            let err = root.open_file(path, read_options(), false).unwrap_err();
+            assert_eq!(
+                err.kind(),
+                io::ErrorKind::PermissionDenied,
+                "Unexpected error for {:?}: {}",
+                path,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn open_file_should_not_follow_symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), b"secret").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        symlink(outside.path(), dir.path().join("dir-link")).unwrap();
+        symlink(outside.path().join("secret"), dir.path().join("file-link"))
+            .unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        let err = root
+            .open_file("dir-link/secret", read_options(), false)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+
+        let err = root
+            .open_file("file-link", read_options(), false)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+
+        // Creating missing parents must not follow the link either
+        let err = root
+            .open_file("dir-link/new/file", create_options(), true)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+        assert!(!outside.path().join("new").exists(), "Escaped root");
+    }
+
+    #[test]
+    fn check_should_reject_symlinks_but_allow_missing_paths() {
+        let outside = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/file"), b"abc").unwrap();
+        symlink(outside.path(), dir.path().join("dir-link")).unwrap();
+        symlink(dir.path().join("sub"), dir.path().join("inner-link")).unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        root.check("sub/file").unwrap();
+        root.check("sub/missing/file").unwrap();
+        root.check(dir.path().join("sub")).unwrap();
+
+        for path in &["dir-link", "dir-link/new", "inner-link/file", ".."] {
+            let err = root.check(path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+        }
+    }
+
+    #[test]
+    fn open_file_should_create_missing_parents_if_requested() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        let err = root
+            .open_file("a/b/file", create_options(), false)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        root.open_file("a/b/file", create_options(), true)
+            .unwrap()
+            .write_all(b"abc")
+            .unwrap();
+        assert_eq!(std::fs::read(dir.path().join("a/b/file")).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn create_dir_all_should_create_each_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        root.create_dir_all("a/b/c", Some(0o755)).unwrap();
+        assert!(dir.path().join("a/b/c").is_dir(), "Dir not created");
+
+        // Creating again succeeds as the dirs already exist
+        root.create_dir_all("a/b", None).unwrap();
+        assert!(root.open_dir("a/b/c").is_ok(), "Failed to open dir");
+    }
+
+    #[test]
+    fn confine_should_produce_path_within_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        assert_eq!(
+            root.confine("a/./b/../c").unwrap(),
+            root.path().join("a/c")
+        );
+        assert!(root.confine("a/../..").is_err(), "Escaped root");
+    }
+
+    #[test]
+    fn kind_and_read_dir_should_report_symlinks_as_they_are() {
+        let outside = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+        symlink(outside.path(), dir.path().join("link")).unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        assert_eq!(root.kind("").unwrap(), EntryKind::Dir);
+        assert_eq!(root.kind("file").unwrap(), EntryKind::File);
+        assert_eq!(root.kind("link").unwrap(), EntryKind::Symlink);
+        let err = root.kind("link/file").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+
+        let mut entries = root.read_dir("").unwrap();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            entries,
+            vec![
+                ("file".into(), EntryKind::File),
+                ("link".into(), EntryKind::Symlink),
+            ]
+        );
+        assert!(root.read_dir("link").is_err());
+    }
+
+    #[test]
+    fn create_dir_should_apply_mode_and_not_follow_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let outside = tempfile::tempdir().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        symlink(outside.path(), dir.path().join("link")).unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        root.create_dir("sub", Some(0o700)).unwrap();
+        let mode = std::fs::metadata(dir.path().join("sub"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        assert!(root.create_dir("link/sub", None).is_err());
+        assert!(root.create_dir_all("link/a/b", None).is_err());
+        assert!(!outside.path().join("sub").exists());
+        assert!(!outside.path().join("a").exists());
+    }
+
+    #[test]
+    fn rename_should_not_follow_symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), b"secret").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"abc").unwrap();
+        symlink(outside.path(), dir.path().join("link")).unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        assert!(root.rename("file", "link/file").is_err());
+        assert!(root.rename("link/secret", "secret").is_err());
+        assert!(!outside.path().join("file").exists());
+
+        // Renaming a link moves the link itself
+        root.rename("link", "moved").unwrap();
+        assert!(std::fs::symlink_metadata(dir.path().join("moved"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(outside.path().join("secret").exists());
+    }
+
+    #[test]
+    fn remove_should_not_follow_symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), b"secret").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        symlink(outside.path(), dir.path().join("sub/link")).unwrap();
+        symlink(outside.path().join("secret"), dir.path().join("file-link"))
+            .unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        assert!(root.remove_file("sub/link/secret").is_err());
+        root.remove_file("file-link").unwrap();
+        assert!(!dir.path().join("file-link").exists());
+
+        assert!(root.remove_dir("sub", false).is_err());
+        root.remove_dir("sub", true).unwrap();
+        assert!(!dir.path().join("sub").exists());
+        assert!(outside.path().join("secret").exists());
+    }
+
+    #[test]
+    fn set_mode_should_not_follow_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let outside = tempfile::tempdir().unwrap();
+        let secret = outside.path().join("secret");
+        std::fs::write(&secret, b"secret").unwrap();
+        let mode = std::fs::metadata(&secret).unwrap().permissions().mode();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+        symlink(&secret, dir.path().join("link")).unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        root.set_mode("file", 0o600).unwrap();
+        let file_mode = std::fs::metadata(dir.path().join("file"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(file_mode & 0o777, 0o600);
+
+        assert!(root.set_mode("link", 0o777).is_err());
+        assert_eq!(
+            std::fs::metadata(&secret).unwrap().permissions().mode(),
+            mode
+        );
+    }
+
+    #[test]
+    fn copy_should_copy_contents_and_not_follow_symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), b"secret").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"abc").unwrap();
+        symlink(outside.path().join("secret"), dir.path().join("file-link"))
+            .unwrap();
+        symlink(outside.path(), dir.path().join("dir-link")).unwrap();
+        let root = SecureRoot::open(dir.path()).unwrap();
+
+        assert_eq!(root.copy("file", "copy").unwrap(), 3);
+        assert_eq!(std::fs::read(dir.path().join("copy")).unwrap(), b"abc");
+
+        assert!(root.copy("file-link", "stolen").is_err());
+        assert!(root.copy("file", "file-link").is_err());
+        assert!(root.copy("file", "dir-link/file").is_err());
+        assert_eq!(
+            std::fs::read(outside.path().join("secret")).unwrap(),
+            b"secret"
+        );
+        assert!(!outside.path().join("file").exists());
+    }
+}
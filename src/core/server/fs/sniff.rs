@@ -0,0 +1,159 @@
+/// MIME type reported when content is text but not otherwise recognized
+pub const TEXT_MIME_TYPE: &str = "text/plain";
+
+/// MIME type reported when content is binary but not otherwise recognized
+pub const BINARY_MIME_TYPE: &str = "application/octet-stream";
+
+/// Known magic numbers found at the start of files, paired with their
+/// MIME type
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"BM", "image/bmp"),
+    (b"II*\x00", "image/tiff"),
+    (b"MM\x00*", "image/tiff"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"\x7fELF", "application/x-elf"),
+    (b"MZ", "application/vnd.microsoft.portable-executable"),
+    (b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (b"\x00asm", "application/wasm"),
+    (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"<?xml", "application/xml"),
+    (b"#!", "text/x-shellscript"),
+];
+
+/// Style of line endings used within text content
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineEndingStyle {
+    None,
+    Lf,
+    CrLf,
+    Cr,
+    Mixed,
+}
+
+/// Detects the MIME type of content using the magic number at its start,
+/// falling back to a generic text or binary type
+pub fn detect_mime_type(bytes: &[u8]) -> &'static str {
+    if let Some((_, mime_type)) = SIGNATURES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+    {
+        return mime_type;
+    }
+
+    // RIFF containers share a prefix and are distinguished by a later tag
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") {
+        match &bytes[8..12] {
+            b"WAVE" => return "audio/wav",
+            b"AVI " => return "video/x-msvideo",
+            b"WEBP" => return "image/webp",
+            _ => {}
+        }
+    }
+
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+
+    if is_binary(bytes) {
+        BINARY_MIME_TYPE
+    } else if bytes.starts_with(b"{") || bytes.starts_with(b"[") {
+        "application/json"
+    } else {
+        TEXT_MIME_TYPE
+    }
+}
+
+/// Determines whether or not content appears to be binary rather than text,
+/// which is the case if it contains a null byte or is not valid UTF-8
+pub fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return true;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(_) => false,
+
+        // Content may have been cut off in the middle of a character, which
+        // is indicated by the error not having a length
+        Err(x) => x.error_len().is_some(),
+    }
+}
+
+/// Detects the style of line endings used within content
+pub fn detect_line_ending(bytes: &[u8]) -> LineEndingStyle {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut iter = bytes.iter().peekable();
+    while let Some(b) = iter.next() {
+        match b {
+            b'\r' if iter.peek() == Some(&&b'\n') => {
+                iter.next();
+                crlf += 1;
+            }
+            b'\r' => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+    }
+
+    match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => LineEndingStyle::None,
+        (true, false, false) => LineEndingStyle::Lf,
+        (false, true, false) => LineEndingStyle::CrLf,
+        (false, false, true) => LineEndingStyle::Cr,
+        _ => LineEndingStyle::Mixed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_mime_type_should_use_magic_numbers() {
+        assert_eq!(detect_mime_type(b"\x89PNG\r\n\x1a\n\x00\x00"), "image/png");
+        assert_eq!(detect_mime_type(b"%PDF-1.4\n"), "application/pdf");
+        assert_eq!(
+            detect_mime_type(b"RIFF\x00\x00\x00\x00WAVEfmt "),
+            "audio/wav"
+        );
+        assert_eq!(detect_mime_type(b"\x7fELF\x02\x01"), "application/x-elf");
+    }
+
+    #[test]
+    fn detect_mime_type_should_fall_back_to_text_or_binary() {
+        assert_eq!(detect_mime_type(b"hello world"), TEXT_MIME_TYPE);
+        assert_eq!(detect_mime_type(b"\x01\x00\x02"), BINARY_MIME_TYPE);
+        assert_eq!(detect_mime_type(b""), TEXT_MIME_TYPE);
+    }
+
+    #[test]
+    fn is_binary_should_allow_truncated_utf8() {
+        let text = "caf\u{e9}".as_bytes();
+        assert!(!is_binary(&text[..text.len() - 1]));
+        assert!(is_binary(b"\xff\xfe\xfd"));
+    }
+
+    #[test]
+    fn detect_line_ending_should_identify_style() {
+        assert_eq!(detect_line_ending(b"abc"), LineEndingStyle::None);
+        assert_eq!(detect_line_ending(b"a\nb\n"), LineEndingStyle::Lf);
+        assert_eq!(detect_line_ending(b"a\r\nb\r\n"), LineEndingStyle::CrLf);
+        assert_eq!(detect_line_ending(b"a\rb\r"), LineEndingStyle::Cr);
+        assert_eq!(detect_line_ending(b"a\r\nb\n"), LineEndingStyle::Mixed);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::utils::TaskTracker;
+use log::error;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::{process::Command, runtime::Handle, sync::Mutex};
+
+/// Name of the directory (within the temp directory) used to store jobs when
+/// no other directory is configured
+pub const DEFAULT_JOBS_DIR_NAME: &str = "over-there-jobs";
+
+const RECORD_FILE_NAME: &str = "record.json";
+const STDOUT_FILE_NAME: &str = "stdout";
+const STDERR_FILE_NAME: &str = "stderr";
+
+/// Describes the process to run for a job
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LocalJobSpec {
+    pub command: String,
+    pub args: Vec<String>,
+    pub current_dir: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LocalJobState {
+    Running,
+    Exited,
+
+    /// The server stopped tracking the job before it finished, such as when
+    /// the server was restarted, so its outcome is unknown
+    Interrupted,
+}
+
+/// Persistent record of a job, where all times are in milliseconds since
+/// the unix epoch
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JobRecord {
+    pub id: u32,
+    pub spec: LocalJobSpec,
+    pub state: LocalJobState,
+    pub submitted_at: u64,
+    pub finished_at: Option<u64>,
+    pub exit_code: Option<i32>,
+}
+
+/// Manages jobs whose output and outcome are stored on disk, allowing them
+/// to be queried independently of any connection (or server) lifetime
+#[derive(Debug)]
+pub struct JobManager {
+    /// Directory containing a subdirectory per job
+    dir: PathBuf,
+
+    /// Ids of jobs whose processes are being awaited by this manager
+    running: Arc<Mutex<HashSet<u32>>>,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new(default_jobs_dir())
+    }
+}
+
+/// Directory used to store jobs when no other directory is configured
+pub fn default_jobs_dir() -> PathBuf {
+    std::env::temp_dir().join(DEFAULT_JOBS_DIR_NAME)
+}
+
+impl JobManager {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            running: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Starts a process for the job described by `spec`, capturing its
+    /// stdout and stderr to files and recording its outcome once it exits,
+    /// with the wait on the process tracked by `tasks`
+    pub async fn submit(
+        &self,
+        tasks: &TaskTracker,
+        spec: LocalJobSpec,
+    ) -> io::Result<JobRecord> {
+        let id = OsRng.next_u32();
+        let job_dir = self.job_dir(id);
+        tokio::fs::create_dir_all(&job_dir).await?;
+
+        let result = self.spawn(tasks, id, &job_dir, spec).await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_dir_all(&job_dir).await;
+        }
+        result
+    }
+
+    async fn spawn(
+        &self,
+        tasks: &TaskTracker,
+        id: u32,
+        job_dir: &Path,
+        spec: LocalJobSpec,
+    ) -> io::Result<JobRecord> {
+        let stdout = File::create(job_dir.join(STDOUT_FILE_NAME))?;
+        let stderr = File::create(job_dir.join(STDERR_FILE_NAME))?;
+
+        let mut cmd = Command::new(&spec.command);
+        cmd.args(&spec.args)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr);
+
+        if let Some(dir) = &spec.current_dir {
+            cmd.current_dir(tokio::fs::canonicalize(dir).await?);
+        }
+
+        let child = cmd.spawn()?;
+        let mut record = JobRecord {
+            id,
+            spec,
+            state: LocalJobState::Running,
+            submitted_at: now_millis(),
+            finished_at: None,
+            exit_code: None,
+        };
+        write_record(job_dir, &record).await?;
+        self.running.lock().await.insert(id);
+
+        let running = Arc::clone(&self.running);
+        let job_dir = job_dir.to_path_buf();
+        let submitted = record.clone();
+        tasks.spawn(&Handle::current(), async move {
+            let exit_code = match child.await {
+                Ok(status) => status.code(),
+                Err(x) => {
+                    error!("Failed to wait on job {}: {}", id, x);
+                    None
+                }
+            };
+
+            record.state = LocalJobState::Exited;
+            record.finished_at = Some(now_millis());
+            record.exit_code = exit_code;
+            if let Err(x) = write_record(&job_dir, &record).await {
+                error!("Failed to record outcome of job {}: {}", id, x);
+            }
+
+            running.lock().await.remove(&id);
+        });
+
+        Ok(submitted)
+    }
+
+    /// Loads the record of the job with `id`, reporting it as interrupted if
+    /// it never finished and is no longer being awaited
+    pub async fn get(&self, id: u32) -> io::Result<JobRecord> {
+        let mut record = self.read_record(id).await?;
+
+        if record.state == LocalJobState::Running
+            && !self.running.lock().await.contains(&id)
+        {
+            record.state = LocalJobState::Interrupted;
+        }
+
+        Ok(record)
+    }
+
+    /// Ids of the jobs whose processes are still being awaited
+    pub async fn running_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> =
+            self.running.lock().await.iter().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Reads all stdout and stderr captured so far for the job with `id`
+    pub async fn read_output(&self, id: u32) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let job_dir = self.job_dir(id);
+
+        // Verify that the job exists before reading its output
+        self.read_record(id).await?;
+
+        let stdout = tokio::fs::read(job_dir.join(STDOUT_FILE_NAME)).await?;
+        let stderr = tokio::fs::read(job_dir.join(STDERR_FILE_NAME)).await?;
+        Ok((stdout, stderr))
+    }
+
+    /// Loads the records of every job stored, ordered by when they were
+    /// submitted
+    pub async fn list(&self) -> io::Result<Vec<JobRecord>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(x) => return Err(x),
+        };
+
+        let mut records = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let id = match entry.file_name().to_str().map(str::parse::<u32>) {
+                Some(Ok(id)) => id,
+                _ => continue,
+            };
+
+            // NOTE: Skip directories without a record, such as those of
+            //       jobs still being created
+            match self.get(id).await {
+                Ok(record) => records.push(record),
+                Err(x) if x.kind() == io::ErrorKind::InvalidInput => {}
+                Err(x) => return Err(x),
+            }
+        }
+
+        records.sort_by_key(|r| (r.submitted_at, r.id));
+        Ok(records)
+    }
+
+    /// Stores a job that ran elsewhere, such as on another server, along
+    /// with its captured output, failing if a job with its id exists
+    ///
+    /// A job that was still running is stored as interrupted, as it is not
+    /// running here.
+    pub async fn restore(
+        &self,
+        mut record: JobRecord,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> io::Result<()> {
+        let job_dir = self.job_dir(record.id);
+        if tokio::fs::metadata(&job_dir).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Job {} already exists", record.id),
+            ));
+        }
+
+        if record.state == LocalJobState::Running {
+            record.state = LocalJobState::Interrupted;
+        }
+
+        tokio::fs::create_dir_all(&job_dir).await?;
+        tokio::fs::write(job_dir.join(STDOUT_FILE_NAME), stdout).await?;
+        tokio::fs::write(job_dir.join(STDERR_FILE_NAME), stderr).await?;
+        write_record(&job_dir, &record).await
+    }
+
+    async fn read_record(&self, id: u32) -> io::Result<JobRecord> {
+        let path = self.job_dir(id).join(RECORD_FILE_NAME);
+        let text = match tokio::fs::read_to_string(path).await {
+            Ok(text) => text,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("No job submitted with id {}", id),
+                ))
+            }
+            Err(x) => return Err(x),
+        };
+
+        serde_json::from_str(&text)
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+    }
+
+    fn job_dir(&self, id: u32) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+}
+
+async fn write_record(job_dir: &Path, record: &JobRecord) -> io::Result<()> {
+    write_json_atomic(&job_dir.join(RECORD_FILE_NAME), record).await
+}
+
+/// Writes `value` as json by way of a temporary file so that readers never
+/// see a partially-written file
+pub(crate) async fn write_json_atomic<T: Serialize>(
+    path: &Path,
+    value: &T,
+) -> io::Result<()> {
+    let text = serde_json::to_string(value)
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+
+    let mut tmp_path = path.as_os_str().to_os_string();
+    tmp_path.push(".tmp");
+    tokio::fs::write(&tmp_path, text).await?;
+    tokio::fs::rename(&tmp_path, path).await
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn make_spec(command: &str, args: &[&str]) -> LocalJobSpec {
+        LocalJobSpec {
+            command: String::from(command),
+            args: args.iter().map(|x| x.to_string()).collect(),
+            current_dir: None,
+        }
+    }
+
+    async fn wait_for_exit(manager: &JobManager, id: u32) -> JobRecord {
+        for _ in 0..50 {
+            let record = manager.get(id).await.unwrap();
+            if record.state != LocalJobState::Running {
+                return record;
+            }
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        panic!("Job {} never exited", id);
+    }
+
+    #[tokio::test]
+    async fn submit_should_capture_output_and_record_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(dir.as_ref());
+
+        let record = manager
+            .submit(
+                &TaskTracker::default(),
+                make_spec("sh", &["-c", "echo out; echo err >&2; exit 3"]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(record.state, LocalJobState::Running);
+
+        let record = wait_for_exit(&manager, record.id).await;
+        assert_eq!(record.state, LocalJobState::Exited);
+        assert_eq!(record.exit_code, Some(3));
+        assert!(record.finished_at.is_some(), "Finish time not recorded");
+
+        let (stdout, stderr) = manager.read_output(record.id).await.unwrap();
+        assert_eq!(stdout, b"out\n".to_vec());
+        assert_eq!(stderr, b"err\n".to_vec());
+    }
+
+    #[tokio::test]
+    async fn get_should_report_unfinished_jobs_from_other_managers_as_interrupted(
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(dir.as_ref());
+
+        let record = manager
+            .submit(&TaskTracker::default(), make_spec("sleep", &["60"]))
+            .await
+            .unwrap();
+
+        // A new manager using the same directory, such as after a restart,
+        // is not awaiting the job
+        let other = JobManager::new(dir.as_ref());
+        let other_record = other.get(record.id).await.unwrap();
+        assert_eq!(other_record.state, LocalJobState::Interrupted);
+        assert_eq!(other_record.spec, record.spec);
+    }
+
+    #[tokio::test]
+    async fn submit_should_track_job_until_it_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(dir.as_ref());
+        let tasks = TaskTracker::default();
+
+        let record = manager
+            .submit(&tasks, make_spec("sleep", &["0.2"]))
+            .await
+            .unwrap();
+        assert_eq!(tasks.active(), 1, "Job was not tracked");
+
+        while manager.get(record.id).await.unwrap().state
+            == LocalJobState::Running
+        {
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+        tokio::time::delay_for(Duration::from_millis(50)).await;
+        assert_eq!(tasks.active(), 0, "Job still tracked after exiting");
+    }
+
+    #[tokio::test]
+    async fn submit_should_not_create_job_if_spawn_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(dir.as_ref());
+
+        let err = manager
+            .submit(&TaskTracker::default(), make_spec("<a><b><c>", &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let entries = std::fs::read_dir(dir.as_ref()).unwrap().count();
+        assert_eq!(entries, 0, "Job directory was not cleaned up");
+    }
+
+    #[tokio::test]
+    async fn get_should_fail_if_job_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = JobManager::new(dir.as_ref());
+
+        let err = manager.get(999).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
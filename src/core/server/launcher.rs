@@ -0,0 +1,54 @@
+use crate::core::request::ExecProcArgs;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use tokio::process::{Child, Command};
+
+pub type ProcLauncherFunc =
+    Box<dyn Fn(&ExecProcArgs, Command) -> io::Result<Child> + Send + Sync>;
+
+/// Spawns the procs requested of a server, given the command that the
+/// server prepared from each request
+///
+/// Applications embedding a server can supply their own launcher to refuse
+/// commands, rewrite them, or run them through a sandbox, where the default
+/// launcher spawns every command as is.
+#[derive(Clone)]
+pub struct ProcLauncher {
+    f: Arc<ProcLauncherFunc>,
+}
+
+impl fmt::Debug for ProcLauncher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcLauncher").finish()
+    }
+}
+
+impl Default for ProcLauncher {
+    fn default() -> Self {
+        Self::from(|_: &ExecProcArgs, mut cmd: Command| cmd.spawn())
+    }
+}
+
+impl ProcLauncher {
+    pub fn new(f: ProcLauncherFunc) -> Self {
+        Self { f: Arc::new(f) }
+    }
+
+    pub fn launch(
+        &self,
+        args: &ExecProcArgs,
+        cmd: Command,
+    ) -> io::Result<Child> {
+        (self.f)(args, cmd)
+    }
+}
+
+impl<F> From<F> for ProcLauncher
+where
+    F: Fn(&ExecProcArgs, Command) -> io::Result<Child> + Send + Sync + 'static,
+{
+    fn from(f: F) -> Self {
+        Self::new(Box::new(f))
+    }
+}
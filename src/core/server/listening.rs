@@ -1,7 +1,11 @@
 use super::state::ServerState;
-use crate::core::event::AddrEventManager;
+use crate::core::{
+    event::AddrEventManager, reply::ServerShuttingDownArgs, Msg, Reply,
+};
+use log::{error, warn};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::task::{JoinError, JoinHandle};
 
 /// Represents a server after listening has begun
@@ -36,6 +40,46 @@ impl ListeningServer {
         self.state.shutdown()
     }
 
+    /// Shuts down the server and waits for requests that are still being
+    /// executed to complete, returning false if some are still running once
+    /// `timeout` has elapsed
+    ///
+    /// Every client that has communicated with the server is told that it is
+    /// shutting down beforehand, so that they can finish what they are doing
+    /// within `timeout` or give up rather than waiting on replies that will
+    /// never arrive.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.announce_shutdown(timeout).await;
+        self.shutdown();
+        self.state.tasks.drain(timeout).await
+    }
+
+    /// Sends a msg to every client that has communicated with the server
+    /// telling it that the server is shutting down within `grace`
+    async fn announce_shutdown(&self, grace: Duration) {
+        let msg =
+            Msg::from(Reply::ServerShuttingDown(ServerShuttingDownArgs {
+                grace_ms: grace.as_millis() as u64,
+            }));
+        let data = match msg.to_vec() {
+            Ok(data) => data,
+            Err(x) => {
+                error!("Failed to encode shutdown msg: {}", x);
+                return;
+            }
+        };
+
+        let addrs: Vec<SocketAddr> =
+            self.state.conns.lock().await.keys().copied().collect();
+        let mut tx = self.addr_event_manager.sender();
+        for addr in addrs {
+            if tx.send((data.clone(), addr)).await.is_err() {
+                warn!("Failed to tell {} of shutdown", addr);
+                break;
+            }
+        }
+    }
+
     /// Waits for the server to complete
     pub async fn wait(self) -> Result<(), JoinError> {
         tokio::try_join!(self.addr_event_manager.wait(), self.event_handle)
@@ -0,0 +1,152 @@
+use crate::core::{
+    reply::PageInfoArgs,
+    request::{PageArgs, SortOrder},
+};
+use std::cmp::Reverse;
+use std::time::Duration;
+
+/// Filters `entries`, sorts those that remain by `key`, and takes the page
+/// of them described by `page`, yielding the page alongside its position
+/// within all matching entries
+///
+/// Sorting is stable, so entries with equal keys keep the order they were
+/// provided in, and callers should use keys that are unique (such as ids)
+/// for pages to remain consistent between requests
+pub fn list<T, K, F, G>(
+    entries: impl IntoIterator<Item = T>,
+    page: &PageArgs,
+    filter: F,
+    key: G,
+) -> (Vec<T>, PageInfoArgs)
+where
+    K: Ord,
+    F: Fn(&T) -> bool,
+    G: Fn(&T) -> K,
+{
+    let mut entries: Vec<T> = entries.into_iter().filter(filter).collect();
+    match page.order {
+        SortOrder::Ascending => entries.sort_by_key(|entry| key(entry)),
+        SortOrder::Descending => {
+            entries.sort_by_key(|entry| Reverse(key(entry)))
+        }
+    }
+
+    let total = entries.len() as u64;
+    let start = page.offset.min(total) as usize;
+    let end = match page.limit {
+        Some(limit) => (start + limit as usize).min(entries.len()),
+        None => entries.len(),
+    };
+    let next_offset = if end < entries.len() {
+        Some(end as u64)
+    } else {
+        None
+    };
+
+    let entries = entries.drain(start..end).collect();
+    (entries, PageInfoArgs { total, next_offset })
+}
+
+/// Whether `age` falls within the optional bounds given in seconds
+pub fn within_age(
+    age: Duration,
+    min_secs: Option<u64>,
+    max_secs: Option<u64>,
+) -> bool {
+    if let Some(min) = min_secs {
+        if age < Duration::from_secs(min) {
+            return false;
+        }
+    }
+
+    if let Some(max) = max_secs {
+        if age > Duration::from_secs(max) {
+            return false;
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(offset: u64, limit: Option<u32>, order: SortOrder) -> PageArgs {
+        PageArgs {
+            offset,
+            limit,
+            order,
+        }
+    }
+
+    #[test]
+    fn list_should_filter_sort_and_page_entries() {
+        let entries = vec![5, 3, 8, 1, 4, 9, 2];
+        let is_small = |x: &u32| *x < 9;
+
+        let (entries_1, info) = list(
+            entries.clone(),
+            &page(0, Some(3), SortOrder::Ascending),
+            is_small,
+            |x| *x,
+        );
+        assert_eq!(entries_1, vec![1, 2, 3]);
+        assert_eq!(
+            info,
+            PageInfoArgs {
+                total: 6,
+                next_offset: Some(3)
+            }
+        );
+
+        let (entries_2, info) = list(
+            entries.clone(),
+            &page(3, Some(3), SortOrder::Ascending),
+            is_small,
+            |x| *x,
+        );
+        assert_eq!(entries_2, vec![4, 5, 8]);
+        assert_eq!(info.next_offset, None);
+
+        let (entries_3, info) = list(
+            entries,
+            &page(10, None, SortOrder::Descending),
+            is_small,
+            |x| *x,
+        );
+        assert!(entries_3.is_empty());
+        assert_eq!(info.total, 6);
+        assert_eq!(info.next_offset, None);
+    }
+
+    #[test]
+    fn list_should_keep_order_of_entries_with_equal_keys() {
+        let entries = vec![(1, 'a'), (0, 'b'), (1, 'c'), (0, 'd')];
+
+        let (entries_1, _) = list(
+            entries.clone(),
+            &page(0, None, SortOrder::Ascending),
+            |_| true,
+            |x| x.0,
+        );
+        assert_eq!(entries_1, vec![(0, 'b'), (0, 'd'), (1, 'a'), (1, 'c')]);
+
+        let (entries_2, _) = list(
+            entries,
+            &page(0, None, SortOrder::Descending),
+            |_| true,
+            |x| x.0,
+        );
+        assert_eq!(entries_2, vec![(1, 'a'), (1, 'c'), (0, 'b'), (0, 'd')]);
+    }
+
+    #[test]
+    fn within_age_should_include_bounds() {
+        let age = Duration::from_secs(10);
+        assert!(within_age(age, None, None));
+        assert!(within_age(age, Some(10), Some(10)));
+        assert!(!within_age(age, Some(11), None));
+        assert!(!within_age(age, None, Some(9)));
+    }
+}
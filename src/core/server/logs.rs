@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+/// Name of the directory (within the temp directory) used to store logs when
+/// no other directory is configured
+pub const DEFAULT_LOGS_DIR_NAME: &str = "over-there-logs";
+
+/// Default size in bytes that a log file can reach before it is rotated
+/// (10 MiB)
+pub const DEFAULT_MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Default number of files kept for each log, including the file currently
+/// being appended to
+pub const DEFAULT_MAX_LOG_FILES: usize = 5;
+
+/// Append-only logs stored as size-rotated files, where operations on the
+/// same log are serialized so that lines from many clients never interleave
+#[derive(Debug)]
+pub struct LogSinks {
+    /// Directory containing the files of every log
+    dir: PathBuf,
+
+    max_file_size: u64,
+    max_files: usize,
+
+    /// Lock of each log by name, held while appending to or reading it
+    locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl Default for LogSinks {
+    fn default() -> Self {
+        Self::new(default_logs_dir())
+    }
+}
+
+/// Directory used to store logs when no other directory is configured
+pub fn default_logs_dir() -> PathBuf {
+    std::env::temp_dir().join(DEFAULT_LOGS_DIR_NAME)
+}
+
+impl LogSinks {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_file_size: DEFAULT_MAX_LOG_FILE_SIZE,
+            max_files: DEFAULT_MAX_LOG_FILES,
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the size a log file can reach before it is rotated and the
+    /// number of files kept for each log, which is at least one
+    pub fn set_limits(
+        &mut self,
+        max_file_size: u64,
+        max_files: usize,
+    ) -> &mut Self {
+        self.max_file_size = max_file_size;
+        self.max_files = max_files.max(1);
+        self
+    }
+
+    /// Appends `lines` to the log `name`, first rotating its file if the
+    /// lines would grow it beyond the maximum file size
+    pub async fn append(&self, name: &str, lines: &[String]) -> io::Result<()> {
+        validate_name(name)?;
+        if lines.iter().any(|line| line.contains('\n')) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Log lines cannot contain newlines",
+            ));
+        }
+
+        let data: String =
+            lines.iter().map(|line| line.clone() + "\n").collect();
+
+        let lock = self.lock(name).await;
+        let _guard = lock.lock().await;
+
+        fs::create_dir_all(&self.dir).await?;
+        let path = self.path(name, 0);
+        let size = match fs::metadata(&path).await {
+            Ok(metadata) => metadata.len(),
+            Err(x) if x.kind() == io::ErrorKind::NotFound => 0,
+            Err(x) => return Err(x),
+        };
+
+        // A single append larger than the maximum size still goes into one
+        // file rather than being split across files
+        if size > 0 && size + data.len() as u64 > self.max_file_size {
+            self.rotate(name).await?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(data.as_bytes()).await?;
+        file.flush().await
+    }
+
+    /// Reads up to `max_lines` lines of the log `name` starting at line
+    /// `start`, returning them along with the total lines in the log
+    ///
+    /// Lines are numbered from the oldest line still kept, so rotation
+    /// shifts the number of every line that remains
+    pub async fn read_range(
+        &self,
+        name: &str,
+        start: u64,
+        max_lines: Option<u64>,
+    ) -> io::Result<(Vec<String>, u64)> {
+        validate_name(name)?;
+
+        let lock = self.lock(name).await;
+        let _guard = lock.lock().await;
+
+        let mut found = false;
+        let mut total = 0;
+        let mut lines = Vec::new();
+        for index in (0..self.max_files).rev() {
+            let data = match fs::read_to_string(self.path(name, index)).await {
+                Ok(data) => data,
+                Err(x) if x.kind() == io::ErrorKind::NotFound => continue,
+                Err(x) => return Err(x),
+            };
+            found = true;
+
+            for line in data.lines() {
+                let within_max =
+                    (lines.len() as u64) < max_lines.unwrap_or(u64::MAX);
+                if total >= start && within_max {
+                    lines.push(line.to_string());
+                }
+                total += 1;
+            }
+        }
+
+        if !found {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No log named {}", name),
+            ));
+        }
+
+        Ok((lines, total))
+    }
+
+    /// Shifts each file of the log `name` to the next oldest position,
+    /// discarding the oldest file once the maximum number of files is kept
+    async fn rotate(&self, name: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(name, self.max_files - 1)).await {
+            Err(x) if x.kind() != io::ErrorKind::NotFound => return Err(x),
+            _ => {}
+        }
+
+        for index in (0..self.max_files - 1).rev() {
+            let from = self.path(name, index);
+            if fs::metadata(&from).await.is_ok() {
+                fs::rename(&from, self.path(name, index + 1)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Path of the file at `index` of the log `name`, where the file at
+    /// index 0 is the one being appended to and higher indexes are older
+    fn path(&self, name: &str, index: usize) -> PathBuf {
+        if index == 0 {
+            self.dir.join(format!("{}.log", name))
+        } else {
+            self.dir.join(format!("{}.log.{}", name, index))
+        }
+    }
+
+    async fn lock(&self, name: &str) -> Arc<Mutex<()>> {
+        Arc::clone(
+            self.locks
+                .lock()
+                .await
+                .entry(name.to_string())
+                .or_insert_with(Default::default),
+        )
+    }
+}
+
+/// Ensures that `name` can only refer to a file directly within the logs
+/// directory
+fn validate_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid log name: {:?}", name),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn append_should_rotate_files_beyond_max_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut logs = LogSinks::new(dir.path());
+        logs.set_limits(8, 2);
+
+        logs.append("app", &lines(&["one", "two"])).await.unwrap();
+        logs.append("app", &lines(&["three"])).await.unwrap();
+        logs.append("app", &lines(&["four"])).await.unwrap();
+
+        // The file containing "one" and "two" has been discarded
+        let (read, total) = logs.read_range("app", 0, None).await.unwrap();
+        assert_eq!(read, lines(&["three", "four"]));
+        assert_eq!(total, 2);
+        assert!(!dir.path().join("app.log.2").exists());
+    }
+
+    #[tokio::test]
+    async fn append_should_serialize_concurrent_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = Arc::new(LogSinks::new(dir.path()));
+
+        let appends = (0..10).map(|i| {
+            let logs = Arc::clone(&logs);
+            tokio::spawn(async move {
+                let batch = vec![format!("{}-a", i), format!("{}-b", i)];
+                logs.append("app", &batch).await.unwrap();
+            })
+        });
+        futures::future::join_all(appends).await;
+
+        // Lines of each append remain adjacent
+        let (read, total) = logs.read_range("app", 0, None).await.unwrap();
+        assert_eq!(total, 20);
+        for pair in read.chunks(2) {
+            assert_eq!(
+                pair[0].trim_end_matches("-a"),
+                pair[1].trim_end_matches("-b")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn read_range_should_return_requested_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogSinks::new(dir.path());
+        logs.append("app", &lines(&["a", "b", "c", "d"]))
+            .await
+            .unwrap();
+
+        let (read, total) = logs.read_range("app", 1, Some(2)).await.unwrap();
+        assert_eq!(read, lines(&["b", "c"]));
+        assert_eq!(total, 4);
+
+        let err = logs.read_range("missing", 0, None).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn append_should_reject_invalid_names_and_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogSinks::new(dir.path());
+
+        for name in &["", "../escape", ".hidden", "a/b"] {
+            let err = logs.append(name, &lines(&["x"])).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", name);
+        }
+
+        let err = logs.append("app", &lines(&["a\nb"])).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
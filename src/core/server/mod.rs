@@ -1,14 +1,46 @@
 mod action;
+pub mod backup;
+pub mod config;
 mod custom;
+pub mod activation;
+pub mod eviction;
 pub mod fs;
+pub mod job;
+pub mod launcher;
 mod listening;
+pub mod listing;
+pub mod logs;
+pub mod origins;
+pub mod permissions;
+pub mod power;
+pub mod privilege;
 pub mod proc;
+pub mod proc_info;
+pub mod pty;
+pub mod relay;
+pub mod schedule;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "seccomp")]
+pub mod seccomp;
+pub mod signing;
 pub mod state;
+pub mod transfers;
+pub mod trusted;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod webhook;
 
 pub use listening::ListeningServer;
 
-use crate::core::transport::{Authenticator, Bicrypter, NetTransmission, Wire};
-use crate::core::{event::AddrEventManager, Msg, Transport};
+use crate::core::transport::{
+    auth::identity::IdentityKey, AssemblyConfig, Authenticator, Bicrypter,
+    NetTransmission, SocketOptions, Wire,
+};
+use crate::core::{
+    event::{AddrEventManager, InboundAddrMsg},
+    Msg, Reply, Transport,
+};
 use derive_builder::Builder;
 use log::error;
 use std::net::SocketAddr;
@@ -22,6 +54,20 @@ use tokio::{
     time,
 };
 
+/// Target of log entries recorded for auditing, such as those of scripts
+/// calling `audit` and of power actions requested by clients
+pub const AUDIT_LOG_TARGET: &str = "over_there::audit";
+
+/// Interval at which schedules are checked for runs that are due
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval at which procs are checked for exits to report to webhooks
+const WEBHOOK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval at which procs are checked for exits to tell the origins that
+/// started them of
+const PROC_EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Represents a server configuration prior to listening
 #[derive(Builder, Clone)]
 pub struct Server<A, B>
@@ -33,6 +79,29 @@ where
     #[builder(default = "crate::core::transport::constants::DEFAULT_TTL")]
     packet_ttl: Duration,
 
+    /// Whether the TTL of a msg is extended each time one of its packets
+    /// arrives, rather than being fixed from the first packet
+    #[builder(default = "true")]
+    adaptive_packet_ttl: bool,
+
+    /// Maximum number of msgs whose packets can be collected at once
+    #[builder(
+        default = "crate::core::transport::constants::DEFAULT_MAX_PACKET_GROUPS"
+    )]
+    max_packet_groups: usize,
+
+    /// Maximum number of packets that can make up a single msg
+    #[builder(
+        default = "crate::core::transport::constants::DEFAULT_MAX_PACKETS_PER_GROUP"
+    )]
+    max_packets_per_msg: usize,
+
+    /// Minimum time between removals of msgs whose packets expired
+    #[builder(
+        default = "crate::core::transport::constants::DEFAULT_PACKET_GC_INTERVAL"
+    )]
+    packet_gc_interval: Duration,
+
     /// Used to sign & verify msgs
     authenticator: A,
 
@@ -42,6 +111,32 @@ where
     /// Transportation mechanism & address to listen on
     transport: Transport,
 
+    /// Whether to listen on a socket handed to the server by the service
+    /// manager that started it, such as through systemd socket activation,
+    /// rather than binding an address of the transport, where only the kind
+    /// of transport is used
+    #[builder(default)]
+    socket_activation: bool,
+
+    /// Name of the socket handed to the server to listen on, which launchd
+    /// requires, where the first of the kind of transport is used if not
+    /// provided
+    #[builder(setter(into, strip_option), default)]
+    activation_socket_name: Option<String>,
+
+    /// User and group to permanently drop privileges to once sockets are
+    /// bound and files such as logs are opened, letting a server bind low
+    /// ports as root without remaining root
+    #[builder(private, setter(name = "run_as_config", strip_option), default)]
+    run_as: Option<privilege::RunAs>,
+
+    /// Whether to restrict the system calls of the server to those it needs
+    /// once it is listening, where the server fails to listen if the filter
+    /// cannot be installed
+    #[cfg(feature = "seccomp")]
+    #[builder(default)]
+    syscall_filter: bool,
+
     /// Internal buffer for cross-thread messaging
     #[builder(default = "1000")]
     buffer: usize,
@@ -62,9 +157,168 @@ where
     #[builder(default = "state::constants::DEFAULT_DEAD_PROC_TTL")]
     dead_proc_ttl: Duration,
 
+    /// Maximum depth of sequence and batch operations nested within a request
+    #[builder(default = "state::constants::DEFAULT_MAX_REQUEST_DEPTH")]
+    max_request_depth: u8,
+
+    /// Maximum total of operations nested within a request across all
+    /// depths, preventing wide but shallow requests from amplifying work
+    #[builder(default = "state::constants::DEFAULT_MAX_NESTED_OPERATIONS")]
+    max_nested_operations: usize,
+
     /// Handler to use for custom msgs
     #[builder(setter(strip_option), default)]
     custom_handler: Option<custom::CustomHandler>,
+
+    /// If provided, will spawn the procs requested of the server, such as
+    /// to restrict which commands can run
+    #[builder(setter(strip_option), default)]
+    proc_launcher: Option<launcher::ProcLauncher>,
+
+    /// If provided, will manage the files and directories of the server,
+    /// such as a manager configured by an application embedding the server,
+    /// where default file and dir modes given to the builder still apply
+    #[builder(setter(strip_option), default)]
+    fs_manager: Option<fs::FileSystemManager>,
+
+    /// Permission bits applied to files created by the server, rather than
+    /// inheriting the umask of the server process
+    #[builder(setter(strip_option), default)]
+    default_file_mode: Option<u32>,
+
+    /// Permission bits applied to directories created by the server, rather
+    /// than inheriting the umask of the server process
+    #[builder(setter(strip_option), default)]
+    default_dir_mode: Option<u32>,
+
+    /// Policy for retrying filesystem operations that fail with transient
+    /// errors, such as a file briefly locked by another process, rather
+    /// than making a single attempt
+    #[builder(setter(strip_option), default)]
+    fs_retry_policy: Option<fs::RetryPolicy>,
+
+    /// Directory where the output and outcome of jobs are stored, defaulting
+    /// to a directory within the system's temp directory
+    #[builder(setter(into, strip_option), default)]
+    jobs_dir: Option<std::path::PathBuf>,
+
+    /// Directory where logs appended to by clients are stored, defaulting
+    /// to a directory within the system's temp directory
+    #[builder(setter(into, strip_option), default)]
+    logs_dir: Option<std::path::PathBuf>,
+
+    /// Size in bytes that a log file can reach before it is rotated
+    #[builder(default = "logs::DEFAULT_MAX_LOG_FILE_SIZE")]
+    max_log_file_size: u64,
+
+    /// Number of files kept for each log, including the file currently
+    /// being appended to
+    #[builder(default = "logs::DEFAULT_MAX_LOG_FILES")]
+    max_log_files: usize,
+
+    /// Endpoints notified via HTTP POST of events such as procs exiting
+    #[builder(default)]
+    webhooks: Vec<webhook::Webhook>,
+
+    /// Key used to prove the identity of the server to clients that pin its
+    /// public half
+    #[builder(setter(strip_option), default)]
+    identity_key: Option<IdentityKey>,
+
+    /// Policy applied to the signatures of msgs, which authenticate their
+    /// original sender even when relayed by other hops
+    #[builder(default)]
+    signature_policy: signing::SignaturePolicy,
+
+    /// Whether udp origins must echo back a nonce sent by the server before
+    /// it accepts requests from them that change anything, guarding against
+    /// spoofed origins
+    #[builder(default)]
+    strict_origin_binding: bool,
+
+    /// Time a bound udp origin can go without communicating with the server
+    /// before it must echo back a new nonce
+    #[builder(default = "origins::DEFAULT_BINDING_TTL")]
+    origin_binding_ttl: Duration,
+
+    /// Client keys trusted by the server, where msgs not signed by one of
+    /// them are rejected; any client is accepted if not provided
+    #[builder(setter(strip_option), default)]
+    trusted_clients: Option<trusted::TrustedClients>,
+
+    /// Maximum ratio of bytes sent to an unbound udp origin to bytes
+    /// received from it, guarding against the server being used to reflect
+    /// and amplify traffic at a spoofed origin; unlimited if not provided
+    #[builder(setter(strip_option), default)]
+    max_amplification: Option<u64>,
+
+    /// Minimum size in bytes of requests from unbound udp origins, where
+    /// smaller requests are ignored
+    #[builder(default)]
+    min_unbound_request_size: u64,
+
+    /// Maximum bytes that any single identity can transfer to and from the
+    /// server, where msgs beyond it are rejected; unlimited if not provided
+    #[builder(setter(strip_option), default)]
+    transfer_quota: Option<u64>,
+
+    /// Number of recent changes to the filesystem kept for clients catching
+    /// up after a gap
+    #[builder(default = "fs::events::DEFAULT_FS_EVENT_HISTORY")]
+    fs_event_history: usize,
+
+    /// Socket-level options such as tcp keepalive and kernel buffer sizes,
+    /// applied to the udp socket or to each accepted tcp connection
+    #[builder(default)]
+    socket_options: SocketOptions,
+
+    /// Store of the config pushed by operators, which can be shared with
+    /// custom handlers, defaulting to one kept only in memory
+    #[builder(setter(strip_option), default)]
+    config_store: Option<config::ConfigStore>,
+
+    /// Admins allowed to reboot or shut down the host running the server,
+    /// where no client can if not provided
+    #[builder(setter(strip_option), default)]
+    power_control: Option<power::PowerControl>,
+
+    /// What clients are allowed to do on the server, where everything is
+    /// allowed by default
+    #[builder(default)]
+    permissions: permissions::Permissions,
+
+    /// Relay through which requests forwarded through the server are sent
+    /// to the servers they are addressed to, where forwarded requests are
+    /// rejected if not provided
+    #[builder(setter(strip_option), default)]
+    relay: Option<relay::Relay>,
+
+    /// Directory that wasm handlers can read and write files within, where
+    /// handlers cannot access any files if not provided
+    #[cfg(feature = "wasm")]
+    #[builder(setter(into, strip_option), default)]
+    wasm_root: Option<std::path::PathBuf>,
+
+    /// Script whose hooks inspect and rewrite each request and reply
+    #[cfg(feature = "script")]
+    #[builder(setter(strip_option), default)]
+    script: Option<script::ScriptHooks>,
+}
+
+impl<A, B> ServerBuilder<A, B>
+where
+    A: Authenticator + Clone,
+    B: Bicrypter + Clone,
+{
+    /// Runs the server as `user`, and `group` if provided or otherwise the
+    /// primary group of the user, once it has bound its sockets, where the
+    /// server fails to listen if the privileges cannot be dropped
+    pub fn run_as(&mut self, user: &str, group: Option<&str>) -> &mut Self {
+        self.run_as_config(privilege::RunAs {
+            user: user.to_string(),
+            group: group.map(ToString::to_string),
+        })
+    }
 }
 
 impl<A, B> Server<A, B>
@@ -72,6 +326,16 @@ where
     A: Authenticator + Send + Sync + 'static,
     B: Bicrypter + Send + Sync + 'static,
 {
+    fn assembly_config(&self) -> AssemblyConfig {
+        AssemblyConfig {
+            packet_ttl: self.packet_ttl,
+            adaptive_ttl: self.adaptive_packet_ttl,
+            max_groups: self.max_packet_groups,
+            max_packets_per_group: self.max_packets_per_msg,
+            gc_interval: self.packet_gc_interval,
+        }
+    }
+
     fn make_state(&self) -> Arc<state::ServerState> {
         let mut state = state::ServerState::new(
             self.file_ttl,
@@ -79,10 +343,85 @@ where
             self.dead_proc_ttl,
         );
 
+        state.set_request_limits(
+            self.max_request_depth,
+            self.max_nested_operations,
+        );
+
         if let Some(custom_handler) = self.custom_handler.clone() {
             state.set_custom_handler(custom_handler);
         }
 
+        if let Some(proc_launcher) = self.proc_launcher.clone() {
+            state.set_proc_launcher(proc_launcher);
+        }
+
+        let mut fs_manager = self.fs_manager.clone().unwrap_or_default();
+        if self.default_file_mode.is_some() {
+            fs_manager.set_default_file_mode(self.default_file_mode);
+        }
+        if self.default_dir_mode.is_some() {
+            fs_manager.set_default_dir_mode(self.default_dir_mode);
+        }
+        if let Some(policy) = self.fs_retry_policy {
+            fs_manager.set_retry_policy(policy);
+        }
+        fs_manager.set_allowed_paths(self.permissions.allowed_paths.clone());
+        state.set_fs_manager(fs_manager);
+
+        if let Some(jobs_dir) = self.jobs_dir.clone() {
+            state.set_schedules(schedule::ScheduleManager::new(&jobs_dir));
+            state.set_jobs(job::JobManager::new(jobs_dir));
+        }
+
+        let mut logs = match self.logs_dir.clone() {
+            Some(logs_dir) => logs::LogSinks::new(logs_dir),
+            None => logs::LogSinks::default(),
+        };
+        logs.set_limits(self.max_log_file_size, self.max_log_files);
+        state.set_logs(logs);
+
+        state.set_webhooks(webhook::Webhooks::new(self.webhooks.clone()));
+        state.set_identity_key(self.identity_key.clone());
+        state.set_signature_policy(self.signature_policy.clone());
+        if let Some(trusted_clients) = self.trusted_clients.clone() {
+            state.set_trusted_clients(trusted_clients);
+        }
+        if let Transport::Udp(_) = self.transport {
+            state.set_origins(
+                origins::OriginBindings::new(self.strict_origin_binding)
+                    .with_max_amplification(self.max_amplification)
+                    .with_min_request_size(self.min_unbound_request_size)
+                    .with_binding_ttl(self.origin_binding_ttl),
+            );
+        }
+        state.set_transfers(transfers::TransferAccounting::new(
+            self.transfer_quota,
+        ));
+        state.set_fs_events(fs::events::FsEventHistory::new(
+            self.fs_event_history,
+        ));
+
+        if let Some(config_store) = self.config_store.clone() {
+            state.set_config(config_store);
+        }
+
+        if let Some(power_control) = self.power_control.clone() {
+            state.set_power(power_control);
+        }
+        state.set_permissions(self.permissions.clone());
+
+        if let Some(relay) = self.relay.clone() {
+            state.set_relay(relay);
+        }
+
+        #[cfg(feature = "wasm")]
+        state
+            .set_wasm_handlers(wasm::WasmHandlers::new(self.wasm_root.clone()));
+
+        #[cfg(feature = "script")]
+        state.set_script(self.script.clone());
+
         Arc::new(state)
     }
 
@@ -95,6 +434,9 @@ where
         let state = self.make_state();
 
         handle.spawn(cleanup_loop(Arc::clone(&state), self.cleanup_interval));
+        handle
+            .spawn(schedule_loop(Arc::clone(&state), SCHEDULE_CHECK_INTERVAL));
+        handle.spawn(webhook_loop(Arc::clone(&state), WEBHOOK_CHECK_INTERVAL));
 
         match self.transport.clone() {
             Transport::Tcp(_) => Err(io::Error::new(
@@ -120,6 +462,9 @@ where
         let state = self.make_state();
 
         handle.spawn(cleanup_loop(Arc::clone(&state), self.cleanup_interval));
+        handle
+            .spawn(schedule_loop(Arc::clone(&state), SCHEDULE_CHECK_INTERVAL));
+        handle.spawn(webhook_loop(Arc::clone(&state), WEBHOOK_CHECK_INTERVAL));
 
         match self.transport.clone() {
             Transport::Tcp(addrs) => {
@@ -132,6 +477,25 @@ where
     }
 }
 
+/// Drops privileges and restricts the server as configured, which must be
+/// done once sockets are bound and before any msg is received
+fn harden<A, B>(server: &Server<A, B>) -> io::Result<()>
+where
+    A: Authenticator,
+    B: Bicrypter,
+{
+    if let Some(run_as) = server.run_as.as_ref() {
+        privilege::drop_privileges(run_as)?;
+    }
+
+    #[cfg(feature = "seccomp")]
+    if server.syscall_filter {
+        seccomp::install()?;
+    }
+
+    Ok(())
+}
+
 async fn build_and_listen_tcp_server<A, B>(
     server: Server<A, B>,
     state: Arc<state::ServerState>,
@@ -146,7 +510,11 @@ where
     // NOTE: Tokio does not support &[SocketAddr] -> ToSocketAddrs,
     //       so we have to loop through manually
     // See https://github.com/tokio-rs/tokio/pull/1760#discussion_r379120864
-    let listener = {
+    let listener = if server.socket_activation {
+        let name = server.activation_socket_name.as_deref();
+        let listener = activation::tcp_listener(name)?;
+        handle.enter(|| TcpListener::from_std(listener))?
+    } else {
         let mut listener = None;
         for addr in addrs.iter() {
             let result = TcpListener::bind(addr).await;
@@ -159,23 +527,37 @@ where
             .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?
     };
     let addr = listener.local_addr()?;
+    harden(&server)?;
 
+    let transmission = NetTransmission::TcpEthernet;
+    let max_msg_size = transmission.max_msg_size();
+    let assembly = server.assembly_config();
     let wire = Wire::new(
-        NetTransmission::TcpEthernet.into(),
+        transmission.into(),
         server.packet_ttl,
         server.authenticator,
         server.bicrypter,
-    );
+    )
+    .with_max_msg_size(max_msg_size)
+    .with_assembly_config(assembly);
 
     let (tx, rx) = mpsc::channel(server.buffer);
-    let event_handle = handle.spawn(tcp_event_loop(Arc::clone(&state), rx));
+    let event_handle =
+        handle.spawn(tcp_event_loop(Arc::clone(&state), rx, max_msg_size));
     let addr_event_manager = AddrEventManager::for_tcp_listener(
         handle.clone(),
         server.buffer,
         listener,
         wire,
         tx,
+        state.connection_tasks.clone(),
+        server.socket_options,
     );
+    handle.spawn(proc_exit_loop(
+        Arc::clone(&state),
+        addr_event_manager.sender(),
+        PROC_EXIT_CHECK_INTERVAL,
+    ));
 
     Ok(ListeningServer {
         addr,
@@ -200,29 +582,46 @@ where
     //       so we have to loop through manually
     // See https://github.com/tokio-rs/tokio/pull/1760#discussion_r379120864
     let socket = {
-        let mut socket = None;
-        for addr in addrs.iter() {
-            let result = UdpSocket::bind(addr).await;
-            if result.is_ok() {
-                socket = result.ok();
-                break;
+        let socket = if server.socket_activation {
+            let name = server.activation_socket_name.as_deref();
+            activation::udp_socket(name)?
+        } else {
+            let mut socket = None;
+            for addr in addrs.iter() {
+                let result = std::net::UdpSocket::bind(addr);
+                if result.is_ok() {
+                    socket = result.ok();
+                    break;
+                }
             }
-        }
-        socket
-            .ok_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable))?
+            socket.ok_or_else(|| {
+                io::Error::from(io::ErrorKind::AddrNotAvailable)
+            })?
+        };
+        let socket = server.socket_options.apply_to_udp_socket(socket)?;
+
+        // NOTE: Must use Handle::enter to provide proper runtime when
+        //       using UdpSocket::from_std
+        handle.enter(|| UdpSocket::from_std(socket))?
     };
     let addr = socket.local_addr()?;
+    harden(&server)?;
     let transmission = NetTransmission::udp_from_addr(addr);
+    let max_msg_size = transmission.max_msg_size();
 
+    let assembly = server.assembly_config();
     let wire = Wire::new(
         transmission.into(),
         server.packet_ttl,
         server.authenticator,
         server.bicrypter,
-    );
+    )
+    .with_max_msg_size(max_msg_size)
+    .with_assembly_config(assembly);
 
     let (tx, rx) = mpsc::channel(server.buffer);
-    let event_handle = handle.spawn(udp_event_loop(Arc::clone(&state), rx));
+    let event_handle =
+        handle.spawn(udp_event_loop(Arc::clone(&state), rx, max_msg_size));
     let addr_event_manager = AddrEventManager::for_udp_socket(
         handle.clone(),
         server.buffer,
@@ -230,6 +629,11 @@ where
         wire,
         tx,
     );
+    handle.spawn(proc_exit_loop(
+        Arc::clone(&state),
+        addr_event_manager.sender(),
+        PROC_EXIT_CHECK_INTERVAL,
+    ));
 
     Ok(ListeningServer {
         addr,
@@ -242,15 +646,13 @@ where
 async fn tcp_event_loop(
     state: Arc<state::ServerState>,
     mut rx: mpsc::Receiver<(Msg, SocketAddr, mpsc::Sender<Vec<u8>>)>,
+    max_msg_size: usize,
 ) {
     while let Some((msg, addr, tx)) = rx.recv().await {
-        if let Err(x) = action::Executor::<Vec<u8>>::new(
-            tx,
-            addr,
-            action::Executor::<Vec<u8>>::DEFAULT_MAX_DEPTH,
-        )
-        .execute(Arc::clone(&state), msg)
-        .await
+        let _guard = state.tasks.track();
+        if let Err(x) = action::Executor::<Vec<u8>>::new(tx, addr, max_msg_size)
+            .execute(Arc::clone(&state), msg)
+            .await
         {
             error!("Failed to execute action: {}", x);
         }
@@ -259,17 +661,15 @@ async fn tcp_event_loop(
 
 async fn udp_event_loop(
     state: Arc<state::ServerState>,
-    mut rx: mpsc::Receiver<(
-        Msg,
-        SocketAddr,
-        mpsc::Sender<(Vec<u8>, SocketAddr)>,
-    )>,
+    mut rx: mpsc::Receiver<InboundAddrMsg>,
+    max_msg_size: usize,
 ) {
     while let Some((msg, addr, tx)) = rx.recv().await {
+        let _guard = state.tasks.track();
         if let Err(x) = action::Executor::<(Vec<u8>, SocketAddr)>::new(
             tx,
             addr,
-            action::Executor::<(Vec<u8>, SocketAddr)>::DEFAULT_MAX_DEPTH,
+            max_msg_size,
         )
         .execute(Arc::clone(&state), msg)
         .await
@@ -279,10 +679,56 @@ async fn udp_event_loop(
     }
 }
 
+async fn schedule_loop(state: Arc<state::ServerState>, period: Duration) {
+    while state.is_running() {
+        if let Err(x) = state.schedules.run_due(&state.jobs, &state.tasks).await
+        {
+            error!("Failed to run scheduled jobs: {}", x);
+        }
+        time::delay_for(period).await;
+    }
+}
+
+async fn webhook_loop(state: Arc<state::ServerState>, period: Duration) {
+    while state.is_running() {
+        // Webhooks can be added while running, so keep checking even if
+        // there are none to report to yet
+        if !state.webhooks.is_empty() {
+            state.report_proc_exits().await;
+        }
+        time::delay_for(period).await;
+    }
+}
+
+/// Tells the origin that started each proc of its exit, sending the status
+/// through `tx` as a msg that is not a reply to any request
+async fn proc_exit_loop(
+    state: Arc<state::ServerState>,
+    mut tx: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+    period: Duration,
+) {
+    while state.is_running() {
+        for (origin, status) in state.take_proc_exits().await {
+            let id = status.id;
+            match Msg::from(Reply::ProcStatus(status)).to_vec() {
+                Ok(data) => {
+                    if tx.send((data, origin)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(x) => error!("Failed to encode exit of proc {}: {}", id, x),
+            }
+        }
+        time::delay_for(period).await;
+    }
+}
+
 async fn cleanup_loop(state: Arc<state::ServerState>, period: Duration) {
     while state.is_running() {
         state.evict_files().await;
         state.evict_procs().await;
+        state.evict_sessions().await;
+        state.origins.evict_expired().await;
         time::delay_for(period).await;
     }
 }
@@ -314,11 +760,8 @@ mod tests {
 
         // Verify expected files were evicted
         let file_ids = state.file_ids.lock().await;
-        assert!(!file_ids.contains(&From::from(0)), "File not evicted");
-        assert!(
-            file_ids.contains(&From::from(1)),
-            "File unexpectedly evicted"
-        );
+        assert!(!file_ids.contains_key(&0), "File not evicted");
+        assert!(file_ids.contains_key(&1), "File unexpectedly evicted");
     }
 
     #[tokio::test]
@@ -344,11 +787,8 @@ mod tests {
 
         // Verify expected files were evicted
         let proc_ids = state.proc_ids.lock().await;
-        assert!(!proc_ids.contains(&From::from(0)), "Proc not evicted");
-        assert!(
-            proc_ids.contains(&From::from(1)),
-            "Proc unexpectedly evicted"
-        );
+        assert!(!proc_ids.contains_key(&0), "Proc not evicted");
+        assert!(proc_ids.contains_key(&1), "Proc unexpectedly evicted");
     }
 
     #[tokio::test]
@@ -366,12 +806,15 @@ mod tests {
         Handle::current()
             .spawn(cleanup_loop(Arc::clone(&state), Duration::from_millis(1)));
 
-        assert!(
-            state.file_ids.lock().await.contains(&From::from(0)),
+        // Expired ids are hidden from lookups until evicted, so count them
+        assert_eq!(
+            state.file_ids.lock().await.len(),
+            1,
             "File unexpectedly evicted"
         );
-        assert!(
-            state.proc_ids.lock().await.contains(&From::from(0)),
+        assert_eq!(
+            state.proc_ids.lock().await.len(),
+            1,
             "Proc unexpectedly evicted"
         );
     }
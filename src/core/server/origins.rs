@@ -0,0 +1,406 @@
+use crate::core::{
+    reply::OriginBindingArgs,
+    request::{signed_nonce, BindOriginArgs},
+    transport::auth::identity,
+};
+use crate::utils::TtlMap;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Time within which the nonce of a challenge must be echoed back
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(30);
+
+/// Default time that a bound origin can go without communicating with the
+/// server before it must be bound again (30 min)
+pub const DEFAULT_BINDING_TTL: Duration = Duration::from_secs(60 * 30);
+
+/// Maximum challenges awaiting an echo at once, bounding the memory that
+/// spoofed origins can make the server hold
+pub const MAX_PENDING_CHALLENGES: usize = 4096;
+
+/// Maximum unbound origins whose traffic is tracked at once, beyond which
+/// replies to untracked origins are dropped
+pub const MAX_TRACKED_ORIGINS: usize = 4096;
+
+/// Size in bytes of the nonce of a challenge
+const NONCE_SIZE: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Binding {
+    /// Origin was sent a nonce that it has yet to echo back, which expires
+    /// after `CHALLENGE_TTL`
+    Challenged { nonce: Vec<u8> },
+
+    /// Origin echoed back its nonce signed, proving that it receives
+    /// replies, which expires once the origin goes quiet
+    Bound,
+}
+
+/// Bytes exchanged with an origin that has yet to be bound
+#[derive(Copy, Clone, Debug)]
+struct Traffic {
+    bytes_in: u64,
+    bytes_out: u64,
+    last_seen: Instant,
+}
+
+/// Origins that have proven they receive the replies sent to them
+///
+/// The source of a udp packet can be spoofed, so when strict, an origin
+/// must echo back a nonce sent to it, signed with an identity key, before
+/// requests that change the server are accepted from it; otherwise a
+/// spoofed packet could trigger actions or direct replies at a victim. A
+/// binding lasts until its origin goes quiet for the binding ttl, as the
+/// same address may later belong to someone else. Tcp origins are proven
+/// by the handshake of their connection, so the bindings of a tcp server
+/// are never strict.
+///
+/// A spoofed origin can also be used to reflect large replies at a victim,
+/// so the bytes sent to an unbound origin can be capped at a multiple of the
+/// bytes received from it, and requests from it below a minimum size can be
+/// ignored altogether.
+#[derive(Debug)]
+pub struct OriginBindings {
+    strict: bool,
+    max_amplification: Option<u64>,
+    min_request_size: u64,
+    origins: Mutex<TtlMap<SocketAddr, Binding>>,
+    traffic: Mutex<HashMap<SocketAddr, Traffic>>,
+}
+
+impl Default for OriginBindings {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            max_amplification: None,
+            min_request_size: 0,
+            origins: Mutex::new(TtlMap::new(DEFAULT_BINDING_TTL)),
+            traffic: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl OriginBindings {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            ..Default::default()
+        }
+    }
+
+    /// Caps the bytes sent to an unbound origin at `ratio` times the bytes
+    /// received from it
+    pub fn with_max_amplification(mut self, ratio: Option<u64>) -> Self {
+        self.max_amplification = ratio;
+        self
+    }
+
+    /// Ignores requests from unbound origins smaller than `size` bytes
+    pub fn with_min_request_size(mut self, size: u64) -> Self {
+        self.min_request_size = size;
+        self
+    }
+
+    /// Unbinds origins once they go `ttl` without communicating
+    pub fn with_binding_ttl(mut self, ttl: Duration) -> Self {
+        self.origins = Mutex::new(TtlMap::new(ttl));
+        self
+    }
+
+    /// Whether origins must be bound before changing the server
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Whether origins are bound by echoing back a nonce, which is the case
+    /// if anything is withheld from unbound origins
+    pub fn is_challenging(&self) -> bool {
+        self.strict
+            || self.max_amplification.is_some()
+            || self.min_request_size > 0
+    }
+
+    pub async fn is_bound(&self, origin: SocketAddr) -> bool {
+        match self.origins.lock().await.get(&origin) {
+            Some(binding) => binding.value == Binding::Bound,
+            None => false,
+        }
+    }
+
+    /// Keeps the binding of `origin` alive, as it just communicated with
+    /// the server; challenges are not renewed so that they still expire
+    pub async fn touch(&self, origin: SocketAddr) {
+        if let Some(binding) = self.origins.lock().await.get_mut(&origin) {
+            if binding.value == Binding::Bound {
+                binding.touch();
+            }
+        }
+    }
+
+    /// Forgets the bindings and challenges of origins that have expired,
+    /// yielding the origins that were bound
+    pub async fn evict_expired(&self) -> Vec<SocketAddr> {
+        self.origins
+            .lock()
+            .await
+            .evict_expired()
+            .into_iter()
+            .filter(|(_, binding)| *binding == Binding::Bound)
+            .map(|(origin, _)| origin)
+            .collect()
+    }
+
+    /// Records a request of `len` bytes from `origin`, yielding whether it
+    /// is large enough to be answered
+    pub async fn record_request(&self, origin: SocketAddr, len: u64) -> bool {
+        if self.max_amplification.is_none() && self.min_request_size == 0 {
+            return true;
+        }
+
+        if self.is_bound(origin).await {
+            return true;
+        }
+
+        if len < self.min_request_size {
+            return false;
+        }
+
+        if self.max_amplification.is_some() {
+            let mut traffic = self.traffic.lock().await;
+            if traffic.len() >= MAX_TRACKED_ORIGINS
+                && !traffic.contains_key(&origin)
+            {
+                traffic.retain(|_, t| t.last_seen.elapsed() < CHALLENGE_TTL);
+            }
+
+            if traffic.len() < MAX_TRACKED_ORIGINS
+                || traffic.contains_key(&origin)
+            {
+                let t = traffic.entry(origin).or_insert(Traffic {
+                    bytes_in: 0,
+                    bytes_out: 0,
+                    last_seen: Instant::now(),
+                });
+                t.bytes_in = t.bytes_in.saturating_add(len);
+                t.last_seen = Instant::now();
+            }
+        }
+
+        true
+    }
+
+    /// Records a reply of `len` bytes to `origin`, yielding false without
+    /// recording it if it would send an unbound origin more than its share
+    /// of the bytes received from it
+    pub async fn record_reply(&self, origin: SocketAddr, len: u64) -> bool {
+        let ratio = match self.max_amplification {
+            Some(x) => x,
+            None => return true,
+        };
+
+        if self.is_bound(origin).await {
+            return true;
+        }
+
+        let mut traffic = self.traffic.lock().await;
+        match traffic.get_mut(&origin) {
+            Some(t) => {
+                let bytes_out = t.bytes_out.saturating_add(len);
+                if bytes_out > t.bytes_in.saturating_mul(ratio) {
+                    return false;
+                }
+                t.bytes_out = bytes_out;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Binds `origin` if `args` echoes the nonce of the challenge it was
+    /// last sent along with a valid signature of it, otherwise sending it a
+    /// new challenge, failing if too many challenges are already awaiting an
+    /// echo
+    pub async fn bind(
+        &self,
+        origin: SocketAddr,
+        args: &BindOriginArgs,
+    ) -> Result<OriginBindingArgs, String> {
+        let mut origins = self.origins.lock().await;
+
+        let echoed = match origins.get(&origin).map(|x| &x.value) {
+            Some(Binding::Bound) => true,
+            Some(Binding::Challenged { nonce }) => {
+                !args.nonce.is_empty()
+                    && args.nonce == *nonce
+                    && identity::verify(
+                        &args.identity_key,
+                        &signed_nonce(nonce),
+                        &args.signature,
+                    )
+            }
+            None => false,
+        };
+
+        if echoed {
+            origins.insert(origin, Binding::Bound);
+            self.traffic.lock().await.remove(&origin);
+            return Ok(OriginBindingArgs {
+                bound: true,
+                nonce: Vec::new(),
+            });
+        }
+
+        origins.evict_expired();
+
+        let pending = origins
+            .iter()
+            .filter(|(_, b)| matches!(b.value, Binding::Challenged { .. }))
+            .count();
+        if pending >= MAX_PENDING_CHALLENGES && !origins.contains_key(&origin) {
+            return Err(format!(
+                "Too many origins ({}) awaiting challenges",
+                pending
+            ));
+        }
+
+        let mut nonce = vec![0; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        origins.insert_with_ttl(
+            origin,
+            Binding::Challenged {
+                nonce: nonce.clone(),
+            },
+            CHALLENGE_TTL,
+        );
+
+        Ok(OriginBindingArgs {
+            bound: false,
+            nonce,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn new_challenge() -> BindOriginArgs {
+        BindOriginArgs::default()
+    }
+
+    fn echo(nonce: &[u8]) -> BindOriginArgs {
+        let key = identity::IdentityKey::generate();
+        BindOriginArgs {
+            nonce: nonce.to_vec(),
+            identity_key: key.public_key().to_vec(),
+            signature: key.sign(&signed_nonce(nonce)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn bind_should_only_bind_origin_that_echoes_its_nonce() {
+        let origins = OriginBindings::new(true);
+
+        let challenge =
+            origins.bind(origin(1), &new_challenge()).await.unwrap();
+        assert!(!challenge.bound);
+        assert_eq!(challenge.nonce.len(), NONCE_SIZE);
+        assert!(!origins.is_bound(origin(1)).await);
+
+        // Another origin cannot bind itself using a nonce not sent to it
+        let other = origins
+            .bind(origin(2), &echo(&challenge.nonce))
+            .await
+            .unwrap();
+        assert!(!other.bound);
+        assert!(!origins.is_bound(origin(2)).await);
+
+        let reply = origins
+            .bind(origin(1), &echo(&challenge.nonce))
+            .await
+            .unwrap();
+        assert!(reply.bound);
+        assert!(reply.nonce.is_empty());
+        assert!(origins.is_bound(origin(1)).await);
+    }
+
+    #[tokio::test]
+    async fn record_reply_should_cap_bytes_sent_to_unbound_origin() {
+        let origins = OriginBindings::new(false)
+            .with_max_amplification(Some(3))
+            .with_min_request_size(100);
+
+        // Requests below the minimum size are not answered or counted
+        assert!(!origins.record_request(origin(1), 99).await);
+        assert!(!origins.record_reply(origin(1), 1).await);
+
+        assert!(origins.record_request(origin(1), 100).await);
+        assert!(origins.record_reply(origin(1), 200).await);
+        assert!(!origins.record_reply(origin(1), 101).await);
+        assert!(origins.record_reply(origin(1), 100).await);
+
+        // Once bound, an origin is no longer limited
+        let challenge =
+            origins.bind(origin(1), &new_challenge()).await.unwrap();
+        origins
+            .bind(origin(1), &echo(&challenge.nonce))
+            .await
+            .unwrap();
+        assert!(origins.record_request(origin(1), 1).await);
+        assert!(origins.record_reply(origin(1), 1_000_000).await);
+    }
+
+    #[tokio::test]
+    async fn bind_should_issue_new_challenge_if_nonce_does_not_match() {
+        let origins = OriginBindings::new(true);
+
+        let first = origins.bind(origin(1), &new_challenge()).await.unwrap();
+        let second = origins.bind(origin(1), &echo(&[1, 2, 3])).await.unwrap();
+        assert!(!second.bound);
+        assert_ne!(first.nonce, second.nonce);
+
+        // The earlier nonce was replaced by the new challenge
+        let reply = origins.bind(origin(1), &echo(&first.nonce)).await.unwrap();
+        assert!(!reply.bound);
+    }
+
+    #[tokio::test]
+    async fn bind_should_require_echoed_nonce_to_be_signed() {
+        let origins = OriginBindings::new(true);
+        let challenge =
+            origins.bind(origin(1), &new_challenge()).await.unwrap();
+
+        // Nonce is signed, but by a key other than the one presented
+        let mut args = echo(&challenge.nonce);
+        args.signature = echo(&challenge.nonce).signature;
+        assert!(!origins.bind(origin(1), &args).await.unwrap().bound);
+        assert!(!origins.is_bound(origin(1)).await);
+    }
+
+    #[tokio::test]
+    async fn evict_expired_should_unbind_origins_that_go_quiet() {
+        let origins = OriginBindings::new(true)
+            .with_binding_ttl(Duration::from_millis(50));
+        for port in 1..=2 {
+            let challenge = origins.bind(origin(port), &new_challenge()).await;
+            let nonce = challenge.unwrap().nonce;
+            origins.bind(origin(port), &echo(&nonce)).await.unwrap();
+        }
+
+        tokio::time::delay_for(Duration::from_millis(30)).await;
+        origins.touch(origin(2)).await;
+        tokio::time::delay_for(Duration::from_millis(30)).await;
+
+        assert_eq!(origins.evict_expired().await, vec![origin(1)]);
+        assert!(!origins.is_bound(origin(1)).await);
+        assert!(origins.is_bound(origin(2)).await);
+    }
+}
@@ -0,0 +1,439 @@
+#[cfg(not(unix))]
+use super::fs::resolve_path;
+#[cfg(unix)]
+use super::fs::secure::SecureRoot;
+use super::AUDIT_LOG_TARGET;
+use crate::core::{RemotePath, Request};
+use log::warn;
+use std::io;
+use std::path::PathBuf;
+
+/// What clients are allowed to do on the server, checked before each
+/// request, including those nested within others, is executed
+///
+/// Requests that spawn procs count as exec, so once exec is allowed a proc
+/// can still reach anything the server can; only requests that touch the
+/// filesystem directly are held to `allowed_paths`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Permissions {
+    /// Whether procs, scripts, jobs, and schedules can be run, inspected,
+    /// signaled, or given input
+    pub allow_exec: bool,
+
+    /// Whether the server itself can be managed, such as reading or changing
+    /// its config, trusted clients, or handlers, or inspecting its
+    /// connections
+    pub allow_admin: bool,
+
+    /// Whether files and dirs can be read, listed, or inspected
+    pub allow_fs_read: bool,
+
+    /// Whether files and dirs can be created, changed, renamed, or removed
+    pub allow_fs_write: bool,
+
+    /// Dirs that paths of requests must fall within, after applying any
+    /// `..` and without passing through symlinks (which are resolved instead
+    /// on platforms without secure path resolution); any path is allowed if
+    /// empty
+    pub allowed_paths: Vec<PathBuf>,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            allow_exec: true,
+            allow_admin: true,
+            allow_fs_read: true,
+            allow_fs_write: true,
+            allowed_paths: Vec::new(),
+        }
+    }
+}
+
+/// Kind of access a request needs, along with the paths it touches
+#[derive(Debug, PartialEq, Eq)]
+enum Access<'a> {
+    Exec,
+    Admin,
+    Read(Vec<&'a RemotePath>),
+    Write(Vec<&'a RemotePath>),
+}
+
+impl Permissions {
+    /// Fails with `PermissionDenied` if `request` is not allowed, not
+    /// counting any requests nested within it, which are checked as they
+    /// are executed
+    pub async fn check(&self, request: &Request) -> io::Result<()> {
+        let (allowed, kind, paths) = match access_of(request) {
+            None => return Ok(()),
+            Some(Access::Exec) => (self.allow_exec, "exec", Vec::new()),
+            Some(Access::Admin) => (self.allow_admin, "admin", Vec::new()),
+            Some(Access::Read(paths)) => (self.allow_fs_read, "fs read", paths),
+            Some(Access::Write(paths)) => {
+                (self.allow_fs_write, "fs write", paths)
+            }
+        };
+
+        if !allowed {
+            return Err(denied(
+                request,
+                format!("Server does not allow {}", kind),
+            ));
+        }
+
+        for path in paths {
+            if !self.is_path_allowed(path).await {
+                return Err(denied(
+                    request,
+                    format!("Server does not allow access to {}", path),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    async fn is_path_allowed(&self, path: &RemotePath) -> bool {
+        if self.allowed_paths.is_empty() {
+            return true;
+        }
+
+        let path = match std::env::current_dir() {
+            Ok(dir) => dir.join(path.to_path_buf()),
+            Err(_) => return false,
+        };
+
+        self.allowed_paths.iter().any(|allowed| {
+            SecureRoot::open(allowed)
+                .and_then(|root| root.check(path.as_path()))
+                .is_ok()
+        })
+    }
+
+    #[cfg(not(unix))]
+    async fn is_path_allowed(&self, path: &RemotePath) -> bool {
+        if self.allowed_paths.is_empty() {
+            return true;
+        }
+
+        let path = match resolve_path(path.to_path_buf()).await {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+
+        for allowed in self.allowed_paths.iter() {
+            if let Ok(allowed) = resolve_path(allowed).await {
+                if path.starts_with(allowed) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+fn denied(request: &Request, msg: String) -> io::Error {
+    warn!(
+        target: AUDIT_LOG_TARGET,
+        "Rejected {} request: {}",
+        request.class(),
+        msg
+    );
+    io::Error::new(io::ErrorKind::PermissionDenied, msg)
+}
+
+/// Access needed by `request`, or none if it only concerns the protocol
+/// itself or merely carries requests that are checked as they are executed
+///
+/// Requests referring to an open file by id only need the kind of access,
+/// as its path was checked when it was opened. Every request is listed so
+/// that a new one cannot skip the check by being forgotten.
+fn access_of(request: &Request) -> Option<Access<'_>> {
+    Some(match request {
+        Request::Heartbeat
+        | Request::Version
+        | Request::Capabilities
+        | Request::TimeInfo(_)
+        | Request::Identify(_)
+        | Request::Handshake(_)
+        | Request::Sealed(_)
+        | Request::BindOrigin(_)
+        | Request::Unsupported(_)
+        | Request::CloseFile(_)
+        | Request::Sequence(_)
+        | Request::Batch(_) => return None,
+
+        Request::ExecProc(_)
+        | Request::ExecScript(_)
+        | Request::ExecProcPty(_)
+        | Request::ResizePty(_)
+        | Request::WriteProcStdin(_)
+        | Request::ReadProcStdout(_)
+        | Request::ReadProcStderr(_)
+        | Request::KillProc(_)
+        | Request::ReadProcStatus(_)
+        | Request::ReadProcTree(_)
+        | Request::ReadProcResources(_)
+        | Request::ReadProcInfo(_)
+        | Request::ListProcs(_)
+        | Request::SubmitJob(_)
+        | Request::QueryJob(_)
+        | Request::CollectJobOutput(_)
+        | Request::CreateSchedule(_)
+        | Request::ListSchedules
+        | Request::DeleteSchedule(_)
+        | Request::Custom(_) => Access::Exec,
+
+        Request::UpdateTrustedClients(_)
+        | Request::PushConfig(_)
+        | Request::UpdateWebhooks(_)
+        | Request::PowerControl(_)
+        | Request::ExportState(_)
+        | Request::ImportState(_)
+        | Request::Forward(_)
+        | Request::Diagnostics(_)
+        | Request::ListConnections(_)
+        | Request::ListPendingEvictions(_)
+        | Request::GetConfig => Access::Admin,
+
+        #[cfg(feature = "wasm")]
+        Request::LoadWasmHandler(_) | Request::UnloadWasmHandler(_) => {
+            Access::Admin
+        }
+
+        #[cfg(feature = "fault-injection")]
+        Request::InjectFault(_) | Request::ClearFaults => Access::Admin,
+
+        Request::ListDirContents(args) => Access::Read(vec![&args.path]),
+        Request::ResolvePath(args) => Access::Read(vec![&args.path]),
+        Request::SniffFile(args) => Access::Read(vec![&args.path]),
+        Request::GetFileChecksum(args) => Access::Read(vec![&args.path]),
+        Request::DiffFiles(args) => {
+            Access::Read(vec![&args.path_a, &args.path_b])
+        }
+        Request::ReadFiles(args) => Access::Read(args.paths.iter().collect()),
+        Request::RecentFsEvents(_)
+        | Request::ListMounts(_)
+        | Request::ReadFile(_)
+        | Request::ReadLogRange(_) => Access::Read(Vec::new()),
+        Request::OpenFile(args)
+            if args.write_access
+                || args.create_if_missing
+                || args.modes.append
+                || args.modes.truncate
+                || args.modes.create_new =>
+        {
+            Access::Write(vec![&args.path])
+        }
+        Request::OpenFile(args) => Access::Read(vec![&args.path]),
+
+        Request::CreateDir(args) => Access::Write(vec![&args.path]),
+        Request::RenameDir(args) => Access::Write(vec![&args.from, &args.to]),
+        Request::RemoveDir(args) => Access::Write(vec![&args.path]),
+        Request::CreatePaths(args) => {
+            Access::Write(args.dirs.iter().chain(args.files.iter()).collect())
+        }
+        Request::RenameUnopenedFile(args) => {
+            Access::Write(vec![&args.from, &args.to])
+        }
+        Request::RenameFile(args) => Access::Write(vec![&args.to]),
+        Request::RemoveUnopenedFile(args) => Access::Write(vec![&args.path]),
+        Request::WriteFileAtomicByPath(args) => Access::Write(vec![&args.path]),
+        Request::PatchFileLines(args) => Access::Write(vec![&args.path]),
+        // NOTE: Preparing an upload creates, resizes, and fills in files with
+        //       chunks that the server already has, before any chunk is sent
+        Request::UploadManifest(args) => {
+            Access::Write(args.files.iter().map(|file| &file.path).collect())
+        }
+        Request::RemoveFile(_)
+        | Request::WriteFile(_)
+        | Request::WriteUploadChunk(_)
+        | Request::AppendLog(_)
+        | Request::Transaction(_) => Access::Write(Vec::new()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::request::{
+        CreateDirArgs, ExecProcArgs, ListDirContentsArgs, ManifestFile,
+        OpenFileArgs, RenameUnopenedFileArgs, UploadManifestArgs,
+    };
+
+    fn list_dir(path: &str) -> Request {
+        Request::ListDirContents(ListDirContentsArgs {
+            path: path.to_string().into(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn check_should_allow_everything_by_default() {
+        let permissions = Permissions::default();
+        let exec = Request::ExecProc(ExecProcArgs::default());
+        permissions.check(&exec).await.unwrap();
+        permissions.check(&list_dir("/")).await.unwrap();
+        permissions.check(&Request::Heartbeat).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_should_reject_denied_kinds_of_access() {
+        let permissions = Permissions {
+            allow_exec: false,
+            allow_fs_write: false,
+            ..Default::default()
+        };
+
+        let exec = Request::ExecProc(ExecProcArgs::default());
+        let err = permissions.check(&exec).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let create = Request::CreateDir(CreateDirArgs {
+            path: "dir".into(),
+            ..Default::default()
+        });
+        let err = permissions.check(&create).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        // Opening a file only to read it needs no write access
+        let open = |write_access| {
+            Request::OpenFile(OpenFileArgs {
+                path: "file".into(),
+                read_access: true,
+                write_access,
+                ..Default::default()
+            })
+        };
+        permissions.check(&open(false)).await.unwrap();
+        assert!(permissions.check(&open(true)).await.is_err());
+
+        permissions.check(&list_dir(".")).await.unwrap();
+        permissions.check(&Request::Heartbeat).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_should_treat_upload_manifest_as_write() {
+        let read_only = Permissions {
+            allow_fs_write: false,
+            ..Default::default()
+        };
+
+        let manifest = Request::UploadManifest(UploadManifestArgs {
+            files: vec![ManifestFile {
+                path: "file".into(),
+                ..Default::default()
+            }],
+        });
+        let err = read_only.check(&manifest).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        // Reading the config of the server is administration rather than
+        // reading the fs
+        read_only.check(&Request::GetConfig).await.unwrap();
+        let no_admin = Permissions {
+            allow_admin: false,
+            ..Default::default()
+        };
+        let err = no_admin.check(&Request::GetConfig).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn check_should_reject_paths_outside_of_allowed_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let allowed = root.as_ref().join("allowed");
+        std::fs::create_dir(&allowed).unwrap();
+        let permissions = Permissions {
+            allowed_paths: vec![allowed.clone()],
+            ..Default::default()
+        };
+        let path = |x: &str| allowed.join(x).to_string_lossy().to_string();
+
+        permissions.check(&list_dir(&path(""))).await.unwrap();
+        permissions
+            .check(&list_dir(&path("missing/dir")))
+            .await
+            .unwrap();
+
+        let err = permissions.check(&list_dir(&path(".."))).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        // Both sides of a rename must be allowed
+        let rename = Request::RenameUnopenedFile(RenameUnopenedFileArgs {
+            from: path("file").into(),
+            to: root.as_ref().join("file").into(),
+            ..Default::default()
+        });
+        assert!(permissions.check(&rename).await.is_err());
+
+        // Symlinks are not followed, even those leading within the allowed
+        // paths, so one cannot be swapped for another after being checked
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.as_ref(), path("link")).unwrap();
+            std::os::unix::fs::symlink(&allowed, path("inner-link")).unwrap();
+            for link in &["link", "inner-link", "link/allowed"] {
+                let err = permissions
+                    .check(&list_dir(&path(link)))
+                    .await
+                    .unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn check_should_reject_state_changing_requests_when_restricted() {
+        let permissions = Permissions {
+            allow_exec: false,
+            allow_admin: false,
+            allow_fs_read: false,
+            allow_fs_write: false,
+            allowed_paths: Vec::new(),
+        };
+
+        #[allow(unused_mut)]
+        let mut requests = vec![
+            Request::KillProc(Default::default()),
+            Request::WriteProcStdin(Default::default()),
+            Request::ResizePty(Default::default()),
+            Request::ReadProcStdout(Default::default()),
+            Request::DeleteSchedule(Default::default()),
+            Request::Custom(Default::default()),
+            Request::AppendLog(Default::default()),
+            Request::ReadLogRange(Default::default()),
+            Request::Transaction(Default::default()),
+            Request::PushConfig(Default::default()),
+            Request::GetConfig,
+            Request::UpdateWebhooks(Default::default()),
+            Request::ImportState(Default::default()),
+            Request::ExportState(Default::default()),
+            Request::UpdateTrustedClients(Default::default()),
+            Request::PowerControl(Default::default()),
+            Request::ListConnections(Default::default()),
+        ];
+
+        #[cfg(feature = "wasm")]
+        requests.extend(vec![
+            Request::LoadWasmHandler(Default::default()),
+            Request::UnloadWasmHandler(Default::default()),
+        ]);
+
+        for request in requests {
+            let err = permissions.check(&request).await.unwrap_err();
+            assert_eq!(
+                err.kind(),
+                io::ErrorKind::PermissionDenied,
+                "{:?} was not denied",
+                request
+            );
+        }
+
+        // Requests concerning only the protocol are always allowed
+        permissions.check(&Request::Heartbeat).await.unwrap();
+        permissions.check(&Request::Version).await.unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+use super::{trusted::normalize_all, AUDIT_LOG_TARGET};
+use crate::core::request::PowerAction;
+use crate::utils::TaskTracker;
+use log::{info, warn};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::{runtime::Handle, sync::Mutex};
+
+/// Default time a client has to confirm a power action before its token
+/// expires
+pub const DEFAULT_CONFIRM_TTL: Duration = Duration::from_secs(60);
+
+/// Gate on clients rebooting or shutting down the host running the server
+///
+/// Only admins, identified by the fingerprints of the identity keys that
+/// sign their msgs, can request power actions, and each action must be
+/// confirmed by the same admin with the token yielded when requesting it
+/// before it is performed. Every request, confirmation, and rejection is
+/// recorded in the audit log. Power actions are rejected outright by a
+/// default gate, which has no admins. Clones share the same pending actions.
+#[derive(Clone, Debug)]
+pub struct PowerControl {
+    admins: BTreeSet<String>,
+    confirm_ttl: Duration,
+    pending: Arc<Mutex<HashMap<String, PendingAction>>>,
+}
+
+/// Power action requested by an admin and awaiting confirmation
+#[derive(Clone, Debug)]
+struct PendingAction {
+    identity: String,
+    action: PowerAction,
+    delay_secs: u64,
+    expires: Instant,
+}
+
+impl Default for PowerControl {
+    fn default() -> Self {
+        Self {
+            admins: BTreeSet::new(),
+            confirm_ttl: DEFAULT_CONFIRM_TTL,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl PowerControl {
+    /// Creates a gate that lets only the clients with the key fingerprints
+    /// `admins` perform power actions
+    pub fn new(admins: Vec<String>) -> io::Result<Self> {
+        Ok(Self {
+            admins: normalize_all(admins)?,
+            ..Default::default()
+        })
+    }
+
+    /// Sets how long a client has to confirm an action after requesting it
+    pub fn with_confirm_ttl(self, confirm_ttl: Duration) -> Self {
+        Self {
+            confirm_ttl,
+            ..self
+        }
+    }
+
+    /// Whether any client can perform power actions
+    pub fn is_enabled(&self) -> bool {
+        !self.admins.is_empty()
+    }
+
+    /// Whether `identity` is the fingerprint of the key of an admin
+    pub fn is_admin(&self, identity: &str) -> bool {
+        self.admins.contains(identity)
+    }
+
+    /// Fingerprints of the keys of admins in sorted order
+    pub fn admins(&self) -> Vec<String> {
+        self.admins.iter().cloned().collect()
+    }
+
+    /// Time a client has to confirm an action after requesting it
+    pub fn confirm_ttl(&self) -> Duration {
+        self.confirm_ttl
+    }
+
+    /// Records `action` as requested by `identity`, yielding the token it
+    /// must be confirmed with
+    pub async fn request(
+        &self,
+        identity: &str,
+        action: &PowerAction,
+        delay_secs: u64,
+    ) -> io::Result<String> {
+        self.check_admin(identity, action)?;
+
+        let token = format!(
+            "{:016x}{:016x}",
+            rand::random::<u64>(),
+            rand::random::<u64>()
+        );
+
+        let mut pending = self.pending.lock().await;
+        let now = Instant::now();
+        pending.retain(|_, x| x.expires > now);
+        pending.insert(
+            token.clone(),
+            PendingAction {
+                identity: String::from(identity),
+                action: action.clone(),
+                delay_secs,
+                expires: now + self.confirm_ttl,
+            },
+        );
+
+        info!(
+            target: AUDIT_LOG_TARGET,
+            "Power action {:?} with delay of {}s requested by {}",
+            action,
+            delay_secs,
+            identity
+        );
+        Ok(token)
+    }
+
+    /// Confirms the action requested with `token`, which must have been
+    /// requested by `identity` with the same `action` and `delay_secs`
+    ///
+    /// Tokens can only be used once, even if the confirmation is rejected
+    pub async fn confirm(
+        &self,
+        identity: &str,
+        token: &str,
+        action: &PowerAction,
+        delay_secs: u64,
+    ) -> io::Result<()> {
+        self.check_admin(identity, action)?;
+
+        let pending = self.pending.lock().await.remove(token);
+        let reason = match pending {
+            None => "unknown token",
+            Some(x) if x.expires <= Instant::now() => "expired token",
+            Some(x) if x.identity != identity => "token of another client",
+            Some(x) if &x.action != action || x.delay_secs != delay_secs => {
+                "action differs from that requested"
+            }
+            Some(_) => {
+                info!(
+                    target: AUDIT_LOG_TARGET,
+                    "Power action {:?} with delay of {}s confirmed by {}",
+                    action,
+                    delay_secs,
+                    identity
+                );
+                return Ok(());
+            }
+        };
+
+        warn!(
+            target: AUDIT_LOG_TARGET,
+            "Power action {:?} rejected for {}: {}", action, identity, reason
+        );
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Cannot confirm power action: {}", reason),
+        ))
+    }
+
+    /// Performs `action` after waiting `delay_secs` in the background,
+    /// recording the outcome in the audit log, with the wait and action
+    /// tracked by `tasks`
+    pub fn schedule(
+        &self,
+        tasks: &TaskTracker,
+        action: PowerAction,
+        delay_secs: u64,
+    ) {
+        tasks.spawn(&Handle::current(), async move {
+            tokio::time::delay_for(Duration::from_secs(delay_secs)).await;
+
+            let (program, args) = command(&action);
+            info!(
+                target: AUDIT_LOG_TARGET,
+                "Performing power action {:?}: {} {:?}", action, program, args
+            );
+            match tokio::process::Command::new(&program)
+                .args(&args)
+                .status()
+                .await
+            {
+                Ok(status) if status.success() => {}
+                Ok(status) => warn!(
+                    target: AUDIT_LOG_TARGET,
+                    "Power action {:?} failed: {}", action, status
+                ),
+                Err(x) => warn!(
+                    target: AUDIT_LOG_TARGET,
+                    "Power action {:?} failed: {}", action, x
+                ),
+            }
+        });
+    }
+
+    fn check_admin(
+        &self,
+        identity: &str,
+        action: &PowerAction,
+    ) -> io::Result<()> {
+        if self.admins.contains(identity) {
+            return Ok(());
+        }
+
+        warn!(
+            target: AUDIT_LOG_TARGET,
+            "Power action {:?} rejected for {}: not an admin", action, identity
+        );
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            if self.is_enabled() {
+                "Power actions require a msg signed by an admin key"
+            } else {
+                "Power control is not enabled on the server"
+            },
+        ))
+    }
+}
+
+/// Program and arguments run to perform `action` immediately
+fn command(action: &PowerAction) -> (String, Vec<String>) {
+    let (program, args): (&str, &[&str]) = match action {
+        PowerAction::Custom { command, args } => {
+            return (command.clone(), args.clone())
+        }
+        #[cfg(windows)]
+        PowerAction::Reboot => ("shutdown", &["/r", "/t", "0"]),
+        #[cfg(windows)]
+        PowerAction::Shutdown => ("shutdown", &["/s", "/t", "0"]),
+        #[cfg(not(windows))]
+        PowerAction::Reboot => ("shutdown", &["-r", "now"]),
+        #[cfg(not(windows))]
+        PowerAction::Shutdown => ("shutdown", &["-h", "now"]),
+    };
+
+    (
+        String::from(program),
+        args.iter().map(|x| String::from(*x)).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADMIN: &str =
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const OTHER: &str =
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn power_control() -> PowerControl {
+        PowerControl::new(vec![String::from(ADMIN)]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn request_should_reject_clients_that_are_not_admins() {
+        let err = PowerControl::default()
+            .request(ADMIN, &PowerAction::Reboot, 0)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let err = power_control()
+            .request(OTHER, &PowerAction::Reboot, 0)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn confirm_should_accept_token_once_for_same_action() {
+        let power = power_control();
+        let token =
+            power.request(ADMIN, &PowerAction::Reboot, 5).await.unwrap();
+
+        power
+            .confirm(ADMIN, &token, &PowerAction::Reboot, 5)
+            .await
+            .unwrap();
+
+        let err = power
+            .confirm(ADMIN, &token, &PowerAction::Reboot, 5)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn confirm_should_reject_token_for_different_action() {
+        let power = power_control();
+        let token =
+            power.request(ADMIN, &PowerAction::Reboot, 5).await.unwrap();
+
+        let err = power
+            .confirm(ADMIN, &token, &PowerAction::Shutdown, 5)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let token =
+            power.request(ADMIN, &PowerAction::Reboot, 5).await.unwrap();
+        let err = power
+            .confirm(ADMIN, &token, &PowerAction::Reboot, 0)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn confirm_should_reject_expired_token() {
+        let power = power_control().with_confirm_ttl(Duration::from_millis(1));
+        let token =
+            power.request(ADMIN, &PowerAction::Reboot, 0).await.unwrap();
+
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+        let err = power
+            .confirm(ADMIN, &token, &PowerAction::Reboot, 0)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
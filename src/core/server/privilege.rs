@@ -0,0 +1,216 @@
+use std::io;
+
+/// User, and optionally group, that the server runs as once it has bound its
+/// sockets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunAs {
+    /// Name or numeric id of the user
+    pub user: String,
+
+    /// Name or numeric id of the group, where the primary group of the user
+    /// is used if not provided
+    pub group: Option<String>,
+}
+
+/// Permanently drops the privileges of the process to those of the user and
+/// group of `run_as`, clearing any supplementary groups
+///
+/// Fails if the user or group cannot be found, if any part of the drop fails,
+/// or if the original privileges can still be regained afterwards, in which
+/// case the server must not carry on.
+pub fn drop_privileges(run_as: &RunAs) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let (uid, primary_gid) = resolve_user(&run_as.user)?;
+        let gid = match run_as.group.as_deref() {
+            Some(group) => resolve_group(group)?,
+            None => primary_gid,
+        };
+
+        // NOTE: Order matters, as groups can no longer be changed once the
+        //       user is unprivileged
+        unsafe {
+            if libc::setgroups(0, std::ptr::null()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setgid(gid) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::setuid(uid) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        verify(uid, gid)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = run_as;
+        Err(io::Error::other(
+            "Dropping privileges is not supported on this platform",
+        ))
+    }
+}
+
+/// Checks that the process runs as `uid` and `gid` and cannot become root
+/// again
+#[cfg(unix)]
+fn verify(uid: libc::uid_t, gid: libc::gid_t) -> io::Result<()> {
+    let (ruid, euid, rgid, egid) = unsafe {
+        (
+            libc::getuid(),
+            libc::geteuid(),
+            libc::getgid(),
+            libc::getegid(),
+        )
+    };
+    if ruid != uid || euid != uid || rgid != gid || egid != gid {
+        return Err(io::Error::other(format!(
+            "Running as uid {}/{} and gid {}/{} rather than {} and {}",
+            ruid, euid, rgid, egid, uid, gid
+        )));
+    }
+
+    if uid != 0 && unsafe { libc::setuid(0) } == 0 {
+        return Err(io::Error::other(
+            "Privileges could be regained after being dropped",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Size of the buffer that passwd and group entries are read into when the
+/// system does not suggest one
+#[cfg(unix)]
+const DEFAULT_ENTRY_BUF_SIZE: usize = 16384;
+
+#[cfg(unix)]
+fn entry_buf_size(name: libc::c_int) -> usize {
+    match unsafe { libc::sysconf(name) } {
+        x if x > 0 => x as usize,
+        _ => DEFAULT_ENTRY_BUF_SIZE,
+    }
+}
+
+#[cfg(unix)]
+fn c_name(name: &str) -> io::Result<std::ffi::CString> {
+    std::ffi::CString::new(name)
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))
+}
+
+/// Resolves the uid and primary gid of the user with `user` as its name or
+/// numeric id
+#[cfg(unix)]
+fn resolve_user(user: &str) -> io::Result<(libc::uid_t, libc::gid_t)> {
+    let name = c_name(user)?;
+    let mut buf =
+        vec![0 as libc::c_char; entry_buf_size(libc::_SC_GETPW_R_SIZE_MAX)];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+    // NOTE: Safe as buf outlives passwd, whose strings point into it
+    let err = unsafe {
+        match user.parse::<libc::uid_t>() {
+            Ok(uid) => libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            ),
+            Err(_) => libc::getpwnam_r(
+                name.as_ptr(),
+                &mut passwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            ),
+        }
+    };
+
+    if err != 0 {
+        Err(io::Error::from_raw_os_error(err))
+    } else if result.is_null() {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No user {}", user),
+        ))
+    } else {
+        Ok((passwd.pw_uid, passwd.pw_gid))
+    }
+}
+
+/// Resolves the gid of the group with `group` as its name or numeric id
+#[cfg(unix)]
+fn resolve_group(group: &str) -> io::Result<libc::gid_t> {
+    let name = c_name(group)?;
+    let mut buf =
+        vec![0 as libc::c_char; entry_buf_size(libc::_SC_GETGR_R_SIZE_MAX)];
+    let mut entry: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+
+    // NOTE: Safe as buf outlives entry, whose strings point into it
+    let err = unsafe {
+        match group.parse::<libc::gid_t>() {
+            Ok(gid) => libc::getgrgid_r(
+                gid,
+                &mut entry,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            ),
+            Err(_) => libc::getgrnam_r(
+                name.as_ptr(),
+                &mut entry,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            ),
+        }
+    };
+
+    if err != 0 {
+        Err(io::Error::from_raw_os_error(err))
+    } else if result.is_null() {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No group {}", group),
+        ))
+    } else {
+        Ok(entry.gr_gid)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_user_should_support_names_and_ids() {
+        assert_eq!(resolve_user("root").unwrap().0, 0);
+        assert_eq!(resolve_user("0").unwrap().0, 0);
+
+        let err = resolve_user("over-there-missing-user").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn resolve_group_should_support_names_and_ids() {
+        assert_eq!(resolve_group("0").unwrap(), 0);
+
+        let err = resolve_group("over-there-missing-group").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn drop_privileges_should_fail_for_missing_user() {
+        let err = drop_privileges(&RunAs {
+            user: String::from("over-there-missing-user"),
+            group: None,
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
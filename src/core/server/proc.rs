@@ -1,9 +1,22 @@
+use crate::core::request::{Newline, ProcIoMode, ProcOutputFilter};
+use crate::core::server::pty::Pty;
+use crate::utils::{Capture, CaptureCursor};
 use log::error;
+use regex::bytes::Regex;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
 use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::process::Output;
 use std::sync::Arc;
-use tokio::{process::Child, runtime::Handle, sync::Mutex, task};
+use std::time::Instant;
+use tokio::{process::Child, runtime::Handle, task};
+
+/// Default bytes of stdout and of stderr held for a proc before the oldest
+/// are dropped to make room, whether or not they have been read
+pub const DEFAULT_OUTPUT_CAPACITY: usize = 8 * 1024 * 1024;
 
 #[derive(Copy, Clone, Debug)]
 pub struct ExitStatus {
@@ -12,6 +25,152 @@ pub struct ExitStatus {
     pub exit_code: Option<i32>,
 }
 
+/// How a proc was spawned, recorded when it is spawned so that differences
+/// from running the same command elsewhere can be explained later
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpawnInfo {
+    pub command: String,
+    pub args: Vec<String>,
+
+    /// Absolute path to the binary that the command resolved to
+    pub program: Option<PathBuf>,
+
+    /// Absolute path to the directory the proc was started in
+    pub current_dir: PathBuf,
+
+    /// Environment variables inherited from the server
+    pub env: BTreeMap<String, String>,
+}
+
+impl SpawnInfo {
+    /// Captures the environment of the server as inherited by `command`
+    /// started in `current_dir`, or the directory of the server if none
+    pub fn capture(
+        command: &str,
+        args: &[String],
+        current_dir: Option<&Path>,
+    ) -> io::Result<Self> {
+        let current_dir = match current_dir {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::current_dir()?,
+        };
+        let env: BTreeMap<String, String> = std::env::vars_os()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().to_string(),
+                    v.to_string_lossy().to_string(),
+                )
+            })
+            .collect();
+        let program = resolve_program(
+            command,
+            &current_dir,
+            env.get("PATH").map(OsStr::new),
+        );
+
+        Ok(Self {
+            command: command.to_string(),
+            args: args.to_vec(),
+            program,
+            current_dir,
+            env,
+        })
+    }
+}
+
+/// Resolves `command` to the binary it runs, which is relative to
+/// `current_dir` if it is a path, or otherwise the first match within the
+/// directories of `path`
+fn resolve_program(
+    command: &str,
+    current_dir: &Path,
+    path: Option<&OsStr>,
+) -> Option<PathBuf> {
+    let command = Path::new(command);
+    if command.components().count() > 1 {
+        return std::fs::canonicalize(current_dir.join(command)).ok();
+    }
+
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| String::from(".EXE;.CMD;.BAT;.COM"))
+            .split(';')
+            .map(String::from)
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+
+    std::env::split_paths(path?)
+        .flat_map(|dir| {
+            extensions.iter().map(move |ext| {
+                let mut name = command.as_os_str().to_os_string();
+                name.push(ext);
+                dir.join(name)
+            })
+        })
+        .find(|candidate| is_executable(candidate))
+        .and_then(|x| std::fs::canonicalize(x).ok())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match std::fs::metadata(path) {
+        Ok(x) => x.is_file() && x.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Filters applied to the output of a proc as it is read
+#[derive(Clone, Debug, Default)]
+pub struct OutputFilter {
+    /// Offset within all output to skip ahead to
+    pub offset: Option<u64>,
+
+    /// Maximum bytes of output to consume
+    pub max_bytes: Option<u64>,
+
+    /// Pattern that lines must match to be returned
+    pub pattern: Option<Regex>,
+}
+
+impl OutputFilter {
+    /// Compiles the filter of a request, failing if its pattern is invalid
+    pub fn compile(filter: &ProcOutputFilter) -> io::Result<Self> {
+        let pattern = match filter.pattern.as_deref() {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|x| {
+                io::Error::new(io::ErrorKind::InvalidInput, x.to_string())
+            })?),
+            None => None,
+        };
+
+        Ok(Self {
+            offset: filter.offset,
+            max_bytes: filter.max_bytes,
+            pattern,
+        })
+    }
+}
+
+/// Output read from a proc
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcOutput {
+    pub output: Vec<u8>,
+
+    /// Offset within all output where the next read continues
+    pub next_offset: u64,
+
+    /// Bytes captured that have yet to be read
+    pub remaining: u64,
+}
+
 #[derive(Debug)]
 pub struct LocalProc {
     id: u32,
@@ -25,15 +184,65 @@ pub struct LocalProc {
     /// Handle to task that is processing stdout/stderr
     io_handle: Option<task::JoinHandle<()>>,
 
-    /// Internal buffer of all stdout that has been acquired
-    stdout_buf: Arc<Mutex<Vec<u8>>>,
+    /// Stdout and stderr captured so far, which are closed once all of
+    /// each has been captured
+    stdout: Arc<Capture<u8>>,
+    stderr: Arc<Capture<u8>>,
+
+    /// Positions of reads of stdout and stderr, which is how much of each
+    /// has already been read
+    stdout_cursor: CaptureCursor<u8>,
+    stderr_cursor: CaptureCursor<u8>,
+
+    /// How stdout and stderr are split into the contents that are read
+    io_mode: ProcIoMode,
+
+    /// Line ending that stdin, stdout, and stderr are converted to in line
+    /// mode
+    newline: Option<Newline>,
+
+    /// Whether or not the proc should be left running when no longer tracked
+    detached: bool,
+
+    /// Labels attached to the proc when it was started
+    labels: Vec<String>,
+
+    /// When the proc was started, used to determine its age
+    started: Instant,
+
+    /// How the proc was spawned, if recorded
+    spawn_info: Option<SpawnInfo>,
+
+    /// File used only by the proc, removed once the proc exits or is dropped
+    temp_file: Option<TempFile>,
+
+    /// Origin to tell once the proc exits, taken once it has been told
+    origin: Option<SocketAddr>,
+
+    /// Pseudo-terminal the proc is attached to, through which its input is
+    /// written and its output (all captured as stdout) is read
+    pty: Option<Pty>,
+}
+
+/// Path to a file that is removed when dropped
+#[derive(Debug)]
+struct TempFile(PathBuf);
 
-    /// Internal buffer of all stderr that has been acquired
-    stderr_buf: Arc<Mutex<Vec<u8>>>,
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(x) = std::fs::remove_file(&self.0) {
+            if x.kind() != io::ErrorKind::NotFound {
+                error!("Failed to remove {:?}: {}", self.0, x);
+            }
+        }
+    }
 }
 
 impl LocalProc {
     pub fn new(child: Child) -> Self {
+        let stdout = Arc::new(Capture::new(DEFAULT_OUTPUT_CAPACITY));
+        let stderr = Arc::new(Capture::new(DEFAULT_OUTPUT_CAPACITY));
+
         Self {
             id: child.id(),
             exit_status: None,
@@ -42,8 +251,19 @@ impl LocalProc {
             supports_stderr: child.stderr.is_some(),
             inner: child,
             io_handle: None,
-            stdout_buf: Arc::new(Mutex::new(Vec::new())),
-            stderr_buf: Arc::new(Mutex::new(Vec::new())),
+            stdout_cursor: stdout.subscribe(),
+            stderr_cursor: stderr.subscribe(),
+            stdout,
+            stderr,
+            io_mode: ProcIoMode::default(),
+            newline: None,
+            detached: false,
+            labels: Vec::new(),
+            started: Instant::now(),
+            spawn_info: None,
+            temp_file: None,
+            origin: None,
+            pty: None,
         }
     }
 
@@ -51,6 +271,92 @@ impl LocalProc {
         self.id
     }
 
+    /// Marks whether or not the proc should be left running rather than
+    /// killed when it is evicted
+    pub fn set_detached(&mut self, detached: bool) {
+        self.detached = detached;
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    pub fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels = labels;
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn set_spawn_info(&mut self, spawn_info: SpawnInfo) {
+        self.spawn_info = Some(spawn_info);
+    }
+
+    /// How the proc was spawned, if recorded when it was spawned
+    pub fn spawn_info(&self) -> Option<&SpawnInfo> {
+        self.spawn_info.as_ref()
+    }
+
+    /// When the proc was started
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Sets how stdout and stderr are split into the contents that are read
+    /// and, in line mode, the line ending that stdin, stdout, and stderr
+    /// are converted to
+    pub fn set_framing(
+        &mut self,
+        io_mode: ProcIoMode,
+        newline: Option<Newline>,
+    ) {
+        self.io_mode = io_mode;
+        self.newline = newline;
+    }
+
+    /// Takes ownership of the file at `path`, removing it once the proc has
+    /// exited (as observed through `exit_status`) or is dropped
+    pub fn set_temp_file(&mut self, path: impl Into<PathBuf>) {
+        self.temp_file = Some(TempFile(path.into()));
+    }
+
+    /// Sets the origin that is told once the proc exits, which is usually
+    /// the origin that started it
+    pub fn set_origin(&mut self, origin: SocketAddr) {
+        self.origin = Some(origin);
+    }
+
+    /// Takes the origin to tell of the exit of the proc if it has exited,
+    /// so that the origin is only told once
+    pub async fn take_exited_origin(
+        &mut self,
+    ) -> Option<(SocketAddr, ExitStatus)> {
+        self.origin?;
+        let status = self.exit_status().await?;
+        self.origin.take().map(|origin| (origin, status))
+    }
+
+    /// Attaches the proc to `pty`, which must be the pseudo-terminal it was
+    /// spawned with, prior to spawning the io-processing task
+    pub fn set_pty(&mut self, pty: Pty) {
+        self.supports_stdin = true;
+        self.supports_stdout = true;
+        self.supports_stderr = true;
+        self.pty = Some(pty);
+    }
+
+    /// Changes the size of the pseudo-terminal of the proc
+    pub fn resize_pty(&self, rows: u16, cols: u16) -> io::Result<()> {
+        match self.pty.as_ref() {
+            Some(pty) => pty.resize(rows, cols),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Proc is not attached to a pseudo-terminal",
+            )),
+        }
+    }
+
     pub fn inner(&self) -> &Child {
         &self.inner
     }
@@ -69,6 +375,7 @@ impl LocalProc {
                     .await;
 
                 if let Some(status) = exit_status {
+                    self.temp_file = None;
                     self.exit_status = Some(ExitStatus {
                         id: self.id,
                         is_success: status.is_ok(),
@@ -95,8 +402,21 @@ impl LocalProc {
         let stdout = self.inner.stdout.take();
         let stderr = self.inner.stderr.take();
 
-        let stdout_buf = Arc::clone(&self.stdout_buf);
-        let stderr_buf = Arc::clone(&self.stderr_buf);
+        let stdout_capture = Arc::clone(&self.stdout);
+        let stderr_capture = Arc::clone(&self.stderr);
+
+        // NOTE: Everything written to a pseudo-terminal is read from it on a
+        //       thread of its own, which closes stdout once done
+        let reads_pty = match self.pty.as_ref() {
+            Some(pty) => match pty.capture_output(Arc::clone(&self.stdout)) {
+                Ok(()) => true,
+                Err(x) => {
+                    error!("pty reader died: {}", x);
+                    false
+                }
+            },
+            None => false,
+        };
 
         let io_handle = handle.spawn(async move {
             let _ = tokio::join!(
@@ -109,10 +429,7 @@ impl LocalProc {
                         loop {
                             match stdout.read(&mut buf).await {
                                 Ok(size) if size > 0 => {
-                                    stdout_buf
-                                        .lock()
-                                        .await
-                                        .extend_from_slice(&buf[..size]);
+                                    stdout_capture.push(&buf[..size]);
                                 }
                                 Ok(_) => break,
                                 Err(x) => {
@@ -122,6 +439,10 @@ impl LocalProc {
                             }
                         }
                     }
+
+                    if !reads_pty {
+                        stdout_capture.close();
+                    }
                 },
                 async {
                     use tokio::io::AsyncReadExt;
@@ -132,10 +453,7 @@ impl LocalProc {
                         loop {
                             match stderr.read(&mut buf).await {
                                 Ok(size) if size > 0 => {
-                                    stderr_buf
-                                        .lock()
-                                        .await
-                                        .extend_from_slice(&buf[..size]);
+                                    stderr_capture.push(&buf[..size]);
                                 }
                                 Ok(_) => break,
                                 Err(x) => {
@@ -145,6 +463,8 @@ impl LocalProc {
                             }
                         }
                     }
+
+                    stderr_capture.close();
                 }
             );
         });
@@ -157,9 +477,20 @@ impl LocalProc {
     pub async fn write_stdin(&mut self, buf: &[u8]) -> io::Result<()> {
         use tokio::io::AsyncWriteExt;
 
+        // NOTE: Input to a pseudo-terminal is passed through as is since the
+        //       terminal handles line endings itself
+        if let Some(pty) = self.pty.as_ref() {
+            return pty.write(buf);
+        }
+
+        let buf = match (self.io_mode, self.newline) {
+            (ProcIoMode::Line, Some(newline)) => newline.normalize(buf),
+            _ => buf.to_vec(),
+        };
+
         match self.inner.stdin.as_mut() {
             Some(stdin) => {
-                let mut result = stdin.write_all(buf).await;
+                let mut result = stdin.write_all(&buf).await;
                 if result.is_ok() {
                     result = stdin.flush().await;
                 }
@@ -169,17 +500,146 @@ impl LocalProc {
         }
     }
 
+    /// Closes stdin of the proc so that it sees the end of its input, after
+    /// which writing to stdin fails
+    pub fn close_stdin(&mut self) {
+        if let Some(pty) = self.pty.as_mut() {
+            pty.close_input();
+        }
+        self.inner.stdin.take();
+    }
+
     pub async fn read_stdout(&mut self) -> io::Result<Vec<u8>> {
+        let filter = OutputFilter::default();
+        Ok(self.read_stdout_filtered(&filter).await?.output)
+    }
+
+    pub async fn read_stderr(&mut self) -> io::Result<Vec<u8>> {
+        let filter = OutputFilter::default();
+        Ok(self.read_stderr_filtered(&filter).await?.output)
+    }
+
+    /// Reads the stdout captured since the last read, applying `filter`
+    pub async fn read_stdout_filtered(
+        &mut self,
+        filter: &OutputFilter,
+    ) -> io::Result<ProcOutput> {
         if self.supports_stdout {
-            Ok(self.stdout_buf.lock().await.drain(..).collect())
+            // NOTE: Checked before reading so that output captured in
+            //       between is never mistaken for all remaining output
+            let closed = self.stdout.is_closed();
+            let buf = self.stdout_cursor.peek();
+            let offset = self.stdout_cursor.offset();
+            let output = self.take_output(&buf, closed, offset, filter);
+            self.stdout_cursor.seek(output.next_offset);
+            Ok(output)
         } else {
             Err(io::Error::from(io::ErrorKind::BrokenPipe))
         }
     }
 
-    pub async fn read_stderr(&mut self) -> io::Result<Vec<u8>> {
+    /// Reads the stderr captured since the last read, applying `filter`
+    pub async fn read_stderr_filtered(
+        &mut self,
+        filter: &OutputFilter,
+    ) -> io::Result<ProcOutput> {
+        if self.supports_stderr {
+            let closed = self.stderr.is_closed();
+            let buf = self.stderr_cursor.peek();
+            let offset = self.stderr_cursor.offset();
+            let output = self.take_output(&buf, closed, offset, filter);
+            self.stderr_cursor.seek(output.next_offset);
+            Ok(output)
+        } else {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    /// Reads the output from `buf`, which starts at `offset` within all
+    /// output, that is ready to be read and passes `filter`, where the
+    /// output yielded continues from the next offset
+    ///
+    /// Reading by line (in line mode or when matching lines against a
+    /// pattern) excludes a trailing partial line unless the output is
+    /// `closed`, and cuts output limited to a maximum size at the last line
+    /// that fits, splitting a line only if none fit
+    fn take_output(
+        &self,
+        buf: &[u8],
+        closed: bool,
+        mut offset: u64,
+        filter: &OutputFilter,
+    ) -> ProcOutput {
+        let mut buf = buf;
+        if let Some(to) = filter.offset {
+            let skip = to.saturating_sub(offset).min(buf.len() as u64);
+            buf = &buf[skip as usize..];
+            offset += skip;
+        }
+
+        let by_line =
+            self.io_mode == ProcIoMode::Line || filter.pattern.is_some();
+        let mut end = if by_line && !closed {
+            line_end(buf)
+        } else {
+            buf.len()
+        };
+        if let Some(max) = filter.max_bytes {
+            let max = max.min(end as u64) as usize;
+            if max < end {
+                end = match line_end(&buf[..max]) {
+                    n if by_line && n > 0 => n,
+                    _ => max,
+                };
+            }
+        }
+        let output = buf[..end].to_vec();
+        offset += end as u64;
+
+        let output = match filter.pattern.as_ref() {
+            Some(pattern) => output
+                .split_inclusive(|b| *b == b'\n')
+                .filter(|line| pattern.is_match(trim_newline(line)))
+                .flatten()
+                .copied()
+                .collect(),
+            None => output,
+        };
+
+        ProcOutput {
+            output: match (self.io_mode, self.newline) {
+                (ProcIoMode::Line, Some(newline)) => newline.normalize(&output),
+                _ => output,
+            },
+            next_offset: offset,
+            remaining: (buf.len() - end) as u64,
+        }
+    }
+
+    /// Reports the bytes of stdout and stderr captured that have yet to
+    /// be read
+    pub async fn buffered_len(&self) -> (usize, usize) {
+        (
+            self.stdout_cursor.remaining(),
+            self.stderr_cursor.remaining(),
+        )
+    }
+
+    /// Subscribes to the stdout captured from the proc, starting with the
+    /// oldest still held, independently of reads through `read_stdout`
+    pub fn subscribe_stdout(&self) -> io::Result<CaptureCursor<u8>> {
+        if self.supports_stdout {
+            Ok(self.stdout.subscribe())
+        } else {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+    }
+
+    /// Subscribes to the stderr captured from the proc, starting with the
+    /// oldest still held, independently of reads through `read_stderr`
+    pub fn subscribe_stderr(&self) -> io::Result<CaptureCursor<u8>> {
         if self.supports_stderr {
-            Ok(self.stderr_buf.lock().await.drain(..).collect())
+            Ok(self.stderr.subscribe())
         } else {
             Err(io::Error::from(io::ErrorKind::BrokenPipe))
         }
@@ -195,6 +655,20 @@ impl LocalProc {
     }
 }
 
+/// Length of `buf` up to and including its last line ending
+fn line_end(buf: &[u8]) -> usize {
+    buf.iter()
+        .rposition(|b| *b == b'\n')
+        .map(|i| i + 1)
+        .unwrap_or_default()
+}
+
+/// Removes the line ending from the end of `line`
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -247,7 +721,7 @@ mod tests {
         let mut local_proc = LocalProc::new(child);
         match local_proc.write_stdin(b"test").await {
             Ok(_) => {
-                match timeout(Duration::from_millis(10), async {
+                match timeout(Duration::from_secs(1), async {
                     use std::io::SeekFrom;
                     use tokio::io::AsyncReadExt;
                     let mut f = fs::File::from_std(f);
@@ -260,7 +734,7 @@ mod tests {
                             break s;
                         }
 
-                        task::yield_now().await;
+                        let _ = task::yield_now().await;
                     }
                 })
                 .await
@@ -311,6 +785,49 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_read_stdout_filtered_should_page_and_match_lines() {
+        let child = Command::new("printf")
+            .arg("one\\ntwo\\nthree\\nfour\\n")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut local_proc = LocalProc::new(child).spawn();
+
+        // Give process some time to run and complete
+        delay_for(Duration::from_millis(50)).await;
+
+        let filter = OutputFilter {
+            offset: Some(4),
+            max_bytes: Some(6),
+            ..Default::default()
+        };
+        let out = local_proc.read_stdout_filtered(&filter).await.unwrap();
+        assert_eq!(out.output, b"two\nth");
+        assert_eq!((out.next_offset, out.remaining), (10, 9));
+
+        // Matching by line never splits a line that can be returned whole
+        let filter = OutputFilter {
+            max_bytes: Some(8),
+            pattern: Some(Regex::new("e$").unwrap()),
+            ..Default::default()
+        };
+        let out = local_proc.read_stdout_filtered(&filter).await.unwrap();
+        assert_eq!(out.output, b"ree\n");
+        assert_eq!((out.next_offset, out.remaining), (14, 5));
+
+        let filter = OutputFilter {
+            pattern: Some(Regex::new("^t").unwrap()),
+            ..Default::default()
+        };
+        let out = local_proc.read_stdout_filtered(&filter).await.unwrap();
+        assert!(out.output.is_empty());
+        assert_eq!((out.next_offset, out.remaining), (19, 0));
+    }
+
     #[tokio::test]
     async fn test_read_stdout_should_not_return_content_returned_previously() {
         let child = Command::new("echo")
@@ -325,7 +842,7 @@ mod tests {
 
         // Get first batch of bytes and discard
         assert!(
-            !timeout(Duration::from_millis(10), async {
+            !timeout(Duration::from_secs(1), async {
                 loop {
                     match local_proc.read_stdout().await {
                         Ok(buf) => {
@@ -336,7 +853,7 @@ mod tests {
                             // NOTE: The read above is too quick as it only awaits
                             //       for a lock, and thereby prevents switching
                             //       to another task -- yield to enable switching
-                            task::yield_now().await;
+                            let _ = task::yield_now().await;
                         }
                         Err(x) => panic!("Unexpected error: {}", x),
                     }
@@ -367,7 +884,7 @@ mod tests {
 
         let mut local_proc = LocalProc::new(child).spawn();
 
-        let buf = timeout(Duration::from_millis(10), async {
+        let buf = timeout(Duration::from_secs(1), async {
             loop {
                 match local_proc.read_stdout().await {
                     Ok(buf) => {
@@ -378,7 +895,7 @@ mod tests {
                         // NOTE: The read above is too quick as it only awaits
                         //       for a lock, and thereby prevents switching
                         //       to another task -- yield to enable switching
-                        task::yield_now().await;
+                        let _ = task::yield_now().await;
                     }
                     Err(x) => panic!("Unexpected error: {}", x),
                 }
@@ -390,6 +907,69 @@ mod tests {
         assert_eq!(buf, b"test\n");
     }
 
+    #[tokio::test]
+    async fn test_subscribe_stdout_should_stream_output_alongside_reads() {
+        let child = Command::new("echo")
+            .arg("test")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut local_proc = LocalProc::new(child).spawn();
+        let mut cursor = local_proc.subscribe_stdout().unwrap();
+
+        let mut streamed = Vec::new();
+        timeout(Duration::from_millis(1000), async {
+            while let Some(buf) = cursor.next().await {
+                streamed.extend(buf);
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(streamed, b"test\n");
+
+        // Output streamed to a subscriber is still there to be read
+        assert_eq!(local_proc.read_stdout().await.unwrap(), b"test\n");
+        assert!(local_proc.subscribe_stderr().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_stdout_should_only_return_complete_lines_in_line_mode() {
+        let child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut local_proc = LocalProc::new(child).spawn();
+        local_proc.set_framing(ProcIoMode::Line, Some(Newline::Lf));
+
+        async fn read_next_lines(local_proc: &mut LocalProc) -> Vec<u8> {
+            timeout(Duration::from_millis(100), async {
+                loop {
+                    let buf = local_proc.read_stdout().await.unwrap();
+                    if !buf.is_empty() {
+                        break buf;
+                    }
+
+                    let _ = task::yield_now().await;
+                }
+            })
+            .await
+            .unwrap()
+        }
+
+        // Partial line is held back until it is completed
+        local_proc.write_stdin(b"a\r\nb").await.unwrap();
+        assert_eq!(read_next_lines(&mut local_proc).await, b"a\n");
+
+        local_proc.write_stdin(b"c\r\n").await.unwrap();
+        assert_eq!(read_next_lines(&mut local_proc).await, b"bc\n");
+    }
+
     #[tokio::test]
     async fn test_read_stderr_should_return_an_error_if_not_piped() {
         let child = Command::new("rev")
@@ -442,7 +1022,7 @@ mod tests {
 
         // Get first batch of bytes and discard
         assert!(
-            !timeout(Duration::from_millis(10), async {
+            !timeout(Duration::from_secs(1), async {
                 loop {
                     match local_proc.read_stderr().await {
                         Ok(buf) => {
@@ -453,7 +1033,7 @@ mod tests {
                             // NOTE: The read above is too quick as it only awaits
                             //       for a lock, and thereby prevents switching
                             //       to another task -- yield to enable switching
-                            task::yield_now().await;
+                            let _ = task::yield_now().await;
                         }
                         Err(x) => panic!("Unexpected error: {}", x),
                     }
@@ -484,7 +1064,7 @@ mod tests {
 
         let mut local_proc = LocalProc::new(child).spawn();
 
-        let buf = timeout(Duration::from_millis(10), async {
+        let buf = timeout(Duration::from_secs(1), async {
             loop {
                 match local_proc.read_stderr().await {
                     Ok(buf) => {
@@ -495,7 +1075,7 @@ mod tests {
                         // NOTE: The read above is too quick as it only awaits
                         //       for a lock, and thereby prevents switching
                         //       to another task -- yield to enable switching
-                        task::yield_now().await;
+                        let _ = task::yield_now().await;
                     }
                     Err(x) => panic!("Unexpected error: {}", x),
                 }
@@ -504,7 +1084,7 @@ mod tests {
         .await
         .unwrap();
 
-        assert!(buf.len() > 0);
+        assert!(!buf.is_empty());
     }
 
     #[tokio::test]
@@ -563,6 +1143,26 @@ mod tests {
         assert!(local_proc.exit_status().await.is_some());
     }
 
+    #[tokio::test]
+    async fn exit_status_should_remove_temp_file_once_exited() {
+        let (_, path) = tempfile::NamedTempFile::new().unwrap().keep().unwrap();
+        let child = Command::new("echo")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut local_proc = LocalProc::new(child).spawn();
+        local_proc.set_temp_file(&path);
+
+        // Give process some time to run and complete
+        delay_for(Duration::from_millis(10)).await;
+
+        assert!(local_proc.exit_status().await.is_some());
+        assert!(!path.exists(), "Temp file not removed");
+    }
+
     #[tokio::test]
     async fn kill_should_send_kill_request_to_process_without_waiting() {
         let child = Command::new("sleep")
@@ -0,0 +1,264 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::Duration;
+
+/// Snapshot of a process running on the same machine as the server, which
+/// may or may not have been spawned by the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub parent_pid: u32,
+
+    /// Full command line of the process, or its name if unavailable
+    pub command: String,
+
+    /// Total CPU time (user and system) consumed by the process
+    pub cpu_time: Duration,
+
+    /// Time since the process was started
+    pub elapsed: Duration,
+
+    /// Resident set size of the process in bytes
+    pub rss_bytes: u64,
+}
+
+impl ProcInfo {
+    /// Average CPU usage over the lifetime of the process as a percentage of
+    /// a single core, matching what `ps` reports
+    pub fn cpu_percent(&self) -> f32 {
+        let elapsed = self.elapsed.as_secs_f32();
+        if elapsed > 0.0 {
+            self.cpu_time.as_secs_f32() / elapsed * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Reads a snapshot of the process with `pid`
+#[cfg(target_os = "linux")]
+pub async fn read_proc_info(pid: u32) -> io::Result<ProcInfo> {
+    let stat = tokio::fs::read_to_string(format!("/proc/{}/stat", pid)).await?;
+    let cmdline = tokio::fs::read(format!("/proc/{}/cmdline", pid))
+        .await
+        .unwrap_or_default();
+    let uptime = tokio::fs::read_to_string("/proc/uptime").await?;
+
+    parse_proc_info(pid, &stat, &cmdline, &uptime, clock_ticks(), page_size())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn read_proc_info(_pid: u32) -> io::Result<ProcInfo> {
+    Err(unsupported())
+}
+
+/// Reads a snapshot of the process with `pid` followed by all of its
+/// descendants, ordered breadth-first
+pub async fn read_proc_tree(pid: u32) -> io::Result<Vec<ProcInfo>> {
+    let mut procs = vec![read_proc_info(pid).await?];
+    procs.extend(list_descendants(pid).await?);
+    Ok(procs)
+}
+
+/// Reads a snapshot of every descendant of the process with `pid`, ordered
+/// breadth-first, skipping any that exit while being read
+pub async fn list_descendants(pid: u32) -> io::Result<Vec<ProcInfo>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (child, parent) in list_parent_pids().await? {
+        children.entry(parent).or_default().push(child);
+    }
+
+    let mut descendants = Vec::new();
+    let mut queue: VecDeque<u32> = VecDeque::new();
+    queue.push_back(pid);
+    while let Some(next) = queue.pop_front() {
+        for child in children.remove(&next).unwrap_or_default() {
+            match read_proc_info(child).await {
+                Ok(info) => descendants.push(info),
+                Err(x) if x.kind() == io::ErrorKind::NotFound => continue,
+                Err(x) => return Err(x),
+            }
+            queue.push_back(child);
+        }
+    }
+
+    Ok(descendants)
+}
+
+/// Forcefully kills the process with `pid`, succeeding if it has already
+/// exited
+#[cfg(unix)]
+pub fn kill_pid(pid: u32) -> io::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } == 0 {
+        return Ok(());
+    }
+
+    match io::Error::last_os_error() {
+        x if x.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        x => Err(x),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill_pid(_pid: u32) -> io::Result<()> {
+    Err(unsupported())
+}
+
+/// Lists the pid of every process alongside the pid of its parent
+#[cfg(target_os = "linux")]
+async fn list_parent_pids() -> io::Result<Vec<(u32, u32)>> {
+    let mut pids = Vec::new();
+    let mut entries = tokio::fs::read_dir("/proc").await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let pid = match entry.file_name().to_str().map(str::parse::<u32>) {
+            Some(Ok(pid)) => pid,
+            _ => continue,
+        };
+
+        // Processes can exit while we are scanning, so skip any that fail
+        let path = entry.path().join("stat");
+        if let Ok(stat) = tokio::fs::read_to_string(path).await {
+            if let Some(parent_pid) = stat_fields(&stat)
+                .and_then(|(_, fields)| fields.get(1)?.parse().ok())
+            {
+                pids.push((pid, parent_pid));
+            }
+        }
+    }
+    Ok(pids)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn list_parent_pids() -> io::Result<Vec<(u32, u32)>> {
+    Err(unsupported())
+}
+
+/// Splits the contents of a stat file into the process name and the fields
+/// that follow it, starting with the state of the process
+fn stat_fields(stat: &str) -> Option<(&str, Vec<&str>)> {
+    // The name is wrapped in parens and can itself contain spaces or parens
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?;
+    Some((name, stat[close + 1..].split_whitespace().collect()))
+}
+
+fn parse_proc_info(
+    pid: u32,
+    stat: &str,
+    cmdline: &[u8],
+    uptime: &str,
+    clock_ticks: u64,
+    page_size: u64,
+) -> io::Result<ProcInfo> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Malformed stat for proc {}", pid),
+        )
+    };
+
+    let (name, fields) = stat_fields(stat).ok_or_else(invalid)?;
+    let field = |i: usize| -> io::Result<u64> {
+        fields
+            .get(i)
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(invalid)
+    };
+
+    let uptime_secs: f64 = uptime
+        .split_whitespace()
+        .next()
+        .and_then(|x| x.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let command = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let clock_ticks = clock_ticks.max(1);
+    let started_secs = field(19)? as f64 / clock_ticks as f64;
+
+    Ok(ProcInfo {
+        pid,
+        parent_pid: field(1)? as u32,
+        command: if command.is_empty() {
+            name.to_string()
+        } else {
+            command
+        },
+        cpu_time: Duration::from_millis(
+            (field(11)? + field(12)?) * 1000 / clock_ticks,
+        ),
+        elapsed: Duration::from_secs_f64((uptime_secs - started_secs).max(0.0)),
+        rss_bytes: field(21)? * page_size,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn clock_ticks() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_CLK_TCK) as u64 }
+}
+
+#[cfg(target_os = "linux")]
+fn page_size() -> u64 {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as u64 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "Process information is not supported on this platform",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proc_info_should_support_names_with_spaces_and_parens() {
+        let stat = "42 (my (odd) proc) S 7 42 42 0 -1 4194560 100 0 0 0 \
+            150 50 0 0 20 0 1 0 1000 1000000 25 18446744073709551615";
+
+        let info =
+            parse_proc_info(42, stat, b"", "30.00 10.00", 100, 4096).unwrap();
+
+        assert_eq!(info.pid, 42);
+        assert_eq!(info.parent_pid, 7);
+        assert_eq!(info.command, "my (odd) proc");
+        assert_eq!(info.cpu_time, Duration::from_secs(2));
+        assert_eq!(info.elapsed, Duration::from_secs(20));
+        assert_eq!(info.rss_bytes, 25 * 4096);
+        assert_eq!(info.cpu_percent(), 10.0);
+    }
+
+    #[test]
+    fn parse_proc_info_should_prefer_full_command_line() {
+        let stat = "42 (sh) S 7 42 42 0 -1 4194560 100 0 0 0 \
+            0 0 0 0 20 0 1 0 0 1000000 25 18446744073709551615";
+
+        let info = parse_proc_info(
+            42,
+            stat,
+            b"sh\0-c\0sleep 60\0",
+            "30.00 10.00",
+            100,
+            4096,
+        )
+        .unwrap();
+
+        assert_eq!(info.command, "sh -c sleep 60");
+    }
+
+    #[test]
+    fn parse_proc_info_should_fail_if_stat_malformed() {
+        let err = parse_proc_info(42, "42 sh S", b"", "30.00", 100, 4096)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
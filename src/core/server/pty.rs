@@ -0,0 +1,265 @@
+use crate::utils::Capture;
+use log::error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::sync::{mpsc, Arc};
+use tokio::process::Command;
+
+/// Byte sent by a terminal for ctrl-d, which ends input in canonical mode
+const EOT: u8 = 0x04;
+
+/// Master side of a pseudo-terminal, through which the server captures what
+/// a proc writes to its terminal, writes input to it, and resizes it
+///
+/// Input is written by a thread of its own so that a proc that stops
+/// reading its terminal never blocks the server.
+#[derive(Debug)]
+pub struct Pty {
+    master: File,
+
+    /// Sends input to the thread writing to the terminal, dropped once the
+    /// input is closed
+    input_tx: Option<mpsc::Sender<Vec<u8>>>,
+}
+
+impl Pty {
+    /// Opens a pseudo-terminal of `rows` and `cols`, returning it alongside
+    /// the terminal side that is handed to the proc as its stdio
+    pub fn open(rows: u16, cols: u16) -> io::Result<(Self, File)> {
+        let (master, terminal) = open_pair()?;
+
+        let mut writer = master.try_clone()?;
+        let (input_tx, input_rx) = mpsc::channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            for input in input_rx {
+                if let Err(x) = writer.write_all(&input) {
+                    error!("pty writer died: {}", x);
+                    break;
+                }
+            }
+        });
+
+        let pty = Self {
+            master,
+            input_tx: Some(input_tx),
+        };
+        pty.resize(rows, cols)?;
+        Ok((pty, terminal))
+    }
+
+    /// Captures everything written to the terminal into `output`, closing
+    /// it once every terminal side has been closed
+    pub fn capture_output(&self, output: Arc<Capture<u8>>) -> io::Result<()> {
+        let mut reader = self.master.try_clone()?;
+        std::thread::spawn(move || {
+            let mut buf = [0; 1024];
+            loop {
+                // NOTE: Linux fails reads with EIO rather than yielding 0
+                //       once the terminal side is closed, so any error is
+                //       taken as the end of the output
+                match reader.read(&mut buf) {
+                    Ok(size) if size > 0 => output.push(&buf[..size]),
+                    Err(x) if x.kind() == io::ErrorKind::Interrupted => (),
+                    _ => break,
+                }
+            }
+
+            output.close();
+        });
+
+        Ok(())
+    }
+
+    /// Queues `buf` to be written to the terminal as input
+    pub fn write(&self, buf: &[u8]) -> io::Result<()> {
+        match self.input_tx.as_ref() {
+            Some(tx) if tx.send(buf.to_vec()).is_ok() => Ok(()),
+            _ => Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+        }
+    }
+
+    /// Ends the input of the terminal as ctrl-d would, after which writing
+    /// fails
+    pub fn close_input(&mut self) {
+        let _ = self.write(&[EOT]);
+        self.input_tx = None;
+    }
+
+    /// Changes the size of the terminal, which signals the proc with
+    /// SIGWINCH on unix
+    pub fn resize(&self, rows: u16, cols: u16) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            let size = libc::winsize {
+                ws_row: rows,
+                ws_col: cols,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            let result = unsafe {
+                libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &size)
+            };
+            if result < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = (rows, cols);
+            Err(unsupported())
+        }
+    }
+}
+
+/// Makes the terminal that the proc receives as its stdin the controlling
+/// terminal of a new session, so that it receives job control signals and
+/// can open /dev/tty
+#[cfg(unix)]
+pub fn set_controlling_terminal(cmd: &mut Command) {
+    // NOTE: This runs in the forked child prior to exec, so we only call
+    //       async-signal-safe functions
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            #[allow(clippy::cast_lossless)]
+            if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn set_controlling_terminal(_cmd: &mut Command) {}
+
+/// Opens the master and terminal sides of a new pseudo-terminal
+#[cfg(unix)]
+fn open_pair() -> io::Result<(File, File)> {
+    use std::os::unix::{fs::OpenOptionsExt, io::FromRawFd};
+
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // NOTE: Owned before anything else can fail so that it is closed
+    let master = unsafe { File::from_raw_fd(fd) };
+    unsafe {
+        if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) < 0
+            || libc::grantpt(fd) < 0
+            || libc::unlockpt(fd) < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    let terminal = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(terminal_name(fd)?)?;
+
+    Ok((master, terminal))
+}
+
+#[cfg(not(unix))]
+fn open_pair() -> io::Result<(File, File)> {
+    Err(unsupported())
+}
+
+/// Path of the terminal side of the pseudo-terminal with master `fd`
+#[cfg(target_os = "linux")]
+fn terminal_name(fd: libc::c_int) -> io::Result<std::path::PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut buf = [0 as libc::c_char; 128];
+    let result = unsafe { libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+
+    let name = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    Ok(std::ffi::OsStr::from_bytes(name.to_bytes()).into())
+}
+
+/// Path of the terminal side of the pseudo-terminal with master `fd`
+///
+/// Not every platform has a reentrant form of ptsname, so the name is
+/// copied out immediately.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn terminal_name(fd: libc::c_int) -> io::Result<std::path::PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = unsafe { libc::ptsname(fd) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    Ok(std::ffi::OsStr::from_bytes(name.to_bytes()).into())
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "Pseudo-terminals are not supported on this platform",
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn capture_output_should_yield_what_the_proc_writes() {
+        let (pty, terminal) = Pty::open(24, 80).unwrap();
+        let output = Arc::new(Capture::new(1024));
+        pty.capture_output(Arc::clone(&output)).unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("stty size; read x; echo got $x")
+            .stdin(terminal.try_clone().unwrap())
+            .stdout(terminal.try_clone().unwrap())
+            .stderr(terminal);
+        set_controlling_terminal(&mut cmd);
+        let child = cmd.spawn().unwrap();
+        drop(cmd);
+
+        pty.write(b"abc\n").unwrap();
+        child.await.unwrap();
+
+        let mut cursor = output.subscribe();
+        for _ in 0..100 {
+            if output.is_closed() {
+                break;
+            }
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+        }
+
+        let text = String::from_utf8(cursor.peek().to_vec()).unwrap();
+        assert!(text.contains("24 80"), "Unexpected output: {:?}", text);
+        assert!(text.contains("got abc"), "Unexpected output: {:?}", text);
+    }
+
+    #[test]
+    fn write_should_fail_once_input_is_closed() {
+        let (mut pty, _terminal) = Pty::open(24, 80).unwrap();
+        pty.write(b"abc").unwrap();
+
+        pty.close_input();
+        let err = pty.write(b"abc").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
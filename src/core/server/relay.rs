@@ -0,0 +1,182 @@
+use crate::core::{
+    client::{error::AskError, Client, ConnectedClient},
+    reply::ForwardArgs,
+    transport::{Authenticator, Bicrypter},
+    Reply, ReplyError,
+};
+use crate::utils::TtlMap;
+use futures::future::{BoxFuture, FutureExt};
+use log::{debug, warn};
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Time a connection to a server forwarded to can go unused before it is
+/// dropped
+pub const DEFAULT_RELAY_IDLE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Maximum connections to servers forwarded to that are kept open at once,
+/// past which the least recently used is dropped
+pub const MAX_RELAY_CONNECTIONS: usize = 64;
+
+/// Connects to a server that requests are forwarded to
+pub type RelayConnector = Arc<
+    dyn Fn(SocketAddr) -> BoxFuture<'static, io::Result<ConnectedClient>>
+        + Send
+        + Sync,
+>;
+
+/// Relay of requests forwarded through the server to other servers
+///
+/// Connections to the servers forwarded to are opened on first use and
+/// reused by later requests, being dropped and opened anew once one fails,
+/// goes unused, or is the least recently used of too many. Forwarded msgs
+/// are sent exactly as encoded by their sender, so the servers forwarded to
+/// can check the signature of the original sender. Every request is
+/// rejected by a default relay, which cannot connect.
+#[derive(Clone)]
+pub struct Relay {
+    connector: Option<RelayConnector>,
+    clients: Arc<Mutex<TtlMap<SocketAddr, Arc<Mutex<ConnectedClient>>>>>,
+}
+
+impl Default for Relay {
+    fn default() -> Self {
+        Self {
+            connector: None,
+            clients: Arc::new(Mutex::new(
+                TtlMap::new(DEFAULT_RELAY_IDLE_TTL)
+                    .with_max_len(MAX_RELAY_CONNECTIONS),
+            )),
+        }
+    }
+}
+
+impl fmt::Debug for Relay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Relay")
+            .field("enabled", &self.is_enabled())
+            .finish()
+    }
+}
+
+impl Relay {
+    /// Creates a relay that connects to servers using `connector`
+    pub fn new(connector: RelayConnector) -> Self {
+        Self {
+            connector: Some(connector),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a relay that connects to servers with the configuration of
+    /// `client`, such as its authenticator and bicrypter, only pointed at
+    /// each server in turn
+    pub fn from_client<A, B>(client: Client<A, B>) -> Self
+    where
+        A: Authenticator + Send + Sync + Clone + 'static,
+        B: Bicrypter + Send + Sync + Clone + 'static,
+    {
+        Self::new(Arc::new(move |addr| {
+            let mut client = client.clone();
+            client.retarget(addr);
+            client.connect().boxed()
+        }))
+    }
+
+    /// Whether requests can be forwarded through the relay
+    pub fn is_enabled(&self) -> bool {
+        self.connector.is_some()
+    }
+
+    /// Addresses of the servers the relay is connected to
+    pub async fn connected(&self) -> Vec<SocketAddr> {
+        self.clients.lock().await.keys().copied().collect()
+    }
+
+    /// Forwards the request encoded in `msg` to the server at `address`
+    /// without changing it, yielding the reply of that server
+    pub async fn forward(&self, address: SocketAddr, msg: Vec<u8>) -> Reply {
+        let client = match self.client(address).await {
+            Ok(client) => client,
+            Err(x) => return Reply::from(x),
+        };
+
+        // NOTE: The client is only locked while sending so that other
+        //       requests to the same server are not held up by this one
+        let sent = client.lock().await.send_encoded(msg).await;
+        let result = match sent {
+            Ok(reply) => reply.await,
+            Err(x) => Err(x),
+        };
+
+        let reply = match result {
+            Ok(reply) => reply,
+            Err(AskError::Failure { msg, code }) => {
+                Reply::Error(ReplyError::with_code(msg, code))
+            }
+            Err(x) => {
+                if matches!(
+                    x,
+                    AskError::SendFailed
+                        | AskError::Timeout
+                        | AskError::CallbackLost
+                ) {
+                    warn!("Dropping connection to {} after: {}", address, x);
+                    self.clients.lock().await.remove(&address);
+                }
+
+                let code = x.code();
+                return Reply::Error(ReplyError::with_code(
+                    format!("Failed to forward to {}: {}", address, x),
+                    code,
+                ));
+            }
+        };
+
+        Reply::Forward(ForwardArgs {
+            address,
+            reply: Box::new(reply),
+        })
+    }
+
+    /// Connection to the server at `address`, opening one if there is none
+    async fn client(
+        &self,
+        address: SocketAddr,
+    ) -> io::Result<Arc<Mutex<ConnectedClient>>> {
+        let connector = self.connector.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Server does not forward requests",
+            )
+        })?;
+
+        {
+            let mut clients = self.clients.lock().await;
+            if clients.touch(&address) {
+                if let Some(client) = clients.get(&address) {
+                    return Ok(Arc::clone(client));
+                }
+            }
+        }
+
+        // NOTE: Connecting happens without holding the lock so that a slow
+        //       server does not hold up requests forwarded to others
+        debug!("Connecting to {} to forward requests", address);
+        let client = Arc::new(Mutex::new(connector(address).await?));
+
+        // Share the connection of any request to the same server that
+        // connected in the meantime, dropping this one
+        let mut clients = self.clients.lock().await;
+        if let Some(existing) = clients.get(&address) {
+            return Ok(Arc::clone(existing));
+        }
+        clients.evict_expired();
+        clients.insert(address, Arc::clone(&client));
+        Ok(client)
+    }
+}
@@ -0,0 +1,529 @@
+use super::job::{
+    default_jobs_dir, now_millis, write_json_atomic, JobManager, LocalJobSpec,
+};
+use crate::utils::TaskTracker;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, TimeZone};
+use chrono::{Timelike, Utc};
+use log::error;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+const SCHEDULES_FILE_NAME: &str = "schedules.json";
+
+/// Maximum runs remembered per schedule, after which the oldest are dropped
+pub const MAX_SCHEDULE_HISTORY: usize = 20;
+
+/// Determines when a schedule submits a new job
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum LocalTrigger {
+    /// Runs repeatedly with the given time between runs
+    Interval(Duration),
+
+    /// Runs whenever the time (in UTC) matches the five-field cron expression
+    Cron(String),
+}
+
+impl LocalTrigger {
+    /// Calculates the next time (in milliseconds since the unix epoch) after
+    /// `after` that the trigger fires, failing if it never will
+    pub fn next_run_after(&self, after: u64) -> io::Result<u64> {
+        match self {
+            Self::Interval(interval) if interval.as_millis() == 0 => {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Schedule interval must be greater than zero",
+                ))
+            }
+            Self::Interval(interval) => Ok(after + interval.as_millis() as u64),
+            Self::Cron(expr) => CronSchedule::from_str(expr)?
+                .next_after(Utc.timestamp_millis(after as i64))
+                .map(|t| t.timestamp_millis() as u64)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Cron expression never runs: {}", expr),
+                    )
+                }),
+        }
+    }
+}
+
+/// A single job submitted by a schedule
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleRun {
+    pub job_id: u32,
+    pub started_at: u64,
+}
+
+/// Persistent record of a schedule, where all times are in milliseconds
+/// since the unix epoch
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LocalSchedule {
+    pub id: u32,
+    pub spec: LocalJobSpec,
+    pub trigger: LocalTrigger,
+    pub created_at: u64,
+    pub next_run_at: u64,
+
+    /// Most recent runs of the schedule, oldest first
+    pub runs: Vec<ScheduleRun>,
+}
+
+/// Manages schedules that submit jobs on a recurring basis, persisted to disk
+/// so that they survive server restarts
+///
+/// Runs missed while the server was not running are not replayed; instead,
+/// an overdue schedule runs once and then resumes its normal cadence
+#[derive(Debug)]
+pub struct ScheduleManager {
+    path: PathBuf,
+
+    /// Schedules keyed by id, loaded from disk on first use
+    schedules: Mutex<Option<HashMap<u32, LocalSchedule>>>,
+}
+
+impl Default for ScheduleManager {
+    fn default() -> Self {
+        Self::new(default_jobs_dir())
+    }
+}
+
+impl ScheduleManager {
+    /// Creates a manager that stores its schedules within `dir`
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: dir.as_ref().join(SCHEDULES_FILE_NAME),
+            schedules: Mutex::new(None),
+        }
+    }
+
+    /// Adds a new schedule that submits a job matching `spec` whenever
+    /// `trigger` fires
+    pub async fn create(
+        &self,
+        spec: LocalJobSpec,
+        trigger: LocalTrigger,
+    ) -> io::Result<LocalSchedule> {
+        let created_at = now_millis();
+        let schedule = LocalSchedule {
+            id: OsRng.next_u32(),
+            next_run_at: trigger.next_run_after(created_at)?,
+            spec,
+            trigger,
+            created_at,
+            runs: Vec::new(),
+        };
+
+        let mut guard = self.schedules.lock().await;
+        let schedules = load(&self.path, &mut guard).await?;
+        schedules.insert(schedule.id, schedule.clone());
+        save(&self.path, schedules).await?;
+
+        Ok(schedule)
+    }
+
+    /// Returns all schedules, ordered by when they were created
+    pub async fn list(&self) -> io::Result<Vec<LocalSchedule>> {
+        let mut guard = self.schedules.lock().await;
+        let mut schedules: Vec<LocalSchedule> = load(&self.path, &mut guard)
+            .await?
+            .values()
+            .cloned()
+            .collect();
+        schedules.sort_by_key(|s| (s.created_at, s.id));
+        Ok(schedules)
+    }
+
+    /// Removes the schedule with `id`, leaving any jobs it already submitted
+    pub async fn delete(&self, id: u32) -> io::Result<LocalSchedule> {
+        let mut guard = self.schedules.lock().await;
+        let schedules = load(&self.path, &mut guard).await?;
+        let schedule = schedules.remove(&id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No schedule created with id {}", id),
+            )
+        })?;
+        save(&self.path, schedules).await?;
+
+        Ok(schedule)
+    }
+
+    /// Adds schedules created elsewhere, such as on another server,
+    /// replacing any with the same ids
+    pub async fn restore(
+        &self,
+        schedules: Vec<LocalSchedule>,
+    ) -> io::Result<()> {
+        let mut guard = self.schedules.lock().await;
+        let existing = load(&self.path, &mut guard).await?;
+        existing.extend(schedules.into_iter().map(|s| (s.id, s)));
+        save(&self.path, existing).await
+    }
+
+    /// Submits a job for every schedule whose next run has arrived, then
+    /// advances each of those schedules to their following run, with the
+    /// jobs tracked by `tasks`
+    pub async fn run_due(
+        &self,
+        jobs: &JobManager,
+        tasks: &TaskTracker,
+    ) -> io::Result<()> {
+        let mut guard = self.schedules.lock().await;
+        let schedules = load(&self.path, &mut guard).await?;
+
+        let now = now_millis();
+        let mut changed = false;
+        for schedule in schedules.values_mut() {
+            if schedule.next_run_at > now {
+                continue;
+            }
+
+            match jobs.submit(tasks, schedule.spec.clone()).await {
+                Ok(record) => {
+                    schedule.runs.push(ScheduleRun {
+                        job_id: record.id,
+                        started_at: record.submitted_at,
+                    });
+                    if schedule.runs.len() > MAX_SCHEDULE_HISTORY {
+                        let extra = schedule.runs.len() - MAX_SCHEDULE_HISTORY;
+                        schedule.runs.drain(..extra);
+                    }
+                }
+                Err(x) => error!(
+                    "Failed to submit job for schedule {}: {}",
+                    schedule.id, x
+                ),
+            }
+
+            // NOTE: Trigger was validated upon creation, so this should only
+            //       fail if the trigger has no remaining runs
+            schedule.next_run_at =
+                schedule.trigger.next_run_after(now).unwrap_or(u64::MAX);
+            changed = true;
+        }
+
+        if changed {
+            save(&self.path, schedules).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Populates `slot` with the schedules stored at `path` if not yet loaded
+async fn load<'a>(
+    path: &Path,
+    slot: &'a mut Option<HashMap<u32, LocalSchedule>>,
+) -> io::Result<&'a mut HashMap<u32, LocalSchedule>> {
+    if slot.is_none() {
+        let schedules: Vec<LocalSchedule> =
+            match tokio::fs::read_to_string(path).await {
+                Ok(text) => serde_json::from_str(&text).map_err(|x| {
+                    io::Error::new(io::ErrorKind::InvalidData, x)
+                })?,
+                Err(x) if x.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(x) => return Err(x),
+            };
+
+        *slot = Some(schedules.into_iter().map(|s| (s.id, s)).collect());
+    }
+
+    Ok(slot.as_mut().unwrap())
+}
+
+async fn save(
+    path: &Path,
+    schedules: &HashMap<u32, LocalSchedule>,
+) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let schedules: Vec<&LocalSchedule> = schedules.values().collect();
+    write_json_atomic(path, &schedules).await
+}
+
+/// Five-field cron expression (minute, hour, day of month, month, and day of
+/// week) where each field supports `*`, single values, ranges (`a-b`), steps
+/// (`*/n` or `a-b/n`), and comma-separated lists of these
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+
+    /// Whether neither the day of month nor day of week began with `*`, in
+    /// which case a day matching either is valid (as with standard cron)
+    either_day: bool,
+}
+
+impl CronSchedule {
+    /// Finds the first minute strictly after `after` matching the schedule,
+    /// searching up to four years ahead to account for leap days
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after.date().and_hms(after.hour(), after.minute(), 0)
+            + ChronoDuration::minutes(1);
+        let limit = t + ChronoDuration::days(366 * 4);
+
+        while t < limit {
+            if !has_bit(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.ymd(year, month, 1).and_hms(0, 0, 0);
+            } else if !self.matches_day(t) {
+                t = t.date().succ().and_hms(0, 0, 0);
+            } else if !has_bit(self.hours, t.hour()) {
+                t = t.date().and_hms(t.hour(), 0, 0) + ChronoDuration::hours(1);
+            } else if !has_bit(self.minutes, t.minute()) {
+                t = t + ChronoDuration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, t: DateTime<Utc>) -> bool {
+        let day = has_bit(self.days, t.day());
+        let weekday =
+            has_bit(self.weekdays, t.weekday().num_days_from_sunday());
+
+        if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid cron expression '{}': {}", s, msg),
+            )
+        };
+
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid("expected 5 fields"));
+        }
+
+        let mut weekdays =
+            parse_cron_field(fields[4], 0, 7).map_err(invalid)?;
+
+        // Sunday can be represented as either 0 or 7
+        if has_bit(weekdays, 7) {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_cron_field(fields[0], 0, 59).map_err(invalid)?,
+            hours: parse_cron_field(fields[1], 0, 23).map_err(invalid)?,
+            days: parse_cron_field(fields[2], 1, 31).map_err(invalid)?,
+            months: parse_cron_field(fields[3], 1, 12).map_err(invalid)?,
+            weekdays,
+            either_day: !fields[2].starts_with('*')
+                && !fields[4].starts_with('*'),
+        })
+    }
+}
+
+/// Parses a single cron field into a bitmask of the values it matches
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, &str> {
+    let parse = |x: &str| x.parse::<u32>().map_err(|_| "invalid number");
+
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => (&part[..i], parse(&part[i + 1..])?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err("step must be greater than zero");
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(i) = range.find('-') {
+            (parse(&range[..i])?, parse(&range[i + 1..])?)
+        } else if step > 1 {
+            (parse(range)?, max)
+        } else {
+            let value = parse(range)?;
+            (value, value)
+        };
+
+        if start < min || end > max || start > end {
+            return Err("value out of range");
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+fn has_bit(mask: u64, bit: u32) -> bool {
+    mask & (1 << bit) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_spec() -> LocalJobSpec {
+        LocalJobSpec {
+            command: String::from("true"),
+            args: vec![],
+            current_dir: None,
+        }
+    }
+
+    fn next(expr: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        CronSchedule::from_str(expr)
+            .unwrap()
+            .next_after(after)
+            .unwrap()
+    }
+
+    #[test]
+    fn cron_schedule_should_find_next_matching_minute() {
+        let after = Utc.ymd(2020, 3, 14).and_hms(10, 7, 30);
+
+        assert_eq!(
+            next("* * * * *", after),
+            Utc.ymd(2020, 3, 14).and_hms(10, 8, 0)
+        );
+        assert_eq!(
+            next("*/15 * * * *", after),
+            Utc.ymd(2020, 3, 14).and_hms(10, 15, 0)
+        );
+        assert_eq!(
+            next("0 9-17/4 * * *", after),
+            Utc.ymd(2020, 3, 14).and_hms(13, 0, 0)
+        );
+        assert_eq!(
+            next("30 2 1 * *", after),
+            Utc.ymd(2020, 4, 1).and_hms(2, 30, 0)
+        );
+        assert_eq!(
+            next("0 0 29 2 *", after),
+            Utc.ymd(2024, 2, 29).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn cron_schedule_should_match_either_day_if_both_restricted() {
+        // 2020-03-14 is a saturday
+        let after = Utc.ymd(2020, 3, 14).and_hms(10, 7, 30);
+
+        assert_eq!(
+            next("0 0 * * 0", after),
+            Utc.ymd(2020, 3, 15).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 * * 7", after),
+            Utc.ymd(2020, 3, 15).and_hms(0, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 20 * 1", after),
+            Utc.ymd(2020, 3, 16).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn cron_schedule_should_reject_invalid_expressions() {
+        for expr in &[
+            "* * * *",
+            "60 * * * *",
+            "* * 0 * *",
+            "*/0 * * * *",
+            "a * * * *",
+        ] {
+            let err = CronSchedule::from_str(expr).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", expr);
+        }
+
+        let trigger = LocalTrigger::Cron(String::from("0 0 31 2 *"));
+        assert!(
+            trigger.next_run_after(0).is_err(),
+            "Impossible cron accepted"
+        );
+    }
+
+    #[tokio::test]
+    async fn schedules_should_persist_across_managers() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ScheduleManager::new(dir.as_ref());
+
+        let trigger = LocalTrigger::Interval(Duration::from_secs(60));
+        let schedule = manager.create(make_spec(), trigger).await.unwrap();
+
+        let other = ScheduleManager::new(dir.as_ref());
+        assert_eq!(other.list().await.unwrap(), vec![schedule.clone()]);
+
+        other.delete(schedule.id).await.unwrap();
+        assert!(other.list().await.unwrap().is_empty());
+
+        let err = other.delete(schedule.id).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn run_due_should_submit_jobs_and_record_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ScheduleManager::new(dir.as_ref());
+        let jobs = JobManager::new(dir.as_ref());
+
+        let trigger = LocalTrigger::Interval(Duration::from_secs(60));
+        let schedule = manager.create(make_spec(), trigger).await.unwrap();
+
+        // Not yet due, so nothing should run
+        manager
+            .run_due(&jobs, &TaskTracker::default())
+            .await
+            .unwrap();
+        assert!(manager.list().await.unwrap()[0].runs.is_empty());
+
+        // Force the schedule to be due
+        manager
+            .schedules
+            .lock()
+            .await
+            .as_mut()
+            .unwrap()
+            .get_mut(&schedule.id)
+            .unwrap()
+            .next_run_at = 0;
+        manager
+            .run_due(&jobs, &TaskTracker::default())
+            .await
+            .unwrap();
+
+        let updated = manager.list().await.unwrap().remove(0);
+        assert_eq!(updated.runs.len(), 1);
+        assert!(updated.next_run_at > now_millis(), "Next run not advanced");
+
+        let record = jobs.get(updated.runs[0].job_id).await.unwrap();
+        assert_eq!(record.spec, make_spec());
+    }
+}
@@ -0,0 +1,382 @@
+//! Hooks that let a server-side Rhai script inspect and rewrite requests
+//! before they are executed and replies before they are sent
+//!
+//! The script may define either or both of:
+//! - `on_request(request)`: given a request as an object map with `type` and
+//!   `payload` fields, returns the request to execute in its place, or `()`
+//!   to execute it unchanged; throwing blocks the request, replying with the
+//!   thrown value as the error
+//! - `on_reply(reply)`: given a reply in the same form, returns the reply to
+//!   send in its place, or `()` to send it unchanged
+//!
+//! Either hook can take the header of the request's msg as a second
+//! parameter, such as to inspect its `trace_id` and `parent_span_id`.
+//!
+//! Scripts can call `audit(tag)` to record an entry in the audit log, tagged
+//! with the trace of the request being processed, and anything they print is
+//! logged. The script is reloaded whenever its file
+//! is modified, and if the modified script fails to compile then the last
+//! script to compile continues to be used
+
+use crate::core::{Header, Reply, ReplyError, Request};
+use log::{debug, info, warn};
+use rhai::{
+    serde::{from_dynamic, to_dynamic},
+    Dynamic, Engine, EvalAltResult, Scope, AST,
+};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+pub use super::AUDIT_LOG_TARGET;
+
+/// Maximum operations a single call of a hook can perform before it is
+/// aborted, so that a misbehaving script cannot stall the server
+pub const MAX_OPERATIONS: u64 = 100_000;
+
+const ON_REQUEST: &str = "on_request";
+const ON_REPLY: &str = "on_reply";
+
+thread_local! {
+    /// Trace of the request whose hook is being called on this thread, which
+    /// tags any audit log entries made by the hook
+    static CURRENT_TRACE: RefCell<Option<String>> =
+        const { RefCell::new(None) };
+}
+
+/// Script whose hooks are applied to each request received by the server
+///
+/// Clones share the same script, reloading it for each other
+#[derive(Clone)]
+pub struct ScriptHooks {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: PathBuf,
+    engine: Engine,
+    script: Mutex<Script>,
+}
+
+struct Script {
+    ast: Arc<AST>,
+    modified: Option<SystemTime>,
+}
+
+impl fmt::Debug for ScriptHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptHooks")
+            .field("path", &self.inner.path)
+            .finish()
+    }
+}
+
+impl ScriptHooks {
+    /// Compiles the script at `path`, failing if it cannot be read or is
+    /// not a valid script
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let engine = make_engine();
+        let script = compile(&engine, &path)?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                path,
+                engine,
+                script: Mutex::new(script),
+            }),
+        })
+    }
+
+    /// Path of the script file
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Passes `request` through the `on_request` hook, yielding the request
+    /// to execute or the reason that it was blocked
+    pub fn on_request(
+        &self,
+        request: Request,
+        header: &Header,
+    ) -> Result<Request, String> {
+        match self.call(ON_REQUEST, &request, header)? {
+            Some(x) => from_dynamic(&x)
+                .map_err(|x| format!("Script yielded invalid request: {}", x)),
+            None => Ok(request),
+        }
+    }
+
+    /// Passes `reply` through the `on_reply` hook, substituting an error if
+    /// the hook fails
+    pub fn on_reply(&self, reply: Reply, header: &Header) -> Reply {
+        match self.call(ON_REPLY, &reply, header) {
+            Ok(Some(x)) => from_dynamic(&x).unwrap_or_else(|x| {
+                Reply::Error(ReplyError::from(format!(
+                    "Script yielded invalid reply: {}",
+                    x
+                )))
+            }),
+            Ok(None) => reply,
+            Err(x) => Reply::Error(ReplyError::from(x)),
+        }
+    }
+
+    /// Calls the hook `name` with `value` (and `header` if the hook takes
+    /// it), yielding none if the script does not define the hook or the hook
+    /// returns `()`
+    fn call<T: Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+        header: &Header,
+    ) -> Result<Option<Dynamic>, String> {
+        let ast = self.current();
+        let arity = match ast
+            .iter_functions()
+            .find(|f| f.name == name && (1..=2).contains(&f.params.len()))
+        {
+            Some(f) => f.params.len(),
+            None => return Ok(None),
+        };
+
+        let arg = to_dynamic(value).map_err(|x| x.to_string())?;
+        CURRENT_TRACE.with(|x| *x.borrow_mut() = header.trace_id.clone());
+        let result = if arity == 1 {
+            self.inner.engine.call_fn::<Dynamic>(
+                &mut Scope::new(),
+                &ast,
+                name,
+                (arg,),
+            )
+        } else {
+            let header = to_dynamic(header).map_err(|x| x.to_string())?;
+            self.inner.engine.call_fn::<Dynamic>(
+                &mut Scope::new(),
+                &ast,
+                name,
+                (arg, header),
+            )
+        };
+        CURRENT_TRACE.with(|x| *x.borrow_mut() = None);
+
+        match result {
+            Ok(x) if x.is_unit() => Ok(None),
+            Ok(x) => Ok(Some(x)),
+            Err(x) => Err(match *x {
+                EvalAltResult::ErrorRuntime(x, _) => x.to_string(),
+                x => format!("Script {} failed: {}", name, x),
+            }),
+        }
+    }
+
+    /// Retrieves the latest script, first recompiling it if its file has
+    /// been modified since it was last compiled
+    fn current(&self) -> Arc<AST> {
+        let path = &self.inner.path;
+        let mut script = match self.inner.script.lock() {
+            Ok(x) => x,
+            Err(x) => x.into_inner(),
+        };
+
+        let modified = modified_time(path);
+        if modified.is_some() && modified != script.modified {
+            match compile(&self.inner.engine, path) {
+                Ok(x) => {
+                    debug!("Reloaded script {:?}", path);
+                    *script = x;
+                }
+                Err(x) => {
+                    warn!("Failed to reload script {:?}: {}", path, x);
+                    script.modified = modified;
+                }
+            }
+        }
+
+        Arc::clone(&script.ast)
+    }
+}
+
+fn make_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|text| info!("Script: {}", text));
+    engine.on_debug(|text, _, pos| debug!("Script ({}): {}", pos, text));
+    engine.register_fn("audit", |tag: &str| {
+        CURRENT_TRACE.with(|trace| match trace.borrow().as_ref() {
+            Some(trace_id) => {
+                info!(target: AUDIT_LOG_TARGET, "[trace {}] {}", trace_id, tag)
+            }
+            None => info!(target: AUDIT_LOG_TARGET, "{}", tag),
+        });
+    });
+    engine
+}
+
+fn compile(engine: &Engine, path: &Path) -> io::Result<Script> {
+    // NOTE: Modified time is captured before reading so that a change made
+    //       while reading is picked up by the next reload
+    let modified = modified_time(path);
+    let text = fs::read_to_string(path)?;
+    let ast = engine
+        .compile(&text)
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+
+    Ok(Script {
+        ast: Arc::new(ast),
+        modified,
+    })
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::request::{ExecProcArgs, RemoveDirArgs};
+
+    fn open_script(text: &str) -> (tempfile::TempDir, ScriptHooks) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hooks.rhai");
+        fs::write(&path, text).unwrap();
+        let hooks = ScriptHooks::open(path).unwrap();
+        (dir, hooks)
+    }
+
+    #[test]
+    fn on_request_should_rewrite_request() {
+        let (_dir, hooks) = open_script(
+            r#"
+            fn on_request(request) {
+                if request.type == "remove_dir_request" {
+                    request.payload.path = "/sandbox" + request.payload.path;
+                    return request;
+                }
+            }
+            "#,
+        );
+
+        let request = hooks
+            .on_request(
+                Request::RemoveDir(RemoveDirArgs {
+                    path: String::from("/dir").into(),
+                    non_empty: false,
+                }),
+                &Header::default(),
+            )
+            .unwrap();
+        match request {
+            Request::RemoveDir(args) => assert_eq!(args.path, "/sandbox/dir"),
+            x => panic!("Unexpected request: {:?}", x),
+        }
+
+        assert_eq!(
+            hooks
+                .on_request(Request::Heartbeat, &Header::default())
+                .unwrap(),
+            Request::Heartbeat
+        );
+    }
+
+    #[test]
+    fn on_request_should_block_request_when_script_throws() {
+        let (_dir, hooks) = open_script(
+            r#"
+            fn on_request(request) {
+                if request.type == "exec_proc_request"
+                    && request.payload.command == "rm" {
+                    throw "rm is not allowed";
+                }
+            }
+            "#,
+        );
+
+        let err = hooks
+            .on_request(
+                Request::ExecProc(ExecProcArgs {
+                    command: String::from("rm"),
+                    ..Default::default()
+                }),
+                &Header::default(),
+            )
+            .unwrap_err();
+        assert_eq!(err, "rm is not allowed");
+    }
+
+    #[test]
+    fn on_request_should_pass_header_to_hook_taking_it() {
+        let (_dir, hooks) = open_script(
+            r#"
+            fn on_request(request, header) {
+                if header.trace_id != "allowed" {
+                    throw "untraced: " + header.parent_span_id;
+                }
+            }
+            "#,
+        );
+
+        let mut header = Header::default();
+        header.with_trace(Some(String::from("allowed")), None);
+        assert!(hooks.on_request(Request::Heartbeat, &header).is_ok());
+
+        header.with_trace(None, Some(String::from("span")));
+        assert_eq!(
+            hooks.on_request(Request::Heartbeat, &header).unwrap_err(),
+            "untraced: span"
+        );
+    }
+
+    #[test]
+    fn on_reply_should_leave_reply_unchanged_without_hook() {
+        let (_dir, hooks) = open_script("let x = 1;");
+        assert_eq!(
+            hooks.on_reply(Reply::Heartbeat, &Header::default()),
+            Reply::Heartbeat
+        );
+    }
+
+    #[test]
+    fn hooks_should_reload_when_script_is_modified() {
+        let (_dir, hooks) =
+            open_script(r#"fn on_request(request) { throw "first"; }"#);
+        assert_eq!(
+            hooks
+                .on_request(Request::Heartbeat, &Header::default())
+                .unwrap_err(),
+            "first"
+        );
+
+        // Ensure the modified time differs on filesystems with coarse times
+        let modified = modified_time(hooks.path());
+        while modified_time(hooks.path()) == modified {
+            fs::write(
+                hooks.path(),
+                r#"fn on_request(request) { throw "second"; }"#,
+            )
+            .unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(
+            hooks
+                .on_request(Request::Heartbeat, &Header::default())
+                .unwrap_err(),
+            "second"
+        );
+
+        // A script that fails to compile leaves the last script in place
+        fs::write(hooks.path(), "fn on_request(").unwrap();
+        assert_eq!(
+            hooks
+                .on_request(Request::Heartbeat, &Header::default())
+                .unwrap_err(),
+            "second"
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Restricts the system calls the server can make once it is listening
+//!
+//! A seccomp-bpf filter is installed on every thread of the process that
+//! allows only the system calls the server needs to serve requests, which
+//! includes spawning procs as they can be requested at any time. Any other
+//! system call fails with a permission error rather than reaching the
+//! kernel, so a flaw in decoding or handling a request cannot be used to
+//! do anything the server itself never does, such as loading kernel modules
+//! or tracing other processes. The filter cannot be removed once installed
+//! and is inherited by procs spawned afterward.
+
+use std::io;
+
+/// Installs the filter on every thread of the process, failing on platforms
+/// other than linux on x86_64 and aarch64
+pub fn install() -> io::Result<()> {
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    {
+        linux::install(&linux::program())
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    {
+        Err(io::Error::other(
+            "Syscall filtering is not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linux {
+    use std::io;
+
+    /// Instruction of a classic bpf program, as in linux/filter.h
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct SockFilter {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
+
+    #[repr(C)]
+    struct SockFprog {
+        len: u16,
+        filter: *const SockFilter,
+    }
+
+    /// Opcodes of the only instructions used, as in linux/bpf_common.h
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_RET_K: u16 = 0x06;
+
+    const SECCOMP_SET_MODE_FILTER: libc::c_long = 1;
+    const SECCOMP_FILTER_FLAG_TSYNC: libc::c_long = 1;
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+    /// Offsets of the fields of seccomp_data, as in linux/seccomp.h
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    /// Syscalls newer than the libc bindings in use, numbered the same on
+    /// every architecture
+    const SYS_PIDFD_OPEN: libc::c_long = 434;
+    const SYS_CLONE3: libc::c_long = 435;
+    const SYS_CLOSE_RANGE: libc::c_long = 436;
+    const SYS_FACCESSAT2: libc::c_long = 439;
+
+    /// Syscalls made by the server and its runtime regardless of the
+    /// requests it serves, named the same on every architecture
+    const COMMON_SYSCALLS: &[libc::c_long] = &[
+        // Memory and threads
+        libc::SYS_brk,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mprotect,
+        libc::SYS_mremap,
+        libc::SYS_madvise,
+        libc::SYS_membarrier,
+        libc::SYS_clone,
+        libc::SYS_futex,
+        libc::SYS_set_robust_list,
+        libc::SYS_get_robust_list,
+        libc::SYS_set_tid_address,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_sched_getparam,
+        libc::SYS_sched_getscheduler,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        libc::SYS_restart_syscall,
+        // Signals
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_kill,
+        libc::SYS_tgkill,
+        libc::SYS_tkill,
+        // Time
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_getres,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_gettimeofday,
+        libc::SYS_nanosleep,
+        libc::SYS_timerfd_create,
+        libc::SYS_timerfd_settime,
+        // Process information
+        libc::SYS_getpid,
+        libc::SYS_getppid,
+        libc::SYS_gettid,
+        libc::SYS_getuid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getegid,
+        libc::SYS_getresuid,
+        libc::SYS_getresgid,
+        libc::SYS_getpgid,
+        libc::SYS_getsid,
+        libc::SYS_getrusage,
+        libc::SYS_getrlimit,
+        libc::SYS_prlimit64,
+        libc::SYS_times,
+        libc::SYS_uname,
+        libc::SYS_sysinfo,
+        libc::SYS_getrandom,
+        libc::SYS_umask,
+        // Descriptors and polling
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_preadv,
+        libc::SYS_pwritev,
+        libc::SYS_close,
+        libc::SYS_lseek,
+        libc::SYS_ioctl,
+        libc::SYS_fcntl,
+        libc::SYS_flock,
+        libc::SYS_dup,
+        libc::SYS_dup3,
+        libc::SYS_pipe2,
+        libc::SYS_eventfd2,
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_ppoll,
+        libc::SYS_pselect6,
+        libc::SYS_memfd_create,
+        libc::SYS_copy_file_range,
+        SYS_CLOSE_RANGE,
+        // Sockets
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept4,
+        libc::SYS_connect,
+        libc::SYS_getsockname,
+        libc::SYS_getpeername,
+        libc::SYS_getsockopt,
+        libc::SYS_setsockopt,
+        libc::SYS_sendto,
+        libc::SYS_recvfrom,
+        libc::SYS_sendmsg,
+        libc::SYS_recvmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_recvmmsg,
+        libc::SYS_shutdown,
+        // Filesystem
+        libc::SYS_openat,
+        libc::SYS_fstat,
+        libc::SYS_newfstatat,
+        libc::SYS_statx,
+        libc::SYS_getdents64,
+        libc::SYS_getcwd,
+        libc::SYS_chdir,
+        libc::SYS_fchdir,
+        libc::SYS_mkdirat,
+        libc::SYS_unlinkat,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_linkat,
+        libc::SYS_symlinkat,
+        libc::SYS_readlinkat,
+        libc::SYS_faccessat,
+        SYS_FACCESSAT2,
+        libc::SYS_fchmod,
+        libc::SYS_fchmodat,
+        libc::SYS_fchown,
+        libc::SYS_fchownat,
+        libc::SYS_utimensat,
+        libc::SYS_fsync,
+        libc::SYS_fdatasync,
+        libc::SYS_fallocate,
+        libc::SYS_inotify_init1,
+        libc::SYS_inotify_add_watch,
+        libc::SYS_inotify_rm_watch,
+        // Procs, which clients can spawn at any time
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_wait4,
+        libc::SYS_waitid,
+        libc::SYS_setpgid,
+        libc::SYS_setsid,
+        SYS_PIDFD_OPEN,
+    ];
+
+    /// Syscalls that only exist on x86_64, largely older forms of those
+    /// above that are still made by libc
+    #[cfg(target_arch = "x86_64")]
+    const ARCH_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_arch_prctl,
+        libc::SYS_open,
+        libc::SYS_creat,
+        libc::SYS_stat,
+        libc::SYS_lstat,
+        libc::SYS_access,
+        libc::SYS_getdents,
+        libc::SYS_readlink,
+        libc::SYS_mkdir,
+        libc::SYS_rmdir,
+        libc::SYS_unlink,
+        libc::SYS_rename,
+        libc::SYS_link,
+        libc::SYS_symlink,
+        libc::SYS_chmod,
+        libc::SYS_chown,
+        libc::SYS_lchown,
+        libc::SYS_utimes,
+        libc::SYS_ftruncate,
+        libc::SYS_statfs,
+        libc::SYS_fstatfs,
+        libc::SYS_sendfile,
+        libc::SYS_pipe,
+        libc::SYS_dup2,
+        libc::SYS_poll,
+        libc::SYS_select,
+        libc::SYS_epoll_create,
+        libc::SYS_epoll_wait,
+        libc::SYS_time,
+        libc::SYS_getpgrp,
+        libc::SYS_fork,
+        libc::SYS_vfork,
+        334, // rseq
+    ];
+
+    /// Syscalls whose names are missing from the libc bindings on aarch64
+    #[cfg(target_arch = "aarch64")]
+    const ARCH_SYSCALLS: &[libc::c_long] = &[
+        43,  // statfs
+        44,  // fstatfs
+        46,  // ftruncate
+        71,  // sendfile
+        293, // rseq
+    ];
+
+    fn stmt(code: u16, k: u32) -> SockFilter {
+        SockFilter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+        SockFilter { code, jt, jf, k }
+    }
+
+    /// Builds the filter, which kills the process for syscalls made through
+    /// another architecture, allows those of the server, and fails the rest
+    pub fn program() -> Vec<SockFilter> {
+        let mut program = vec![
+            stmt(BPF_LD_W_ABS, ARCH_OFFSET),
+            jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, NR_OFFSET),
+            // NOTE: Callers of clone3 only fall back to clone when told the
+            //       syscall does not exist
+            jump(BPF_JMP_JEQ_K, SYS_CLONE3 as u32, 0, 1),
+            stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+        ];
+
+        for nr in COMMON_SYSCALLS.iter().chain(ARCH_SYSCALLS.iter()) {
+            program.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+            program.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        }
+
+        program.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+        program
+    }
+
+    pub fn install(program: &[SockFilter]) -> io::Result<()> {
+        let prog = SockFprog {
+            len: program.len() as u16,
+            filter: program.as_ptr(),
+        };
+
+        // NOTE: Required to install a filter without CAP_SYS_ADMIN and keeps
+        //       procs spawned afterward from gaining privileges through exec
+        if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // NOTE: Safe as the kernel copies the program before returning;
+        //       TSYNC applies the filter to every thread of the process,
+        //       such as those of the runtime, rather than only this one
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_TSYNC,
+                &prog as *const SockFprog,
+            )
+        };
+
+        match result {
+            0 => Ok(()),
+            x if x < 0 => Err(io::Error::last_os_error()),
+            tid => Err(io::Error::other(format!(
+                "Thread {} could not be filtered",
+                tid
+            ))),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn program_should_fit_within_bpf_limits() {
+            let program = program();
+
+            // NOTE: The kernel rejects programs over 4096 instructions
+            assert!(program.len() <= 4096);
+            assert_eq!(
+                program.last(),
+                Some(&stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32))
+            );
+        }
+
+        #[test]
+        fn program_should_allow_each_syscall_once() {
+            let mut nrs: Vec<libc::c_long> = COMMON_SYSCALLS
+                .iter()
+                .chain(ARCH_SYSCALLS.iter())
+                .copied()
+                .collect();
+            let total = nrs.len();
+            nrs.sort_unstable();
+            nrs.dedup();
+            assert_eq!(nrs.len(), total, "Syscall allowed more than once");
+            assert!(!nrs.contains(&SYS_CLONE3));
+            assert!(!nrs.contains(&libc::SYS_ptrace));
+        }
+    }
+}
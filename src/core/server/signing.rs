@@ -0,0 +1,147 @@
+use crate::core::{transport::auth::identity, Msg};
+
+/// How a server treats the signatures of msgs it receives
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SignatureMode {
+    /// Signatures are not checked
+    #[default]
+    Ignore,
+
+    /// Signed msgs are rejected if their signature is invalid or from an
+    /// untrusted key, while unsigned msgs are accepted
+    Verify,
+
+    /// Msgs are rejected unless they have a valid signature from a trusted
+    /// key
+    Require,
+}
+
+/// Policy applied by a server to the signatures of msgs, which authenticate
+/// the original sender of a msg even when it was relayed by other hops
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignaturePolicy {
+    pub mode: SignatureMode,
+
+    /// Public identity keys whose signatures are trusted, where any key is
+    /// trusted if empty
+    pub trusted_keys: Vec<Vec<u8>>,
+}
+
+impl SignatureMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ignore => "ignore",
+            Self::Verify => "verify",
+            Self::Require => "require",
+        }
+    }
+}
+
+impl SignaturePolicy {
+    pub fn new(mode: SignatureMode, trusted_keys: Vec<Vec<u8>>) -> Self {
+        Self { mode, trusted_keys }
+    }
+
+    /// Whether signatures by `public_key` are trusted
+    pub fn trusts(&self, public_key: &[u8]) -> bool {
+        self.trusted_keys.is_empty()
+            || self.trusted_keys.iter().any(|key| key == public_key)
+    }
+
+    /// Checks the signature of `msg`, yielding the reason it is rejected
+    ///
+    /// A msg whose flags require a signature is checked as if signatures
+    /// were required, whatever the mode of the policy
+    pub fn check(&self, msg: &Msg) -> Result<(), String> {
+        let mode = if msg.header.flags.require_signature {
+            SignatureMode::Require
+        } else {
+            self.mode
+        };
+
+        if mode == SignatureMode::Ignore {
+            return Ok(());
+        }
+
+        let public_key = match msg.verify_signature() {
+            Ok(Some(public_key)) => public_key,
+            Ok(None) if mode == SignatureMode::Require => {
+                return Err(String::from("Msg must be signed"))
+            }
+            Ok(None) => return Ok(()),
+            Err(x) => return Err(format!("Msg signature rejected: {}", x)),
+        };
+
+        if self.trusts(public_key) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Msg signed by untrusted key {}",
+                identity::fingerprint(public_key)
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{transport::auth::identity::IdentityKey, Request};
+
+    fn signed_msg(key: &IdentityKey) -> Msg {
+        let mut msg = Msg::from(Request::Heartbeat);
+        msg.sign(key).unwrap();
+        msg
+    }
+
+    #[test]
+    fn check_should_only_reject_unsigned_msgs_if_required() {
+        let unsigned = Msg::from(Request::Heartbeat);
+        let policy = |mode| SignaturePolicy::new(mode, Vec::new());
+
+        assert!(policy(SignatureMode::Ignore).check(&unsigned).is_ok());
+        assert!(policy(SignatureMode::Verify).check(&unsigned).is_ok());
+        assert!(policy(SignatureMode::Require).check(&unsigned).is_err());
+    }
+
+    #[test]
+    fn check_should_require_signature_if_msg_flags_require_it() {
+        let mut unsigned = Msg::from(Request::Heartbeat);
+        unsigned.header.flags.require_signature = true;
+        let policy = SignaturePolicy::default();
+        assert!(policy.check(&unsigned).is_err());
+
+        let key = IdentityKey::generate();
+        let mut signed = Msg::from(Request::Heartbeat);
+        signed.header.flags.require_signature = true;
+        signed.sign(&key).unwrap();
+        assert!(policy.check(&signed).is_ok());
+
+        let policy = SignaturePolicy::new(
+            SignatureMode::Ignore,
+            vec![IdentityKey::generate().public_key().to_vec()],
+        );
+        assert!(policy.check(&signed).is_err());
+    }
+
+    #[test]
+    fn check_should_reject_invalid_or_untrusted_signatures() {
+        let key = IdentityKey::generate();
+        let other_key = IdentityKey::generate();
+        let policy = SignaturePolicy::new(
+            SignatureMode::Verify,
+            vec![key.public_key().to_vec()],
+        );
+
+        assert!(policy.check(&signed_msg(&key)).is_ok());
+        assert!(policy.check(&signed_msg(&other_key)).is_err());
+
+        let mut tampered = signed_msg(&key);
+        tampered.content = Request::Version.into();
+        assert!(policy.check(&tampered).is_err());
+
+        // Ignoring signatures accepts even those that are invalid
+        let policy = SignaturePolicy::default();
+        assert!(policy.check(&tampered).is_ok());
+    }
+}
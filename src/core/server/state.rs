@@ -1,10 +1,35 @@
-use super::{custom::CustomHandler, fs::FileSystemManager, proc::LocalProc};
-use crate::utils::TtlValue;
-use log::error;
+use super::{
+    config::ConfigStore,
+    custom::CustomHandler,
+    eviction::EvictionCounters,
+    fs::{chunks::ChunkStore, events::FsEventHistory, FileSystemManager},
+    job::JobManager,
+    launcher::ProcLauncher,
+    logs::LogSinks,
+    origins::OriginBindings,
+    permissions::Permissions,
+    power::PowerControl,
+    proc::LocalProc,
+    relay::Relay,
+    schedule::ScheduleManager,
+    signing::SignaturePolicy,
+    transfers::TransferAccounting,
+    trusted::TrustedClients,
+    webhook::{WebhookEvent, Webhooks},
+};
+use crate::core::transport::{
+    auth::identity::IdentityKey, crypto::handshake::Session,
+};
+use crate::core::{
+    reply::{EvictionReason, ProcStatusArgs},
+    request::EvictionKind,
+};
+use crate::utils::{TaskTracker, TtlMap};
+use log::{error, info};
 use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 pub mod constants {
@@ -19,27 +44,125 @@ pub mod constants {
     /// Default proc ttl (time since last touched) since a proc has exited
     /// before removing from queriable state (30 sec)
     pub const DEFAULT_DEAD_PROC_TTL: Duration = Duration::from_secs(30);
+
+    /// Default session ttl (time since the origin of a session last
+    /// communicated with the server) before its key is forgotten (30 min)
+    pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(60 * 30);
+
+    /// Default maximum depth of sequence and batch operations nested within
+    /// a request
+    pub const DEFAULT_MAX_REQUEST_DEPTH: u8 = 5;
+
+    /// Default maximum total of operations nested within a request across
+    /// all depths
+    pub const DEFAULT_MAX_NESTED_OPERATIONS: usize = 256;
 }
 
 #[derive(Debug)]
 pub struct ServerState {
     /// Connections server has with clients and last time each client
     /// communicated with the server
-    pub conns: Mutex<HashMap<SocketAddr, Instant>>,
+    pub conns: Mutex<TtlMap<SocketAddr, ()>>,
+
+    /// Origins that have proven they receive replies sent to them
+    pub origins: OriginBindings,
+
+    /// Session negotiated through a handshake by each origin, whose msgs
+    /// must then be sealed with its key
+    pub sessions: Mutex<TtlMap<SocketAddr, Session>>,
 
     /// Mapping of file id -> file on same machine as server
     pub fs_manager: Mutex<FileSystemManager>,
-    pub(super) file_ids: Mutex<HashSet<TtlValue<u32>>>,
+    pub(super) file_ids: Mutex<TtlMap<u32, ()>>,
     file_ttl: Duration,
 
     /// Mapping of proc id -> proc on same machine as server
     pub procs: Mutex<HashMap<u32, LocalProc>>,
-    pub(super) proc_ids: Mutex<HashSet<TtlValue<u32>>>,
+    pub(super) proc_ids: Mutex<TtlMap<u32, ()>>,
     proc_ttl: Duration,
     pub(crate) dead_proc_ttl: Duration,
 
+    /// Limits on the operations nested within a single request
+    max_request_depth: u8,
+    max_nested_operations: usize,
+
+    /// Jobs whose output and outcome persist beyond any connection
+    pub jobs: JobManager,
+
+    /// Schedules that submit jobs on a recurring basis
+    pub schedules: ScheduleManager,
+
+    /// Logs appended to by clients
+    pub logs: LogSinks,
+
+    /// Endpoints notified of events on the server
+    pub webhooks: Webhooks,
+
+    /// Ids of procs whose exit has already been reported to webhooks
+    reported_proc_exits: Mutex<HashSet<u32>>,
+
+    /// Totals of the files, procs, and sessions evicted for going untouched
+    pub evictions: EvictionCounters,
+
     pub custom_handler: Option<CustomHandler>,
 
+    /// Spawns the procs requested of the server
+    pub proc_launcher: ProcLauncher,
+
+    /// Key used to prove the identity of the server to clients that pin it
+    identity_key: Option<IdentityKey>,
+
+    /// Policy applied to the signatures of msgs received by the server
+    pub signature_policy: SignaturePolicy,
+
+    /// Client keys trusted by the server, where msgs from other clients are
+    /// rejected if enabled
+    pub trusted_clients: TrustedClients,
+
+    /// Bytes transferred by each identity and class of request, along with
+    /// the quota of bytes any single identity can transfer
+    pub transfers: TransferAccounting,
+
+    /// Recent changes the server made to the filesystem
+    pub fs_events: FsEventHistory,
+
+    /// Chunks expected by files being uploaded in chunks and where chunks
+    /// seen before can be found
+    pub chunks: ChunkStore,
+
+    /// Config pushed to the server by operators
+    pub config: ConfigStore,
+
+    /// Admins allowed to reboot or shut down the host, along with the power
+    /// actions awaiting their confirmation
+    pub power: PowerControl,
+
+    /// What clients are allowed to do on the server
+    pub permissions: Permissions,
+
+    /// Connections to the servers that requests are forwarded to
+    pub relay: Relay,
+
+    /// Requests being executed along with any tasks they spawn, such as the
+    /// operations of a batch or the delivery of webhook events
+    pub tasks: TaskTracker,
+
+    /// Tasks servicing individual connections to the server
+    pub connection_tasks: TaskTracker,
+
+    /// Faults forced upon requests by the type of request, used to exercise
+    /// error paths when testing
+    #[cfg(feature = "fault-injection")]
+    pub faults: Mutex<HashMap<String, crate::core::request::InjectFaultArgs>>,
+
+    /// Wasm modules serving custom requests of their namespaces
+    #[cfg(feature = "wasm")]
+    pub wasm_handlers: super::wasm::WasmHandlers,
+
+    /// Script whose hooks inspect and rewrite each request and reply
+    #[cfg(feature = "script")]
+    pub script: Option<super::script::ScriptHooks>,
+
     /// Indicator of whether or not the server is running, used to signal
     /// to looping handlers that it is time to shut down if false
     running: AtomicBool,
@@ -52,19 +175,168 @@ impl ServerState {
         dead_proc_ttl: Duration,
     ) -> Self {
         Self {
-            conns: Mutex::new(HashMap::default()),
+            // NOTE: Connections are tracked for as long as the server runs
+            conns: Mutex::new(TtlMap::new(Duration::MAX)),
+            origins: OriginBindings::default(),
+            sessions: Mutex::new(TtlMap::new(constants::DEFAULT_SESSION_TTL)),
             fs_manager: Mutex::new(FileSystemManager::default()),
-            file_ids: Mutex::new(HashSet::default()),
+            file_ids: Mutex::new(TtlMap::new(file_ttl)),
             file_ttl,
             procs: Mutex::new(HashMap::default()),
-            proc_ids: Mutex::new(HashSet::default()),
+            proc_ids: Mutex::new(TtlMap::new(proc_ttl)),
             proc_ttl,
             dead_proc_ttl,
+            max_request_depth: constants::DEFAULT_MAX_REQUEST_DEPTH,
+            max_nested_operations: constants::DEFAULT_MAX_NESTED_OPERATIONS,
+            jobs: JobManager::default(),
+            schedules: ScheduleManager::default(),
+            logs: LogSinks::default(),
+            webhooks: Webhooks::default(),
+            reported_proc_exits: Mutex::new(HashSet::default()),
+            evictions: EvictionCounters::default(),
             custom_handler: None,
+            proc_launcher: ProcLauncher::default(),
+            identity_key: None,
+            signature_policy: SignaturePolicy::default(),
+            trusted_clients: TrustedClients::default(),
+            transfers: TransferAccounting::default(),
+            fs_events: FsEventHistory::default(),
+            chunks: ChunkStore::default(),
+            config: ConfigStore::default(),
+            power: PowerControl::default(),
+            permissions: Permissions::default(),
+            relay: Relay::default(),
+            tasks: TaskTracker::default(),
+            connection_tasks: TaskTracker::default(),
+            #[cfg(feature = "fault-injection")]
+            faults: Mutex::new(HashMap::default()),
+            #[cfg(feature = "wasm")]
+            wasm_handlers: super::wasm::WasmHandlers::default(),
+            #[cfg(feature = "script")]
+            script: None,
             running: AtomicBool::new(true),
         }
     }
 
+    pub fn set_fs_manager(
+        &mut self,
+        fs_manager: FileSystemManager,
+    ) -> &mut Self {
+        self.fs_manager = Mutex::new(fs_manager);
+        self
+    }
+
+    pub fn set_jobs(&mut self, jobs: JobManager) -> &mut Self {
+        self.jobs = jobs;
+        self
+    }
+
+    pub fn set_schedules(&mut self, schedules: ScheduleManager) -> &mut Self {
+        self.schedules = schedules;
+        self
+    }
+
+    pub fn set_logs(&mut self, logs: LogSinks) -> &mut Self {
+        self.logs = logs;
+        self
+    }
+
+    pub fn set_webhooks(&mut self, webhooks: Webhooks) -> &mut Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    pub fn set_identity_key(&mut self, key: Option<IdentityKey>) -> &mut Self {
+        self.identity_key = key;
+        self
+    }
+
+    pub fn identity_key(&self) -> Option<&IdentityKey> {
+        self.identity_key.as_ref()
+    }
+
+    pub fn set_signature_policy(
+        &mut self,
+        signature_policy: SignaturePolicy,
+    ) -> &mut Self {
+        self.signature_policy = signature_policy;
+        self
+    }
+
+    pub fn set_trusted_clients(
+        &mut self,
+        trusted_clients: TrustedClients,
+    ) -> &mut Self {
+        self.trusted_clients = trusted_clients;
+        self
+    }
+
+    pub fn set_origins(&mut self, origins: OriginBindings) -> &mut Self {
+        self.origins = origins;
+        self
+    }
+
+    pub fn set_transfers(
+        &mut self,
+        transfers: TransferAccounting,
+    ) -> &mut Self {
+        self.transfers = transfers;
+        self
+    }
+
+    pub fn set_fs_events(&mut self, fs_events: FsEventHistory) -> &mut Self {
+        self.fs_events = fs_events;
+        self
+    }
+
+    pub fn set_config(&mut self, config: ConfigStore) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    pub fn set_power(&mut self, power: PowerControl) -> &mut Self {
+        self.power = power;
+        self
+    }
+
+    pub fn set_permissions(&mut self, permissions: Permissions) -> &mut Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn set_relay(&mut self, relay: Relay) -> &mut Self {
+        self.relay = relay;
+        self
+    }
+
+    #[cfg(feature = "wasm")]
+    pub fn set_wasm_handlers(
+        &mut self,
+        wasm_handlers: super::wasm::WasmHandlers,
+    ) -> &mut Self {
+        self.wasm_handlers = wasm_handlers;
+        self
+    }
+
+    #[cfg(feature = "script")]
+    pub fn set_script(
+        &mut self,
+        script: Option<super::script::ScriptHooks>,
+    ) -> &mut Self {
+        self.script = script;
+        self
+    }
+
+    pub fn set_request_limits(
+        &mut self,
+        max_request_depth: u8,
+        max_nested_operations: usize,
+    ) -> &mut Self {
+        self.max_request_depth = max_request_depth;
+        self.max_nested_operations = max_nested_operations;
+        self
+    }
+
     pub fn set_custom_handler(
         &mut self,
         custom_handler: CustomHandler,
@@ -73,6 +345,14 @@ impl ServerState {
         self
     }
 
+    pub fn set_proc_launcher(
+        &mut self,
+        proc_launcher: ProcLauncher,
+    ) -> &mut Self {
+        self.proc_launcher = proc_launcher;
+        self
+    }
+
     /// Creates or updates an internal TTL for a file with `id` using the
     /// state-configured TTL as the max untouched lifetime
     pub async fn touch_file_id(&self, id: u32) {
@@ -82,33 +362,49 @@ impl ServerState {
     /// Creates or updates an internal TTL for a file with `id` using the
     /// given `ttl` as the max untouched lifetime
     pub async fn touch_file_id_with_ttl(&self, id: u32, ttl: Duration) {
-        self.file_ids.lock().await.replace(TtlValue::new(id, ttl));
+        self.file_ids.lock().await.insert_with_ttl(id, (), ttl);
     }
 
     /// Removes id associated with an open file, used for internal TTL tracking
     pub async fn remove_file_id(&self, id: u32) {
-        self.file_ids.lock().await.remove(&TtlValue::from(id));
+        self.file_ids.lock().await.remove(&id);
+    }
+
+    /// Forgets the keys of sessions whose origins have gone quiet
+    pub async fn evict_sessions(&self) {
+        let expired = self.sessions.lock().await.evict_expired();
+        for (addr, _) in expired {
+            self.record_eviction(
+                EvictionKind::Session,
+                addr.to_string(),
+                EvictionReason::Expired,
+            );
+        }
     }
 
     /// Evicts any files that have not been touched in TTL or longer time,
     /// removing them using the associated file manager
     pub async fn evict_files(&self) {
-        let mut fsm = self.fs_manager.lock().await;
-        self.file_ids.lock().await.retain(|v| {
-            let expired = v.has_expired();
-
-            if expired {
-                let handle = fsm.get(**v).map(|f| f.handle());
+        let expired_ids = self.file_ids.lock().await.evict_expired();
 
-                if let Some(h) = handle {
-                    if let Err(x) = fsm.close_file(h) {
-                        error!("Failed to evict file {}: {}", **v, x);
-                    }
-                }
+        let mut fsm = self.fs_manager.lock().await;
+        for (id, _) in expired_ids {
+            let file = match fsm.get(id) {
+                Some(file) => file,
+                None => continue,
+            };
+
+            let handle = file.lock().await.handle();
+            self.chunks.finish_upload(id).await;
+            match fsm.close_file(handle).await {
+                Ok(_) => self.record_eviction(
+                    EvictionKind::File,
+                    id.to_string(),
+                    EvictionReason::Expired,
+                ),
+                Err(x) => error!("Failed to evict file {}: {}", id, x),
             }
-
-            !expired
-        });
+        }
     }
 
     /// Creates or updates an internal TTL for a proc with `id` using the
@@ -120,31 +416,102 @@ impl ServerState {
     /// Creates or updates an internal TTL for a proc with `id` using the
     /// given `ttl` as the max untouched lifetime
     pub async fn touch_proc_id_with_ttl(&self, id: u32, ttl: Duration) {
-        self.proc_ids.lock().await.replace(TtlValue::new(id, ttl));
+        self.proc_ids.lock().await.insert_with_ttl(id, (), ttl);
     }
 
     /// Removes id associated with a proc, used for internal TTL tracking
     pub async fn remove_proc_id(&self, id: u32) {
-        self.proc_ids.lock().await.remove(&TtlValue::from(id));
+        self.proc_ids.lock().await.remove(&id);
     }
 
     /// Evicts any proc that have not been touched in TTL or longer time,
-    /// removing them by killing them
+    /// removing them by killing them unless they are detached
     pub async fn evict_procs(&self) {
         let mut proc_map = self.procs.lock().await;
-        self.proc_ids.lock().await.retain(|v| {
-            let expired = v.has_expired();
-
-            if expired {
-                if let Some(mut proc) = proc_map.remove(&**v) {
-                    if let Err(x) = proc.kill() {
-                        error!("Failed to kill proc {}: {}", **v, x);
-                    }
+        for (id, _) in self.proc_ids.lock().await.evict_expired() {
+            if let Some(mut proc) = proc_map.remove(&id) {
+                let reason = match proc.exit_status().await {
+                    Some(_) => EvictionReason::Exited,
+                    None => EvictionReason::Expired,
+                };
+                self.record_eviction(
+                    EvictionKind::Proc,
+                    id.to_string(),
+                    reason,
+                );
+
+                if proc.is_detached() {
+                    continue;
                 }
+
+                if let Err(x) = proc.kill() {
+                    error!("Failed to kill proc {}: {}", id, x);
+                }
+            }
+        }
+    }
+
+    /// Reports the eviction of a resource to the log and webhooks, counting
+    /// it towards the totals of its kind
+    fn record_eviction(
+        &self,
+        kind: EvictionKind,
+        id: String,
+        reason: EvictionReason,
+    ) {
+        info!("Evicted {} {} ({})", kind.name(), id, reason.name());
+        self.evictions.record(kind);
+        self.webhooks.fire(
+            &self.tasks,
+            WebhookEvent::ResourceEvicted { kind, id, reason },
+        );
+    }
+
+    /// Fires a webhook event for each tracked proc that has exited since the
+    /// last time this was called
+    pub async fn report_proc_exits(&self) {
+        let mut proc_map = self.procs.lock().await;
+        let mut reported = self.reported_proc_exits.lock().await;
+
+        // Forget about procs that are no longer tracked
+        reported.retain(|id| proc_map.contains_key(id));
+
+        for (id, proc) in proc_map.iter_mut() {
+            if reported.contains(id) {
+                continue;
+            }
+
+            if let Some(status) = proc.exit_status().await {
+                reported.insert(*id);
+                self.webhooks.fire(
+                    &self.tasks,
+                    WebhookEvent::ProcExited {
+                        id: *id,
+                        exit_code: status.exit_code,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Takes the origin of each tracked proc that has exited along with the
+    /// status to tell it of, so that each origin is told of an exit once
+    pub async fn take_proc_exits(&self) -> Vec<(SocketAddr, ProcStatusArgs)> {
+        let mut exits = Vec::new();
+        for proc in self.procs.lock().await.values_mut() {
+            if let Some((origin, status)) = proc.take_exited_origin().await {
+                exits.push((
+                    origin,
+                    ProcStatusArgs {
+                        id: status.id,
+                        is_alive: false,
+                        exit_code: status.exit_code,
+                    },
+                ));
             }
+        }
 
-            !expired
-        });
+        exits
     }
 
     /// Reports the status of the server, used by looping tasks to know whether
@@ -158,27 +525,25 @@ impl ServerState {
         self.running.store(false, Ordering::Relaxed);
     }
 
-    /// Acquires debug-related information for each part of state,
-    /// which requires locking each component
-    pub(crate) async fn internal_debug(&self) -> String {
-        format!(
-            "Conns: {:#?}
-            FS Manager: {:#?}
-            Files IDs: {:#?}
-            File Untouched TTL: {:?}
-            Procs: {:#?}
-            Proc IDs: {:#?}
-            Proc Untouched TTL: {:?}
-            Dead Proc Untouched TTL: {:?}",
-            self.conns.lock().await,
-            self.fs_manager.lock().await,
-            self.file_ids.lock().await,
-            self.file_ttl,
-            self.procs.lock().await,
-            self.proc_ids.lock().await,
-            self.proc_ttl,
-            self.dead_proc_ttl,
-        )
+    /// Time a file can go untouched before being closed
+    pub fn file_ttl(&self) -> Duration {
+        self.file_ttl
+    }
+
+    /// Time a proc can go untouched before being killed
+    pub fn proc_ttl(&self) -> Duration {
+        self.proc_ttl
+    }
+
+    /// Maximum depth of sequence and batch operations nested within a
+    /// request
+    pub fn max_request_depth(&self) -> u8 {
+        self.max_request_depth
+    }
+
+    /// Maximum total of operations nested within a request across all depths
+    pub fn max_nested_operations(&self) -> usize {
+        self.max_nested_operations
     }
 }
 
@@ -202,12 +567,12 @@ mod tests {
     async fn touch_file_id_should_produce_a_new_id_if_never_touched() {
         let state = ServerState::default();
 
-        assert!(!state.file_ids.lock().await.contains(&From::from(1)));
+        assert!(!state.file_ids.lock().await.contains_key(&1));
 
         state.touch_file_id(1).await;
 
         let ids = state.file_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("File id missing");
+        let id = ids.get(&1).expect("File id missing");
 
         assert_eq!(id.ttl(), &state.file_ttl);
     }
@@ -220,14 +585,14 @@ mod tests {
 
         let last_touched = {
             let ids = state.file_ids.lock().await;
-            let id = ids.get(&From::from(1)).expect("File id missing");
+            let id = ids.get(&1).expect("File id missing");
             *id.last_touched()
         };
 
         state.touch_file_id(1).await;
 
         let ids = state.file_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("File id missing");
+        let id = ids.get(&1).expect("File id missing");
 
         assert!(
             id.last_touched() > &last_touched,
@@ -239,14 +604,14 @@ mod tests {
     async fn touch_file_id_with_ttl_should_produce_a_new_id_if_never_touched() {
         let state = ServerState::default();
 
-        assert!(!state.file_ids.lock().await.contains(&From::from(1)));
+        assert!(!state.file_ids.lock().await.contains_key(&1));
 
         state
             .touch_file_id_with_ttl(1, Duration::new(999, 111))
             .await;
 
         let ids = state.file_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("file id missing");
+        let id = ids.get(&1).expect("file id missing");
 
         assert_eq!(id.ttl(), &Duration::new(999, 111));
     }
@@ -261,7 +626,7 @@ mod tests {
 
         let last_touched = {
             let ids = state.file_ids.lock().await;
-            let id = ids.get(&From::from(1)).expect("File id missing");
+            let id = ids.get(&1).expect("File id missing");
             *id.last_touched()
         };
 
@@ -270,7 +635,7 @@ mod tests {
             .await;
 
         let ids = state.file_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("File id missing");
+        let id = ids.get(&1).expect("File id missing");
 
         assert!(
             id.last_touched() > &last_touched,
@@ -303,7 +668,7 @@ mod tests {
         // Now remove the file id and verify it is still open in the manager
         state.remove_file_id(handle.id).await;
         assert!(
-            !state.file_ids.lock().await.contains(&From::from(handle.id)),
+            !state.file_ids.lock().await.contains_key(&handle.id),
             "ID was unexpectedly not removed from list"
         );
         assert!(
@@ -359,19 +724,11 @@ mod tests {
         state.evict_files().await;
 
         assert!(
-            !state
-                .file_ids
-                .lock()
-                .await
-                .contains(&From::from(handle_1.id)),
+            !state.file_ids.lock().await.contains_key(&handle_1.id),
             "File 1 id was unexpectedly not removed from list"
         );
         assert!(
-            state
-                .file_ids
-                .lock()
-                .await
-                .contains(&From::from(handle_2.id)),
+            state.file_ids.lock().await.contains_key(&handle_2.id),
             "File 2 id was unexpectedly removed from list"
         );
         assert!(
@@ -382,18 +739,19 @@ mod tests {
             state.fs_manager.lock().await.exists(handle_2.id),
             "File 2 was unexpectedly removed from manager"
         );
+        assert_eq!(state.evictions.total(EvictionKind::File), 1);
     }
 
     #[tokio::test]
     async fn touch_proc_id_should_produce_a_new_id_if_never_touched() {
         let state = ServerState::default();
 
-        assert!(!state.proc_ids.lock().await.contains(&From::from(1)));
+        assert!(!state.proc_ids.lock().await.contains_key(&1));
 
         state.touch_proc_id(1).await;
 
         let ids = state.proc_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("Proc id missing");
+        let id = ids.get(&1).expect("Proc id missing");
 
         assert_eq!(id.ttl(), &state.proc_ttl);
     }
@@ -406,14 +764,14 @@ mod tests {
 
         let last_touched = {
             let ids = state.proc_ids.lock().await;
-            let id = ids.get(&From::from(1)).expect("Proc id missing");
+            let id = ids.get(&1).expect("Proc id missing");
             *id.last_touched()
         };
 
         state.touch_proc_id(1).await;
 
         let ids = state.proc_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("Proc id missing");
+        let id = ids.get(&1).expect("Proc id missing");
 
         assert!(
             id.last_touched() > &last_touched,
@@ -425,14 +783,14 @@ mod tests {
     async fn touch_proc_id_with_ttl_should_produce_a_new_id_if_never_touched() {
         let state = ServerState::default();
 
-        assert!(!state.proc_ids.lock().await.contains(&From::from(1)));
+        assert!(!state.proc_ids.lock().await.contains_key(&1));
 
         state
             .touch_proc_id_with_ttl(1, Duration::new(999, 111))
             .await;
 
         let ids = state.proc_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("Proc id missing");
+        let id = ids.get(&1).expect("Proc id missing");
 
         assert_eq!(id.ttl(), &Duration::new(999, 111));
     }
@@ -447,7 +805,7 @@ mod tests {
 
         let last_touched = {
             let ids = state.proc_ids.lock().await;
-            let id = ids.get(&From::from(1)).expect("Proc id missing");
+            let id = ids.get(&1).expect("Proc id missing");
             *id.last_touched()
         };
 
@@ -456,7 +814,7 @@ mod tests {
             .await;
 
         let ids = state.proc_ids.lock().await;
-        let id = ids.get(&From::from(1)).expect("Proc id missing");
+        let id = ids.get(&1).expect("Proc id missing");
 
         assert!(
             id.last_touched() > &last_touched,
@@ -492,7 +850,7 @@ mod tests {
         // Verify that the id has been removed from our list, but not the
         // map of ids to procs
         assert!(
-            !state.proc_ids.lock().await.contains(&From::from(id)),
+            !state.proc_ids.lock().await.contains_key(&id),
             "ID was unexpectedly not removed from list"
         );
         assert!(
@@ -560,11 +918,11 @@ mod tests {
 
         // Verify that proc 1 has been removed while proc 2 has not
         assert!(
-            !state.proc_ids.lock().await.contains(&From::from(id_1)),
+            !state.proc_ids.lock().await.contains_key(&id_1),
             "Proc 1 was unexpectedly not removed from list"
         );
         assert!(
-            state.proc_ids.lock().await.contains(&From::from(id_2)),
+            state.proc_ids.lock().await.contains_key(&id_2),
             "Proc 2 was unexpectedly removed from list"
         );
         assert!(
@@ -575,6 +933,7 @@ mod tests {
             state.procs.lock().await.contains_key(&id_2),
             "Proc 2 was unexpectedly removed from map"
         );
+        assert_eq!(state.evictions.total(EvictionKind::Proc), 1);
 
         // Verify that proc 2 has not exited/been killed
         let mut procs = state.procs.lock().await;
@@ -588,4 +947,81 @@ mod tests {
             Some(x) => panic!("Unexpected content: {:?}", x),
         }
     }
+
+    #[tokio::test]
+    async fn report_proc_exits_should_only_report_exited_procs() {
+        let state = ServerState::default();
+
+        let spawn = |cmd: &str, args: &[&str]| {
+            let child = Command::new(cmd)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+                .expect("Failed to spawn child process");
+            LocalProc::new(child).spawn()
+        };
+
+        let exiting = spawn("true", &[]);
+        let running = spawn("sleep", &["60"]);
+        let (exiting_id, running_id) = (exiting.id(), running.id());
+        state.procs.lock().await.insert(exiting_id, exiting);
+        state.procs.lock().await.insert(running_id, running);
+
+        // Wait for the short-lived proc to exit
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        state.report_proc_exits().await;
+
+        let reported = state.reported_proc_exits.lock().await;
+        assert!(reported.contains(&exiting_id), "Exit not reported");
+        assert!(!reported.contains(&running_id), "Running proc reported");
+    }
+
+    #[tokio::test]
+    async fn take_proc_exits_should_yield_each_exit_with_an_origin_once() {
+        let state = ServerState::default();
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+
+        let spawn = |cmd: &str, args: &[&str]| {
+            let child = Command::new(cmd)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true)
+                .spawn()
+                .expect("Failed to spawn child process");
+            LocalProc::new(child).spawn()
+        };
+
+        let mut exiting = spawn("sh", &["-c", "exit 3"]);
+        let mut running = spawn("sleep", &["60"]);
+        let untold = spawn("true", &[]);
+        exiting.set_origin(origin);
+        running.set_origin(origin);
+        let exiting_id = exiting.id();
+        let mut procs = state.procs.lock().await;
+        procs.insert(exiting_id, exiting);
+        procs.insert(running.id(), running);
+        procs.insert(untold.id(), untold);
+        drop(procs);
+
+        // Wait for the short-lived procs to exit
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+
+        assert_eq!(
+            state.take_proc_exits().await,
+            vec![(
+                origin,
+                ProcStatusArgs {
+                    id: exiting_id,
+                    is_alive: false,
+                    exit_code: Some(3),
+                }
+            )]
+        );
+        assert!(state.take_proc_exits().await.is_empty(), "Told twice");
+    }
 }
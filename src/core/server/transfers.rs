@@ -0,0 +1,284 @@
+use crate::core::{
+    reply::QuotaExceededArgs, transport::auth::identity, Content, Msg,
+};
+use crate::utils::TtlMap;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Time an identity can go without transferring anything before its totals
+/// are forgotten
+const IDENTITY_IDLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most identities whose totals are kept at once, beyond which those of the
+/// least recently active identity are forgotten
+const MAX_TRACKED_IDENTITIES: usize = 4096;
+
+/// Bytes transferred to and from the server
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransferTotals {
+    /// Bytes of msgs received by the server
+    pub bytes_in: u64,
+
+    /// Bytes of msgs sent by the server
+    pub bytes_out: u64,
+}
+
+impl TransferTotals {
+    /// Bytes transferred in either direction
+    pub fn total(&self) -> u64 {
+        self.bytes_in.saturating_add(self.bytes_out)
+    }
+}
+
+/// Who a transfer is accounted to and the class of request it belongs to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferAccount {
+    /// Fingerprint of the key that validly signed the msg, or otherwise the
+    /// ip address of the origin of the msg
+    pub identity: String,
+
+    /// Class of the request, such as file or proc
+    pub class: &'static str,
+}
+
+impl TransferAccount {
+    /// Determines the account of `msg` received from `origin`, where
+    /// `signer` is the public key that its signature was already verified
+    /// against, if any
+    pub fn of(msg: &Msg, signer: Option<&[u8]>, origin: SocketAddr) -> Self {
+        let identity = match signer {
+            Some(public_key) => identity::fingerprint(public_key),
+            None => origin.ip().to_string(),
+        };
+
+        let class = match &msg.content {
+            Content::Request(request) => request.class(),
+            _ => "other",
+        };
+
+        Self { identity, class }
+    }
+}
+
+/// Bytes transferred by each identity and class of request, along with an
+/// optional quota of bytes that any single identity can transfer
+///
+/// Totals of each class are kept for the lifetime of the server, while
+/// those of an identity are forgotten once it goes idle for a day or is the
+/// least recently active of `MAX_TRACKED_IDENTITIES`, which also resets its
+/// quota, so that clients cannot grow the totals without bound by sending
+/// from many addresses.
+#[derive(Debug)]
+pub struct TransferAccounting {
+    quota: Option<u64>,
+    by_identity: Mutex<TtlMap<String, TransferTotals>>,
+    by_class: Mutex<HashMap<&'static str, TransferTotals>>,
+}
+
+impl Default for TransferAccounting {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl TransferAccounting {
+    pub fn new(quota: Option<u64>) -> Self {
+        Self {
+            quota,
+            by_identity: Mutex::new(
+                TtlMap::new(IDENTITY_IDLE_TTL)
+                    .with_max_len(MAX_TRACKED_IDENTITIES),
+            ),
+            by_class: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Maximum bytes that any single identity can transfer
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+
+    /// Records `bytes` received for `account`, failing without recording
+    /// them if doing so would exceed the quota of its identity
+    pub async fn record_in(
+        &self,
+        account: &TransferAccount,
+        bytes: u64,
+    ) -> Result<(), QuotaExceededArgs> {
+        let mut by_identity = self.by_identity.lock().await;
+        let totals = identity_totals(&mut by_identity, &account.identity);
+
+        if let Some(quota) = self.quota {
+            let used = totals.total();
+            if used.saturating_add(bytes) > quota {
+                return Err(QuotaExceededArgs {
+                    identity: account.identity.clone(),
+                    used,
+                    quota,
+                });
+            }
+        }
+
+        totals.bytes_in = totals.bytes_in.saturating_add(bytes);
+        drop(by_identity);
+
+        let mut by_class = self.by_class.lock().await;
+        let totals = by_class.entry(account.class).or_default();
+        totals.bytes_in = totals.bytes_in.saturating_add(bytes);
+
+        Ok(())
+    }
+
+    /// Records `bytes` sent for `account`, which is never rejected as the
+    /// reply has already been produced; the quota instead applies to the
+    /// next msg received for the identity
+    pub async fn record_out(&self, account: &TransferAccount, bytes: u64) {
+        let mut by_identity = self.by_identity.lock().await;
+        let totals = identity_totals(&mut by_identity, &account.identity);
+        totals.bytes_out = totals.bytes_out.saturating_add(bytes);
+        drop(by_identity);
+
+        let mut by_class = self.by_class.lock().await;
+        let totals = by_class.entry(account.class).or_default();
+        totals.bytes_out = totals.bytes_out.saturating_add(bytes);
+    }
+
+    /// Bytes transferred by each identity still tracked
+    pub async fn by_identity(&self) -> HashMap<String, TransferTotals> {
+        self.by_identity
+            .lock()
+            .await
+            .iter()
+            .map(|(identity, totals)| (identity.clone(), **totals))
+            .collect()
+    }
+
+    /// Bytes transferred by each class of request
+    pub async fn by_class(&self) -> HashMap<&'static str, TransferTotals> {
+        self.by_class.lock().await.clone()
+    }
+}
+
+/// Totals of `identity`, renewing how long they are kept or starting them
+/// anew if not tracked
+fn identity_totals<'a>(
+    by_identity: &'a mut TtlMap<String, TransferTotals>,
+    identity: &str,
+) -> &'a mut TransferTotals {
+    let identity = identity.to_string();
+    if !by_identity.touch(&identity) {
+        by_identity.insert(identity.clone(), TransferTotals::default());
+    }
+
+    by_identity
+        .get_mut(&identity)
+        .expect("Totals of identity missing after insert")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{transport::auth::identity::IdentityKey, Request};
+
+    fn account(identity: &str, class: &'static str) -> TransferAccount {
+        TransferAccount {
+            identity: identity.to_string(),
+            class,
+        }
+    }
+
+    #[test]
+    fn of_should_use_verified_signer_as_identity_if_any() {
+        let origin: SocketAddr = "127.0.0.1:60123".parse().unwrap();
+
+        let msg = Msg::from(Request::Heartbeat);
+        assert_eq!(
+            TransferAccount::of(&msg, None, origin),
+            account("127.0.0.1", "meta")
+        );
+
+        let key = IdentityKey::generate();
+        assert_eq!(
+            TransferAccount::of(&msg, Some(key.public_key()), origin).identity,
+            identity::fingerprint(key.public_key())
+        );
+    }
+
+    #[tokio::test]
+    async fn record_should_total_bytes_by_identity_and_class() {
+        let accounting = TransferAccounting::default();
+        accounting
+            .record_in(&account("a", "file"), 10)
+            .await
+            .unwrap();
+        accounting
+            .record_in(&account("b", "file"), 5)
+            .await
+            .unwrap();
+        accounting.record_out(&account("a", "proc"), 7).await;
+
+        let by_identity = accounting.by_identity().await;
+        assert_eq!(
+            by_identity["a"],
+            TransferTotals {
+                bytes_in: 10,
+                bytes_out: 7
+            }
+        );
+        assert_eq!(by_identity["b"].total(), 5);
+
+        let by_class = accounting.by_class().await;
+        assert_eq!(by_class["file"].bytes_in, 15);
+        assert_eq!(by_class["proc"].bytes_out, 7);
+    }
+
+    #[tokio::test]
+    async fn record_should_forget_least_recently_active_identity_if_full() {
+        let accounting = TransferAccounting::default();
+        for i in 0..=MAX_TRACKED_IDENTITIES {
+            accounting
+                .record_out(&account(&i.to_string(), "file"), 1)
+                .await;
+        }
+
+        let by_identity = accounting.by_identity().await;
+        assert_eq!(by_identity.len(), MAX_TRACKED_IDENTITIES);
+        assert!(!by_identity.contains_key("0"));
+        assert_eq!(
+            accounting.by_class().await["file"].bytes_out,
+            MAX_TRACKED_IDENTITIES as u64 + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn record_in_should_reject_bytes_exceeding_quota_of_identity() {
+        let accounting = TransferAccounting::new(Some(10));
+        accounting
+            .record_in(&account("a", "file"), 6)
+            .await
+            .unwrap();
+        accounting.record_out(&account("a", "file"), 3).await;
+
+        let err = accounting
+            .record_in(&account("a", "file"), 2)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            QuotaExceededArgs {
+                identity: String::from("a"),
+                used: 9,
+                quota: 10,
+            }
+        );
+
+        // Rejected bytes are not recorded and other identities are unaffected
+        assert_eq!(accounting.by_identity().await["a"].total(), 9);
+        accounting
+            .record_in(&account("b", "file"), 10)
+            .await
+            .unwrap();
+    }
+}
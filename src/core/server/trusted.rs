@@ -0,0 +1,209 @@
+use super::job::write_json_atomic;
+use crate::core::{transport::auth::identity, Msg};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Allow list of the clients trusted by a server, identified by the
+/// fingerprints of the identity keys that sign their msgs
+///
+/// Once enabled, msgs that are not signed by a trusted key are rejected
+/// before they are routed. Operators can change the list at runtime, and
+/// each change is persisted if the list was opened from a file. Clones share
+/// the same list.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedClients {
+    enabled: bool,
+    path: Option<PathBuf>,
+    fingerprints: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl TrustedClients {
+    /// Creates a list that trusts only `fingerprints`, keeping any changes
+    /// in memory
+    pub fn in_memory(fingerprints: Vec<String>) -> io::Result<Self> {
+        Ok(Self {
+            enabled: true,
+            path: None,
+            fingerprints: Arc::new(Mutex::new(normalize_all(fingerprints)?)),
+        })
+    }
+
+    /// Creates a list persisted at `path`, loading the fingerprints already
+    /// stored there in addition to `fingerprints`
+    pub fn open(
+        path: impl Into<PathBuf>,
+        fingerprints: Vec<String>,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let mut all = normalize_all(fingerprints)?;
+        if path.exists() {
+            all.extend(load(&path)?);
+        }
+
+        Ok(Self {
+            enabled: true,
+            path: Some(path),
+            fingerprints: Arc::new(Mutex::new(all)),
+        })
+    }
+
+    /// Whether msgs are checked against the list, which is never the case
+    /// for a default list
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Path where the list is persisted, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Fingerprints of the trusted keys in sorted order
+    pub async fn fingerprints(&self) -> Vec<String> {
+        self.fingerprints.lock().await.iter().cloned().collect()
+    }
+
+    /// Checks that `msg` is signed by a trusted key, yielding the reason it
+    /// is rejected
+    pub async fn check(&self, msg: &Msg) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let fingerprint = match msg.verify_signature() {
+            Ok(Some(public_key)) => identity::fingerprint(public_key),
+            Ok(None) => {
+                return Err(String::from("Msg must be signed by a trusted key"))
+            }
+            Err(x) => return Err(format!("Msg signature rejected: {}", x)),
+        };
+
+        if self.fingerprints.lock().await.contains(&fingerprint) {
+            Ok(())
+        } else {
+            Err(format!("Client key {} is not trusted", fingerprint))
+        }
+    }
+
+    /// Trusts the keys of `add` and stops trusting those of `remove`,
+    /// persisting the result if the list has a path and yielding the
+    /// fingerprints now trusted
+    pub async fn update(
+        &self,
+        add: &[String],
+        remove: &[String],
+    ) -> io::Result<Vec<String>> {
+        if !self.enabled {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Server has no list of trusted clients",
+            ));
+        }
+
+        let add = normalize_all(add.iter().cloned())?;
+        let remove = normalize_all(remove.iter().cloned())?;
+
+        let mut fingerprints = self.fingerprints.lock().await;
+        let mut updated = fingerprints.clone();
+        updated.extend(add);
+        updated.retain(|x| !remove.contains(x));
+
+        if updated != *fingerprints {
+            if let Some(path) = self.path.as_ref() {
+                write_json_atomic(path, &updated).await?;
+            }
+            *fingerprints = updated;
+        }
+
+        Ok(fingerprints.iter().cloned().collect())
+    }
+}
+
+fn load(path: &Path) -> io::Result<BTreeSet<String>> {
+    let fingerprints: Vec<String> =
+        serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+    normalize_all(fingerprints)
+}
+
+pub(crate) fn normalize_all(
+    fingerprints: impl IntoIterator<Item = String>,
+) -> io::Result<BTreeSet<String>> {
+    fingerprints.into_iter().map(|x| normalize(&x)).collect()
+}
+
+/// Lowercases `fingerprint`, failing if it is not the hex of a sha256 digest
+fn normalize(fingerprint: &str) -> io::Result<String> {
+    let fingerprint = fingerprint.trim().to_lowercase();
+    if fingerprint.len() == 64
+        && fingerprint.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        Ok(fingerprint)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid key fingerprint: {:?}", fingerprint),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{transport::auth::identity::IdentityKey, Request};
+
+    fn signed_msg(key: &IdentityKey) -> Msg {
+        let mut msg = Msg::from(Request::Heartbeat);
+        msg.sign(key).unwrap();
+        msg
+    }
+
+    fn fingerprint(key: &IdentityKey) -> String {
+        identity::fingerprint(key.public_key())
+    }
+
+    #[tokio::test]
+    async fn check_should_only_accept_msgs_signed_by_trusted_keys() {
+        let key = IdentityKey::generate();
+        let other_key = IdentityKey::generate();
+
+        let trusted =
+            TrustedClients::in_memory(vec![fingerprint(&key)]).unwrap();
+        assert!(trusted.check(&signed_msg(&key)).await.is_ok());
+        assert!(trusted.check(&signed_msg(&other_key)).await.is_err());
+        assert!(trusted.check(&Msg::from(Request::Heartbeat)).await.is_err());
+
+        // A list that was never enabled accepts anything
+        let trusted = TrustedClients::default();
+        assert!(trusted.check(&Msg::from(Request::Heartbeat)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn update_should_persist_changes_for_next_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trusted.json");
+        let key = IdentityKey::generate();
+        let other_key = IdentityKey::generate();
+
+        let trusted =
+            TrustedClients::open(&path, vec![fingerprint(&key)]).unwrap();
+        let fingerprints = trusted
+            .update(&[fingerprint(&other_key).to_uppercase()], &[])
+            .await
+            .unwrap();
+        assert_eq!(fingerprints.len(), 2);
+
+        let trusted = TrustedClients::open(&path, Vec::new()).unwrap();
+        assert!(trusted.check(&signed_msg(&key)).await.is_ok());
+        assert!(trusted.check(&signed_msg(&other_key)).await.is_ok());
+
+        trusted.update(&[], &[fingerprint(&key)]).await.unwrap();
+        let trusted = TrustedClients::open(&path, Vec::new()).unwrap();
+        assert_eq!(trusted.fingerprints().await, vec![fingerprint(&other_key)]);
+
+        assert!(trusted.update(&[String::from("bad")], &[]).await.is_err());
+    }
+}
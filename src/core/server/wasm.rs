@@ -0,0 +1,664 @@
+//! Sandbox for custom handlers uploaded as WebAssembly modules, each serving
+//! the custom requests of a namespace
+//!
+//! A module must export:
+//! - `memory`: linear memory shared with the server
+//! - `alloc(len: i32) -> i32`: reserves `len` bytes, returning their offset
+//! - `handle(ptr: i32, len: i32) -> i64`: handles the request data at `ptr`,
+//!   returning the offset and length of the reply data packed as
+//!   `(ptr << 32) | len`, or a negative value if handling failed
+//!
+//! The only functions a module can import are those of the `over_there`
+//! module, giving no access to the network and access to files only within
+//! the sandbox root:
+//! - `log(ptr: i32, len: i32)`: logs the text at `ptr`
+//! - `read_file(path_ptr: i32, path_len: i32) -> i64`: reads the file at
+//!   the relative path, returning its contents (allocated via `alloc`) packed
+//!   like the result of `handle`, or -1 on failure
+//! - `write_file(path_ptr: i32, path_len: i32, ptr: i32, len: i32) -> i32`:
+//!   writes the data at `ptr` to the file at the relative path, returning 0
+//!   on success or -1 on failure
+//!
+//! Each request is handled by a fresh instance of the module on the blocking
+//! pool, so no state is kept between requests, and is limited in fuel and
+//! memory so that a misbehaving module cannot stall the server
+//!
+//! Files are opened without following any symlink on the way to them, which
+//! is only possible on unix platforms, so modules elsewhere cannot access
+//! files at all.
+
+#[cfg(unix)]
+use super::fs::secure::{SecureOpenOptions, SecureRoot};
+use log::debug;
+#[cfg(unix)]
+use log::warn;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::path::{Component, Path};
+use tokio::sync::Mutex;
+use wasmtime::{
+    Caller, Config, Engine, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+/// Name of the module that provides host functions to wasm handlers
+pub const HOST_MODULE: &str = "over_there";
+
+/// Names of the functions provided to wasm handlers
+pub const HOST_FUNCTIONS: &[&str] = &["log", "read_file", "write_file"];
+
+/// Default fuel given to each invocation of a wasm handler, roughly the
+/// number of instructions it can execute
+pub const DEFAULT_FUEL: u64 = 10_000_000;
+
+/// Maximum size in bytes of the memory of each wasm handler (64 MiB)
+pub const MAX_MEMORY_SIZE: usize = 64 * 1024 * 1024;
+
+struct HostState {
+    /// Secure root that files are accessed within, or none if modules
+    /// cannot access files
+    #[cfg(unix)]
+    root: Option<SecureRoot>,
+
+    limits: StoreLimits,
+}
+
+/// Wasm modules serving custom requests, keyed by namespace
+pub struct WasmHandlers {
+    engine: Engine,
+    linker: Linker<HostState>,
+
+    /// Directory that modules can read and write files within, or none if
+    /// modules cannot access files
+    root: Option<PathBuf>,
+
+    fuel: u64,
+    modules: Mutex<HashMap<String, Module>>,
+}
+
+impl Default for WasmHandlers {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl fmt::Debug for WasmHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmHandlers")
+            .field("root", &self.root)
+            .field("fuel", &self.fuel)
+            .finish()
+    }
+}
+
+impl WasmHandlers {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+
+        // NOTE: Creating an engine only fails for unsupported configs, and
+        //       fuel is supported on every platform
+        let engine = Engine::new(&config).expect("Failed to create engine");
+        let linker = make_linker(&engine);
+
+        Self {
+            engine,
+            linker,
+            root,
+            fuel: DEFAULT_FUEL,
+            modules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the fuel given to each invocation of a wasm handler
+    pub fn set_fuel(&mut self, fuel: u64) -> &mut Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Compiles `module` (binary or text format) to serve requests of
+    /// `namespace`, replacing any module already serving it
+    pub async fn load(&self, namespace: &str, module: &[u8]) -> io::Result<()> {
+        let module = Module::new(&self.engine, module)
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?;
+        validate(&module)?;
+
+        self.modules
+            .lock()
+            .await
+            .insert(namespace.to_string(), module);
+        Ok(())
+    }
+
+    /// Stops serving requests of `namespace`, returning whether or not a
+    /// module was serving it
+    pub async fn unload(&self, namespace: &str) -> bool {
+        self.modules.lock().await.remove(namespace).is_some()
+    }
+
+    /// Namespaces served by a module
+    pub async fn namespaces(&self) -> Vec<String> {
+        self.modules.lock().await.keys().cloned().collect()
+    }
+
+    /// Handles `data` with the module serving `namespace`, yielding none if
+    /// no module serves it
+    pub async fn invoke(
+        &self,
+        namespace: &str,
+        data: &[u8],
+    ) -> Option<io::Result<Vec<u8>>> {
+        let module = self.modules.lock().await.get(namespace).cloned()?;
+
+        // NOTE: A module runs until it returns or runs out of fuel, which
+        //       would block the runtime for that long if run on it
+        let runner = Runner {
+            engine: self.engine.clone(),
+            linker: self.linker.clone(),
+            #[cfg(unix)]
+            root: self.root.clone(),
+            fuel: self.fuel,
+        };
+        let data = data.to_vec();
+        let result =
+            tokio::task::spawn_blocking(move || runner.run(&module, &data))
+                .await;
+
+        Some(result.map_err(other_error).and_then(|x| x))
+    }
+}
+
+/// Everything needed to run a module apart from the handlers, so that the
+/// module can run on the blocking pool
+struct Runner {
+    engine: Engine,
+    linker: Linker<HostState>,
+    #[cfg(unix)]
+    root: Option<PathBuf>,
+    fuel: u64,
+}
+
+impl Runner {
+    fn run(&self, module: &Module, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                #[cfg(unix)]
+                root: self.root.as_ref().and_then(|root| {
+                    SecureRoot::open(root)
+                        .map_err(|x| {
+                            warn!("wasm: Cannot open {:?}: {}", root, x)
+                        })
+                        .ok()
+                }),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_SIZE)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.add_fuel(self.fuel).map_err(other_error)?;
+
+        let instance = self
+            .linker
+            .instantiate(&mut store, module)
+            .map_err(other_error)?;
+        let memory =
+            instance.get_memory(&mut store, "memory").ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Missing memory")
+            })?;
+        let alloc = instance
+            .get_typed_func::<i32, i32, _>(&mut store, "alloc")
+            .map_err(other_error)?;
+        let handle = instance
+            .get_typed_func::<(i32, i32), i64, _>(&mut store, "handle")
+            .map_err(other_error)?;
+
+        let len = i32::try_from(data.len())
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?;
+        let ptr = alloc.call(&mut store, len).map_err(other_error)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, data)
+            .map_err(other_error)?;
+
+        let (ptr, len) =
+            unpack(handle.call(&mut store, (ptr, len)).map_err(other_error)?)
+                .ok_or_else(|| io::Error::other("Wasm handler failed"))?;
+
+        // NOTE: Check the reply fits in memory before allocating room for it,
+        //       as its length comes from the module
+        if !fits(ptr, len, memory.data_size(&store)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Wasm handler replied beyond its memory",
+            ));
+        }
+
+        let mut reply = vec![0; len];
+        memory.read(&store, ptr, &mut reply).map_err(other_error)?;
+        Ok(reply)
+    }
+}
+
+/// Ensures that a module only imports host functions and exports what is
+/// needed to handle requests
+fn validate(module: &Module) -> io::Result<()> {
+    for import in module.imports() {
+        if import.module() != HOST_MODULE
+            || !HOST_FUNCTIONS.contains(&import.name())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Module imports unavailable {}::{}",
+                    import.module(),
+                    import.name()
+                ),
+            ));
+        }
+    }
+
+    for name in &["memory", "alloc", "handle"] {
+        if module.get_export(name).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Module does not export {}", name),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn make_linker(engine: &Engine) -> Linker<HostState> {
+    let mut linker = Linker::new(engine);
+
+    // NOTE: Defining a function only fails if it is already defined, and
+    //       each is defined once
+    linker
+        .func_wrap(
+            HOST_MODULE,
+            "log",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                if let Some(text) = read_guest(&mut caller, ptr, len) {
+                    debug!("wasm: {}", String::from_utf8_lossy(&text));
+                }
+            },
+        )
+        .expect("Failed to define log");
+
+    linker
+        .func_wrap(
+            HOST_MODULE,
+            "read_file",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> i64 {
+                read_guest(&mut caller, ptr, len)
+                    .and_then(|path| read_file(caller.data(), &path))
+                    .and_then(|data| write_guest(&mut caller, &data))
+                    .unwrap_or(-1)
+            },
+        )
+        .expect("Failed to define read_file");
+
+    linker
+        .func_wrap(
+            HOST_MODULE,
+            "write_file",
+            |mut caller: Caller<'_, HostState>,
+             path_ptr: i32,
+             path_len: i32,
+             ptr: i32,
+             len: i32|
+             -> i32 {
+                let path = read_guest(&mut caller, path_ptr, path_len);
+                let data = read_guest(&mut caller, ptr, len);
+
+                match (path, data) {
+                    (Some(path), Some(data)) => {
+                        match write_file(caller.data(), &path, &data) {
+                            Some(_) => 0,
+                            None => -1,
+                        }
+                    }
+                    _ => -1,
+                }
+            },
+        )
+        .expect("Failed to define write_file");
+
+    linker
+}
+
+/// Reads the file at `path` within the root of `state`
+#[cfg(unix)]
+fn read_file(state: &HostState, path: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Read;
+    let options = SecureOpenOptions {
+        read: true,
+        ..Default::default()
+    };
+    let mut file = state
+        .root
+        .as_ref()?
+        .open_file(resolve(path)?, options, false)
+        .ok()?;
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+#[cfg(not(unix))]
+fn read_file(_state: &HostState, _path: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+/// Replaces the contents of the file at `path` within the root of `state`
+/// with `data`, creating the file if missing
+#[cfg(unix)]
+fn write_file(state: &HostState, path: &[u8], data: &[u8]) -> Option<()> {
+    use std::io::Write;
+    let options = SecureOpenOptions {
+        write: true,
+        create: true,
+        truncate: true,
+        mode: 0o666,
+        ..Default::default()
+    };
+    let mut file = state
+        .root
+        .as_ref()?
+        .open_file(resolve(path)?, options, false)
+        .ok()?;
+
+    file.write_all(data).ok()
+}
+
+#[cfg(not(unix))]
+fn write_file(_state: &HostState, _path: &[u8], _data: &[u8]) -> Option<()> {
+    None
+}
+
+/// Interprets `path` as relative to the root, yielding none if it is not
+/// valid utf-8, is absolute, or contains `..`
+///
+/// Symlinks on the way to the path are rejected by the root itself.
+#[cfg(unix)]
+fn resolve(path: &[u8]) -> Option<&Path> {
+    let path = Path::new(std::str::from_utf8(path).ok()?);
+    if path.components().all(|c| matches!(c, Component::Normal(_))) {
+        Some(path)
+    } else {
+        warn!("wasm: Denied access to {:?}", path);
+        None
+    }
+}
+
+/// Reads `len` bytes of guest memory at `ptr`, yielding none if they do not
+/// all lie within the memory
+fn read_guest(
+    caller: &mut Caller<'_, HostState>,
+    ptr: i32,
+    len: i32,
+) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let (ptr, len) = (usize::try_from(ptr).ok()?, usize::try_from(len).ok()?);
+    if !fits(ptr, len, memory.data_size(&*caller)) {
+        return None;
+    }
+
+    let mut data = vec![0; len];
+    memory.read(&*caller, ptr, &mut data).ok()?;
+    Some(data)
+}
+
+/// Whether `len` bytes at `ptr` lie within memory of `size` bytes
+fn fits(ptr: usize, len: usize, size: usize) -> bool {
+    matches!(ptr.checked_add(len), Some(end) if end <= size)
+}
+
+/// Copies `data` into memory reserved by the guest, returning its offset
+/// and length packed together
+fn write_guest(caller: &mut Caller<'_, HostState>, data: &[u8]) -> Option<i64> {
+    let alloc = caller
+        .get_export("alloc")?
+        .into_func()?
+        .typed::<i32, i32, _>(&*caller)
+        .ok()?;
+    let len = i32::try_from(data.len()).ok()?;
+    let ptr = alloc.call(&mut *caller, len).ok()?;
+
+    let memory = caller.get_export("memory")?.into_memory()?;
+    memory
+        .write(&mut *caller, usize::try_from(ptr).ok()?, data)
+        .ok()?;
+    Some(pack(ptr as u32, len as u32))
+}
+
+fn pack(ptr: u32, len: u32) -> i64 {
+    ((ptr as i64) << 32) | len as i64
+}
+
+fn unpack(packed: i64) -> Option<(usize, usize)> {
+    if packed < 0 {
+        None
+    } else {
+        Some(((packed >> 32) as usize, (packed & 0xffff_ffff) as usize))
+    }
+}
+
+fn other_error<E>(x: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::other(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bump allocator shared by test modules
+    const ALLOC: &str = r#"
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+    "#;
+
+    fn module(imports: &str, handle: &str) -> Vec<u8> {
+        format!("(module {} {} {})", imports, ALLOC, handle).into_bytes()
+    }
+
+    fn echo_module() -> Vec<u8> {
+        module(
+            "",
+            r#"(func (export "handle") (param $ptr i32) (param $len i32)
+                (result i64)
+                (i64.or
+                    (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                    (i64.extend_i32_u (local.get $len))))"#,
+        )
+    }
+
+    /// Module that treats request data as a path and replies with the
+    /// contents of the file at that path
+    fn read_file_module() -> Vec<u8> {
+        module(
+            r#"(import "over_there" "read_file"
+                (func $read_file (param i32 i32) (result i64)))"#,
+            r#"(func (export "handle") (param $ptr i32) (param $len i32)
+                (result i64)
+                (call $read_file (local.get $ptr) (local.get $len)))"#,
+        )
+    }
+
+    #[tokio::test]
+    async fn invoke_should_reply_with_output_of_module() {
+        let handlers = WasmHandlers::default();
+        handlers.load("echo", &echo_module()).await.unwrap();
+
+        let reply = handlers.invoke("echo", b"hello").await.unwrap().unwrap();
+        assert_eq!(reply, b"hello");
+        assert!(handlers.invoke("missing", b"hello").await.is_none());
+
+        assert!(handlers.unload("echo").await);
+        assert!(handlers.invoke("echo", b"hello").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn invoke_should_fail_if_module_runs_out_of_fuel() {
+        let mut handlers = WasmHandlers::default();
+        handlers.set_fuel(1000);
+        handlers
+            .load(
+                "loop",
+                &module(
+                    "",
+                    r#"(func (export "handle") (param i32 i32) (result i64)
+                        (loop $l (br $l))
+                        (i64.const 0))"#,
+                ),
+            )
+            .await
+            .unwrap();
+
+        assert!(handlers.invoke("loop", b"").await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn load_should_fail_if_module_imports_unavailable_functions() {
+        let handlers = WasmHandlers::default();
+        let err = handlers
+            .load(
+                "net",
+                &module(
+                    r#"(import "env" "connect" (func (param i32 i32)))"#,
+                    r#"(func (export "handle") (param i32 i32) (result i64)
+                        (i64.const 0))"#,
+                ),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let err = handlers.load("empty", b"(module)").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn read_file_should_only_access_files_within_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("inside.txt"), b"inside").unwrap();
+        std::fs::write(dir.path().join("outside.txt"), b"outside").unwrap();
+
+        let handlers = WasmHandlers::new(Some(root.clone()));
+        handlers.load("read", &read_file_module()).await.unwrap();
+
+        let reply = handlers.invoke("read", b"inside.txt").await.unwrap();
+        assert_eq!(reply.unwrap(), b"inside");
+
+        for path in &[
+            "../outside.txt",
+            dir.path().join("outside.txt").to_str().unwrap(),
+        ] {
+            let reply = handlers.invoke("read", path.as_bytes()).await.unwrap();
+            assert!(reply.is_err(), "Read {} outside of root", path);
+        }
+
+        // Without a root, no files can be accessed
+        let handlers = WasmHandlers::default();
+        handlers.load("read", &read_file_module()).await.unwrap();
+        let reply = handlers.invoke("read", b"inside.txt").await.unwrap();
+        assert!(reply.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn write_file_should_not_follow_symlinks_out_of_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        std::fs::write(outside.join("secret"), b"secret").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("dir-link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), root.join("link"))
+            .unwrap();
+
+        // Module that writes the request data to the file at that path
+        let handlers = WasmHandlers::new(Some(root.clone()));
+        handlers
+            .load(
+                "write",
+                &module(
+                    r#"(import "over_there" "write_file"
+                        (func $write_file (param i32 i32 i32 i32)
+                            (result i32)))"#,
+                    r#"(func (export "handle") (param $ptr i32)
+                        (param $len i32) (result i64)
+                        (i64.extend_i32_s (call $write_file
+                            (local.get $ptr) (local.get $len)
+                            (local.get $ptr) (local.get $len))))"#,
+                ),
+            )
+            .await
+            .unwrap();
+
+        let reply = handlers.invoke("write", b"inside").await.unwrap();
+        assert_eq!(reply.unwrap(), b"");
+        assert_eq!(std::fs::read(root.join("inside")).unwrap(), b"inside");
+
+        for path in &["dir-link/secret", "dir-link/new", "link"] {
+            let reply =
+                handlers.invoke("write", path.as_bytes()).await.unwrap();
+            assert!(reply.is_err(), "Wrote {} outside of root", path);
+        }
+        assert_eq!(std::fs::read(outside.join("secret")).unwrap(), b"secret");
+        assert!(!outside.join("new").exists());
+    }
+
+    #[tokio::test]
+    async fn invoke_should_not_read_beyond_memory_of_module() {
+        let handlers = WasmHandlers::default();
+        handlers
+            .load(
+                "log",
+                &module(
+                    r#"(import "over_there" "log"
+                        (func $log (param i32 i32)))"#,
+                    r#"(func (export "handle") (param i32 i32) (result i64)
+                        (call $log (i32.const 0) (i32.const 0x7fffffff))
+                        (i64.const 0))"#,
+                ),
+            )
+            .await
+            .unwrap();
+        handlers
+            .load(
+                "huge",
+                &module(
+                    "",
+                    r#"(func (export "handle") (param i32 i32) (result i64)
+                        (i64.const 0xffffffff))"#,
+                ),
+            )
+            .await
+            .unwrap();
+
+        let reply = handlers.invoke("log", b"").await.unwrap();
+        assert_eq!(reply.unwrap(), b"");
+
+        let err = handlers.invoke("huge", b"").await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
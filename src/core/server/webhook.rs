@@ -0,0 +1,436 @@
+use super::job::now_millis;
+use crate::core::{reply::EvictionReason, request::EvictionKind};
+use crate::utils::TaskTracker;
+use hmac::{Hmac, Mac};
+use log::{error, trace};
+use serde::Serialize;
+use sha2::Sha256;
+use std::io;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    runtime::Handle,
+    time,
+};
+
+/// Maximum time to deliver a single event to a webhook
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header containing the hex-encoded HMAC-SHA256 of the request body when
+/// a webhook has a secret
+pub const SIGNATURE_HEADER: &str = "X-Over-There-Signature";
+
+/// Events on the server that are reported to webhooks
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum WebhookEvent {
+    /// A proc executed by the server has exited
+    #[serde(rename = "proc_exited")]
+    ProcExited { id: u32, exit_code: Option<i32> },
+
+    /// A new config has been pushed to the server
+    #[serde(rename = "config_changed")]
+    ConfigChanged { version: u64 },
+
+    /// A file, proc, or session went untouched and was evicted
+    #[serde(rename = "resource_evicted")]
+    ResourceEvicted {
+        kind: EvictionKind,
+        id: String,
+        reason: EvictionReason,
+    },
+
+    /// A msg was rejected for lacking a valid signature or for being signed
+    /// by a client that is not trusted
+    #[serde(rename = "auth_failed")]
+    AuthFailed { origin: String, reason: String },
+
+    /// A msg was rejected for going over the transfer quota of its sender
+    #[serde(rename = "quota_exceeded")]
+    QuotaExceeded { identity: String, class: String },
+}
+
+impl WebhookEvent {
+    /// Name of the event as it appears in payloads, used to filter which
+    /// events a webhook receives
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ProcExited { .. } => "proc_exited",
+            Self::ConfigChanged { .. } => "config_changed",
+            Self::ResourceEvicted { .. } => "resource_evicted",
+            Self::AuthFailed { .. } => "auth_failed",
+            Self::QuotaExceeded { .. } => "quota_exceeded",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    event: &'a WebhookEvent,
+    timestamp_millis: u64,
+}
+
+/// Endpoint that receives events as an HTTP POST with a JSON payload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Webhook {
+    /// Url of the endpoint, which must use http
+    pub url: String,
+
+    /// If provided, used to sign the body of each request
+    pub secret: Option<Vec<u8>>,
+
+    /// Names of the events to send, or all events if empty
+    pub events: Vec<String>,
+}
+
+impl Webhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Checks that the url of the webhook is one that events can be sent to
+    pub fn validate(&self) -> io::Result<()> {
+        parse_http_url(&self.url).map(|_| ())
+    }
+
+    pub fn wants(&self, event: &WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event.name())
+    }
+
+    /// Sends `event` to the webhook, failing if the endpoint cannot be
+    /// reached or does not respond with a success status
+    pub async fn send(&self, event: &WebhookEvent) -> io::Result<()> {
+        let body = serde_json::to_vec(&WebhookPayload {
+            event,
+            timestamp_millis: now_millis(),
+        })
+        .map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+
+        time::timeout(WEBHOOK_TIMEOUT, self.post(&body))
+            .await
+            .map_err(|x| io::Error::new(io::ErrorKind::TimedOut, x))?
+    }
+
+    async fn post(&self, body: &[u8]) -> io::Result<()> {
+        let (host, port, path) = parse_http_url(&self.url)?;
+
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n",
+            path,
+            host,
+            body.len()
+        );
+        if let Some(secret) = self.secret.as_ref() {
+            head.push_str(&format!(
+                "{}: sha256={}\r\n",
+                SIGNATURE_HEADER,
+                sign(secret, body)
+            ));
+        }
+        head.push_str("\r\n");
+
+        let mut stream = TcpStream::connect((host, port)).await?;
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body).await?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line).await?;
+
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "Webhook responded with {}",
+                status_line.trim_end()
+            ))),
+        }
+    }
+}
+
+/// Collection of webhooks that events are dispatched to, which can be
+/// changed while the server is running
+#[derive(Debug, Default)]
+pub struct Webhooks {
+    hooks: RwLock<Vec<Webhook>>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<Webhook>) -> Self {
+        Self {
+            hooks: RwLock::new(hooks),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.read().unwrap().is_empty()
+    }
+
+    /// Adds the webhooks of `add`, replacing any with the same url, and
+    /// removes those with a url in `remove`, yielding the webhooks that
+    /// remain; nothing changes if any added webhook is invalid
+    pub fn update(
+        &self,
+        add: Vec<Webhook>,
+        remove: &[String],
+    ) -> io::Result<Vec<Webhook>> {
+        for hook in add.iter() {
+            hook.validate()?;
+        }
+
+        let mut hooks = self.hooks.write().unwrap();
+        for hook in add {
+            match hooks.iter_mut().find(|h| h.url == hook.url) {
+                Some(existing) => *existing = hook,
+                None => hooks.push(hook),
+            }
+        }
+        hooks.retain(|h| !remove.contains(&h.url));
+
+        Ok(hooks.clone())
+    }
+
+    /// Sends `event` in the background to every webhook that wants it,
+    /// logging rather than returning any failures, with each delivery
+    /// tracked by `tasks`
+    pub fn fire(&self, tasks: &TaskTracker, event: WebhookEvent) {
+        let hooks = self.hooks.read().unwrap();
+        for hook in hooks.iter().filter(|h| h.wants(&event)) {
+            let hook = hook.clone();
+            let event = event.clone();
+            tasks.spawn(&Handle::current(), async move {
+                trace!("Sending {} to webhook {}", event.name(), hook.url);
+                if let Err(x) = hook.send(&event).await {
+                    error!("Failed to send webhook to {}: {}", hook.url, x);
+                }
+            });
+        }
+    }
+}
+
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    // HMAC can take a key of any size, so we can safely unwrap here
+    let mut mac = Hmac::<Sha256>::new_varkey(secret).unwrap();
+    mac.input(body);
+    format!("{:x}", mac.result().code())
+}
+
+/// Splits an http url into its host, port, and path
+fn parse_http_url(url: &str) -> io::Result<(&str, u16, &str)> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported webhook url: {}", url),
+        )
+    };
+
+    if !url.starts_with("http://") {
+        return Err(invalid());
+    }
+
+    let rest = &url["http://".len()..];
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+
+    match authority.rfind(':') {
+        Some(i) => {
+            let port = authority[i + 1..].parse().map_err(|_| invalid())?;
+            Ok((&authority[..i], port, path))
+        }
+        None if !authority.is_empty() => Ok((authority, 80, path)),
+        None => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    #[test]
+    fn parse_http_url_should_default_port_and_path() {
+        assert_eq!(
+            parse_http_url("http://example.com").unwrap(),
+            ("example.com", 80, "/")
+        );
+        assert_eq!(
+            parse_http_url("http://127.0.0.1:8080/hooks/a?b=c").unwrap(),
+            ("127.0.0.1", 8080, "/hooks/a?b=c")
+        );
+        assert!(parse_http_url("https://example.com").is_err());
+        assert!(parse_http_url("http://example.com:abc/").is_err());
+    }
+
+    #[test]
+    fn wants_should_filter_by_event_name() {
+        let event = WebhookEvent::ProcExited {
+            id: 1,
+            exit_code: Some(0),
+        };
+
+        let mut hook = Webhook::new("http://localhost");
+        assert!(hook.wants(&event), "Empty filter rejected event");
+
+        hook.events = vec![String::from("other")];
+        assert!(!hook.wants(&event), "Unlisted event accepted");
+
+        hook.events.push(String::from("proc_exited"));
+        assert!(hook.wants(&event), "Listed event rejected");
+    }
+
+    #[test]
+    fn update_should_replace_hooks_by_url_and_reject_invalid_ones() {
+        let webhooks = Webhooks::new(vec![
+            Webhook::new("http://localhost/a"),
+            Webhook::new("http://localhost/b"),
+        ]);
+
+        let mut a = Webhook::new("http://localhost/a");
+        a.events = vec![String::from("auth_failed")];
+        let hooks = webhooks
+            .update(
+                vec![a.clone(), Webhook::new("http://localhost/c")],
+                &[String::from("http://localhost/b")],
+            )
+            .unwrap();
+        assert_eq!(hooks, vec![a, Webhook::new("http://localhost/c")]);
+
+        let err = webhooks
+            .update(
+                vec![
+                    Webhook::new("http://localhost/d"),
+                    Webhook::new("https://localhost/e"),
+                ],
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            webhooks.update(Vec::new(), &[]).unwrap(),
+            hooks,
+            "Hooks changed on failure"
+        );
+    }
+
+    #[test]
+    fn events_should_serialize_with_their_name_as_type() {
+        let events = vec![
+            WebhookEvent::AuthFailed {
+                origin: String::from("127.0.0.1:60000"),
+                reason: String::from("Msg is not signed"),
+            },
+            WebhookEvent::QuotaExceeded {
+                identity: String::from("127.0.0.1"),
+                class: String::from("file"),
+            },
+        ];
+
+        for event in events {
+            let value = serde_json::to_value(&event).unwrap();
+            assert_eq!(value["type"], event.name());
+        }
+    }
+
+    /// Accepts a single request, responding with `response` once the full
+    /// request has been read, and yields the request
+    async fn serve_once(
+        response: &'static [u8],
+    ) -> (std::net::SocketAddr, tokio::task::JoinHandle<String>) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(i) = text.find("\r\n\r\n") {
+                    let len: usize = text
+                        .lines()
+                        .find(|l| l.starts_with("Content-Length: "))
+                        .map(|l| l["Content-Length: ".len()..].parse().unwrap())
+                        .unwrap();
+                    if request.len() >= i + 4 + len {
+                        break;
+                    }
+                }
+            }
+
+            stream.write_all(response).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn send_should_post_signed_json_payload() {
+        let (addr, server) =
+            serve_once(b"HTTP/1.1 204 No Content\r\n\r\n").await;
+
+        let mut hook = Webhook::new(format!("http://{}/hook", addr));
+        hook.secret = Some(b"secret".to_vec());
+        hook.send(&WebhookEvent::ProcExited {
+            id: 5,
+            exit_code: Some(1),
+        })
+        .await
+        .unwrap();
+
+        let request = server.await.unwrap();
+        let (head, body) = request.split_at(request.find("\r\n\r\n").unwrap());
+        let body = &body[4..];
+
+        assert!(head.starts_with("POST /hook HTTP/1.1\r\n"), "{}", head);
+        assert!(
+            body.starts_with(
+                r#"{"event":{"type":"proc_exited","id":5,"exit_code":1}"#
+            ),
+            "{}",
+            body
+        );
+        assert!(
+            head.contains(&format!(
+                "{}: sha256={}",
+                SIGNATURE_HEADER,
+                sign(b"secret", body.as_bytes())
+            )),
+            "Missing or invalid signature: {}",
+            head
+        );
+    }
+
+    #[tokio::test]
+    async fn send_should_fail_if_endpoint_rejects_event() {
+        let (addr, _) =
+            serve_once(b"HTTP/1.1 500 Internal Server Error\r\n\r\n").await;
+
+        let hook = Webhook::new(format!("http://{}", addr));
+        let err = hook
+            .send(&WebhookEvent::ProcExited {
+                id: 5,
+                exit_code: None,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+}
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_big_array::big_array;
 use std::convert::TryFrom;
@@ -7,11 +8,12 @@ pub type Digest512Bits = [u8; 64];
 
 big_array! { BigArray; }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(JsonSchema, Serialize, Deserialize, Clone)]
 pub enum Digest {
     Digest256Bits(Digest256Bits),
 
     #[serde(with = "BigArray")]
+    #[schemars(with = "Vec<u8>")]
     Digest512Bits(Digest512Bits),
 }
 
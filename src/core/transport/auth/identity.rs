@@ -0,0 +1,150 @@
+//! Proof that a server holds an identity key, used by clients that pin the
+//! public keys of the servers they expect to connect to
+//!
+//! Identity keys are ed25519 keypairs separate from the key used to sign
+//! packets, so clients only need a server's public key to verify it and
+//! knowing the packet key is not enough to impersonate a pinned server
+
+use ed25519_dalek::{
+    Keypair, PublicKey, SecretKey, Signature, Signer, Verifier,
+};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+/// Size in bytes of the challenges that clients send to servers
+pub const CHALLENGE_SIZE: usize = 32;
+
+/// Size in bytes of the secret half of an identity key
+pub const SECRET_KEY_SIZE: usize = ed25519_dalek::SECRET_KEY_LENGTH;
+
+/// Size in bytes of the public half of an identity key, which is what
+/// clients pin
+pub const PUBLIC_KEY_SIZE: usize = ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+/// Keypair used by a server to prove its identity
+pub struct IdentityKey {
+    keypair: Keypair,
+}
+
+impl IdentityKey {
+    /// Generates a new, random identity key
+    pub fn generate() -> Self {
+        Self {
+            keypair: Keypair::generate(&mut rand::rngs::OsRng),
+        }
+    }
+
+    /// Restores an identity key from its secret half, failing if the secret
+    /// is not `SECRET_KEY_SIZE` bytes
+    pub fn from_secret(secret: &[u8]) -> io::Result<Self> {
+        let secret = SecretKey::from_bytes(secret)
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?;
+        let public = PublicKey::from(&secret);
+
+        Ok(Self {
+            keypair: Keypair { secret, public },
+        })
+    }
+
+    /// Secret half of the key, which must not leave the server
+    pub fn secret(&self) -> &[u8] {
+        self.keypair.secret.as_bytes()
+    }
+
+    /// Public half of the key, which is pinned by clients
+    pub fn public_key(&self) -> &[u8] {
+        self.keypair.public.as_bytes()
+    }
+
+    /// Proves possession of the key by signing `challenge` with it
+    pub fn prove(&self, challenge: &[u8]) -> Vec<u8> {
+        self.sign(challenge)
+    }
+
+    /// Signs `data` with the key, which can be checked with `verify`
+    pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+        self.keypair.sign(data).to_bytes().to_vec()
+    }
+}
+
+impl Clone for IdentityKey {
+    fn clone(&self) -> Self {
+        // NOTE: Secret was validated when the key was made, so it is safe
+        //       to unwrap here
+        Self::from_secret(self.secret()).unwrap()
+    }
+}
+
+impl fmt::Debug for IdentityKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentityKey")
+            .field("fingerprint", &fingerprint(self.public_key()))
+            .finish()
+    }
+}
+
+/// Produces a random challenge for a server to prove its identity against
+pub fn new_challenge() -> Vec<u8> {
+    use rand::RngCore;
+    let mut challenge = vec![0; CHALLENGE_SIZE];
+    rand::thread_rng().fill_bytes(&mut challenge);
+    challenge
+}
+
+/// Hex-encoded SHA-256 of `public_key`, identifying the key concisely
+pub fn fingerprint(public_key: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(public_key))
+}
+
+/// Checks that `proof` was produced by signing `challenge` with the secret
+/// half of `public_key`
+pub fn verify(public_key: &[u8], challenge: &[u8], proof: &[u8]) -> bool {
+    let public_key = match PublicKey::from_bytes(public_key) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+
+    match Signature::try_from(proof) {
+        Ok(signature) => public_key.verify(challenge, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_should_only_accept_proof_from_same_key_and_challenge() {
+        let key = IdentityKey::generate();
+        let other_key = IdentityKey::generate();
+        let challenge = new_challenge();
+        let proof = key.prove(&challenge);
+
+        assert!(verify(key.public_key(), &challenge, &proof));
+        assert!(!verify(other_key.public_key(), &challenge, &proof));
+        assert!(!verify(key.public_key(), &new_challenge(), &proof));
+        assert!(!verify(key.public_key(), &challenge, &proof[1..]));
+        assert!(!verify(&key.public_key()[1..], &challenge, &proof));
+    }
+
+    #[test]
+    fn from_secret_should_restore_same_public_key() {
+        let key = IdentityKey::generate();
+        let restored = IdentityKey::from_secret(key.secret()).unwrap();
+        assert_eq!(restored.public_key(), key.public_key());
+
+        let err = IdentityKey::from_secret(&key.secret()[1..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn fingerprint_should_be_hex_encoded_sha256() {
+        let fp = fingerprint(IdentityKey::generate().public_key());
+        assert_eq!(fp.len(), 64);
+        assert!(fp.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(fp, fingerprint(IdentityKey::generate().public_key()));
+    }
+}
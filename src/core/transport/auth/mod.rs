@@ -7,6 +7,7 @@ pub use impls::{
 mod digest;
 pub use digest::{Digest, Digest256Bits, Digest512Bits};
 
+pub mod identity;
 pub mod split;
 
 use hmac::{Hmac, Mac};
@@ -96,7 +96,7 @@ mod tests {
             .encrypt(&data, &associated_data)
             .expect("Decrypt failed unexpectedly");
         assert_eq!(
-            [&vec![1, 2, 3], associated_data.nonce_slice().unwrap()].concat(),
+            [&[1, 2, 3], associated_data.nonce_slice().unwrap()].concat(),
             encrypted_data
         );
     }
@@ -126,7 +126,7 @@ mod tests {
             .decrypt(&data, &associated_data)
             .expect("Decrypt failed unexpectedly");
         assert_eq!(
-            [&vec![1, 2, 3], associated_data.nonce_slice().unwrap()].concat(),
+            [&[1, 2, 3], associated_data.nonce_slice().unwrap()].concat(),
             decrypted_data
         );
     }
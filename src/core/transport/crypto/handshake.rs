@@ -0,0 +1,244 @@
+//! Key exchange that negotiates a key for a single session
+//!
+//! Each side generates an ephemeral x25519 keypair, sends the public half to
+//! the other, and combines the public half it receives with its own secret
+//! half. Both sides arrive at the same key without it ever being sent, and
+//! the key cannot be recovered later as the secret halves are discarded.
+
+use super::{
+    key::Key256Bits,
+    nonce::{cache::NonceCacheBicrypter, Nonce, Nonce96Bits},
+    Aes256GcmBicrypter, AssociatedData, CryptError, Decrypter, Encrypter,
+};
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use std::convert::TryInto;
+use std::fmt;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+/// Context mixed into every session key so that keys derived by this
+/// handshake are never valid for anything else
+const SESSION_KEY_CONTEXT: &[u8] = b"over-there session key v1";
+
+/// Context signed along with the public keys of a handshake by the identity
+/// key of the responder, so that the signature cannot be passed off as
+/// proof of anything else
+const SIGNED_KEYS_CONTEXT: &[u8] = b"over-there handshake keys v1";
+
+/// Size in bytes of the public key sent by each side of a handshake
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Maximum number of nonces remembered by each side of a session in order
+/// to reject sealed data that is replayed
+pub const SESSION_NONCE_CACHE_SIZE: usize = 4096;
+
+/// Bytes added to data when it is sealed and wrapped in a msg of its own,
+/// including the nonce, the authentication tag, and the outer header
+pub const SEALED_MSG_OVERHEAD: usize = 512;
+
+/// Side of a handshake, where the initiator sends its public key first and
+/// the responder replies with its own
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Role {
+    Initiator,
+    Responder,
+}
+
+/// One side of a handshake in progress
+pub struct Handshake {
+    role: Role,
+    secret: EphemeralSecret,
+    public_key: PublicKey,
+}
+
+impl Handshake {
+    /// Starts a handshake with a new ephemeral keypair
+    pub fn new(role: Role) -> Self {
+        let secret = EphemeralSecret::new(OsRng);
+        let public_key = PublicKey::from(&secret);
+        Self {
+            role,
+            secret,
+            public_key,
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Public key to send to the other side
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_SIZE] {
+        self.public_key.to_bytes()
+    }
+
+    /// Completes the handshake with the public key sent by the other side,
+    /// deriving the key of the session
+    ///
+    /// Fails if the public key is malformed or is one that would let the
+    /// other side force a known key.
+    pub fn finish(
+        self,
+        peer_public_key: &[u8],
+    ) -> Result<Key256Bits, CryptError> {
+        let peer: [u8; PUBLIC_KEY_SIZE] =
+            peer_public_key.try_into().map_err(|_| {
+                CryptError::DecryptFailed(format!(
+                    "Handshake public key is {} bytes instead of {}",
+                    peer_public_key.len(),
+                    PUBLIC_KEY_SIZE
+                ))
+            })?;
+        let peer = PublicKey::from(peer);
+
+        let shared = self.secret.diffie_hellman(&peer);
+        if !shared.was_contributory() {
+            return Err(CryptError::DecryptFailed(String::from(
+                "Handshake public key is of low order",
+            )));
+        }
+
+        // Bind the key to both public keys in a fixed order so that either
+        // side derives the same key
+        let (initiator, responder) = match self.role {
+            Role::Initiator => (self.public_key, peer),
+            Role::Responder => (peer, self.public_key),
+        };
+
+        let mut mac = Hmac::<Sha256>::new_varkey(shared.as_bytes()).unwrap();
+        mac.input(SESSION_KEY_CONTEXT);
+        mac.input(initiator.as_bytes());
+        mac.input(responder.as_bytes());
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&mac.result().code());
+        Ok(key)
+    }
+}
+
+/// Data signed by the identity key of the responder to vouch for the
+/// public keys exchanged by a handshake
+pub fn signed_keys(initiator: &[u8], responder: &[u8]) -> Vec<u8> {
+    let mut data = SIGNED_KEYS_CONTEXT.to_vec();
+    data.extend_from_slice(initiator);
+    data.extend_from_slice(responder);
+    data
+}
+
+/// Seals and opens the msgs of a session with the key negotiated by its
+/// handshake, refusing any sealed data whose nonce was seen before
+#[derive(Clone)]
+pub struct Session {
+    bicrypter: NonceCacheBicrypter<Aes256GcmBicrypter>,
+
+    /// Public key of the identity that signed the handshake negotiating the
+    /// session, if it was signed
+    identity: Option<Vec<u8>>,
+}
+
+impl Session {
+    pub fn new(key: &Key256Bits) -> Self {
+        Self {
+            bicrypter: NonceCacheBicrypter::new(
+                Aes256GcmBicrypter::new(key),
+                SESSION_NONCE_CACHE_SIZE,
+            ),
+            identity: None,
+        }
+    }
+
+    /// Pins the session to the identity with `public_key`, which signed the
+    /// handshake negotiating it
+    pub fn with_identity(mut self, public_key: Option<Vec<u8>>) -> Self {
+        self.identity = public_key;
+        self
+    }
+
+    /// Public key of the identity the session is pinned to, if any
+    pub fn identity(&self) -> Option<&[u8]> {
+        self.identity.as_deref()
+    }
+
+    /// Encrypts `data` with the key of the session, yielding the nonce used
+    /// along with the encrypted data
+    pub fn seal(&self, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), CryptError> {
+        let associated_data = self.bicrypter.new_encrypt_associated_data();
+        let data = self.bicrypter.encrypt(data, &associated_data)?;
+        let nonce = associated_data
+            .nonce_slice()
+            .ok_or(CryptError::MissingNonce)?;
+        Ok((nonce.to_vec(), data))
+    }
+
+    /// Decrypts `data` sealed by the other side of the session with `nonce`
+    pub fn open(
+        &self,
+        nonce: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>, CryptError> {
+        let nonce: Nonce96Bits =
+            nonce.try_into().map_err(|_| CryptError::NonceWrongSize {
+                provided_size: nonce.len(),
+            })?;
+        self.bicrypter
+            .decrypt(data, &AssociatedData::from(Nonce::from(nonce)))
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish_should_derive_same_key_on_both_sides() {
+        let initiator = Handshake::new(Role::Initiator);
+        let responder = Handshake::new(Role::Responder);
+        let initiator_public_key = initiator.public_key();
+        let responder_public_key = responder.public_key();
+
+        let a = initiator.finish(&responder_public_key).unwrap();
+        let b = responder.finish(&initiator_public_key).unwrap();
+        assert_eq!(a, b);
+
+        // Every handshake negotiates a new key
+        let c = Handshake::new(Role::Initiator)
+            .finish(&Handshake::new(Role::Responder).public_key())
+            .unwrap();
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn finish_should_reject_malformed_or_low_order_public_keys() {
+        let handshake = Handshake::new(Role::Responder);
+        assert!(handshake.finish(&[1, 2, 3]).is_err());
+
+        let handshake = Handshake::new(Role::Responder);
+        assert!(handshake.finish(&[0; PUBLIC_KEY_SIZE]).is_err());
+    }
+
+    #[test]
+    fn session_should_only_open_data_sealed_once() {
+        let key = Handshake::new(Role::Initiator)
+            .finish(&Handshake::new(Role::Responder).public_key())
+            .unwrap();
+        let sealer = Session::new(&key);
+        let opener = Session::new(&key);
+
+        let (nonce, data) = sealer.seal(b"some data").unwrap();
+        assert_eq!(opener.open(&nonce, &data).unwrap(), b"some data".to_vec());
+
+        // Replayed data is refused
+        assert!(opener.open(&nonce, &data).is_err());
+
+        // Data with a malformed nonce is refused
+        let (nonce, data) = sealer.seal(b"some data").unwrap();
+        assert!(opener.open(&nonce[1..], &data).is_err());
+    }
+}
@@ -16,6 +16,9 @@ pub use noop::NoopBicrypter;
 mod closure;
 pub use closure::{ClosureDecrypter, ClosureEncrypter};
 
+pub mod handshake;
+pub use handshake::{Handshake, Session};
+
 pub mod split;
 
 use derive_more::{Display, Error};
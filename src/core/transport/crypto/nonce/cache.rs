@@ -5,10 +5,13 @@ use super::{
 use lru::LruCache;
 use std::sync::{Arc, RwLock};
 
+/// Nonces seen so far, shared between clones of the bicrypter
+type NonceCache = Arc<RwLock<LruCache<Vec<u8>, ()>>>;
+
 #[derive(Clone)]
 pub struct NonceCacheBicrypter<T: Bicrypter> {
     bicrypter: T,
-    cache: Option<Arc<RwLock<LruCache<Vec<u8>, ()>>>>,
+    cache: Option<NonceCache>,
 }
 
 impl<T: Bicrypter> NonceCacheBicrypter<T> {
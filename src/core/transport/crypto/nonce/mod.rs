@@ -2,6 +2,7 @@ pub mod cache;
 
 use super::{AssociatedData, CryptError};
 use rand::Rng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Represents a 96-bit nonce (12 bytes)
@@ -10,7 +11,7 @@ pub type Nonce96Bits = [u8; 12];
 /// Represents a 128-bit nonce (16 bytes)
 pub type Nonce128Bits = [u8; 16];
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy)]
 pub enum Nonce {
     Nonce96Bits(Nonce96Bits),
     Nonce128Bits(Nonce128Bits),
@@ -8,6 +8,19 @@ pub mod constants {
 
     /// 5 minute default TTL
     pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 5);
+
+    /// Default maximum size of a msg prior to being split into packets
+    pub const DEFAULT_MAX_MSG_SIZE: usize = super::net::tcp::MAX_MSG_SIZE;
+
+    /// Default maximum groups of packets assembled into msgs at once
+    pub const DEFAULT_MAX_PACKET_GROUPS: usize = 1024;
+
+    /// Default maximum packets within a single group, which covers the
+    /// largest msg allowed on any transport
+    pub const DEFAULT_MAX_PACKETS_PER_GROUP: usize = 65536;
+
+    /// Default minimum time between sweeps for expired groups of packets
+    pub const DEFAULT_PACKET_GC_INTERVAL: Duration = Duration::from_secs(1);
 }
 
 // Export errors
@@ -17,11 +30,13 @@ pub use wire::{
 };
 
 // Export useful constructs
-pub use net::NetTransmission;
+pub use net::{NetTransmission, SocketOptions};
 pub use wire::{
+    spec::WireSpec,
     tcp::{TcpStreamInboundWire, TcpStreamOutboundWire, TcpStreamWire},
     udp::{UdpSocketInboundWire, UdpSocketOutboundWire, UdpSocketWire},
-    InboundWire, OutboundWire, Wire,
+    AssemblyConfig, BufferPool, BufferPoolStats, InboundWire, OutboundWire,
+    PooledBuffer, Wire,
 };
 
 // Re-export the auth and crypto interfaces
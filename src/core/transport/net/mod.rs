@@ -1,6 +1,9 @@
+mod options;
 pub mod tcp;
 pub mod udp;
 
+pub use options::SocketOptions;
+
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 /// The Internet Assigned Numbers Authority (IANA) suggested range
@@ -9,11 +12,11 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 /// - FreeBSD uses this range since release 4.6
 /// - Windows Vista, 7, and Server 2008 use this range
 pub const IANA_EPHEMERAL_PORT_RANGE: std::ops::RangeInclusive<u16> =
-    (49152..=65535);
+    49152..=65535;
 
 /// Common Linux kernel port range
 pub const LINUX_EPHEMERAL_PORT_RANGE: std::ops::RangeInclusive<u16> =
-    (32768..=61000);
+    32768..=61000;
 
 pub fn make_addr_list(host: IpAddr, ports: Vec<u16>) -> Vec<SocketAddr> {
     ports.iter().map(|p| SocketAddr::new(host, *p)).collect()
@@ -59,10 +62,38 @@ impl NetTransmission {
             Self::UdpIpv6 => udp::MAX_IPV6_DATAGRAM_SIZE,
         }
     }
+
+    /// Produces the maximum size of a msg prior to being split into packets
+    pub fn max_msg_size(self) -> usize {
+        match self {
+            Self::TcpEthernet | Self::TcpDialup => tcp::MAX_MSG_SIZE,
+            Self::UdpIpv4 | Self::UdpIpv6 => udp::MAX_MSG_SIZE,
+        }
+    }
+
+    /// All transmissions that can be used to communicate
+    pub fn all() -> [Self; 4] {
+        [
+            Self::TcpEthernet,
+            Self::TcpDialup,
+            Self::UdpIpv4,
+            Self::UdpIpv6,
+        ]
+    }
+
+    /// Name of the transmission as reported to remote instances
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TcpEthernet => "tcp_ethernet",
+            Self::TcpDialup => "tcp_dialup",
+            Self::UdpIpv4 => "udp_ipv4",
+            Self::UdpIpv6 => "udp_ipv6",
+        }
+    }
 }
 
-impl Into<usize> for NetTransmission {
-    fn into(self) -> usize {
-        self.size()
+impl From<NetTransmission> for usize {
+    fn from(val: NetTransmission) -> Self {
+        val.size()
     }
 }
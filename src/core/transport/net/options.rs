@@ -0,0 +1,119 @@
+use std::io;
+use std::net;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Socket-level options applied to the sockets of a client or server, where
+/// any option that is not provided is left at the default of the operating
+/// system
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Time a tcp connection can sit idle before keepalive probes are sent,
+    /// which keeps long-idle sessions alive behind NATs and detects peers
+    /// that silently went away
+    pub tcp_keepalive: Option<Duration>,
+
+    /// Whether small tcp writes are sent immediately rather than coalesced
+    /// (TCP_NODELAY, disabling Nagle's algorithm)
+    pub tcp_nodelay: bool,
+
+    /// Size of the kernel buffer for data received by a tcp stream or udp
+    /// socket (SO_RCVBUF)
+    pub recv_buffer_size: Option<usize>,
+
+    /// Size of the kernel buffer for data sent by a tcp stream or udp
+    /// socket (SO_SNDBUF)
+    pub send_buffer_size: Option<usize>,
+}
+
+impl SocketOptions {
+    /// Applies the options to a connected tcp stream
+    pub fn apply_to_tcp_stream(&self, stream: &TcpStream) -> io::Result<()> {
+        if self.tcp_keepalive.is_some() {
+            stream.set_keepalive(self.tcp_keepalive)?;
+        }
+
+        if self.tcp_nodelay {
+            stream.set_nodelay(true)?;
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            stream.set_recv_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            stream.set_send_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the buffer sizes to a udp socket, which is returned so that
+    /// it can be handed over to the runtime afterwards
+    pub fn apply_to_udp_socket(
+        &self,
+        socket: net::UdpSocket,
+    ) -> io::Result<net::UdpSocket> {
+        if self.recv_buffer_size.is_none() && self.send_buffer_size.is_none() {
+            return Ok(socket);
+        }
+
+        // NOTE: The standard library does not expose the buffer sizes of a
+        //       udp socket, so they are set through socket2 instead
+        let socket = socket2::Socket::from(socket);
+
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        Ok(socket.into_udp_socket())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_to_udp_socket_should_set_buffer_sizes() {
+        let options = SocketOptions {
+            recv_buffer_size: Some(64 * 1024),
+            send_buffer_size: Some(64 * 1024),
+            ..Default::default()
+        };
+
+        let socket = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = options.apply_to_udp_socket(socket).unwrap();
+
+        // NOTE: Operating systems are free to adjust the size requested,
+        //       such as linux doubling it for bookkeeping
+        let socket = socket2::Socket::from(socket);
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+    }
+
+    #[tokio::test]
+    async fn apply_to_tcp_stream_should_set_nodelay_and_keepalive() {
+        let mut listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stream, _) = tokio::join!(TcpStream::connect(addr), async {
+            listener.accept().await.unwrap()
+        });
+        let stream = stream.unwrap();
+
+        let options = SocketOptions {
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            tcp_nodelay: true,
+            ..Default::default()
+        };
+        options.apply_to_tcp_stream(&stream).unwrap();
+
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(stream.keepalive().unwrap(), Some(Duration::from_secs(30)));
+    }
+}
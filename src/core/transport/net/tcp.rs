@@ -7,6 +7,10 @@ pub const MTU_ETHERNET_SIZE: usize = 1500;
 /// Maximum Transmission Unit for Dialup in bytes
 pub const MTU_DIALUP_SIZE: usize = 576;
 
+/// Maximum size of a single msg in bytes, bounding the memory needed to
+/// reassemble it from packets
+pub const MAX_MSG_SIZE: usize = 16 * 1024 * 1024;
+
 pub fn bind(host: IpAddr, port: Vec<u16>) -> io::Result<TcpListener> {
     let addr_candidates = super::make_addr_list(host, port);
     TcpListener::bind(&addr_candidates[..])
@@ -7,6 +7,10 @@ pub const MAX_IPV4_DATAGRAM_SIZE: usize = 508;
 /// IPv6 :: 1212 = 1280 - 60 (IP header) - 8 (udp header)
 pub const MAX_IPV6_DATAGRAM_SIZE: usize = 1212;
 
+/// Maximum size of a single msg in bytes, which is smaller than tcp as lost
+/// datagrams are not resent and every packet of a msg must arrive
+pub const MAX_MSG_SIZE: usize = 1024 * 1024;
+
 pub fn bind(host: IpAddr, port: Vec<u16>) -> io::Result<UdpSocket> {
     let addr_candidates = super::make_addr_list(host, port);
     UdpSocket::bind(&addr_candidates[..])
@@ -19,17 +23,7 @@ pub fn bind(host: IpAddr, port: Vec<u16>) -> io::Result<UdpSocket> {
 /// NOTE: This seems to be equivalent to a non-bound socket doing a connect,
 ///       which could look like UdpSocket::bind("0.0.0.0:0").connect(...)
 pub fn connect(addr: SocketAddr) -> io::Result<UdpSocket> {
-    let socket = if addr.is_ipv4() {
-        bind(
-            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
-            super::IANA_EPHEMERAL_PORT_RANGE.collect(),
-        )?
-    } else {
-        bind(
-            IpAddr::V6(Ipv6Addr::UNSPECIFIED),
-            super::IANA_EPHEMERAL_PORT_RANGE.collect(),
-        )?
-    };
+    let socket = unconnected(addr.is_ipv4())?;
 
     // NOTE: There appears to be a limitation in MacOS that causes subsequent
     //       calls to socket.send_to(...) after a connect to fail with OS
@@ -41,6 +35,23 @@ pub fn connect(addr: SocketAddr) -> io::Result<UdpSocket> {
     Ok(socket)
 }
 
+/// Binds to a local, ephemeral port on all interfaces without connecting,
+/// so that the socket can communicate with any number of remote addresses
+/// of the same ip version
+pub fn unconnected(ipv4: bool) -> io::Result<UdpSocket> {
+    if ipv4 {
+        bind(
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            super::IANA_EPHEMERAL_PORT_RANGE.collect(),
+        )
+    } else {
+        bind(
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            super::IANA_EPHEMERAL_PORT_RANGE.collect(),
+        )
+    }
+}
+
 pub fn local() -> io::Result<UdpSocket> {
     bind(
         IpAddr::from(Ipv4Addr::LOCALHOST),
@@ -1,8 +1,51 @@
 use crate::core::transport::{constants, wire::packet::Packet};
-use crate::utils::TtlValue;
 use derive_more::{Display, Error};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Limits and garbage collection applied when assembling packets into msgs
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AssemblyConfig {
+    /// Time-to-live of a group of packets before it is discarded incomplete
+    pub packet_ttl: Duration,
+
+    /// If true, the deadline of a group is extended each time one of its
+    /// packets arrives, so that only groups that stop receiving packets
+    /// expire rather than any group taking longer than the ttl to arrive
+    pub adaptive_ttl: bool,
+
+    /// Maximum groups assembled at once, where packets starting any new
+    /// group are rejected until an existing group completes or expires
+    pub max_groups: usize,
+
+    /// Maximum packets within a single group
+    pub max_packets_per_group: usize,
+
+    /// Minimum time between sweeps for expired groups
+    pub gc_interval: Duration,
+}
+
+impl AssemblyConfig {
+    /// Creates a config with the provided ttl and all other settings default
+    pub fn with_ttl(packet_ttl: Duration) -> Self {
+        Self {
+            packet_ttl,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for AssemblyConfig {
+    fn default() -> Self {
+        Self {
+            packet_ttl: constants::DEFAULT_TTL,
+            adaptive_ttl: true,
+            max_groups: constants::DEFAULT_MAX_PACKET_GROUPS,
+            max_packets_per_group: constants::DEFAULT_MAX_PACKETS_PER_GROUP,
+            gc_interval: constants::DEFAULT_PACKET_GC_INTERVAL,
+        }
+    }
+}
 
 #[derive(Debug, Display, Error)]
 pub enum DecoderError {
@@ -22,6 +65,15 @@ pub enum DecoderError {
         index: u32,
     },
     IncompletePacketCollection,
+    #[display(fmt = "Already assembling maximum of {} groups", max)]
+    TooManyGroups {
+        max: usize,
+    },
+    #[display(fmt = "id:{}, exceeds maximum of {} packets", id, max)]
+    TooManyPackets {
+        id: u32,
+        max: usize,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -32,32 +84,47 @@ struct PacketGroup {
     /// The final index of the packet group, which we only know once we've
     /// received the final packet (can still be out of order)
     final_index: Option<u32>,
+
+    /// Time at which the group expires if it has not been completed
+    deadline: Instant,
 }
 
-impl Default for PacketGroup {
-    fn default() -> Self {
+impl PacketGroup {
+    fn new(ttl: Duration) -> Self {
         Self {
             packets: HashMap::new(),
             final_index: None,
+            deadline: Instant::now() + ttl,
         }
     }
+
+    fn has_expired(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Decoder {
     /// Map of unique id to associated group of packets being decoded
-    packet_groups: HashMap<TtlValue<u32>, PacketGroup>,
+    packet_groups: HashMap<u32, PacketGroup>,
+
+    /// Limits and expiration of groups of packets
+    config: AssemblyConfig,
 
-    /// Maximum time-to-live for each group of packets before being removed;
-    /// this time can be updated upon adding a new packet to a group
-    ttl: Duration,
+    /// Time at which expired groups were last swept
+    last_gc: Instant,
 }
 
 impl Decoder {
     pub fn new(ttl: Duration) -> Self {
+        Self::with_config(AssemblyConfig::with_ttl(ttl))
+    }
+
+    pub fn with_config(config: AssemblyConfig) -> Self {
         Self {
             packet_groups: HashMap::new(),
-            ttl,
+            config,
+            last_gc: Instant::now(),
         }
     }
 
@@ -67,6 +134,11 @@ impl Decoder {
         self.packet_groups.len()
     }
 
+    /// Returns whether or not the decoder has no packet groups
+    pub fn is_empty(&self) -> bool {
+        self.packet_groups.is_empty()
+    }
+
     /// Adds a new packet to the decoder, consuming it for reconstruction
     pub fn add_packet(&mut self, packet: Packet) -> Result<(), DecoderError> {
         let id = packet.id();
@@ -74,17 +146,45 @@ impl Decoder {
         let is_final = packet.is_final();
 
         // Check if we already have a group for this packet, otherwise create
-        // a new group
+        // a new group if not already at the maximum
+        let AssemblyConfig {
+            packet_ttl,
+            adaptive_ttl,
+            max_groups,
+            max_packets_per_group,
+            ..
+        } = self.config;
+        let expired = self.packet_groups.get(&id).map(PacketGroup::has_expired);
+        if expired == Some(true) {
+            self.packet_groups.remove(&id);
+        }
+        if !self.packet_groups.contains_key(&id)
+            && self.packet_groups.len() >= max_groups
+        {
+            // Expired groups may not have been swept yet, so make room
+            self.remove_expired();
+            if self.packet_groups.len() >= max_groups {
+                return Err(DecoderError::TooManyGroups { max: max_groups });
+            }
+        }
         let group = self
             .packet_groups
-            .entry(TtlValue::new(id, self.ttl))
-            .or_default();
+            .entry(id)
+            .or_insert_with(|| PacketGroup::new(packet_ttl));
 
         // Check if we already have this packet
         if group.packets.contains_key(&index) {
             return Err(DecoderError::PacketExists { id, index });
         }
 
+        // Check if the group would grow beyond the maximum packets
+        if group.packets.len() >= max_packets_per_group {
+            return Err(DecoderError::TooManyPackets {
+                id,
+                max: max_packets_per_group,
+            });
+        }
+
         // Check if we are adding a final packet when we already have one
         if let Some(last_index) = group.final_index {
             if is_final {
@@ -106,24 +206,38 @@ impl Decoder {
             group.final_index = Some(index);
         }
 
+        // Keep the group alive while its packets keep arriving
+        if adaptive_ttl {
+            group.deadline = Instant::now() + packet_ttl;
+        }
+
         Ok(())
     }
 
     /// Removes the specified packet group, returning whether or not the
     /// group existed to be removed
     pub fn remove_group(&mut self, group_id: u32) -> bool {
-        self.packet_groups.remove(&group_id.into()).is_some()
+        self.packet_groups.remove(&group_id).is_some()
     }
 
     /// Removes all expired packet groups from the decoder
     pub fn remove_expired(&mut self) {
-        self.packet_groups.retain(|k, _| !k.has_expired())
+        self.last_gc = Instant::now();
+        self.packet_groups.retain(|_, g| !g.has_expired())
+    }
+
+    /// Removes all expired packet groups from the decoder if the interval
+    /// between sweeps has elapsed since the last sweep
+    pub fn gc(&mut self) {
+        if self.last_gc.elapsed() >= self.config.gc_interval {
+            self.remove_expired();
+        }
     }
 
     /// Determines whether or not all packets have been added to the decoder
     pub fn verify(&self, group_id: u32) -> bool {
         self.packet_groups
-            .get(&group_id.into())
+            .get(&group_id)
             .and_then(|g| {
                 let total_packets = g.packets.len() as u32;
                 g.final_index.map(|i| i + 1 == total_packets)
@@ -141,7 +255,7 @@ impl Decoder {
         }
 
         // Grab the appropriate group, which we can now assume exists
-        let group = self.packet_groups.get(&group_id.into()).unwrap();
+        let group = self.packet_groups.get(&group_id).unwrap();
 
         // Gather references to packets in proper order
         let mut packets = group.packets.values().collect::<Vec<&Packet>>();
@@ -197,9 +311,8 @@ mod tests {
 
         // Add first packet successfully
         let result = a.add_packet(make_empty_packet(id, index, false));
-        assert_eq!(
+        assert!(
             result.is_ok(),
-            true,
             "Expected success for adding first packet, but got {}",
             result.unwrap_err(),
         );
@@ -227,9 +340,8 @@ mod tests {
 
         // Add first packet successfully
         let result = a.add_packet(make_empty_packet(id, 0, true));
-        assert_eq!(
+        assert!(
             result.is_ok(),
-            true,
             "Expected success for adding first packet, but got {}",
             result.unwrap_err(),
         );
@@ -253,9 +365,8 @@ mod tests {
 
         // Make the second packet (index) be the last packet
         let result = a.add_packet(make_empty_packet(0, 1, true));
-        assert_eq!(
+        assert!(
             result.is_ok(),
-            true,
             "Expected success for adding first packet, but got {}",
             result.unwrap_err(),
         );
@@ -312,10 +423,80 @@ mod tests {
         assert_eq!(a.packet_groups.len(), 1, "Unexpired packet did not remain");
     }
 
+    #[test]
+    fn add_packet_should_extend_deadline_of_group_if_adaptive() {
+        let mut a = Decoder::new(Duration::from_millis(100));
+
+        // Packets keep arriving within the ttl, but the group as a whole
+        // takes longer than the ttl to arrive
+        a.add_packet(make_empty_packet(0, 0, false)).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        a.add_packet(make_empty_packet(0, 1, false)).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        a.add_packet(make_empty_packet(0, 2, true)).unwrap();
+        assert!(a.verify(0), "Group expired while receiving packets");
+
+        let mut a = Decoder::with_config(AssemblyConfig {
+            adaptive_ttl: false,
+            ..AssemblyConfig::with_ttl(Duration::from_millis(100))
+        });
+        a.add_packet(make_empty_packet(0, 0, false)).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        a.add_packet(make_empty_packet(0, 1, false)).unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        a.add_packet(make_empty_packet(0, 2, true)).unwrap();
+        assert!(!a.verify(0), "Group did not expire with fixed ttl");
+    }
+
+    #[test]
+    fn add_packet_fails_if_beyond_maximum_groups_or_packets() {
+        let mut a = Decoder::with_config(AssemblyConfig {
+            max_groups: 1,
+            max_packets_per_group: 2,
+            ..Default::default()
+        });
+
+        a.add_packet(make_empty_packet(0, 0, false)).unwrap();
+        a.add_packet(make_empty_packet(0, 1, false)).unwrap();
+        match a.add_packet(make_empty_packet(0, 2, true)) {
+            Err(DecoderError::TooManyPackets { id, max }) => {
+                assert_eq!(id, 0);
+                assert_eq!(max, 2);
+            }
+            x => panic!("Unexpected result: {:?}", x),
+        }
+
+        match a.add_packet(make_empty_packet(1, 0, true)) {
+            Err(DecoderError::TooManyGroups { max }) => assert_eq!(max, 1),
+            x => panic!("Unexpected result: {:?}", x),
+        }
+
+        // Once the group is removed, there is room for another
+        a.remove_group(0);
+        a.add_packet(make_empty_packet(1, 0, true)).unwrap();
+    }
+
+    #[test]
+    fn gc_should_only_remove_expired_groups_once_interval_elapses() {
+        let mut a = Decoder::with_config(AssemblyConfig {
+            packet_ttl: Duration::new(0, 0),
+            gc_interval: Duration::from_millis(20),
+            ..Default::default()
+        });
+        a.add_packet(make_empty_packet(0, 0, false)).unwrap();
+
+        a.gc();
+        assert_eq!(a.len(), 1, "Swept before interval elapsed");
+
+        std::thread::sleep(Duration::from_millis(21));
+        a.gc();
+        assert!(a.is_empty(), "Did not sweep after interval elapsed");
+    }
+
     #[test]
     fn verify_yields_false_if_empty() {
         let a = Decoder::default();
-        assert_eq!(a.verify(0), false);
+        assert!(!a.verify(0));
     }
 
     #[test]
@@ -325,7 +506,7 @@ mod tests {
         // Add first packet (index 0), still needing final packet
         let _ = a.add_packet(make_empty_packet(0, 0, false));
 
-        assert_eq!(a.verify(0), false);
+        assert!(!a.verify(0));
     }
 
     #[test]
@@ -333,13 +514,12 @@ mod tests {
         let mut a = Decoder::default();
 
         // Add packet at end (index 1), still needing first packet
-        assert_eq!(
+        assert!(
             a.add_packet(make_empty_packet(0, 1, true)).is_ok(),
-            true,
             "Unexpectedly failed to add a new packet",
         );
 
-        assert_eq!(a.verify(0), false);
+        assert!(!a.verify(0));
     }
 
     #[test]
@@ -347,33 +527,30 @@ mod tests {
         let mut a = Decoder::default();
 
         // Add packet at beginning (index 0)
-        assert_eq!(
+        assert!(
             a.add_packet(make_empty_packet(0, 0, false)).is_ok(),
-            true,
             "Unexpectedly failed to add a new packet",
         );
 
         // Add packet at end (index 2)
-        assert_eq!(
+        assert!(
             a.add_packet(make_empty_packet(0, 2, true)).is_ok(),
-            true,
             "Unexpectedly failed to add a new packet",
         );
 
-        assert_eq!(a.verify(0), false);
+        assert!(!a.verify(0));
     }
 
     #[test]
     fn verify_yields_true_if_have_all_packets() {
         let mut a = Decoder::default();
 
-        assert_eq!(
+        assert!(
             a.add_packet(make_empty_packet(0, 0, true)).is_ok(),
-            true,
             "Unexpectedly failed to add a new packet",
         );
 
-        assert_eq!(a.verify(0), true);
+        assert!(a.verify(0));
     }
 
     #[test]
@@ -2,9 +2,10 @@ pub mod decoder;
 
 use crate::core::transport::crypto::{AssociatedData, CryptError, Decrypter, Nonce};
 use crate::core::transport::{auth::Verifier, wire::packet::Packet};
-use decoder::Decoder;
+use decoder::{AssemblyConfig, Decoder};
 use derive_more::{Display, Error};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 
 #[derive(Debug, Display, Error)]
 pub enum InputProcessorError {
@@ -24,6 +25,11 @@ where
     decoder: Decoder,
     verifier: V,
     decrypter: D,
+
+    /// Decoders for packets processed by the peer that sent them, so that
+    /// the packet groups of different peers are never mixed
+    peer_decoders: HashMap<SocketAddr, Decoder>,
+    config: AssemblyConfig,
 }
 
 impl<V, D> InputProcessor<V, D>
@@ -31,12 +37,13 @@ where
     V: Verifier,
     D: Decrypter,
 {
-    pub fn new(packet_ttl: Duration, verifier: V, decrypter: D) -> Self {
-        let decoder = Decoder::new(packet_ttl);
+    pub fn new(config: AssemblyConfig, verifier: V, decrypter: D) -> Self {
         Self {
-            decoder,
+            decoder: Decoder::with_config(config),
             verifier,
             decrypter,
+            peer_decoders: HashMap::new(),
+            config,
         }
     }
 
@@ -44,44 +51,83 @@ where
         &mut self,
         data: &[u8],
     ) -> Result<Option<Vec<u8>>, InputProcessorError> {
-        if data.is_empty() {
-            return Ok(None);
-        }
+        process_packet(&mut self.decoder, &self.verifier, &self.decrypter, data)
+    }
 
-        // Process the data as a packet
-        let p = Packet::from_slice(data)
-            .map_err(InputProcessorError::EncodePacket)?;
+    /// Processes data sent by `addr`, assembling its packets separately
+    /// from those of any other peer
+    pub fn process_from(
+        &mut self,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> Result<Option<Vec<u8>>, InputProcessorError> {
+        let config = self.config;
+        let decoder = self
+            .peer_decoders
+            .entry(addr)
+            .or_insert_with(|| Decoder::with_config(config));
+        let result =
+            process_packet(decoder, &self.verifier, &self.decrypter, data);
+
+        // Forget about peers once they have no partial msgs, which includes
+        // any whose packet groups have all expired
+        self.peer_decoders.retain(|_, d| {
+            d.gc();
+            !d.is_empty()
+        });
+
+        result
+    }
 
-        // Verify the packet's signature, skipping any form of assembly if
-        // it is not a legit packet
-        if !verify_packet(&self.verifier, &p)? {
-            return Err(InputProcessorError::InvalidPacketSignature);
-        }
+    /// Total peers with msgs that have only been partially received
+    #[cfg(test)]
+    pub fn peers_with_partial_msgs(&self) -> usize {
+        self.peer_decoders.len()
+    }
+}
 
-        let group_id = p.id();
-        let nonce = p.nonce().cloned();
-
-        // Ensure that packet groups are still valid
-        self.decoder.remove_expired();
-
-        // Add the packet, see if we are ready to decode the data, and do so
-        let do_decode = add_packet_and_verify(&mut self.decoder, p)?;
-        if do_decode {
-            // Gather the complete data
-            let data = decode_and_decrypt(
-                group_id,
-                &self.decoder,
-                &self.decrypter,
-                nonce,
-            )?;
-
-            // Remove the underlying group as we no longer need to keep it
-            self.decoder.remove_group(group_id);
-
-            Ok(Some(data))
-        } else {
-            Ok(None)
-        }
+fn process_packet<V, D>(
+    decoder: &mut Decoder,
+    verifier: &V,
+    decrypter: &D,
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, InputProcessorError>
+where
+    V: Verifier,
+    D: Decrypter,
+{
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    // Process the data as a packet
+    let p =
+        Packet::from_slice(data).map_err(InputProcessorError::EncodePacket)?;
+
+    // Verify the packet's signature, skipping any form of assembly if
+    // it is not a legit packet
+    if !verify_packet(verifier, &p)? {
+        return Err(InputProcessorError::InvalidPacketSignature);
+    }
+
+    let group_id = p.id();
+    let nonce = p.nonce().cloned();
+
+    // Periodically sweep for packet groups that are no longer valid
+    decoder.gc();
+
+    // Add the packet, see if we are ready to decode the data, and do so
+    let do_decode = add_packet_and_verify(decoder, p)?;
+    if do_decode {
+        // Gather the complete data
+        let data = decode_and_decrypt(group_id, decoder, decrypter, nonce)?;
+
+        // Remove the underlying group as we no longer need to keep it
+        decoder.remove_group(group_id);
+
+        Ok(Some(data))
+    } else {
+        Ok(None)
     }
 }
 
@@ -152,7 +198,7 @@ mod tests {
 
     fn new_processor() -> InputProcessor<NoopAuthenticator, NoopBicrypter> {
         InputProcessor::new(
-            Duration::from_secs(1),
+            AssemblyConfig::with_ttl(Duration::from_secs(1)),
             NoopAuthenticator,
             NoopBicrypter,
         )
@@ -204,9 +250,8 @@ mod tests {
             })
             .unwrap()[0];
         let data = p.to_vec().unwrap();
-        assert_eq!(
+        assert!(
             processor.process(&data).is_ok(),
-            true,
             "Failed to receive first packet!"
         );
 
@@ -306,7 +351,7 @@ mod tests {
         // Create a custom context whose packet groups within its decoder
         // will expire immediately
         let mut processor = InputProcessor::new(
-            Duration::new(0, 0),
+            AssemblyConfig::with_ttl(Duration::new(0, 0)),
             NoopAuthenticator,
             NoopBicrypter,
         );
@@ -363,6 +408,57 @@ mod tests {
         assert_eq!(processor.decoder.len(), 0);
     }
 
+    #[test]
+    fn input_processor_process_from_should_assemble_packets_per_peer() {
+        let mut processor = new_processor();
+        let addr_a: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let addr_b: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let data_a = vec![1; 16];
+        let data_b = vec![2; 16];
+
+        // Make many small packets for each peer, both using the same id
+        let encode = |data: &[u8]| {
+            let max_packet_size = Encoder::default()
+                .estimate_packet_size(
+                    1,
+                    PacketType::NotFinal,
+                    &NoopAuthenticator,
+                )
+                .unwrap()
+                + 4;
+            Encoder::default()
+                .encode(EncodeArgs {
+                    id: 0,
+                    encryption: PacketEncryption::None,
+                    data,
+                    max_packet_size,
+                    signer: &NoopAuthenticator,
+                })
+                .unwrap()
+        };
+        let packets_a = encode(&data_a);
+        let packets_b = encode(&data_b);
+        assert!(packets_a.len() > 1, "Did not produce many small packets");
+
+        let mut results = Vec::new();
+        for (a, b) in packets_a.iter().zip(packets_b.iter()) {
+            let pdata = a.to_vec().unwrap();
+            if let Some(data) = processor.process_from(&pdata, addr_a).unwrap()
+            {
+                results.push((addr_a, data));
+            }
+
+            let pdata = b.to_vec().unwrap();
+            if let Some(data) = processor.process_from(&pdata, addr_b).unwrap()
+            {
+                results.push((addr_b, data));
+            }
+        }
+
+        assert_eq!(results, vec![(addr_a, data_a), (addr_b, data_b)]);
+        assert_eq!(processor.peers_with_partial_msgs(), 0);
+    }
+
     #[cfg(test)]
     mod crypt {
         use super::*;
@@ -397,7 +493,7 @@ mod tests {
 
         fn new_processor() -> InputProcessor<NoopAuthenticator, BadDecrypter> {
             InputProcessor::new(
-                Duration::from_secs(1),
+                AssemblyConfig::with_ttl(Duration::from_secs(1)),
                 NoopAuthenticator,
                 BadDecrypter,
             )
@@ -438,9 +534,8 @@ mod tests {
             // First N-1 packets should succeed
             for p in packets[..packets.len() - 1].iter() {
                 let pdata = p.to_vec().unwrap();
-                assert_eq!(
+                assert!(
                     processor.process(&pdata).is_ok(),
-                    true,
                     "Unexpectedly failed to receive packet"
                 );
             }
@@ -1,10 +1,13 @@
 mod input;
 mod output;
 mod packet;
+mod pool;
+pub mod spec;
 pub mod tcp;
 pub mod udp;
 
 use crate::core::transport::auth::{self as auth, Authenticator, Signer, Verifier};
+use crate::core::transport::constants;
 use crate::core::transport::crypto::{
     self as crypto, Bicrypter, Decrypter, Encrypter,
 };
@@ -15,10 +18,18 @@ use std::time::Duration;
 use tokio::net::{TcpStream, UdpSocket};
 
 // Export errors
-pub use input::decoder::DecoderError;
+pub use input::decoder::{AssemblyConfig, DecoderError};
 pub use input::{InputProcessor, InputProcessorError};
 pub use output::encoder::EncoderError;
 pub use output::{OutputProcessor, OutputProcessorError};
+pub use pool::{BufferPool, BufferPoolStats, PooledBuffer};
+
+/// Inbound and outbound halves of a wire whose authenticator and
+/// bicrypter are shared between them
+type ArcSplitWire<A, B> = (
+    InboundWire<auth::split::VerifierHalf<A>, crypto::split::DecrypterHalf<B>>,
+    OutboundWire<auth::split::SignerHalf<A>, crypto::split::EncrypterHalf<B>>,
+);
 
 #[derive(Debug, Clone)]
 pub struct Wire<A, B>
@@ -27,9 +38,13 @@ where
     B: Bicrypter,
 {
     transmission_size: usize,
-    packet_ttl: Duration,
+    max_msg_size: usize,
+    assembly: AssemblyConfig,
     authenticator: A,
     bicrypter: B,
+
+    /// Buffers that inbound data is read into, shared by clones of the wire
+    pool: BufferPool,
 }
 
 impl<A, B> Wire<A, B>
@@ -45,18 +60,46 @@ where
     ) -> Self {
         Self {
             transmission_size,
-            packet_ttl,
+            max_msg_size: constants::DEFAULT_MAX_MSG_SIZE,
+            assembly: AssemblyConfig::with_ttl(packet_ttl),
             authenticator,
             bicrypter,
+            pool: BufferPool::new(transmission_size),
         }
     }
 
+    /// Sets the maximum size of a msg that can be sent out on the wire,
+    /// prior to being split into packets
+    pub fn with_max_msg_size(mut self, max_msg_size: usize) -> Self {
+        self.max_msg_size = max_msg_size;
+        self
+    }
+
+    /// Sets the limits and garbage collection applied when assembling
+    /// inbound packets into msgs, including the packet ttl
+    pub fn with_assembly_config(mut self, assembly: AssemblyConfig) -> Self {
+        self.assembly = assembly;
+        self
+    }
+
     pub fn transmission_size(&self) -> usize {
         self.transmission_size
     }
 
+    pub fn max_msg_size(&self) -> usize {
+        self.max_msg_size
+    }
+
     pub fn packet_ttl(&self) -> Duration {
-        self.packet_ttl
+        self.assembly.packet_ttl
+    }
+
+    pub fn assembly_config(&self) -> &AssemblyConfig {
+        &self.assembly
+    }
+
+    pub fn buffer_pool(&self) -> &BufferPool {
+        &self.pool
     }
 
     pub fn with_tcp_stream(
@@ -74,30 +117,23 @@ where
         udp::UdpSocketWire::new(self, socket)
     }
 
-    pub fn arc_split(
-        self,
-    ) -> (
-        InboundWire<
-            auth::split::VerifierHalf<A>,
-            crypto::split::DecrypterHalf<B>,
-        >,
-        OutboundWire<
-            auth::split::SignerHalf<A>,
-            crypto::split::EncrypterHalf<B>,
-        >,
-    ) {
+    pub fn arc_split(self) -> ArcSplitWire<A, B> {
         let Self {
             transmission_size,
-            packet_ttl,
+            max_msg_size,
+            assembly,
             authenticator,
             bicrypter,
+            pool,
         } = self;
 
         let (signer, verifier) = auth::split::split(authenticator);
         let (encrypter, decrypter) = crypto::split::split(bicrypter);
         new_inbound_outbound_wires(
             transmission_size,
-            packet_ttl,
+            max_msg_size,
+            assembly,
+            pool,
             signer,
             verifier,
             encrypter,
@@ -114,15 +150,19 @@ where
     pub fn clone_split(self) -> (InboundWire<A, B>, OutboundWire<A, B>) {
         let Self {
             transmission_size,
-            packet_ttl,
+            max_msg_size,
+            assembly,
             authenticator,
             bicrypter,
+            pool,
         } = self;
         let (signer, verifier) = auth::split::clone_split(authenticator);
         let (encrypter, decrypter) = crypto::split::clone_split(bicrypter);
         new_inbound_outbound_wires(
             transmission_size,
-            packet_ttl,
+            max_msg_size,
+            assembly,
+            pool,
             signer,
             verifier,
             encrypter,
@@ -149,6 +189,9 @@ where
 
     /// Processes input coming into the wire
     input_processor: InputProcessor<V, D>,
+
+    /// Buffers that data is read into before being processed
+    pool: BufferPool,
 }
 
 impl<V, D> InboundWire<V, D>
@@ -158,22 +201,39 @@ where
 {
     pub fn new(
         transmission_size: usize,
-        packet_ttl: Duration,
+        assembly: AssemblyConfig,
         verifier: V,
         decrypter: D,
     ) -> Self {
         let input_processor =
-            InputProcessor::new(packet_ttl, verifier, decrypter);
+            InputProcessor::new(assembly, verifier, decrypter);
         Self {
             transmission_size,
             input_processor,
+            pool: BufferPool::new(transmission_size),
         }
     }
 
+    /// Reads into buffers from `pool` rather than one of its own, allowing
+    /// many wires to share idle buffers
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
     pub fn transmission_size(&self) -> usize {
         self.transmission_size
     }
 
+    pub fn buffer_pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    /// Takes a buffer large enough to hold a single transmission
+    pub fn take_buffer(&self) -> PooledBuffer {
+        self.pool.take()
+    }
+
     pub fn with_tcp_stream(
         self,
         stream: tokio::io::ReadHalf<TcpStream>,
@@ -198,6 +258,19 @@ where
             .process(buf)
             .map_err(InboundWireError::InputProcessor)
     }
+
+    /// Processes data sent by `addr`, keeping partial msgs of each peer
+    /// separate from one another
+    #[inline]
+    pub fn process_from(
+        &mut self,
+        buf: &[u8],
+        addr: SocketAddr,
+    ) -> Result<Option<Vec<u8>>, InboundWireError> {
+        self.input_processor
+            .process_from(buf, addr)
+            .map_err(InboundWireError::InputProcessor)
+    }
 }
 
 #[derive(Debug, Display, Error)]
@@ -207,6 +280,13 @@ pub enum OutboundWireError {
 
     /// When fail to send all bytes out together on the wire
     IncompleteSend,
+
+    /// When the msg is larger than the wire is allowed to carry
+    #[display(fmt = "Msg of {} bytes exceeds maximum of {} bytes", size, max)]
+    MsgTooLarge {
+        size: usize,
+        max: usize,
+    },
 }
 
 /// Wire for outbound communication
@@ -216,6 +296,9 @@ where
     S: Signer,
     E: Encrypter,
 {
+    /// Maximum size of a msg prior to being split into packets
+    max_msg_size: usize,
+
     /// Processes output leaving on the wire
     output_processor: OutputProcessor<S, E>,
 }
@@ -225,10 +308,22 @@ where
     S: Signer,
     E: Encrypter,
 {
-    pub fn new(transmission_size: usize, signer: S, encrypter: E) -> Self {
+    pub fn new(
+        transmission_size: usize,
+        max_msg_size: usize,
+        signer: S,
+        encrypter: E,
+    ) -> Self {
         let output_processor =
             OutputProcessor::new(transmission_size, signer, encrypter);
-        Self { output_processor }
+        Self {
+            max_msg_size,
+            output_processor,
+        }
+    }
+
+    pub fn max_msg_size(&self) -> usize {
+        self.max_msg_size
     }
 
     pub fn with_tcp_stream(
@@ -250,15 +345,25 @@ where
         &mut self,
         buf: &[u8],
     ) -> Result<Vec<Vec<u8>>, OutboundWireError> {
+        if buf.len() > self.max_msg_size {
+            return Err(OutboundWireError::MsgTooLarge {
+                size: buf.len(),
+                max: self.max_msg_size,
+            });
+        }
+
         self.output_processor
             .process(buf)
             .map_err(OutboundWireError::OutputProcessor)
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn new_inbound_outbound_wires<S, V, E, D>(
     transmission_size: usize,
-    packet_ttl: Duration,
+    max_msg_size: usize,
+    assembly: AssemblyConfig,
+    pool: BufferPool,
     signer: S,
     verifier: V,
     encrypter: E,
@@ -271,8 +376,34 @@ where
     D: Decrypter,
 {
     let inbound_wire =
-        InboundWire::new(transmission_size, packet_ttl, verifier, decrypter);
-    let outbound_wire = OutboundWire::new(transmission_size, signer, encrypter);
+        InboundWire::new(transmission_size, assembly, verifier, decrypter)
+            .with_buffer_pool(pool);
+    let outbound_wire =
+        OutboundWire::new(transmission_size, max_msg_size, signer, encrypter);
 
     (inbound_wire, outbound_wire)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transport::{
+        auth::NoopAuthenticator, crypto::NoopBicrypter,
+    };
+
+    #[test]
+    fn outbound_wire_process_should_fail_if_msg_exceeds_max_size() {
+        let mut wire =
+            OutboundWire::new(100, 10, NoopAuthenticator, NoopBicrypter);
+
+        match wire.process(&[0; 11]) {
+            Err(OutboundWireError::MsgTooLarge { size, max }) => {
+                assert_eq!(size, 11);
+                assert_eq!(max, 10);
+            }
+            x => panic!("Unexpected result: {:?}", x),
+        }
+
+        assert!(wire.process(&[0; 10]).is_ok(), "Msg at max size rejected");
+    }
+}
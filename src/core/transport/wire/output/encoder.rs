@@ -30,7 +30,7 @@ pub enum EncoderError {
     FailedToSignPacket,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub(crate) struct Encoder {
     /// Key = Max Packet Size + PacketType
     /// Value = Max data size to fit in packet without exceeding max packet size
@@ -243,8 +243,8 @@ impl Encoder {
         //       but is the most straightforward way I can think of unless
         //       serde offers some form of size hinting for msgpack/cbor specifically
         let packet_size = Encoder::make_new_packet(
-            u32::max_value(),
-            u32::max_value(),
+            u32::MAX,
+            u32::MAX,
             r#type,
             &fake_data,
             signer,
@@ -258,15 +258,6 @@ impl Encoder {
     }
 }
 
-impl Default for Encoder {
-    fn default() -> Self {
-        Self {
-            max_data_size_cache: HashMap::new(),
-            packet_size_cache: HashMap::new(),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -281,7 +272,7 @@ mod tests {
             .encode(EncodeArgs {
                 id: 0,
                 encryption: PacketEncryption::None,
-                data: &vec![1, 2, 3],
+                data: &[1, 2, 3],
                 max_packet_size: chunk_size,
                 signer: &NoopAuthenticator,
             })
@@ -317,11 +308,7 @@ mod tests {
         let p = &packets[0];
         assert_eq!(p.id(), id, "ID not properly set on packet");
         assert_eq!(p.index(), 0, "Unexpected index for single packet");
-        assert_eq!(
-            p.is_final(),
-            true,
-            "Single packet not marked as last packet"
-        );
+        assert!(p.is_final(), "Single packet not marked as last packet");
         assert_eq!(p.data(), &data);
     }
 
@@ -357,12 +344,11 @@ mod tests {
         assert_eq!(packets.len(), 2, "Unexpected number of packets");
 
         // Check data quality of first packet
-        let p1 = packets.get(0).unwrap();
+        let p1 = packets.first().unwrap();
         assert_eq!(p1.id(), id, "ID not properly set on first packet");
         assert_eq!(p1.index(), 0, "First packet not marked with index 0");
-        assert_eq!(
-            p1.is_final(),
-            false,
+        assert!(
+            !p1.is_final(),
             "Non-final packet unexpectedly marked as last"
         );
         assert_eq!(&p1.data()[..], &data[0..2]);
@@ -371,7 +357,7 @@ mod tests {
         let p2 = packets.get(1).unwrap();
         assert_eq!(p2.id(), id, "ID not properly set on second packet");
         assert_eq!(p2.index(), 1, "Last packet not marked with correct index");
-        assert_eq!(p2.is_final(), true, "Last packet not marked as final");
+        assert!(p2.is_final(), "Last packet not marked as final");
         assert_eq!(&p2.data()[..], &data[2..]);
     }
 
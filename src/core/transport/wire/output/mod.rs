@@ -119,8 +119,7 @@ mod tests {
     #[test]
     fn output_processor_process_should_return_signed_and_encrypted_serialized_packets(
     ) {
-        use std::convert::TryFrom;
-        let signer = ClosureSigner::new(|_| Digest::try_from([9; 32]).unwrap());
+        let signer = ClosureSigner::new(|_| Digest::from([9; 32]));
         let encrypter = ClosureEncrypter::new(|msg, _| {
             let mut v = Vec::new();
 
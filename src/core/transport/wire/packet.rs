@@ -1,8 +1,9 @@
 use crate::core::transport::auth::Digest;
 use crate::core::transport::crypto::{AssociatedData, Nonce};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy)]
 pub(crate) enum PacketEncryption {
     None,
     Encrypted,
@@ -43,7 +44,7 @@ impl From<Option<Nonce>> for PacketEncryption {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy)]
 pub(crate) enum PacketType {
     /// Represents packets that are not the final in a collection
     NotFinal,
@@ -63,14 +64,11 @@ impl PacketType {
 
     /// Indicates if this is the final packet in a collection
     pub fn is_final(&self) -> bool {
-        match self {
-            Self::Final { .. } => true,
-            _ => false,
-        }
+        matches!(self, Self::Final { .. })
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Copy)]
 pub(crate) struct Metadata {
     /// ID used to collect packets forming a single message
     pub(crate) id: u32,
@@ -85,12 +83,12 @@ pub(crate) struct Metadata {
 
 impl Metadata {
     /// Serializes the metadata to a collection of bytes
-    pub fn to_vec(&self) -> Result<Vec<u8>, serde_cbor::Error> {
+    pub fn to_vec(self) -> Result<Vec<u8>, serde_cbor::Error> {
         serde_cbor::ser::to_vec_packed(&self)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct Packet {
     /// Represents metadata associated with the packet
     metadata: Metadata,
@@ -99,6 +97,7 @@ pub(crate) struct Packet {
     signature: Digest,
 
     #[serde(with = "serde_bytes")]
+    #[schemars(with = "Vec<u8>")]
     /// Represents the actual data being transmitted
     data: Vec<u8>,
 }
@@ -159,4 +158,17 @@ impl Packet {
     pub fn from_slice(slice: &[u8]) -> Result<Self, serde_cbor::Error> {
         serde_cbor::from_slice(slice)
     }
+
+    /// Determines the length in bytes of the packet at the start of the
+    /// slice, or none if the slice ends before the packet does
+    pub fn peek_len(slice: &[u8]) -> Result<Option<usize>, serde_cbor::Error> {
+        let mut iter = serde_cbor::Deserializer::from_slice(slice)
+            .into_iter::<serde::de::IgnoredAny>();
+        match iter.next() {
+            Some(Ok(_)) => Ok(Some(iter.byte_offset())),
+            Some(Err(x)) if x.is_eof() => Ok(None),
+            Some(Err(x)) => Err(x),
+            None => Ok(None),
+        }
+    }
 }
@@ -0,0 +1,202 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Number of buffers returned to a pool between checks of whether it holds
+/// more idle buffers than recent demand calls for
+const SHRINK_INTERVAL: usize = 64;
+
+/// Counts of how a pool has satisfied requests for buffers
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Buffers that had to be allocated because none were idle
+    pub allocated: u64,
+
+    /// Buffers handed out again after being returned to the pool
+    pub reused: u64,
+
+    /// Idle buffers dropped when shrinking the pool
+    pub discarded: u64,
+}
+
+/// Pool of fixed-size buffers that inbound wires read into, reusing them
+/// across reads rather than allocating a new buffer for each one
+///
+/// Clones share the same buffers, so a single pool can serve every
+/// connection of a server. The pool keeps as many idle buffers as were in
+/// use at once since it last shrank, dropping the rest, so that a burst of
+/// connections does not pin memory once it has passed.
+#[derive(Clone, Debug)]
+pub struct BufferPool {
+    buffer_size: usize,
+    state: Arc<Mutex<PoolState>>,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    idle: Vec<Box<[u8]>>,
+
+    /// Buffers currently handed out
+    in_use: usize,
+
+    /// Most buffers handed out at once since the pool last shrank
+    high_water: usize,
+
+    /// Buffers returned since the pool last shrank
+    returned: usize,
+
+    stats: BufferPoolStats,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            buffer_size,
+            state: Arc::new(Mutex::new(PoolState::default())),
+        }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Number of buffers waiting in the pool to be reused
+    pub fn idle(&self) -> usize {
+        self.lock().idle.len()
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        self.lock().stats
+    }
+
+    /// Takes an idle buffer from the pool or allocates a new one, which is
+    /// returned to the pool once dropped
+    ///
+    /// A reused buffer still holds whatever was last read into it
+    pub fn take(&self) -> PooledBuffer {
+        let mut state = self.lock();
+        state.in_use += 1;
+        state.high_water = state.high_water.max(state.in_use);
+
+        let buf = match state.idle.pop() {
+            Some(buf) => {
+                state.stats.reused += 1;
+                buf
+            }
+            None => {
+                state.stats.allocated += 1;
+                vec![0; self.buffer_size].into_boxed_slice()
+            }
+        };
+
+        PooledBuffer {
+            buf: Some(buf),
+            pool: self.clone(),
+        }
+    }
+
+    fn put(&self, buf: Box<[u8]>) {
+        let mut state = self.lock();
+        state.in_use -= 1;
+        state.idle.push(buf);
+        state.returned += 1;
+
+        if state.returned >= SHRINK_INTERVAL {
+            let keep = state.high_water.saturating_sub(state.in_use);
+            if state.idle.len() > keep {
+                state.stats.discarded += (state.idle.len() - keep) as u64;
+                state.idle.truncate(keep);
+                state.idle.shrink_to_fit();
+            }
+
+            state.returned = 0;
+            state.high_water = state.in_use;
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        match self.state.lock() {
+            Ok(x) => x,
+            Err(x) => x.into_inner(),
+        }
+    }
+}
+
+/// Buffer taken from a `BufferPool`, which is returned to it when dropped
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buf: Option<Box<[u8]>>,
+    pool: BufferPool,
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buf.as_ref().expect("Buffer taken before drop")
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buf.as_mut().expect("Buffer taken before drop")
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.pool.put(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_should_reuse_returned_buffers() {
+        let pool = BufferPool::new(16);
+
+        for _ in 0..10 {
+            let buf = pool.take();
+            assert_eq!(buf.len(), 16);
+        }
+
+        assert_eq!(pool.idle(), 1);
+        assert_eq!(
+            pool.stats(),
+            BufferPoolStats {
+                allocated: 1,
+                reused: 9,
+                discarded: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn put_should_shrink_idle_buffers_to_recent_high_water_mark() {
+        let pool = BufferPool::new(16);
+
+        // A burst of concurrent reads leaves many buffers idle
+        let burst: Vec<PooledBuffer> = (0..8).map(|_| pool.take()).collect();
+        drop(burst);
+        assert_eq!(pool.idle(), 8);
+
+        // Once demand settles, the pool only keeps what was recently needed,
+        // which covers the burst until the next shrink has passed
+        for _ in 0..SHRINK_INTERVAL / 2 {
+            let _a = pool.take();
+            let _b = pool.take();
+        }
+        assert_eq!(pool.idle(), 8);
+
+        for _ in 0..SHRINK_INTERVAL / 2 {
+            let _a = pool.take();
+            let _b = pool.take();
+        }
+        assert_eq!(pool.idle(), 2);
+        assert_eq!(pool.stats().allocated, 8);
+        assert_eq!(pool.stats().discarded, 6);
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{
+    de::{self, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
+};
+use std::fmt;
+
+/// Order of the members of a type, which packed CBOR refers to by index
+/// rather than by name
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Layout {
+    Struct { fields: Vec<&'static str> },
+    Enum { variants: Vec<&'static str> },
+}
+
+/// Name and layout of a type, captured from its `Deserialize` impl
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NamedLayout {
+    pub name: &'static str,
+    pub layout: Layout,
+}
+
+/// Captures the name and layout of `T` by asking it to deserialize itself
+/// and stopping at the point where it reveals its fields or variants
+///
+/// Yields none if `T` is neither a struct nor an enum with named members
+pub fn layout_of<T>() -> Option<NamedLayout>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match T::deserialize(LayoutDeserializer) {
+        Err(Captured::Layout(layout)) => Some(layout),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+enum Captured {
+    Layout(NamedLayout),
+    Other(String),
+}
+
+impl fmt::Display for Captured {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Layout(layout) => write!(f, "Captured {}", layout.name),
+            Self::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Captured {}
+
+impl de::Error for Captured {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Other(msg.to_string())
+    }
+}
+
+/// Deserializer that never produces a value, only capturing what a type
+/// reveals about itself when it starts deserializing
+struct LayoutDeserializer;
+
+impl<'de> Deserializer<'de> for LayoutDeserializer {
+    type Error = Captured;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(Captured::Other(String::from("Not a struct or enum")))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(Captured::Layout(NamedLayout {
+            name,
+            layout: Layout::Struct {
+                fields: fields.to_vec(),
+            },
+        }))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(Captured::Layout(NamedLayout {
+            name,
+            layout: Layout::Enum {
+                variants: variants.to_vec(),
+            },
+        }))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Example {
+        b: u8,
+        a: u8,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum ExampleEnum {
+        Second,
+        First(u8),
+    }
+
+    #[test]
+    fn layout_of_should_capture_members_in_declaration_order() {
+        assert_eq!(
+            layout_of::<Example>(),
+            Some(NamedLayout {
+                name: "Example",
+                layout: Layout::Struct {
+                    fields: vec!["b", "a"]
+                },
+            })
+        );
+
+        assert_eq!(
+            layout_of::<ExampleEnum>(),
+            Some(NamedLayout {
+                name: "ExampleEnum",
+                layout: Layout::Enum {
+                    variants: vec!["Second", "First"]
+                },
+            })
+        );
+
+        assert_eq!(layout_of::<u8>(), None);
+    }
+}
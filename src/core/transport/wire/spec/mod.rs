@@ -0,0 +1,274 @@
+//! Machine-readable description of the bytes exchanged between a client and
+//! server, generated from the types that are actually sent so that it cannot
+//! drift from the implementation
+
+mod layout;
+
+pub use layout::Layout;
+
+use super::packet::{Metadata, Packet, PacketEncryption, PacketType};
+use crate::core::transport::{
+    auth::Digest, crypto::Nonce, net::NetTransmission,
+};
+use crate::core::{Header, Msg, MsgSignature};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema, Map,
+};
+use serde::{Deserialize, Serialize};
+
+/// Steps taken to turn a msg into the bytes sent over a transport, which are
+/// reversed by the receiving side
+const PIPELINE: &[&str] = &[
+    "Encode the msg as CBOR with fields keyed by name (see type Msg); if \
+     the msg is compressed, it is instead encoded as a CBOR byte string \
+     holding the raw DEFLATE compression of the encoded msg",
+    "Encrypt the encoded msg as a whole using the cipher of the connection, \
+     producing the nonce (if any) that it was encrypted with",
+    "Split the encrypted bytes into packets sharing a random id and numbered \
+     by index starting at 0, where only the last packet is final and holds \
+     the encryption (and nonce) of the msg",
+    "Sign each packet using the digest of the connection over the packed \
+     CBOR of its metadata followed by its data",
+    "Encode each packet as packed CBOR, where struct fields are keyed by \
+     their position in `fields` and enum variants are identified by their \
+     position in `variants`, with variants holding data encoded as a map of \
+     that position to the data",
+    "Send the packets using the framing of the transport; the receiver \
+     verifies each packet, joins the data of packets sharing an id in index \
+     order once all of them have arrived, then decrypts and decodes the msg",
+];
+
+/// How a type is encoded as CBOR
+#[derive(Serialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Struct fields are keyed by name and enum variants by name
+    Cbor,
+
+    /// Struct fields are keyed by position and enum variants by position
+    CborPacked,
+}
+
+/// Describes a single type that appears on the wire
+#[derive(Serialize, Clone, Debug)]
+pub struct WireType {
+    /// Name of the type as referenced by schemas
+    pub name: &'static str,
+
+    pub encoding: Encoding,
+
+    /// Order of the fields or variants of the type
+    #[serde(flatten)]
+    pub layout: Layout,
+
+    /// JSON schema of the type, whose referenced schemas are found in the
+    /// definitions of the spec
+    pub schema: Schema,
+}
+
+/// Describes how packets are sent over a transport
+#[derive(Serialize, Clone, Debug)]
+pub struct TransmissionSpec {
+    pub name: &'static str,
+
+    /// How the boundaries between packets are preserved
+    pub framing: &'static str,
+
+    /// Maximum size of a single encoded packet in bytes
+    pub packet_size: usize,
+
+    /// Maximum size of an encoded msg in bytes, prior to being encrypted
+    /// and split into packets
+    pub max_msg_size: usize,
+}
+
+/// Describes a cipher that msgs can be encrypted with
+#[derive(Serialize, Clone, Debug)]
+pub struct CipherSpec {
+    pub name: &'static str,
+    pub key_bytes: usize,
+
+    /// Size of the nonce generated for each msg, found in its final packet
+    pub nonce_bytes: usize,
+}
+
+/// Describes a digest that packets can be signed with
+#[derive(Serialize, Clone, Debug)]
+pub struct DigestSpec {
+    pub name: &'static str,
+
+    /// Variant of the packet's signature holding the digest
+    pub variant: &'static str,
+    pub digest_bytes: usize,
+}
+
+/// Complete description of the wire protocol
+#[derive(Serialize, Clone, Debug)]
+pub struct WireSpec {
+    /// Version of the implementation that generated the spec
+    pub version: &'static str,
+
+    pub pipeline: Vec<&'static str>,
+    pub transmissions: Vec<TransmissionSpec>,
+    pub ciphers: Vec<CipherSpec>,
+    pub digests: Vec<DigestSpec>,
+    pub types: Vec<WireType>,
+
+    /// Schemas referenced by the schemas of types
+    pub definitions: Map<String, Schema>,
+}
+
+impl WireSpec {
+    /// Generates the spec from the types sent over the wire
+    pub fn generate() -> Self {
+        let mut gen = SchemaSettings::draft07().into_generator();
+        let types = vec![
+            wire_type::<Packet>(&mut gen, Encoding::CborPacked),
+            wire_type::<Metadata>(&mut gen, Encoding::CborPacked),
+            wire_type::<PacketType>(&mut gen, Encoding::CborPacked),
+            wire_type::<PacketEncryption>(&mut gen, Encoding::CborPacked),
+            wire_type::<Nonce>(&mut gen, Encoding::CborPacked),
+            wire_type::<Digest>(&mut gen, Encoding::CborPacked),
+            wire_type::<Msg>(&mut gen, Encoding::Cbor),
+            wire_type::<Header>(&mut gen, Encoding::Cbor),
+            wire_type::<MsgSignature>(&mut gen, Encoding::Cbor),
+        ];
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            pipeline: PIPELINE.to_vec(),
+            transmissions: NetTransmission::all()
+                .iter()
+                .map(|t| transmission_spec(*t))
+                .collect(),
+            ciphers: vec![
+                cipher_spec("aes128_gcm", 16, 12),
+                cipher_spec("aes256_gcm", 32, 12),
+                cipher_spec("aes128_gcm_siv", 16, 12),
+                cipher_spec("aes256_gcm_siv", 32, 12),
+                cipher_spec("aes128_siv", 32, 16),
+                cipher_spec("aes256_siv", 64, 16),
+            ],
+            digests: vec![
+                DigestSpec {
+                    name: "none",
+                    variant: "Digest256Bits",
+                    digest_bytes: 32,
+                },
+                DigestSpec {
+                    name: "hmac_sha256",
+                    variant: "Digest256Bits",
+                    digest_bytes: 32,
+                },
+                DigestSpec {
+                    name: "hmac_sha512",
+                    variant: "Digest512Bits",
+                    digest_bytes: 64,
+                },
+            ],
+            types,
+            definitions: gen.definitions().clone(),
+        }
+    }
+}
+
+fn wire_type<T>(gen: &mut SchemaGenerator, encoding: Encoding) -> WireType
+where
+    T: JsonSchema + for<'de> Deserialize<'de>,
+{
+    let layout::NamedLayout { name, layout } =
+        layout::layout_of::<T>().expect("Wire types are structs or enums");
+
+    WireType {
+        name,
+        encoding,
+        layout,
+        schema: gen.subschema_for::<T>(),
+    }
+}
+
+fn transmission_spec(transmission: NetTransmission) -> TransmissionSpec {
+    let (name, framing) = match transmission {
+        NetTransmission::TcpEthernet => ("tcp_ethernet", TCP_FRAMING),
+        NetTransmission::TcpDialup => ("tcp_dialup", TCP_FRAMING),
+        NetTransmission::UdpIpv4 => ("udp_ipv4", UDP_FRAMING),
+        NetTransmission::UdpIpv6 => ("udp_ipv6", UDP_FRAMING),
+    };
+
+    TransmissionSpec {
+        name,
+        framing,
+        packet_size: transmission.size(),
+        max_msg_size: transmission.max_msg_size(),
+    }
+}
+
+const TCP_FRAMING: &str = "Packets are written back to back on the stream; \
+    each is a single CBOR item, so the next packet begins where the \
+    previous one ends";
+
+const UDP_FRAMING: &str = "Each datagram holds exactly one packet";
+
+fn cipher_spec(
+    name: &'static str,
+    key_bytes: usize,
+    nonce_bytes: usize,
+) -> CipherSpec {
+    CipherSpec {
+        name,
+        key_bytes,
+        nonce_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_should_describe_packet_fields_in_encoded_order() {
+        let spec = WireSpec::generate();
+        let packet = spec.types.iter().find(|t| t.name == "Packet").unwrap();
+        assert_eq!(packet.encoding, Encoding::CborPacked);
+        assert_eq!(
+            packet.layout,
+            Layout::Struct {
+                fields: vec!["metadata", "signature", "data"]
+            }
+        );
+
+        // Packed encoding keys the fields by the positions that were
+        // described above
+        let encoded = Packet::new(
+            Metadata {
+                id: 7,
+                index: 0,
+                r#type: PacketType::NotFinal,
+            },
+            Digest::default(),
+            vec![1, 2, 3],
+        )
+        .to_vec()
+        .unwrap();
+        let value: serde_cbor::Value =
+            serde_cbor::from_slice(&encoded).unwrap();
+        match value {
+            serde_cbor::Value::Map(map) => assert_eq!(
+                map.get(&serde_cbor::Value::Integer(2)),
+                Some(&serde_cbor::Value::Bytes(vec![1, 2, 3]))
+            ),
+            x => panic!("Unexpected value: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn generate_should_include_definitions_referenced_by_types() {
+        let spec = WireSpec::generate();
+        let json = serde_json::to_value(&spec).unwrap();
+
+        assert!(json["definitions"].get("Content").is_some());
+        assert!(spec.transmissions.iter().any(|t| t.name == "udp_ipv4"));
+    }
+}
@@ -1,13 +1,30 @@
 use super::{
-    auth, crypto, Authenticator, Bicrypter, Decrypter, Encrypter, InboundWire,
-    InboundWireError, OutboundWire, OutboundWireError, Signer, Verifier, Wire,
+    auth, crypto, packet::Packet, Authenticator, Bicrypter, Decrypter,
+    Encrypter, InboundWire, InboundWireError, InputProcessorError,
+    OutboundWire, OutboundWireError, Signer, Verifier, Wire,
 };
+use bytes::Buf;
+use std::collections::VecDeque;
+use std::io::IoSlice;
 use std::net::SocketAddr;
 use tokio::{
     io::{self, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf},
     net::TcpStream,
 };
 
+/// Inbound and outbound halves of a tcp stream wire whose authenticator and
+/// bicrypter are shared between them
+type ArcSplitTcpStreamWire<A, B> = (
+    TcpStreamInboundWire<
+        auth::split::VerifierHalf<A>,
+        crypto::split::DecrypterHalf<B>,
+    >,
+    TcpStreamOutboundWire<
+        auth::split::SignerHalf<A>,
+        crypto::split::EncrypterHalf<B>,
+    >,
+);
+
 pub struct TcpStreamWire<A, B>
 where
     A: Authenticator,
@@ -35,18 +52,7 @@ where
         }
     }
 
-    pub fn arc_split(
-        self,
-    ) -> (
-        TcpStreamInboundWire<
-            auth::split::VerifierHalf<A>,
-            crypto::split::DecrypterHalf<B>,
-        >,
-        TcpStreamOutboundWire<
-            auth::split::SignerHalf<A>,
-            crypto::split::EncrypterHalf<B>,
-        >,
-    ) {
+    pub fn arc_split(self) -> ArcSplitTcpStreamWire<A, B> {
         let Self {
             wire,
             stream,
@@ -86,6 +92,13 @@ where
     inbound_wire: InboundWire<V, D>,
     stream: ReadHalf<TcpStream>,
     remote_addr: SocketAddr,
+
+    /// Data read that does not yet form a complete packet, as a stream does
+    /// not preserve the boundaries of packets written to it
+    buf: Vec<u8>,
+
+    /// Msgs completed by a prior read that have yet to be returned
+    msgs: VecDeque<Vec<u8>>,
 }
 
 impl<V, D> TcpStreamInboundWire<V, D>
@@ -102,22 +115,82 @@ where
             inbound_wire,
             stream,
             remote_addr,
+            buf: Vec::new(),
+            msgs: VecDeque::new(),
         }
     }
 
+    /// Reads data from the stream, processing every complete packet within
+    /// it and returning a msg once one is complete
+    ///
+    /// A single read can contain many packets, where msgs completed beyond
+    /// the first are returned by the following reads
     pub async fn read(
         &mut self,
     ) -> Result<(Option<Vec<u8>>, SocketAddr), InboundWireError> {
-        let mut buf =
-            vec![0; self.inbound_wire.transmission_size()].into_boxed_slice();
+        if let Some(data) = self.msgs.pop_front() {
+            return Ok((Some(data), self.remote_addr));
+        }
+
+        let transmission_size = self.inbound_wire.transmission_size();
+        let mut buf = self.inbound_wire.take_buffer();
         let size = self
             .stream
             .read(&mut buf)
             .await
             .map_err(InboundWireError::IO)?;
-        let data = self.inbound_wire.process(&buf[..size])?;
 
-        Ok((data, self.remote_addr))
+        // Reading nothing means that the other side closed the stream, after
+        // which every read would return immediately with nothing again
+        if size == 0 {
+            return Err(InboundWireError::IO(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Stream closed by {}", self.remote_addr),
+            )));
+        }
+
+        self.buf.extend_from_slice(&buf[..size]);
+
+        let mut offset = 0;
+        while offset < self.buf.len() {
+            let len = match Packet::peek_len(&self.buf[offset..]) {
+                Ok(Some(len)) => len,
+                Ok(None) => break,
+                Err(x) => {
+                    // Without a valid packet, there is no way to know where
+                    // the next packet begins
+                    self.buf.clear();
+                    return Err(InboundWireError::InputProcessor(
+                        InputProcessorError::EncodePacket(x),
+                    ));
+                }
+            };
+
+            let result =
+                self.inbound_wire.process(&self.buf[offset..offset + len]);
+            offset += len;
+            match result {
+                Ok(Some(data)) => self.msgs.push_back(data),
+                Ok(None) => {}
+                Err(x) => {
+                    self.buf.drain(..offset);
+                    return Err(x);
+                }
+            }
+        }
+        self.buf.drain(..offset);
+
+        // No packet is larger than the transmission size, so anything
+        // larger is not a packet
+        if self.buf.len() > transmission_size {
+            self.buf.clear();
+            return Err(InboundWireError::IO(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Partial packet exceeds transmission size",
+            )));
+        }
+
+        Ok((self.msgs.pop_front(), self.remote_addr))
     }
 }
 
@@ -145,20 +218,132 @@ where
         }
     }
 
+    /// Writes the msg as packets to the stream, flushing once every packet
+    /// has been written
+    ///
+    /// Packets are written together using vectored writes rather than one
+    /// write per packet, which avoids a syscall per packet for msgs that are
+    /// split into many packets
     pub async fn write(&mut self, buf: &[u8]) -> Result<(), OutboundWireError> {
         let data = self.outbound_wire.process(buf)?;
 
-        for packet_bytes in data.iter() {
+        let mut packets = PacketsBuf::new(&data);
+        while packets.has_remaining() {
             let size = self
                 .stream
-                .write(packet_bytes)
+                .write_buf(&mut packets)
                 .await
                 .map_err(OutboundWireError::IO)?;
-            if size < packet_bytes.len() {
+            if size == 0 {
                 return Err(OutboundWireError::IncompleteSend);
             }
         }
 
-        Ok(())
+        self.stream.flush().await.map_err(OutboundWireError::IO)
+    }
+}
+
+/// Packets viewed as one contiguous buffer, so that they can be written to a
+/// stream together
+struct PacketsBuf<'a> {
+    packets: &'a [Vec<u8>],
+
+    /// Index of the packet that has not been completely consumed
+    index: usize,
+
+    /// Bytes consumed of the packet at `index`
+    offset: usize,
+}
+
+impl<'a> PacketsBuf<'a> {
+    fn new(packets: &'a [Vec<u8>]) -> Self {
+        let mut buf = Self {
+            packets,
+            index: 0,
+            offset: 0,
+        };
+        buf.skip_consumed();
+        buf
+    }
+
+    /// Moves past any packets that have been completely consumed
+    fn skip_consumed(&mut self) {
+        while self.index < self.packets.len()
+            && self.offset >= self.packets[self.index].len()
+        {
+            self.index += 1;
+            self.offset = 0;
+        }
+    }
+}
+
+impl<'a> Buf for PacketsBuf<'a> {
+    fn remaining(&self) -> usize {
+        self.packets[self.index..]
+            .iter()
+            .map(Vec::len)
+            .sum::<usize>()
+            - self.offset
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self.packets.get(self.index) {
+            Some(packet) => &packet[self.offset..],
+            None => &[],
+        }
+    }
+
+    fn bytes_vectored<'b>(&'b self, dst: &mut [IoSlice<'b>]) -> usize {
+        let packets = self.packets[self.index..]
+            .iter()
+            .enumerate()
+            .filter(|(_, packet)| !packet.is_empty());
+
+        let mut count = 0;
+        for (slot, (i, packet)) in dst.iter_mut().zip(packets) {
+            let start = if i == 0 { self.offset } else { 0 };
+            *slot = IoSlice::new(&packet[start..]);
+            count += 1;
+        }
+        count
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        while cnt > 0 && self.index < self.packets.len() {
+            let available = self.packets[self.index].len() - self.offset;
+            let consumed = cnt.min(available);
+            self.offset += consumed;
+            cnt -= consumed;
+            self.skip_consumed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_buf_should_view_packets_as_contiguous_bytes() {
+        let packets = vec![vec![1, 2], vec![], vec![3, 4, 5], vec![6]];
+        let mut buf = PacketsBuf::new(&packets);
+        assert_eq!(buf.remaining(), 6);
+
+        let mut slices = [IoSlice::new(&[]); 4];
+        assert_eq!(buf.bytes_vectored(&mut slices), 3);
+        assert_eq!(&*slices[0], &[1, 2]);
+        assert_eq!(&*slices[1], &[3, 4, 5]);
+
+        buf.advance(3);
+        assert_eq!(buf.remaining(), 3);
+        assert_eq!(buf.bytes(), &[4, 5]);
+
+        let mut slices = [IoSlice::new(&[]); 1];
+        assert_eq!(buf.bytes_vectored(&mut slices), 1);
+        assert_eq!(&*slices[0], &[4, 5]);
+
+        buf.advance(3);
+        assert!(!buf.has_remaining());
+        assert!(buf.bytes().is_empty());
     }
 }
@@ -8,6 +8,19 @@ use tokio::net::{
     UdpSocket,
 };
 
+/// Inbound and outbound halves of a udp socket wire whose authenticator and
+/// bicrypter are shared between them
+type ArcSplitUdpSocketWire<A, B> = (
+    UdpSocketInboundWire<
+        auth::split::VerifierHalf<A>,
+        crypto::split::DecrypterHalf<B>,
+    >,
+    UdpSocketOutboundWire<
+        auth::split::SignerHalf<A>,
+        crypto::split::EncrypterHalf<B>,
+    >,
+);
+
 pub struct UdpSocketWire<A, B>
 where
     A: Authenticator,
@@ -26,18 +39,7 @@ where
         Self { wire, socket }
     }
 
-    pub fn arc_split(
-        self,
-    ) -> (
-        UdpSocketInboundWire<
-            auth::split::VerifierHalf<A>,
-            crypto::split::DecrypterHalf<B>,
-        >,
-        UdpSocketOutboundWire<
-            auth::split::SignerHalf<A>,
-            crypto::split::EncrypterHalf<B>,
-        >,
-    ) {
+    pub fn arc_split(self) -> ArcSplitUdpSocketWire<A, B> {
         let Self { wire, socket } = self;
         let (r, s) = socket.split();
         let (iw, ow) = wire.arc_split();
@@ -85,14 +87,13 @@ where
     pub async fn read(
         &mut self,
     ) -> Result<(Option<Vec<u8>>, SocketAddr), InboundWireError> {
-        let mut buf =
-            vec![0; self.inbound_wire.transmission_size()].into_boxed_slice();
+        let mut buf = self.inbound_wire.take_buffer();
         let (size, addr) = self
             .socket
             .recv_from(&mut buf)
             .await
             .map_err(InboundWireError::IO)?;
-        let data = self.inbound_wire.process(&buf[..size])?;
+        let data = self.inbound_wire.process_from(&buf[..size], addr)?;
 
         Ok((data, addr))
     }
@@ -5,5 +5,9 @@ pub mod cli;
 /// Contains necessary structures and code for client/server interaction
 pub mod core;
 
+/// Contains helpers to spin up a real server and connected client for tests
+#[cfg(feature = "test-util")]
+pub mod testkit;
+
 /// Contains miscellaneous code used throughout the project
 pub mod utils;
@@ -1,13 +1,20 @@
 use clap::derive::Clap;
-use over_there;
-use tokio::runtime::Runtime;
+use tokio::runtime::{Builder, Runtime};
 
 fn main() {
     env_logger::init();
     let opts = over_there::cli::Opts::parse();
 
-    let mut rt = Runtime::new().expect("Failed to start runtime");
+    // A client mostly waits on the server, so it avoids the startup cost of
+    // a pool of worker threads by running everything on the main thread
+    let rt = if opts.is_client() {
+        Builder::new().basic_scheduler().enable_all().build()
+    } else {
+        Runtime::new()
+    };
+    let mut rt = rt.expect("Failed to start runtime");
     if let Err(x) = rt.block_on(over_there::cli::run(opts)) {
         eprintln!("{}", x);
+        std::process::exit(over_there::cli::error_code(&*x).exit_code());
     }
 }
@@ -0,0 +1,357 @@
+use crate::core::{
+    self,
+    transport::{
+        auth::{identity::IdentityKey, Authenticator, Sha256Authenticator},
+        crypto::{self, Aes256GcmBicrypter, Bicrypter},
+    },
+    AskError, ClientBuilder, ConnectedClient, ListeningServer, Relay, Reply,
+    ReplyError, Request, ServerBuilder, SignaturePolicy, Transport,
+};
+use log::debug;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tempfile::TempDir;
+
+/// Default time a test bench's client waits for a reply before failing
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// Key used to sign msgs when no authenticator is provided
+pub const DEFAULT_SIGN_KEY: &[u8] = b"my signature key";
+
+/// Transport used between the client and server of a test bench
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TestTransport {
+    Tcp,
+    Udp,
+}
+
+/// Initializes logging for tests, doing nothing if already initialized
+pub fn init_logger() {
+    let _ = env_logger::builder()
+        .is_test(true)
+        .filter_level(log::LevelFilter::Debug)
+        .try_init();
+}
+
+/// Temporary directory that is removed once dropped, used as the root of
+/// any files a test manipulates through the server
+#[derive(Debug)]
+pub struct TempRoot(TempDir);
+
+impl TempRoot {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self(tempfile::tempdir()?))
+    }
+
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// Joins `rel` onto the root
+    pub fn join(&self, rel: impl AsRef<Path>) -> PathBuf {
+        self.path().join(rel)
+    }
+
+    /// Joins `rel` onto the root as a string, which is how paths are
+    /// provided within requests
+    pub fn join_string(&self, rel: impl AsRef<Path>) -> String {
+        self.join(rel).to_string_lossy().to_string()
+    }
+
+    /// Writes `contents` to `rel` within the root, creating any missing
+    /// parent directories, and returns the full path
+    pub fn write(
+        &self,
+        rel: impl AsRef<Path>,
+        contents: impl AsRef<[u8]>,
+    ) -> io::Result<PathBuf> {
+        let path = self.join(rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    /// Reads the contents of `rel` within the root
+    pub fn read(&self, rel: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        fs::read(self.join(rel))
+    }
+
+    /// Creates `rel` and any missing parents within the root, returning the
+    /// full path
+    pub fn create_dir(&self, rel: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let path = self.join(rel);
+        fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+}
+
+/// Real server listening on an ephemeral local port alongside a client
+/// connected to it and a temporary root for files
+pub struct TestBench {
+    pub client: ConnectedClient,
+    pub server: ListeningServer,
+    pub root: TempRoot,
+}
+
+impl TestBench {
+    /// Starts a bench over `transport` using the default authenticator,
+    /// bicrypter, and timeout
+    pub async fn start(transport: TestTransport) -> io::Result<Self> {
+        TestBenchBuilder::new(transport).start().await
+    }
+
+    /// Asks the server, panicking if the ask fails or the reply is an error
+    pub async fn ask_ok(&mut self, request: Request) -> Reply {
+        match self.client.ask(request.clone()).await {
+            Ok(Reply::Error(x)) => {
+                panic!("{:?} yielded error: {}", request, x)
+            }
+            Ok(reply) => reply,
+            Err(x) => panic!("{:?} failed: {}", request, x),
+        }
+    }
+
+    /// Asks the server, panicking unless the reply is an error
+    pub async fn ask_err(&mut self, request: Request) -> ReplyError {
+        match self.client.ask(request.clone()).await {
+            Ok(Reply::Error(x)) => x,
+            Err(AskError::Failure { msg, code }) => {
+                ReplyError::with_code(msg, code)
+            }
+            Ok(reply) => {
+                panic!("{:?} unexpectedly yielded {:?}", request, reply)
+            }
+            Err(x) => panic!("{:?} failed: {}", request, x),
+        }
+    }
+}
+
+/// Configures the authenticator, bicrypter, timeout, and identity of a test
+/// bench before starting it
+pub struct TestBenchBuilder<A, B> {
+    transport: TestTransport,
+    authenticator: A,
+    bicrypter: B,
+    timeout: Duration,
+    identity_key: Option<IdentityKey>,
+    pinned_server_keys: Vec<Vec<u8>>,
+    handshake: bool,
+    forwarding: bool,
+    signature_policy: SignaturePolicy,
+}
+
+impl TestBenchBuilder<Sha256Authenticator, Aes256GcmBicrypter> {
+    /// Creates a builder that signs with `DEFAULT_SIGN_KEY` and encrypts
+    /// with a freshly-generated key
+    pub fn new(transport: TestTransport) -> Self {
+        Self {
+            transport,
+            authenticator: Sha256Authenticator::new(DEFAULT_SIGN_KEY),
+            bicrypter: Aes256GcmBicrypter::new(&crypto::key::new_256bit_key()),
+            timeout: DEFAULT_TIMEOUT,
+            identity_key: None,
+            pinned_server_keys: Vec::new(),
+            handshake: false,
+            forwarding: false,
+            signature_policy: SignaturePolicy::default(),
+        }
+    }
+}
+
+impl<A, B> TestBenchBuilder<A, B>
+where
+    A: Authenticator + Send + Sync + Clone + Default + 'static,
+    B: Bicrypter + Send + Sync + Clone + Default + 'static,
+{
+    pub fn authenticator<A2>(self, authenticator: A2) -> TestBenchBuilder<A2, B>
+    where
+        A2: Authenticator + Send + Sync + Clone + Default + 'static,
+    {
+        TestBenchBuilder {
+            transport: self.transport,
+            authenticator,
+            bicrypter: self.bicrypter,
+            timeout: self.timeout,
+            identity_key: self.identity_key,
+            pinned_server_keys: self.pinned_server_keys,
+            handshake: self.handshake,
+            forwarding: self.forwarding,
+            signature_policy: self.signature_policy,
+        }
+    }
+
+    pub fn bicrypter<B2>(self, bicrypter: B2) -> TestBenchBuilder<A, B2>
+    where
+        B2: Bicrypter + Send + Sync + Clone + Default + 'static,
+    {
+        TestBenchBuilder {
+            transport: self.transport,
+            authenticator: self.authenticator,
+            bicrypter,
+            timeout: self.timeout,
+            identity_key: self.identity_key,
+            pinned_server_keys: self.pinned_server_keys,
+            handshake: self.handshake,
+            forwarding: self.forwarding,
+            signature_policy: self.signature_policy,
+        }
+    }
+
+    /// Time the client waits for a reply before failing
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Key the server uses to prove its identity
+    pub fn identity_key(mut self, key: IdentityKey) -> Self {
+        self.identity_key = Some(key);
+        self
+    }
+
+    /// Public identity keys the client requires the server to prove it holds
+    pub fn pinned_server_keys(mut self, keys: Vec<Vec<u8>>) -> Self {
+        self.pinned_server_keys = keys;
+        self
+    }
+
+    /// Whether the client negotiates a key for its session with the server
+    pub fn handshake(mut self, handshake: bool) -> Self {
+        self.handshake = handshake;
+        self
+    }
+
+    /// Whether the server relays requests forwarded through it
+    pub fn forwarding(mut self, forwarding: bool) -> Self {
+        self.forwarding = forwarding;
+        self
+    }
+
+    /// Policy the server applies to the signatures of msgs
+    pub fn signature_policy(mut self, policy: SignaturePolicy) -> Self {
+        self.signature_policy = policy;
+        self
+    }
+
+    /// Starts the server, storing jobs and logs within the temporary root,
+    /// and connects the client to it
+    pub async fn start(self) -> io::Result<TestBench> {
+        init_logger();
+
+        let root = TempRoot::new()?;
+        let addrs = core::net::make_local_ipv4_addr_list();
+        let mut server = ServerBuilder::default();
+        server
+            .authenticator(self.authenticator.clone())
+            .bicrypter(self.bicrypter.clone())
+            .transport(match self.transport {
+                TestTransport::Tcp => Transport::Tcp(addrs),
+                TestTransport::Udp => Transport::Udp(addrs),
+            })
+            .jobs_dir(root.join("jobs"))
+            .logs_dir(root.join("logs"))
+            .signature_policy(self.signature_policy);
+        if let Some(key) = self.identity_key {
+            server.identity_key(key);
+        }
+        if self.forwarding {
+            server.relay(Relay::from_client(
+                ClientBuilder::default()
+                    .authenticator(self.authenticator.clone())
+                    .bicrypter(self.bicrypter.clone())
+                    .transport(match self.transport {
+                        TestTransport::Tcp => Transport::Tcp(Vec::new()),
+                        TestTransport::Udp => Transport::Udp(Vec::new()),
+                    })
+                    .build()
+                    .map_err(|x| {
+                        io::Error::new(io::ErrorKind::InvalidInput, x)
+                    })?,
+            ));
+        }
+        let server = server
+            .build()
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?;
+        let server = match self.transport {
+            TestTransport::Tcp => server.cloneable_listen().await?,
+            TestTransport::Udp => server.listen().await?,
+        };
+        debug!("{:?} Server listening: {}", self.transport, server.addr());
+
+        let mut client = ClientBuilder::default()
+            .authenticator(self.authenticator)
+            .bicrypter(self.bicrypter)
+            .transport(match self.transport {
+                TestTransport::Tcp => Transport::Tcp(vec![server.addr()]),
+                TestTransport::Udp => Transport::Udp(vec![server.addr()]),
+            })
+            .pinned_server_keys(self.pinned_server_keys)
+            .handshake(self.handshake)
+            .build()
+            .map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?
+            .connect()
+            .await?;
+        debug!(
+            "{:?} Client connected: {}",
+            self.transport,
+            client.remote_addr()
+        );
+
+        // Ensure that we fail after the provided timeout
+        client.timeout = self.timeout;
+
+        Ok(TestBench {
+            client,
+            server,
+            root,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::request::RemoveDirArgs;
+
+    #[tokio::test]
+    async fn ask_should_reach_server_over_tcp_and_udp() {
+        for transport in &[TestTransport::Tcp, TestTransport::Udp] {
+            let mut bench = TestBench::start(*transport).await.unwrap();
+            assert_eq!(
+                bench.ask_ok(Request::Heartbeat).await,
+                Reply::Heartbeat
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn ask_err_should_yield_reply_error() {
+        let mut bench = TestBench::start(TestTransport::Tcp).await.unwrap();
+        let path = bench.root.join_string("missing");
+
+        match bench
+            .ask_err(Request::RemoveDir(RemoveDirArgs {
+                path: path.into(),
+                non_empty: false,
+            }))
+            .await
+        {
+            ReplyError::Io(_) => {}
+            x => panic!("Unexpected error: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn temp_root_should_write_and_read_nested_files() {
+        let root = TempRoot::new().unwrap();
+        let path = root.write("a/b/c.txt", b"abc").unwrap();
+
+        assert!(path.starts_with(root.path()));
+        assert_eq!(root.read("a/b/c.txt").unwrap(), b"abc");
+    }
+}
@@ -0,0 +1,417 @@
+use super::DEFAULT_DELIMITER;
+use futures::{ready, Sink, Stream};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Size of the chunks read at a time from the underlying reader
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Stream of frames read from an async reader, where each frame is the data
+/// found between delimiters with the delimiter itself removed
+///
+/// A frame larger than the max frame size yields an `InvalidData` error
+/// rather than being buffered, after which the rest of that frame is skipped
+/// and reading resumes with the frame that follows it. Data left over once
+/// the reader is exhausted is yielded as a final frame.
+pub struct AsyncDelimiterReader<R> {
+    inner: R,
+    delimiter: Vec<u8>,
+    max_frame_size: usize,
+
+    /// Data read but not yet yielded as a frame
+    buf: Vec<u8>,
+
+    /// Portion of buf already searched without finding a delimiter
+    searched: usize,
+
+    /// Whether the remainder of a frame that was too large is being skipped
+    discarding: bool,
+
+    chunk: Box<[u8]>,
+    eof: bool,
+}
+
+impl<R> AsyncDelimiterReader<R> {
+    /// Creates a reader of frames separated by `delimiter`, which must not
+    /// be empty
+    pub fn new_with_delimiter(
+        inner: R,
+        max_frame_size: usize,
+        delimiter: &[u8],
+    ) -> Self {
+        assert!(!delimiter.is_empty(), "Delimiter cannot be empty");
+        Self {
+            inner,
+            delimiter: delimiter.to_vec(),
+            max_frame_size,
+            buf: Vec::new(),
+            searched: 0,
+            discarding: false,
+            chunk: vec![0; READ_CHUNK_SIZE].into_boxed_slice(),
+            eof: false,
+        }
+    }
+
+    pub fn new(inner: R, max_frame_size: usize) -> Self {
+        Self::new_with_delimiter(inner, max_frame_size, DEFAULT_DELIMITER)
+    }
+
+    pub fn delimiter(&self) -> &[u8] {
+        &self.delimiter
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Yields the underlying reader, dropping any data buffered but not yet
+    /// yielded as a frame
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Removes the next complete frame from the buffer if there is one
+    fn next_frame(&mut self) -> Option<Vec<u8>> {
+        let len = self.delimiter.len();
+
+        // Resume just before the end of the last search in case it ended
+        // partway through a delimiter
+        let start = self.searched.saturating_sub(len - 1);
+        match self.buf[start..]
+            .windows(len)
+            .position(|x| x == &self.delimiter[..])
+        {
+            Some(pos) => {
+                let mut frame: Vec<u8> =
+                    self.buf.drain(..start + pos + len).collect();
+                frame.truncate(start + pos);
+                self.searched = 0;
+                Some(frame)
+            }
+            None => {
+                self.searched = self.buf.len();
+                None
+            }
+        }
+    }
+
+    fn frame_too_large(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame exceeds max size of {} bytes", self.max_frame_size),
+        )
+    }
+
+    /// Drops buffered data that cannot be part of a delimiter, keeping only
+    /// what might be the start of one
+    fn discard(&mut self) {
+        let keep = self.delimiter.len() - 1;
+        if self.buf.len() > keep {
+            self.buf.drain(..self.buf.len() - keep);
+            self.searched = self.buf.len();
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncDelimiterReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(frame) = this.next_frame() {
+                if this.discarding {
+                    this.discarding = false;
+                    continue;
+                } else if frame.len() > this.max_frame_size {
+                    return Poll::Ready(Some(Err(this.frame_too_large())));
+                }
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            if this.discarding {
+                this.discard();
+            } else if this.buf.len()
+                >= this.max_frame_size + this.delimiter.len()
+            {
+                this.discarding = true;
+                this.discard();
+                return Poll::Ready(Some(Err(this.frame_too_large())));
+            }
+
+            if this.eof {
+                let frame = std::mem::take(&mut this.buf);
+                this.searched = 0;
+                if frame.is_empty() || std::mem::take(&mut this.discarding) {
+                    return Poll::Ready(None);
+                } else if frame.len() > this.max_frame_size {
+                    return Poll::Ready(Some(Err(this.frame_too_large())));
+                }
+                return Poll::Ready(Some(Ok(frame)));
+            }
+
+            let n = ready!(
+                Pin::new(&mut this.inner).poll_read(cx, &mut this.chunk)
+            )?;
+            if n == 0 {
+                this.eof = true;
+            } else {
+                this.buf.extend_from_slice(&this.chunk[..n]);
+            }
+        }
+    }
+}
+
+/// Sink of frames written to an async writer, each followed by a delimiter
+///
+/// Frames larger than the max frame size or containing the delimiter are
+/// rejected with an `InvalidInput` error, as they would not be read back as
+/// the same frame.
+pub struct AsyncDelimiterWriter<W> {
+    inner: W,
+    delimiter: Vec<u8>,
+    max_frame_size: usize,
+
+    /// Delimited frames not yet written to the underlying writer
+    buf: Vec<u8>,
+
+    /// Portion of buf already written
+    written: usize,
+}
+
+impl<W> AsyncDelimiterWriter<W> {
+    /// Creates a writer of frames separated by `delimiter`, which must not
+    /// be empty
+    pub fn new_with_delimiter(
+        inner: W,
+        max_frame_size: usize,
+        delimiter: &[u8],
+    ) -> Self {
+        assert!(!delimiter.is_empty(), "Delimiter cannot be empty");
+        Self {
+            inner,
+            delimiter: delimiter.to_vec(),
+            max_frame_size,
+            buf: Vec::new(),
+            written: 0,
+        }
+    }
+
+    pub fn new(inner: W, max_frame_size: usize) -> Self {
+        Self::new_with_delimiter(inner, max_frame_size, DEFAULT_DELIMITER)
+    }
+
+    pub fn delimiter(&self) -> &[u8] {
+        &self.delimiter
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Yields the underlying writer, dropping any frames not yet flushed
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncDelimiterWriter<W> {
+    /// Writes out all buffered frames without flushing the underlying writer
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.buf.len() {
+            let n = ready!(Pin::new(&mut self.inner)
+                .poll_write(cx, &self.buf[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(
+                    io::ErrorKind::WriteZero,
+                )));
+            }
+            self.written += n;
+        }
+
+        self.buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W, T> Sink<T> for AsyncDelimiterWriter<W>
+where
+    W: AsyncWrite + Unpin,
+    T: AsRef<[u8]>,
+{
+    type Error = io::Error;
+
+    fn poll_ready(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_write_buf(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let frame = item.as_ref();
+
+        if frame.len() > this.max_frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Frame of {} bytes exceeds max size of {} bytes",
+                    frame.len(),
+                    this.max_frame_size
+                ),
+            ));
+        }
+
+        if frame
+            .windows(this.delimiter.len())
+            .any(|x| x == &this.delimiter[..])
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Frame contains delimiter",
+            ));
+        }
+
+        this.buf.extend_from_slice(frame);
+        this.buf.extend_from_slice(&this.delimiter);
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use std::io::Cursor;
+
+    async fn read_all(
+        data: &[u8],
+        max_frame_size: usize,
+    ) -> Vec<io::Result<Vec<u8>>> {
+        AsyncDelimiterReader::new_with_delimiter(
+            Cursor::new(data.to_vec()),
+            max_frame_size,
+            b"</>",
+        )
+        .collect()
+        .await
+    }
+
+    #[tokio::test]
+    async fn reader_should_yield_frames_between_delimiters() {
+        let frames = read_all(b"abc</></>de</>f", 10).await;
+        let frames: Vec<Vec<u8>> =
+            frames.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            frames,
+            vec![b"abc".to_vec(), b"".to_vec(), b"de".to_vec(), b"f".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn reader_should_find_delimiters_split_across_reads() {
+        // Frames larger than a single chunk force the delimiter to straddle
+        // the boundary between reads
+        let frame = vec![7; READ_CHUNK_SIZE - 1];
+        let mut data = frame.clone();
+        data.extend_from_slice(b"</>");
+        data.extend_from_slice(&frame);
+        data.extend_from_slice(b"</>");
+
+        let frames = read_all(&data, READ_CHUNK_SIZE).await;
+        assert_eq!(frames.len(), 2);
+        for x in frames {
+            assert_eq!(x.unwrap(), frame);
+        }
+    }
+
+    #[tokio::test]
+    async fn reader_should_skip_frames_exceeding_max_size() {
+        let frames = read_all(b"ok</>too large</>ok2</>", 4).await;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].as_ref().unwrap(), b"ok");
+        assert_eq!(
+            frames[1].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(frames[2].as_ref().unwrap(), b"ok2");
+
+        // Frames spanning many reads are skipped without being buffered
+        let mut data = vec![7; READ_CHUNK_SIZE * 3];
+        data.extend_from_slice(b"</>ok</>");
+        let frames = read_all(&data, 4).await;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            frames[0].as_ref().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(frames[1].as_ref().unwrap(), b"ok");
+    }
+
+    #[tokio::test]
+    async fn writer_should_append_delimiter_to_each_frame() {
+        let mut writer =
+            AsyncDelimiterWriter::new_with_delimiter(Vec::new(), 4, b"\n");
+        writer.send(b"abc").await.unwrap();
+        writer.send(b"").await.unwrap();
+        writer.send(b"de").await.unwrap();
+
+        assert_eq!(writer.get_ref(), b"abc\n\nde\n");
+    }
+
+    #[tokio::test]
+    async fn writer_should_reject_frames_that_cannot_be_read_back() {
+        let mut writer =
+            AsyncDelimiterWriter::new_with_delimiter(Vec::new(), 4, b"\n");
+
+        let err = writer.send(b"abcde").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = writer.send(b"a\nb").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        assert!(writer.get_ref().is_empty());
+    }
+}
@@ -1,45 +1,137 @@
+use super::TtlValue;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Debug;
+use std::time::Duration;
 
 pub type Callback<T> = dyn FnOnce(&T) + Send;
 
-/// Synchronous manager of one-time callback functions
+/// Callback that can be invoked many times, returning true while it
+/// wants to continue receiving input
+pub type RepeatingCallback<T> = dyn FnMut(&T) -> bool + Send;
+
+/// Synchronous manager of one-time and repeating callback functions
 /// that are allocated on the heap
 pub struct CallbackManager<T> {
     /// Contains callback functions to invoke when a
     /// response is received for a msg with a specific id
-    callbacks: HashMap<u32, Box<Callback<T>>>,
+    callbacks: HashMap<u32, TtlValue<Box<Callback<T>>>>,
+
+    /// Contains callback functions to invoke each time a
+    /// response is received for a msg with a specific id
+    repeating_callbacks: HashMap<u32, TtlValue<Box<RepeatingCallback<T>>>>,
+
+    /// Time a callback can go without being invoked before it is considered
+    /// stale and removed by `remove_expired`
+    ttl: Duration,
+
+    /// Total callbacks that have been removed for being stale
+    expired_count: usize,
 }
 
 impl<T> CallbackManager<T> {
+    /// Default time a callback can go without being invoked before being
+    /// considered stale
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            callbacks: HashMap::default(),
+            repeating_callbacks: HashMap::default(),
+            ttl,
+            expired_count: 0,
+        }
+    }
+
     /// Adds a new callback, associated with the given id
     pub fn add_callback(
         &mut self,
         id: u32,
         callback: impl FnOnce(&T) + Send + 'static,
     ) {
-        self.callbacks.insert(id, Box::new(callback));
+        self.callbacks
+            .insert(id, TtlValue::new(Box::new(callback), self.ttl));
+    }
+
+    /// Adds a new callback, associated with the given id, that is invoked
+    /// for every input until it returns false
+    pub fn add_repeating_callback(
+        &mut self,
+        id: u32,
+        callback: impl FnMut(&T) -> bool + Send + 'static,
+    ) {
+        self.repeating_callbacks
+            .insert(id, TtlValue::new(Box::new(callback), self.ttl));
+    }
+
+    /// Whether a one-time callback is associated with the id
+    pub fn has_callback(&self, id: u32) -> bool {
+        self.callbacks.contains_key(&id)
     }
 
     /// Retrieves the callback with the associated id, but does not invoke it
     pub fn take_callback(&mut self, id: u32) -> Option<Box<Callback<T>>> {
-        self.callbacks.remove(&id)
+        self.callbacks.remove(&id).map(|x| x.value)
     }
 
-    /// Retrieves and invokes the callback with the associated id
+    /// Removes the callback (one-time or repeating) with the associated id
+    /// without invoking it, returning true if a callback was removed
+    pub fn remove_callback(&mut self, id: u32) -> bool {
+        self.callbacks.remove(&id).is_some()
+            || self.repeating_callbacks.remove(&id).is_some()
+    }
+
+    /// Retrieves and invokes the callback with the associated id, falling
+    /// back to a repeating callback that is removed once it is finished
     pub fn invoke_callback(&mut self, id: u32, input: &T) {
         if let Some(callback) = self.take_callback(id) {
             callback(input)
+        } else if let Some(callback) = self.repeating_callbacks.get_mut(&id) {
+            // Each input keeps a repeating callback alive for another ttl
+            callback.touch();
+
+            if !(callback.value)(input) {
+                self.repeating_callbacks.remove(&id);
+            }
         }
     }
+
+    /// Removes all callbacks that have gone longer than the ttl without
+    /// being invoked, returning the total removed
+    pub fn remove_expired(&mut self) -> usize {
+        let before = self.len();
+
+        self.callbacks.retain(|_, c| !c.has_expired());
+        self.repeating_callbacks.retain(|_, c| !c.has_expired());
+
+        let removed = before - self.len();
+        self.expired_count += removed;
+        removed
+    }
+
+    /// Total callbacks (one-time and repeating) awaiting input
+    pub fn len(&self) -> usize {
+        self.callbacks.len() + self.repeating_callbacks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total callbacks removed for being stale over the lifetime of
+    /// the manager
+    pub fn expired_count(&self) -> usize {
+        self.expired_count
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
 }
 
 impl<T> Default for CallbackManager<T> {
     fn default() -> Self {
-        Self {
-            callbacks: HashMap::default(),
-        }
+        Self::new(Self::DEFAULT_TTL)
     }
 }
 
@@ -47,8 +139,73 @@ impl<T> Debug for CallbackManager<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "CallbackManager {{ callbacks: {:?} }}",
+            "CallbackManager {{ callbacks: {:?}, repeating_callbacks: {:?} }}",
             self.callbacks.keys(),
+            self.repeating_callbacks.keys(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn invoke_callback_should_remove_one_time_callback() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut manager = CallbackManager::<u8>::default();
+
+        let c = Arc::clone(&count);
+        manager.add_callback(1, move |_| {
+            c.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(manager.len(), 1);
+
+        manager.invoke_callback(1, &0);
+        manager.invoke_callback(1, &0);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(manager.is_empty(), "Callback not removed after invoking");
+    }
+
+    #[test]
+    fn remove_expired_should_only_remove_stale_callbacks() {
+        let mut manager = CallbackManager::<u8>::new(Duration::from_millis(0));
+        manager.add_callback(1, |_| {});
+        manager.add_repeating_callback(2, |_| true);
+
+        let mut fresh = CallbackManager::<u8>::new(Duration::from_secs(60));
+        fresh.add_callback(1, |_| {});
+
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(manager.remove_expired(), 2);
+        assert!(manager.is_empty(), "Stale callbacks remain");
+        assert_eq!(manager.expired_count(), 2);
+
+        assert_eq!(fresh.remove_expired(), 0);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh.expired_count(), 0);
+    }
+
+    #[test]
+    fn remove_callback_should_remove_without_invoking() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut manager = CallbackManager::<u8>::default();
+
+        let c = Arc::clone(&count);
+        manager.add_repeating_callback(1, move |_| {
+            c.fetch_add(1, Ordering::SeqCst);
+            true
+        });
+
+        assert!(manager.remove_callback(1), "Callback not found");
+        assert!(!manager.remove_callback(1), "Callback removed twice");
+
+        manager.invoke_callback(1, &0);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+}
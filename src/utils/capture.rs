@@ -1,37 +1,302 @@
-use std::sync::RwLock;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::watch;
 
+/// Bounded buffer of items that any number of subscribers read at their own
+/// pace, each through a cursor of its own
+///
+/// Items are kept until every subscriber has read them or, once the buffer
+/// holds more than its capacity, until they are the oldest and make way for
+/// new items; subscribers that fall that far behind skip what was dropped,
+/// which their cursor counts. A subscriber starts from the oldest item still
+/// held, so one that subscribes late still sees recent history.
+#[derive(Debug)]
 pub struct Capture<T> {
-    value: RwLock<Option<T>>,
+    inner: Mutex<Inner<T>>,
+
+    /// Total items ever pushed, broadcast to wake subscribers waiting on
+    /// more items
+    pushed_tx: watch::Sender<u64>,
+    pushed_rx: watch::Receiver<u64>,
 }
 
-impl<T> Capture<T> {
-    pub fn take(&self) -> Option<T> {
-        let mut x = self.value.write().unwrap();
-        x.take()
+#[derive(Debug)]
+struct Inner<T> {
+    buf: VecDeque<T>,
+
+    /// Offset within all items pushed of the start of buf
+    start: u64,
+
+    capacity: usize,
+    closed: bool,
+
+    /// Offset of each cursor by its id
+    cursors: HashMap<usize, u64>,
+    next_cursor_id: usize,
+}
+
+impl<T> Inner<T> {
+    fn end(&self) -> u64 {
+        self.start + self.buf.len() as u64
     }
 
-    pub fn set(&self, value: T) {
-        let mut x = self.value.write().unwrap();
-        *x = Some(value);
+    /// Drops items read by every cursor and then any beyond the capacity
+    fn trim(&mut self) {
+        if let Some(min) = self.cursors.values().min().copied() {
+            let read =
+                min.saturating_sub(self.start).min(self.buf.len() as u64);
+            self.buf.drain(..read as usize);
+            self.start += read;
+        }
+
+        let overflow = self.buf.len().saturating_sub(self.capacity);
+        self.buf.drain(..overflow);
+        self.start += overflow as u64;
     }
 }
 
-impl<T> Default for Capture<T> {
+impl<T: Clone> Capture<T> {
+    /// Creates a capture that holds at most `capacity` items
+    pub fn new(capacity: usize) -> Self {
+        let (pushed_tx, pushed_rx) = watch::channel(0);
+        Self {
+            inner: Mutex::new(Inner {
+                buf: VecDeque::new(),
+                start: 0,
+                capacity,
+                closed: false,
+                cursors: HashMap::new(),
+                next_cursor_id: 0,
+            }),
+            pushed_tx,
+            pushed_rx,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<T>> {
+        self.inner.lock().unwrap()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Total items held that have yet to be read by every subscriber
+    pub fn len(&self) -> usize {
+        self.lock().buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().buf.is_empty()
+    }
+
+    /// Whether no more items will be pushed
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    /// Appends `items`, dropping the oldest items held if that exceeds the
+    /// capacity
+    pub fn push(&self, items: &[T]) {
+        let end = {
+            let mut inner = self.lock();
+            inner.buf.extend(items.iter().cloned());
+            inner.trim();
+            inner.end()
+        };
+        let _ = self.pushed_tx.broadcast(end);
+    }
+
+    /// Marks that no more items will be pushed, letting subscribers know
+    /// once they have read everything held
+    pub fn close(&self) {
+        let end = {
+            let mut inner = self.lock();
+            inner.closed = true;
+            inner.end()
+        };
+        let _ = self.pushed_tx.broadcast(end);
+    }
+
+    /// Creates a cursor that reads from the oldest item still held
+    pub fn subscribe(self: &Arc<Self>) -> CaptureCursor<T> {
+        let mut inner = self.lock();
+        let id = inner.next_cursor_id;
+        let offset = inner.start;
+        inner.next_cursor_id += 1;
+        inner.cursors.insert(id, offset);
+
+        CaptureCursor {
+            capture: Arc::clone(self),
+            id,
+            offset,
+            dropped: 0,
+            pushed: self.pushed_rx.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Default for Capture<T> {
+    /// Creates a capture with no practical limit on the items it holds
     fn default() -> Self {
-        Self::from(None)
+        Self::new(usize::MAX)
     }
 }
 
-impl<T> From<T> for Capture<T> {
-    fn from(x: T) -> Self {
-        Self::from(Some(x))
+/// Position of a single subscriber within a capture
+#[derive(Debug)]
+pub struct CaptureCursor<T> {
+    capture: Arc<Capture<T>>,
+    id: usize,
+    offset: u64,
+    dropped: u64,
+    pushed: watch::Receiver<u64>,
+}
+
+impl<T: Clone> CaptureCursor<T> {
+    /// Offset within all items pushed of the next item to read
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Total items dropped from the capture before this cursor read them
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Items held that this cursor has yet to read
+    pub fn remaining(&self) -> usize {
+        let inner = self.capture.lock();
+        (inner.end() - self.offset.max(inner.start)) as usize
+    }
+
+    /// Whether no more items will be pushed and this cursor has read all
+    /// that are held
+    pub fn is_done(&self) -> bool {
+        let inner = self.capture.lock();
+        inner.closed && self.offset >= inner.end()
+    }
+
+    /// Copies the items held that this cursor has yet to read without
+    /// moving past them, first skipping past any that were dropped
+    pub fn peek(&mut self) -> Vec<T> {
+        let inner = self.capture.lock();
+        if self.offset < inner.start {
+            self.dropped += inner.start - self.offset;
+            self.offset = inner.start;
+        }
+
+        let skip = (self.offset - inner.start) as usize;
+        inner.buf.iter().skip(skip).cloned().collect()
+    }
+
+    /// Moves the cursor to `offset`, never past the last item pushed,
+    /// letting the capture drop items that every cursor has moved past
+    pub fn seek(&mut self, offset: u64) {
+        let mut inner = self.capture.lock();
+        self.offset = offset.min(inner.end());
+        inner.cursors.insert(self.id, self.offset);
+        inner.trim();
+    }
+
+    /// Reads all items that this cursor has yet to read, moving past them
+    pub fn read(&mut self) -> Vec<T> {
+        let items = self.peek();
+        self.seek(self.offset + items.len() as u64);
+        items
+    }
+
+    /// Waits for items to read, yielding them all, or none once the capture
+    /// is closed and this cursor has read everything
+    pub async fn next(&mut self) -> Option<Vec<T>> {
+        loop {
+            let items = self.read();
+            if !items.is_empty() {
+                return Some(items);
+            } else if self.is_done() {
+                return None;
+            }
+
+            // Dropping the capture's sender means nothing more can be pushed
+            self.pushed.recv().await?;
+        }
     }
 }
 
-impl<T> From<Option<T>> for Capture<T> {
-    fn from(x: Option<T>) -> Self {
-        Self {
-            value: RwLock::new(x),
+impl<T> Drop for CaptureCursor<T> {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.capture.inner.lock() {
+            inner.cursors.remove(&self.id);
+            inner.trim();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn cursors_should_read_independently() {
+        let capture = Arc::new(Capture::new(10));
+        let mut a = capture.subscribe();
+        capture.push(&[1, 2, 3]);
+        let mut b = capture.subscribe();
+
+        assert_eq!(a.read(), vec![1, 2, 3]);
+        capture.push(&[4]);
+        assert_eq!(a.read(), vec![4]);
+        assert_eq!(b.read(), vec![1, 2, 3, 4]);
+
+        // Items read by every cursor are no longer held
+        assert!(capture.is_empty());
+    }
+
+    #[test]
+    fn cursors_should_count_items_dropped_before_being_read() {
+        let capture = Arc::new(Capture::new(3));
+        let mut slow = capture.subscribe();
+        let mut fast = capture.subscribe();
+
+        capture.push(&[1, 2]);
+        assert_eq!(fast.read(), vec![1, 2]);
+        capture.push(&[3, 4, 5]);
+        assert_eq!(fast.read(), vec![3, 4, 5]);
+
+        assert_eq!(slow.read(), vec![3, 4, 5]);
+        assert_eq!(slow.dropped(), 2);
+        assert_eq!(slow.offset(), 5);
+        assert_eq!(fast.dropped(), 0);
+    }
+
+    #[test]
+    fn dropping_cursor_should_release_items_it_had_not_read() {
+        let capture = Arc::new(Capture::new(10));
+        let mut a = capture.subscribe();
+        let b = capture.subscribe();
+
+        capture.push(&[1, 2, 3]);
+        a.read();
+        assert_eq!(capture.len(), 3);
+
+        drop(b);
+        assert!(capture.is_empty());
+    }
+
+    #[tokio::test]
+    async fn next_should_wait_for_items_until_closed() {
+        let capture = Arc::new(Capture::new(10));
+        let mut cursor = capture.subscribe();
+
+        let capture_2 = Arc::clone(&capture);
+        tokio::spawn(async move {
+            tokio::time::delay_for(Duration::from_millis(10)).await;
+            capture_2.push(&[1, 2]);
+            capture_2.close();
+        });
+
+        assert_eq!(cursor.next().await, Some(vec![1, 2]));
+        assert_eq!(cursor.next().await, None);
+    }
+}
@@ -37,25 +37,25 @@ mod tests {
     #[test]
     fn is_left_yields_true_if_left() {
         let e = Either::Left::<u32, &str>(123);
-        assert_eq!(e.is_left(), true);
+        assert!(e.is_left());
     }
 
     #[test]
     fn is_left_yields_false_if_right() {
         let e = Either::Right::<u32, &str>("123");
-        assert_eq!(e.is_left(), false);
+        assert!(!e.is_left());
     }
 
     #[test]
     fn is_right_yields_true_if_right() {
         let e = Either::Right::<u32, &str>("123");
-        assert_eq!(e.is_right(), true);
+        assert!(e.is_right());
     }
 
     #[test]
     fn is_right_yields_false_if_left() {
         let e = Either::Left::<u32, &str>(123);
-        assert_eq!(e.is_right(), false);
+        assert!(!e.is_right());
     }
 
     #[test]
@@ -1,15 +1,23 @@
+mod async_delimiter;
 mod callback;
 mod capture;
+mod clock;
 mod delay;
 mod delimiter;
 mod either;
 pub mod exec;
 pub mod serializers;
+mod task_tracker;
 mod ttl;
+mod ttl_map;
 
+pub use async_delimiter::{AsyncDelimiterReader, AsyncDelimiterWriter};
 pub use callback::CallbackManager;
-pub use capture::Capture;
+pub use capture::{Capture, CaptureCursor};
+pub use clock::now_micros;
 pub use delay::Delay;
 pub use delimiter::{DelimiterReader, DelimiterWriter, DEFAULT_DELIMITER};
 pub use either::Either;
+pub use task_tracker::{TaskGuard, TaskTracker};
 pub use ttl::{EmptyTtlValue, TtlValue};
+pub use ttl_map::{Eviction, EvictionCallback, TtlMap};
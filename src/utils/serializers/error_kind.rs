@@ -7,7 +7,7 @@ use std::io;
 pub fn serialize<S>(
     kind: &io::ErrorKind,
     serializer: S,
-) -> serde::export::Result<S::Ok, S::Error>
+) -> std::result::Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
@@ -158,7 +158,7 @@ where
 // deserializer for io::Errorkind, originally auto-generated by serde-derive
 pub fn deserialize<'de, D>(
     deserializer: D,
-) -> serde::export::Result<io::ErrorKind, D::Error>
+) -> std::result::Result<io::ErrorKind, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -188,37 +188,34 @@ where
         type Value = Field;
         fn expecting(
             &self,
-            formatter: &mut serde::export::Formatter,
-        ) -> serde::export::fmt::Result {
-            serde::export::Formatter::write_str(formatter, "variant identifier")
+            formatter: &mut std::fmt::Formatter,
+        ) -> std::fmt::Result {
+            std::fmt::Formatter::write_str(formatter, "variant identifier")
         }
-        fn visit_u64<E>(
-            self,
-            value: u64,
-        ) -> serde::export::Result<Self::Value, E>
+        fn visit_u64<E>(self, value: u64) -> std::result::Result<Self::Value, E>
         where
             E: serde::de::Error,
         {
             match value {
-                0u64 => serde::export::Ok(Field::field0),
-                1u64 => serde::export::Ok(Field::field1),
-                2u64 => serde::export::Ok(Field::field2),
-                3u64 => serde::export::Ok(Field::field3),
-                4u64 => serde::export::Ok(Field::field4),
-                5u64 => serde::export::Ok(Field::field5),
-                6u64 => serde::export::Ok(Field::field6),
-                7u64 => serde::export::Ok(Field::field7),
-                8u64 => serde::export::Ok(Field::field8),
-                9u64 => serde::export::Ok(Field::field9),
-                10u64 => serde::export::Ok(Field::field10),
-                11u64 => serde::export::Ok(Field::field11),
-                12u64 => serde::export::Ok(Field::field12),
-                13u64 => serde::export::Ok(Field::field13),
-                14u64 => serde::export::Ok(Field::field14),
-                15u64 => serde::export::Ok(Field::field15),
-                16u64 => serde::export::Ok(Field::field16),
-                17u64 => serde::export::Ok(Field::field17),
-                _ => serde::export::Err(serde::de::Error::invalid_value(
+                0u64 => Ok(Field::field0),
+                1u64 => Ok(Field::field1),
+                2u64 => Ok(Field::field2),
+                3u64 => Ok(Field::field3),
+                4u64 => Ok(Field::field4),
+                5u64 => Ok(Field::field5),
+                6u64 => Ok(Field::field6),
+                7u64 => Ok(Field::field7),
+                8u64 => Ok(Field::field8),
+                9u64 => Ok(Field::field9),
+                10u64 => Ok(Field::field10),
+                11u64 => Ok(Field::field11),
+                12u64 => Ok(Field::field12),
+                13u64 => Ok(Field::field13),
+                14u64 => Ok(Field::field14),
+                15u64 => Ok(Field::field15),
+                16u64 => Ok(Field::field16),
+                17u64 => Ok(Field::field17),
+                _ => Err(serde::de::Error::invalid_value(
                     serde::de::Unexpected::Unsigned(value),
                     &"variant index 0 <= i < 18",
                 )),
@@ -227,65 +224,61 @@ where
         fn visit_str<E>(
             self,
             value: &str,
-        ) -> serde::export::Result<Self::Value, E>
+        ) -> std::result::Result<Self::Value, E>
         where
             E: serde::de::Error,
         {
             match value {
-                "NotFound" => serde::export::Ok(Field::field0),
-                "PermissionDenied" => serde::export::Ok(Field::field1),
-                "ConnectionRefused" => serde::export::Ok(Field::field2),
-                "ConnectionReset" => serde::export::Ok(Field::field3),
-                "ConnectionAborted" => serde::export::Ok(Field::field4),
-                "NotConnected" => serde::export::Ok(Field::field5),
-                "AddrInUse" => serde::export::Ok(Field::field6),
-                "AddrNotAvailable" => serde::export::Ok(Field::field7),
-                "BrokenPipe" => serde::export::Ok(Field::field8),
-                "AlreadyExists" => serde::export::Ok(Field::field9),
-                "WouldBlock" => serde::export::Ok(Field::field10),
-                "InvalidInput" => serde::export::Ok(Field::field11),
-                "InvalidData" => serde::export::Ok(Field::field12),
-                "TimedOut" => serde::export::Ok(Field::field13),
-                "WriteZero" => serde::export::Ok(Field::field14),
-                "Interrupted" => serde::export::Ok(Field::field15),
-                "Other" => serde::export::Ok(Field::field16),
-                "UnexpectedEof" => serde::export::Ok(Field::field17),
-                _ => serde::export::Err(serde::de::Error::unknown_variant(
-                    value, VARIANTS,
-                )),
+                "NotFound" => Ok(Field::field0),
+                "PermissionDenied" => Ok(Field::field1),
+                "ConnectionRefused" => Ok(Field::field2),
+                "ConnectionReset" => Ok(Field::field3),
+                "ConnectionAborted" => Ok(Field::field4),
+                "NotConnected" => Ok(Field::field5),
+                "AddrInUse" => Ok(Field::field6),
+                "AddrNotAvailable" => Ok(Field::field7),
+                "BrokenPipe" => Ok(Field::field8),
+                "AlreadyExists" => Ok(Field::field9),
+                "WouldBlock" => Ok(Field::field10),
+                "InvalidInput" => Ok(Field::field11),
+                "InvalidData" => Ok(Field::field12),
+                "TimedOut" => Ok(Field::field13),
+                "WriteZero" => Ok(Field::field14),
+                "Interrupted" => Ok(Field::field15),
+                "Other" => Ok(Field::field16),
+                "UnexpectedEof" => Ok(Field::field17),
+                _ => Err(serde::de::Error::unknown_variant(value, VARIANTS)),
             }
         }
         fn visit_bytes<E>(
             self,
             value: &[u8],
-        ) -> serde::export::Result<Self::Value, E>
+        ) -> std::result::Result<Self::Value, E>
         where
             E: serde::de::Error,
         {
             match value {
-                b"NotFound" => serde::export::Ok(Field::field0),
-                b"PermissionDenied" => serde::export::Ok(Field::field1),
-                b"ConnectionRefused" => serde::export::Ok(Field::field2),
-                b"ConnectionReset" => serde::export::Ok(Field::field3),
-                b"ConnectionAborted" => serde::export::Ok(Field::field4),
-                b"NotConnected" => serde::export::Ok(Field::field5),
-                b"AddrInUse" => serde::export::Ok(Field::field6),
-                b"AddrNotAvailable" => serde::export::Ok(Field::field7),
-                b"BrokenPipe" => serde::export::Ok(Field::field8),
-                b"AlreadyExists" => serde::export::Ok(Field::field9),
-                b"WouldBlock" => serde::export::Ok(Field::field10),
-                b"InvalidInput" => serde::export::Ok(Field::field11),
-                b"InvalidData" => serde::export::Ok(Field::field12),
-                b"TimedOut" => serde::export::Ok(Field::field13),
-                b"WriteZero" => serde::export::Ok(Field::field14),
-                b"Interrupted" => serde::export::Ok(Field::field15),
-                b"Other" => serde::export::Ok(Field::field16),
-                b"UnexpectedEof" => serde::export::Ok(Field::field17),
+                b"NotFound" => Ok(Field::field0),
+                b"PermissionDenied" => Ok(Field::field1),
+                b"ConnectionRefused" => Ok(Field::field2),
+                b"ConnectionReset" => Ok(Field::field3),
+                b"ConnectionAborted" => Ok(Field::field4),
+                b"NotConnected" => Ok(Field::field5),
+                b"AddrInUse" => Ok(Field::field6),
+                b"AddrNotAvailable" => Ok(Field::field7),
+                b"BrokenPipe" => Ok(Field::field8),
+                b"AlreadyExists" => Ok(Field::field9),
+                b"WouldBlock" => Ok(Field::field10),
+                b"InvalidInput" => Ok(Field::field11),
+                b"InvalidData" => Ok(Field::field12),
+                b"TimedOut" => Ok(Field::field13),
+                b"WriteZero" => Ok(Field::field14),
+                b"Interrupted" => Ok(Field::field15),
+                b"Other" => Ok(Field::field16),
+                b"UnexpectedEof" => Ok(Field::field17),
                 _ => {
-                    let value = &serde::export::from_utf8_lossy(value);
-                    serde::export::Err(serde::de::Error::unknown_variant(
-                        value, VARIANTS,
-                    ))
+                    let value = &String::from_utf8_lossy(value);
+                    Err(serde::de::Error::unknown_variant(value, VARIANTS))
                 }
             }
         }
@@ -294,7 +287,7 @@ where
         #[inline]
         fn deserialize<D>(
             deserializer: D,
-        ) -> serde::export::Result<Self, D::Error>
+        ) -> std::result::Result<Self, D::Error>
         where
             D: serde::Deserializer<'de>,
         {
@@ -305,196 +298,196 @@ where
         }
     }
     struct Visitor<'de> {
-        marker: serde::export::PhantomData<io::ErrorKind>,
-        lifetime: serde::export::PhantomData<&'de ()>,
+        marker: std::marker::PhantomData<io::ErrorKind>,
+        lifetime: std::marker::PhantomData<&'de ()>,
     }
     impl<'de> serde::de::Visitor<'de> for Visitor<'de> {
         type Value = io::ErrorKind;
         fn expecting(
             &self,
-            formatter: &mut serde::export::Formatter,
-        ) -> serde::export::fmt::Result {
-            serde::export::Formatter::write_str(formatter, "enum io::ErrorKind")
+            formatter: &mut std::fmt::Formatter,
+        ) -> std::fmt::Result {
+            std::fmt::Formatter::write_str(formatter, "enum io::ErrorKind")
         }
         fn visit_enum<A>(
             self,
             data: A,
-        ) -> serde::export::Result<Self::Value, A::Error>
+        ) -> std::result::Result<Self::Value, A::Error>
         where
             A: serde::de::EnumAccess<'de>,
         {
             match match serde::de::EnumAccess::variant(data) {
-                serde::export::Ok(val) => val,
-                serde::export::Err(err) => {
-                    return serde::export::Err(err);
+                Ok(val) => val,
+                Err(err) => {
+                    return Err(err);
                 }
             } {
                 (Field::field0, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::NotFound)
+                    Ok(io::ErrorKind::NotFound)
                 }
                 (Field::field1, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::PermissionDenied)
+                    Ok(io::ErrorKind::PermissionDenied)
                 }
                 (Field::field2, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::ConnectionRefused)
+                    Ok(io::ErrorKind::ConnectionRefused)
                 }
                 (Field::field3, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::ConnectionReset)
+                    Ok(io::ErrorKind::ConnectionReset)
                 }
                 (Field::field4, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::ConnectionAborted)
+                    Ok(io::ErrorKind::ConnectionAborted)
                 }
                 (Field::field5, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::NotConnected)
+                    Ok(io::ErrorKind::NotConnected)
                 }
                 (Field::field6, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::AddrInUse)
+                    Ok(io::ErrorKind::AddrInUse)
                 }
                 (Field::field7, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::AddrNotAvailable)
+                    Ok(io::ErrorKind::AddrNotAvailable)
                 }
                 (Field::field8, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::BrokenPipe)
+                    Ok(io::ErrorKind::BrokenPipe)
                 }
                 (Field::field9, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::AlreadyExists)
+                    Ok(io::ErrorKind::AlreadyExists)
                 }
                 (Field::field10, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::WouldBlock)
+                    Ok(io::ErrorKind::WouldBlock)
                 }
                 (Field::field11, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::InvalidInput)
+                    Ok(io::ErrorKind::InvalidInput)
                 }
                 (Field::field12, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::InvalidData)
+                    Ok(io::ErrorKind::InvalidData)
                 }
                 (Field::field13, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::TimedOut)
+                    Ok(io::ErrorKind::TimedOut)
                 }
                 (Field::field14, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::WriteZero)
+                    Ok(io::ErrorKind::WriteZero)
                 }
                 (Field::field15, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::Interrupted)
+                    Ok(io::ErrorKind::Interrupted)
                 }
                 (Field::field16, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::Other)
+                    Ok(io::ErrorKind::Other)
                 }
                 (Field::field17, variant) => {
                     match serde::de::VariantAccess::unit_variant(variant) {
-                        serde::export::Ok(val) => val,
-                        serde::export::Err(err) => {
-                            return serde::export::Err(err);
+                        Ok(val) => val,
+                        Err(err) => {
+                            return Err(err);
                         }
                     };
-                    serde::export::Ok(io::ErrorKind::UnexpectedEof)
+                    Ok(io::ErrorKind::UnexpectedEof)
                 }
             }
         }
     }
-    const VARIANTS: &'static [&'static str] = &[
+    const VARIANTS: &[&str] = &[
         "NotFound",
         "PermissionDenied",
         "ConnectionRefused",
@@ -519,8 +512,8 @@ where
         "ErrorKind",
         VARIANTS,
         Visitor {
-            marker: serde::export::PhantomData::<io::ErrorKind>,
-            lifetime: serde::export::PhantomData,
+            marker: std::marker::PhantomData::<io::ErrorKind>,
+            lifetime: std::marker::PhantomData,
         },
     )
 }
@@ -32,12 +32,12 @@ impl From<io::Error> for SerIoError {
     }
 }
 
-impl Into<io::Error> for SerIoError {
-    fn into(self) -> io::Error {
-        if let Some(code) = self.os_code {
+impl From<SerIoError> for io::Error {
+    fn from(x: SerIoError) -> Self {
+        if let Some(code) = x.os_code {
             io::Error::from_raw_os_error(code)
         } else {
-            io::Error::new(self.error_kind, self.description)
+            io::Error::new(x.error_kind, x.description)
         }
     }
 }
@@ -45,7 +45,7 @@ impl Into<io::Error> for SerIoError {
 pub fn serialize<S>(
     error: &io::Error,
     serializer: S,
-) -> serde::export::Result<S::Ok, S::Error>
+) -> std::result::Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
@@ -55,7 +55,7 @@ where
 
 pub fn deserialize<'de, D>(
     deserializer: D,
-) -> serde::export::Result<io::Error, D::Error>
+) -> std::result::Result<io::Error, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -0,0 +1,138 @@
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+use tokio::{runtime::Handle, task::JoinHandle, time};
+
+/// Interval at which a drain checks whether all tasks have completed
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Records tasks as they are spawned and complete, so that they can be
+/// counted and waited upon rather than left detached
+#[derive(Clone, Debug, Default)]
+pub struct TaskTracker {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    active: AtomicUsize,
+    total: AtomicUsize,
+}
+
+/// Marks a task as active until dropped, which happens even if the task
+/// panics or is cancelled
+#[derive(Debug)]
+pub struct TaskGuard {
+    inner: Arc<Inner>,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.inner.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl TaskTracker {
+    /// Marks a task that is not spawned, such as work done inline by a
+    /// loop, as active until the returned guard is dropped
+    pub fn track(&self) -> TaskGuard {
+        self.inner.active.fetch_add(1, Ordering::AcqRel);
+        self.inner.total.fetch_add(1, Ordering::AcqRel);
+        TaskGuard {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Spawns `future` onto the runtime of `handle`, tracking it as active
+    /// until it completes
+    pub fn spawn<F>(&self, handle: &Handle, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let guard = self.track();
+        handle.spawn(async move {
+            let _guard = guard;
+            future.await
+        })
+    }
+
+    /// Total tasks that are still running
+    pub fn active(&self) -> usize {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    /// Total tasks that have ever been tracked
+    pub fn total(&self) -> usize {
+        self.inner.total.load(Ordering::Acquire)
+    }
+
+    /// Waits for all active tasks to complete, returning false if some are
+    /// still running once `timeout` has elapsed
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while self.active() > 0 {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            time::delay_for(DRAIN_POLL_INTERVAL).await;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawn_should_track_task_until_it_completes() {
+        let tracker = TaskTracker::default();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let handle = tracker.spawn(&Handle::current(), async move {
+            let _ = rx.await;
+        });
+        assert_eq!(tracker.active(), 1);
+        assert_eq!(tracker.total(), 1);
+
+        tx.send(()).unwrap();
+        handle.await.unwrap();
+        assert_eq!(tracker.active(), 0);
+        assert_eq!(tracker.total(), 1);
+    }
+
+    #[tokio::test]
+    async fn spawn_should_stop_tracking_task_that_panics() {
+        let tracker = TaskTracker::default();
+
+        let result = tracker
+            .spawn(&Handle::current(), async { panic!("Task panicked") })
+            .await;
+        assert!(result.is_err(), "Task did not panic");
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn drain_should_wait_for_active_tasks() {
+        let tracker = TaskTracker::default();
+        tracker.spawn(&Handle::current(), async {
+            time::delay_for(Duration::from_millis(50)).await;
+        });
+
+        assert!(tracker.drain(Duration::from_secs(1)).await);
+        assert_eq!(tracker.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn drain_should_fail_if_tasks_outlive_timeout() {
+        let tracker = TaskTracker::default();
+        let _guard = tracker.track();
+
+        assert!(!tracker.drain(Duration::from_millis(20)).await);
+        assert_eq!(tracker.active(), 1);
+    }
+}
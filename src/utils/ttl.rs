@@ -34,6 +34,11 @@ impl<T> TtlValue<T> {
         self.last_touched = Instant::now();
     }
 
+    /// Marks the value as last touched at `instant` rather than now
+    pub fn touch_at(&mut self, instant: Instant) {
+        self.last_touched = instant;
+    }
+
     pub fn last_touched(&self) -> &Instant {
         &self.last_touched
     }
@@ -45,6 +50,13 @@ impl<T> TtlValue<T> {
     pub fn has_expired(&self) -> bool {
         self.last_touched.elapsed().checked_sub(self.ttl).is_some()
     }
+
+    /// Time until the value expires, which is zero once it has
+    pub fn remaining(&self) -> Duration {
+        self.ttl
+            .checked_sub(self.last_touched.elapsed())
+            .unwrap_or_default()
+    }
 }
 
 impl<T: Hash> Hash for TtlValue<T> {
@@ -91,15 +103,15 @@ impl<T> From<T> for TtlValue<T> {
     }
 }
 
-impl<T> Into<Instant> for TtlValue<T> {
-    fn into(self) -> Instant {
-        self.last_touched
+impl<T> From<TtlValue<T>> for Instant {
+    fn from(val: TtlValue<T>) -> Self {
+        val.last_touched
     }
 }
 
-impl<T> Into<Duration> for TtlValue<T> {
-    fn into(self) -> Duration {
-        self.ttl
+impl<T> From<TtlValue<T>> for Duration {
+    fn from(val: TtlValue<T>) -> Self {
+        val.ttl
     }
 }
 
@@ -167,8 +179,8 @@ mod tests {
         set.insert(v2);
 
         assert_eq!(set.len(), 2);
-        assert!(set.get(&0.into()).is_some());
-        assert!(set.get(&1.into()).is_some());
+        assert!(set.contains(&0.into()));
+        assert!(set.contains(&1.into()));
 
         // Values do overlap, so creates one entry
         let mut set = HashSet::new();
@@ -179,7 +191,7 @@ mod tests {
         set.insert(v2);
 
         assert_eq!(set.len(), 1);
-        assert!(set.get(&2.into()).is_some());
+        assert!(set.contains(&2.into()));
     }
 
     #[test]
@@ -224,7 +236,7 @@ mod tests {
     #[test]
     fn into_should_return_last_touched_if_type_is_instant() {
         let v1 = TtlValue::new(3, Duration::from_millis(5));
-        let v2 = v1.clone();
+        let v2 = v1;
 
         let i: Instant = v1.into();
         assert_eq!(i, v2.last_touched);
@@ -0,0 +1,356 @@
+use super::TtlValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::{sync::Mutex, task::JoinHandle, time};
+
+/// Callback invoked with each entry removed from a map without being asked
+pub type EvictionCallback<K, V> = dyn FnMut(&K, &V, Eviction) + Send;
+
+/// Reason that an entry was removed from a map without being asked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// Entry went untouched for longer than its ttl
+    Expired,
+
+    /// Entry was the least recently touched when the map was full
+    Capacity,
+}
+
+/// Map of entries that each have a limited lifetime since they were last
+/// touched, optionally capped in size
+///
+/// Expiration is lazy, where expired entries are no longer yielded by
+/// lookups but remain in the map until `evict_expired` is called, either
+/// directly or periodically by a task from `spawn_evictor`. Inserting a new
+/// key into a full map evicts expired entries and then the least recently
+/// touched one to make room.
+pub struct TtlMap<K, V> {
+    entries: HashMap<K, TtlValue<V>>,
+    ttl: Duration,
+    max_len: Option<usize>,
+    on_evict: Option<Box<EvictionCallback<K, V>>>,
+}
+
+impl<K, V> fmt::Debug for TtlMap<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TtlMap")
+            .field("entries", &self.entries)
+            .field("ttl", &self.ttl)
+            .field("max_len", &self.max_len)
+            .finish()
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> TtlMap<K, V> {
+    /// Creates a map whose entries expire after going untouched for `ttl`
+    /// unless inserted with a ttl of their own
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            ttl,
+            max_len: None,
+            on_evict: None,
+        }
+    }
+
+    /// Caps the map at `max_len` entries
+    pub fn with_max_len(self, max_len: usize) -> Self {
+        Self {
+            max_len: Some(max_len),
+            ..self
+        }
+    }
+
+    /// Invokes `on_evict` with each entry removed without being asked
+    pub fn with_eviction_callback(
+        self,
+        on_evict: impl FnMut(&K, &V, Eviction) + Send + 'static,
+    ) -> Self {
+        Self {
+            on_evict: Some(Box::new(on_evict)),
+            ..self
+        }
+    }
+
+    /// Time an entry can go untouched before expiring unless inserted with
+    /// a ttl of its own
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    /// Total entries in the map, including those that have expired but are
+    /// yet to be evicted
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Inserts `value` using the ttl of the map, returning the value it
+    /// replaced if that had not expired
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let ttl = self.ttl;
+        self.insert_with_ttl(key, value, ttl)
+    }
+
+    /// Inserts `value` to expire after going untouched for `ttl`, returning
+    /// the value it replaced if that had not expired
+    pub fn insert_with_ttl(
+        &mut self,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) -> Option<V> {
+        if !self.entries.contains_key(&key) {
+            self.make_room();
+        }
+
+        self.entries
+            .insert(key, TtlValue::new(value, ttl))
+            .filter(|x| !x.has_expired())
+            .map(|x| x.value)
+    }
+
+    /// Renews the lifetime of the entry with `key`, returning true if it
+    /// exists and had not expired
+    pub fn touch(&mut self, key: &K) -> bool {
+        match self.get_mut(key) {
+            Some(x) => {
+                x.touch();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&TtlValue<V>> {
+        self.entries.get(key).filter(|x| !x.has_expired())
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut TtlValue<V>> {
+        self.entries.get_mut(key).filter(|x| !x.has_expired())
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the entry with `key`, returning its value if it had not
+    /// expired
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries
+            .remove(key)
+            .filter(|x| !x.has_expired())
+            .map(|x| x.value)
+    }
+
+    /// Iterates over the entries that have not expired in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &TtlValue<V>)> {
+        self.entries.iter().filter(|(_, x)| !x.has_expired())
+    }
+
+    /// Keys of the entries that have not expired in arbitrary order
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Keys of the entries that expire within `within` along with the time
+    /// until each does, including those that have expired but are yet to
+    /// be evicted
+    pub fn expiring_within(
+        &self,
+        within: Duration,
+    ) -> impl Iterator<Item = (&K, Duration)> {
+        self.entries
+            .iter()
+            .map(|(k, x)| (k, x.remaining()))
+            .filter(move |(_, remaining)| *remaining <= within)
+    }
+
+    /// Removes all entries that have expired, yielding them after invoking
+    /// the eviction callback with each
+    pub fn evict_expired(&mut self) -> Vec<(K, V)> {
+        let expired: Vec<K> = self
+            .entries
+            .iter()
+            .filter(|(_, x)| x.has_expired())
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|k| self.evict(k, Eviction::Expired))
+            .collect()
+    }
+
+    /// Evicts entries until there is room for one more
+    fn make_room(&mut self) {
+        let max_len = match self.max_len {
+            Some(max_len) if self.entries.len() >= max_len => max_len,
+            _ => return,
+        };
+
+        self.evict_expired();
+        while !self.entries.is_empty() && self.entries.len() >= max_len {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, x)| *x.last_touched())
+                .map(|(k, _)| k.clone());
+            match oldest {
+                Some(k) => self.evict(k, Eviction::Capacity),
+                None => break,
+            };
+        }
+    }
+
+    fn evict(&mut self, key: K, reason: Eviction) -> Option<(K, V)> {
+        let value = self.entries.remove(&key)?.value;
+        if let Some(on_evict) = self.on_evict.as_mut() {
+            on_evict(&key, &value, reason);
+        }
+        Some((key, value))
+    }
+}
+
+impl<K, V> TtlMap<K, V>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + 'static,
+{
+    /// Spawns a task that evicts expired entries from `map` every
+    /// `interval`, stopping once the map is dropped
+    pub fn spawn_evictor(
+        map: &Arc<Mutex<Self>>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        let map: Weak<Mutex<Self>> = Arc::downgrade(map);
+        tokio::spawn(async move {
+            loop {
+                time::delay_for(interval).await;
+                match map.upgrade() {
+                    Some(map) => {
+                        map.lock().await.evict_expired();
+                    }
+                    None => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn expire(map: &mut TtlMap<u32, &'static str>, key: u32) {
+        map.get_mut(&key)
+            .unwrap()
+            .touch_at(Instant::now() - Duration::from_secs(60));
+    }
+
+    #[test]
+    fn get_should_not_yield_expired_entries_before_eviction() {
+        let mut map = TtlMap::new(Duration::from_secs(1));
+        map.insert(1, "one");
+        map.insert(2, "two");
+        expire(&mut map, 1);
+
+        assert!(!map.contains_key(&1));
+        assert_eq!(map.get(&2).map(|x| **x), Some("two"));
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.evict_expired(), vec![(1, "one")]);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn insert_should_evict_least_recently_touched_when_full() {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let evicted_2 = Arc::clone(&evicted);
+        let mut map = TtlMap::new(Duration::from_secs(1))
+            .with_max_len(2)
+            .with_eviction_callback(move |k, _, reason| {
+                evicted_2.lock().unwrap().push((*k, reason))
+            });
+
+        map.insert(1, "one");
+        map.insert(2, "two");
+        map.get_mut(&1)
+            .unwrap()
+            .touch_at(Instant::now() - Duration::from_millis(100));
+        map.insert(3, "three");
+        assert_eq!(map.keys().count(), 2);
+        assert!(!map.contains_key(&1));
+
+        // Expired entries are evicted before any that are still alive
+        expire(&mut map, 3);
+        map.insert(4, "four");
+        assert!(map.contains_key(&2));
+
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            vec![(1, Eviction::Capacity), (3, Eviction::Expired)]
+        );
+    }
+
+    #[test]
+    fn touch_should_renew_lifetime_of_entry() {
+        let mut map = TtlMap::new(Duration::from_secs(1));
+        map.insert_with_ttl(1, "one", Duration::from_secs(30));
+        map.get_mut(&1)
+            .unwrap()
+            .touch_at(Instant::now() - Duration::from_secs(10));
+
+        assert!(map.touch(&1));
+        assert!(map.get(&1).unwrap().last_touched().elapsed().as_secs() < 10);
+        assert!(!map.touch(&2));
+    }
+
+    #[test]
+    fn expiring_within_should_include_expired_entries_yet_to_be_evicted() {
+        let mut map = TtlMap::new(Duration::from_secs(60));
+        map.insert(1, "one");
+        map.insert_with_ttl(2, "two", Duration::from_secs(5));
+        map.insert(3, "three");
+        expire(&mut map, 3);
+
+        let mut expiring: Vec<(u32, Duration)> = map
+            .expiring_within(Duration::from_secs(10))
+            .map(|(k, remaining)| (*k, remaining))
+            .collect();
+        expiring.sort();
+
+        assert_eq!(expiring.len(), 2);
+        assert_eq!(expiring[0], (2, expiring[0].1));
+        assert!(expiring[0].1 > Duration::from_secs(4));
+        assert_eq!(expiring[1], (3, Duration::default()));
+    }
+
+    #[tokio::test]
+    async fn spawn_evictor_should_evict_expired_entries_periodically() {
+        let map = Arc::new(Mutex::new(TtlMap::new(Duration::from_millis(1))));
+        map.lock().await.insert(1, "one");
+
+        let handle = TtlMap::spawn_evictor(&map, Duration::from_millis(5));
+        time::delay_for(Duration::from_millis(50)).await;
+        assert!(map.lock().await.is_empty());
+
+        drop(map);
+        handle.await.unwrap();
+    }
+}
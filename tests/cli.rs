@@ -74,3 +74,32 @@ async fn test_tcp_client_server_multiple_msgs() {
         } => {},
     }
 }
+
+#[cfg(feature = "cli")]
+#[tokio::test]
+async fn test_server_check_config() {
+    cc::run(cc::build_server_opts(
+        "127.0.0.1:60124",
+        "tcp",
+        vec!["--check-config", "--transfer-quota", "1024"],
+    ))
+    .await
+    .expect("Valid config rejected");
+
+    // Only http webhooks are supported
+    let result = cc::run(cc::build_server_opts(
+        "127.0.0.1:60124",
+        "tcp",
+        vec!["--check-config", "--webhook", "https://example.com"],
+    ))
+    .await;
+    assert!(result.is_err(), "Invalid webhook accepted");
+
+    let result = cc::run(cc::build_server_opts(
+        "127.0.0.1:60124",
+        "tcp",
+        vec!["--check-config", "--max-request-depth", "0"],
+    ))
+    .await;
+    assert!(result.is_err(), "Invalid request depth accepted");
+}
@@ -13,6 +13,9 @@ pub async fn run(args: Vec<&str>) -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(feature = "cli")]
     return over_there::cli::run(opts).await;
 
+    #[cfg(not(feature = "cli"))]
+    let _ = args;
+
     #[cfg(not(feature = "cli"))]
     return Err("cli feature not enabled".into());
 }
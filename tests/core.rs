@@ -1,88 +1,316 @@
 mod core_common;
 
 use core_common::{scenarios, setup};
-use setup::TestMode;
+use over_there::testkit::TestTransport;
 
 #[tokio::test]
 async fn test_tcp_client_ask_heartbeat() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::heartbeat::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_ask_heartbeat() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::heartbeat::async_test(test_bench.client).await;
 }
 
+#[tokio::test]
+async fn test_tcp_client_close() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::close::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_close() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::close::async_test(test_bench.client).await;
+}
+
 #[tokio::test]
 async fn test_tcp_client_ask_version() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::version::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_ask_version() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::version::async_test(test_bench.client).await;
 }
 
+#[tokio::test]
+async fn test_tcp_client_ask_clock_offset() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::time::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_ask_clock_offset() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::time::async_test(test_bench.client).await;
+}
+
 #[tokio::test]
 async fn test_tcp_client_ask_capabilities() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::capabilities::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_ask_capabilities() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::capabilities::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_tcp_client_file_manipulation() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::file::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_file_manipulation() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::file::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_tcp_client_dir_manipulation() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::dir::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_dir_manipulation() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::dir::async_test(test_bench.client).await;
 }
 
+#[tokio::test]
+async fn test_tcp_client_copy_dir() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::copy_dir::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_copy_dir() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::copy_dir::async_test(test_bench.client).await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_tcp_client_copy_dir_non_utf8() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::copy_dir::async_test_non_utf8(test_bench.client).await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_udp_client_copy_dir_non_utf8() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::copy_dir::async_test_non_utf8(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_mirror() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::mirror::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_mirror() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::mirror::async_test(test_bench.client).await;
+}
+
 #[tokio::test]
 async fn test_tcp_client_remote_process() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::proc::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_remote_process() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::proc::async_test(test_bench.client).await;
 }
 
+#[tokio::test]
+async fn test_tcp_client_proc_exit() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::proc_exit::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_proc_exit() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::proc_exit::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_pty() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::pty::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_pty() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::pty::async_test(test_bench.client).await;
+}
+
 #[tokio::test]
 async fn test_tcp_client_ask_timeout() {
-    let test_bench = setup::setup(TestMode::Tcp).await;
+    let test_bench = setup::setup(TestTransport::Tcp).await;
     scenarios::ask_timeout::async_test(test_bench.client).await;
 }
 
 #[tokio::test]
 async fn test_udp_client_ask_timeout() {
-    let test_bench = setup::setup(TestMode::Udp).await;
+    let test_bench = setup::setup(TestTransport::Udp).await;
     scenarios::ask_timeout::async_test(test_bench.client).await;
 }
+
+#[tokio::test]
+async fn test_tcp_client_ask_msg_too_large() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::msg_too_large::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_ask_msg_too_large() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::msg_too_large::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_large_msg() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::large_msg::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_large_msg() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::large_msg::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_compression() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::compression::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_compression() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::compression::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_inject_fault() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::fault::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_inject_fault() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::fault::async_test(test_bench.client).await;
+}
+
+#[cfg(feature = "wasm")]
+#[tokio::test]
+async fn test_tcp_client_wasm_handler() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::wasm::async_test(test_bench.client).await;
+}
+
+#[cfg(feature = "wasm")]
+#[tokio::test]
+async fn test_udp_client_wasm_handler() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::wasm::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_ask_batch_stream() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::batch_stream::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_ask_batch_stream() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::batch_stream::async_test(test_bench.client).await;
+}
+
+#[tokio::test]
+async fn test_udp_shared_socket_clients() {
+    let (bench_a, bench_b, socket) = setup::setup_shared_udp().await;
+    scenarios::shared_udp::async_test(bench_a, bench_b, socket).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_failover() {
+    scenarios::failover::async_test(TestTransport::Tcp).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_failover() {
+    scenarios::failover::async_test(TestTransport::Udp).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_server_shutdown() {
+    let test_bench = setup::setup(TestTransport::Tcp).await;
+    scenarios::shutdown::async_test(test_bench).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_server_shutdown() {
+    let test_bench = setup::setup(TestTransport::Udp).await;
+    scenarios::shutdown::async_test(test_bench).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_push_and_get_config() {
+    scenarios::config::async_test(TestTransport::Tcp).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_push_and_get_config() {
+    scenarios::config::async_test(TestTransport::Udp).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_pinned_server_identity() {
+    scenarios::identity::async_test(TestTransport::Tcp).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_pinned_server_identity() {
+    scenarios::identity::async_test(TestTransport::Udp).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_handshake() {
+    scenarios::handshake::async_test(TestTransport::Tcp).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_handshake() {
+    scenarios::handshake::async_test(TestTransport::Udp).await;
+}
+
+#[tokio::test]
+async fn test_tcp_client_forward_through_relay() {
+    scenarios::forward::async_test(TestTransport::Tcp).await;
+}
+
+#[tokio::test]
+async fn test_udp_client_forward_through_relay() {
+    scenarios::forward::async_test(TestTransport::Udp).await;
+}
@@ -11,8 +11,9 @@ pub async fn async_test(mut client: ConnectedClient) {
     // Ask for something custom, which won't have a response; this would
     // cause us to wait forever if we didn't have a timeout
     let result = client
-        .ask(From::from(Request::Custom(CustomArgs { data: vec![] })))
+        .ask(Request::Custom(CustomArgs::from(vec![])))
         .await;
 
     assert_eq!(result.unwrap_err(), AskError::Timeout);
+    assert_eq!(client.stats().await.timeouts, 1);
 }
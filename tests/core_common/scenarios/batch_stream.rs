@@ -0,0 +1,34 @@
+use futures::stream::StreamExt;
+use over_there::core::{request::Fault, ConnectedClient, Reply, Request};
+
+pub async fn async_test(mut client: ConnectedClient) {
+    // Slow down versions so that heartbeats complete first
+    client
+        .ask_inject_fault("version_request", Fault::Delay { millis: 250 }, None)
+        .await
+        .expect("Failed to inject delay fault");
+
+    let results: Vec<(usize, Reply)> = client
+        .ask_batch_stream(vec![Request::Version, Request::Heartbeat])
+        .await
+        .expect("Failed to ask batch")
+        .map(|r| r.expect("Failed to receive result"))
+        .collect()
+        .await;
+
+    assert_eq!(results.len(), 2, "Unexpected results: {:?}", results);
+    assert_eq!(results[0], (1, Reply::Heartbeat));
+    match &results[1] {
+        (0, Reply::Version(_)) => {}
+        x => panic!("Unexpected result: {:?}", x),
+    }
+
+    // Empty batches have nothing to stream
+    let results: Vec<_> = client
+        .ask_batch_stream(vec![])
+        .await
+        .expect("Failed to ask empty batch")
+        .collect()
+        .await;
+    assert!(results.is_empty(), "Unexpected results: {:?}", results);
+}
@@ -7,11 +7,13 @@ pub async fn async_test(mut client: ConnectedClient) {
         .expect("Failed to get capabilities")
         .capabilities;
 
-    let expected = vec![
+    let expected = [
         Capability::Custom,
         Capability::FileSystem,
         Capability::Exec,
         Capability::Forward,
+        #[cfg(feature = "wasm")]
+        Capability::Wasm,
     ];
 
     assert_eq!(
@@ -0,0 +1,29 @@
+use over_there::core::{request::CreateDirArgs, ConnectedClient, Request};
+use std::time::Duration;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.as_ref().join("told");
+
+    // A tell is only queued, so closing must still send it to the server
+    client
+        .tell(Request::CreateDir(CreateDirArgs {
+            path: path.as_path().into(),
+            include_components: false,
+            mode: None,
+        }))
+        .await
+        .expect("Failed to tell");
+    tokio::time::timeout(Duration::from_secs(1), client.close())
+        .await
+        .expect("Timed out closing client")
+        .expect("Failed to close client");
+
+    for _ in 0..100 {
+        if path.exists() {
+            return;
+        }
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("Server never received tell sent before close");
+}
@@ -0,0 +1,47 @@
+use over_there::core::{
+    transport::auth::identity::IdentityKey, AskError, AskOptions,
+    ConnectedClient, Reply, Request, DEFAULT_COMPRESSION_THRESHOLD,
+};
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let file_path = dir.path().join("zeros").to_string_lossy().to_string();
+
+    // Contents larger than the biggest msg that UDP can carry, which only
+    // fit once compressed in both directions
+    let contents = vec![0; 2 * 1024 * 1024];
+    client.compression_threshold = Some(DEFAULT_COMPRESSION_THRESHOLD);
+
+    let mut file = client
+        .ask_open_file(file_path)
+        .await
+        .expect("Failed to open file")
+        .into();
+    client
+        .ask_write_file(&mut file, &contents)
+        .await
+        .expect("Failed to write to file");
+
+    let read = client
+        .ask_read_file(&file)
+        .await
+        .expect("Failed to read file")
+        .contents;
+    assert!(read == contents, "Read {} bytes that differ", read.len());
+
+    // Requiring a signature fails before sending without a key to sign
+    // with, and succeeds once the client has one
+    let options = AskOptions {
+        require_signature: true,
+        ..Default::default()
+    };
+    let result = client.ask_with_options(Request::Heartbeat, options).await;
+    assert_eq!(result.unwrap_err(), AskError::SigningKeyRequired);
+
+    client.signing_key = Some(IdentityKey::generate());
+    let reply = client
+        .ask_with_options(Request::Heartbeat, options)
+        .await
+        .expect("Failed to ask with signature");
+    assert_eq!(reply, Reply::Heartbeat);
+}
@@ -0,0 +1,39 @@
+use over_there::testkit::{TestBench, TestTransport};
+
+pub async fn async_test(transport: TestTransport) {
+    let mut bench = TestBench::start(transport)
+        .await
+        .expect("Failed to start test bench");
+
+    // Nothing is available until a config has been pushed
+    assert!(bench.client.ask_get_config().await.is_err());
+
+    let args = bench
+        .client
+        .ask_push_config(b"first".to_vec(), 1)
+        .await
+        .expect("Failed to push config");
+    assert_eq!(args.version, 1);
+
+    let args = bench
+        .client
+        .ask_push_config(b"second".to_vec(), 2)
+        .await
+        .expect("Failed to push newer config");
+    assert_eq!(args.version, 2);
+
+    // Stale versions are rejected without replacing the stored config
+    assert!(bench
+        .client
+        .ask_push_config(b"stale".to_vec(), 2)
+        .await
+        .is_err());
+
+    let args = bench
+        .client
+        .ask_get_config()
+        .await
+        .expect("Failed to get config");
+    assert_eq!(args.blob, b"second");
+    assert_eq!(args.version, 2);
+}
@@ -0,0 +1,71 @@
+use over_there::core::{ConnectedClient, RemotePath};
+use std::fs;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let source = tempfile::TempDir::new().unwrap();
+    let target = tempfile::TempDir::new().unwrap();
+    fs::create_dir_all(source.path().join("sub").join("empty")).unwrap();
+    fs::write(source.path().join("a.txt"), b"a").unwrap();
+    fs::write(source.path().join("sub").join("b.txt"), b"bb").unwrap();
+
+    // Uploading creates every dir, including empty ones
+    let remote = target.path().join("remote");
+    let mut copy = client
+        .upload_dir(source.path(), remote.to_string_lossy().to_string())
+        .await
+        .expect("Failed to upload dir");
+    copy.files.sort();
+    assert_eq!(copy.files, vec!["a.txt", "sub/b.txt"]);
+    assert_eq!(copy.bytes, 3);
+    assert_eq!(fs::read(remote.join("sub").join("b.txt")).unwrap(), b"bb");
+    assert!(remote.join("sub").join("empty").is_dir());
+
+    // Downloading brings the same tree back
+    let local = target.path().join("local");
+    let mut copy = client
+        .download_dir(remote.to_string_lossy().to_string(), &local)
+        .await
+        .expect("Failed to download dir");
+    copy.dirs.sort();
+    assert_eq!(copy.dirs, vec!["sub", "sub/empty"]);
+    assert_eq!(copy.bytes, 3);
+    assert_eq!(fs::read(local.join("a.txt")).unwrap(), b"a");
+    assert_eq!(fs::read(local.join("sub").join("b.txt")).unwrap(), b"bb");
+    assert!(local.join("sub").join("empty").is_dir());
+    assert!(!local.join("a.txt.part").exists());
+}
+
+/// Names that are not valid UTF-8 are copied as is in both directions
+#[cfg(unix)]
+pub async fn async_test_non_utf8(mut client: ConnectedClient) {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = OsStr::from_bytes(b"\xffname.txt");
+    let source = tempfile::TempDir::new().unwrap();
+    let target = tempfile::TempDir::new().unwrap();
+    fs::create_dir(source.path().join("sub")).unwrap();
+    fs::write(source.path().join("sub").join(name), b"x").unwrap();
+
+    let remote = target.path().join("remote");
+    let copy = client
+        .upload_dir(source.path(), remote.as_path())
+        .await
+        .expect("Failed to upload dir");
+    assert_eq!(
+        copy.files,
+        vec![RemotePath::from_bytes(&b"sub/\xffname.txt"[..])]
+    );
+    assert_eq!(fs::read(remote.join("sub").join(name)).unwrap(), b"x");
+
+    let local = target.path().join("local");
+    let copy = client
+        .download_dir(remote.as_path(), &local)
+        .await
+        .expect("Failed to download dir");
+    assert_eq!(
+        copy.files,
+        vec![RemotePath::from_bytes(&b"sub/\xffname.txt"[..])]
+    );
+    assert_eq!(fs::read(local.join("sub").join(name)).unwrap(), b"x");
+}
@@ -1,3 +1,4 @@
+use futures::stream::StreamExt;
 use over_there::core::ConnectedClient;
 
 pub async fn async_test(mut client: ConnectedClient) {
@@ -31,6 +32,19 @@ pub async fn async_test(mut client: ConnectedClient) {
         .entries;
     assert_eq!(dir_contents.len(), 1);
 
+    // Streaming the same contents should yield the same entries
+    let streamed_contents: Vec<_> = client
+        .ask_list_dir_stream(root_str.clone())
+        .await
+        .expect("Failed to stream dir contents")
+        .collect()
+        .await;
+    assert_eq!(streamed_contents.len(), 1);
+    assert_eq!(
+        streamed_contents[0].as_ref().expect("Bad streamed entry"),
+        &dir_contents[0]
+    );
+
     // Moving the directory should fail
     if client
         .ask_rename_dir(root_str.clone(), root_str_2.clone())
@@ -0,0 +1,75 @@
+use futures::StreamExt;
+use over_there::{
+    core::{
+        transport::{
+            auth::Sha256Authenticator,
+            crypto::{key, Aes256GcmBicrypter},
+        },
+        ClientBuilder, ClientEvent, Transport,
+    },
+    testkit::{TestBenchBuilder, TestTransport, DEFAULT_SIGN_KEY},
+};
+use std::net::{TcpListener, UdpSocket};
+use std::time::Duration;
+
+pub async fn async_test(transport: TestTransport) {
+    let bicrypter = Aes256GcmBicrypter::new(&key::new_256bit_key());
+    let fallback = TestBenchBuilder::new(transport)
+        .bicrypter(bicrypter.clone())
+        .start()
+        .await
+        .expect("Failed to start fallback bench");
+    let fallback_addr = fallback.server.addr();
+
+    // Primary server accepts the connection of the client, but never replies
+    // and closes any tcp stream straight away
+    let (primary_addr, _primary) = match transport {
+        TestTransport::Tcp => {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            std::thread::spawn(move || drop(listener.accept()));
+            (addr, None)
+        }
+        TestTransport::Udp => {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            (socket.local_addr().unwrap(), Some(socket))
+        }
+    };
+
+    let mut client = ClientBuilder::default()
+        .authenticator(Sha256Authenticator::new(DEFAULT_SIGN_KEY))
+        .bicrypter(bicrypter)
+        .transport(match transport {
+            TestTransport::Tcp => Transport::Tcp(vec![primary_addr]),
+            TestTransport::Udp => Transport::Udp(vec![primary_addr]),
+        })
+        .timeout(Duration::from_millis(250))
+        .fallback_servers(vec![fallback_addr])
+        .build()
+        .expect("Failed to build client")
+        .connect()
+        .await
+        .expect("Failed to connect to primary server");
+    assert_eq!(client.fallback_servers(), vec![fallback_addr]);
+
+    // Idempotent ask that goes unanswered is replayed against the fallback
+    let mut events = client.events().await;
+    client
+        .ask_version()
+        .await
+        .expect("Failed to ask version after failing over");
+    assert_eq!(client.remote_addr(), fallback_addr);
+    assert!(client.fallback_servers().is_empty());
+    assert_eq!(
+        events.next().await,
+        Some(ClientEvent::Failover {
+            from: primary_addr,
+            to: fallback_addr,
+        })
+    );
+
+    client
+        .ask_heartbeat()
+        .await
+        .expect("Failed to ask heartbeat of fallback server");
+}
@@ -0,0 +1,56 @@
+use over_there::core::{
+    reply::ErrorCode, request::Fault, AskError, ConnectedClient,
+};
+use std::time::Duration;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    client.timeout = Duration::from_millis(250);
+
+    // Fail the next heartbeat only, after which heartbeats succeed again
+    client
+        .ask_inject_fault(
+            "heartbeat_request",
+            Fault::Fail {
+                msg: String::from("forced"),
+            },
+            Some(1),
+        )
+        .await
+        .expect("Failed to inject fail fault");
+    assert_eq!(
+        client.ask_heartbeat().await.unwrap_err(),
+        AskError::Failure {
+            msg: String::from("forced"),
+            code: ErrorCode::GENERIC,
+        }
+    );
+    client.ask_heartbeat().await.expect("Fault not removed");
+
+    // Delay longer than the client is willing to wait
+    client
+        .ask_inject_fault("version_request", Fault::Delay { millis: 500 }, None)
+        .await
+        .expect("Failed to inject delay fault");
+    assert_eq!(client.ask_version().await.unwrap_err(), AskError::Timeout);
+
+    // Wait for the server to finish the delayed request before continuing
+    tokio::time::delay_for(Duration::from_millis(500)).await;
+
+    // Panicking means that no reply is ever sent
+    client
+        .ask_inject_fault("capabilities_request", Fault::Panic, None)
+        .await
+        .expect("Failed to inject panic fault");
+    assert_eq!(
+        client.ask_capabilities().await.unwrap_err(),
+        AskError::Timeout
+    );
+
+    let cleared = client.ask_clear_faults().await.expect("Failed to clear");
+    assert_eq!(cleared.cleared, 2);
+    client.ask_version().await.expect("Delay fault not cleared");
+    client
+        .ask_capabilities()
+        .await
+        .expect("Panic fault not cleared");
+}
@@ -25,10 +25,11 @@ pub async fn async_test(mut client: ConnectedClient) {
         .await
         .expect("Failed to open file")
         .into();
-    client
+    let etag = client
         .ask_write_file(&mut file, b"Hello!\nThis is a test!\nGoodbye!")
         .await
-        .expect("Failed to write to file");
+        .expect("Failed to write to file")
+        .etag;
 
     let dir_contents = client
         .ask_list_dir_contents(dir_path.clone())
@@ -61,6 +62,14 @@ pub async fn async_test(mut client: ConnectedClient) {
     );
     assert_eq!(result, "Hello!\nThis is a test!\nGoodbye!");
 
+    // Contents are omitted when the client already has them
+    let x = client
+        .ask_read_file_if_none_match(&file, Some(etag))
+        .await
+        .expect("Failed to conditionally read file");
+    assert!(x.not_modified, "File unexpectedly modified");
+    assert!(x.contents.is_empty(), "Contents unexpectedly sent");
+
     client
         .ask_remove_file(&mut file)
         .await
@@ -0,0 +1,85 @@
+use over_there::core::{
+    request::RemoveDirArgs,
+    transport::{
+        auth::identity::IdentityKey,
+        crypto::{key, Aes256GcmBicrypter},
+    },
+    Reply, Request, SignatureMode, SignaturePolicy,
+};
+use over_there::testkit::{TestBenchBuilder, TestTransport};
+
+pub async fn async_test(transport: TestTransport) {
+    // Servers forwarded to are connected to with the keys of the relay
+    let bicrypter = Aes256GcmBicrypter::new(&key::new_256bit_key());
+    let mut target = TestBenchBuilder::new(transport)
+        .bicrypter(bicrypter.clone())
+        .start()
+        .await
+        .expect("Failed to start target bench");
+    let mut relay = TestBenchBuilder::new(transport)
+        .bicrypter(bicrypter)
+        .forwarding(true)
+        .start()
+        .await
+        .expect("Failed to start relay bench");
+    let target_addr = target.server.addr();
+
+    let reply = relay
+        .client
+        .ask_forward(target_addr, Request::Version)
+        .await
+        .expect("Failed to forward version request");
+    assert!(matches!(reply, Reply::Version(_)), "{:?}", reply);
+
+    // Errors of the server forwarded to are passed back as its reply, over
+    // the same connection as before
+    let path = target.root.join_string("missing");
+    let reply = relay
+        .client
+        .ask_forward(
+            target_addr,
+            Request::RemoveDir(RemoveDirArgs {
+                path: path.into(),
+                non_empty: false,
+            }),
+        )
+        .await
+        .expect("Failed to forward remove dir request");
+    assert!(matches!(reply, Reply::Error(_)), "{:?}", reply);
+
+    // Msgs reach the server forwarded to as signed by the original sender
+    // rather than by the relay, even if the relay has a key of its own
+    let sender_key = IdentityKey::generate();
+    let bicrypter = Aes256GcmBicrypter::new(&key::new_256bit_key());
+    let signed_target = TestBenchBuilder::new(transport)
+        .bicrypter(bicrypter.clone())
+        .signature_policy(SignaturePolicy::new(
+            SignatureMode::Require,
+            vec![sender_key.public_key().to_vec()],
+        ))
+        .start()
+        .await
+        .expect("Failed to start signature-checking target bench");
+    let mut signing_relay = TestBenchBuilder::new(transport)
+        .bicrypter(bicrypter)
+        .identity_key(IdentityKey::generate())
+        .forwarding(true)
+        .start()
+        .await
+        .expect("Failed to start signing relay bench");
+    signing_relay.client.signing_key = Some(sender_key);
+
+    let reply = signing_relay
+        .client
+        .ask_forward(signed_target.server.addr(), Request::Version)
+        .await
+        .expect("Failed to forward signed version request");
+    assert!(matches!(reply, Reply::Version(_)), "{:?}", reply);
+
+    // Servers reject forwarded requests unless forwarding is enabled
+    assert!(target
+        .client
+        .ask_forward(relay.server.addr(), Request::Version)
+        .await
+        .is_err());
+}
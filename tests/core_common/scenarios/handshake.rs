@@ -0,0 +1,52 @@
+use over_there::{
+    core::transport::auth::identity::IdentityKey,
+    testkit::{TestBenchBuilder, TestTransport},
+};
+use std::io;
+
+pub async fn async_test(transport: TestTransport) {
+    let server_key = IdentityKey::generate();
+
+    // Msgs are sealed once the server signs the handshake with a pinned key
+    let mut bench = TestBenchBuilder::new(transport)
+        .identity_key(server_key.clone())
+        .pinned_server_keys(vec![server_key.public_key().to_vec()])
+        .handshake(true)
+        .start()
+        .await
+        .expect("Failed to negotiate session with pinned server");
+    bench
+        .client
+        .ask_heartbeat()
+        .await
+        .expect("Failed to ask heartbeat within session");
+    bench
+        .client
+        .ask_version()
+        .await
+        .expect("Failed to ask version within session");
+
+    // A server without an identity can still negotiate a session when no
+    // keys are pinned
+    let mut bench = TestBenchBuilder::new(transport)
+        .handshake(true)
+        .start()
+        .await
+        .expect("Failed to negotiate session with server");
+    bench
+        .client
+        .ask_heartbeat()
+        .await
+        .expect("Failed to ask heartbeat within session");
+
+    // Negotiating fails when the server signs with an unpinned key
+    let err = TestBenchBuilder::new(transport)
+        .identity_key(IdentityKey::generate())
+        .pinned_server_keys(vec![server_key.public_key().to_vec()])
+        .handshake(true)
+        .start()
+        .await
+        .err()
+        .expect("Negotiated session with unpinned server");
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+}
@@ -2,4 +2,11 @@ use over_there::core::ConnectedClient;
 
 pub async fn async_test(mut client: ConnectedClient) {
     assert!(client.ask_heartbeat().await.is_ok());
+
+    // The server reports the time it spent, which is part of the round trip
+    let stats = client.stats().await;
+    assert_eq!(stats.asks, 1);
+    let timing = stats.last.expect("Missing timing of ask");
+    let server = timing.server.expect("Missing server time");
+    assert!(server <= timing.rtt, "{:?} > {:?}", server, timing.rtt);
 }
@@ -0,0 +1,45 @@
+use over_there::{
+    core::transport::auth::identity::IdentityKey,
+    testkit::{TestBenchBuilder, TestTransport},
+};
+use std::io;
+
+pub async fn async_test(transport: TestTransport) {
+    let server_key = IdentityKey::generate();
+    let other_key = IdentityKey::generate();
+
+    // Connecting succeeds when the server proves it holds a pinned key
+    let mut bench = TestBenchBuilder::new(transport)
+        .identity_key(server_key.clone())
+        .pinned_server_keys(vec![
+            other_key.public_key().to_vec(),
+            server_key.public_key().to_vec(),
+        ])
+        .start()
+        .await
+        .expect("Failed to connect to server with pinned identity");
+    bench
+        .client
+        .ask_heartbeat()
+        .await
+        .expect("Failed to ask heartbeat of pinned server");
+
+    // Connecting fails when the server holds a different key
+    let err = TestBenchBuilder::new(transport)
+        .identity_key(server_key.clone())
+        .pinned_server_keys(vec![other_key.public_key().to_vec()])
+        .start()
+        .await
+        .err()
+        .expect("Connected to server with unpinned identity");
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+
+    // Connecting fails when the server has no identity to prove
+    let err = TestBenchBuilder::new(transport)
+        .pinned_server_keys(vec![server_key.public_key().to_vec()])
+        .start()
+        .await
+        .err()
+        .expect("Connected to server without identity");
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", err);
+}
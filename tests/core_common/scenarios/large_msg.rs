@@ -0,0 +1,77 @@
+use over_there::core::{ConnectedClient, DownloadOptions};
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let file_path = dir.path().join("large").to_string_lossy().to_string();
+
+    // Contents large enough that both the request and reply are split into
+    // many packets on the wire
+    let contents: Vec<u8> = (0..4 * 1024).map(|i| (i % 251) as u8).collect();
+
+    let mut file = client
+        .ask_open_file(file_path)
+        .await
+        .expect("Failed to open file")
+        .into();
+    client
+        .ask_write_file(&mut file, &contents)
+        .await
+        .expect("Failed to write to file");
+
+    let read = client
+        .ask_read_file(&file)
+        .await
+        .expect("Failed to read file")
+        .contents;
+    assert!(read == contents, "Read {} bytes that differ", read.len());
+
+    // Reading in many chunks with several in flight at once yields the same
+    // contents in order
+    let read = client
+        .ask_read_file_chunked(&file, None, 500, 4)
+        .await
+        .expect("Failed to read file in chunks");
+    assert_eq!(read.size, Some(contents.len() as u64));
+    assert!(
+        read.contents == contents,
+        "Read {} bytes in chunks that differ",
+        read.contents.len()
+    );
+
+    // Downloading streams the same contents in order, reporting progress
+    // as each chunk is written
+    let mut downloaded = Vec::new();
+    let mut progress = Vec::new();
+    let options = DownloadOptions {
+        chunk_size: 500,
+        window: 4,
+        ..Default::default()
+    };
+    let args = client
+        .download_to(file.path().to_string(), &mut downloaded, options, |p| {
+            progress.push(p.written)
+        })
+        .await
+        .expect("Failed to download file");
+    assert!(!args.not_modified);
+    assert!(
+        downloaded == contents,
+        "Downloaded {} bytes that differ",
+        downloaded.len()
+    );
+    assert_eq!(progress.len(), 9);
+    assert_eq!(progress.last(), Some(&(contents.len() as u64)));
+
+    // Nothing is downloaded if the local copy is already up-to-date
+    let mut downloaded = Vec::new();
+    let options = DownloadOptions {
+        if_none_match: Some(args.etag),
+        ..Default::default()
+    };
+    let args = client
+        .download_to(file.path().to_string(), &mut downloaded, options, |_| {})
+        .await
+        .expect("Failed to check file");
+    assert!(args.not_modified);
+    assert!(downloaded.is_empty());
+}
@@ -0,0 +1,66 @@
+use over_there::core::{
+    ConnectedClient, Mirror, MirrorOptions, MIN_MIRROR_INTERVAL,
+};
+use std::fs;
+use std::time::Duration;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let remote = tempfile::TempDir::new().unwrap();
+    let local = tempfile::TempDir::new().unwrap();
+    fs::create_dir(remote.path().join("sub")).unwrap();
+    fs::write(remote.path().join("a.txt"), b"a").unwrap();
+    fs::write(remote.path().join("sub").join("b.txt"), b"b").unwrap();
+
+    let mut mirror = Mirror::new(
+        remote.path().to_string_lossy().to_string(),
+        local.path().join("mirror"),
+        MirrorOptions {
+            two_way: true,
+            ..Default::default()
+        },
+    );
+    let local = local.path().join("mirror");
+
+    // Passes are never run back to back, however short the interval asked for
+    let polling = Mirror::new(
+        remote.path(),
+        &local,
+        MirrorOptions {
+            interval: Duration::from_millis(0),
+            ..Default::default()
+        },
+    );
+    assert_eq!(polling.interval(), MIN_MIRROR_INTERVAL);
+
+    // First pass pulls everything on the server
+    let pass = mirror.sync(&mut client).await.expect("Failed first pass");
+    assert_eq!(pass.pulled, vec!["a.txt", "sub/b.txt"]);
+    assert_eq!(fs::read(local.join("sub").join("b.txt")).unwrap(), b"b");
+
+    // Nothing changed, so nothing is pulled or pushed
+    let pass = mirror.sync(&mut client).await.expect("Failed idle pass");
+    assert!(pass.is_empty(), "{:?}", pass);
+
+    // Changes on either side are applied to the other
+    fs::write(remote.path().join("a.txt"), b"a2").unwrap();
+    fs::write(local.join("sub").join("c.txt"), b"c").unwrap();
+    fs::remove_file(remote.path().join("sub").join("b.txt")).unwrap();
+    let pass = mirror.sync(&mut client).await.expect("Failed second pass");
+    assert_eq!(pass.pulled, vec!["a.txt"]);
+    assert_eq!(pass.pushed, vec!["sub/c.txt"]);
+    assert_eq!(pass.removed_local, vec!["sub/b.txt"]);
+    assert_eq!(fs::read(local.join("a.txt")).unwrap(), b"a2");
+    assert_eq!(
+        fs::read(remote.path().join("sub").join("c.txt")).unwrap(),
+        b"c"
+    );
+    assert!(!local.join("sub").join("b.txt").exists());
+
+    // Changing both sides is a conflict that leaves both untouched
+    fs::write(remote.path().join("a.txt"), b"remote").unwrap();
+    fs::write(local.join("a.txt"), b"local").unwrap();
+    let pass = mirror.sync(&mut client).await.expect("Failed third pass");
+    assert_eq!(pass.conflicts, vec!["a.txt"]);
+    assert_eq!(fs::read(remote.path().join("a.txt")).unwrap(), b"remote");
+    assert_eq!(fs::read(local.join("a.txt")).unwrap(), b"local");
+}
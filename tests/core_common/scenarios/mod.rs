@@ -1,7 +1,27 @@
 pub mod ask_timeout;
+pub mod batch_stream;
 pub mod capabilities;
+pub mod close;
+pub mod compression;
+pub mod config;
+pub mod copy_dir;
 pub mod dir;
+pub mod failover;
+pub mod fault;
 pub mod file;
+pub mod forward;
+pub mod handshake;
 pub mod heartbeat;
+pub mod identity;
+pub mod large_msg;
+pub mod mirror;
+pub mod msg_too_large;
 pub mod proc;
+pub mod proc_exit;
+pub mod pty;
+pub mod shared_udp;
+pub mod shutdown;
+pub mod time;
 pub mod version;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,26 @@
+use over_there::core::{
+    request::CustomArgs, AskError, ConnectedClient, Request,
+};
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let max = client.max_msg_size();
+
+    // Send a request whose data alone exceeds what the transport can carry,
+    // which should fail before anything is sent
+    let result = client
+        .ask(Request::Custom(CustomArgs::from(vec![0; max + 1])))
+        .await;
+
+    match result.unwrap_err() {
+        AskError::MsgTooLarge {
+            request,
+            size,
+            max: actual_max,
+        } => {
+            assert_eq!(request, "custom_request");
+            assert!(size > max, "Reported size {} within max", size);
+            assert_eq!(actual_max, max);
+        }
+        x => panic!("Unexpected error: {}", x),
+    }
+}
@@ -67,7 +67,7 @@ async fn wait_for_nonempty_output(
 
     while start.elapsed() < timeout {
         let output = client
-            .ask_read_proc_stdout(&proc)
+            .ask_read_proc_stdout(proc)
             .await
             .expect("Failed to get stdout")
             .output;
@@ -0,0 +1,37 @@
+use futures::StreamExt;
+use over_there::core::{ClientEvent, ConnectedClient, RemoteProc};
+use std::time::Duration;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let mut events = client.events().await;
+    let proc: RemoteProc = client
+        .ask_exec_proc(
+            String::from("sh"),
+            vec![String::from("-c"), String::from("exit 3")],
+        )
+        .await
+        .expect("Failed to exec proc")
+        .into();
+
+    // Server pushes the exit without being asked for it
+    let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("Timed out waiting for exit event");
+    assert_eq!(
+        event,
+        Some(ClientEvent::ProcExited {
+            id: proc.id(),
+            exit_code: Some(3),
+        })
+    );
+
+    let status =
+        tokio::time::timeout(Duration::from_secs(5), proc.wait(&mut client))
+            .await
+            .expect("Timed out waiting for proc")
+            .expect("Failed to wait for proc");
+    assert_eq!(status.id, proc.id());
+    assert!(!status.is_alive, "Proc reported alive after exit");
+    assert_eq!(status.exit_code, Some(3));
+    assert_eq!(client.proc_exit(&proc).await, Some(status));
+}
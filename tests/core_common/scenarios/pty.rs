@@ -0,0 +1,53 @@
+use over_there::core::{request::ExecProcPtyArgs, ConnectedClient, RemoteProc};
+use std::time::Duration;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let proc: RemoteProc = client
+        .ask_exec_proc_pty(ExecProcPtyArgs {
+            command: String::from("sh"),
+            args: vec![String::from("-c"), String::from("read x; stty size")],
+            rows: 24,
+            cols: 80,
+            ..Default::default()
+        })
+        .await
+        .expect("Failed to exec proc with pty")
+        .into();
+
+    let resized = client
+        .ask_resize_pty(&proc, 40, 120)
+        .await
+        .expect("Failed to resize pty");
+    assert_eq!((resized.rows, resized.cols), (40, 120));
+
+    client
+        .ask_write_proc_stdin(&proc, b"\n")
+        .await
+        .expect("Failed to write stdin");
+
+    let status =
+        tokio::time::timeout(Duration::from_secs(5), proc.wait(&mut client))
+            .await
+            .expect("Timed out waiting for proc")
+            .expect("Failed to wait for proc");
+    assert_eq!(status.exit_code, Some(0));
+
+    // Output of the terminal, which is all read as stdout, reflects the
+    // size it was changed to
+    let mut output = Vec::new();
+    for _ in 0..50 {
+        output.extend(
+            client
+                .ask_read_proc_stdout(&proc)
+                .await
+                .expect("Failed to read stdout")
+                .output,
+        );
+        if String::from_utf8_lossy(&output).contains("40 120") {
+            break;
+        }
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+    }
+    let output = String::from_utf8_lossy(&output);
+    assert!(output.contains("40 120"), "Unexpected output: {:?}", output);
+}
@@ -0,0 +1,50 @@
+use over_there::{
+    core::{reply::DirEntry, SharedUdpSocket},
+    testkit::TestBench,
+};
+use std::time::Duration;
+
+pub async fn async_test(
+    bench_a: TestBench,
+    bench_b: TestBench,
+    socket: SharedUdpSocket,
+) {
+    bench_a.root.write("a.txt", b"a").unwrap();
+    bench_b.root.write("b.txt", b"b").unwrap();
+
+    let mut client_a = socket.connect(bench_a.server.addr()).await.unwrap();
+    let mut client_b = socket.connect(bench_b.server.addr()).await.unwrap();
+    assert!(
+        socket.connect(bench_a.server.addr()).await.is_err(),
+        "Connected to the same server twice"
+    );
+
+    // Ask both servers at once, where each reply must reach the client of
+    // the server that sent it
+    let (list_a, list_b) = tokio::join!(
+        client_a.ask_list_dir_contents(bench_a.root.join_string("")),
+        client_b.ask_list_dir_contents(bench_b.root.join_string("")),
+    );
+    let has_file = |entries: &[DirEntry], name: &str| {
+        entries
+            .iter()
+            .any(|e| e.path.to_string_lossy().ends_with(name))
+    };
+    let list_a = list_a.expect("Failed to list dir of server a");
+    let list_b = list_b.expect("Failed to list dir of server b");
+    assert!(has_file(&list_a.entries, "a.txt"), "{:?}", list_a);
+    assert!(has_file(&list_b.entries, "b.txt"), "{:?}", list_b);
+
+    // Once disconnected, the client no longer receives msgs
+    assert!(socket.disconnect(bench_b.server.addr()).await);
+    tokio::time::timeout(Duration::from_secs(1), client_b.wait())
+        .await
+        .expect("Client did not conclude after disconnecting")
+        .unwrap();
+    assert_eq!(socket.peers().await, vec![bench_a.server.addr()]);
+
+    client_a
+        .ask_heartbeat()
+        .await
+        .expect("Failed to ask heartbeat after other client disconnected");
+}
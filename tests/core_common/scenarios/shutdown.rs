@@ -0,0 +1,25 @@
+use futures::StreamExt;
+use over_there::{core::ClientEvent, testkit::TestBench};
+use std::time::Duration;
+
+pub async fn async_test(mut bench: TestBench) {
+    // Server only knows of clients that have communicated with it
+    bench
+        .client
+        .ask_heartbeat()
+        .await
+        .expect("Failed to ask heartbeat");
+
+    let mut events = bench.client.events().await;
+    assert!(bench.server.drain(Duration::from_millis(500)).await);
+
+    let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .expect("Timed out waiting for shutdown event");
+    assert_eq!(
+        event,
+        Some(ClientEvent::ServerShuttingDown {
+            grace: Duration::from_millis(500),
+        })
+    );
+}
@@ -0,0 +1,18 @@
+use over_there::core::ConnectedClient;
+use std::time::Duration;
+
+pub async fn async_test(mut client: ConnectedClient) {
+    let offset = client
+        .ask_clock_offset(3)
+        .await
+        .expect("Failed to get clock offset");
+
+    // Client and server share a clock, so any offset is within the error of
+    // the estimate
+    let error = offset.round_trip / 2 + Duration::from_millis(1);
+    assert!(
+        i128::from(offset.offset_micros).abs() <= error.as_micros() as i128,
+        "Unexpected offset: {:?}",
+        offset
+    );
+}
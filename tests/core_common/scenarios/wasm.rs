@@ -0,0 +1,49 @@
+use over_there::core::{
+    reply, request::CustomArgs, ConnectedClient, Reply, Request,
+};
+
+/// Binary form of a module that echoes its input, kept small so that the
+/// load request fits within a single packet:
+///
+/// ```wat
+/// (module
+///     (memory (export "memory") 1)
+///     (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+///     (func (export "handle") (param $ptr i32) (param $len i32) (result i64)
+///         (i64.or
+///             (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+///             (i64.extend_i32_u (local.get $len)))))
+/// ```
+const ECHO_MODULE: &[u8] = &[
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 12, 2, 96, 1, 127, 1, 127, 96, 2, 127, 127,
+    1, 126, 3, 3, 2, 0, 1, 5, 3, 1, 0, 1, 7, 27, 3, 6, 109, 101, 109, 111, 114,
+    121, 2, 0, 5, 97, 108, 108, 111, 99, 0, 0, 6, 104, 97, 110, 100, 108, 101,
+    0, 1, 10, 20, 2, 5, 0, 65, 128, 8, 11, 12, 0, 32, 0, 173, 66, 32, 134, 32,
+    1, 173, 132, 11,
+];
+
+pub async fn async_test(mut client: ConnectedClient) {
+    client
+        .ask_load_wasm_handler("echo", ECHO_MODULE.to_vec())
+        .await
+        .expect("Failed to load wasm handler");
+
+    let mut args = CustomArgs::from(b"some data".to_vec());
+    args.namespace = Some(String::from("echo"));
+    let reply = client
+        .ask(Request::Custom(args))
+        .await
+        .expect("Failed to ask wasm handler");
+    assert_eq!(
+        reply,
+        Reply::Custom(reply::CustomArgs {
+            data: b"some data".to_vec()
+        })
+    );
+
+    client
+        .ask_unload_wasm_handler("echo")
+        .await
+        .expect("Failed to unload wasm handler");
+    assert!(client.ask_unload_wasm_handler("echo").await.is_err());
+}